@@ -0,0 +1,176 @@
+//! Criterion benchmarks for the request hot paths:
+//!
+//! - `repo/*`: create, get, list, and update against the in-memory repo.
+//! - `router/*`: the same operations through the full router via `oneshot`,
+//!   so the gap to `repo/*` is the middleware and serialization overhead.
+//! - `serialize_list/*`: JSON encoding of 1k, 10k, and 100k todos.
+//! - `concurrent_mixed`: a read-heavy mix from many tasks on a multi-threaded
+//!   runtime, the case lock contention shows up in.
+//!
+//! Run with `cargo bench --bench hot_paths`; CI only needs
+//! `cargo bench --no-run`.
+
+#[path = "../tests/test_support/mod.rs"]
+mod test_support;
+
+use std::{sync::Arc, time::Instant};
+
+use axum::{body::Body, http::Request, Router};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    models::{CreateTodo, UpdateTodo},
+    state::{in_memory_repo, TodoRepo},
+    AppState,
+};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+/// Todos in the store for the get/list/update benchmarks.
+const SEEDED: usize = 1_000;
+
+/// Tasks in the concurrent workload, each doing [`OPS_PER_TASK`] operations.
+const TASKS: usize = 64;
+const OPS_PER_TASK: usize = 100;
+
+fn current_thread() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn titled(title: &str) -> CreateTodo {
+    CreateTodo::new(title.parse().unwrap())
+}
+
+fn toggle(done: bool) -> UpdateTodo {
+    UpdateTodo {
+        done: Some(done),
+        ..Default::default()
+    }
+}
+
+fn repo(c: &mut Criterion) {
+    let rt = current_thread();
+    let repo = in_memory_repo();
+    rt.block_on(test_support::seed(repo.as_ref(), SEEDED));
+    let mut group = c.benchmark_group("repo");
+
+    group.bench_function("create", |b| {
+        b.to_async(&rt).iter(|| repo.create(titled("benchmark")));
+    });
+    group.bench_function("get", |b| b.to_async(&rt).iter(|| repo.get(SEEDED as u64 / 2)));
+    group.bench_function("list_1k", |b| {
+        b.to_async(&rt).iter(|| async {
+            let page = repo.list_after(&Default::default(), None, SEEDED).await.unwrap();
+            assert_eq!(page.len(), SEEDED);
+        });
+    });
+    let mut done = false;
+    group.bench_function("update", |b| {
+        b.to_async(&rt).iter(|| {
+            done = !done;
+            repo.update(1, toggle(done))
+        });
+    });
+    group.finish();
+}
+
+async fn send(router: &Router, method: &str, uri: &str, body: Option<&str>) -> usize {
+    let mut request = Request::builder().method(method).uri(uri);
+    if body.is_some() {
+        request = request.header("content-type", "application/json");
+    }
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    let response = router.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+    assert!(response.status().is_success(), "{method} {uri}: {}", response.status());
+    response.into_body().collect().await.unwrap().to_bytes().len()
+}
+
+fn router(c: &mut Criterion) {
+    let rt = current_thread();
+    let state = AppState::new_in_memory();
+    rt.block_on(test_support::seed(state.service().repo().as_ref(), SEEDED));
+    let router = app(state);
+    let mut group = c.benchmark_group("router");
+
+    group.bench_function("create", |b| {
+        b.to_async(&rt)
+            .iter(|| send(&router, "POST", "/todos", Some(r#"{"title":"benchmark"}"#)));
+    });
+    let uri = format!("/todos/{}", SEEDED / 2);
+    group.bench_function("get", |b| b.to_async(&rt).iter(|| send(&router, "GET", &uri, None)));
+    group.bench_function("list_1k", |b| {
+        b.to_async(&rt)
+            .iter(|| send(&router, "GET", "/todos?limit=1000&offset=0", None));
+    });
+    let mut done = false;
+    group.bench_function("update", |b| {
+        b.to_async(&rt).iter(|| {
+            done = !done;
+            let body = if done { r#"{"done":true}"# } else { r#"{"done":false}"# };
+            send(&router, "PUT", "/todos/1", Some(body))
+        });
+    });
+    group.finish();
+}
+
+fn serialize_list(c: &mut Criterion) {
+    let rt = current_thread();
+    let mut group = c.benchmark_group("serialize_list");
+    group.sample_size(10);
+    for count in [1_000, 10_000, 100_000] {
+        let repo = in_memory_repo();
+        rt.block_on(test_support::seed(repo.as_ref(), count));
+        let todos = rt.block_on(repo.list()).unwrap();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &todos, |b, todos| {
+            b.iter(|| serde_json::to_vec(todos).unwrap());
+        });
+    }
+    group.finish();
+}
+
+/// One task's share of the mixed workload: 80% gets, 15% updates, 5%
+/// creates.
+async fn mixed(repo: Arc<dyn TodoRepo>, task: usize) {
+    for op in 0..OPS_PER_TASK {
+        let id = ((task * OPS_PER_TASK + op) % SEEDED + 1) as u64;
+        match op % 20 {
+            0 => drop(repo.create(titled("concurrent")).await.unwrap()),
+            1..=3 => drop(repo.update(id, toggle(op % 2 == 0)).await.unwrap()),
+            _ => drop(repo.get(id).await.unwrap()),
+        }
+    }
+}
+
+fn concurrent_mixed(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("concurrent_mixed");
+    group.throughput(Throughput::Elements((TASKS * OPS_PER_TASK) as u64));
+    group.bench_function("in_memory", |b| {
+        b.to_async(&rt).iter_custom(|iters| async move {
+            let repo = in_memory_repo();
+            test_support::seed(repo.as_ref(), SEEDED).await;
+            let started = Instant::now();
+            for _ in 0..iters {
+                let tasks: Vec<_> = (0..TASKS)
+                    .map(|task| tokio::spawn(mixed(Arc::clone(&repo), task)))
+                    .collect();
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            }
+            started.elapsed()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, repo, router, serialize_list, concurrent_mixed);
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+//! Compares buffered and streamed serialization of `GET /todos` with 100k
+//! todos: total time, time to first byte, and peak heap growth.
+//!
+//! Run with `cargo bench --bench list_streaming`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use axum::{body::Body, http::Request};
+use http_body_util::BodyExt;
+use rust_api::{app, models::CreateTodo, AppState};
+use tower::ServiceExt;
+
+const TODOS: usize = 100_000;
+
+/// Wraps the system allocator to track live and peak heap usage.
+struct CountingAlloc;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+struct Sample {
+    total: Duration,
+    first_byte: Duration,
+    peak_growth: usize,
+    bytes: usize,
+}
+
+fn reset_peak() -> usize {
+    let live = LIVE.load(Ordering::Relaxed);
+    PEAK.store(live, Ordering::Relaxed);
+    live
+}
+
+/// The pre-streaming approach: clone everything, then serialize everything.
+async fn buffered(state: &AppState) -> Sample {
+    let baseline = reset_peak();
+    let started = Instant::now();
+
+    let todos = state.service().repo().list().await.unwrap();
+    let body = serde_json::to_vec(&todos).unwrap();
+    let first_byte = started.elapsed();
+
+    Sample {
+        total: started.elapsed(),
+        first_byte,
+        peak_growth: PEAK.load(Ordering::Relaxed) - baseline,
+        bytes: body.len(),
+    }
+}
+
+/// The real handler, consuming frames as a socket writer would.
+async fn streamed(state: &AppState) -> Sample {
+    let router = app(state.clone());
+    let baseline = reset_peak();
+    let started = Instant::now();
+
+    let res = router
+        .oneshot(Request::builder().uri("/todos").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let mut body = res.into_body();
+    let mut first_byte = None;
+    let mut bytes = 0;
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame.unwrap().into_data() {
+            first_byte.get_or_insert_with(|| started.elapsed());
+            bytes += data.len();
+        }
+    }
+
+    Sample {
+        total: started.elapsed(),
+        first_byte: first_byte.unwrap_or_default(),
+        peak_growth: PEAK.load(Ordering::Relaxed) - baseline,
+        bytes,
+    }
+}
+
+fn report(name: &str, sample: &Sample) {
+    println!(
+        "{name:<9} total {:>9.2?}  first byte {:>9.2?}  peak heap +{:>6} KiB  body {} KiB",
+        sample.total,
+        sample.first_byte,
+        sample.peak_growth / 1024,
+        sample.bytes / 1024,
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let state = AppState::new_in_memory();
+        for i in 0..TODOS {
+            state
+                .service().repo()
+                .create(CreateTodo::new(format!("benchmark todo number {i}").parse().unwrap()))
+                .await
+                .unwrap();
+        }
+
+        println!("GET /todos with {TODOS} todos");
+        report("buffered", &buffered(&state).await);
+        report("streamed", &streamed(&state).await);
+    });
+}
@@ -0,0 +1,18 @@
+//! Arbitrary bytes through the request body models and their validation.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_api::models::{AssignTodo, CreateTodo, UpdateTodo};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = serde_json::from_slice::<CreateTodo>(data) {
+        let _ = input.validate();
+    }
+    if let Ok(input) = serde_json::from_slice::<UpdateTodo>(data) {
+        let _ = input.validate();
+    }
+    if let Ok(input) = serde_json::from_slice::<AssignTodo>(data) {
+        let _ = UpdateTodo::from(input).validate();
+    }
+});
@@ -0,0 +1,80 @@
+//! Arbitrary requests through the full router.
+//!
+//! Inputs are a tiny HTTP-like text format so seeds stay readable: a
+//! `METHOD URI` line, optional `name: value` header lines, a blank line, then
+//! the body. Inputs that aren't a valid request are skipped.
+
+#![no_main]
+
+use std::sync::OnceLock;
+
+use axum::{
+    body::Body,
+    http::{Method, Request},
+    Router,
+};
+use http_body_util::BodyExt;
+use libfuzzer_sys::fuzz_target;
+use rust_api::{app, config::Config, models::CreateTodo, AppState};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+/// The runtime and router are built once; each iteration clones the router,
+/// which only bumps reference counts.
+fn harness() -> &'static (Runtime, Router) {
+    static HARNESS: OnceLock<(Runtime, Router)> = OnceLock::new();
+    HARNESS.get_or_init(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime builds");
+        let dir = std::env::temp_dir().join("rust-api-fuzz-attachments");
+        let config = Config::from_lookup(|key| match key {
+            "ATTACHMENT_DIR" => Some(dir.display().to_string()),
+            _ => None,
+        })
+        .expect("fuzz config is valid");
+        let state = AppState::new_in_memory().with_config(config);
+        runtime.block_on(async {
+            for title in ["first", "second"] {
+                let input = CreateTodo {
+                    title: title.to_string(),
+                    ..Default::default()
+                };
+                state.service().repo().create(input).await.expect("seed todo");
+            }
+        });
+        (runtime, app(state))
+    })
+}
+
+fn parse(data: &[u8]) -> Option<Request<Body>> {
+    let split = data.windows(2).position(|pair| pair == b"\n\n");
+    let (head, body) = match split {
+        Some(at) => (&data[..at], &data[at + 2..]),
+        None => (data, &[][..]),
+    };
+    let head = std::str::from_utf8(head).ok()?;
+    let mut lines = head.lines();
+    let (method, uri) = lines.next()?.split_once(' ')?;
+    let mut request = Request::builder()
+        .method(Method::from_bytes(method.as_bytes()).ok()?)
+        .uri(uri);
+    for line in lines {
+        let (name, value) = line.split_once(':')?;
+        request = request.header(name.trim(), value.trim());
+    }
+    request.body(Body::from(body.to_vec())).ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some(request) = parse(data) else {
+        return;
+    };
+    let (runtime, router) = harness();
+    runtime.block_on(async {
+        let response = router.clone().oneshot(request).await.expect("infallible");
+        // Drain the body so streamed responses run to completion too.
+        let _ = response.into_body().collect().await;
+    });
+});
@@ -0,0 +1,171 @@
+//! One structured log line per request.
+//!
+//! `TraceLayer` produces spans, which are great for tracing but awkward for
+//! access-log analysis. This middleware emits a single event under the
+//! `rust_api::access` target with method, route template, status, latency,
+//! response bytes, client IP, and request id.
+//!
+//! # Counting bytes
+//!
+//! The response body may be streamed (or compressed on the fly), so its size
+//! is not known when the handler returns. [`CountingBody`] wraps the body,
+//! counts bytes as they are polled, and writes the log line when the body
+//! finishes — or when it is dropped early because the client went away.
+//!
+//! # Levels
+//!
+//! `5xx` is always logged at error, `4xx` at warn when `LOG_CLIENT_ERRORS` is
+//! set (info otherwise), and everything else at info.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{MatchedPath, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+
+use crate::{middleware::client_ip, state::AppState};
+
+pub async fn access_log(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = state.config();
+    if config
+        .access_log_exclude
+        .iter()
+        .any(|path| path == req.uri().path())
+    {
+        return next.run(req).await;
+    }
+
+    let mut entry = AccessEntry {
+        method: req.method().clone(),
+        route: req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string()),
+        client_ip: client_ip(&req).map(|ip| ip.to_string()),
+        request_id: req
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        status: StatusCode::OK,
+        started: Instant::now(),
+        log_client_errors: config.log_client_errors,
+    };
+
+    let response = next.run(req).await;
+    entry.status = response.status();
+
+    let (parts, body) = response.into_parts();
+    Response::from_parts(
+        parts,
+        Body::new(CountingBody {
+            inner: body,
+            bytes: 0,
+            entry: Some(entry),
+        }),
+    )
+}
+
+struct AccessEntry {
+    method: Method,
+    route: Option<String>,
+    client_ip: Option<String>,
+    request_id: Option<String>,
+    status: StatusCode,
+    started: Instant,
+    log_client_errors: bool,
+}
+
+impl AccessEntry {
+    fn emit(self, bytes: u64, completed: bool) {
+        let latency_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        let route = self.route.as_deref().unwrap_or("unmatched");
+
+        macro_rules! access {
+            ($level:expr) => {
+                tracing::event!(
+                    target: "rust_api::access",
+                    $level,
+                    method = %self.method,
+                    route,
+                    status = self.status.as_u16(),
+                    latency_ms,
+                    bytes,
+                    client_ip = self.client_ip.as_deref(),
+                    request_id = self.request_id.as_deref(),
+                    completed,
+                    "request completed"
+                )
+            };
+        }
+
+        if self.status.is_server_error() {
+            access!(tracing::Level::ERROR);
+        } else if self.status.is_client_error() && self.log_client_errors {
+            access!(tracing::Level::WARN);
+        } else {
+            access!(tracing::Level::INFO);
+        }
+    }
+}
+
+/// Body wrapper that counts bytes and logs once the body is finished.
+struct CountingBody {
+    inner: Body,
+    bytes: u64,
+    entry: Option<AccessEntry>,
+}
+
+impl HttpBody for CountingBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(entry) = self.entry.take() {
+                    entry.emit(self.bytes, true);
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        // Still pending means the body was never fully sent, e.g. the client
+        // disconnected or the server only wrote headers (HEAD, 204).
+        if let Some(entry) = self.entry.take() {
+            let completed = self.inner.is_end_stream();
+            entry.emit(self.bytes, completed);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Atom (RFC 4287) feed of recently changed todos.
+//!
+//! `GET /todos/feed.atom` lets feed readers follow changes. Entries are the
+//! [`MAX_ENTRIES`] most recently created or updated todos, newest first, and
+//! the feed's own `<updated>` is the newest entry's, so readers can tell at a
+//! glance whether anything changed.
+//!
+//! # Identifiers
+//!
+//! Entry ids are `tag:` URIs (RFC 4151) built from the todo id alone. An
+//! update keeps the id and bumps `<updated>`, which readers show as an edit of
+//! the same entry rather than a new one.
+//!
+//! Titles are user input and go through the same escaping as the HTML view;
+//! its output is valid XML too.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{models::Todo, render::escape_html};
+
+/// Entries in one feed document.
+pub const MAX_ENTRIES: usize = 50;
+
+/// Fractional-second digits in feed timestamps.
+pub const PRECISION: u16 = 6;
+
+const FEED_ID: &str = "tag:rust-api,2024:todos";
+
+/// Renders the feed. `todos` must already be newest first and at most
+/// [`MAX_ENTRIES`] long.
+pub fn feed(todos: &[Todo]) -> String {
+    // An empty feed still needs `<updated>`; the epoch keeps it stable.
+    let updated = todos.first().map_or(DateTime::UNIX_EPOCH, |todo| todo.updated_at);
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(out, "  <id>{FEED_ID}</id>");
+    out.push_str("  <title>Todos</title>\n");
+    let _ = writeln!(out, "  <updated>{}</updated>", timestamp(updated));
+    out.push_str("  <author><name>rust-api</name></author>\n");
+    for todo in todos {
+        out.push_str("  <entry>\n");
+        let _ = writeln!(out, "    <id>{FEED_ID}:{}</id>", todo.id);
+        let _ = writeln!(out, "    <title>{}</title>", escape_html(&todo.title));
+        let _ = writeln!(out, "    <published>{}</published>", timestamp(todo.created_at));
+        let _ = writeln!(out, "    <updated>{}</updated>", timestamp(todo.updated_at));
+        let _ = writeln!(out, "    <link rel=\"alternate\" href=\"/todos/{}\"/>", todo.id);
+        let status = if todo.done { "done" } else { "open" };
+        let _ = writeln!(out, "    <content type=\"text\">{status}</content>");
+        out.push_str("  </entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
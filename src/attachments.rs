@@ -0,0 +1,636 @@
+//! File attachments on todos.
+//!
+//! # Storage layout
+//!
+//! Uploads are written to `ATTACHMENT_DIR/<todo id>/<sha256>`. Naming files
+//! after their contents means the client-supplied filename never touches the
+//! filesystem, so names like `../../etc/passwd` can't escape the directory;
+//! the name is only kept (sanitized) for `Content-Disposition` on download.
+//! Uploading the same bytes twice to one todo stores them once.
+//!
+//! Keeping one directory per todo makes cleanup a single `remove_dir_all`:
+//! [`Cleanup`] wraps the repository so deleting a todo through any API also
+//! deletes its files.
+//!
+//! # Uploads
+//!
+//! [`store`] streams a multipart field to a temporary file while hashing it,
+//! and gives up as soon as it passes `ATTACHMENT_MAX_BYTES`, so an oversized
+//! upload never sits in memory or on disk. The temporary file is renamed into
+//! place only once it is complete.
+//!
+//! # Quotas
+//!
+//! `ATTACHMENTS_MAX_BYTES` caps all files together and
+//! `ATTACHMENT_MAX_PER_TODO` the files of one todo. A [`StorageLedger`] on
+//! the [`AppState`](crate::AppState), [`InMemoryLedger`] unless one is
+//! plugged in with `with_storage_ledger`, counts every stored file. Once an
+//! upload is complete, [`store`] reserves its bytes in one atomic step that
+//! fails if either limit would be passed, so concurrent uploads can't both
+//! take the last bytes; the refused upload gets `413` (the todo's quota) or
+//! `507` (the global one) with code `storage_quota_exceeded`, and the body's
+//! `storage` says which limit, how much of it was in use, and how large the
+//! upload was. Deleting an attachment or its todo releases its bytes, once
+//! no other record shares the file.
+//!
+//! The ledger starts empty, so at startup [`reconcile`] recounts it from
+//! `ATTACHMENT_DIR`, and removes uploads a crash left half-written.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use axum::{extract::multipart::Field, http::HeaderValue};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    config::Config,
+    errors::AppError,
+    models::{
+        Attachment, Bucket, Changes, CreateTodo, ImportMode, ImportReport, NewAttachment,
+        RunMode, SearchHit, Snapshot, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+    },
+    state::TodoRepo,
+};
+
+/// Longest filename kept, in bytes.
+const MAX_FILENAME: usize = 255;
+
+/// Distinguishes concurrent uploads' temporary files.
+static UPLOADS: AtomicU64 = AtomicU64::new(0);
+
+/// Prefix of a temporary upload's filename.
+const UPLOAD_PREFIX: &str = ".upload-";
+
+/// The attachment quotas in effect; 0 means no limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageLimits {
+    /// `ATTACHMENTS_MAX_BYTES`.
+    pub total: u64,
+    /// `ATTACHMENT_MAX_PER_TODO`.
+    pub per_todo: u64,
+}
+
+impl StorageLimits {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            total: config.attachment_storage_max_bytes,
+            per_todo: config.attachment_max_per_todo,
+        }
+    }
+}
+
+/// Which quota a refused upload would have broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageScope {
+    /// `ATTACHMENT_MAX_PER_TODO`.
+    Todo,
+    /// `ATTACHMENTS_MAX_BYTES`.
+    Total,
+}
+
+/// Error details for an upload a quota refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageOverage {
+    pub scope: StorageScope,
+    pub limit: u64,
+    /// Bytes in use under that limit before the upload.
+    pub used: u64,
+    /// Size of the refused upload.
+    pub size: u64,
+}
+
+/// How much attachment storage is in use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub used_bytes: u64,
+    pub files: u64,
+}
+
+/// A file as it lies in `ATTACHMENT_DIR`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredFile {
+    pub todo_id: u64,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Counts the bytes of stored attachment files, for the quotas.
+///
+/// Files are identified by todo and SHA-256, like on disk, so storing the
+/// same bytes on a todo twice counts them once.
+pub trait StorageLedger: Send + Sync + 'static {
+    /// Counts `file`, unless that would pass one of `limits`. A file already
+    /// counted costs nothing. The check and the update must be atomic.
+    fn reserve(&self, file: &StoredFile, limits: StorageLimits) -> Result<(), StorageOverage>;
+
+    /// Stops counting one file of `todo_id`.
+    fn release(&self, todo_id: u64, sha256: &str);
+
+    /// Stops counting every file of `todo_id`.
+    fn release_todo(&self, todo_id: u64);
+
+    /// What all counted files add up to.
+    fn usage(&self) -> StorageUsage;
+
+    /// Replaces everything counted with `files`; see [`reconcile`].
+    fn reset(&self, files: Vec<StoredFile>);
+}
+
+/// A [`StorageLedger`] in process memory.
+#[derive(Debug, Default)]
+pub struct InMemoryLedger {
+    inner: Mutex<Counted>,
+}
+
+#[derive(Debug, Default)]
+struct Counted {
+    /// File sizes by todo and SHA-256.
+    todos: HashMap<u64, BTreeMap<String, u64>>,
+    usage: StorageUsage,
+}
+
+impl Counted {
+    fn remove(&mut self, size: u64) {
+        self.usage.used_bytes -= size;
+        self.usage.files -= 1;
+    }
+}
+
+impl StorageLedger for InMemoryLedger {
+    fn reserve(&self, file: &StoredFile, limits: StorageLimits) -> Result<(), StorageOverage> {
+        let mut counted = self.inner.lock().expect("storage ledger lock poisoned");
+        let todo = counted.todos.get(&file.todo_id);
+        if todo.is_some_and(|files| files.contains_key(&file.sha256)) {
+            return Ok(());
+        }
+        let todo_used = todo.map_or(0, |files| files.values().sum());
+        let total_used = counted.usage.used_bytes;
+        let over = |scope, limit: u64, used: u64| {
+            (limit > 0 && used + file.size > limit).then_some(StorageOverage {
+                scope,
+                limit,
+                used,
+                size: file.size,
+            })
+        };
+        if let Some(overage) = over(StorageScope::Todo, limits.per_todo, todo_used)
+            .or_else(|| over(StorageScope::Total, limits.total, total_used))
+        {
+            return Err(overage);
+        }
+
+        counted
+            .todos
+            .entry(file.todo_id)
+            .or_default()
+            .insert(file.sha256.clone(), file.size);
+        counted.usage.used_bytes += file.size;
+        counted.usage.files += 1;
+        Ok(())
+    }
+
+    fn release(&self, todo_id: u64, sha256: &str) {
+        let mut counted = self.inner.lock().expect("storage ledger lock poisoned");
+        let Some(files) = counted.todos.get_mut(&todo_id) else {
+            return;
+        };
+        let Some(size) = files.remove(sha256) else {
+            return;
+        };
+        if files.is_empty() {
+            counted.todos.remove(&todo_id);
+        }
+        counted.remove(size);
+    }
+
+    fn release_todo(&self, todo_id: u64) {
+        let mut counted = self.inner.lock().expect("storage ledger lock poisoned");
+        for size in counted.todos.remove(&todo_id).into_iter().flat_map(BTreeMap::into_values) {
+            counted.remove(size);
+        }
+    }
+
+    fn usage(&self) -> StorageUsage {
+        self.inner.lock().expect("storage ledger lock poisoned").usage
+    }
+
+    fn reset(&self, files: Vec<StoredFile>) {
+        let mut counted = Counted::default();
+        for file in files {
+            counted.usage.used_bytes += file.size;
+            counted.usage.files += 1;
+            counted.todos.entry(file.todo_id).or_default().insert(file.sha256, file.size);
+        }
+        *self.inner.lock().expect("storage ledger lock poisoned") = counted;
+    }
+}
+
+/// The ledger in use, shared by the state and [`Cleanup`] so one can be
+/// plugged in after both are built.
+pub type SharedLedger = Arc<ArcSwap<Arc<dyn StorageLedger>>>;
+
+/// Recounts `ledger` from the files under `root`, deleting half-written
+/// uploads on the way, and returns the usage found.
+pub async fn reconcile(root: &Path, ledger: &dyn StorageLedger) -> std::io::Result<StorageUsage> {
+    let mut files = Vec::new();
+    let mut todos = match fs::read_dir(root).await {
+        Ok(todos) => todos,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            ledger.reset(files);
+            return Ok(StorageUsage::default());
+        }
+        Err(err) => return Err(err),
+    };
+    while let Some(todo) = todos.next_entry().await? {
+        let Some(todo_id) = todo.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        if !todo.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut entries = fs::read_dir(todo.path()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(UPLOAD_PREFIX) {
+                let _ = fs::remove_file(entry.path()).await;
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                files.push(StoredFile {
+                    todo_id,
+                    sha256: name,
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+    ledger.reset(files);
+    Ok(ledger.usage())
+}
+
+/// Directory holding one todo's files.
+pub fn todo_dir(root: &Path, todo_id: u64) -> PathBuf {
+    root.join(todo_id.to_string())
+}
+
+/// Where an attachment's bytes live.
+pub fn path(root: &Path, attachment: &Attachment) -> PathBuf {
+    todo_dir(root, attachment.todo_id).join(&attachment.sha256)
+}
+
+/// Streams `field` into `todo_id`'s directory, counts it in `ledger`, and
+/// returns its metadata.
+///
+/// Fails with `415` when the declared content type is not allowed, `413`
+/// once the upload exceeds the configured size, and `413` or `507` when it
+/// doesn't fit the [quotas](self#quotas).
+pub async fn store(
+    config: &Config,
+    ledger: &dyn StorageLedger,
+    todo_id: u64,
+    mut field: Field<'_>,
+) -> Result<NewAttachment, AppError> {
+    let content_type = field
+        .content_type()
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !config.attachment_content_types.contains(&content_type) {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "attachments of type `{content_type}` are not allowed"
+        )));
+    }
+    let filename = sanitize_filename(field.file_name().unwrap_or_default());
+
+    let dir = todo_dir(&config.attachment_dir, todo_id);
+    fs::create_dir_all(&dir).await.map_err(io_error)?;
+    let temp = dir.join(format!("{UPLOAD_PREFIX}{}", UPLOADS.fetch_add(1, Ordering::Relaxed)));
+
+    let written = async {
+        let mut file = fs::File::create(&temp).await.map_err(io_error)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|err| AppError::Validation(err.body_text().into()))?
+        {
+            size += chunk.len() as u64;
+            if size > config.attachment_max_bytes {
+                return Err(AppError::PayloadTooLarge);
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(io_error)?;
+        }
+        file.flush().await.map_err(io_error)?;
+        Ok((hex(&hasher.finalize()), size))
+    }
+    .await;
+
+    let (sha256, size) = match written {
+        Ok(written) => written,
+        Err(err) => {
+            let _ = fs::remove_file(&temp).await;
+            return Err(err);
+        }
+    };
+    let file = StoredFile {
+        todo_id,
+        sha256,
+        size,
+    };
+    if let Err(overage) = ledger.reserve(&file, StorageLimits::new(config)) {
+        let _ = fs::remove_file(&temp).await;
+        return Err(AppError::StorageQuotaExceeded(overage));
+    }
+    if let Err(err) = fs::rename(&temp, dir.join(&file.sha256)).await {
+        ledger.release(todo_id, &file.sha256);
+        let _ = fs::remove_file(&temp).await;
+        return Err(io_error(err));
+    }
+    let StoredFile { sha256, .. } = file;
+
+    Ok(NewAttachment {
+        filename,
+        content_type,
+        size,
+        sha256,
+    })
+}
+
+/// Deletes every file stored for `todo_id`. Missing directories are fine.
+pub async fn remove_todo_files(root: &Path, todo_id: u64) {
+    match fs::remove_dir_all(todo_dir(root, todo_id)).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => tracing::error!(todo_id, error = %err, "failed to delete attachments"),
+    }
+}
+
+/// Reduces a client-supplied filename to a bare, printable name.
+pub fn sanitize_filename(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim();
+
+    let mut name = String::new();
+    for c in cleaned.chars() {
+        if name.len() + c.len_utf8() > MAX_FILENAME {
+            break;
+        }
+        name.push(c);
+    }
+    if name.is_empty() {
+        name.push_str("attachment");
+    }
+    name
+}
+
+/// `Content-Disposition: attachment` naming the file, with an ASCII fallback
+/// for old clients and the exact UTF-8 name in `filename*` (RFC 6266).
+pub fn content_disposition(filename: &str) -> HeaderValue {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+    .expect("value is printable ASCII")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn io_error(err: std::io::Error) -> AppError {
+    tracing::error!(error = %err, "attachment storage failed");
+    AppError::Internal
+}
+
+/// Repository decorator that deletes a todo's files along with the todo, or
+/// an attachment's file along with its last record, and releases their
+/// bytes in the ledger.
+pub struct Cleanup {
+    inner: Arc<dyn TodoRepo>,
+    config: Arc<ArcSwap<Config>>,
+    ledger: SharedLedger,
+}
+
+impl Cleanup {
+    pub fn new(inner: Arc<dyn TodoRepo>, config: Arc<ArcSwap<Config>>, ledger: SharedLedger) -> Self {
+        Self {
+            inner,
+            config,
+            ledger,
+        }
+    }
+
+    async fn remove_todo(&self, id: u64) {
+        remove_todo_files(&self.config.load().attachment_dir, id).await;
+        self.ledger.load().release_todo(id);
+    }
+}
+
+#[async_trait]
+impl TodoRepo for Cleanup {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.inner.list().await
+    }
+
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.list_after(filter, after, limit).await
+    }
+
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.list_page(filter, offset, limit).await
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        self.inner.count(filter).await
+    }
+
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
+        self.inner.create(input).await
+    }
+
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        self.inner.create_many(inputs).await
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        self.inner.update(id, input).await
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        self.inner.toggle(id).await
+    }
+
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        self.inner.update_many_at(revision, updates).await
+    }
+
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        self.inner.upsert(id, input).await
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        self.inner.delete(id).await?;
+        self.remove_todo(id).await;
+        Ok(())
+    }
+
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        self.inner.version(id).await
+    }
+
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        self.inner.delete_if_version(id, version).await?;
+        self.remove_todo(id).await;
+        Ok(())
+    }
+
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        self.inner.add_attachment(todo_id, input).await
+    }
+
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.inner.list_attachments(todo_id).await
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.inner.get_attachment(id).await
+    }
+
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        let attachment = self.inner.delete_attachment(id).await?;
+        let shared = match self.inner.list_attachments(attachment.todo_id).await {
+            Ok(others) => others.iter().any(|other| other.sha256 == attachment.sha256),
+            // The todo went away meanwhile, and its files with it.
+            Err(AppError::NotFound) => return Ok(attachment),
+            Err(err) => return Err(err),
+        };
+        if !shared {
+            let path = path(&self.config.load().attachment_dir, &attachment);
+            match fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => tracing::error!(attachment = id, error = %err, "failed to delete attachment"),
+            }
+            self.ledger.load().release(attachment.todo_id, &attachment.sha256);
+        }
+        Ok(attachment)
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        self.inner.search(query, limit).await
+    }
+
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        self.inner.find_similar(title, threshold).await
+    }
+
+    async fn due_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.due_between(from, to).await
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        self.inner.mark_reminded(id, at).await
+    }
+
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        self.inner.timeseries(bucket, range).await
+    }
+
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        self.inner.changes_since(since).await
+    }
+
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        self.inner.export_all().await
+    }
+
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        let report = self.inner.import_all(snapshot, mode, run).await?;
+        if run == RunMode::Apply {
+            for &id in &report.removed {
+                self.remove_todo(id).await;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        self.inner.revision().await
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        self.inner.ping().await
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+}
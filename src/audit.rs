@@ -0,0 +1,57 @@
+//! Audit trail of writes.
+//!
+//! The [`TodoService`](crate::service::TodoService) records one [`AuditEntry`]
+//! per successful create, update, and delete, whichever API it came through.
+//! Entries are kept in memory, newest last, up to [`CAPACITY`]; older ones
+//! are dropped. Each entry is also logged at `info` under the `audit` target,
+//! so a log pipeline can keep the full history.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Entries kept in memory.
+pub const CAPACITY: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEntry {
+    #[serde(with = "crate::timestamps")]
+    pub at: DateTime<Utc>,
+    pub action: AuditAction,
+    pub todo_id: u64,
+}
+
+#[derive(Default)]
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub fn record(&self, action: AuditAction, todo_id: u64) {
+        tracing::info!(target: "audit", ?action, todo_id, "todo changed");
+        let mut entries = self.entries.lock().expect("audit lock poisoned");
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(AuditEntry {
+            at: Utc::now(),
+            action,
+            todo_id,
+        });
+    }
+
+    /// The retained entries, oldest first.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().expect("audit lock poisoned");
+        entries.iter().cloned().collect()
+    }
+}
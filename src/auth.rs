@@ -0,0 +1,266 @@
+//! Who may call which route.
+//!
+//! Every HTTP route belongs to a [`RouteGroup`], and [`ROUTES`] lists each
+//! path template with its group. Each group requires one [`Policy`], chosen
+//! with its `*_POLICY` variable, and [`authorize`], the one middleware in
+//! front of every route, looks up the matched template and enforces it:
+//!
+//! - `public`: anyone.
+//! - `api_key`: a key from `API_KEYS` or `ADMIN_API_KEYS`, sent as
+//!   `Authorization: Bearer <key>` or `X-Api-Key: <key>`. Without one, `401`
+//!   with `unauthorized`.
+//! - `admin`: a key from `ADMIN_API_KEYS`. Other keys get `403` with
+//!   `forbidden`.
+//! - `disabled`: `404`, as if the route didn't exist.
+//!
+//! Browsers can log in for a [session](crate::sessions) cookie instead,
+//! which counts as the key it was opened with. A request that changes
+//! something on a cookie alone must carry the session's CSRF token, or gets
+//! `403` with `csrf_failed`.
+//!
+//! The probes, the session routes and opening a
+//! [share link](crate::sharing) are always public. A route missing from [`ROUTES`] fails the
+//! [startup check](crate::routes::manifest), and is refused with `500` should
+//! one be served anyway, so a new route can't go out without someone deciding
+//! who may call it. Policies and keys are read per request, so a `SIGHUP` reload
+//! applies them to the next one.
+
+use std::{fmt, str::FromStr};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::{Config, Redacted},
+    errors::AppError,
+    sessions,
+    state::AppState,
+};
+
+/// Header carrying an API key, for clients that can't set `Authorization`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Roles a key can carry beyond plain API access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Keys from `ADMIN_API_KEYS`.
+    Admin,
+}
+
+/// What a caller needs to reach a route group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    Public,
+    ApiKey,
+    Role(Role),
+    Disabled,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "public" => Ok(Self::Public),
+            "api_key" => Ok(Self::ApiKey),
+            "admin" => Ok(Self::Role(Role::Admin)),
+            "disabled" => Ok(Self::Disabled),
+            _ => Err(format!(
+                "unknown policy `{value}`, expected public, api_key, admin, or disabled"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Public => "public",
+            Self::ApiKey => "api_key",
+            Self::Role(Role::Admin) => "admin",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+/// Routes that share a policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteGroup {
+    /// `/health` and `/ready`; always public.
+    Probes,
+    /// The todo API, under `/v1` and without a prefix, and `/graphql`.
+    Api,
+    /// `/admin/*`.
+    Admin,
+    /// `/metrics`.
+    Metrics,
+    /// The web UI under `/ui` (`ui` feature).
+    Ui,
+    /// `/auth/session`, where browsers log in and out; always public.
+    Session,
+    /// `/shared/:token`, where [share links](crate::sharing) are opened;
+    /// always public, as the token is the credential.
+    Shared,
+}
+
+impl RouteGroup {
+    /// The policy `config` sets for the group.
+    pub fn policy(self, config: &Config) -> Policy {
+        match self {
+            Self::Probes | Self::Session | Self::Shared => Policy::Public,
+            Self::Api => config.api_policy,
+            Self::Admin => config.admin_policy,
+            Self::Metrics => config.metrics_policy,
+            Self::Ui => config.ui_policy,
+        }
+    }
+}
+
+/// Every route template and its group. API routes are listed once, without
+/// `/v1`, and cover both prefixes.
+pub const ROUTES: &[(&str, RouteGroup)] = &[
+    ("/health", RouteGroup::Probes),
+    ("/ready", RouteGroup::Probes),
+    ("/metrics", RouteGroup::Metrics),
+    ("/admin/export", RouteGroup::Admin),
+    ("/admin/import", RouteGroup::Admin),
+    ("/admin/outbox", RouteGroup::Admin),
+    ("/admin/outbox/:id/retry", RouteGroup::Admin),
+    ("/admin/routes", RouteGroup::Admin),
+    ("/auth/session", RouteGroup::Session),
+    ("/shared/:token", RouteGroup::Shared),
+    ("/graphql", RouteGroup::Api),
+    ("/todos", RouteGroup::Api),
+    ("/todos/search", RouteGroup::Api),
+    ("/todos/calendar.ics", RouteGroup::Api),
+    ("/todos/feed.atom", RouteGroup::Api),
+    ("/todos/stats/timeseries", RouteGroup::Api),
+    ("/todos/stats/storage", RouteGroup::Api),
+    ("/todos/changes", RouteGroup::Api),
+    ("/todos/poll", RouteGroup::Api),
+    ("/todos/events", RouteGroup::Api),
+    ("/todos/events/log", RouteGroup::Api),
+    ("/todos/batch", RouteGroup::Api),
+    ("/todos/:id", RouteGroup::Api),
+    ("/todos/:id/assign", RouteGroup::Api),
+    ("/todos/:id/attachments", RouteGroup::Api),
+    ("/todos/:id/share", RouteGroup::Api),
+    ("/attachments/:id", RouteGroup::Api),
+    ("/preferences", RouteGroup::Api),
+    ("/templates", RouteGroup::Api),
+    ("/templates/:id", RouteGroup::Api),
+    ("/templates/:id/instantiate", RouteGroup::Api),
+    ("/ui", RouteGroup::Ui),
+    ("/ui/", RouteGroup::Ui),
+    ("/ui/*path", RouteGroup::Ui),
+];
+
+/// The group of a matched route template, `/v1` or not.
+pub fn group(template: &str) -> Option<RouteGroup> {
+    let unversioned = template
+        .strip_prefix("/v1")
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(template);
+    ROUTES
+        .iter()
+        .find(|(listed, _)| *listed == unversioned)
+        .map(|(_, group)| *group)
+}
+
+/// What the caller's credentials let them reach.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    Anonymous,
+    Key,
+    Admin,
+}
+
+impl Access {
+    fn of(headers: &HeaderMap, config: &Config) -> Self {
+        presented_key(headers).map_or(Self::Anonymous, |key| Self::of_key(key, config))
+    }
+
+    fn of_key(key: &str, config: &Config) -> Self {
+        if config.admin_api_keys.iter().any(|admin| matches(admin, key)) {
+            Self::Admin
+        } else if config.api_keys.iter().any(|known| matches(known, key)) {
+            Self::Key
+        } else {
+            Self::Anonymous
+        }
+    }
+}
+
+/// Whether `key` is in `API_KEYS` or `ADMIN_API_KEYS`.
+pub fn is_known_key(key: &str, config: &Config) -> bool {
+    Access::of_key(key, config) != Access::Anonymous
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get(API_KEY_HEADER)?.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// Compares without stopping at the first differing byte, so response times
+/// don't tell a guesser how much of a key was right.
+fn matches(known: &Redacted<String>, presented: &str) -> bool {
+    let known = known.expose().as_bytes();
+    let presented = presented.as_bytes();
+    known.len() == presented.len()
+        && known.iter().zip(presented).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether `access` satisfies `policy`.
+fn check(policy: Policy, access: Access) -> Result<(), AppError> {
+    match (policy, access) {
+        (Policy::Public, _) => Ok(()),
+        (Policy::Disabled, _) => Err(AppError::NotFound),
+        (_, Access::Anonymous) => Err(AppError::Unauthorized),
+        (Policy::ApiKey, _) | (Policy::Role(Role::Admin), Access::Admin) => Ok(()),
+        (Policy::Role(Role::Admin), Access::Key) => Err(AppError::Forbidden),
+    }
+}
+
+/// Enforces the policy of the matched route; see the [module docs](self).
+/// Requests that matched no route pass through to the `404`.
+pub async fn authorize(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(template) = req.extensions().get::<MatchedPath>() else {
+        return Ok(next.run(req).await);
+    };
+    let Some(group) = group(template.as_str()) else {
+        tracing::error!(route = template.as_str(), "route has no entry in auth::ROUTES");
+        return Err(AppError::Internal);
+    };
+    let config = state.config();
+    let policy = group.policy(&config);
+    if policy != Policy::Public {
+        let mut access = Access::of(req.headers(), &config);
+        if access == Access::Anonymous && presented_key(req.headers()).is_none() {
+            if let Some(session) = state.sessions().from_headers(req.headers(), state.clock().now()) {
+                if !sessions::is_safe(req.method()) && !session.csrf_matches(req.headers()) {
+                    return Err(AppError::CsrfFailed);
+                }
+                access = Access::of_key(session.key.expose(), &config);
+            }
+        }
+        check(policy, access)?;
+    }
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,393 @@
+//! Load generator for a running instance.
+//!
+//! Seeds `--seed` todos, then runs `--concurrency` workers for `--duration`,
+//! each on its own keep-alive connection picking operations by the `--mix`
+//! weights. At the end it prints throughput and p50/p95/p99 latencies per
+//! operation (`--json` for a machine-readable report) and exits with status 1
+//! if the error rate is above `--max-error-rate`.
+//!
+//! ```text
+//! cargo run --release --bin loadgen -- --url http://127.0.0.1:8080 \
+//!     --concurrency 32 --duration 30s --mix list=80,create=15,delete=5 --cleanup
+//! ```
+
+use std::{
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use hdrhistogram::Histogram;
+use rust_api::{
+    client::{Client, ClientError},
+    models::CreateTodo,
+};
+use serde_json::{json, Value};
+
+const USAGE: &str = "\
+usage: loadgen [options]
+
+  --url URL               server to load (default http://127.0.0.1:8080)
+  --concurrency N         parallel connections (default 8)
+  --duration D            how long to run, e.g. 30s, 500ms, 2m (default 10s)
+  --mix OP=W,...          operation weights over list, create, delete
+                          (default list=80,create=15,delete=5)
+  --seed N                todos to create before starting (default 100)
+  --cleanup               delete every todo loadgen created when done
+  --max-error-rate R      fail when errors / operations exceeds R (default 0.01)
+  --json                  print the report as JSON";
+
+/// Todos fetched per list request.
+const PAGE: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    List,
+    Create,
+    Delete,
+}
+
+impl Op {
+    const ALL: [Op; 3] = [Op::List, Op::Create, Op::Delete];
+
+    fn name(self) -> &'static str {
+        match self {
+            Op::List => "list",
+            Op::Create => "create",
+            Op::Delete => "delete",
+        }
+    }
+}
+
+struct Options {
+    url: String,
+    concurrency: usize,
+    duration: Duration,
+    /// Weight of each of [`Op::ALL`].
+    mix: [u32; 3],
+    seed: usize,
+    cleanup: bool,
+    max_error_rate: f64,
+    json: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Options {
+            url: "http://127.0.0.1:8080".to_string(),
+            concurrency: 8,
+            duration: Duration::from_secs(10),
+            mix: [80, 15, 5],
+            seed: 100,
+            cleanup: false,
+            max_error_rate: 0.01,
+            json: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--url" => options.url = value()?,
+                "--concurrency" => options.concurrency = value()?.parse()?,
+                "--duration" => options.duration = parse_duration(&value()?)?,
+                "--mix" => options.mix = parse_mix(&value()?)?,
+                "--seed" => options.seed = value()?.parse()?,
+                "--cleanup" => options.cleanup = true,
+                "--max-error-rate" => options.max_error_rate = value()?.parse()?,
+                "--json" => options.json = true,
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => bail!("unknown argument `{arg}`\n\n{USAGE}"),
+            }
+        }
+        if options.concurrency == 0 {
+            bail!("--concurrency must be at least 1");
+        }
+        Ok(options)
+    }
+}
+
+/// `30s`, `500ms`, `2m`, or bare seconds.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid = || format!("invalid duration `{value}`");
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(at) => value.split_at(at),
+        None => (value, "s"),
+    };
+    let number: f64 = number.parse().with_context(invalid)?;
+    let secs = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => bail!(invalid()),
+    };
+    Duration::try_from_secs_f64(secs).with_context(invalid)
+}
+
+/// `list=80,create=15,delete=5`; operations left out get no weight.
+fn parse_mix(value: &str) -> Result<[u32; 3]> {
+    let mut mix = [0; 3];
+    for part in value.split(',') {
+        let (name, weight) = part.split_once('=').with_context(|| format!("bad mix `{part}`"))?;
+        let op = Op::ALL
+            .iter()
+            .position(|op| op.name() == name.trim())
+            .with_context(|| format!("unknown operation `{name}` in --mix"))?;
+        mix[op] = weight.trim().parse().with_context(|| format!("bad weight in `{part}`"))?;
+    }
+    if mix.iter().sum::<u32>() == 0 {
+        bail!("--mix needs at least one positive weight");
+    }
+    Ok(mix)
+}
+
+/// Per-operation latencies of successful calls, in microseconds, and error
+/// counts.
+struct Stats {
+    latencies: Vec<Histogram<u64>>,
+    errors: [u64; 3],
+}
+
+impl Stats {
+    fn new() -> Self {
+        let histogram = || Histogram::new_with_bounds(1, 60_000_000, 3).expect("valid bounds");
+        Self {
+            latencies: Op::ALL.iter().map(|_| histogram()).collect(),
+            errors: [0; 3],
+        }
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        for (mine, theirs) in self.latencies.iter_mut().zip(&other.latencies) {
+            mine.add(theirs).expect("histograms share bounds");
+        }
+        for (mine, theirs) in self.errors.iter_mut().zip(other.errors) {
+            *mine += theirs;
+        }
+    }
+}
+
+/// Tiny xorshift generator; operation choice needs no more.
+struct Rng(u64);
+
+impl Rng {
+    fn seeded(worker: usize) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        Self((u64::from(nanos) ^ (worker as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1)
+    }
+
+    fn below(&mut self, bound: u32) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % u64::from(bound)) as u32
+    }
+
+    fn pick(&mut self, mix: &[u32; 3]) -> Op {
+        let mut roll = self.below(mix.iter().sum());
+        for (op, weight) in Op::ALL.into_iter().zip(mix) {
+            if roll < *weight {
+                return op;
+            }
+            roll -= weight;
+        }
+        unreachable!("roll is below the total weight")
+    }
+}
+
+/// Ids of todos loadgen created that still exist; deletes take from here so
+/// workers never delete the same todo twice.
+type Live = Arc<Mutex<Vec<u64>>>;
+
+fn input(n: usize) -> CreateTodo {
+    CreateTodo::new(format!("loadgen {n}").parse().unwrap())
+}
+
+async fn worker(index: usize, options: Arc<Options>, live: Live, deadline: Instant) -> Stats {
+    let mut stats = Stats::new();
+    let mut rng = Rng::seeded(index);
+    let mut client = None;
+    let mut created = 0;
+
+    while Instant::now() < deadline {
+        let mut op = rng.pick(&options.mix);
+        let victim = match op {
+            Op::Delete => live.lock().expect("live ids lock poisoned").pop(),
+            _ => None,
+        };
+        // Nothing left to delete: grow the store instead.
+        if op == Op::Delete && victim.is_none() {
+            op = Op::Create;
+        }
+
+        let started = Instant::now();
+        let result = async {
+            if client.is_none() {
+                client = Some(Client::connect(&options.url).await?);
+            }
+            let client = client.as_mut().expect("connected above");
+            match op {
+                Op::List => client.list(0, PAGE).await.map(drop),
+                Op::Create => {
+                    let todo = client.create(&input(created)).await?;
+                    created += 1;
+                    live.lock().expect("live ids lock poisoned").push(todo.id);
+                    Ok(())
+                }
+                Op::Delete => client.delete(victim.expect("delete has an id")).await,
+            }
+        }
+        .await;
+        let elapsed = started.elapsed();
+
+        match result {
+            Ok(()) => {
+                let micros = elapsed.as_micros().try_into().unwrap_or(u64::MAX);
+                stats.latencies[op as usize].saturating_record(micros.max(1));
+            }
+            Err(err) => {
+                stats.errors[op as usize] += 1;
+                // Connection-level failures leave the connection unusable.
+                if matches!(err, ClientError::Connect(_) | ClientError::Http(_)) {
+                    client = None;
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        }
+    }
+    stats
+}
+
+fn report(stats: &Stats, elapsed: Duration, options: &Options) -> Value {
+    let secs = elapsed.as_secs_f64();
+    let millis = |histogram: &Histogram<u64>, quantile| {
+        histogram.value_at_quantile(quantile) as f64 / 1000.0
+    };
+    let mut ops = serde_json::Map::new();
+    let (mut count, mut errors) = (0, 0);
+    for op in Op::ALL {
+        let latencies = &stats.latencies[op as usize];
+        let op_errors = stats.errors[op as usize];
+        let op_count = latencies.len() + op_errors;
+        count += op_count;
+        errors += op_errors;
+        ops.insert(
+            op.name().to_string(),
+            json!({
+                "count": op_count,
+                "errors": op_errors,
+                "throughput": op_count as f64 / secs,
+                "p50_ms": millis(latencies, 0.50),
+                "p95_ms": millis(latencies, 0.95),
+                "p99_ms": millis(latencies, 0.99),
+            }),
+        );
+    }
+    let error_rate = if count == 0 { 0.0 } else { errors as f64 / count as f64 };
+    json!({
+        "duration_secs": secs,
+        "concurrency": options.concurrency,
+        "ops": ops,
+        "total": { "count": count, "errors": errors, "throughput": count as f64 / secs },
+        "error_rate": error_rate,
+        "max_error_rate": options.max_error_rate,
+    })
+}
+
+fn print_table(report: &Value) {
+    println!(
+        "{:<8} {:>9} {:>7} {:>10} {:>9} {:>9} {:>9}",
+        "op", "count", "errors", "ops/s", "p50 ms", "p95 ms", "p99 ms"
+    );
+    for op in Op::ALL {
+        let row = &report["ops"][op.name()];
+        println!(
+            "{:<8} {:>9} {:>7} {:>10.1} {:>9.2} {:>9.2} {:>9.2}",
+            op.name(),
+            row["count"].as_u64().unwrap_or_default(),
+            row["errors"].as_u64().unwrap_or_default(),
+            row["throughput"].as_f64().unwrap_or_default(),
+            row["p50_ms"].as_f64().unwrap_or_default(),
+            row["p95_ms"].as_f64().unwrap_or_default(),
+            row["p99_ms"].as_f64().unwrap_or_default(),
+        );
+    }
+    let total = &report["total"];
+    println!(
+        "{:<8} {:>9} {:>7} {:>10.1}",
+        "total",
+        total["count"].as_u64().unwrap_or_default(),
+        total["errors"].as_u64().unwrap_or_default(),
+        total["throughput"].as_f64().unwrap_or_default(),
+    );
+    println!(
+        "error rate {:.2}% (limit {:.2}%) over {:.1}s",
+        report["error_rate"].as_f64().unwrap_or_default() * 100.0,
+        report["max_error_rate"].as_f64().unwrap_or_default() * 100.0,
+        report["duration_secs"].as_f64().unwrap_or_default(),
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let options = Arc::new(Options::parse(std::env::args().skip(1))?);
+    let live: Live = Arc::default();
+
+    let mut client = Client::connect(&options.url)
+        .await
+        .with_context(|| format!("connecting to {}", options.url))?;
+    for n in 0..options.seed {
+        let todo = client.create(&input(n)).await.context("seeding")?;
+        live.lock().expect("live ids lock poisoned").push(todo.id);
+    }
+
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|index| {
+            let (options, live) = (Arc::clone(&options), Arc::clone(&live));
+            tokio::spawn(worker(index, options, live, deadline))
+        })
+        .collect();
+    let mut stats = Stats::new();
+    for worker in workers {
+        stats.merge(&worker.await?);
+    }
+    let report = report(&stats, started.elapsed(), &options);
+
+    if options.cleanup {
+        let ids = std::mem::take(&mut *live.lock().expect("live ids lock poisoned"));
+        for id in ids {
+            match client.delete(id).await {
+                Ok(()) => {}
+                // The server may have closed the idle seeding connection.
+                Err(ClientError::Http(_)) => {
+                    client = Client::connect(&options.url).await?;
+                    client.delete(id).await.context("cleaning up")?;
+                }
+                Err(err) => return Err(err).context("cleaning up"),
+            }
+        }
+    }
+
+    if options.json {
+        println!("{report}");
+    } else {
+        print_table(&report);
+    }
+
+    let error_rate = report["error_rate"].as_f64().unwrap_or_default();
+    if error_rate > options.max_error_rate {
+        eprintln!(
+            "error rate {:.2}% exceeds the limit of {:.2}%",
+            error_rate * 100.0,
+            options.max_error_rate * 100.0
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
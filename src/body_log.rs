@@ -0,0 +1,214 @@
+//! Request and response bodies in the logs, for debugging client
+//! integrations.
+//!
+//! With `LOG_BODIES` on, each text body is logged at debug level under the
+//! `rust_api::bodies` target, cut to `LOG_BODY_MAX_BYTES`. JSON bodies have
+//! any field named `password`, `token`, or `authorization` replaced with
+//! `"***"` before they are cut, at any depth. Even so, this logs what users
+//! send, so it is off by default and `main` warns at startup when it is on.
+//!
+//! # Skipped bodies
+//!
+//! Binary content types (MessagePack, uploads, downloads) are not logged,
+//! and neither are streamed responses (no exact size) or request bodies that
+//! are still compressed at this layer.
+//!
+//! # Handlers see the same body
+//!
+//! Nothing is buffered up front. Like the access log's byte counter,
+//! [`LoggedBody`] wraps the body and copies frames as they pass through. It
+//! logs when the body finishes, or when it is dropped partway through. Body
+//! limits, streaming, and backpressure behave as before.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use serde_json::Value;
+
+use crate::state::AppState;
+
+/// JSON field names whose values never reach the logs, compared
+/// case-insensitively.
+pub const REDACTED_FIELDS: [&str; 3] = ["password", "token", "authorization"];
+
+/// Wraps loggable request and response bodies in a [`LoggedBody`] while
+/// `LOG_BODIES` is on.
+pub async fn log_bodies(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = state.config();
+    if !config.log_bodies {
+        return next.run(req).await;
+    }
+    let max = config.log_body_max_bytes;
+
+    let compressed = req
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding != "identity");
+    let req = match Loggable::from_headers(req.headers()) {
+        Some(kind) if !compressed && !req.body().is_end_stream() => {
+            req.map(|body| LoggedBody::wrap(body, "request", kind, max))
+        }
+        _ => req,
+    };
+
+    let res = next.run(req).await;
+    let streamed = res.body().size_hint().exact().is_none();
+    match Loggable::from_headers(res.headers()) {
+        Some(kind) if !streamed && !res.body().is_end_stream() => {
+            res.map(|body| LoggedBody::wrap(body, "response", kind, max))
+        }
+        _ => res,
+    }
+}
+
+/// Text bodies worth logging, and whether they can be redacted as JSON.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Loggable {
+    Json,
+    Text,
+}
+
+impl Loggable {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let essence = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        if essence == "application/json" || essence.ends_with("+json") {
+            Some(Loggable::Json)
+        } else if essence.starts_with("text/")
+            || essence == "application/xml"
+            || essence.ends_with("+xml")
+            || essence == "application/x-www-form-urlencoded"
+        {
+            Some(Loggable::Text)
+        } else {
+            None
+        }
+    }
+}
+
+/// Body wrapper that copies what passes through and logs it once.
+struct LoggedBody {
+    inner: Body,
+    direction: &'static str,
+    kind: Loggable,
+    max: usize,
+    /// JSON is kept whole so it can be parsed for redaction; other text is
+    /// kept up to `max`.
+    captured: Vec<u8>,
+    bytes: usize,
+    logged: bool,
+}
+
+impl LoggedBody {
+    fn wrap(inner: Body, direction: &'static str, kind: Loggable, max: usize) -> Body {
+        Body::new(Self {
+            inner,
+            direction,
+            kind,
+            max,
+            captured: Vec::new(),
+            bytes: 0,
+            logged: false,
+        })
+    }
+
+    fn emit(&mut self) {
+        if std::mem::replace(&mut self.logged, true) {
+            return;
+        }
+        let body = match self.kind {
+            Loggable::Json => match serde_json::from_slice::<Value>(&self.captured) {
+                Ok(mut value) => {
+                    redact(&mut value);
+                    value.to_string().into_bytes()
+                }
+                // Unparseable (or cut short by a disconnect): there is no
+                // safe way to find the secrets, so nothing is shown.
+                Err(_) => b"<unparseable JSON withheld>".to_vec(),
+            },
+            Loggable::Text => std::mem::take(&mut self.captured),
+        };
+        let truncated = body.len() > self.max;
+        let body = String::from_utf8_lossy(&body[..body.len().min(self.max)]);
+        tracing::debug!(
+            target: "rust_api::bodies",
+            direction = self.direction,
+            bytes = self.bytes,
+            truncated,
+            body = %body,
+            "body"
+        );
+    }
+}
+
+impl HttpBody for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    let this = &mut *self;
+                    this.bytes += data.len();
+                    let room = match this.kind {
+                        Loggable::Json => data.len(),
+                        Loggable::Text => this.max.saturating_sub(this.captured.len()),
+                    };
+                    this.captured.extend_from_slice(&data[..data.len().min(room)]);
+                }
+            }
+            Poll::Ready(None) => self.emit(),
+            _ => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        // Handlers may stop reading early, and clients may hang up.
+        if self.bytes > 0 {
+            self.emit();
+        }
+    }
+}
+
+/// Replaces the values of [`REDACTED_FIELDS`] with `"***"`, at any depth.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if REDACTED_FIELDS.iter().any(|redacted| name.eq_ignore_ascii_case(redacted)) {
+                    *field = Value::String("***".to_string());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
@@ -0,0 +1,95 @@
+//! Budgets of their own for the heaviest routes.
+//!
+//! An import takes a body far larger than anything else and works on it for
+//! a long time, so the global body limit, rate limit, and deadline don't fit
+//! it. [`import`] wraps an import route with three budgets instead, each
+//! failing with its own error code:
+//!
+//! - `IMPORT_MAX_BODY_MB` caps the body, counted after decompression and
+//!   checked while it streams in: `413` with `import_too_large`.
+//! - `IMPORT_RATE_PER_HOUR` caps the imports each client (by IP) starts in a
+//!   fixed hour-long window: `429` with `import_rate_limited` and
+//!   `Retry-After`. These also count towards `RATE_LIMIT_PER_MINUTE`.
+//! - `IMPORT_TIMEOUT_MS` bounds the processing once the body is read: `504`
+//!   with `import_timeout`. A shorter `REQUEST_TIMEOUT_MS` still wins.
+//!
+//! All three are read per request, so a `SIGHUP` reload applies them to the
+//! next import. Exports are capped separately, with `MAX_CONCURRENT_EXPORTS`
+//! when the router is built.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Request, State},
+    http::header,
+    middleware::{from_fn_with_state, Next},
+    response::Response,
+    routing::MethodRouter,
+};
+use futures::StreamExt;
+
+use crate::{errors::AppError, middleware::client_key, state::AppState};
+
+/// Window `IMPORT_RATE_PER_HOUR` is counted in.
+pub const IMPORT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+const MB: usize = 1024 * 1024;
+
+/// Puts `route` under the import budgets in place of the global body limit.
+pub fn import(route: MethodRouter<AppState>, state: &AppState) -> MethodRouter<AppState> {
+    route
+        // The budget below reads the whole body; the extractors must not cut
+        // it off at `MAX_BODY_BYTES` afterwards.
+        .layer(DefaultBodyLimit::disable())
+        .route_layer(from_fn_with_state(state.clone(), import_budget))
+}
+
+/// Enforces the import budgets; see the [module docs](self).
+pub async fn import_budget(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let config = state.config();
+
+    let per_hour = config.import_rate_per_hour;
+    if per_hour > 0 {
+        state
+            .import_limiter()
+            .check(&client_key(&req), per_hour, Instant::now())
+            .map_err(|retry_after_secs| AppError::ImportRateLimited { retry_after_secs })?;
+    }
+
+    // Checked up front as well, so an honest client sending too much is
+    // refused before uploading any of it.
+    let max_mb = config.import_max_body_mb;
+    let too_large = AppError::ImportTooLarge { max_mb };
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > max_mb * MB) {
+        return Err(too_large);
+    }
+    let (parts, body) = req.into_parts();
+    let mut chunks = body.into_data_stream();
+    let mut buffered = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|err| {
+            AppError::Validation(format!("failed to read the request body: {err}").into())
+        })?;
+        if buffered.len() + chunk.len() > max_mb * MB {
+            return Err(too_large);
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+    let req = Request::from_parts(parts, Body::from(buffered));
+
+    match config.import_timeout_ms {
+        0 => Ok(next.run(req).await),
+        ms => tokio::time::timeout(Duration::from_millis(ms), next.run(req))
+            .await
+            .map_err(|_| AppError::ImportTimeout),
+    }
+}
@@ -0,0 +1,301 @@
+//! HTTP caching headers and conditional GETs.
+//!
+//! # Cache-Control
+//!
+//! Responses are `no-store` unless a handler opts in by returning a
+//! [`CachePolicy`] as part of its response (it implements `IntoResponseParts`,
+//! so it composes with tuples like `(policy, Json(todo))`). That way errors and
+//! mutations can never be cached by accident, and each read endpoint decides
+//! for itself.
+//!
+//! # ETags
+//!
+//! [`etag`] hashes the body of successful `GET`/`HEAD` responses whose size is
+//! known up front and answers `304 Not Modified` when the client's
+//! `If-None-Match` already matches. The hash is FNV-1a, which is stable across
+//! restarts and builds, so caches keep validating after a deploy.
+//!
+//! Lists are the exception: they are tagged with the collection's revision
+//! ([`revision_tag`]), which needs no buffering, so streamed lists carry one
+//! too. Clients echo it in `If-Match` on bulk writes to make sure nothing
+//! changed since they fetched the list.
+//!
+//! A single todo is tagged with its version ([`version_tag`]), the revision
+//! it was last written at, which `DELETE /todos/:id` checks `If-Match`
+//! against.
+//!
+//! # Encodings
+//!
+//! Tags are computed on the uncompressed representation, so a gzip body and
+//! the identity body would otherwise share one strong tag and a cache could
+//! answer a conditional request with the wrong bytes. [`encoding_tags`] sits
+//! just outside compression and suffixes the tag of every compressed response
+//! with its coding (`"rev-7"` becomes `"rev-7-gzip"`), strips that suffix
+//! from `If-None-Match` and `If-Match` on the way in, so the handlers and
+//! [`etag`] keep comparing plain tags, and marks tagged responses
+//! `Vary: Accept-Encoding`. A tag still names the todo or list it was issued
+//! for, so `If-Match: "3-gzip"` deletes version 3 just like `"3"` does.
+
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponseParts, Response, ResponseParts},
+};
+use http_body::Body as HttpBody;
+
+/// Largest body we are willing to buffer just to compute an ETag.
+const ETAG_MAX_BODY: u64 = 1024 * 1024;
+
+/// Caching behavior a handler wants for its successful response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Never store (the default for anything that doesn't opt in).
+    NoStore,
+    /// Storable, but the client must revalidate with the ETag every time.
+    Revalidate,
+    /// Cacheable by the client only, for `max_age` seconds.
+    Private { max_age: u64 },
+}
+
+impl CachePolicy {
+    fn header_value(self) -> HeaderValue {
+        match self {
+            CachePolicy::NoStore => HeaderValue::from_static("no-store"),
+            CachePolicy::Revalidate => HeaderValue::from_static("no-cache"),
+            CachePolicy::Private { max_age } => {
+                HeaderValue::from_str(&format!("private, max-age={max_age}"))
+                    .expect("digits are a valid header value")
+            }
+        }
+    }
+}
+
+impl IntoResponseParts for CachePolicy {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let headers = res.headers_mut();
+        headers.insert(header::CACHE_CONTROL, self.header_value());
+        if self != CachePolicy::NoStore {
+            // The representation depends on content negotiation and encoding.
+            headers.append(header::VARY, HeaderValue::from_static("accept, accept-encoding"));
+        }
+        Ok(res)
+    }
+}
+
+/// Fills in `Cache-Control: no-store` for responses that did not choose a
+/// policy and collapses `Vary` into one de-duplicated header (the compression
+/// layer appends its own `accept-encoding`).
+pub async fn cache_headers(req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+
+    // Errors are never cacheable, whatever the handler asked for.
+    let cacheable = res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED;
+    let headers = res.headers_mut();
+    if !cacheable || !headers.contains_key(header::CACHE_CONTROL) {
+        headers.insert(header::CACHE_CONTROL, CachePolicy::NoStore.header_value());
+    }
+
+    normalize_vary(headers);
+    res
+}
+
+fn normalize_vary(headers: &mut HeaderMap) {
+    let mut names: Vec<String> = Vec::new();
+    for value in headers.get_all(header::VARY) {
+        for name in value.to_str().unwrap_or_default().split(',') {
+            let name = name.trim().to_ascii_lowercase();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&names.join(", ")) {
+        headers.insert(header::VARY, value);
+    }
+}
+
+/// Adds a strong `ETag` to small successful reads and turns matching
+/// `If-None-Match` requests into `304 Not Modified`.
+pub async fn etag(req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let res = next.run(req).await;
+    let small = res
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len <= ETAG_MAX_BODY);
+    if res.status() != StatusCode::OK || !small {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let body = if parts.headers.contains_key(header::ETAG) {
+        body
+    } else {
+        // The size check above guarantees the body is already in memory.
+        let Ok(bytes) = axum::body::to_bytes(body, ETAG_MAX_BODY as usize).await else {
+            return Response::from_parts(parts, Body::empty());
+        };
+        parts.headers.insert(header::ETAG, body_tag(&bytes));
+        Body::from(bytes)
+    };
+
+    let fresh = match (&if_none_match, parts.headers.get(header::ETAG)) {
+        (Some(candidates), Some(tag)) => matches_any(candidates, tag),
+        _ => false,
+    };
+    if fresh {
+        return not_modified(parts);
+    }
+    Response::from_parts(parts, body)
+}
+
+/// Content codings [`encoding_tags`] suffixes tags with.
+const CODINGS: [&str; 4] = ["gzip", "br", "zstd", "deflate"];
+
+/// Gives compressed responses their own `ETag` and lets conditional requests
+/// name either variant; see [Encodings](self#encodings).
+pub async fn encoding_tags(mut req: Request, next: Next) -> Response {
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    for name in [header::IF_NONE_MATCH, header::IF_MATCH] {
+        if let Some(value) = req.headers().get(&name) {
+            let plain = value.to_str().ok().map(|tags| {
+                tags.split(',').map(|tag| strip_coding(tag.trim())).collect::<Vec<_>>().join(", ")
+            });
+            if let Some(plain) = plain.and_then(|plain| HeaderValue::from_str(&plain).ok()) {
+                req.headers_mut().insert(name, plain);
+            }
+        }
+    }
+
+    let mut res = next.run(req).await;
+    let Some(tag) = res.headers().get(header::ETAG).and_then(|tag| tag.to_str().ok()) else {
+        return res;
+    };
+    let coding = res.headers().get(header::CONTENT_ENCODING).and_then(|c| c.to_str().ok());
+    let tag = match coding {
+        Some(coding) if CODINGS.contains(&coding) => {
+            let (weak, quoted) = tag.strip_prefix("W/").map_or(("", tag), |tag| ("W/", tag));
+            let opaque = quoted.trim_end_matches('"');
+            HeaderValue::from_str(&format!("{weak}{opaque}-{coding}\""))
+        }
+        // A `304` has no body to compress, so it names the variant the
+        // client already holds.
+        _ if res.status() == StatusCode::NOT_MODIFIED => {
+            let candidates = if_none_match.as_ref().and_then(|value| value.to_str().ok());
+            let held = candidates.into_iter().flat_map(|tags| tags.split(',')).map(str::trim).find(
+                |candidate| strip_weak(&strip_coding(candidate)) == strip_weak(tag),
+            );
+            HeaderValue::from_str(held.unwrap_or(tag))
+        }
+        _ => HeaderValue::from_str(tag),
+    };
+    if let Ok(tag) = tag {
+        res.headers_mut().insert(header::ETAG, tag);
+    }
+    res.headers_mut().append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    res
+}
+
+/// Drops the coding suffix from an entity tag: `"abc-gzip"` is `"abc"`.
+fn strip_coding(tag: &str) -> String {
+    let Some(opaque) = tag.strip_suffix('"') else {
+        return tag.to_string();
+    };
+    for coding in CODINGS {
+        if let Some(plain) = opaque.strip_suffix(coding).and_then(|o| o.strip_suffix('-')) {
+            return format!("{plain}\"");
+        }
+    }
+    tag.to_string()
+}
+
+/// Strong `ETag` for revision `revision` of the whole collection.
+pub fn revision_tag(revision: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"rev-{revision}\""))
+        .expect("digits are a valid header value")
+}
+
+/// Whether an `If-Match` header accepts collection revision `revision`.
+/// Strong comparison, as RFC 9110 requires, so weak tags never match.
+pub fn if_match_accepts(if_match: &HeaderValue, revision: u64) -> bool {
+    let Ok(candidates) = if_match.to_str() else {
+        return false;
+    };
+    let current = revision_tag(revision);
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.as_bytes() == current.as_bytes())
+}
+
+/// Strong `ETag` for a todo at `version`.
+pub fn version_tag(version: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{version}\"")).expect("digits are a valid header value")
+}
+
+/// The todo version an `If-Match` names with a [`version_tag`]; `None` for
+/// `*`, which any todo that exists matches. Anything else, weak tags and
+/// lists included, names version 0, which no todo is at.
+pub fn if_match_version(if_match: &HeaderValue) -> Option<u64> {
+    let tag = if_match.to_str().unwrap_or_default().trim();
+    if tag == "*" {
+        return None;
+    }
+    let version = tag.strip_prefix('"').and_then(|tag| tag.strip_suffix('"'));
+    Some(version.and_then(|version| version.parse().ok()).unwrap_or(0))
+}
+
+fn not_modified(mut parts: axum::http::response::Parts) -> Response {
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// Weak comparison, as RFC 9110 requires for `If-None-Match`.
+fn matches_any(candidates: &HeaderValue, tag: &HeaderValue) -> bool {
+    let Ok(candidates) = candidates.to_str() else {
+        return false;
+    };
+    let tag = strip_weak(tag.to_str().unwrap_or_default());
+
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || strip_weak(candidate) == tag)
+}
+
+/// Strong `ETag` for a response body: a hash of its bytes, as [`etag`]
+/// would add.
+pub fn body_tag(bytes: &[u8]) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{:016x}\"", fnv1a(bytes)))
+        .expect("hex digits are a valid header value")
+}
+
+fn strip_weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
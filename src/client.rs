@@ -0,0 +1,133 @@
+//! Minimal HTTP client for the todo API, used by the `loadgen` binary.
+//!
+//! A [`Client`] holds one keep-alive HTTP/1.1 connection and sends requests on
+//! it one at a time, so each concurrent caller should own its own. If the
+//! server drops the connection, requests fail with [`ClientError::Http`] and
+//! the caller reconnects with [`Client::connect`].
+
+use axum::{
+    body::{self, Body},
+    http::{header, uri::Authority, Method, Request, StatusCode, Uri},
+};
+use hyper::client::conn::http1::{self, SendRequest};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tokio::net::TcpStream;
+
+use crate::models::{CreateTodo, Todo};
+
+/// Largest response body read, to bound memory when listing big stores.
+const MAX_BODY: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("invalid base url `{0}`, expected http://host:port")]
+    Url(String),
+    #[error("connect: {0}")]
+    Connect(#[from] std::io::Error),
+    #[error("http: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("reading body: {0}")]
+    Body(#[from] axum::Error),
+    /// The server answered with an unexpected status.
+    #[error("{status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("decoding body: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+pub struct Client {
+    authority: Authority,
+    sender: SendRequest<Body>,
+}
+
+impl Client {
+    /// Opens a connection to the server at `base_url`, e.g.
+    /// `http://127.0.0.1:8080`.
+    pub async fn connect(base_url: &str) -> Result<Self, ClientError> {
+        let invalid = || ClientError::Url(base_url.to_string());
+        let uri: Uri = base_url.parse().map_err(|_| invalid())?;
+        if uri.scheme_str() != Some("http") {
+            return Err(invalid());
+        }
+        let authority = uri.authority().ok_or_else(invalid)?.clone();
+        let port = authority.port_u16().unwrap_or(80);
+
+        let stream = TcpStream::connect((authority.host(), port)).await?;
+        stream.set_nodelay(true)?;
+        let (sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::debug!(error = %err, "client connection closed");
+            }
+        });
+        Ok(Self { authority, sender })
+    }
+
+    /// `GET /v1/todos?offset=&limit=`.
+    pub async fn list(&mut self, offset: usize, limit: usize) -> Result<Vec<Todo>, ClientError> {
+        let uri = format!("/v1/todos?offset={offset}&limit={limit}");
+        self.json(Method::GET, &uri, None, StatusCode::OK).await
+    }
+
+    /// `POST /v1/todos`.
+    pub async fn create(&mut self, input: &CreateTodo) -> Result<Todo, ClientError> {
+        let body = serde_json::to_vec(input)?;
+        self.json(Method::POST, "/v1/todos", Some(body), StatusCode::CREATED).await
+    }
+
+    /// `GET /v1/todos/:id`.
+    pub async fn get(&mut self, id: u64) -> Result<Todo, ClientError> {
+        let uri = format!("/v1/todos/{id}");
+        self.json(Method::GET, &uri, None, StatusCode::OK).await
+    }
+
+    /// `DELETE /v1/todos/:id`.
+    pub async fn delete(&mut self, id: u64) -> Result<(), ClientError> {
+        let uri = format!("/v1/todos/{id}");
+        self.send(Method::DELETE, &uri, None, StatusCode::NO_CONTENT).await?;
+        Ok(())
+    }
+
+    async fn json<T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        uri: &str,
+        body: Option<Vec<u8>>,
+        expected: StatusCode,
+    ) -> Result<T, ClientError> {
+        let bytes = self.send(method, uri, body, expected).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Sends one request, with a JSON body if given, and returns the body of
+    /// a response with the `expected` status.
+    async fn send(
+        &mut self,
+        method: Method,
+        uri: &str,
+        body: Option<Vec<u8>>,
+        expected: StatusCode,
+    ) -> Result<body::Bytes, ClientError> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, self.authority.as_str())
+            .header(header::ACCEPT, "application/json");
+        if body.is_some() {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+        }
+        let body = body.map_or_else(Body::empty, Body::from);
+        let request = request.body(body).expect("request parts are valid");
+        self.sender.ready().await?;
+        let response = self.sender.send_request(request).await?;
+        let status = response.status();
+        let bytes = body::to_bytes(Body::new(response.into_body()), MAX_BODY).await?;
+        if status != expected {
+            let body = String::from_utf8_lossy(&bytes).into_owned();
+            return Err(ClientError::Status { status, body });
+        }
+        Ok(bytes)
+    }
+}
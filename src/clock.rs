@@ -0,0 +1,30 @@
+//! Where the current time comes from.
+//!
+//! Everything that stamps or compares wall-clock time (the repository's
+//! `created_at`/`updated_at`/`done_at`, reminders, the time series window,
+//! the calendar's `DTSTAMP`) asks a [`Clock`] instead of calling
+//! `Utc::now()`. Production uses [`SystemClock`]; tests pass a
+//! [`MockClock`](crate::test_utils::MockClock) they advance by hand, so
+//! nothing races the real clock.
+//!
+//! Timeouts and latency measurements use `Instant` and are not affected.
+//! Stored times keep the clock's full precision, which keeps successive
+//! updates ordered; only their [wire format](crate::timestamps) is cut to
+//! milliseconds.
+
+use chrono::{DateTime, Utc};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
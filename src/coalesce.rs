@@ -0,0 +1,106 @@
+//! Sharing one read among identical concurrent requests.
+//!
+//! When the list cache turns over on a popular dashboard, hundreds of
+//! identical `GET /todos?limit=...` requests can arrive within a few
+//! milliseconds, and each would read the repository on its own. With
+//! `COALESCE_LISTS` on (the default), [`TodoService`](crate::service::TodoService)
+//! runs list reads through a [`Coalescer`] instead: the first request for a
+//! key does the read, and every request for the same key that arrives while
+//! it is in flight waits for it and gets the same page, or the same error.
+//!
+//! Only reads that overlap are shared; nothing is kept once the read is done
+//! (that's the [list cache](crate::list_cache)'s job). If the request doing
+//! the read panics or is cancelled, the waiters don't inherit that: the
+//! entry is removed all the same, and the next of them does the read again.
+
+use std::{collections::HashMap, future::Future, hash::Hash, sync::Mutex};
+
+use tokio::sync::watch;
+
+use crate::errors::AppError;
+
+type Outcome<V> = Option<Result<V, AppError>>;
+
+/// Reads in flight, by key.
+pub struct Coalescer<K, V> {
+    in_flight: Mutex<HashMap<K, watch::Receiver<Outcome<V>>>>,
+}
+
+impl<K, V> Default for Coalescer<K, V> {
+    fn default() -> Self {
+        Self {
+            in_flight: Mutex::default(),
+        }
+    }
+}
+
+enum Role<V> {
+    Lead(watch::Sender<Outcome<V>>),
+    Wait(watch::Receiver<Outcome<V>>),
+}
+
+impl<K, V> Coalescer<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// The result of `read`, or of the read already in flight for `key`.
+    pub async fn run(
+        &self,
+        key: K,
+        read: impl Future<Output = Result<V, AppError>>,
+    ) -> Result<V, AppError> {
+        loop {
+            match self.join(&key) {
+                Role::Lead(done) => {
+                    // Dropped before `done`, so a waiter woken by the sender
+                    // going away never finds the stale entry.
+                    let _entry = Entry {
+                        coalescer: self,
+                        key: &key,
+                    };
+                    let outcome = read.await;
+                    done.send_replace(Some(outcome.clone()));
+                    return outcome;
+                }
+                Role::Wait(mut done) => {
+                    if let Ok(outcome) = done.wait_for(Option::is_some).await {
+                        return outcome.clone().expect("waited for an outcome");
+                    }
+                    // The leader panicked or was cancelled; try again.
+                }
+            }
+        }
+    }
+
+    fn join(&self, key: &K) -> Role<V> {
+        let mut in_flight = self.lock();
+        match in_flight.get(key) {
+            Some(done) => Role::Wait(done.clone()),
+            None => {
+                let (done, waiting) = watch::channel(None);
+                in_flight.insert(key.clone(), waiting);
+                Role::Lead(done)
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, watch::Receiver<Outcome<V>>>> {
+        self.in_flight.lock().expect("coalescer lock poisoned")
+    }
+}
+
+/// Removes the leader's entry however its read ends.
+struct Entry<'a, K: Eq + Hash, V> {
+    coalescer: &'a Coalescer<K, V>,
+    key: &'a K,
+}
+
+impl<K: Eq + Hash, V> Drop for Entry<'_, K, V> {
+    fn drop(&mut self) {
+        // Not `lock()`: this may run while unwinding from a panic.
+        if let Ok(mut in_flight) = self.coalescer.in_flight.lock() {
+            in_flight.remove(self.key);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Response compression.
+//!
+//! # What is configurable when
+//!
+//! `CompressionLayer` fixes its encoders and quality when it is built, so
+//! `COMPRESSION_ALGORITHMS` and `COMPRESSION_LEVEL` need a restart. Whether to
+//! compress a given response is decided per request by [`LivePredicate`], which
+//! reads the current config; `COMPRESSION_ENABLED` and `COMPRESSION_MIN_BYTES`
+//! therefore take effect on SIGHUP like the rest of the hot settings.
+//!
+//! # Skipped responses
+//!
+//! Besides small bodies, the predicate keeps tower-http's defaults: gRPC,
+//! images (other than SVG), and server-sent events are never compressed.
+
+use axum::http::Response;
+use http_body::Body as HttpBody;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+use crate::{config::CompressionAlgorithm, state::AppState};
+
+/// Builds the compression layer from the config in `state` at startup.
+pub fn layer(state: &AppState) -> CompressionLayer<LivePredicate> {
+    let config = state.config();
+    let offers = |algorithm| config.compression_algorithms.contains(&algorithm);
+
+    CompressionLayer::new()
+        .gzip(offers(CompressionAlgorithm::Gzip))
+        .br(offers(CompressionAlgorithm::Brotli))
+        .zstd(offers(CompressionAlgorithm::Zstd))
+        .quality(config.compression_level)
+        .compress_when(LivePredicate {
+            state: state.clone(),
+        })
+}
+
+/// Compression predicate driven by the hot-reloadable config.
+#[derive(Clone)]
+pub struct LivePredicate {
+    state: AppState,
+}
+
+impl Predicate for LivePredicate {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let config = self.state.config();
+        config.compression_enabled
+            && SizeAbove::new(config.compression_min_bytes).should_compress(response)
+            && NotForContentType::GRPC.should_compress(response)
+            && NotForContentType::IMAGES.should_compress(response)
+            && NotForContentType::SSE.should_compress(response)
+    }
+}
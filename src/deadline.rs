@@ -0,0 +1,102 @@
+//! Request deadlines.
+//!
+//! Callers with a time budget of their own send `X-Request-Deadline`, either
+//! as an RFC 3339 timestamp or as milliseconds from now. [`deadline`] turns it
+//! into a [`Deadline`], capped by `REQUEST_TIMEOUT_MS`, and abandons the
+//! request with `504` once it passes: nobody is waiting for the answer any
+//! more.
+//!
+//! The deadline is stored in the request extensions for handlers and in a
+//! task-local for code that never sees the request. Repository
+//! implementations read it with [`current`], e.g. to set a statement timeout,
+//! without every `TodoRepo` method growing a context argument.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+use crate::{errors::AppError, state::AppState};
+
+/// Header carrying the caller's deadline.
+pub const HEADER: &str = "x-request-deadline";
+
+tokio::task_local! {
+    static CURRENT: Deadline;
+}
+
+/// Point in time after which the request's result is no longer wanted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Time left, zero once the deadline has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Deadline of the request being handled on this task, if it has one.
+pub fn current() -> Option<Deadline> {
+    CURRENT.try_with(|deadline| *deadline).ok()
+}
+
+/// Runs `future` with `deadline` visible through [`current`].
+pub async fn scope<F: std::future::Future>(deadline: Deadline, future: F) -> F::Output {
+    CURRENT.scope(deadline, future).await
+}
+
+/// Parses an `X-Request-Deadline` value into the budget left from now. A
+/// timestamp in the past is a zero budget, not an error.
+pub fn parse(raw: &HeaderValue) -> Result<Duration, String> {
+    let raw = raw.to_str().map_err(|_| "not valid UTF-8".to_string())?.trim();
+    if !raw.is_empty() && raw.bytes().all(|b| b.is_ascii_digit()) {
+        let ms = raw.parse().map_err(|_| format!("`{raw}` is too large"))?;
+        return Ok(Duration::from_millis(ms));
+    }
+    let at = DateTime::parse_from_rfc3339(raw)
+        .map_err(|_| format!("`{raw}` is neither milliseconds nor an RFC 3339 timestamp"))?;
+    Ok((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Applies the tighter of the caller's deadline and `REQUEST_TIMEOUT_MS`.
+/// A malformed header is logged and ignored rather than failing a request
+/// that may well finish in time.
+pub async fn deadline(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let requested = req.headers().get(HEADER).and_then(|raw| {
+        parse(raw)
+            .inspect_err(|reason| tracing::warn!(reason, "ignoring malformed X-Request-Deadline"))
+            .ok()
+    });
+    let timeout = match state.config().request_timeout_ms {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    };
+    let Some(budget) = requested.into_iter().chain(timeout).min() else {
+        return Ok(next.run(req).await);
+    };
+
+    let deadline = Deadline(Instant::now() + budget);
+    req.extensions_mut().insert(deadline);
+    tokio::time::timeout_at(deadline.instant(), scope(deadline, next.run(req)))
+        .await
+        .map_err(|_| AppError::DeadlineExceeded)
+}
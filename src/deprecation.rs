@@ -0,0 +1,116 @@
+//! Marking and counting requests to legacy routes.
+//!
+//! The API lives under `/v1`. The same routes without the prefix still work
+//! for clients that predate it, but [`legacy`] wraps each of them:
+//!
+//! - `Deprecation: @<unix time>` (RFC 9745) and `Sunset: <HTTP date>`
+//!   (RFC 8594) go on every response once `LEGACY_DEPRECATION_DATE` and
+//!   `LEGACY_SUNSET_DATE` are set.
+//! - `deprecated_requests_total{route, client}` counts the requests, so we
+//!   know who still has to move before the routes go away. `client` is the
+//!   first product in `User-Agent`, such as `curl` or `todo-ios`.
+//! - A warning names each client at most once per [`WARN_EVERY`], so a busy
+//!   client doesn't flood the logs.
+//!
+//! Only [`MAX_CLIENTS`] distinct client labels are kept; later ones are
+//! counted as `other`, which bounds the metric's cardinality.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::state::AppState;
+
+/// How often each client is warned about at most.
+pub const WARN_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// Distinct `client` labels before the rest are reported as `other`.
+pub const MAX_CLIENTS: usize = 100;
+
+/// Longest `User-Agent` product kept as a label.
+const MAX_LABEL_LEN: usize = 32;
+
+/// Clients seen on legacy routes and when each was last warned about.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    warned: Mutex<HashMap<String, Instant>>,
+}
+
+impl Tracker {
+    /// The label to count `user_agent` under.
+    pub fn client(&self, user_agent: Option<&str>) -> String {
+        let product = user_agent
+            .and_then(|agent| agent.split(['/', ' ']).next())
+            .unwrap_or_default();
+        if product.is_empty() {
+            return "unknown".to_string();
+        }
+        let valid = product.len() <= MAX_LABEL_LEN
+            && product
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        let warned = self.warned.lock().expect("deprecation tracker lock poisoned");
+        if !valid || (!warned.contains_key(product) && warned.len() >= MAX_CLIENTS) {
+            return "other".to_string();
+        }
+        product.to_string()
+    }
+
+    /// Whether `client` is due a warning at `now`, recording it if so.
+    pub fn should_warn(&self, client: &str, now: Instant) -> bool {
+        let mut warned = self.warned.lock().expect("deprecation tracker lock poisoned");
+        match warned.get(client) {
+            Some(last) if now.duration_since(*last) < WARN_EVERY => false,
+            _ => {
+                warned.insert(client.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Middleware for routes outside `/v1`; see the [module docs](self).
+pub async fn legacy(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |path| path.as_str().to_string());
+    let user_agent = req.headers().get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let tracker = state.deprecations();
+    let client = tracker.client(user_agent);
+
+    state
+        .metrics()
+        .deprecated_requests_total
+        .with_label_values(&[route.as_str(), client.as_str()])
+        .inc();
+    if tracker.should_warn(&client, Instant::now()) {
+        tracing::warn!(
+            %route,
+            %client,
+            "request to a deprecated route; clients should move to /v1"
+        );
+    }
+
+    let mut res = next.run(req).await;
+    let config = state.config();
+    let headers = res.headers_mut();
+    if let Some(at) = config.legacy_deprecation_date {
+        let value = format!("@{}", at.timestamp());
+        headers.insert("deprecation", HeaderValue::try_from(value).expect("digits are valid"));
+    }
+    if let Some(at) = config.legacy_sunset_date {
+        let value = at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        headers.insert("sunset", HeaderValue::try_from(value).expect("dates are valid"));
+    }
+    res
+}
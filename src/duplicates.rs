@@ -0,0 +1,90 @@
+//! Duplicate detection when creating todos.
+//!
+//! # Normalizing
+//!
+//! Titles are compared after [`normalize`]: reduced to their
+//! [`canonical_key`] with punctuation dropped, so `"Buy milk!"`, `"buy  milk"`
+//! and `"ＢＵＹ ＭＩＬＫ"` are the same title.
+//!
+//! # Similarity
+//!
+//! [`similarity`] is the Levenshtein distance between two normalized titles,
+//! scaled by the longer one and flipped so `1.0` means identical. Titles are
+//! at most 100 bytes, so the quadratic distance is cheap.
+//!
+//! # Policy
+//!
+//! [`check`] runs before `POST /todos` creates anything. With
+//! `REJECT_EXACT_DUPLICATES` a normalized-identical open todo is always a
+//! `409`, whatever else is configured. Otherwise, with `DUPLICATE_WARNING`,
+//! open todos at least `DUPLICATE_THRESHOLD` similar are returned so the
+//! handler can warn about them, or rejected when the client asked for
+//! `?strict_duplicates=true` (or the `strict_duplicates` feature flag is
+//! on). The check is advisory: two concurrent creates can still both
+//! succeed.
+
+use crate::{config::Config, errors::AppError, models::canonical_key, state::TodoRepo};
+
+/// Words of `title`'s [`canonical_key`] separated by single spaces.
+pub fn normalize(title: &str) -> String {
+    // Punctuation goes after composing, so accents aren't dropped with it.
+    let cleaned: String = canonical_key(title)
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// How alike two normalized titles are, from `0.0` (nothing shared) to
+/// `1.0` (identical).
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Applies the configured duplicate policy to a new title.
+///
+/// Returns the ids of near-duplicates to warn about (empty when there are
+/// none or the warning is off), or [`AppError::Duplicate`] when the create
+/// must be refused.
+pub async fn check(
+    repo: &dyn TodoRepo,
+    config: &Config,
+    title: &str,
+    strict: bool,
+) -> Result<Vec<u64>, AppError> {
+    if config.reject_exact_duplicates {
+        let exact = repo.find_similar(title, 1.0).await?;
+        if !exact.is_empty() {
+            return Err(AppError::Duplicate(exact));
+        }
+    }
+    if !config.duplicate_warning {
+        return Ok(Vec::new());
+    }
+
+    let similar = repo.find_similar(title, config.duplicate_threshold).await?;
+    if strict && !similar.is_empty() {
+        return Err(AppError::Duplicate(similar));
+    }
+    Ok(similar)
+}
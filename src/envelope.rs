@@ -0,0 +1,195 @@
+//! Optional `{ "data": ..., "meta": ... }` response envelope.
+//!
+//! Some clients expect every response in a uniform wrapper:
+//!
+//! ```json
+//! { "data": [...], "meta": { "request_id": "...", "pagination": { ... } } }
+//! { "error": { "code": "not_found", "message": "not found" }, "meta": { ... } }
+//! ```
+//!
+//! The envelope is off by default. `ENVELOPE_RESPONSES=true` turns it on for
+//! everyone, as does the `envelope` [feature flag](crate::flags) for the
+//! requests it is on for, and `?envelope=true|false` overrides both.
+//!
+//! # No handler changes
+//!
+//! Handlers keep returning bare values; [`envelope`] reshapes the response on
+//! the way out. Success bodies are not parsed at all: the envelope is written
+//! around them as a prefix and suffix, so streamed lists stay streamed. That
+//! works for MessagePack too, since a two-entry map is just a header byte
+//! followed by its keys and values. Pagination details come from the
+//! [`Pagination`] extension list handlers attach.
+//!
+//! Error bodies are small and always JSON at this point (MessagePack error
+//! transcoding happens further out), so they are parsed and rebuilt.
+//!
+//! Responses that already have a fixed shape of their own (GraphQL) carry the
+//! [`Bare`] extension and are left alone.
+//!
+//! Because `meta` carries the request id, enveloped bodies differ on every
+//! request and never get an ETag.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use futures::{stream, StreamExt};
+use http_body::Body as HttpBody;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::ErrorCode,
+    flags::{Flag, RequestFlags},
+    models::Pagination,
+    negotiation::MSGPACK,
+    state::AppState,
+};
+
+/// Largest error body we are willing to rewrite.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// MessagePack `fixmap` header for a map with two entries.
+const MSGPACK_MAP2: u8 = 0x82;
+
+/// Response extension that opts a response out of the envelope.
+#[derive(Clone, Copy, Debug)]
+pub struct Bare;
+
+#[derive(Deserialize)]
+struct EnvelopeQuery {
+    envelope: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct Meta {
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pagination: Option<Pagination>,
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope {
+    error: ErrorDetails,
+    meta: Meta,
+}
+
+#[derive(Serialize)]
+struct ErrorDetails {
+    code: String,
+    message: String,
+}
+
+/// Wraps responses in the envelope when the config or the request asks for it.
+pub async fn envelope(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let wanted = Query::<EnvelopeQuery>::try_from_uri(req.uri())
+        .ok()
+        .and_then(|Query(query)| query.envelope)
+        .unwrap_or_else(|| {
+            let flags = req.extensions().get::<RequestFlags>().copied().unwrap_or_default();
+            flags.enabled(Flag::Envelope) || state.config().envelope_responses
+        });
+    if !wanted {
+        return next.run(req).await;
+    }
+
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let res = next.run(req).await;
+    if res.extensions().get::<Bare>().is_some() {
+        return res;
+    }
+    let meta = Meta {
+        request_id,
+        pagination: res.extensions().get::<Pagination>().copied(),
+    };
+
+    if res.status().is_success() {
+        wrap_data(res, meta)
+    } else {
+        wrap_error(res, meta).await
+    }
+}
+
+fn wrap_data(res: Response, meta: Meta) -> Response {
+    let is_empty = res.body().size_hint().exact() == Some(0);
+    let (prefix, suffix) = match content_type(&res) {
+        _ if is_empty => return res,
+        Some("application/json") => {
+            let meta = serde_json::to_vec(&meta).expect("meta serializes");
+            (
+                Bytes::from_static(b"{\"data\":"),
+                [&b",\"meta\":"[..], &meta, b"}"].concat(),
+            )
+        }
+        Some(MSGPACK) => {
+            let data_key = rmp_serde::to_vec("data").expect("strings serialize");
+            let meta_key = rmp_serde::to_vec("meta").expect("strings serialize");
+            let meta = rmp_serde::to_vec_named(&meta).expect("meta serializes");
+            (
+                Bytes::from([&[MSGPACK_MAP2][..], &data_key].concat()),
+                [meta_key, meta].concat(),
+            )
+        }
+        // Plain text, metrics and the like have no natural place for `data`.
+        _ => return res,
+    };
+
+    let (mut parts, body) = res.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let chunks = stream::once(async move { Ok(prefix) })
+        .chain(body.into_data_stream())
+        .chain(stream::once(async move { Ok(Bytes::from(suffix)) }));
+    Response::from_parts(parts, Body::from_stream(chunks))
+}
+
+async fn wrap_error(res: Response, meta: Meta) -> Response {
+    let status = res.status();
+    let code = res.extensions().get::<ErrorCode>().map(|ErrorCode(code)| *code);
+    let is_json = content_type(&res) == Some("application/json");
+
+    let (mut parts, body) = res.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    // Our own errors carry `{"error": "message"}`; anything else (e.g. axum's
+    // bare 404/405) falls back to the status text.
+    let message = is_json
+        .then(|| serde_json::from_slice::<Value>(&bytes).ok())
+        .flatten()
+        .and_then(|value| value.get("error")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| reason(status));
+    let code = code.map_or_else(|| reason(status).replace(' ', "_"), str::to_string);
+
+    let body = ErrorEnvelope {
+        error: ErrorDetails { code, message },
+        meta,
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    let body = serde_json::to_vec(&body).expect("error envelope serializes");
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// The media type of the response, without parameters.
+fn content_type(res: &Response) -> Option<&str> {
+    let value = res.headers().get(header::CONTENT_TYPE)?.to_str().ok()?;
+    Some(value.split(';').next().unwrap_or_default().trim())
+}
+
+fn reason(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .unwrap_or("error")
+        .to_ascii_lowercase()
+}
@@ -19,30 +19,238 @@
 //! By implementing `IntoResponse`, we can return `Result<T, AppError>` directly
 //! from our handlers.
 
+use std::{error::Error as _, fmt};
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+use crate::{
+    attachments::{StorageOverage, StorageScope},
+    caching,
+    quota::Overage,
+    reporting::ReportedError,
+};
+
 /// Application-level error. Each variant maps to an HTTP status via the
 /// `IntoResponse` impl at the bottom.
-#[derive(Debug, Error)]
+#[derive(Clone, Debug, Error)]
 pub enum AppError {
     #[error("not found")]
     NotFound,
     #[error("validation error: {0}")]
-    Validation(String),
+    Validation(ValidationError),
+    /// An `:id` path segment that can't name a record.
+    #[error("invalid id `{0}`: ids are whole numbers from 1 to {max}", max = u64::MAX)]
+    InvalidId(String),
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("payload too large")]
+    PayloadTooLarge,
     #[error("internal error")]
     Internal,
+    #[error("service is in read-only mode")]
+    ReadOnly,
+    #[error("too many requests")]
+    RateLimited { retry_after_secs: u64 },
+    /// A backing service the request depends on is down.
+    #[error("service temporarily unavailable")]
+    Unavailable,
+    /// Too many requests are already in flight; this one was shed.
+    #[error("server is overloaded, try again shortly")]
+    Overloaded,
+    /// The caller's `X-Request-Deadline` (or `REQUEST_TIMEOUT_MS`) passed
+    /// before the response was ready.
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    /// Open todos the new one would duplicate, most similar first.
+    #[error("possible duplicate of todos {0:?}")]
+    Duplicate(Vec<u64>),
+    /// A delta sync asked for changes the server no longer remembers; the
+    /// client must refetch everything.
+    #[error("resync required: changes since that revision are no longer available")]
+    ResyncRequired,
+    /// An `If-Match` named a collection revision other than the current one
+    /// (`None` when the repository keeps no revision), so nothing was
+    /// written.
+    #[error("precondition failed: the collection has changed since it was fetched")]
+    PreconditionFailed { revision: Option<u64> },
+    /// An `If-Match` named a todo version other than its current one (`None`
+    /// when the repository keeps no versions), so it was left alone.
+    #[error("precondition failed: the todo has changed since it was fetched")]
+    VersionMismatch { version: Option<u64> },
+    /// An import body above `IMPORT_MAX_BODY_MB`.
+    #[error("import is larger than {max_mb} MB")]
+    ImportTooLarge { max_mb: usize },
+    /// The caller used up `IMPORT_RATE_PER_HOUR`.
+    #[error("too many imports, try again later")]
+    ImportRateLimited { retry_after_secs: u64 },
+    /// An import still running after `IMPORT_TIMEOUT_MS`.
+    #[error("import did not finish in time")]
+    ImportTimeout,
+    /// The route needs an API key and the request carried no valid one.
+    #[error("a valid API key is required")]
+    Unauthorized,
+    /// The key is valid but lacks the role the route needs.
+    #[error("this API key may not use this route")]
+    Forbidden,
+    /// A write authenticated by session cookie didn't echo the session's
+    /// CSRF token; see [`sessions`](crate::sessions).
+    #[error("cookie-authenticated writes must send the session's CSRF token")]
+    CsrfFailed,
+    /// The upload would take its todo past `ATTACHMENT_MAX_PER_TODO` (`413`)
+    /// or all attachments past `ATTACHMENTS_MAX_BYTES` (`507`).
+    #[error("attachment storage quota exceeded: {} of {} bytes used", .0.used, .0.limit)]
+    StorageQuotaExceeded(StorageOverage),
+    /// A [share link](crate::sharing) whose signature doesn't match.
+    #[error("invalid share link")]
+    InvalidShareLink,
+    /// A share link past its expiry.
+    #[error("this share link has expired")]
+    ShareLinkExpired,
+    /// A share link revoked with `DELETE /todos/:id/share`.
+    #[error("this share link has been revoked")]
+    ShareLinkRevoked,
+    /// The create would take the store past `MAX_TODOS`.
+    #[error("quota exceeded: {} over the limit of {} todos", .0.over, .0.limit)]
+    QuotaExceeded(Overage),
+}
+
+impl AppError {
+    /// Stable, machine-readable identifier for the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound => "not_found",
+            AppError::Validation(_) => "validation_failed",
+            AppError::InvalidId(_) => "invalid_id",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::Internal => "internal",
+            AppError::ReadOnly => "read_only",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::Unavailable => "unavailable",
+            AppError::Overloaded => "overloaded",
+            AppError::DeadlineExceeded => "deadline_exceeded",
+            AppError::Duplicate(_) => "duplicate",
+            AppError::ResyncRequired => "resync_required",
+            AppError::PreconditionFailed { .. } | AppError::VersionMismatch { .. } => {
+                "precondition_failed"
+            }
+            AppError::ImportTooLarge { .. } => "import_too_large",
+            AppError::ImportRateLimited { .. } => "import_rate_limited",
+            AppError::ImportTimeout => "import_timeout",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Forbidden => "forbidden",
+            AppError::CsrfFailed => "csrf_failed",
+            AppError::InvalidShareLink => "invalid_share_link",
+            AppError::ShareLinkExpired => "share_link_expired",
+            AppError::ShareLinkRevoked => "share_link_revoked",
+            AppError::QuotaExceeded(_) => "quota_exceeded",
+            AppError::StorageQuotaExceeded(_) => "storage_quota_exceeded",
+        }
+    }
+}
+
+/// Why input was rejected. Clients only see `message`; the field, rule, and
+/// fingerprint are logged so a `400` can be traced to the rule that fired
+/// without the logs ever holding what the user sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationError {
+    pub message: String,
+    /// Input field the rule guards, e.g. `title`.
+    pub field: Option<&'static str>,
+    /// Short name of the rule, e.g. `empty` or `too_long`.
+    pub rule: Option<&'static str>,
+    /// [`fingerprint`] of the offending value.
+    pub fingerprint: Option<String>,
+}
+
+impl ValidationError {
+    /// `value` broke `rule` on `field`.
+    pub fn field(
+        field: &'static str,
+        rule: &'static str,
+        value: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            field: Some(field),
+            rule: Some(rule),
+            fingerprint: Some(fingerprint(value)),
+        }
+    }
+}
+
+impl From<String> for ValidationError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&str> for ValidationError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
+/// First 12 hex digits of the SHA-256 of `value`: enough to tell whether two
+/// failures saw the same input, useless for recovering it.
+pub fn fingerprint(value: &str) -> String {
+    Sha256::digest(value.as_bytes())[..6]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// `err` followed by each of its sources, separated by `: `.
+fn chain(err: &AppError) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push_str(&format!(": {err}"));
+        source = err.source();
+    }
+    chain
+}
+
+/// [`AppError::code`] of an error response, stored in its extensions so
+/// layers can reshape the body without parsing the message.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorCode(pub &'static str);
+
 /// Shape of the JSON error response sent back to clients.
 #[derive(Serialize)]
 struct ErrorBody {
     error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    possible_duplicates: Vec<u64>,
+    /// The collection's current revision, after a failed `If-Match`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revision: Option<u64>,
+    /// The todo's current version, after a failed `If-Match`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u64>,
+    /// How far over `MAX_TODOS` a refused create would have gone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quota: Option<Overage>,
+    /// The attachment quota a refused upload would have broken.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage: Option<StorageOverage>,
 }
 
 impl IntoResponse for AppError {
@@ -50,12 +258,136 @@ impl IntoResponse for AppError {
         let (status, msg) = match &self {
             AppError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
             AppError::Validation(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::InvalidId(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::UnsupportedMediaType(_) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, self.to_string())
+            }
+            AppError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
             AppError::Internal => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 self.to_string(),
             ),
+            AppError::ReadOnly => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            AppError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            AppError::Overloaded => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            AppError::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
+            AppError::Duplicate(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::ResyncRequired => (StatusCode::CONFLICT, self.to_string()),
+            AppError::PreconditionFailed { .. } | AppError::VersionMismatch { .. } => {
+                (StatusCode::PRECONDITION_FAILED, self.to_string())
+            }
+            AppError::ImportTooLarge { .. } => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            AppError::ImportRateLimited { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
+            AppError::ImportTimeout => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AppError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::CsrfFailed => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::InvalidShareLink => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AppError::ShareLinkExpired | AppError::ShareLinkRevoked => {
+                (StatusCode::GONE, self.to_string())
+            }
+            AppError::QuotaExceeded(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::StorageQuotaExceeded(overage) => {
+                let status = match overage.scope {
+                    StorageScope::Todo => StatusCode::PAYLOAD_TOO_LARGE,
+                    StorageScope::Total => StatusCode::INSUFFICIENT_STORAGE,
+                };
+                (status, self.to_string())
+            }
+        };
+        // Emitted inside the request span, so these line up with the access
+        // log entry for the same request.
+        match &self {
+            AppError::Validation(err) => tracing::warn!(
+                code = self.code(),
+                field = err.field,
+                rule = err.rule,
+                payload_hash = err.fingerprint.as_deref(),
+                "validation failed"
+            ),
+            AppError::Internal => {
+                tracing::error!(code = self.code(), error = %chain(&self), "request failed")
+            }
+            _ if status.is_server_error() => {
+                tracing::warn!(code = self.code(), error = %chain(&self), "request failed")
+            }
+            _ => {}
+        }
+
+        let possible_duplicates = match &self {
+            AppError::Duplicate(ids) => ids.clone(),
+            _ => Vec::new(),
+        };
+
+        let revision = match &self {
+            AppError::PreconditionFailed { revision } => *revision,
+            _ => None,
+        };
+
+        let version = match &self {
+            AppError::VersionMismatch { version } => *version,
+            _ => None,
+        };
+
+        let quota = match &self {
+            AppError::QuotaExceeded(overage) => Some(*overage),
+            _ => None,
         };
 
-        (status, Json(ErrorBody { error: msg })).into_response()
+        let storage = match &self {
+            AppError::StorageQuotaExceeded(overage) => Some(*overage),
+            _ => None,
+        };
+
+        let body = ErrorBody {
+            error: msg,
+            possible_duplicates,
+            revision,
+            version,
+            quota,
+            storage,
+        };
+        let mut response = (status, Json(body)).into_response();
+        response.extensions_mut().insert(ErrorCode(self.code()));
+        // Failures on our side, for the error tracker.
+        if matches!(self, AppError::Internal | AppError::Unavailable) {
+            response.extensions_mut().insert(ReportedError {
+                code: self.code(),
+                message: chain(&self),
+            });
+        }
+
+        // Tell well-behaved clients how long to back off.
+        if let AppError::RateLimited { retry_after_secs }
+        | AppError::ImportRateLimited { retry_after_secs } = self
+        {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after_secs.into());
+        }
+        // How to authenticate; see `auth`.
+        if let AppError::Unauthorized = self {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        // Load comes and goes quickly; a short pause is enough.
+        if let AppError::Overloaded = self {
+            response.headers_mut().insert(header::RETRY_AFTER, 1.into());
+        }
+        // The tag to send in `If-Match` after refetching.
+        if let Some(revision) = revision {
+            response
+                .headers_mut()
+                .insert(header::ETAG, caching::revision_tag(revision));
+        }
+        if let Some(version) = version {
+            response.headers_mut().insert(header::ETAG, caching::version_tag(version));
+        }
+
+        response
     }
 }
@@ -0,0 +1,366 @@
+//! Change notifications.
+//!
+//! # Buses
+//!
+//! Every successful create, update, and delete produces a [`TodoEvent`] on an
+//! [`EventBus`]. Any number of subscribers (streaming RPCs, the notifier) get
+//! their own stream of everything published after they subscribed.
+//!
+//! [`LocalBus`], the default, is an in-process `tokio::sync::broadcast`
+//! channel: a subscriber that falls more than [`CAPACITY`] events behind
+//! skips ahead rather than slowing writers down. It only reaches subscribers
+//! in the same process, so replicas behind a load balancer need the `redis`
+//! feature's [`redis::RedisBus`], selected by `EVENT_BUS_URL`.
+//!
+//! # Where events come from
+//!
+//! [`Publishing`] wraps the repository inside the
+//! [`TodoService`](crate::service::TodoService), so REST, GraphQL, gRPC, and
+//! tests driving `state.service().repo()` directly all publish without each caller
+//! having to remember to. Reminders are the exception: they are not
+//! writes, so the [`reminders`](crate::reminders) task publishes them itself.
+//!
+//! # Replay
+//!
+//! Subscribers only see what is published while they are subscribed. The
+//! [`replay::EventLog`] keeps recent events numbered so clients that were
+//! away can catch up.
+
+use std::{ops::Range, pin::Pin, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Mutex,
+};
+
+use crate::{
+    errors::AppError,
+    models::{
+        Attachment, Bucket, Changes, CreateTodo, ImportMode, ImportReport, NewAttachment,
+        RunMode, SearchHit, Snapshot, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+    },
+    state::TodoRepo,
+};
+
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod replay;
+
+/// Events buffered per subscriber before the slowest one starts lagging.
+pub const CAPACITY: usize = 1024;
+
+/// Something that happened to a todo.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TodoEvent {
+    Created { todo: Todo },
+    Updated { todo: Todo },
+    Deleted { id: u64 },
+    /// The todo was given to `todo.assignee`; follows the `Created` or
+    /// `Updated` event of the write that assigned it.
+    Assigned { todo: Todo },
+    /// The todo's due date passed; sent once per due date by
+    /// [`reminders`](crate::reminders).
+    Reminder { todo: Todo },
+}
+
+/// Events from one subscription, in publish order. It ends when the bus is
+/// dropped.
+pub type EventStream = Pin<Box<dyn Stream<Item = TodoEvent> + Send>>;
+
+/// Fan-out channel for [`TodoEvent`]s.
+pub trait EventBus: Send + Sync + 'static {
+    /// Sends `event` to every current subscriber. Having none is fine.
+    /// Never waits on I/O, so it is safe to call while holding locks.
+    fn publish(&self, event: TodoEvent);
+
+    /// Receives every event published from now on.
+    fn subscribe(&self) -> EventStream;
+
+    /// Subscriptions open in this process. Dropping an [`EventStream`]
+    /// releases its own, which tests use to check that clients who hang up
+    /// don't leave subscriptions behind.
+    fn subscriber_count(&self) -> usize;
+}
+
+/// [`EventBus`] reaching subscribers in this process only.
+pub struct LocalBus {
+    sender: broadcast::Sender<TodoEvent>,
+}
+
+impl Default for LocalBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus for LocalBus {
+    fn publish(&self, event: TodoEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> EventStream {
+        let receiver = self.sender.subscribe();
+        Box::pin(stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "event subscriber lagged, events skipped");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+
+    fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Repository decorator that publishes an event after each successful write.
+///
+/// Writes through it take turns, each holding `writes` until its events are
+/// published, so events go out in the order the writes were applied. Without
+/// that, an update racing a delete could announce the update after the
+/// delete.
+pub struct Publishing {
+    inner: Arc<dyn TodoRepo>,
+    events: Arc<dyn EventBus>,
+    writes: Mutex<()>,
+}
+
+impl Publishing {
+    pub fn new(inner: Arc<dyn TodoRepo>, events: Arc<dyn EventBus>) -> Self {
+        Self {
+            inner,
+            events,
+            writes: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TodoRepo for Publishing {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.inner.list().await
+    }
+
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.list_after(filter, after, limit).await
+    }
+
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.list_page(filter, offset, limit).await
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        self.inner.count(filter).await
+    }
+
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
+        let _turn = self.writes.lock().await;
+        let todo = self.inner.create(input).await?;
+        self.events.publish(TodoEvent::Created { todo: todo.clone() });
+        if todo.assignee.is_some() {
+            self.events.publish(TodoEvent::Assigned { todo: todo.clone() });
+        }
+        Ok(todo)
+    }
+
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        let _turn = self.writes.lock().await;
+        let todos = self.inner.create_many(inputs).await?;
+        for todo in &todos {
+            self.events.publish(TodoEvent::Created { todo: todo.clone() });
+            if todo.assignee.is_some() {
+                self.events.publish(TodoEvent::Assigned { todo: todo.clone() });
+            }
+        }
+        Ok(todos)
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        let assigned = matches!(input.assignee, Some(Some(_)));
+        let _turn = self.writes.lock().await;
+        let todo = self.inner.update(id, input).await?;
+        self.events.publish(TodoEvent::Updated { todo: todo.clone() });
+        if assigned {
+            self.events.publish(TodoEvent::Assigned { todo: todo.clone() });
+        }
+        Ok(todo)
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        let _turn = self.writes.lock().await;
+        let todo = self.inner.toggle(id).await?;
+        self.events.publish(TodoEvent::Updated { todo: todo.clone() });
+        Ok(todo)
+    }
+
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        let assigned: Vec<bool> = updates
+            .iter()
+            .map(|(_, input)| matches!(input.assignee, Some(Some(_))))
+            .collect();
+        let _turn = self.writes.lock().await;
+        let results = self.inner.update_many_at(revision, updates).await?;
+        for (result, assigned) in results.iter().zip(assigned) {
+            let Ok(todo) = result else { continue };
+            self.events.publish(TodoEvent::Updated { todo: todo.clone() });
+            if assigned {
+                self.events.publish(TodoEvent::Assigned { todo: todo.clone() });
+            }
+        }
+        Ok(results)
+    }
+
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        let assigned = matches!(input.assignee, Some(Some(_)));
+        let _turn = self.writes.lock().await;
+        let (todo, created) = self.inner.upsert(id, input).await?;
+        let event = if created {
+            TodoEvent::Created { todo: todo.clone() }
+        } else {
+            TodoEvent::Updated { todo: todo.clone() }
+        };
+        self.events.publish(event);
+        if assigned {
+            self.events.publish(TodoEvent::Assigned { todo: todo.clone() });
+        }
+        Ok((todo, created))
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        let _turn = self.writes.lock().await;
+        self.inner.delete(id).await?;
+        self.events.publish(TodoEvent::Deleted { id });
+        Ok(())
+    }
+
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        self.inner.version(id).await
+    }
+
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        let _turn = self.writes.lock().await;
+        self.inner.delete_if_version(id, version).await?;
+        self.events.publish(TodoEvent::Deleted { id });
+        Ok(())
+    }
+
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        self.inner.add_attachment(todo_id, input).await
+    }
+
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.inner.list_attachments(todo_id).await
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.inner.get_attachment(id).await
+    }
+
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.inner.delete_attachment(id).await
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        self.inner.search(query, limit).await
+    }
+
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        self.inner.find_similar(title, threshold).await
+    }
+
+    async fn due_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner.due_between(from, to).await
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        self.inner.mark_reminded(id, at).await
+    }
+
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        self.inner.timeseries(bucket, range).await
+    }
+
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        self.inner.changes_since(since).await
+    }
+
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        self.inner.export_all().await
+    }
+
+    /// Publishes a `Deleted` for each todo a replace removed and a `Created`
+    /// for each imported one, overwritten or not. A preview publishes
+    /// nothing.
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        let _turn = self.writes.lock().await;
+        let todos = snapshot.todos.clone();
+        let report = self.inner.import_all(snapshot, mode, run).await?;
+        if run == RunMode::Apply && report.conflicts.is_empty() {
+            for &id in &report.removed {
+                self.events.publish(TodoEvent::Deleted { id });
+            }
+            for todo in todos {
+                self.events.publish(TodoEvent::Created { todo });
+            }
+        }
+        Ok(report)
+    }
+
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        self.inner.revision().await
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        self.inner.ping().await
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+}
@@ -0,0 +1,172 @@
+//! [`EventBus`] shared between replicas through Redis pub/sub.
+//!
+//! Events are published as JSON on [`CHANNEL`]. Every replica, including the
+//! one that published, receives them back through its own subscription and
+//! fans them out to local subscribers, so all replicas see the same events in
+//! the same order.
+//!
+//! # Connection loss
+//!
+//! Two background tasks own the connections: one drains a queue of outgoing
+//! events into `PUBLISH`, the other holds the `SUBSCRIBE`. Either one
+//! reconnects with exponential backoff (from [`MIN_BACKOFF`] up to
+//! [`MAX_BACKOFF`]) when its connection drops. Outgoing events wait in the
+//! queue meanwhile, up to [`CAPACITY`]; events published to Redis while the
+//! subscriber is reconnecting are missed, as with any pub/sub consumer.
+
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use redis::{aio::MultiplexedConnection, AsyncCommands, Client};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{EventBus, EventStream, LocalBus, TodoEvent, CAPACITY};
+
+/// Redis channel carrying the events.
+pub const CHANNEL: &str = "rust-api:todo-events";
+
+/// First reconnect delay.
+pub const MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest reconnect delay.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// [`EventBus`] backed by Redis pub/sub.
+pub struct RedisBus {
+    outgoing: mpsc::Sender<String>,
+    local: Arc<LocalBus>,
+    /// Dropping this stops the subscriber task.
+    _stop: oneshot::Sender<()>,
+}
+
+impl RedisBus {
+    /// Connects to `url` (e.g. `redis://localhost:6379`) and returns once the
+    /// subscription is live, so events published afterwards are delivered.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the URL is invalid or the first subscription attempt fails;
+    /// later connection losses are retried in the background instead.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let client = Client::open(url)?;
+        let local = Arc::new(LocalBus::default());
+
+        let pubsub = resubscribe(&client).await?;
+        let (stop, stopped) = oneshot::channel();
+        tokio::spawn(subscribe(client.clone(), pubsub, Arc::clone(&local), stopped));
+
+        let (outgoing, queue) = mpsc::channel(CAPACITY);
+        tokio::spawn(publish(client, queue));
+
+        Ok(Self {
+            outgoing,
+            local,
+            _stop: stop,
+        })
+    }
+}
+
+impl EventBus for RedisBus {
+    fn publish(&self, event: TodoEvent) {
+        let payload = serde_json::to_string(&event).expect("events serialize");
+        if self.outgoing.try_send(payload).is_err() {
+            tracing::warn!("redis event queue full, event dropped");
+        }
+    }
+
+    fn subscribe(&self) -> EventStream {
+        self.local.subscribe()
+    }
+
+    fn subscriber_count(&self) -> usize {
+        self.local.subscriber_count()
+    }
+}
+
+/// Relays `CHANNEL` into `local`, resubscribing whenever the connection drops,
+/// until `stopped` fires.
+async fn subscribe(
+    client: Client,
+    mut pubsub: redis::aio::PubSub,
+    local: Arc<LocalBus>,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        {
+            let mut messages = pubsub.on_message();
+            loop {
+                let message = tokio::select! {
+                    _ = &mut stopped => return,
+                    message = messages.next() => message,
+                };
+                let Some(message) = message else {
+                    break;
+                };
+                backoff = MIN_BACKOFF;
+                let event = message
+                    .get_payload::<String>()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|payload| Ok(serde_json::from_str::<TodoEvent>(&payload)?));
+                match event {
+                    Ok(event) => local.publish(event),
+                    Err(err) => tracing::warn!(error = %err, "ignoring malformed redis event"),
+                }
+            }
+        }
+        tracing::warn!("redis subscription lost, reconnecting");
+
+        pubsub = loop {
+            tokio::select! {
+                _ = &mut stopped => return,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            match resubscribe(&client).await {
+                Ok(pubsub) => break pubsub,
+                Err(err) => tracing::warn!(error = %err, ?backoff, "redis subscribe failed"),
+            }
+        };
+        tracing::info!("redis subscription restored");
+    }
+}
+
+async fn resubscribe(client: &Client) -> redis::RedisResult<redis::aio::PubSub> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(CHANNEL).await?;
+    Ok(pubsub)
+}
+
+/// Publishes queued events in order, reconnecting as needed. An event that
+/// fails is retried on the next connection rather than skipped. Stops once
+/// the bus is dropped and the queue is drained.
+async fn publish(client: Client, mut queue: mpsc::Receiver<String>) {
+    let mut connection: Option<MultiplexedConnection> = None;
+    let mut backoff = MIN_BACKOFF;
+    while let Some(payload) = queue.recv().await {
+        loop {
+            let conn = match &mut connection {
+                Some(conn) => conn,
+                None => match client.get_multiplexed_async_connection().await {
+                    Ok(conn) => connection.insert(conn),
+                    Err(err) => {
+                        tracing::warn!(error = %err, ?backoff, "redis connect failed");
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                        continue;
+                    }
+                },
+            };
+            match conn.publish::<_, _, ()>(CHANNEL, &payload).await {
+                Ok(()) => {
+                    backoff = MIN_BACKOFF;
+                    break;
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, "redis publish failed, reconnecting");
+                    connection = None;
+                }
+            }
+        }
+    }
+}
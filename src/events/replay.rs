@@ -0,0 +1,165 @@
+//! Recent events, numbered so clients can catch up on what they missed.
+//!
+//! [`EventLog`] keeps the last `EVENT_LOG_CAPACITY` events published by this
+//! process, each with a sequence number one higher than the one before.
+//! Integrations that can't keep a connection open poll
+//! `GET /todos/events/log?after_seq=` with the last `seq` they saw.
+//! `GET /todos/events` streams the same events live, and a client that
+//! reconnects with that `seq` in `Last-Event-ID` first gets the gap replayed
+//! from the log. Replay and subscription happen under one lock, so nothing
+//! is missed or sent twice between the two.
+//!
+//! A `seq` that has already fallen out of the log is a `409` with code
+//! `resync_required`: the client must refetch the list before it follows
+//! events again. Sequence numbers start over when the process restarts and
+//! are not shared between replicas; each replica logs only the events it
+//! published.
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+use futures::{stream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::{EventBus, EventStream, TodoEvent, CAPACITY};
+use crate::{config::Config, errors::AppError};
+
+/// An event and its place in the log.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sequenced {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: TodoEvent,
+}
+
+/// Logged events followed by live ones, in `seq` order.
+pub type SequencedStream = Pin<Box<dyn Stream<Item = Sequenced> + Send>>;
+
+/// Bounded, numbered history of the events published in this process.
+pub struct EventLog {
+    ring: Mutex<Ring>,
+    live: broadcast::Sender<Sequenced>,
+    config: Arc<ArcSwap<Config>>,
+}
+
+struct Ring {
+    /// `seq` of the next event.
+    next: u64,
+    events: VecDeque<Sequenced>,
+}
+
+impl Ring {
+    /// Fails when events after `after` have already been dropped, or when
+    /// `after` was never handed out (it comes from before a restart).
+    fn check(&self, after: u64) -> Result<(), AppError> {
+        let oldest = self.events.front().map_or(self.next, |entry| entry.seq);
+        if after >= self.next || after + 1 < oldest {
+            return Err(AppError::ResyncRequired);
+        }
+        Ok(())
+    }
+
+    fn after(&self, after: u64) -> impl Iterator<Item = &Sequenced> {
+        self.events.iter().skip_while(move |entry| entry.seq <= after)
+    }
+}
+
+impl EventLog {
+    /// An empty log that keeps as many events as `config` says at the time.
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Self {
+        let (live, _) = broadcast::channel(CAPACITY);
+        Self {
+            ring: Mutex::new(Ring {
+                next: 1,
+                events: VecDeque::new(),
+            }),
+            live,
+            config,
+        }
+    }
+
+    /// Numbers `event`, appends it, and hands it to live subscribers.
+    pub fn record(&self, event: TodoEvent) {
+        let capacity = self.config.load().event_log_capacity;
+        let mut ring = self.ring.lock().expect("event log lock poisoned");
+        let entry = Sequenced {
+            seq: ring.next,
+            event,
+        };
+        ring.next += 1;
+        ring.events.push_back(entry.clone());
+        while ring.events.len() > capacity {
+            ring.events.pop_front();
+        }
+        // Sent under the lock, so `subscribe` can't miss it or see it twice.
+        let _ = self.live.send(entry);
+    }
+
+    /// Up to `limit` events after `after`, oldest first.
+    pub fn read(&self, after: u64, limit: usize) -> Result<Vec<Sequenced>, AppError> {
+        let ring = self.ring.lock().expect("event log lock poisoned");
+        ring.check(after)?;
+        Ok(ring.after(after).take(limit).cloned().collect())
+    }
+
+    /// The events after `after` followed by every later one as it happens,
+    /// or just the later ones when `after` is `None`. The stream ends if the
+    /// subscriber falls too far behind; reconnecting from the last `seq`
+    /// picks up where it stopped.
+    pub fn subscribe(&self, after: Option<u64>) -> Result<SequencedStream, AppError> {
+        let ring = self.ring.lock().expect("event log lock poisoned");
+        let replay: Vec<Sequenced> = match after {
+            Some(after) => {
+                ring.check(after)?;
+                ring.after(after).cloned().collect()
+            }
+            None => Vec::new(),
+        };
+        let receiver = self.live.subscribe();
+        drop(ring);
+
+        let live = stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(entry) => Some((entry, receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "event log subscriber lagged, closing its stream");
+                    None
+                }
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(Box::pin(stream::iter(replay).chain(live)))
+    }
+}
+
+/// [`EventBus`] that records everything it publishes in an [`EventLog`].
+pub struct Recording {
+    inner: Arc<dyn EventBus>,
+    log: Arc<EventLog>,
+}
+
+impl Recording {
+    pub fn new(inner: Arc<dyn EventBus>, log: Arc<EventLog>) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl EventBus for Recording {
+    fn publish(&self, event: TodoEvent) {
+        self.log.record(event.clone());
+        self.inner.publish(event);
+    }
+
+    fn subscribe(&self) -> EventStream {
+        self.inner.subscribe()
+    }
+
+    fn subscriber_count(&self) -> usize {
+        self.inner.subscriber_count()
+    }
+}
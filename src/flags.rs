@@ -0,0 +1,128 @@
+//! Per-request feature flags, for rolling behavior out gradually.
+//!
+//! Each request gets a [`RequestFlags`]: the flags in `FEATURE_FLAGS`, plus
+//! any the client names in `X-Feature-Flags` (comma-separated) that
+//! `CLIENT_FEATURE_FLAGS` lets clients turn on. Anything else in the header
+//! is ignored with a debug log, so a client can never enable a flag the
+//! operator hasn't opened up, and a stale client naming a retired flag still
+//! works.
+//!
+//! [`request_flags`] stores the result in the request extensions. Handlers
+//! take [`RequestFlags`] as an extractor and pass what they need on to the
+//! service; middleware further in reads the extension directly.
+
+use std::{fmt, str::FromStr};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{config::Config, state::AppState};
+
+/// Header naming the flags a client wants.
+pub const HEADER: &str = "x-feature-flags";
+
+/// A behavior that can be turned on per request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    /// Wrap responses in the `{ data, meta }` envelope.
+    Envelope,
+    /// Refuse near-duplicate creates with `409` instead of warning.
+    StrictDuplicates,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 2] = [Flag::Envelope, Flag::StrictDuplicates];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flag::Envelope => "envelope",
+            Flag::StrictDuplicates => "strict_duplicates",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Flag {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let name = raw.trim().to_ascii_lowercase();
+        Flag::ALL.into_iter().find(|flag| flag.as_str() == name).ok_or_else(|| {
+            let names: Vec<_> = Flag::ALL.iter().map(|flag| flag.as_str()).collect();
+            format!("unknown feature flag `{raw}`, expected one of: {}", names.join(", "))
+        })
+    }
+}
+
+/// The flags in effect for one request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestFlags(u8);
+
+impl RequestFlags {
+    pub fn enabled(self, flag: Flag) -> bool {
+        self.0 & flag.bit() != 0
+    }
+
+    pub fn with(self, flag: Flag) -> Self {
+        Self(self.0 | flag.bit())
+    }
+
+    /// The configured defaults plus the allowed flags named in `header`.
+    pub fn resolve(config: &Config, header: Option<&HeaderValue>) -> Self {
+        let defaults = config.feature_flags.iter().fold(Self::default(), |flags, &flag| {
+            flags.with(flag)
+        });
+        let Some(raw) = header.and_then(|value| value.to_str().ok()) else {
+            return defaults;
+        };
+
+        let names = raw.split(',').map(str::trim).filter(|name| !name.is_empty());
+        names.fold(defaults, |flags, name| match name.parse::<Flag>() {
+            Ok(flag) if config.client_feature_flags.contains(&flag) => flags.with(flag),
+            Ok(flag) => {
+                tracing::debug!(%flag, "ignoring feature flag clients may not set");
+                flags
+            }
+            Err(reason) => {
+                tracing::debug!(reason, "ignoring unknown feature flag");
+                flags
+            }
+        })
+    }
+}
+
+/// Requests that skipped [`request_flags`] (handlers called directly in
+/// tests) get no flags.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestFlags {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<RequestFlags>().copied().unwrap_or_default())
+    }
+}
+
+/// Works out the request's flags and stores them in its extensions.
+pub async fn request_flags(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let flags = RequestFlags::resolve(&state.config(), req.headers().get(HEADER));
+    req.extensions_mut().insert(flags);
+    next.run(req).await
+}
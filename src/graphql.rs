@@ -0,0 +1,210 @@
+//! GraphQL API (`graphql` feature).
+//!
+//! `POST /graphql` serves a schema over the same [`TodoRepo`](crate::state::TodoRepo)
+//! the REST routes use, so both APIs always see the same data and the same
+//! validation rules. `GET /graphql` serves the GraphQL Playground when
+//! `ENABLE_DOCS` is on and `404`s otherwise.
+//!
+//! Requests go through our own [`AppJson`] extractor rather than an
+//! integration crate, so GraphQL bodies get the same JSON/MessagePack
+//! decoding, size limit and error bodies as the REST routes.
+//!
+//! # Errors
+//!
+//! Resolvers return [`AppError`]s converted by [`graphql_error`], which keeps
+//! the REST error code under `extensions.code`:
+//!
+//! ```json
+//! { "message": "not found", "extensions": { "code": "not_found" } }
+//! ```
+//!
+//! # Read-only mode
+//!
+//! Queries and mutations share one `POST` route, so `read_only_guard` lets
+//! `/graphql` through and each mutation checks `READ_ONLY` itself.
+
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Context, EmptySubscription, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema,
+};
+use chrono::{DateTime, Utc};
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Extension, Json,
+};
+
+use crate::{
+    envelope::Bare,
+    errors::AppError,
+    models::{Color, CreateTodo, ListQuery, PageParam, Pagination, Title, Todo, UpdateTodo},
+    negotiation::AppJson,
+    state::AppState,
+};
+
+pub type TodoSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// Builds the schema; resolvers reach the repository through `state`.
+pub fn schema(state: AppState) -> TodoSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(state)
+        .finish()
+}
+
+/// `POST /graphql` - executes a query or mutation.
+pub async fn execute(
+    Extension(schema): Extension<TodoSchema>,
+    AppJson(req): AppJson<async_graphql::Request>,
+) -> impl IntoResponse {
+    // GraphQL has its own `{ data, errors }` shape; never wrap it again.
+    (Extension(Bare), Json(schema.execute(req).await))
+}
+
+/// `GET /graphql` - the interactive playground, when docs are enabled.
+pub async fn playground(State(app): State<AppState>) -> Result<Html<String>, AppError> {
+    if !app.config().enable_docs {
+        return Err(AppError::NotFound);
+    }
+    Ok(Html(playground_source(GraphQLPlaygroundConfig::new("/graphql"))))
+}
+
+/// Converts an [`AppError`] into a GraphQL error carrying the REST error code.
+pub fn graphql_error(err: AppError) -> async_graphql::Error {
+    let code = err.code();
+    async_graphql::Error::new(err.to_string()).extend_with(|_, ext| ext.set("code", code))
+}
+
+/// Same criteria as `GET /todos?done=&q=&assignee=&color=`.
+#[derive(InputObject, Default)]
+pub struct TodoFilterInput {
+    pub done: Option<bool>,
+    pub q: Option<String>,
+    /// `"none"` selects unassigned todos.
+    pub assignee: Option<String>,
+    pub color: Option<Color>,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Todos in id order. `limit`/`offset` follow the REST pagination rules.
+    async fn todos(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TodoFilterInput>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> async_graphql::Result<Vec<Todo>> {
+        let filter = filter.unwrap_or_default();
+        let query = ListQuery {
+            limit: limit.map(PageParam::from),
+            offset: offset.map(PageParam::from),
+            done: filter.done,
+            q: filter.q,
+            assignee: filter.assignee,
+            color: filter.color,
+            ..ListQuery::default()
+        };
+        let page = query.page(&state(ctx).config()).map_err(graphql_error)?.unwrap_or(Pagination {
+            limit: usize::MAX,
+            ..Pagination::default()
+        });
+        let listed = state(ctx).service().list(&query.filter(), page).await;
+        listed.map(|(todos, _)| todos).map_err(graphql_error)
+    }
+
+    async fn todo(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<Todo> {
+        state(ctx).service().get(id).await.map_err(graphql_error)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_todo(
+        &self,
+        ctx: &Context<'_>,
+        title: String,
+        description: Option<String>,
+        due: Option<DateTime<Utc>>,
+        assignee: Option<String>,
+        color: Option<Color>,
+    ) -> async_graphql::Result<Todo> {
+        let app = writable(ctx)?;
+        let title = Title::new(title).map_err(|err| graphql_error(AppError::Validation(err)))?;
+        let input = CreateTodo {
+            title,
+            description,
+            due,
+            assignee,
+            color,
+        };
+        let created = app.service().create(input, false).await;
+        created.map(|created| created.todo).map_err(graphql_error)
+    }
+
+    /// Omitted arguments are left unchanged; `assignee: null` unassigns and
+    /// `color: null` clears the color.
+    // Each Rust argument is a GraphQL argument, so the count is the schema's.
+    #[allow(clippy::too_many_arguments)]
+    async fn update_todo(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        title: Option<String>,
+        description: Option<String>,
+        done: Option<bool>,
+        due: Option<DateTime<Utc>>,
+        assignee: MaybeUndefined<String>,
+        color: MaybeUndefined<Color>,
+    ) -> async_graphql::Result<Todo> {
+        let app = writable(ctx)?;
+        let title = title.map(Title::new).transpose();
+        let title = title.map_err(|err| graphql_error(AppError::Validation(err)))?;
+        let input = UpdateTodo {
+            title,
+            description,
+            done,
+            due,
+            assignee: match assignee {
+                MaybeUndefined::Undefined => None,
+                MaybeUndefined::Null => Some(None),
+                MaybeUndefined::Value(assignee) => Some(Some(assignee)),
+            },
+            color: match color {
+                MaybeUndefined::Undefined => None,
+                MaybeUndefined::Null => Some(None),
+                MaybeUndefined::Value(color) => Some(Some(color)),
+            },
+        };
+        app.service().update(id, input).await.map_err(graphql_error)
+    }
+
+    /// Returns `true` once the todo is gone.
+    async fn delete_todo(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<bool> {
+        let app = writable(ctx)?;
+        app.service().delete(id).await.map_err(graphql_error)?;
+        Ok(true)
+    }
+
+    /// Flips `done`.
+    async fn toggle_todo(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<Todo> {
+        let app = writable(ctx)?;
+        app.service().toggle(id).await.map_err(graphql_error)
+    }
+}
+
+fn state<'a>(ctx: &Context<'a>) -> &'a AppState {
+    ctx.data_unchecked::<AppState>()
+}
+
+/// The state, unless `READ_ONLY` forbids mutations.
+fn writable<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a AppState> {
+    let app = state(ctx);
+    if app.config().read_only {
+        return Err(graphql_error(AppError::ReadOnly));
+    }
+    Ok(app)
+}
@@ -0,0 +1,277 @@
+//! gRPC API (`grpc` feature).
+//!
+//! [`TodoGrpcService`] implements `proto/todo.proto` on top of the same
+//! [`TodoRepo`](crate::state::TodoRepo) the REST routes use, so all APIs share
+//! data and validation. `main.rs` serves it on `GRPC_ADDR`, next to (not
+//! inside) the HTTP router: the HTTP middleware stack (envelope, negotiation,
+//! compression) does not apply to gRPC framing.
+//!
+//! # Status codes
+//!
+//! [`status`] maps each [`AppError`] to the closest gRPC code: `NotFound`
+//! becomes `NOT_FOUND`, validation failures `INVALID_ARGUMENT`, and so on.
+//! The REST error code travels in the `error-code` metadata entry.
+//!
+//! # Watch
+//!
+//! `Watch` forwards the [event broadcast](crate::events) to the client. A
+//! client that falls too far behind skips the events it missed rather than
+//! holding up writers. A client that disconnects releases its subscription
+//! as soon as tonic drops the response stream.
+
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use prost::Message;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    errors::AppError,
+    events::TodoEvent,
+    models::{self, Color, CreateTodo, ListQuery, PageParam, Pagination, Title, UpdateTodo},
+    state::AppState,
+    streaming,
+};
+
+/// Code generated from `proto/todo.proto`.
+pub mod proto {
+    tonic::include_proto!("todo.v1");
+}
+
+use proto::todos_server::{Todos, TodosServer};
+
+/// The `todo.v1.Todos` service.
+pub struct TodoGrpcService {
+    state: AppState,
+}
+
+impl TodoGrpcService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Wraps the service for `tonic::transport::Server::add_service`.
+    pub fn server(state: AppState) -> TodosServer<Self> {
+        TodosServer::new(Self::new(state))
+    }
+
+    fn writable(&self) -> Result<(), AppError> {
+        if self.state.config().read_only {
+            return Err(AppError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
+/// Converts an [`AppError`] into a gRPC status carrying the REST error code.
+pub fn status(err: AppError) -> Status {
+    let code = match err {
+        AppError::NotFound => tonic::Code::NotFound,
+        AppError::Validation(_) => tonic::Code::InvalidArgument,
+        AppError::InvalidId(_) => tonic::Code::InvalidArgument,
+        AppError::UnsupportedMediaType(_) => tonic::Code::InvalidArgument,
+        AppError::PayloadTooLarge => tonic::Code::ResourceExhausted,
+        AppError::RateLimited { .. } => tonic::Code::ResourceExhausted,
+        AppError::ReadOnly => tonic::Code::FailedPrecondition,
+        AppError::Unavailable => tonic::Code::Unavailable,
+        AppError::Overloaded => tonic::Code::Unavailable,
+        AppError::DeadlineExceeded => tonic::Code::DeadlineExceeded,
+        AppError::Duplicate(_) => tonic::Code::AlreadyExists,
+        AppError::ResyncRequired => tonic::Code::FailedPrecondition,
+        AppError::PreconditionFailed { .. } | AppError::VersionMismatch { .. } => {
+            tonic::Code::FailedPrecondition
+        }
+        AppError::ImportTooLarge { .. } => tonic::Code::ResourceExhausted,
+        AppError::ImportRateLimited { .. } => tonic::Code::ResourceExhausted,
+        AppError::ImportTimeout => tonic::Code::DeadlineExceeded,
+        AppError::Unauthorized => tonic::Code::Unauthenticated,
+        AppError::Forbidden | AppError::CsrfFailed => tonic::Code::PermissionDenied,
+        AppError::InvalidShareLink => tonic::Code::Unauthenticated,
+        AppError::ShareLinkExpired | AppError::ShareLinkRevoked => tonic::Code::NotFound,
+        AppError::QuotaExceeded(_) | AppError::StorageQuotaExceeded(_) => {
+            tonic::Code::ResourceExhausted
+        }
+        AppError::Internal => tonic::Code::Internal,
+    };
+    let mut status = Status::new(code, err.to_string());
+    status
+        .metadata_mut()
+        .insert("error-code", err.code().parse().expect("codes are ascii"));
+    status
+}
+
+impl From<models::Todo> for proto::Todo {
+    fn from(todo: models::Todo) -> Self {
+        Self {
+            id: todo.id,
+            title: todo.title.into(),
+            done: todo.done,
+            due: todo.due.map(|due| due.to_rfc3339()),
+            created_at: todo.created_at.to_rfc3339(),
+            updated_at: todo.updated_at.to_rfc3339(),
+            description: todo.description,
+            assignee: todo.assignee,
+            reminded_at: todo.reminded_at.map(|at| at.to_rfc3339()),
+            color: todo.color.map(|color| color.to_string()),
+            done_at: todo.done_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+impl From<TodoEvent> for proto::TodoEvent {
+    fn from(event: TodoEvent) -> Self {
+        use proto::todo_event::Event;
+
+        let event = match event {
+            TodoEvent::Created { todo } => Event::Created(todo.into()),
+            TodoEvent::Updated { todo } => Event::Updated(todo.into()),
+            TodoEvent::Deleted { id } => Event::Deleted(id),
+            TodoEvent::Assigned { todo } => Event::Assigned(todo.into()),
+            TodoEvent::Reminder { todo } => Event::Reminder(todo.into()),
+        };
+        Self { event: Some(event) }
+    }
+}
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<proto::TodoEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Todos for TodoGrpcService {
+    async fn create(
+        &self,
+        request: Request<proto::CreateRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        self.writable().map_err(status)?;
+        let request = request.into_inner();
+        let input = CreateTodo {
+            title: Title::new(request.title).map_err(AppError::Validation).map_err(status)?,
+            description: request.description,
+            due: parse_due(request.due).map_err(status)?,
+            assignee: request.assignee,
+            color: parse_color(request.color).map_err(status)?,
+        };
+        let created = self.state.service().create(input, false).await.map_err(status)?;
+        Ok(Response::new(created.todo.into()))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let todo = self
+            .state
+            .service()
+            .get(request.into_inner().id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(todo.into()))
+    }
+
+    async fn list(
+        &self,
+        request: Request<proto::ListRequest>,
+    ) -> Result<Response<proto::ListResponse>, Status> {
+        let request = request.into_inner();
+        let query = ListQuery {
+            limit: request.limit.map(saturating_usize).map(PageParam::from),
+            offset: request.offset.map(saturating_usize).map(PageParam::from),
+            done: request.done,
+            q: request.q,
+            assignee: request.assignee,
+            color: parse_color(request.color).map_err(status)?,
+            ..ListQuery::default()
+        };
+        let page = query.page(&self.state.config()).map_err(status)?.unwrap_or(Pagination {
+            limit: usize::MAX,
+            ..Pagination::default()
+        });
+        let (todos, page) = self
+            .state
+            .service()
+            .list(&query.filter(), page)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::ListResponse {
+            todos: todos.into_iter().map(Into::into).collect(),
+            total: page.total as u64,
+        }))
+    }
+
+    async fn update(
+        &self,
+        request: Request<proto::UpdateRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        self.writable().map_err(status)?;
+        let request = request.into_inner();
+        let input = UpdateTodo {
+            title: parse_title(request.title).map_err(status)?,
+            description: request.description,
+            done: request.done,
+            due: parse_due(request.due).map_err(status)?,
+            assignee: request
+                .assignee
+                .map(|assignee| Some(assignee).filter(|assignee| !assignee.is_empty())),
+            color: match request.color.as_deref() {
+                Some("") => Some(None),
+                _ => parse_color(request.color).map_err(status)?.map(Some),
+            },
+        };
+        let todo = self
+            .state
+            .service()
+            .update(request.id, input)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(todo.into()))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteRequest>,
+    ) -> Result<Response<proto::DeleteResponse>, Status> {
+        self.writable().map_err(status)?;
+        self.state
+            .service()
+            .delete(request.into_inner().id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::DeleteResponse {}))
+    }
+
+    type WatchStream = WatchStream;
+
+    async fn watch(
+        &self,
+        _request: Request<proto::WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let events = self.state.events().subscribe().map(proto::TodoEvent::from).map(Ok);
+        let events = streaming::track("watch", events, proto::TodoEvent::encoded_len);
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+fn parse_due(raw: Option<String>) -> Result<Option<DateTime<Utc>>, AppError> {
+    raw.map(|raw| {
+        DateTime::parse_from_rfc3339(&raw)
+            .map(|due| due.with_timezone(&Utc))
+            .map_err(|_| {
+                let message = format!("due must be an RFC 3339 timestamp, got `{raw}`");
+                AppError::Validation(message.into())
+            })
+    })
+    .transpose()
+}
+
+fn parse_title(raw: Option<String>) -> Result<Option<Title>, AppError> {
+    raw.map(Title::new).transpose().map_err(AppError::Validation)
+}
+
+fn parse_color(raw: Option<String>) -> Result<Option<Color>, AppError> {
+    raw.map(|raw| raw.parse().map_err(|message: String| AppError::Validation(message.into())))
+        .transpose()
+}
+
+fn saturating_usize(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
@@ -0,0 +1,240 @@
+//! Dates in the reader's language for the human-readable views.
+//!
+//! The `text/plain` and `text/html` [renderings](crate::render) are read by
+//! people, who would rather see "due tomorrow" or "vor 2 Stunden" than an
+//! RFC 3339 timestamp. [`localize`] picks a [`Locale`] from the request's
+//! `Accept-Language` (English when nothing supported is asked for) and reads
+//! the time from the [`Clock`](crate::clock::Clock) once per request, then
+//! keeps both in a task-local [`Context`] for the renderers, which never see
+//! the request. JSON and MessagePack bodies keep their RFC 3339 timestamps.
+//!
+//! Times within a week are relative ("2 hours ago", "in 3 days"), due dates
+//! are counted in calendar days ("due tomorrow"), and anything further away
+//! falls back to the date itself. Days are UTC days.
+
+use std::fmt;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Datelike, Duration, Utc};
+
+use crate::state::AppState;
+
+tokio::task_local! {
+    static CURRENT: Context;
+}
+
+/// Languages the human-readable views are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    /// The supported language with the highest `q` value in
+    /// `Accept-Language`. Ties go to the one listed first; region subtags
+    /// are ignored, and nothing supported means English.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let Some(accept) = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Locale::En;
+        };
+
+        let mut best: Option<(Locale, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let tag = params.next().unwrap_or_default();
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let language = tag.split(['-', '_']).next().unwrap_or_default();
+            let locale = match language.to_ascii_lowercase().as_str() {
+                "en" | "*" => Locale::En,
+                "de" => Locale::De,
+                _ => continue,
+            };
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((locale, q));
+            }
+        }
+
+        best.map_or(Locale::En, |(locale, _)| locale)
+    }
+}
+
+impl Locale {
+    /// The language tag, as sent in `Content-Language`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// How the request being handled wants dates shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Context {
+    pub locale: Locale,
+    /// The clock's time when the request arrived; relative dates count from
+    /// it.
+    pub now: DateTime<Utc>,
+}
+
+impl Context {
+    /// `at` relative to now: "just now", "5 minutes ago", "in 2 days", or
+    /// the date once it is a week or more away.
+    pub fn ago(&self, at: DateTime<Utc>) -> String {
+        let delta = at - self.now;
+        let span = delta.abs();
+        if span >= Duration::days(7) {
+            return self.date(at);
+        }
+        if span < Duration::minutes(1) {
+            return match self.locale {
+                Locale::En => "just now".to_string(),
+                Locale::De => "gerade eben".to_string(),
+            };
+        }
+        let (count, unit) = if span < Duration::hours(1) {
+            (span.num_minutes(), Unit::Minute)
+        } else if span < Duration::days(1) {
+            (span.num_hours(), Unit::Hour)
+        } else {
+            (span.num_days(), Unit::Day)
+        };
+        let amount = unit.amount(self.locale, count);
+        match (self.locale, delta < Duration::zero()) {
+            (Locale::En, true) => format!("{amount} ago"),
+            (Locale::En, false) => format!("in {amount}"),
+            (Locale::De, true) => format!("vor {amount}"),
+            (Locale::De, false) => format!("in {amount}"),
+        }
+    }
+
+    /// When `due` is, in calendar days from today: "due today", "due
+    /// tomorrow", "due in 3 days", or the date once it is a week or more
+    /// away.
+    pub fn due(&self, due: DateTime<Utc>) -> String {
+        let days = (due.date_naive() - self.now.date_naive()).num_days();
+        let amount = |days: i64| Unit::Day.amount(self.locale, days);
+        match (self.locale, days) {
+            (Locale::En, 0) => "due today".to_string(),
+            (Locale::En, 1) => "due tomorrow".to_string(),
+            (Locale::En, -1) => "due yesterday".to_string(),
+            (Locale::En, 2..=6) => format!("due in {}", amount(days)),
+            (Locale::En, -6..=-2) => format!("due {} ago", amount(-days)),
+            (Locale::En, _) => format!("due {}", self.date(due)),
+            (Locale::De, 0) => "heute fällig".to_string(),
+            (Locale::De, 1) => "morgen fällig".to_string(),
+            (Locale::De, -1) => "gestern fällig".to_string(),
+            (Locale::De, 2..=6) => format!("in {} fällig", amount(days)),
+            (Locale::De, -6..=-2) => format!("seit {} fällig", amount(-days)),
+            (Locale::De, _) => format!("fällig am {}", self.date(due)),
+        }
+    }
+
+    /// The day `at` falls on: "Mar 5, 2026" or "5. März 2026".
+    pub fn date(&self, at: DateTime<Utc>) -> String {
+        let month = at.month0() as usize;
+        match self.locale {
+            Locale::En => format!("{} {}, {}", EN_MONTHS[month], at.day(), at.year()),
+            Locale::De => format!("{}. {} {}", at.day(), DE_MONTHS[month], at.year()),
+        }
+    }
+}
+
+const EN_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const DE_MONTHS: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Unit {
+    /// "3 hours", "1 Tag". German counts come after `vor`, `in` and `seit`,
+    /// so plurals are dative.
+    fn amount(self, locale: Locale, count: i64) -> String {
+        let (one, many) = match (locale, self) {
+            (Locale::En, Unit::Minute) => ("minute", "minutes"),
+            (Locale::En, Unit::Hour) => ("hour", "hours"),
+            (Locale::En, Unit::Day) => ("day", "days"),
+            (Locale::De, Unit::Minute) => ("Minute", "Minuten"),
+            (Locale::De, Unit::Hour) => ("Stunde", "Stunden"),
+            (Locale::De, Unit::Day) => ("Tag", "Tagen"),
+        };
+        let word = if count == 1 { one } else { many };
+        format!("{count} {word}")
+    }
+}
+
+/// The request's [`Context`], or English and the system time outside one.
+pub fn current() -> Context {
+    CURRENT.try_with(|context| *context).unwrap_or(Context {
+        locale: Locale::En,
+        now: Utc::now(),
+    })
+}
+
+/// Runs `future` with `context` visible through [`current`].
+pub async fn scope<F: std::future::Future>(context: Context, future: F) -> F::Output {
+    CURRENT.scope(context, future).await
+}
+
+/// Negotiates the request's [`Context`] for the renderers. Human-readable
+/// responses say which language they are in with `Content-Language`, and
+/// `Vary: Accept-Language` keeps caches from mixing them up.
+pub async fn localize(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let context = Context {
+        locale: Locale::from_headers(req.headers()),
+        now: state.clock().now(),
+    };
+    let mut res = scope(context, next.run(req)).await;
+
+    let readable = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain") || value.starts_with("text/html"));
+    if readable {
+        let headers = res.headers_mut();
+        let language = HeaderValue::from_static(context.locale.tag());
+        headers.insert(header::CONTENT_LANGUAGE, language);
+        headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    }
+    res
+}
@@ -0,0 +1,80 @@
+//! iCalendar (RFC 5545) export.
+//!
+//! `GET /todos/calendar.ics` lets calendar apps subscribe to todos that have a
+//! due date. Each one becomes a `VTODO` whose `UID` is derived from the todo
+//! id, so re-importing the feed updates entries instead of duplicating them.
+//!
+//! # Format rules
+//!
+//! - Lines end in CRLF and are folded at 75 octets, continuing on the next
+//!   line after a single space. Folding never splits a UTF-8 character.
+//! - In text values, backslash, `;` and `,` are backslash-escaped and
+//!   newlines become a literal `\n`. [`escape_text`] does this.
+//! - Timestamps are UTC, written as `20240501T170000Z`.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::Todo;
+
+const PRODID: &str = "-//rust-api//todos//EN";
+
+/// Longest content line, in octets, before it must be folded.
+const MAX_LINE: usize = 75;
+
+/// Renders `todos` as a `VCALENDAR`. Todos without a due date are skipped.
+/// `now` becomes every component's `DTSTAMP`.
+pub fn calendar(todos: &[Todo], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, &format!("PRODID:{PRODID}"));
+    for todo in todos {
+        let Some(due) = todo.due else { continue };
+        line(&mut out, "BEGIN:VTODO");
+        line(&mut out, &format!("UID:todo-{}@rust-api", todo.id));
+        line(&mut out, &format!("DTSTAMP:{}", timestamp(now)));
+        line(&mut out, &format!("SUMMARY:{}", escape_text(&todo.title)));
+        line(&mut out, &format!("DUE:{}", timestamp(due)));
+        let status = if todo.done { "COMPLETED" } else { "NEEDS-ACTION" };
+        line(&mut out, &format!("STATUS:{status}"));
+        line(&mut out, "END:VTODO");
+    }
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Escapes a TEXT value (RFC 5545 section 3.3.11).
+pub fn escape_text(raw: &str) -> String {
+    let mut escaped = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Appends one content line, folded and CRLF-terminated.
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            // The leading space counts towards the continuation line.
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,201 @@
+//! How the repository picks ids for new todos.
+//!
+//! `ID_STRATEGY` selects one of three [`IdGenerator`]s:
+//!
+//! - `sequential` (the default): 1, 2, 3, ... Compact, but it reveals how many
+//!   todos exist and collides as soon as two replicas share a store.
+//! - `snowflake`: milliseconds since 2024-01-01 in the top 41 bits, then the
+//!   10-bit `NODE_ID`, then a 12-bit sequence. Ids from one node grow with
+//!   time, and replicas with distinct node ids never collide.
+//! - `random`: uniformly random 63-bit ids.
+//!
+//! Snowflake and random ids go past 2^53, so JavaScript clients must not
+//! parse them as plain numbers. A generator may hand out an id that is
+//! already taken, for example one a client chose with `PUT /todos/:id`; the
+//! repository then asks again, up to [`ATTEMPTS`] times.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{config::Config, errors::AppError};
+
+/// How many ids the repository tries before giving up on a create.
+pub const ATTEMPTS: usize = 8;
+
+/// Source of ids for new todos.
+pub trait IdGenerator: Send + Sync + 'static {
+    /// A new id, at least 1. Fails only when the generator is exhausted.
+    fn next_id(&self) -> Result<u64, AppError>;
+
+    /// Tells the generator `id` was taken some other way, such as a
+    /// client-chosen id.
+    fn observe(&self, id: u64) {
+        let _ = id;
+    }
+
+    /// The id [`next_id`](Self::next_id) would hand out now, for generators
+    /// whose ids follow from their state; carried over by exports.
+    fn peek(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The `ID_STRATEGY` values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdStrategy {
+    #[default]
+    Sequential,
+    Snowflake,
+    Random,
+}
+
+impl FromStr for IdStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sequential" => Ok(Self::Sequential),
+            "snowflake" => Ok(Self::Snowflake),
+            "random" => Ok(Self::Random),
+            _ => Err(format!(
+                "unknown id strategy `{value}`, expected sequential, snowflake, or random"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IdStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sequential => "sequential",
+            Self::Snowflake => "snowflake",
+            Self::Random => "random",
+        })
+    }
+}
+
+/// The generator `config` selects.
+pub fn from_config(config: &Config) -> Arc<dyn IdGenerator> {
+    match config.id_strategy {
+        IdStrategy::Sequential => Arc::new(Sequential::default()),
+        IdStrategy::Snowflake => Arc::new(Snowflake::new(config.node_id)),
+        IdStrategy::Random => Arc::new(Random::default()),
+    }
+}
+
+/// 1, 2, 3, ..., continuing above any id it [observes](IdGenerator::observe).
+#[derive(Debug, Default)]
+pub struct Sequential {
+    last: AtomicU64,
+}
+
+impl IdGenerator for Sequential {
+    fn next_id(&self) -> Result<u64, AppError> {
+        self.last
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| last.checked_add(1))
+            .map(|last| last + 1)
+            // Only reachable when a client chose the largest possible id.
+            .map_err(|_| AppError::Internal)
+    }
+
+    fn observe(&self, id: u64) {
+        self.last.fetch_max(id, Ordering::Relaxed);
+    }
+
+    fn peek(&self) -> Option<u64> {
+        self.last.load(Ordering::Relaxed).checked_add(1)
+    }
+}
+
+/// Bits of a snowflake id holding the node id.
+pub const NODE_BITS: u32 = 10;
+/// Bits of a snowflake id holding the per-millisecond sequence.
+pub const SEQUENCE_BITS: u32 = 12;
+/// Largest `NODE_ID`.
+pub const MAX_NODE_ID: u64 = (1 << NODE_BITS) - 1;
+const TIMESTAMP_BITS: u32 = 63 - NODE_BITS - SEQUENCE_BITS;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// 2024-01-01T00:00:00Z, the zero of snowflake timestamps.
+pub const SNOWFLAKE_EPOCH: Duration = Duration::from_secs(1_704_067_200);
+
+/// Time-ordered ids unique per node: timestamp, node id, sequence.
+#[derive(Debug)]
+pub struct Snowflake {
+    node: u64,
+    /// Millisecond and sequence of the last id.
+    last: Mutex<(u64, u64)>,
+}
+
+impl Snowflake {
+    /// # Panics
+    ///
+    /// When `node` is above [`MAX_NODE_ID`]; config loading rejects those.
+    pub fn new(node: u64) -> Self {
+        assert!(node <= MAX_NODE_ID, "node id {node} is above {MAX_NODE_ID}");
+        Self {
+            node,
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    fn now() -> u64 {
+        let since_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH + SNOWFLAKE_EPOCH)
+            .unwrap_or_default();
+        since_epoch.as_millis().try_into().unwrap_or(u64::MAX)
+    }
+}
+
+impl IdGenerator for Snowflake {
+    fn next_id(&self) -> Result<u64, AppError> {
+        let now = Self::now();
+        let mut last = self.last.lock().expect("snowflake lock poisoned");
+        let (millis, sequence) = *last;
+        // A clock that steps back keeps using the last millisecond, and a
+        // millisecond that runs out of sequence numbers borrows the next one,
+        // so ids never repeat or go down.
+        let next = if now > millis {
+            (now, 0)
+        } else if sequence < MAX_SEQUENCE {
+            (millis, sequence + 1)
+        } else {
+            (millis + 1, 0)
+        };
+        if next.0 >> TIMESTAMP_BITS != 0 {
+            return Err(AppError::Internal);
+        }
+        *last = next;
+        Ok(next.0 << (NODE_BITS + SEQUENCE_BITS) | self.node << SEQUENCE_BITS | next.1)
+    }
+}
+
+/// Uniformly random ids below 2^63.
+#[derive(Debug, Default)]
+pub struct Random {
+    /// Keyed randomly per process, so hashing a counter gives random bits.
+    keys: RandomState,
+    counter: AtomicU64,
+}
+
+impl IdGenerator for Random {
+    fn next_id(&self) -> Result<u64, AppError> {
+        loop {
+            let mut hasher = self.keys.build_hasher();
+            hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+            let id = hasher.finish() >> 1;
+            if id != 0 {
+                return Ok(id);
+            }
+        }
+    }
+}
@@ -1,49 +1,173 @@
 //! High-level application wiring.
 //!
 //! `lib.rs` exposes the Axum router so integration tests can instantiate the
-//! full stack without spinning up a TCP listener.
+//! full stack without spinning up a TCP listener. Embedders serving it from a
+//! hyper server of their own use [`app_with_connect_info`] instead, so the
+//! access log and the rate limiter see each client's address.
 //!
 //! # Middleware (Tower)
 //!
 //! Axum is built on top of `tower`, a library for modular networking components.
 //! "Layers" allow us to wrap our application with cross-cutting concerns like:
-//! - **Compression**: Gzip/Brotli responses automatically.
+//! - **Compression**: Gzip/Brotli/Zstd responses as configured by
+//!   `COMPRESSION_*`, and gzip/deflate request bodies are decompressed before
+//!   extractors see them.
 //! - **CORS**: Allow/deny requests from different origins (e.g., frontend apps).
 //! - **Tracing**: Log every incoming request and outgoing response.
 
+pub mod access_log;
+pub mod atom;
+pub mod attachments;
+pub mod audit;
+pub mod auth;
+pub mod budgets;
+pub mod body_log;
+pub mod caching;
+pub mod clock;
+pub mod client;
+pub mod coalesce;
+pub mod collation;
+pub mod compression;
 pub mod config;
+pub mod deadline;
+pub mod deprecation;
+pub mod duplicates;
+pub mod envelope;
 pub mod errors;
+pub mod events;
+pub mod flags;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod i18n;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod ical;
+pub mod ids;
+pub mod list_cache;
+pub mod markdown;
+pub mod metrics;
+pub mod middleware;
 pub mod models;
+pub mod negotiation;
+#[cfg(feature = "email")]
+pub mod notify;
+pub mod outbox;
+pub mod poll;
+pub mod preflight;
+pub mod preferences;
+pub mod pretty;
+pub mod projection;
+pub mod query;
+pub mod quota;
+pub mod rate_limit;
+pub mod ready;
+pub mod reload;
+pub mod reminders;
+pub mod render;
+pub mod reporting;
 pub mod routes;
+pub mod search;
+pub mod service;
+pub mod sessions;
+pub mod sharing;
+pub mod startup;
 pub mod state;
+pub mod stats;
+pub mod streaming;
+pub mod supervisor;
+pub mod telemetry;
+pub mod templates;
+pub mod timestamps;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "ui")]
+pub mod ui;
 
-use axum::{routing::get, Router};
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, DefaultBodyLimit},
+    middleware::{from_fn, from_fn_with_state},
+    Router,
+};
 use tower_http::{
-    compression::CompressionLayer,
-    cors::CorsLayer,
+    decompression::RequestDecompressionLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::TraceLayer,
 };
 
 pub use state::AppState;
 
+/// Largest request body accepted, measured *after* decompression so a small
+/// gzip bomb can't expand past it.
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
 pub fn app(state: AppState) -> Router {
-    // Each call to `route` returns a new router, so we can keep chaining.
-    Router::new()
-    .route("/health", get(routes::health))
-        .route(
-            "/todos",
-            get(routes::list_todos).post(routes::create_todo),
-        )
-        .route(
-            "/todos/:id",
-            get(routes::get_todo)
-                .put(routes::update_todo)
-                .delete(routes::delete_todo),
-        )
+    // Every route, its limits and the deprecation of the unprefixed API come
+    // from the manifest, which refuses to build with conflicting routes.
+    let router = routes::manifest()
+        .router(&state)
         // Layers run from bottom to top; we build them here so every handler
-        // benefits from compression, permissive CORS, and request tracing.
-        .with_state(state)
-        .layer(CompressionLayer::new())
-        .layer(CorsLayer::permissive())
-        .layer(TraceLayer::new_for_http())
+        // benefits from request decompression, the read-only guard, the route
+        // policies, the rate limit, request deadlines, error reporting, the
+        // optional response envelope, per-request feature flags, negotiated
+        // error bodies, the reader's locale, optional pretty-printing,
+        // slow-request detection, ETags, exact Content-Length, optional body
+        // logging, compression, per-encoding ETags, caching headers,
+        // trailing-slash redirects, CORS, access logging, and request tracing.
+        // The request id is assigned first so every layer below can see it.
+        .with_state(state.clone())
+        // Extractors read the already-decompressed body, so the limit counts
+        // inflated bytes.
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .layer(RequestDecompressionLayer::new())
+        .layer(from_fn(middleware::content_encoding))
+        .layer(from_fn_with_state(state.clone(), middleware::read_only_guard))
+        .layer(from_fn_with_state(state.clone(), auth::authorize))
+        .layer(from_fn_with_state(state.clone(), middleware::rate_limit))
+        .layer(from_fn_with_state(state.clone(), deadline::deadline))
+        .layer(from_fn_with_state(state.clone(), reporting::report_errors))
+        .layer(from_fn_with_state(state.clone(), envelope::envelope))
+        .layer(from_fn_with_state(state.clone(), flags::request_flags))
+        .layer(from_fn(negotiation::negotiate_errors))
+        .layer(from_fn_with_state(state.clone(), i18n::localize))
+        .layer(from_fn_with_state(state.clone(), pretty::pretty))
+        .layer(from_fn_with_state(state.clone(), middleware::slow_requests))
+        .layer(from_fn(caching::etag))
+        .layer(from_fn(middleware::content_length))
+        .layer(from_fn_with_state(state.clone(), body_log::log_bodies))
+        .layer(compression::layer(&state))
+        .layer(from_fn(caching::encoding_tags))
+        .layer(from_fn(caching::cache_headers))
+        .layer(from_fn(middleware::trailing_slash))
+        .layer(middleware::cors(&state))
+        .layer(from_fn_with_state(state, access_log::access_log))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
+
+    // Axum only adds `Allow` to a `405` once every layer above has run, so
+    // plain `OPTIONS` requests are answered from one level further out.
+    Router::new()
+        .fallback_service(router)
+        .layer(from_fn(middleware::allow))
+}
+
+/// [`app`] as a make-service that gives every request its connection's peer
+/// address, as [`startup::run`] serves it. The access log records the
+/// client IP from it and the rate limits count per IP. Requests without
+/// connect info, such as [`app`] driven through `oneshot`, are served all
+/// the same: they are logged without an IP and share one rate-limit bucket.
+pub fn app_with_connect_info(
+    state: AppState,
+) -> IntoMakeServiceWithConnectInfo<Router, SocketAddr> {
+    app_with_connect_info_as(state)
+}
+
+/// Like [`app_with_connect_info`], with connect info of any type the server
+/// can produce, such as the peer of a Unix socket. Only a [`SocketAddr`]
+/// carries an IP, so with anything else requests are logged and rate
+/// limited as if there were no connect info.
+pub fn app_with_connect_info_as<C>(state: AppState) -> IntoMakeServiceWithConnectInfo<Router, C> {
+    app(state).into_make_service_with_connect_info::<C>()
 }
@@ -0,0 +1,227 @@
+//! Reusing list pages while the collection stays the same.
+//!
+//! Dashboards ask for the same filtered, sorted page every few seconds, and
+//! each time [`TodoService::list`](crate::service::TodoService::list) would
+//! walk the repository again. With `LIST_CACHE_ENTRIES` above zero the
+//! service keeps the pages it read in a [`ListCache`], keyed by the filter,
+//! the order, and the page, and remembers the collection revision each was
+//! read at. A page is served again only while the repository still reports
+//! that revision, so every write invalidates the whole cache without being
+//! told about it. Repositories that don't track a revision are never cached.
+//!
+//! Each page also keeps the JSON and MessagePack bodies it was served as,
+//! with their `ETag`s, so a hit under the same format and fields is written
+//! out without serializing or hashing the todos again. Text and HTML are
+//! rendered in the reader's locale and are always rendered afresh.
+//!
+//! The least recently used page goes first once the cache is full. Hits and
+//! misses are counted in `list_cache_hits_total` and
+//! `list_cache_misses_total`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use arc_swap::ArcSwap;
+use axum::{body::Bytes, http::HeaderValue};
+
+use crate::{
+    caching,
+    config::Config,
+    errors::AppError,
+    metrics::Metrics,
+    collation::Collation,
+    models::{SortField, SortOrder, Todo, TodoFilter},
+    negotiation::Format,
+    projection::{Projected, Projection},
+};
+
+/// What a cached page was read for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ListKey {
+    filter: TodoFilter,
+    sort: SortField,
+    order: SortOrder,
+    collation: Collation,
+    offset: usize,
+    limit: usize,
+}
+
+impl ListKey {
+    /// The key for one page. Asking for the same ids in another order or
+    /// more than once lists the same todos, so those share an entry.
+    pub fn new(
+        filter: &TodoFilter,
+        sort: SortField,
+        order: SortOrder,
+        collation: Collation,
+        offset: usize,
+        limit: usize,
+    ) -> Self {
+        let mut filter = filter.clone();
+        if let Some(ids) = filter.ids.as_mut() {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+        Self {
+            filter,
+            sort,
+            order,
+            collation,
+            offset,
+            limit,
+        }
+    }
+}
+
+/// A page as the repository returned it.
+#[derive(Debug)]
+pub struct CachedPage {
+    pub todos: Vec<Todo>,
+    /// Todos matching the filter, not just this page.
+    pub total: usize,
+    encoded: Mutex<Encodings>,
+}
+
+type Encodings = HashMap<(Format, Option<Projection>), Encoded>;
+
+/// A page serialized in one format, with the `ETag` of those bytes.
+#[derive(Debug, Clone)]
+pub struct Encoded {
+    pub bytes: Bytes,
+    pub etag: HeaderValue,
+}
+
+impl CachedPage {
+    pub fn new(todos: Vec<Todo>, total: usize) -> Self {
+        Self {
+            todos,
+            total,
+            encoded: Mutex::default(),
+        }
+    }
+
+    /// The page as `format` with only `fields`, serialized the first time it
+    /// is asked for. `None` for formats that depend on the reader.
+    pub fn encoded(
+        &self,
+        format: Format,
+        fields: Option<Projection>,
+    ) -> Result<Option<Encoded>, AppError> {
+        if let Some(encoded) = self.lock().get(&(format, fields)) {
+            return Ok(Some(encoded.clone()));
+        }
+        let body = Projected::new(&self.todos, fields);
+        let bytes = match format {
+            Format::Json => serde_json::to_vec(&body).map_err(|err| {
+                tracing::error!(error = %err, "failed to encode json response");
+                AppError::Internal
+            })?,
+            // `to_vec_named`, as `Negotiated` encodes it.
+            Format::MsgPack => rmp_serde::to_vec_named(&body).map_err(|err| {
+                tracing::error!(error = %err, "failed to encode msgpack response");
+                AppError::Internal
+            })?,
+            Format::Text | Format::Html => return Ok(None),
+        };
+        let encoded = Encoded {
+            etag: caching::body_tag(&bytes),
+            bytes: Bytes::from(bytes),
+        };
+        // Two readers racing here encode the same bytes; either copy will do.
+        self.lock().insert((format, fields), encoded.clone());
+        Ok(Some(encoded))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Encodings> {
+        self.encoded.lock().expect("encoded page lock poisoned")
+    }
+}
+
+struct Entry {
+    revision: u64,
+    page: Arc<CachedPage>,
+    /// Tick of the last lookup that returned this entry.
+    used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<ListKey, Entry>,
+    tick: u64,
+}
+
+/// Bounded LRU of list pages, each tagged with its revision.
+pub struct ListCache {
+    entries: Mutex<Entries>,
+    config: Arc<ArcSwap<Config>>,
+    metrics: Arc<Metrics>,
+}
+
+impl ListCache {
+    /// An empty cache holding as many pages as `config` says at the time.
+    pub fn new(config: Arc<ArcSwap<Config>>, metrics: Arc<Metrics>) -> Self {
+        Self {
+            entries: Mutex::default(),
+            config,
+            metrics,
+        }
+    }
+
+    /// Whether pages are cached at all.
+    pub fn enabled(&self) -> bool {
+        self.config.load().list_cache_entries > 0
+    }
+
+    /// The page stored for `key` if it was read at `revision`, counting the
+    /// lookup as a hit or a miss.
+    pub fn get(&self, key: &ListKey, revision: u64) -> Option<Arc<CachedPage>> {
+        let mut entries = self.entries.lock().expect("list cache lock poisoned");
+        entries.tick += 1;
+        let tick = entries.tick;
+        let hit = entries
+            .map
+            .get_mut(key)
+            .filter(|entry| entry.revision == revision)
+            .map(|entry| {
+                entry.used = tick;
+                Arc::clone(&entry.page)
+            });
+        match hit {
+            Some(_) => self.metrics.list_cache_hits_total.inc(),
+            None => self.metrics.list_cache_misses_total.inc(),
+        }
+        hit
+    }
+
+    /// Stores `page`, read for `key` at `revision`, evicting the least
+    /// recently used pages past `LIST_CACHE_ENTRIES`.
+    pub fn put(&self, key: ListKey, revision: u64, page: Arc<CachedPage>) {
+        let capacity = self.config.load().list_cache_entries;
+        let mut entries = self.entries.lock().expect("list cache lock poisoned");
+        entries.tick += 1;
+        let used = entries.tick;
+        entries.map.insert(
+            key,
+            Entry {
+                revision,
+                page,
+                used,
+            },
+        );
+        // A linear scan, but only after a miss, which has just paid for a
+        // repository read.
+        while entries.map.len() > capacity {
+            let oldest = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => entries.map.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
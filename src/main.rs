@@ -3,45 +3,243 @@
 //! Keeping the bulk of our logic inside `lib.rs` means the `main` function just
 //! wires up logging, state, and graceful shutdown.
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
-use axum::serve;
-use rust_api::{app, AppState};
-use tokio::net::TcpListener;
-use tracing_subscriber::{fmt, EnvFilter};
+use tokio_util::sync::CancellationToken;
+use rust_api::{
+    config::Config,
+    events::{EventBus, LocalBus},
+    ids,
+    outbox::{self, HttpSender, WebhookSender},
+    preflight,
+    rate_limit::{RateLimitStore, RateLimiter},
+    ready,
+    reload::Reloader,
+    reminders,
+    reporting::ErrorReporter,
+    startup,
+    supervisor::supervise,
+    state::{in_memory_repo, in_memory_repo_with_ids},
+    telemetry, AppState,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Loading `.env` files locally keeps credentials out of the shell session.
     dotenvy::dotenv().ok();
 
+    if check_requested() {
+        return check().await;
+    }
+
     // Load configuration from the environment.
     // This will fail fast if required variables are missing.
-    let config = rust_api::config::Config::from_env()?;
+    let config = Config::from_env()?;
 
-    // Initialize the tracing subscriber for logging.
-    // `EnvFilter` uses the `RUST_LOG` variable to determine what to log.
-    let env_filter = EnvFilter::new(&config.rust_log);
+    // Initialize the tracing subscriber for logging. The guard flushes any
+    // buffered spans when `main` returns.
+    let (log_filter, _telemetry) = telemetry::init(&config)?;
 
-    fmt().with_env_filter(env_filter).compact().init();
+    config.log_summary();
+    for warning in config.warnings() {
+        tracing::warn!("{warning}");
+    }
 
-    let state = AppState::new_in_memory();
-    let app = app(state);
+    #[cfg(feature = "grpc")]
+    let grpc_addr = config.grpc_addr;
+    #[cfg(feature = "email")]
+    let notifier = rust_api::notify::Notifier::from_config(&config)?;
+    let events = event_bus(&config).await?;
+    let rate_limits = rate_limit_store(&config)?;
+    let repo = in_memory_repo_with_ids(ids::from_config(&config));
+    let mut state =
+        AppState::with_repo_and_events(repo, events).with_rate_limit_store(rate_limits);
+    if let Some(reporter) = error_reporter(&config)? {
+        state = state.with_error_reporter(reporter);
+    }
+    let outbox_path = config.outbox_path.clone();
+    let state = state.with_config(config);
+    if let Some(path) = outbox_path {
+        let queued = state.outbox().load(&path)?;
+        tracing::info!(path = %path.display(), queued, "loaded the webhook outbox");
+    }
 
-    tracing::info!(addr = %config.server_addr, "starting server");
+    // Everything stops once the state shuts down: on a signal, or when a
+    // failed task asks for it under `ON_TASK_FAILURE=shutdown`.
+    let stopping = state.shutting_down().clone();
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            wait_for_signal().await;
+            state.begin_shutdown();
+        }
+    });
 
-    // `TcpListener` + `serve` gives us finer control over graceful shutdown.
-    let listener = TcpListener::bind(config.server_addr).await?;
-    serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    #[cfg(feature = "grpc")]
+    let grpc = tokio::spawn(serve_grpc(grpc_addr, state.clone()));
+
+    // Background tasks are restarted or shut the server down when they
+    // fail; see `supervisor`.
+    let reminders = tokio::spawn(supervise(state.clone(), "reminders", false, {
+        let (state, stopping) = (state.clone(), stopping.clone());
+        move || reminders::run(state.clone(), stopping.clone().cancelled_owned())
+    }));
+    // Webhooks are how integrations hear about changes, so the server isn't
+    // ready without their dispatcher.
+    tokio::spawn(supervise(state.clone(), "outbox", true, {
+        let (state, stopping) = (state.clone(), stopping.clone());
+        let sender: Arc<dyn WebhookSender> = Arc::new(HttpSender);
+        move || outbox::run(state.clone(), Arc::clone(&sender), stopping.clone().cancelled_owned())
+    }));
+    #[cfg(feature = "email")]
+    if let Some(notifier) = notifier {
+        tracing::info!("sending email notifications");
+        let notifier = Arc::new(notifier);
+        tokio::spawn(supervise(state.clone(), "notifier", false, {
+            let (state, stopping) = (state.clone(), stopping.clone());
+            move || {
+                let notifier = Arc::clone(&notifier);
+                let events = state.events().subscribe();
+                let stopping = stopping.clone();
+                async move { notifier.serve(events, stopping.cancelled()).await }
+            }
+        }));
+    }
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(Reloader::new(state.clone()).with_log_filter(log_filter)));
+    // Without SIGHUP nothing can trigger a reload.
+    #[cfg(not(unix))]
+    drop(log_filter);
+
+    // Binds only once the repository answers; see `startup`.
+    startup::run(state, shutdown_signal(stopping)).await?;
+
+    #[cfg(feature = "grpc")]
+    grpc.await??;
+    reminders.await?;
+
+    Ok(())
+}
+
+/// `--check` on the command line or `APP_CHECK=1|true` in the environment.
+fn check_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--check")
+        || std::env::var("APP_CHECK").is_ok_and(|value| matches!(value.trim(), "1" | "true"))
+}
+
+/// Runs the [`preflight`] checks, prints one line per check, and exits with
+/// status 1 if any failed. Nothing is served.
+async fn check() -> Result<()> {
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            println!("FAIL  config: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    println!("ok    config");
+    for warning in config.warnings() {
+        println!("warn  {warning}");
+    }
+
+    let report = preflight::run(&config, in_memory_repo().as_ref()).await;
+    print!("{report}");
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
+/// The bus selected by `EVENT_BUS_URL`, or an in-process one.
+async fn event_bus(config: &Config) -> Result<Arc<dyn EventBus>> {
+    #[cfg(feature = "redis")]
+    if let Some(url) = &config.event_bus_url {
+        tracing::info!("sharing events through redis");
+        let bus = rust_api::events::redis::RedisBus::connect(url.expose()).await?;
+        return Ok(Arc::new(bus));
+    }
+    #[cfg(not(feature = "redis"))]
+    let _ = config;
+    Ok(Arc::new(LocalBus::default()))
+}
+
+/// The rate limit counters selected by `RATE_LIMIT_URL`, or in-process ones.
+fn rate_limit_store(config: &Config) -> Result<Arc<dyn RateLimitStore>> {
+    #[cfg(feature = "redis")]
+    if let Some(url) = &config.rate_limit_url {
+        tracing::info!("counting rate limits in redis");
+        let store = rust_api::rate_limit::redis::RedisRateLimitStore::new(url.expose())?;
+        return Ok(Arc::new(store));
+    }
+    #[cfg(not(feature = "redis"))]
+    let _ = config;
+    Ok(Arc::new(RateLimiter::default()))
+}
+
+fn error_reporter(config: &Config) -> Result<Option<Arc<dyn ErrorReporter>>> {
+    #[cfg(feature = "error-reporting")]
+    if let Some(dsn) = &config.sentry_dsn {
+        tracing::info!("reporting server errors to sentry");
+        let reporter = rust_api::reporting::sentry::SentryReporter::new(dsn.expose())?;
+        return Ok(Some(Arc::new(reporter)));
+    }
+    #[cfg(not(feature = "error-reporting"))]
+    let _ = config;
+    Ok(None)
+}
+
+/// Serves the gRPC API until the process is asked to stop.
+#[cfg(feature = "grpc")]
+async fn serve_grpc(addr: std::net::SocketAddr, state: AppState) -> Result<()> {
+    use rust_api::grpc::TodoGrpcService;
+
+    tracing::info!(%addr, "starting grpc server");
+    let stopping = state.shutting_down().clone();
+    tonic::transport::Server::builder()
+        .add_service(TodoGrpcService::server(state))
+        .serve_with_shutdown(addr, async move {
+            stopping.cancelled().await;
+            tracing::info!("grpc server draining");
+        })
+        .await?;
     Ok(())
 }
 
-/// Waits for Ctrl+C (or SIGTERM on Unix) so we can exit cleanly.
-async fn shutdown_signal() {
+/// Re-applies configuration every time the process receives SIGHUP.
+#[cfg(unix)]
+async fn reload_on_sighup(reloader: Reloader) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to install SIGHUP handler, reload disabled");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        tracing::info!("SIGHUP received, reloading configuration");
+        if let Err(err) = reloader.reload_from_env() {
+            tracing::error!(error = format!("{err:#}"), "config reload rejected");
+        }
+    }
+}
+
+/// Waits for `stopping`, cancelled on Ctrl+C (or SIGTERM on Unix), so we can
+/// exit cleanly.
+async fn shutdown_signal(stopping: CancellationToken) {
+    stopping.cancelled().await;
+    ready::stopping();
+
+    tracing::warn!("shutdown signal received, waiting 200ms...");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+}
+
+/// Resolves on the first Ctrl+C or SIGTERM.
+async fn wait_for_signal() {
     use tokio::signal;
 
     let ctrl_c = async {
@@ -64,7 +262,4 @@ async fn shutdown_signal() {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
-
-    tracing::warn!("shutdown signal received, waiting 200ms...");
-    tokio::time::sleep(Duration::from_millis(200)).await;
 }
@@ -0,0 +1,387 @@
+//! Prometheus metrics.
+//!
+//! Each [`AppState`](crate::AppState) owns its own `Registry` rather than using
+//! the process-wide default. That keeps tests isolated: two routers built in
+//! the same test binary never see each other's counters. `GET /metrics`
+//! renders the registry in the Prometheus text format.
+//!
+//! # Repository metrics
+//!
+//! [`Metered`] wraps the repository itself, below every other decorator, so
+//! `repo_op_duration_seconds` measures the store alone. Comparing it with
+//! the request latency tells a slow handler from a slow store. Each series
+//! is labelled with the operation (the `TodoRepo` method) and the backend
+//! (`memory`, or whatever [`TodoRepo::backend`] reports), and failures count
+//! in `repo_errors_total` by error code.
+//!
+//! The histogram buckets come from `REPO_LATENCY_BUCKETS` and are fixed once
+//! the first repository call registers them.
+
+use std::{future::Future, ops::Range, sync::Arc, sync::OnceLock, time::Instant};
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::{
+    config::Config,
+    errors::AppError,
+    models::{
+        Attachment, Bucket, Changes, CreateTodo, ImportMode, ImportReport, NewAttachment,
+        RunMode, SearchHit, Snapshot, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+    },
+    state::TodoRepo,
+};
+
+pub struct Metrics {
+    registry: Registry,
+    /// Requests that exceeded their latency budget, by route template.
+    pub slow_requests_total: IntCounterVec,
+    /// Requests to [legacy routes](crate::deprecation), by route template and
+    /// client.
+    pub deprecated_requests_total: IntCounterVec,
+    /// List pages served from the [list cache](crate::list_cache).
+    pub list_cache_hits_total: IntCounter,
+    /// List pages the list cache had to read from the repository.
+    pub list_cache_misses_total: IntCounter,
+    /// [Supervised](crate::supervisor) background task failures, by task.
+    pub task_failures_total: IntCounterVec,
+    /// Bytes of attachment files stored, refreshed on every scrape.
+    pub attachment_storage_bytes: IntGauge,
+    /// Attachment files stored, refreshed on every scrape.
+    pub attachment_storage_files: IntGauge,
+    /// Uploads an [attachment quota](crate::attachments#quotas) refused, by
+    /// `scope` (`todo` or `total`).
+    pub attachment_quota_rejections_total: IntCounterVec,
+    repo: OnceLock<RepoMetrics>,
+}
+
+/// Latency and failures of repository calls.
+pub struct RepoMetrics {
+    /// Seconds per call, by `op` and `backend`.
+    pub op_duration_seconds: HistogramVec,
+    /// Failed calls, by `op`, `backend`, and error `kind`.
+    pub errors_total: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let slow_requests_total = IntCounterVec::new(
+            Opts::new(
+                "slow_requests_total",
+                "Requests slower than their configured threshold",
+            ),
+            &["route"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(slow_requests_total.clone()))
+            .expect("metric registered once");
+
+        let deprecated_requests_total = IntCounterVec::new(
+            Opts::new(
+                "deprecated_requests_total",
+                "Requests to routes outside /v1 that will be removed",
+            ),
+            &["route", "client"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(deprecated_requests_total.clone()))
+            .expect("metric registered once");
+
+        let list_cache_hits_total =
+            IntCounter::new("list_cache_hits_total", "List pages served from the cache")
+                .expect("metric definition is valid");
+        let list_cache_misses_total = IntCounter::new(
+            "list_cache_misses_total",
+            "List pages read from the repository because the cache had none",
+        )
+        .expect("metric definition is valid");
+        for counter in [&list_cache_hits_total, &list_cache_misses_total] {
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("metric registered once");
+        }
+
+        let task_failures_total = IntCounterVec::new(
+            Opts::new("task_failures_total", "Background tasks that panicked or failed"),
+            &["task"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(task_failures_total.clone()))
+            .expect("metric registered once");
+
+        let attachment_storage_bytes =
+            IntGauge::new("attachment_storage_bytes", "Bytes of attachment files stored")
+                .expect("metric definition is valid");
+        let attachment_storage_files =
+            IntGauge::new("attachment_storage_files", "Attachment files stored")
+                .expect("metric definition is valid");
+        for gauge in [&attachment_storage_bytes, &attachment_storage_files] {
+            registry
+                .register(Box::new(gauge.clone()))
+                .expect("metric registered once");
+        }
+        let attachment_quota_rejections_total = IntCounterVec::new(
+            Opts::new(
+                "attachment_quota_rejections_total",
+                "Uploads refused because they would pass an attachment quota",
+            ),
+            &["scope"],
+        )
+        .expect("metric definition is valid");
+        registry
+            .register(Box::new(attachment_quota_rejections_total.clone()))
+            .expect("metric registered once");
+
+        Self {
+            registry,
+            slow_requests_total,
+            deprecated_requests_total,
+            list_cache_hits_total,
+            list_cache_misses_total,
+            task_failures_total,
+            attachment_storage_bytes,
+            attachment_storage_files,
+            attachment_quota_rejections_total,
+            repo: OnceLock::new(),
+        }
+    }
+
+    /// The repository metrics, registered with `buckets` on first use.
+    pub fn repo(&self, buckets: &[f64]) -> &RepoMetrics {
+        self.repo.get_or_init(|| {
+            let op_duration_seconds = HistogramVec::new(
+                HistogramOpts::new("repo_op_duration_seconds", "Time spent in repository calls")
+                    .buckets(buckets.to_vec()),
+                &["op", "backend"],
+            )
+            .expect("metric definition is valid");
+            let errors_total = IntCounterVec::new(
+                Opts::new("repo_errors_total", "Repository calls that failed"),
+                &["op", "backend", "kind"],
+            )
+            .expect("metric definition is valid");
+            self.registry
+                .register(Box::new(op_duration_seconds.clone()))
+                .expect("metric registered once");
+            self.registry
+                .register(Box::new(errors_total.clone()))
+                .expect("metric registered once");
+            RepoMetrics {
+                op_duration_seconds,
+                errors_total,
+            }
+        })
+    }
+
+    /// Renders every registered metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Repository decorator that times every call into [`RepoMetrics`].
+pub struct Metered {
+    inner: Arc<dyn TodoRepo>,
+    metrics: Arc<Metrics>,
+    config: Arc<ArcSwap<Config>>,
+}
+
+impl Metered {
+    pub fn new(
+        inner: Arc<dyn TodoRepo>,
+        metrics: Arc<Metrics>,
+        config: Arc<ArcSwap<Config>>,
+    ) -> Self {
+        Self {
+            inner,
+            metrics,
+            config,
+        }
+    }
+
+    async fn observe<T>(
+        &self,
+        op: &'static str,
+        call: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        let started = Instant::now();
+        let result = call.await;
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let metrics = self.metrics.repo(&self.config.load().repo_latency_buckets);
+        let backend = self.inner.backend();
+        metrics.op_duration_seconds.with_label_values(&[op, backend]).observe(elapsed);
+        if let Err(err) = &result {
+            metrics.errors_total.with_label_values(&[op, backend, err.code()]).inc();
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl TodoRepo for Metered {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.observe("list", self.inner.list()).await
+    }
+
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.observe("list_after", self.inner.list_after(filter, after, limit)).await
+    }
+
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.observe("list_page", self.inner.list_page(filter, offset, limit)).await
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        self.observe("count", self.inner.count(filter)).await
+    }
+
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
+        self.observe("create", self.inner.create(input)).await
+    }
+
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        self.observe("create_many", self.inner.create_many(inputs)).await
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        self.observe("get", self.inner.get(id)).await
+    }
+
+    async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        self.observe("update", self.inner.update(id, input)).await
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        self.observe("toggle", self.inner.toggle(id)).await
+    }
+
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        self.observe("update_many_at", self.inner.update_many_at(revision, updates)).await
+    }
+
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        self.observe("upsert", self.inner.upsert(id, input)).await
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        self.observe("delete", self.inner.delete(id)).await
+    }
+
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        self.observe("version", self.inner.version(id)).await
+    }
+
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        self.observe("delete_if_version", self.inner.delete_if_version(id, version)).await
+    }
+
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        self.observe("add_attachment", self.inner.add_attachment(todo_id, input)).await
+    }
+
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.observe("list_attachments", self.inner.list_attachments(todo_id)).await
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.observe("get_attachment", self.inner.get_attachment(id)).await
+    }
+
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.observe("delete_attachment", self.inner.delete_attachment(id)).await
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        self.observe("search", self.inner.search(query, limit)).await
+    }
+
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        self.observe("find_similar", self.inner.find_similar(title, threshold)).await
+    }
+
+    async fn due_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.observe("due_between", self.inner.due_between(from, to)).await
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        self.observe("mark_reminded", self.inner.mark_reminded(id, at)).await
+    }
+
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        self.observe("timeseries", self.inner.timeseries(bucket, range)).await
+    }
+
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        self.observe("changes_since", self.inner.changes_since(since)).await
+    }
+
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        self.observe("export_all", self.inner.export_all()).await
+    }
+
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        self.observe("import_all", self.inner.import_all(snapshot, mode, run)).await
+    }
+
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        self.observe("revision", self.inner.revision()).await
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        self.observe("ping", self.inner.ping()).await
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+}
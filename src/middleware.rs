@@ -0,0 +1,334 @@
+//! Custom middleware.
+//!
+//! # `from_fn` middleware
+//!
+//! `axum::middleware::from_fn_with_state` turns a plain async function into a
+//! Tower layer. The function receives the request plus a `Next` handle; it can
+//! short-circuit by returning early (e.g., with an `AppError`) or call
+//! `next.run(req)` to continue down the stack.
+//!
+//! Every function here reads `state.config()` per request instead of capturing
+//! values at construction, which is what makes SIGHUP reloads take effect
+//! immediately.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::Body,
+    error_handling::HandleErrorLayer,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{self, header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    BoxError, Router,
+};
+use futures::future::BoxFuture;
+use http_body::Body as HttpBody;
+use tower::{limit::GlobalConcurrencyLimitLayer, Layer, Service, ServiceBuilder, ServiceExt};
+use tower_http::cors::{AllowOrigin, Any, Cors, CorsLayer};
+
+use crate::{errors::AppError, state::AppState};
+
+/// Rejects mutating requests while `READ_ONLY` is set, e.g. during a backup or
+/// migration. Reads keep working. `/graphql` carries queries over `POST` too,
+/// so its mutations check the flag themselves.
+pub async fn read_only_guard(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || req.uri().path() == "/graphql";
+    if !safe && state.config().read_only {
+        return Err(AppError::ReadOnly);
+    }
+    Ok(next.run(req).await)
+}
+
+/// Per-client fixed-window limiter. `/health` and `/ready` are exempt so
+/// probes never trip.
+/// If the store is down, `RATE_LIMIT_FAIL_OPEN` picks between serving the
+/// request unlimited and refusing it with `503`.
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let config = state.config();
+    let limit = config.rate_limit_per_minute;
+    if limit == 0 || matches!(req.uri().path(), "/health" | "/ready") {
+        return Ok(next.run(req).await);
+    }
+
+    match state.rate_limiter().hit(&client_key(&req), limit).await {
+        Ok(result) => {
+            result.map_err(|retry_after_secs| AppError::RateLimited { retry_after_secs })?
+        }
+        Err(err) if config.rate_limit_fail_open => {
+            tracing::warn!(error = %err, "rate limit store unavailable, allowing request");
+        }
+        Err(err) => {
+            tracing::error!(error = %err, "rate limit store unavailable, refusing request");
+            return Err(AppError::Unavailable);
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Flags requests that blow their latency budget with a warn log and a
+/// `slow_requests_total{route}` increment. The fast path costs one
+/// `Instant::now()` pair and a comparison.
+pub async fn slow_requests(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    // Both clones are reference-count bumps, not string copies.
+    let route = req.extensions().get::<MatchedPath>().cloned();
+    let request_id = req.headers().get("x-request-id").cloned();
+
+    let response = next.run(req).await;
+    let elapsed = started.elapsed();
+
+    let route = route.as_ref().map_or("unmatched", MatchedPath::as_str);
+    if elapsed >= state.config().slow_request_threshold(route) {
+        tracing::warn!(
+            route,
+            duration_ms = elapsed.as_millis() as u64,
+            request_id = request_id.as_ref().and_then(|id| id.to_str().ok()),
+            "slow request"
+        );
+        state
+            .metrics()
+            .slow_requests_total
+            .with_label_values(&[route])
+            .inc();
+    }
+
+    response
+}
+
+/// Rejects request bodies in an encoding the decompression layer can't undo,
+/// so clients get our JSON `415` instead of an empty one.
+pub async fn content_encoding(req: Request, next: Next) -> Result<Response, AppError> {
+    if let Some(encoding) = req.headers().get(header::CONTENT_ENCODING) {
+        // Matched byte-for-byte, exactly as `RequestDecompressionLayer` does.
+        if !matches!(encoding.as_bytes(), b"gzip" | b"deflate" | b"identity") {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "content encoding `{}` is not supported, use gzip or deflate",
+                encoding.to_str().unwrap_or("<binary>")
+            )));
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+/// Writes `Content-Length` for bodies of known size. Must run inside the
+/// compression layer: its body wrapper hides the size, which would otherwise
+/// leave `HEAD` responses without a length and force chunked `GET`s. When a
+/// response does get compressed, the compression layer drops the header again.
+pub async fn content_length(req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+    let bodiless = matches!(res.status(), StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED)
+        || res.status().is_informational();
+    if let Some(len) = res.body().size_hint().exact().filter(|_| !bodiless) {
+        res.headers_mut()
+            .entry(header::CONTENT_LENGTH)
+            .or_insert_with(|| len.into());
+    }
+    res
+}
+
+/// The caller's IP, when the server recorded a [`SocketAddr`] as connect
+/// info; see [`app_with_connect_info`](crate::app_with_connect_info).
+pub(crate) fn client_ip(req: &Request) -> Option<IpAddr> {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Identifies the caller by IP. Requests without connection info (e.g. tests
+/// driving the router through `oneshot`) share a single bucket.
+pub(crate) fn client_key(req: &Request) -> String {
+    client_ip(req).map_or_else(|| "unknown".to_string(), |ip| ip.to_string())
+}
+
+/// A budget of at most `max` requests in flight, shared by every router
+/// [`limit_concurrency`] applies a clone of it to. `0` means no limit.
+#[derive(Clone)]
+pub struct ConcurrencyLimit(Option<GlobalConcurrencyLimitLayer>);
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize) -> Self {
+        Self((max > 0).then(|| GlobalConcurrencyLimitLayer::new(max)))
+    }
+}
+
+/// Lets the routes registered on `router` so far into `limit`, and answers
+/// requests beyond it right away with [`AppError::Overloaded`] instead of
+/// queueing them.
+///
+/// Unlike the `from_fn` middleware above this is fixed when the router is
+/// built, so the limits need a restart to change.
+pub fn limit_concurrency<S>(router: Router<S>, limit: &ConcurrencyLimit) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let Some(layer) = limit.0.clone() else {
+        return router;
+    };
+    router.route_layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async { AppError::Overloaded }))
+            .load_shed()
+            // `route_layer` wraps every route separately; the global variant
+            // makes them share one semaphore, and so do its clones.
+            .layer(layer),
+    )
+}
+
+/// Redirects `/todos/` and `/todos/5/` to `/todos` and `/todos/5` with a
+/// `308`, keeping the query string. `308` tells clients to repeat the method
+/// and body, so a `POST` or `PUT` isn't turned into a `GET` on the way. The
+/// bundled UI lives at `/ui/`, so that one path keeps its slash.
+pub async fn trailing_slash(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let canonical = path.trim_end_matches('/');
+    // `//example.com` in `Location` would send the client to another host.
+    if canonical.len() == path.len()
+        || canonical.is_empty()
+        || canonical.starts_with("//")
+        || path == "/ui/"
+    {
+        return next.run(req).await;
+    }
+
+    let location = match req.uri().query() {
+        Some(query) => format!("{canonical}?{query}"),
+        None => canonical.to_string(),
+    };
+    let Ok(location) = HeaderValue::try_from(location) else {
+        return next.run(req).await;
+    };
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = StatusCode::PERMANENT_REDIRECT;
+    res.headers_mut().insert(header::LOCATION, location);
+    res
+}
+
+/// Answers a plain `OPTIONS` with `204` and the route's `Allow` list. The
+/// list is the one axum puts on the `405` for methods a route doesn't
+/// register, so it always matches the router. Axum adds it after every layer
+/// on the router has run, which is why `app` wraps the router once more for
+/// this. Unknown paths stay `404`.
+pub async fn allow(req: Request, next: Next) -> Response {
+    let preflight = req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if req.method() != Method::OPTIONS || preflight {
+        return keep_length_unset(next.run(req).await);
+    }
+
+    let mut res = next.run(req).await;
+    let allow = res
+        .headers()
+        .get(header::ALLOW)
+        .and_then(|allow| allow.to_str().ok())
+        .filter(|_| res.status() == StatusCode::METHOD_NOT_ALLOWED)
+        .and_then(|allow| HeaderValue::try_from(format!("{allow},OPTIONS")).ok());
+    if let Some(allow) = allow {
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        res.headers_mut().insert(header::ALLOW, allow);
+        res.headers_mut().remove(header::CONTENT_LENGTH);
+        *res.body_mut() = Body::empty();
+    }
+    keep_length_unset(res)
+}
+
+/// The outer router fills in `Content-Length` from an exact body size, as the
+/// inner one already did. A response still without one had its body stripped
+/// on purpose (`HEAD`, `304`, `204`), so hide the size rather than announce
+/// an empty body.
+fn keep_length_unset(res: Response) -> Response {
+    let exact = res.body().size_hint().exact().is_some();
+    if !exact || res.headers().contains_key(header::CONTENT_LENGTH) {
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    Response::from_parts(parts, Body::from_stream(body.into_data_stream()))
+}
+
+/// CORS that consults the live `CORS_ORIGINS` list on every request. An empty
+/// list keeps the old permissive behavior.
+pub fn cors(state: &AppState) -> CorsForBrowsers {
+    let state = state.clone();
+    let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        let config = state.config();
+        config.cors_origins.is_empty()
+            || config
+                .cors_origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+    });
+
+    CorsForBrowsers(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any),
+    )
+}
+
+/// `CorsLayer` treats every `OPTIONS` as a preflight and answers it itself.
+/// This sends only real preflights (those with `Access-Control-Request-Method`)
+/// its way; a plain `OPTIONS` goes on to the router, for [`allow`].
+#[derive(Clone)]
+pub struct CorsForBrowsers(CorsLayer);
+
+impl<S: Clone> Layer<S> for CorsForBrowsers {
+    type Service = CorsForBrowsersService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CorsForBrowsersService {
+            cors: self.0.layer(inner.clone()),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CorsForBrowsersService<S> {
+    cors: Cors<S>,
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for CorsForBrowsersService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Each call drives a clone of whichever service it picks to
+        // readiness itself.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let preflight = req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        if req.method() == Method::OPTIONS && !preflight {
+            Box::pin(self.inner.clone().oneshot(req))
+        } else {
+            Box::pin(self.cors.clone().oneshot(req))
+        }
+    }
+}
@@ -9,62 +9,1260 @@
 //!
 //! # Validation
 //!
-//! We implement `validate()` methods on our input models to ensure data integrity
-//! before it reaches the repository. This keeps the domain logic clean.
+//! We implement `validate()` methods on our input models to ensure data integrity.
+//! Handlers validate new todos before checking them for duplicates; updates
+//! are validated by the repository itself, so every caller gets the same rules.
+//! Titles are the exception: a [`Title`] can only be made valid, so the rules
+//! run when one is deserialized or built with [`Title::new`].
 
-use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashSet,
+    fmt,
+    ops::{Deref, Range},
+    str::FromStr,
+};
 
-use crate::errors::AppError;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    collation::{Collation, Collator},
+    config::Config,
+    errors::{AppError, ValidationError},
+    outbox::DeliveryState,
+    query::{comma_separated, QueryParams},
+    quota::Quota,
+    timestamps,
+};
 
 /// Representation of a todo item as it leaves the repository or gets
 /// serialized back to the client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Todo {
     pub id: u64,
-    pub title: String,
+    pub title: Title,
+    /// Free-form notes in Markdown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub done: bool,
+    /// When `done` last became true; cleared when the todo is reopened.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "timestamps::option")]
+    pub done_at: Option<DateTime<Utc>>,
+    /// When the todo should be finished, if ever.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "timestamps::option")]
+    pub due: Option<DateTime<Utc>>,
+    /// Who should do it; a user id once auth exists, free-form until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    /// Palette label UIs group todos by.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    /// When the reminder for the current due date went out.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "timestamps::option")]
+    pub reminded_at: Option<DateTime<Utc>>,
+    #[serde(with = "timestamps")]
+    pub created_at: DateTime<Utc>,
+    /// Last successful create or update.
+    #[serde(with = "timestamps")]
+    pub updated_at: DateTime<Utc>,
 }
 
-/// Payload used when creating a new todo.
-#[derive(Debug, Clone, Deserialize)]
-pub struct CreateTodo {
-    pub title: String,
+impl Todo {
+    /// An open todo with just a title, created and last updated at `now`.
+    pub fn new(id: u64, title: Title, now: DateTime<Utc>) -> Self {
+        Self {
+            id,
+            title,
+            description: None,
+            done: false,
+            done_at: None,
+            due: None,
+            assignee: None,
+            color: None,
+            reminded_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
 }
 
-impl CreateTodo {
+/// Query string accepted by `GET /todos`. Supplying `limit` or `offset` turns
+/// on pagination.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListQuery {
+    pub limit: Option<PageParam>,
+    pub offset: Option<PageParam>,
+    pub done: Option<bool>,
+    pub q: Option<String>,
+    /// An assignee, or [`UNASSIGNED`] for todos without one.
+    pub assignee: Option<String>,
+    pub color: Option<Color>,
+    /// [Fields](crate::projection) to return; repeatable and comma-separated.
+    pub fields: Option<String>,
+    pub sort: Option<SortField>,
+    pub order: Option<SortOrder>,
+    /// How `sort=title` orders titles.
+    pub collation: Option<Collation>,
+    /// Only these todos; repeatable and comma-separated.
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub ids: Option<Vec<u64>>,
+}
+
+impl QueryParams for ListQuery {
+    const REPEATABLE: &'static [&'static str] = &["ids", "fields"];
+}
+
+/// `?assignee=` value selecting unassigned todos. It is reserved, so nobody
+/// can be assigned under that name.
+pub const UNASSIGNED: &str = "none";
+
+impl ListQuery {
+    /// The filters in this query.
+    pub fn filter(&self) -> TodoFilter {
+        TodoFilter {
+            done: self.done,
+            q: self
+                .q
+                .as_deref()
+                .map(str::trim)
+                .filter(|q| !q.is_empty())
+                .map(canonical_key),
+            assignee: self.assignee.as_deref().map(str::trim).map(|assignee| {
+                (assignee != UNASSIGNED).then(|| assignee.to_string())
+            }),
+            color: self.color,
+            ids: self.ids.clone(),
+        }
+    }
+
+    /// The requested page, or `None` when the client wants everything.
+    pub fn page(&self, config: &Config) -> Result<Option<Pagination>, AppError> {
+        if self.limit.is_none() && self.offset.is_none() {
+            return Ok(None);
+        }
+        Pagination::requested(self.limit.as_ref(), self.offset.as_ref(), config).map(Some)
+    }
+
+    /// Fills in what the query leaves out from the caller's `preferences`.
+    pub fn or_preferences(mut self, preferences: &Preferences) -> Self {
+        if self.limit.is_none() && self.offset.is_none() {
+            self.limit = preferences.page_size.map(PageParam::from);
+        }
+        if preferences.hide_completed {
+            self.done = self.done.or(Some(false));
+        }
+        self.sort = self.sort.or(Some(preferences.default_sort));
+        self.order = self.order.or(Some(preferences.default_order));
+        self
+    }
+}
+
+/// What `GET /todos?sort=` orders by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Id,
+    /// By the request's [`Collation`](crate::collation::Collation).
+    Title,
+    CreatedAt,
+    UpdatedAt,
+    /// Todos without a due date come after those with one.
+    Due,
+}
+
+impl SortField {
+    /// Ascending order by this field, then by id. Titles are compared with
+    /// `collator`.
+    pub fn compare(self, collator: &Collator, a: &Todo, b: &Todo) -> Ordering {
+        let by_field = match self {
+            SortField::Id => Ordering::Equal,
+            SortField::Title => collator.compare(&a.title, &b.title),
+            SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            SortField::Due => match (a.due, b.due) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        by_field.then(a.id.cmp(&b.id))
+    }
+}
+
+/// `GET /todos?order=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A user's defaults for `GET /todos`, used for whatever a request leaves
+/// out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preferences {
+    #[serde(default)]
+    pub default_sort: SortField,
+    #[serde(default)]
+    pub default_order: SortOrder,
+    /// Page size for requests without `limit` or `offset`; `null` returns
+    /// the whole list.
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// Lists only open todos unless the request sets `done`.
+    #[serde(default)]
+    pub hide_completed: bool,
+}
+
+impl Preferences {
+    /// Whether these change nothing about a list.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `page_size` must fit under `MAX_PAGE_SIZE`.
+    pub fn validate(&self, config: &Config) -> Result<(), AppError> {
+        match self.page_size {
+            Some(size) if !(1..=config.max_page_size).contains(&size) => {
+                let raw = PageParam::from(size);
+                Err(out_of_range("page_size", &raw, 1, config.max_page_size))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A named set of todos that can be created together with
+/// `POST /templates/:id/instantiate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: u64,
+    pub name: String,
+    /// The todos each instantiation creates, in order.
+    pub items: Vec<CreateTodo>,
+}
+
+/// POST/PUT payload for a [`Template`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SaveTemplate {
+    pub name: String,
+    pub items: Vec<CreateTodo>,
+}
+
+impl SaveTemplate {
+    /// Most items a template may hold.
+    pub const MAX_ITEMS: usize = 50;
+    /// Longest name accepted, in characters, after trimming.
+    pub const MAX_NAME_CHARS: usize = 100;
+
+    /// A name, between 1 and [`MAX_ITEMS`](Self::MAX_ITEMS) items, and every
+    /// item valid as a `POST /todos` body, so instantiating never fails on
+    /// an item the template was saved with.
     pub fn validate(&self) -> Result<(), AppError> {
-        if self.title.trim().is_empty() {
-            return Err(AppError::Validation("title cannot be empty".to_string()));
+        let name = self.name.trim();
+        if name.is_empty() {
+            let error = ValidationError::field("name", "empty", name, "name cannot be empty");
+            return Err(AppError::Validation(error));
         }
-        if self.title.len() > 100 {
+        if name.chars().count() > Self::MAX_NAME_CHARS {
+            let message = format!("name cannot be longer than {} characters", Self::MAX_NAME_CHARS);
             return Err(AppError::Validation(
-                "title cannot be longer than 100 characters".to_string(),
+                ValidationError::field("name", "too_long", name, message),
             ));
         }
+        if !(1..=Self::MAX_ITEMS).contains(&self.items.len()) {
+            return Err(AppError::Validation(
+                format!("a template must have between 1 and {} items", Self::MAX_ITEMS).into(),
+            ));
+        }
+        for (index, item) in self.items.iter().enumerate() {
+            item.validate().map_err(|err| match err {
+                AppError::Validation(mut error) => {
+                    error.message = format!("items[{index}]: {}", error.message);
+                    AppError::Validation(error)
+                }
+                other => other,
+            })?;
+        }
         Ok(())
     }
 }
 
-/// PATCH/PUT payload that lets the caller flip the completion state or rename
-/// the todo.
-#[derive(Debug, Clone, Deserialize)]
+/// A file uploaded to a todo. The bytes live on disk under
+/// `ATTACHMENT_DIR`; the repository only tracks this metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: u64,
+    pub todo_id: u64,
+    /// Client-supplied name with any path components removed.
+    pub filename: String,
+    pub content_type: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Hex SHA-256 of the contents, which is also the file's name on disk.
+    pub sha256: String,
+}
+
+/// Metadata for a stored upload, before the repository assigns an id.
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Query string accepted by `GET /todos/search`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<PageParam>,
+}
+
+impl QueryParams for SearchQuery {}
+
+/// One search result, best first.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub todo: Todo,
+    /// Relevance; only meaningful relative to other hits of the same query.
+    pub score: f64,
+    pub highlights: Highlights,
+}
+
+/// `[start, end)` byte offsets of matched words, per field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Highlights {
+    pub title: Vec<[usize; 2]>,
+    pub description: Vec<[usize; 2]>,
+}
+
+/// Query string accepted by `GET /todos/calendar.ics`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CalendarQuery {
+    /// Also export completed todos.
+    #[serde(default)]
+    pub include_done: bool,
+}
+
+impl QueryParams for CalendarQuery {}
+
+/// Query string accepted by `GET /todos/feed.atom`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedQuery {
+    /// Only todos changed after this instant.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl QueryParams for FeedQuery {}
+
+/// Query string accepted by `GET /todos/stats/timeseries`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TimeseriesQuery {
+    #[serde(default)]
+    pub bucket: Bucket,
+    /// Start of the range; defaults to [`DEFAULT_DAYS`](Self::DEFAULT_DAYS)
+    /// before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive; defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl QueryParams for TimeseriesQuery {}
+
+impl TimeseriesQuery {
+    /// Days covered when `from` is not given.
+    pub const DEFAULT_DAYS: i64 = 30;
+    /// Longest range a client may ask for, in days.
+    pub const MAX_DAYS: i64 = 366;
+
+    /// The requested range, validated.
+    pub fn range(&self, now: DateTime<Utc>) -> Result<Range<DateTime<Utc>>, AppError> {
+        let to = self.to.unwrap_or(now);
+        let from = self.from.unwrap_or(to - Duration::days(Self::DEFAULT_DAYS));
+        let invalid = |rule, message: String| {
+            AppError::Validation(ValidationError::field("from", rule, &from.to_rfc3339(), message))
+        };
+        if from >= to {
+            return Err(invalid("after_to", "from must be before to".to_string()));
+        }
+        if to - from > Duration::days(Self::MAX_DAYS) {
+            let message = format!("the range cannot be longer than {} days", Self::MAX_DAYS);
+            return Err(invalid("too_long", message));
+        }
+        Ok(from..to)
+    }
+}
+
+/// Width of one point of a time series. Buckets are aligned to UTC
+/// midnight; weeks start on Monday.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Day,
+    Week,
+}
+
+impl Bucket {
+    /// Start of the bucket containing `at`.
+    pub fn start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let day = at.date_naive();
+        let day = match self {
+            Bucket::Day => day,
+            Bucket::Week => day - Duration::days(day.weekday().num_days_from_monday().into()),
+        };
+        day.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    pub fn width(self) -> Duration {
+        match self {
+            Bucket::Day => Duration::days(1),
+            Bucket::Week => Duration::weeks(1),
+        }
+    }
+}
+
+/// Body of `GET /todos/stats/storage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub used_bytes: u64,
+    pub files: u64,
+    /// `ATTACHMENTS_MAX_BYTES`, unless unlimited.
+    pub max_bytes: Option<u64>,
+    /// `ATTACHMENT_MAX_PER_TODO`, unless unlimited.
+    pub max_bytes_per_todo: Option<u64>,
+}
+
+/// Todos created and completed in one bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeseriesPoint {
+    #[serde(with = "timestamps")]
+    pub bucket_start: DateTime<Utc>,
+    pub created: usize,
+    pub completed: usize,
+}
+
+/// Query string accepted by `GET /todos/changes`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChangesQuery {
+    /// The `revision` of the client's last sync; 0 (the default) means it has
+    /// nothing yet.
+    #[serde(default)]
+    pub since: u64,
+}
+
+impl QueryParams for ChangesQuery {}
+
+/// Query string accepted by `GET /todos/poll`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PollQuery {
+    /// The `revision` of the client's last sync, as for `GET /todos/changes`.
+    #[serde(default)]
+    pub revision: u64,
+    /// Seconds to wait for a change; see [`poll::timeout`](crate::poll::timeout).
+    pub timeout_secs: Option<u64>,
+}
+
+impl QueryParams for PollQuery {}
+
+/// Query string accepted by `GET /todos/events/log`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventLogQuery {
+    /// The `seq` of the last event the client saw; 0 (the default) means
+    /// none.
+    #[serde(default)]
+    pub after_seq: u64,
+    pub limit: Option<PageParam>,
+}
+
+impl QueryParams for EventLogQuery {}
+
+/// What changed after a revision, for clients that keep a local copy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Changes {
+    /// The collection's current revision; pass it as `since` next time.
+    pub revision: u64,
+    /// Todos created or updated since, in id order.
+    pub changed: Vec<Todo>,
+    /// Ids deleted since, in deletion order.
+    pub deleted: Vec<u64>,
+}
+
+/// Criteria a todo must meet to be listed. The default matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TodoFilter {
+    /// Only todos with this completion state.
+    pub done: Option<bool>,
+    /// Substring the title's [`canonical_key`] must contain. Pass it through
+    /// `canonical_key` too, or it may never match.
+    pub q: Option<String>,
+    /// Only todos with this assignee; `Some(None)` means unassigned.
+    pub assignee: Option<Option<String>>,
+    /// Only todos with this color.
+    pub color: Option<Color>,
+    /// Only todos with one of these ids.
+    pub ids: Option<Vec<u64>>,
+}
+
+impl TodoFilter {
+    pub fn matches(&self, todo: &Todo) -> bool {
+        self.done.is_none_or(|done| todo.done == done)
+            && self
+                .q
+                .as_deref()
+                .is_none_or(|q| canonical_key(&todo.title).contains(q))
+            && self
+                .assignee
+                .as_ref()
+                .is_none_or(|assignee| todo.assignee == *assignee)
+            && self.color.is_none_or(|color| todo.color == Some(color))
+            && self.ids.as_ref().is_none_or(|ids| ids.contains(&todo.id))
+    }
+}
+
+/// Position of a page within the full list. Handlers attach it to list
+/// responses as an extension so response-shaping layers can describe it.
+///
+/// Every endpoint that pages reads `?limit=` and `?offset=` through
+/// [`Pagination::requested`], so they share one default, one cap
+/// (`MAX_PAGE_SIZE`), and the same errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Pagination {
+    /// Todos matching the filters, not just this page.
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+    /// The client asked for more than `MAX_PAGE_SIZE` and got that many.
+    #[serde(skip)]
+    pub clamped: bool,
+}
+
+impl Pagination {
+    /// Page size when the client doesn't pick one.
+    pub const DEFAULT_LIMIT: usize = 50;
+
+    /// The page `limit` and `offset` ask for. A limit above `MAX_PAGE_SIZE` is
+    /// cut down to it, or rejected with `STRICT_PAGINATION`; zero, negative,
+    /// and non-numeric values are always rejected.
+    pub fn requested(
+        limit: Option<&PageParam>,
+        offset: Option<&PageParam>,
+        config: &Config,
+    ) -> Result<Self, AppError> {
+        let max = config.max_page_size;
+        let (limit, clamped) = match limit {
+            None => (Self::DEFAULT_LIMIT.min(max), false),
+            Some(raw) => match raw.number() {
+                Some(limit) if (1..=max as i128).contains(&limit) => (limit as usize, false),
+                Some(limit) if limit > max as i128 && !config.strict_pagination => (max, true),
+                _ => return Err(out_of_range("limit", raw, 1, max)),
+            },
+        };
+        let offset = match offset {
+            None => 0,
+            Some(raw) => match raw.number() {
+                Some(offset) if offset >= 0 => usize::try_from(offset).unwrap_or(usize::MAX),
+                _ => return Err(out_of_range("offset", raw, 0, usize::MAX)),
+            },
+        };
+        Ok(Self {
+            total: 0,
+            limit,
+            offset,
+            clamped,
+        })
+    }
+}
+
+/// A `?limit=` or `?offset=` value as sent, so that a malformed one gets the
+/// valid range in its error instead of a generic parse failure.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct PageParam(pub String);
+
+impl PageParam {
+    fn number(&self) -> Option<i128> {
+        self.0.trim().parse().ok()
+    }
+}
+
+impl From<usize> for PageParam {
+    fn from(value: usize) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Payload used when creating a new todo. `Serialize` is for the
+/// [`client`](crate::client).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTodo {
+    pub title: Title,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// RFC 3339 timestamp, e.g. `2024-05-01T17:00:00Z`.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "timestamps::option")]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+}
+
+impl CreateTodo {
+    /// A todo with just a title.
+    pub fn new(title: Title) -> Self {
+        Self {
+            title,
+            description: None,
+            due: None,
+            assignee: None,
+            color: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        validate_description(self.description.as_deref())?;
+        validate_assignee(self.assignee.as_deref())
+    }
+}
+
+/// Query string accepted by `POST /todos`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateQuery {
+    /// Refuse to create the todo when it looks like a duplicate.
+    #[serde(default)]
+    pub strict_duplicates: bool,
+}
+
+impl QueryParams for CreateQuery {}
+
+/// A newly created todo, plus open todos it may duplicate.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedTodo {
+    #[serde(flatten)]
+    pub todo: Todo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub possible_duplicates: Vec<u64>,
+    /// The quota standing after the create, sent as headers.
+    #[serde(skip)]
+    pub quota: Option<Quota>,
+}
+
+/// PATCH/PUT payload that lets the caller flip the completion state, rename
+/// the todo, set its description or due date, (re)assign it, or recolor it.
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct UpdateTodo {
-    pub title: Option<String>,
+    pub title: Option<Title>,
+    pub description: Option<String>,
     pub done: Option<bool>,
+    pub due: Option<DateTime<Utc>>,
+    /// `Some(None)` (JSON `null`) clears the assignee; absent leaves it.
+    #[serde(default, deserialize_with = "present")]
+    pub assignee: Option<Option<String>>,
+    /// `Some(None)` (JSON `null`) clears the color; absent leaves it.
+    #[serde(default, deserialize_with = "present")]
+    pub color: Option<Option<Color>>,
 }
 
 impl UpdateTodo {
+    /// True when the update would change nothing.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.done.is_none()
+            && self.due.is_none()
+            && self.assignee.is_none()
+            && self.color.is_none()
+    }
+
+    /// Every rule an update must pass. Repositories call this from `update`,
+    /// so callers don't need to.
     pub fn validate(&self) -> Result<(), AppError> {
-        if let Some(title) = &self.title {
-            if title.trim().is_empty() {
-                return Err(AppError::Validation("title cannot be empty".to_string()));
+        // PUT/patching nothing is usually a client mistake.
+        if self.is_empty() {
+            return Err(AppError::Validation(ValidationError {
+                message: "provide at least one field to update".to_string(),
+                rule: Some("no_fields"),
+                ..ValidationError::default()
+            }));
+        }
+        validate_description(self.description.as_deref())?;
+        validate_assignee(self.assignee.as_ref().and_then(Option::as_deref))
+    }
+}
+
+/// Body of `POST /todos/:id/assign`. The field is required; `null`
+/// unassigns.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AssignTodo {
+    #[serde(deserialize_with = "Option::deserialize")]
+    pub assignee: Option<String>,
+}
+
+impl From<AssignTodo> for UpdateTodo {
+    fn from(input: AssignTodo) -> Self {
+        Self {
+            assignee: Some(input.assignee),
+            ..Default::default()
+        }
+    }
+}
+
+/// One entry of a `PATCH /todos/batch` body: a todo id plus the fields of
+/// an [`UpdateTodo`].
+#[derive(Debug, Clone)]
+pub struct BatchUpdate {
+    pub id: u64,
+    /// The fields, or why their title was refused. One bad title fails its
+    /// own entry rather than the whole body.
+    pub update: Result<UpdateTodo, ValidationError>,
+}
+
+impl BatchUpdate {
+    /// Most entries one request may carry.
+    pub const MAX_ENTRIES: usize = 100;
+}
+
+impl<'de> Deserialize<'de> for BatchUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Entry {
+            id: u64,
+            #[serde(flatten)]
+            fields: serde_json::Map<String, serde_json::Value>,
+        }
+
+        let Entry { id, fields } = Entry::deserialize(deserializer)?;
+        let fields = serde_json::Value::Object(fields);
+        let update = match Title::refusal(|| UpdateTodo::deserialize(fields)) {
+            (Ok(update), _) => Ok(update),
+            (Err(_), Some(refused)) => Err(refused),
+            (Err(err), None) => return Err(de::Error::custom(err)),
+        };
+        Ok(Self { id, update })
+    }
+}
+
+/// How one batch entry went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Ok,
+    NotFound,
+    Invalid,
+    /// Anything else, such as a storage failure.
+    Error,
+}
+
+/// Outcome of one batch entry: the updated todo, or why it wasn't updated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub id: u64,
+    pub status: BatchStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub todo: Option<Todo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Body of a `PATCH /todos/batch` response, one result per entry in the order
+/// they were sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResults {
+    pub results: Vec<BatchResult>,
+}
+
+/// Longest title in user-perceived characters (grapheme clusters), not
+/// counting surrounding whitespace. Counting bytes would hold non-Latin
+/// titles to a fraction of the limit.
+pub const MAX_TITLE_CHARS: usize = 100;
+
+/// A paging parameter that isn't a whole number in `min..=max`.
+fn out_of_range(field: &'static str, raw: &PageParam, min: usize, max: usize) -> AppError {
+    let message = if max == usize::MAX {
+        format!("{field} must be a whole number of at least {min}")
+    } else {
+        format!("{field} must be a whole number between {min} and {max}")
+    };
+    AppError::Validation(ValidationError::field(field, "out_of_range", &raw.0, message))
+}
+
+/// What two strings are compared by when they should match "the same text":
+/// case-folded, in compatibility-composed form (NFKC, so fullwidth and
+/// decomposed letters match their usual forms), with whitespace trimmed and
+/// collapsed to single spaces. `"CAFE\u{301}"` and `"café"` both become
+/// `"café"`.
+///
+/// Duplicate detection, the `q` filter and search all compare through it, so
+/// a title that matches under one matches under all of them.
+pub fn canonical_key(text: &str) -> String {
+    // Folding can leave text decomposed ("İ" folds to "i\u{307}"), so
+    // compose again after it.
+    let folded: String = caseless::default_case_fold_str(&text.nfkc().collect::<String>())
+        .nfkc()
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A todo title that passed every title rule. [`Title::new`] is the only
+/// way to make one, and deserializing one goes through it, so no `Todo` can
+/// hold a title the API would refuse:
+///
+/// ```compile_fail
+/// let title = rust_api::models::Title(String::new());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Title(String);
+
+thread_local! {
+    /// Why the last title deserialized on this thread was refused, while
+    /// [`Title::refusal`] is watching.
+    static REFUSED: RefCell<Option<Option<ValidationError>>> = const { RefCell::new(None) };
+}
+
+impl Title {
+    /// Trims `raw` and collapses each run of whitespace inside it to one
+    /// space. Fails if nothing is left, if it holds control characters, or
+    /// if what is left is longer than [`MAX_TITLE_CHARS`].
+    pub fn new(raw: impl Into<String>) -> Result<Self, ValidationError> {
+        let raw = raw.into();
+        let invalid = |rule, message: String| ValidationError::field("title", rule, &raw, message);
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return Err(invalid("empty", "title cannot be empty".to_string()));
+        }
+        if trimmed.chars().any(char::is_control) {
+            let message = "title cannot contain control characters".to_string();
+            return Err(invalid("control_characters", message));
+        }
+        // Counted as stored, so inner runs of whitespace don't count
+        // against the limit.
+        let collapsed = trimmed.split_whitespace().collect::<Vec<_>>().join(" ");
+        if collapsed.graphemes(true).count() > MAX_TITLE_CHARS {
+            let message = format!("title cannot be longer than {MAX_TITLE_CHARS} characters");
+            return Err(invalid("too_long", message));
+        }
+        Ok(Self(collapsed))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Runs `parse` and also returns why it refused a title, if it did.
+    /// serde only passes an error's message along, so this is how
+    /// [`AppJson`](crate::negotiation::AppJson) reports the field and rule.
+    pub fn refusal<T>(parse: impl FnOnce() -> T) -> (T, Option<ValidationError>) {
+        let outer = REFUSED.replace(Some(None));
+        let parsed = parse();
+        let refused = REFUSED.replace(outer).flatten();
+        (parsed, refused)
+    }
+}
+
+impl Deref for Title {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Title {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Title {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<String> for Title {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for Title {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Title {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Title {
+    type Err = ValidationError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::new(raw)
+    }
+}
+
+impl TryFrom<String> for Title {
+    type Error = ValidationError;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        Self::new(raw)
+    }
+}
+
+impl From<Title> for String {
+    fn from(title: Title) -> Self {
+        title.0
+    }
+}
+
+impl Serialize for Title {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Title {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::new(raw).map_err(|err| {
+            let message = err.to_string();
+            REFUSED.with_borrow_mut(|refused| {
+                if let Some(first @ None) = refused {
+                    *first = Some(err);
+                }
+            });
+            de::Error::custom(message)
+        })
+    }
+}
+
+/// A title is a GraphQL `String`.
+#[cfg(feature = "graphql")]
+impl async_graphql::OutputType for Title {
+    fn type_name() -> std::borrow::Cow<'static, str> {
+        <str as async_graphql::OutputType>::type_name()
+    }
+
+    fn create_type_info(registry: &mut async_graphql::registry::Registry) -> String {
+        <str as async_graphql::OutputType>::create_type_info(registry)
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &async_graphql::ContextSelectionSet<'_>,
+        field: &async_graphql::Positioned<async_graphql::parser::types::Field>,
+    ) -> async_graphql::ServerResult<async_graphql::Value> {
+        self.as_str().resolve(ctx, field).await
+    }
+}
+
+/// Longest assignee accepted, in characters, after trimming.
+pub const MAX_ASSIGNEE_CHARS: usize = 64;
+
+fn validate_assignee(assignee: Option<&str>) -> Result<(), AppError> {
+    let Some(assignee) = assignee.map(str::trim) else {
+        return Ok(());
+    };
+    let invalid = |rule, message: String| {
+        AppError::Validation(ValidationError::field("assignee", rule, assignee, message))
+    };
+    if assignee.is_empty() {
+        let message = "assignee cannot be empty; send null to unassign".to_string();
+        return Err(invalid("empty", message));
+    }
+    if assignee.chars().count() > MAX_ASSIGNEE_CHARS {
+        let message = format!("assignee cannot be longer than {MAX_ASSIGNEE_CHARS} characters");
+        return Err(invalid("too_long", message));
+    }
+    if assignee == UNASSIGNED {
+        let message = format!("`{UNASSIGNED}` is reserved for the unassigned filter");
+        return Err(invalid("reserved", message));
+    }
+    Ok(())
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field
+/// (`None`, via `#[serde(default)]`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Longest description accepted, in bytes.
+pub const MAX_DESCRIPTION_BYTES: usize = 100 * 1024;
+
+fn validate_description(description: Option<&str>) -> Result<(), AppError> {
+    let Some(description) = description else {
+        return Ok(());
+    };
+    if description.len() > MAX_DESCRIPTION_BYTES {
+        let message = format!("description cannot be longer than {MAX_DESCRIPTION_BYTES} bytes");
+        return Err(AppError::Validation(ValidationError::field(
+            "description",
+            "too_long",
+            description,
+            message,
+        )));
+    }
+    Ok(())
+}
+
+/// The fixed palette todos can be labeled with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+pub enum Color {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Pink,
+    Gray,
+}
+
+impl Color {
+    /// Every color, in palette order.
+    pub const ALL: [Color; 8] = [
+        Color::Red,
+        Color::Orange,
+        Color::Yellow,
+        Color::Green,
+        Color::Blue,
+        Color::Purple,
+        Color::Pink,
+        Color::Gray,
+    ];
+
+    /// The name used on the wire.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Color::Red => "red",
+            Color::Orange => "orange",
+            Color::Yellow => "yellow",
+            Color::Green => "green",
+            Color::Blue => "blue",
+            Color::Purple => "purple",
+            Color::Pink => "pink",
+            Color::Gray => "gray",
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Unknown names get an error listing the whole palette.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Color::ALL
+            .into_iter()
+            .find(|color| color.as_str() == name)
+            .ok_or_else(|| {
+                let valid: Vec<_> = Color::ALL.iter().map(|color| color.as_str()).collect();
+                format!("unknown color `{name}`, expected one of: {}", valid.join(", "))
+            })
+    }
+}
+
+/// Hand-written so unknown names get [`Color::from_str`]'s message instead of
+/// serde's generic variant error.
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Query string accepted by `GET /todos/:id`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GetQuery {
+    /// Adds a rendered copy of the description.
+    pub render: Option<RenderAs>,
+    /// [Fields](crate::projection) to return; repeatable and comma-separated.
+    pub fields: Option<String>,
+}
+
+impl QueryParams for GetQuery {
+    const REPEATABLE: &'static [&'static str] = &["fields"];
+}
+
+/// Formats the description can be rendered to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderAs {
+    Html,
+}
+
+/// A todo plus derived fields that are computed per read, never stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedTodo {
+    #[serde(flatten)]
+    pub todo: Todo,
+    /// Sanitized HTML of `todo.description`.
+    pub description_html: String,
+}
+
+/// `schema_version` of the documents `GET /admin/export` writes.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+/// Every todo as stored, ids and timestamps included, for moving data
+/// between backends with `GET /admin/export` and `POST /admin/import`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub schema_version: u32,
+    /// The id the next created todo gets, when the store's ids follow a
+    /// sequence. Importing it keeps new ids above the imported ones.
+    #[serde(default)]
+    pub next_id: Option<u64>,
+    /// In id order.
+    pub todos: Vec<Todo>,
+}
+
+impl Snapshot {
+    /// Checks what every backend relies on: a schema this build reads, and
+    /// ids that are valid and distinct.
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.schema_version != SNAPSHOT_SCHEMA_VERSION {
+            return Err(AppError::Validation(
+                format!(
+                    "unsupported schema_version {}, expected {SNAPSHOT_SCHEMA_VERSION}",
+                    self.schema_version
+                )
+                .into(),
+            ));
+        }
+        let mut seen = HashSet::with_capacity(self.todos.len());
+        for todo in &self.todos {
+            if todo.id == 0 {
+                return Err(AppError::Validation("todo ids must be at least 1".into()));
             }
-            if title.len() > 100 {
+            if !seen.insert(todo.id) {
                 return Err(AppError::Validation(
-                    "title cannot be longer than 100 characters".to_string(),
+                    format!("todo {} appears more than once", todo.id).into(),
                 ));
             }
         }
         Ok(())
     }
 }
+
+/// `POST /admin/import?mode=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// The snapshot becomes the whole store: todos missing from it are
+    /// deleted, the others are overwritten.
+    Replace,
+    /// The snapshot's todos are added to the store. Nothing is written if
+    /// any of their ids is taken.
+    Merge,
+}
+
+/// Whether a bulk write is carried out or only worked out. Repository
+/// methods take it so a dry run goes through the same matching as the real
+/// thing and can't drift from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunMode {
+    #[default]
+    Apply,
+    /// Reports what would change and writes nothing: no todos, no events,
+    /// no audit entries.
+    Preview,
+}
+
+impl RunMode {
+    /// `?dry_run=`.
+    pub fn dry_run(dry_run: bool) -> Self {
+        if dry_run {
+            Self::Preview
+        } else {
+            Self::Apply
+        }
+    }
+}
+
+/// Most ids a [`DryRun`] lists.
+pub const DRY_RUN_MAX_IDS: usize = 1000;
+
+/// Answer to a bulk write with `?dry_run=true`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DryRun {
+    /// Always `true`, so the answer can't be mistaken for the real thing.
+    pub dry_run: bool,
+    /// Todos the write would create, change or delete.
+    pub would_affect: usize,
+    /// Their ids in ascending order, the first [`DRY_RUN_MAX_IDS`] of them.
+    pub ids: Vec<u64>,
+}
+
+impl DryRun {
+    pub fn new(mut ids: Vec<u64>) -> Self {
+        ids.sort_unstable();
+        ids.dedup();
+        let would_affect = ids.len();
+        ids.truncate(DRY_RUN_MAX_IDS);
+        Self {
+            dry_run: true,
+            would_affect,
+            ids,
+        }
+    }
+}
+
+/// Query string accepted by `POST /admin/import`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportQuery {
+    pub mode: ImportMode,
+    /// Report what the import would do instead of doing it.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl QueryParams for ImportQuery {}
+
+/// Query string accepted by `GET /admin/outbox`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OutboxQuery {
+    /// Only entries in this state; all of them when absent.
+    pub state: Option<DeliveryState>,
+}
+
+impl QueryParams for OutboxQuery {}
+
+/// What an import did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    /// Todos written from the snapshot.
+    pub imported: usize,
+    /// Ids a replace deleted because the snapshot lacks them.
+    pub removed: Vec<u64>,
+    /// Ids a merge found already taken; when there are any, nothing was
+    /// imported.
+    pub conflicts: Vec<u64>,
+}
@@ -0,0 +1,213 @@
+//! Content negotiation between JSON, MessagePack, and human-readable formats.
+//!
+//! # Requests
+//!
+//! [`AppJson<T>`] replaces `axum::Json<T>` as the body extractor. It decodes
+//! `application/json` as before and `application/msgpack` for clients that
+//! prefer a compact binary format. Either way the handler receives the same
+//! typed payload and runs the same `validate()` call. Decoding failures become
+//! `AppError`s, so clients get our usual error body instead of axum's plain
+//! text rejection.
+//!
+//! # Responses
+//!
+//! Handlers take a [`Format`] extractor (parsed from `Accept`) and wrap their
+//! value in [`Negotiated`]. JSON stays the default, including for `*/*`.
+//! `text/plain` and `text/html` produce the renderings in `render.rs`.
+//! `AppError` cannot see the request, so [`negotiate_errors`] re-encodes JSON
+//! error bodies for clients that asked for MessagePack.
+
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{errors::AppError, models::Title, render::Render};
+
+pub(crate) const MSGPACK: &str = "application/msgpack";
+
+/// Wire format for a response body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+    #[default]
+    Json,
+    MsgPack,
+    /// `text/plain` checklist.
+    Text,
+    /// `text/html` table.
+    Html,
+}
+
+impl Format {
+    /// Picks the supported media type with the highest `q` value in `Accept`.
+    /// Ties go to the type listed first; nothing acceptable means JSON.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let Some(accept) = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Format::Json;
+        };
+
+        let mut best: Option<(Format, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let media = params.next().unwrap_or_default().to_ascii_lowercase();
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match media.as_str() {
+                "application/json" | "application/*" | "*/*" => Format::Json,
+                "application/msgpack" | "application/x-msgpack" => Format::MsgPack,
+                "text/plain" | "text/*" => Format::Text,
+                "text/html" => Format::Html,
+                _ => continue,
+            };
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((format, q));
+            }
+        }
+
+        best.map_or(Format::Json, |(format, _)| format)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Format::from_headers(&parts.headers))
+    }
+}
+
+/// A response body serialized in the format the client negotiated.
+pub struct Negotiated<T> {
+    pub format: Format,
+    pub value: T,
+}
+
+impl<T> Negotiated<T> {
+    pub fn new(format: Format, value: T) -> Self {
+        Self { format, value }
+    }
+}
+
+impl<T: Serialize + Render> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        match self.format {
+            Format::Json => Json(self.value).into_response(),
+            // `to_vec_named` keeps field names so the document is
+            // self-describing, just like the JSON one.
+            Format::MsgPack => match rmp_serde::to_vec_named(&self.value) {
+                Ok(bytes) => ([(header::CONTENT_TYPE, MSGPACK)], bytes).into_response(),
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to encode msgpack response");
+                    AppError::Internal.into_response()
+                }
+            },
+            Format::Text => self.value.text().into_response(),
+            Format::Html => Html(self.value.html()).into_response(),
+        }
+    }
+}
+
+/// Body extractor accepting JSON or MessagePack, chosen by `Content-Type`.
+pub struct AppJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for AppJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase()
+            })
+            .unwrap_or_default();
+
+        let is_json = content_type == "application/json"
+            || (content_type.starts_with("application/") && content_type.ends_with("+json"));
+        let is_msgpack = content_type == MSGPACK || content_type == "application/x-msgpack";
+        if !is_json && !is_msgpack {
+            return Err(AppError::UnsupportedMediaType(
+                "expected `application/json` or `application/msgpack`".to_string(),
+            ));
+        }
+
+        let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                AppError::PayloadTooLarge
+            } else {
+                AppError::Validation(rejection.body_text().into())
+            }
+        })?;
+
+        let (parsed, refused) = Title::refusal(|| {
+            if is_json {
+                serde_json::from_slice(&bytes).map_err(|err| format!("invalid JSON body: {err}"))
+            } else {
+                rmp_serde::from_slice(&bytes).map_err(|err| format!("invalid msgpack body: {err}"))
+            }
+        });
+        // A refused title is reported like any other rule, not as bad syntax.
+        match (parsed, refused) {
+            (Ok(value), _) => Ok(AppJson(value)),
+            (Err(_), Some(refused)) => Err(AppError::Validation(refused)),
+            (Err(message), None) => Err(AppError::Validation(message.into())),
+        }
+    }
+}
+
+/// Re-encodes JSON error bodies as MessagePack when the client asked for it.
+pub async fn negotiate_errors(req: Request, next: Next) -> Response {
+    let format = Format::from_headers(req.headers());
+    let res = next.run(req).await;
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if format != Format::MsgPack || res.status().is_success() || !is_json {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let transcoded = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .and_then(|value| rmp_serde::to_vec_named(&value).ok());
+
+    match transcoded {
+        Some(msgpack) => {
+            parts
+                .headers
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(msgpack))
+        }
+        None => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
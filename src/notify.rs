@@ -0,0 +1,249 @@
+//! Email notifications for reminders and assignments (`email` feature).
+//!
+//! # Pieces
+//!
+//! - [`Mailer`] sends one message. [`SmtpMailer`] is the real one; tests
+//!   substitute a recorder.
+//! - [`Notifier`] turns [`TodoEvent::Reminder`] and [`TodoEvent::Assigned`]
+//!   into messages, rendering bodies from the `templates/` directory (compiled
+//!   in) with `minijinja`.
+//!
+//! # Recipients
+//!
+//! An assignee that is an email address gets the message. Anything else,
+//! including unassigned todos, goes to `EMAIL_DEFAULT_TO`, or nowhere if that
+//! is unset.
+//!
+//! # Delivery
+//!
+//! Each recipient gets at most `EMAIL_MAX_PER_HOUR` messages in any hour;
+//! the excess is dropped with a warning rather than queued, so a burst of
+//! due todos can't turn into a mail flood later. Each send is tried up to
+//! [`MAX_ATTEMPTS`] times with doubling delays. Events are handled one at a
+//! time, so a slow mail server delays later notifications; if it falls far
+//! enough behind the event bus, the oldest events are skipped.
+//!
+//! Without `SMTP_URL` no notifier is started at all.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use futures::StreamExt;
+use lettre::{
+    message::Mailbox, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use minijinja::{context, Environment};
+
+use crate::{
+    config::Config,
+    events::{EventStream, TodoEvent},
+    models::Todo,
+};
+
+/// Attempts per message before giving up.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Window the per-recipient limit applies to.
+const RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Sends a plain-text email.
+#[async_trait]
+pub trait Mailer: Send + Sync + 'static {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()>;
+}
+
+/// [`Mailer`] that talks to an SMTP server.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Connects lazily to `url` (`smtp://` or `smtps://`, credentials in the
+    /// userinfo part), sending as `from`.
+    pub fn new(url: &str, from: &str) -> anyhow::Result<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+            .context("SMTP_URL is not a valid smtp:// or smtps:// URL")?
+            .build();
+        let from = from
+            .parse()
+            .with_context(|| format!("SMTP_FROM `{from}` is not an email address"))?;
+        Ok(Self { transport, from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body.to_string())?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Turns todo events into emails.
+pub struct Notifier {
+    mailer: Arc<dyn Mailer>,
+    templates: Environment<'static>,
+    default_to: Option<String>,
+    max_per_hour: u32,
+    retry_delay: Duration,
+    /// Send times per recipient within the last [`RATE_WINDOW`].
+    sent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl Notifier {
+    pub fn new(mailer: Arc<dyn Mailer>) -> Self {
+        let mut templates = Environment::new();
+        templates
+            .add_template("reminder.txt", include_str!("../templates/reminder.txt"))
+            .expect("reminder template parses");
+        templates
+            .add_template("assigned.txt", include_str!("../templates/assigned.txt"))
+            .expect("assigned template parses");
+        Self {
+            mailer,
+            templates,
+            default_to: None,
+            max_per_hour: 10,
+            retry_delay: Duration::from_secs(1),
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The SMTP notifier described by `config`, or `None` when `SMTP_URL` is
+    /// unset.
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let (Some(url), Some(from)) = (&config.smtp_url, &config.smtp_from) else {
+            return Ok(None);
+        };
+        let mailer = SmtpMailer::new(url.expose(), from)?;
+        Ok(Some(
+            Self::new(Arc::new(mailer))
+                .with_default_to(config.email_default_to.clone())
+                .with_max_per_hour(config.email_max_per_hour),
+        ))
+    }
+
+    /// Where notifications go when the assignee isn't an email address.
+    pub fn with_default_to(mut self, to: Option<String>) -> Self {
+        self.default_to = to;
+        self
+    }
+
+    pub fn with_max_per_hour(mut self, max: u32) -> Self {
+        self.max_per_hour = max;
+        self
+    }
+
+    /// Delay before the first retry; it doubles for each one after.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sends the email for `event`, if it calls for one. Returns whether a
+    /// message was delivered.
+    pub async fn handle(&self, event: &TodoEvent) -> bool {
+        let (template, subject, todo) = match event {
+            TodoEvent::Reminder { todo } => ("reminder.txt", format!("Due: {}", todo.title), todo),
+            TodoEvent::Assigned { todo } => {
+                ("assigned.txt", format!("Assigned: {}", todo.title), todo)
+            }
+            _ => return false,
+        };
+        let Some(to) = self.recipient(todo) else {
+            return false;
+        };
+        let body = match self.render(template, todo) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!(template, error = %err, "failed to render email");
+                return false;
+            }
+        };
+        if !self.allow(&to) {
+            tracing::warn!(%to, todo_id = todo.id, "email rate limit reached, dropping");
+            return false;
+        }
+
+        let mut delay = self.retry_delay;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.mailer.send(&to, &subject, &body).await {
+                Ok(()) => return true,
+                Err(err) if attempt < MAX_ATTEMPTS => {
+                    tracing::warn!(%to, attempt, error = %err, "email failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(err) => tracing::error!(%to, error = %err, "email failed, giving up"),
+            }
+        }
+        false
+    }
+
+    /// Handles events from `events` until `shutdown` resolves.
+    pub async fn run(self, events: EventStream, shutdown: impl Future<Output = ()>) {
+        self.serve(events, shutdown).await;
+    }
+
+    /// Like [`run`](Self::run), for a notifier that is shared so it can be
+    /// [restarted](crate::supervisor).
+    pub async fn serve(&self, mut events: EventStream, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            let event = tokio::select! {
+                _ = &mut shutdown => break,
+                event = events.next() => event,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            self.handle(&event).await;
+        }
+        tracing::info!("notifier stopped");
+    }
+
+    fn recipient(&self, todo: &Todo) -> Option<String> {
+        todo.assignee
+            .as_deref()
+            .filter(|assignee| assignee.parse::<Mailbox>().is_ok())
+            .map(str::to_string)
+            .or_else(|| self.default_to.clone())
+    }
+
+    fn render(&self, template: &str, todo: &Todo) -> Result<String, minijinja::Error> {
+        let due = todo.due.map(|due| due.format("%Y-%m-%d %H:%M UTC").to_string());
+        self.templates
+            .get_template(template)?
+            .render(context! { todo, due })
+    }
+
+    /// Records a send to `to` unless that would exceed the hourly limit.
+    fn allow(&self, to: &str) -> bool {
+        let now = Instant::now();
+        let mut sent = self.sent.lock().expect("rate limit lock poisoned");
+        let times = sent.entry(to.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= RATE_WINDOW)
+        {
+            times.pop_front();
+        }
+        if times.len() >= self.max_per_hour as usize {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
@@ -0,0 +1,452 @@
+//! Durable webhook delivery.
+//!
+//! Bus subscribers only see events published while they are listening, so a
+//! webhook fed straight from the bus loses whatever happens while it is down
+//! or the process restarts. Instead, each event published while
+//! `WEBHOOK_URLS` is set becomes one [`OutboxEntry`] per URL, queued by the
+//! [`Outboxing`] bus. Writes publish while holding the
+//! [`Publishing`](crate::events::Publishing) turn, so by the time a write is
+//! acknowledged its entries are queued, in write order.
+//!
+//! # Delivery
+//!
+//! [`run`] drains the queue with [`Outbox::dispatch`]:
+//!
+//! - At least once: an entry leaves the queue only after its URL answered
+//!   `2xx`, so receivers should dedupe on the `X-Outbox-Id` header. Ids
+//!   are never reissued: they continue from the clock at startup, and from
+//!   the journal's last id when that is higher.
+//! - In order per URL: only the oldest pending entry of each URL is tried,
+//!   so a failing receiver holds back its own entries and nobody else's.
+//! - With backoff: after a failure the entry waits `OUTBOX_BACKOFF_MS`,
+//!   doubling per attempt up to an hour.
+//! - Then dead-lettered: after `OUTBOX_MAX_ATTEMPTS` the entry is marked
+//!   `failed` and the URL moves on. `GET /admin/outbox?state=failed` lists
+//!   those, and `POST /admin/outbox/:id/retry` queues one again, ahead of
+//!   the URL's newer entries.
+//!
+//! # Persistence
+//!
+//! With `OUTBOX_PATH` set, every change is appended to that file as a JSON
+//! line, and [`Outbox::load`] replays it at startup. The file is emptied
+//! whenever the queue drains, all but the last id issued. Appends are not synced to disk, so a
+//! process crash loses nothing but a power cut can. Without `OUTBOX_PATH`
+//! the queue lives in memory and a restart empties it.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{header, Request, Uri},
+};
+use chrono::{DateTime, Utc};
+use hyper::client::conn::http1;
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpStream, sync::Notify};
+
+use crate::{
+    config::Config,
+    errors::AppError,
+    events::{EventBus, EventStream, TodoEvent},
+    state::AppState,
+    timestamps,
+};
+
+/// Header carrying the entry id, for receivers to drop repeats.
+pub const OUTBOX_ID_HEADER: &str = "x-outbox-id";
+
+/// Longest a single delivery may take before it counts as failed.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest wait between two attempts at one entry.
+const MAX_BACKOFF: chrono::Duration = chrono::Duration::hours(1);
+
+/// How often [`run`] looks for entries whose backoff has passed.
+const POLL: Duration = Duration::from_secs(1);
+
+/// Where an entry is in its delivery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    /// Waiting for its turn or its next attempt.
+    Pending,
+    /// Out of attempts; only a manual retry sends it again.
+    Failed,
+}
+
+/// One event waiting to reach one webhook.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// Increasing in queue order.
+    pub id: u64,
+    /// The webhook URL.
+    pub destination: String,
+    pub event: TodoEvent,
+    pub state: DeliveryState,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    /// Not tried again before this; unset means as soon as its turn comes.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "timestamps::option")]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// One line of the `OUTBOX_PATH` journal.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    /// The entry as it now is.
+    Put { entry: Box<OutboxEntry> },
+    /// The entry was delivered.
+    Done { id: u64 },
+    /// Every id up to `id` has been issued. Kept when the queue drains, so
+    /// a restart carries on after it.
+    Issued { id: u64 },
+}
+
+/// Sends one entry to its destination.
+#[async_trait]
+pub trait WebhookSender: Send + Sync + 'static {
+    /// Succeeds only once the receiver has accepted the entry.
+    async fn send(&self, entry: &OutboxEntry) -> anyhow::Result<()>;
+}
+
+/// [`WebhookSender`] that `POST`s the event as JSON over plain HTTP.
+pub struct HttpSender;
+
+#[async_trait]
+impl WebhookSender for HttpSender {
+    async fn send(&self, entry: &OutboxEntry) -> anyhow::Result<()> {
+        let uri: Uri = entry.destination.parse()?;
+        let authority = uri.authority().ok_or_else(|| anyhow!("no host in the URL"))?;
+        let stream = TcpStream::connect((authority.host(), authority.port_u16().unwrap_or(80)))
+            .await
+            .context("connecting")?;
+        let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(connection);
+
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        let request = Request::post(path)
+            .header(header::HOST, authority.as_str())
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(OUTBOX_ID_HEADER, entry.id)
+            .body(Body::from(serde_json::to_vec(&entry.event)?))?;
+        let status = sender.send_request(request).await?.status();
+        if !status.is_success() {
+            bail!("the webhook answered {status}");
+        }
+        Ok(())
+    }
+}
+
+/// The queue of [`OutboxEntry`]s; see the [module docs](self).
+pub struct Outbox {
+    config: Arc<ArcSwap<Config>>,
+    queue: Mutex<Queue>,
+    wake: Notify,
+}
+
+#[derive(Default)]
+struct Queue {
+    next_id: u64,
+    entries: BTreeMap<u64, OutboxEntry>,
+    journal: Option<File>,
+}
+
+impl Queue {
+    fn append(&mut self, record: &Record) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let mut line = serde_json::to_vec(record).expect("outbox records serialize");
+        line.push(b'\n');
+        if let Err(err) = journal.write_all(&line) {
+            tracing::error!(error = %err, "failed to write the outbox journal");
+        }
+    }
+
+    /// Stores `entry` and journals it.
+    fn put(&mut self, entry: OutboxEntry) {
+        self.append(&Record::Put {
+            entry: Box::new(entry.clone()),
+        });
+        self.entries.insert(entry.id, entry);
+    }
+}
+
+impl Outbox {
+    /// An empty in-memory queue that reads `WEBHOOK_URLS` and the retry
+    /// settings from `config` as it goes.
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Self {
+        // Microseconds since the epoch: higher than any id an earlier run
+        // issued, unless it queued a million entries a second or the clock
+        // went back, which the journal covers.
+        let queue = Queue {
+            next_id: Utc::now().timestamp_micros().try_into().unwrap_or(0),
+            ..Queue::default()
+        };
+        Self {
+            config,
+            queue: Mutex::new(queue),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Replays the journal at `path`, if there is one, and appends every
+    /// later change to it. Returns how many entries it held.
+    pub fn load(&self, path: &Path) -> anyhow::Result<usize> {
+        let mut entries = BTreeMap::new();
+        let mut issued = 0;
+        match File::open(path) {
+            Ok(file) => {
+                for (number, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let record = serde_json::from_str(&line).with_context(|| {
+                        format!("{} line {} is not an outbox record", path.display(), number + 1)
+                    })?;
+                    match record {
+                        Record::Put { entry } => {
+                            issued = issued.max(entry.id);
+                            entries.insert(entry.id, *entry);
+                        }
+                        Record::Done { id } => {
+                            entries.remove(&id);
+                        }
+                        Record::Issued { id } => issued = issued.max(id),
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("reading {}", path.display()));
+            }
+        }
+
+        // Rewrite it with just the live entries, so it doesn't keep growing
+        // across restarts.
+        let compacted = path.with_extension("compacting");
+        let mut file = File::create(&compacted)
+            .with_context(|| format!("writing {}", compacted.display()))?;
+        let mut queue = self.queue.lock().unwrap();
+        queue.next_id = queue.next_id.max(issued);
+        let records = std::iter::once(Record::Issued { id: queue.next_id });
+        let puts = entries.values().map(|entry| Record::Put {
+            entry: Box::new(entry.clone()),
+        });
+        for record in records.chain(puts) {
+            serde_json::to_writer(&mut file, &record)?;
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+        fs::rename(&compacted, path).with_context(|| format!("replacing {}", path.display()))?;
+        let journal = OpenOptions::new().append(true).open(path)?;
+
+        let loaded = entries.len();
+        queue.entries.extend(entries);
+        queue.journal = Some(journal);
+        Ok(loaded)
+    }
+
+    /// Queues `event` once for every configured webhook.
+    pub fn enqueue(&self, event: &TodoEvent) {
+        let config = self.config.load();
+        if config.webhook_urls.is_empty() {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        for url in &config.webhook_urls {
+            queue.next_id += 1;
+            let entry = OutboxEntry {
+                id: queue.next_id,
+                destination: url.clone(),
+                event: event.clone(),
+                state: DeliveryState::Pending,
+                attempts: 0,
+                next_attempt_at: None,
+                last_error: None,
+            };
+            queue.put(entry);
+        }
+        drop(queue);
+        self.wake.notify_one();
+    }
+
+    /// Queued entries in queue order, only those in `state` if given.
+    pub fn entries(&self, state: Option<DeliveryState>) -> Vec<OutboxEntry> {
+        let queue = self.queue.lock().unwrap();
+        queue
+            .entries
+            .values()
+            .filter(|entry| state.is_none_or(|state| entry.state == state))
+            .cloned()
+            .collect()
+    }
+
+    /// Sends entry `id` again at the next chance, with a fresh set of
+    /// attempts if it had failed.
+    pub fn retry(&self, id: u64) -> Result<OutboxEntry, AppError> {
+        let mut queue = self.queue.lock().unwrap();
+        let mut entry = queue.entries.get(&id).cloned().ok_or(AppError::NotFound)?;
+        if entry.state == DeliveryState::Failed {
+            entry.state = DeliveryState::Pending;
+            entry.attempts = 0;
+        }
+        entry.next_attempt_at = None;
+        queue.put(entry.clone());
+        drop(queue);
+        self.wake.notify_one();
+        Ok(entry)
+    }
+
+    /// Tries every destination's oldest pending entry that is due at `now`,
+    /// and keeps going until none is. Returns how many were delivered.
+    pub async fn dispatch(&self, sender: &dyn WebhookSender, now: DateTime<Utc>) -> usize {
+        let mut delivered = 0;
+        loop {
+            let due = self.due(now);
+            if due.is_empty() {
+                return delivered;
+            }
+            let attempts = due.iter().map(|entry| async move {
+                match tokio::time::timeout(TIMEOUT, sender.send(entry)).await {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(err)) => Err(format!("{err:#}")),
+                    Err(_) => Err("timed out".to_string()),
+                }
+            });
+            let results = futures::future::join_all(attempts).await;
+            for (entry, result) in due.iter().zip(results) {
+                match result {
+                    Ok(()) => {
+                        self.delivered(entry.id);
+                        delivered += 1;
+                    }
+                    Err(error) => self.failed(entry.id, error, now),
+                }
+            }
+        }
+    }
+
+    /// The head of each destination's pending entries, when due at `now`.
+    fn due(&self, now: DateTime<Utc>) -> Vec<OutboxEntry> {
+        let queue = self.queue.lock().unwrap();
+        let mut seen = HashSet::new();
+        queue
+            .entries
+            .values()
+            .filter(|entry| entry.state == DeliveryState::Pending)
+            .filter(|entry| seen.insert(entry.destination.as_str()))
+            .filter(|entry| entry.next_attempt_at.is_none_or(|at| at <= now))
+            .cloned()
+            .collect()
+    }
+
+    fn delivered(&self, id: u64) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.entries.remove(&id).is_none() {
+            return;
+        }
+        if queue.entries.is_empty() {
+            if let Some(journal) = &queue.journal {
+                if let Err(err) = journal.set_len(0) {
+                    tracing::error!(error = %err, "failed to empty the outbox journal");
+                }
+            }
+            let id = queue.next_id;
+            queue.append(&Record::Issued { id });
+        } else {
+            queue.append(&Record::Done { id });
+        }
+    }
+
+    fn failed(&self, id: u64, error: String, now: DateTime<Utc>) {
+        let config = self.config.load();
+        let mut queue = self.queue.lock().unwrap();
+        let Some(mut entry) = queue.entries.get(&id).cloned() else {
+            return;
+        };
+        entry.attempts += 1;
+        tracing::warn!(
+            id,
+            destination = %entry.destination,
+            attempts = entry.attempts,
+            error = %error,
+            "webhook delivery failed"
+        );
+        if entry.attempts >= config.outbox_max_attempts {
+            tracing::error!(id, destination = %entry.destination, "webhook entry dead-lettered");
+            entry.state = DeliveryState::Failed;
+            entry.next_attempt_at = None;
+        } else {
+            let base = chrono::Duration::milliseconds(config.outbox_backoff_ms as i64);
+            let doublings = (entry.attempts - 1).min(30);
+            let backoff = base.checked_mul(1 << doublings).unwrap_or(MAX_BACKOFF);
+            entry.next_attempt_at = Some(now + backoff.min(MAX_BACKOFF));
+        }
+        entry.last_error = Some(error);
+        queue.put(entry);
+    }
+}
+
+/// [`EventBus`] that also queues every event it publishes in an [`Outbox`].
+pub struct Outboxing {
+    inner: Arc<dyn EventBus>,
+    outbox: Arc<Outbox>,
+}
+
+impl Outboxing {
+    pub fn new(inner: Arc<dyn EventBus>, outbox: Arc<Outbox>) -> Self {
+        Self { inner, outbox }
+    }
+}
+
+impl EventBus for Outboxing {
+    fn publish(&self, event: TodoEvent) {
+        self.outbox.enqueue(&event);
+        self.inner.publish(event);
+    }
+
+    fn subscribe(&self) -> EventStream {
+        self.inner.subscribe()
+    }
+
+    fn subscriber_count(&self) -> usize {
+        self.inner.subscriber_count()
+    }
+}
+
+/// Delivers the state's outbox through `sender`, with the time from the
+/// state's [clock](crate::clock), until `shutdown` resolves.
+pub async fn run(
+    state: AppState,
+    sender: Arc<dyn WebhookSender>,
+    shutdown: impl Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    loop {
+        let outbox = state.outbox();
+        outbox.dispatch(sender.as_ref(), state.clock().now()).await;
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = outbox.wake.notified() => {}
+            _ = tokio::time::sleep(POLL) => {}
+        }
+    }
+    tracing::info!("outbox dispatcher stopped");
+}
@@ -0,0 +1,72 @@
+//! Long polling for clients that can't hold an event stream open.
+//!
+//! Some proxies buffer or cut server-sent events, so `GET /todos/poll`
+//! offers the same near-real-time updates over plain requests. A poll names
+//! the revision the client last synced; when the collection has moved past
+//! it, [`wait_for_change`] answers straight away with the
+//! [delta](crate::models::Changes) since. Otherwise the request is parked on
+//! an [event](crate::events) subscription until a change lands or the
+//! timeout elapses, when it answers with nothing changed and the same
+//! revision, and the client simply polls again.
+//!
+//! Parked polls are released as soon as the state is
+//! [shut down](crate::state::AppState::begin_shutdown), so a graceful
+//! shutdown doesn't wait out their timeouts. `REQUEST_TIMEOUT_MS` still
+//! applies: keep it above the timeouts clients ask for.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+
+use crate::{errors::AppError, models::Changes, state::AppState};
+
+/// How long a poll waits when it doesn't say.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Longest wait a poll may ask for.
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The wait asked for with `?timeout_secs=`, [`DEFAULT_TIMEOUT`] without one.
+pub fn timeout(secs: Option<u64>) -> Result<Duration, AppError> {
+    let Some(secs) = secs else {
+        return Ok(DEFAULT_TIMEOUT);
+    };
+    let timeout = Duration::from_secs(secs);
+    if timeout > MAX_TIMEOUT {
+        let max = MAX_TIMEOUT.as_secs();
+        return Err(AppError::Validation(
+            format!("timeout_secs must be at most {max}").into(),
+        ));
+    }
+    Ok(timeout)
+}
+
+/// What changed after revision `since`, waiting up to `timeout` for a change
+/// if nothing has yet.
+pub async fn wait_for_change(
+    app: &AppState,
+    since: u64,
+    timeout: Duration,
+) -> Result<Changes, AppError> {
+    // Subscribe before reading, so a change landing in between still wakes
+    // the poll.
+    let mut events = app.events().subscribe();
+    let mut changes = app.service().changes_since(since).await?;
+    let stopping = app.shutting_down();
+    let expired = tokio::time::sleep(timeout);
+    tokio::pin!(expired);
+    // Not every event moves the revision, so keep waiting until one does.
+    while changes.revision <= since {
+        tokio::select! {
+            event = events.next() => {
+                if event.is_none() {
+                    break;
+                }
+            }
+            _ = &mut expired => break,
+            _ = stopping.cancelled() => break,
+        }
+        changes = app.service().changes_since(since).await?;
+    }
+    Ok(changes)
+}
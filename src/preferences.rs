@@ -0,0 +1,47 @@
+//! Per-user defaults for listing todos.
+//!
+//! `PUT /preferences` stores a [`Preferences`] and `GET /todos` falls back
+//! on it for the sort order, page size, and `done` filter a request leaves
+//! out. Explicit query parameters always win.
+//!
+//! Requests aren't authenticated yet, so everyone shares the [`ANONYMOUS`]
+//! profile. The store is keyed by user already, so per-user profiles need no
+//! storage change once they are.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::{errors::AppError, models::Preferences};
+
+/// Profile used for unauthenticated requests.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Storage for [`Preferences`], by user.
+#[async_trait]
+pub trait PreferencesRepo: Send + Sync + 'static {
+    /// `None` when `user` never saved any.
+    async fn get(&self, user: &str) -> Result<Option<Preferences>, AppError>;
+
+    /// Replaces `user`'s preferences.
+    async fn put(&self, user: &str, preferences: Preferences) -> Result<(), AppError>;
+}
+
+/// [`PreferencesRepo`] that keeps everything in process memory.
+#[derive(Default)]
+pub struct InMemoryPreferences {
+    profiles: RwLock<HashMap<String, Preferences>>,
+}
+
+#[async_trait]
+impl PreferencesRepo for InMemoryPreferences {
+    async fn get(&self, user: &str) -> Result<Option<Preferences>, AppError> {
+        Ok(self.profiles.read().await.get(user).cloned())
+    }
+
+    async fn put(&self, user: &str, preferences: Preferences) -> Result<(), AppError> {
+        self.profiles.write().await.insert(user.to_string(), preferences);
+        Ok(())
+    }
+}
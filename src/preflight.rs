@@ -0,0 +1,125 @@
+//! Startup self-check.
+//!
+//! `rust-api --check` (or `APP_CHECK=1`) loads the configuration, runs
+//! [`run`], prints the [`Report`], and exits without binding a listener: 0
+//! when every check passed, 1 otherwise. Deploy pipelines use it to reject a
+//! release before it receives traffic.
+//!
+//! Each check runs even when an earlier one failed, so one run names every
+//! problem. Checks that reach the network give up after [`TIMEOUT`].
+
+use std::{fmt, future::Future, path::Path, time::Duration};
+
+use crate::{config::Config, state::TodoRepo};
+
+/// How long a single check may take.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one check.
+#[derive(Debug)]
+pub struct Check {
+    /// Short, stable name such as `repository`.
+    pub name: &'static str,
+    /// Why it failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Every check that ran, in order.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| check.error.is_some())
+    }
+
+    fn record(&mut self, name: &'static str, result: Result<(), String>) {
+        self.checks.push(Check {
+            name,
+            error: result.err(),
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    /// One line per check: `ok    repository` or `FAIL  repository: why`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.error {
+                None => writeln!(f, "ok    {}", check.name)?,
+                Some(error) => writeln!(f, "FAIL  {}: {error}", check.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks everything the server needs from its environment: the repository,
+/// the attachment directory, and the optional SMTP and Redis settings when
+/// they are configured and compiled in.
+pub async fn run(config: &Config, repo: &dyn TodoRepo) -> Report {
+    let mut report = Report::default();
+
+    report.record("repository", repository(repo).await);
+    report.record("attachment_dir", writable(&config.attachment_dir).await);
+
+    #[cfg(feature = "email")]
+    if let (Some(url), Some(from)) = (&config.smtp_url, &config.smtp_from) {
+        let mailer = crate::notify::SmtpMailer::new(url.expose(), from);
+        report.record("smtp", mailer.map(drop).map_err(|err| format!("{err:#}")));
+    }
+
+    #[cfg(feature = "redis")]
+    {
+        if let Some(url) = &config.event_bus_url {
+            report.record("event_bus", within_timeout(redis_ping(url.expose())).await);
+        }
+        if let Some(url) = &config.rate_limit_url {
+            report.record("rate_limit_store", within_timeout(redis_ping(url.expose())).await);
+        }
+    }
+
+    report
+}
+
+/// Whether `repo` answers a ping within [`TIMEOUT`]. Startup waits for this
+/// before binding, and `GET /ready` reports it.
+pub async fn repository(repo: &dyn TodoRepo) -> Result<(), String> {
+    within_timeout(async { repo.ping().await.map_err(|err| err.to_string()) }).await
+}
+
+async fn within_timeout(check: impl Future<Output = Result<(), String>>) -> Result<(), String> {
+    tokio::time::timeout(TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("no answer within {}s", TIMEOUT.as_secs())))
+}
+
+/// Creates `dir` if needed and writes and removes a probe file in it, the way
+/// uploads will.
+async fn writable(dir: &Path) -> Result<(), String> {
+    let failed = |err: std::io::Error| format!("{}: {err}", dir.display());
+    tokio::fs::create_dir_all(dir).await.map_err(failed)?;
+    let probe = dir.join(".preflight");
+    tokio::fs::write(&probe, b"").await.map_err(failed)?;
+    tokio::fs::remove_file(&probe).await.map_err(failed)
+}
+
+#[cfg(feature = "redis")]
+async fn redis_ping(url: &str) -> Result<(), String> {
+    let client = redis::Client::open(url).map_err(|err| err.to_string())?;
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|err| err.to_string())?;
+    let _: String = redis::cmd("PING")
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
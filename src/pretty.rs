@@ -0,0 +1,114 @@
+//! Indented JSON for people reading responses in a terminal.
+//!
+//! `?pretty=true` re-indents a JSON response the way
+//! `serde_json::to_string_pretty` would have written it; `PRETTY_JSON_DEFAULT`
+//! turns that on for every request (handy in development) and `?pretty=false`
+//! opts back out. Like the [envelope](crate::envelope), it is applied once on
+//! the way out, so handlers and error bodies need no changes.
+//!
+//! Only `application/json` bodies are touched: MessagePack, text, and HTML
+//! responses pass through as they are. The body is re-indented token by token
+//! rather than parsed, so field order and number formatting stay exactly as
+//! serialized. Streamed lists are buffered first, which is fine for a
+//! development aid but a reason to leave it off in production.
+
+use axum::{
+    body::Body,
+    extract::{Query, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+struct PrettyQuery {
+    pretty: Option<bool>,
+}
+
+/// Re-indents JSON responses when the config or the request asks for it.
+pub async fn pretty(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let wanted = Query::<PrettyQuery>::try_from_uri(req.uri())
+        .ok()
+        .and_then(|Query(query)| query.pretty)
+        .unwrap_or_else(|| state.config().pretty_json_default);
+    let res = next.run(req).await;
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !wanted || !is_json {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to buffer response for pretty-printing");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(indent(&bytes)))
+}
+
+/// Re-indents JSON with two spaces per level, matching
+/// `serde_json::to_string_pretty`. Whitespace between tokens is dropped
+/// first; strings are copied byte for byte.
+pub fn indent(json: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(json.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        out.push(b'\n');
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+    };
+
+    let mut bytes = json.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if in_string {
+            out.push(byte);
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => {
+                in_string = true;
+                out.push(byte);
+            }
+            b'{' | b'[' => {
+                out.push(byte);
+                // Empty containers stay on one line: `[]`, `{}`.
+                if matches!(bytes.peek(), Some(b'}' | b']')) {
+                    out.push(bytes.next().expect("peeked"));
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(byte);
+            }
+            b',' => {
+                out.push(byte);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            b' ' | b'\n' | b'\r' | b'\t' => {}
+            _ => out.push(byte),
+        }
+    }
+    out
+}
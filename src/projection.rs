@@ -0,0 +1,106 @@
+//! `?fields=` projection of todos.
+//!
+//! `GET /todos?fields=id,done` returns each todo with just those fields, so a
+//! list view on a slow network doesn't pay for titles and descriptions it
+//! never shows. `id` is always included. Names that aren't todo fields are a
+//! `400` listing the valid ones.
+//!
+//! Projection happens after serialization: the todo is turned into a JSON
+//! value and the other keys are dropped. Keys that aren't todo fields, such as
+//! `description_html` from `?render=html`, are left alone, since the client
+//! asked for them separately. The text and HTML formats are for people and
+//! always show the whole todo.
+
+use serde::{ser::Error as _, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{errors::AppError, render::Render};
+
+/// Every field of a serialized [`Todo`](crate::models::Todo).
+pub const FIELDS: [&str; 11] = [
+    "id",
+    "title",
+    "description",
+    "done",
+    "done_at",
+    "due",
+    "assignee",
+    "color",
+    "reminded_at",
+    "created_at",
+    "updated_at",
+];
+
+/// The todo fields a client asked for, as a bitset over [`FIELDS`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Projection(u16);
+
+impl Projection {
+    /// Parses a comma-separated `?fields=` value. `None` means every field.
+    pub fn parse(raw: Option<&str>) -> Result<Option<Self>, AppError> {
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+        // `id` is bit 0.
+        let mut bits = 1;
+        for name in raw.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let index = FIELDS.iter().position(|field| *field == name).ok_or_else(|| {
+                AppError::Validation(format!(
+                    "unknown field `{name}` in fields, expected any of: {}",
+                    FIELDS.join(", ")
+                )
+                .into())
+            })?;
+            bits |= 1 << index;
+        }
+        Ok(Some(Self(bits)))
+    }
+
+    fn keeps(self, key: &str) -> bool {
+        FIELDS
+            .iter()
+            .position(|field| *field == key)
+            .is_none_or(|index| self.0 & (1 << index) != 0)
+    }
+
+    fn apply(self, value: &mut Value) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.apply(item)),
+            Value::Object(object) => object.retain(|key, _| self.keeps(key)),
+            _ => {}
+        }
+    }
+}
+
+/// A todo, or list of todos, serialized with only the requested fields.
+pub struct Projected<T> {
+    pub value: T,
+    pub fields: Option<Projection>,
+}
+
+impl<T> Projected<T> {
+    pub fn new(value: T, fields: Option<Projection>) -> Self {
+        Self { value, fields }
+    }
+}
+
+impl<T: Serialize> Serialize for Projected<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = self.fields else {
+            return self.value.serialize(serializer);
+        };
+        let mut value = serde_json::to_value(&self.value).map_err(S::Error::custom)?;
+        fields.apply(&mut value);
+        value.serialize(serializer)
+    }
+}
+
+impl<T: Render> Render for Projected<T> {
+    fn text(&self) -> String {
+        self.value.text()
+    }
+
+    fn html(&self) -> String {
+        self.value.html()
+    }
+}
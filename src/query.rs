@@ -0,0 +1,105 @@
+//! Query string extraction with one policy for repeated parameters.
+//!
+//! Frameworks disagree on `?done=true&done=false`: some take the first value,
+//! some the last, axum's `Query` fails only for some field types. Every
+//! handler here reads its query through [`AppQuery`] instead, which
+//!
+//! - rejects a repeated scalar parameter with a `400` naming it, and
+//! - accumulates the parameters a query type lists in
+//!   [`QueryParams::REPEATABLE`]: `?ids=1,2&ids=3` is the same as `?ids=1,2,3`.
+//!   At most [`MAX_REPEATED_VALUES`] values are accepted per parameter.
+//!
+//! Repeatable fields deserialize with [`comma_separated`].
+
+use std::{fmt::Display, str::FromStr};
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::errors::AppError;
+
+/// Most values one repeatable parameter may carry, across repeats and commas.
+pub const MAX_REPEATED_VALUES: usize = 100;
+
+/// A query string type [`AppQuery`] can extract.
+pub trait QueryParams: DeserializeOwned {
+    /// Parameters that may be given more than once.
+    const REPEATABLE: &'static [&'static str] = &[];
+}
+
+/// Like `axum::extract::Query`, but enforcing the module's rules for
+/// repeated parameters and rejecting with [`AppError::Validation`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AppQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for AppQuery<T>
+where
+    T: QueryParams,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let merged = merge(parts.uri.query().unwrap_or_default(), T::REPEATABLE)?;
+        serde_urlencoded::from_str(&merged).map(AppQuery).map_err(|err| {
+            AppError::Validation(format!("Failed to deserialize query string: {err}").into())
+        })
+    }
+}
+
+/// Re-encodes `raw` with each repeatable parameter joined into one
+/// comma-separated value, failing on repeated scalars.
+fn merge(raw: &str, repeatable: &[&str]) -> Result<String, AppError> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(raw).map_err(|err| {
+        AppError::Validation(format!("Failed to deserialize query string: {err}").into())
+    })?;
+    let mut merged: Vec<(String, String)> = Vec::with_capacity(pairs.len());
+    for (key, value) in pairs {
+        let Some(index) = merged.iter().position(|(seen, _)| *seen == key) else {
+            merged.push((key, value));
+            continue;
+        };
+        if !repeatable.contains(&key.as_str()) {
+            return Err(AppError::Validation(
+                format!("query parameter `{key}` may only be given once").into(),
+            ));
+        }
+        let joined = &mut merged[index].1;
+        joined.push(',');
+        joined.push_str(&value);
+    }
+    for (key, value) in &merged {
+        let count = values(value).count();
+        if repeatable.contains(&key.as_str()) && count > MAX_REPEATED_VALUES {
+            return Err(AppError::Validation(
+                format!("`{key}` takes at most {MAX_REPEATED_VALUES} values, got {count}").into(),
+            ));
+        }
+    }
+    Ok(serde_urlencoded::to_string(&merged).expect("string pairs always encode"))
+}
+
+fn values(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split(',').map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Deserializes `1,2,3` into `Some(vec![1, 2, 3])`, for use with
+/// `#[serde(default, deserialize_with = "...")]` on repeatable fields.
+pub fn comma_separated<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let raw = String::deserialize(deserializer)?;
+    values(&raw)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| de::Error::custom(format!("invalid value `{value}`: {err}")))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
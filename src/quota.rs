@@ -0,0 +1,95 @@
+//! The `MAX_TODOS` quota.
+//!
+//! With `MAX_TODOS` set, creates that would take the store past it fail with
+//! `409` and code `quota_exceeded`; the body's `quota` says how many todos
+//! the request was over by. So clients can warn before that happens,
+//! successful creates and `GET /todos/stats/timeseries` carry the standing:
+//!
+//! - `X-Quota-Limit`: `MAX_TODOS`.
+//! - `X-Quota-Remaining`: todos that can still be created.
+//! - `X-Quota-Warning: true`: what remains is under `QUOTA_WARNING_PERCENT`
+//!   of the limit.
+//!
+//! Creates include `PUT`s that create under a client-chosen id and merge
+//! imports; a replace import restores a known state and is exempt.
+//!
+//! Todos have no owner, so the quota covers the whole store. The check and
+//! the create run under one lock in the [service](crate::service), so
+//! concurrent creates can't both take the last slot.
+
+use axum::{
+    http::HeaderValue,
+    response::{IntoResponseParts, ResponseParts},
+};
+use serde::Serialize;
+
+use crate::{config::Config, errors::AppError};
+
+pub const LIMIT_HEADER: &str = "x-quota-limit";
+pub const REMAINING_HEADER: &str = "x-quota-remaining";
+pub const WARNING_HEADER: &str = "x-quota-warning";
+
+/// How much of the quota is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: usize,
+    pub used: usize,
+    warning_percent: u8,
+}
+
+/// Error details for a create the quota refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Overage {
+    pub limit: usize,
+    pub used: usize,
+    /// Todos past the limit the request would have left the store at.
+    pub over: usize,
+}
+
+impl Quota {
+    /// The standing with `used` todos stored; `None` when `MAX_TODOS` is off.
+    pub fn new(config: &Config, used: usize) -> Option<Self> {
+        (config.max_todos > 0).then_some(Self {
+            limit: config.max_todos,
+            used,
+            warning_percent: config.quota_warning_percent,
+        })
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Whether less than `QUOTA_WARNING_PERCENT` of the limit remains.
+    pub fn warning(&self) -> bool {
+        self.remaining() * 100 < self.limit * usize::from(self.warning_percent)
+    }
+
+    /// The standing after creating `count` more todos, or
+    /// [`AppError::QuotaExceeded`] if they don't fit.
+    pub fn admit(self, count: usize) -> Result<Self, AppError> {
+        let used = self.used + count;
+        if used > self.limit {
+            return Err(AppError::QuotaExceeded(Overage {
+                limit: self.limit,
+                used: self.used,
+                over: used - self.limit,
+            }));
+        }
+        Ok(Self { used, ..self })
+    }
+}
+
+impl IntoResponseParts for Quota {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let headers = res.headers_mut();
+        headers.insert(LIMIT_HEADER, self.limit.into());
+        headers.insert(REMAINING_HEADER, self.remaining().into());
+        if self.warning() {
+            headers.insert(WARNING_HEADER, HeaderValue::from_static("true"));
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,116 @@
+//! Per-client request budgeting.
+//!
+//! # Fixed windows
+//!
+//! The limiter counts requests per client key inside one-minute windows (the
+//! [import budget](crate::budgets) uses hour-long ones). When a window is
+//! full, the caller learns how many seconds remain until it resets.
+//! Fixed windows allow short bursts at window edges, which is an acceptable
+//! trade-off for the tiny amount of state they need.
+//!
+//! The limit itself is *not* stored here: the middleware passes the current
+//! value from the hot-reloadable config on every call, so a SIGHUP reload
+//! takes effect on the very next request.
+//!
+//! # Stores
+//!
+//! The counters live behind [`RateLimitStore`]. [`RateLimiter`], the default,
+//! keeps them in process memory, so each replica enforces the limit on its
+//! own. With the `redis` feature, `RATE_LIMIT_URL` selects
+//! [`redis::RedisRateLimitStore`], which shares them between replicas. When
+//! the store can't be reached, `RATE_LIMIT_FAIL_OPEN` decides whether requests
+//! are let through or refused.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+#[cfg(feature = "redis")]
+pub mod redis;
+
+/// Length of one counting window.
+pub const WINDOW: Duration = Duration::from_secs(60);
+
+/// Upper bound on tracked clients before stale windows are swept.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Where request counts are kept.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Records one request for `key`. The inner result is `Err(retry_after_secs)`
+    /// when the client already used `limit` requests in the current window;
+    /// the outer one fails only when the store itself is unavailable.
+    async fn hit(&self, key: &str, limit: u32) -> anyhow::Result<Result<(), u64>>;
+}
+
+/// In-process [`RateLimitStore`].
+pub struct RateLimiter {
+    windows: Mutex<HashMap<String, Window>>,
+    window: Duration,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::with_window(WINDOW)
+    }
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+impl RateLimiter {
+    /// A limiter counting in windows of `window` instead of [`WINDOW`].
+    pub fn with_window(window: Duration) -> Self {
+        Self {
+            windows: Mutex::default(),
+            window,
+        }
+    }
+
+    /// Records one request for `key`. Returns `Err(retry_after_secs)` when the
+    /// client already used `limit` requests in the current window.
+    pub fn check(&self, key: &str, limit: u32, now: Instant) -> Result<(), u64> {
+        let mut windows = self.windows.lock().expect("rate limiter lock poisoned");
+
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, window| now.duration_since(window.started) < self.window);
+        }
+
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+
+        let elapsed = now.duration_since(window.started);
+        if elapsed >= self.window {
+            window.started = now;
+            window.count = 0;
+        }
+
+        if window.count >= limit {
+            return Err(retry_after(self.window.saturating_sub(elapsed)));
+        }
+
+        window.count += 1;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for RateLimiter {
+    async fn hit(&self, key: &str, limit: u32) -> anyhow::Result<Result<(), u64>> {
+        Ok(self.check(key, limit, Instant::now()))
+    }
+}
+
+/// Whole seconds until `remaining` has passed, rounded up so clients never
+/// retry a fraction of a second too early.
+fn retry_after(remaining: Duration) -> u64 {
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
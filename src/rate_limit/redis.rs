@@ -0,0 +1,89 @@
+//! [`RateLimitStore`] shared between replicas through Redis.
+//!
+//! Each client gets a counter key that lives for one [`WINDOW`]. A single
+//! `MULTI` creates it with its expiry if absent (`SET NX PX`), increments it,
+//! and reads the remaining lifetime, so concurrent replicas never lose a hit
+//! or leave a counter without an expiry.
+//!
+//! Every command is bounded by [`TIMEOUT`] so a stalled server costs requests
+//! a short delay rather than hanging them. A failed connection is dropped and
+//! re-established on the next request.
+
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use redis::{aio::MultiplexedConnection, Client};
+
+use super::{retry_after, RateLimitStore, WINDOW};
+
+/// Prefix of the counter keys.
+pub const KEY_PREFIX: &str = "rust-api:rate:";
+
+/// Longest a single check may take before the store counts as unavailable.
+pub const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// [`RateLimitStore`] backed by Redis.
+pub struct RedisRateLimitStore {
+    client: Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisRateLimitStore {
+    /// Validates `url` (e.g. `redis://localhost:6379`). The connection is
+    /// opened on first use, so the server need not be up yet.
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(url)?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn connection(&self) -> redis::RedisResult<MultiplexedConnection> {
+        let cached = self.connection.lock().expect("redis connection lock poisoned").clone();
+        if let Some(conn) = cached {
+            return Ok(conn);
+        }
+        let conn = self.client.get_multiplexed_async_connection().await?;
+        *self.connection.lock().expect("redis connection lock poisoned") = Some(conn.clone());
+        Ok(conn)
+    }
+
+    async fn count(&self, key: &str) -> redis::RedisResult<(u32, i64)> {
+        let mut conn = self.connection().await?;
+        let window_ms = WINDOW.as_millis() as u64;
+        let result = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(key)
+            .arg(0)
+            .arg("NX")
+            .arg("PX")
+            .arg(window_ms)
+            .ignore()
+            .incr(key, 1)
+            .pttl(key)
+            .query_async(&mut conn)
+            .await;
+        if result.is_err() {
+            *self.connection.lock().expect("redis connection lock poisoned") = None;
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn hit(&self, key: &str, limit: u32) -> anyhow::Result<Result<(), u64>> {
+        let key = format!("{KEY_PREFIX}{key}");
+        let (count, ttl_ms) = tokio::time::timeout(TIMEOUT, self.count(&key))
+            .await
+            .context("redis rate limit check timed out")??;
+        if count <= limit {
+            return Ok(Ok(()));
+        }
+        // A negative TTL means the key just expired; the next window is open.
+        let remaining = Duration::from_millis(ttl_ms.max(0) as u64);
+        Ok(Err(retry_after(remaining).max(1)))
+    }
+}
@@ -0,0 +1,75 @@
+//! Telling supervisors the server is accepting connections.
+//!
+//! A started process isn't a ready one: configuration is still loading and
+//! the port isn't bound yet. Once the listener is bound, `main` calls
+//! [`announce`], which
+//!
+//! - with `READY_ANNOUNCE=true`, prints one line to stdout:
+//!   `{"event":"listening","addr":"127.0.0.1:8080","pid":4242}`. Logs go to
+//!   stderr in that mode, so a harness can read stdout line by line until it
+//!   sees the event instead of sleeping.
+//! - with the `systemd` feature on Linux, sends `READY=1` for `Type=notify`
+//!   units. [`stopping`] sends `STOPPING=1` when shutdown begins. Both do
+//!   nothing without `NOTIFY_SOCKET`, or without the feature.
+
+use std::net::SocketAddr;
+
+use serde::Serialize;
+
+use crate::config::Config;
+
+/// The line [`announce`] prints.
+#[derive(Debug, Serialize)]
+pub struct Listening {
+    /// Always `"listening"`.
+    pub event: &'static str,
+    pub addr: SocketAddr,
+    pub pid: u32,
+}
+
+impl Listening {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            event: "listening",
+            addr,
+            pid: std::process::id(),
+        }
+    }
+}
+
+/// Reports that the listener on `addr` is accepting connections.
+pub fn announce(addr: SocketAddr, config: &Config) {
+    if config.ready_announce {
+        let line = serde_json::to_string(&Listening::new(addr)).expect("plain fields serialize");
+        println!("{line}");
+    }
+    notify(Phase::Ready);
+}
+
+/// Reports that the server stopped accepting work and is draining.
+pub fn stopping() {
+    notify(Phase::Stopping);
+}
+
+enum Phase {
+    Ready,
+    Stopping,
+}
+
+#[cfg(all(feature = "systemd", target_os = "linux"))]
+fn notify(phase: Phase) {
+    use sd_notify::NotifyState;
+
+    let state = match phase {
+        Phase::Ready => NotifyState::Ready,
+        Phase::Stopping => NotifyState::Stopping,
+    };
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        tracing::warn!(error = %err, "failed to notify systemd");
+    }
+}
+
+#[cfg(not(all(feature = "systemd", target_os = "linux")))]
+fn notify(phase: Phase) {
+    let _ = phase;
+}
@@ -0,0 +1,582 @@
+//! Runtime configuration reload.
+//!
+//! On SIGHUP the binary re-reads its configuration and hands it to
+//! [`Reloader::apply`]. Settings that are consulted per request (log filter,
+//! rate limit, read-only flag, CORS origins, route policies and API keys) take
+//! effect immediately. Settings baked in at startup (bind address, secrets, span
+//! export) are kept at their old values and reported as needing a restart.
+//!
+//! # Why `.env` wins on reload
+//!
+//! The environment of a running process cannot be changed from outside, so the
+//! only source that can carry new values is the `.env` file. On reload it is
+//! re-read and its values take precedence over the inherited environment.
+
+use std::{collections::HashMap, env, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use tracing_subscriber::EnvFilter;
+
+use crate::{config::Config, flags::Flag, state::AppState, telemetry::LogFilterHandle};
+
+/// What a reload changed, mostly useful for logging and tests.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Variables whose new values are now in effect.
+    pub applied: Vec<&'static str>,
+    /// Variables that changed but only take effect after a restart.
+    pub requires_restart: Vec<&'static str>,
+}
+
+pub struct Reloader {
+    state: AppState,
+    log_filter: Option<LogFilterHandle>,
+}
+
+impl Reloader {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            log_filter: None,
+        }
+    }
+
+    /// Lets reloads swap the active `RUST_LOG` filter.
+    pub fn with_log_filter(mut self, handle: LogFilterHandle) -> Self {
+        self.log_filter = Some(handle);
+        self
+    }
+
+    /// Re-reads `.env` plus the process environment and applies the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns the validation error if the new configuration is invalid; the
+    /// running configuration is left untouched in that case.
+    pub fn reload_from_env(&self) -> anyhow::Result<ReloadReport> {
+        let file: HashMap<String, String> = dotenvy::dotenv_iter()
+            .map(|iter| iter.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+
+        let next =
+            Config::from_lookup(|key| file.get(key).cloned().or_else(|| env::var(key).ok()))?;
+        Ok(self.apply(next))
+    }
+
+    /// Publishes the hot-reloadable parts of `next`.
+    pub fn apply(&self, mut next: Config) -> ReloadReport {
+        let current = self.state.config();
+        let mut report = ReloadReport::default();
+
+        // Baked in at startup: keep the running values so the published config
+        // never claims something that isn't true.
+        if next.server_addr != current.server_addr {
+            report.requires_restart.push("HOST/PORT");
+            next.server_addr = current.server_addr;
+        }
+        if next.ready_announce != current.ready_announce {
+            report.requires_restart.push("READY_ANNOUNCE");
+            next.ready_announce = current.ready_announce;
+        }
+        if next.startup_wait_secs != current.startup_wait_secs {
+            report.requires_restart.push("STARTUP_WAIT_SECS");
+            next.startup_wait_secs = current.startup_wait_secs;
+        }
+        if next.id_strategy != current.id_strategy {
+            report.requires_restart.push("ID_STRATEGY");
+            next.id_strategy = current.id_strategy;
+        }
+        if next.node_id != current.node_id {
+            report.requires_restart.push("NODE_ID");
+            next.node_id = current.node_id;
+        }
+        if next.jwt_secret != current.jwt_secret {
+            report.requires_restart.push("JWT_SECRET");
+            next.jwt_secret = current.jwt_secret.clone();
+        }
+        // A new key would break every share link already handed out.
+        if next.share_secret != current.share_secret {
+            report.requires_restart.push("SHARE_SECRET");
+            next.share_secret = current.share_secret.clone();
+        }
+        if next.grpc_addr != current.grpc_addr {
+            report.requires_restart.push("GRPC_ADDR");
+            next.grpc_addr = current.grpc_addr;
+        }
+        if next.attachment_dir != current.attachment_dir {
+            report.requires_restart.push("ATTACHMENT_DIR");
+            next.attachment_dir = current.attachment_dir.clone();
+        }
+        if next.smtp_url != current.smtp_url {
+            report.requires_restart.push("SMTP_URL");
+            next.smtp_url = current.smtp_url.clone();
+        }
+        if next.smtp_from != current.smtp_from {
+            report.requires_restart.push("SMTP_FROM");
+            next.smtp_from = current.smtp_from.clone();
+        }
+        if next.email_default_to != current.email_default_to {
+            report.requires_restart.push("EMAIL_DEFAULT_TO");
+            next.email_default_to = current.email_default_to.clone();
+        }
+        if next.email_max_per_hour != current.email_max_per_hour {
+            report.requires_restart.push("EMAIL_MAX_PER_HOUR");
+            next.email_max_per_hour = current.email_max_per_hour;
+        }
+        if next.rate_limit_url != current.rate_limit_url {
+            report.requires_restart.push("RATE_LIMIT_URL");
+            next.rate_limit_url = current.rate_limit_url.clone();
+        }
+        if next.outbox_path != current.outbox_path {
+            report.requires_restart.push("OUTBOX_PATH");
+            next.outbox_path = current.outbox_path.clone();
+        }
+        if next.event_bus_url != current.event_bus_url {
+            report.requires_restart.push("EVENT_BUS_URL");
+            next.event_bus_url = current.event_bus_url.clone();
+        }
+        if next.sentry_dsn != current.sentry_dsn {
+            report.requires_restart.push("SENTRY_DSN");
+            next.sentry_dsn = current.sentry_dsn.clone();
+        }
+        if next.otel_endpoint != current.otel_endpoint {
+            report.requires_restart.push("OTEL_EXPORTER_OTLP_ENDPOINT");
+            next.otel_endpoint = current.otel_endpoint.clone();
+        }
+
+        if next.compression_algorithms != current.compression_algorithms {
+            report.requires_restart.push("COMPRESSION_ALGORITHMS");
+            next.compression_algorithms = current.compression_algorithms.clone();
+        }
+        if next.compression_level != current.compression_level {
+            report.requires_restart.push("COMPRESSION_LEVEL");
+            next.compression_level = current.compression_level;
+        }
+        if next.max_concurrent_requests != current.max_concurrent_requests {
+            report.requires_restart.push("MAX_CONCURRENT_REQUESTS");
+            next.max_concurrent_requests = current.max_concurrent_requests;
+        }
+        if next.max_concurrent_expensive_requests != current.max_concurrent_expensive_requests {
+            report.requires_restart.push("MAX_CONCURRENT_EXPENSIVE_REQUESTS");
+            next.max_concurrent_expensive_requests = current.max_concurrent_expensive_requests;
+        }
+        if next.max_concurrent_exports != current.max_concurrent_exports {
+            report.requires_restart.push("MAX_CONCURRENT_EXPORTS");
+            next.max_concurrent_exports = current.max_concurrent_exports;
+        }
+
+        if next.repo_latency_buckets != current.repo_latency_buckets {
+            report.requires_restart.push("REPO_LATENCY_BUCKETS");
+            next.repo_latency_buckets = current.repo_latency_buckets.clone();
+        }
+
+        if next.rust_log != current.rust_log {
+            match &self.log_filter {
+                Some(handle) => match handle.reload(EnvFilter::new(&next.rust_log)) {
+                    Ok(()) => applied(&mut report, "RUST_LOG", &current.rust_log, &next.rust_log),
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to swap log filter");
+                        next.rust_log = current.rust_log.clone();
+                    }
+                },
+                // No subscriber to update (e.g. tests): record the value anyway.
+                None => applied(&mut report, "RUST_LOG", &current.rust_log, &next.rust_log),
+            }
+        }
+        for (setting, old, new) in [
+            ("API_POLICY", current.api_policy, next.api_policy),
+            ("ADMIN_POLICY", current.admin_policy, next.admin_policy),
+            ("METRICS_POLICY", current.metrics_policy, next.metrics_policy),
+            ("UI_POLICY", current.ui_policy, next.ui_policy),
+        ] {
+            if new != old {
+                applied(&mut report, setting, old, new);
+            }
+        }
+        // Only the count is logged; the keys are secrets.
+        if next.api_keys != current.api_keys {
+            applied(&mut report, "API_KEYS", current.api_keys.len(), next.api_keys.len());
+        }
+        if next.admin_api_keys != current.admin_api_keys {
+            applied(
+                &mut report,
+                "ADMIN_API_KEYS",
+                current.admin_api_keys.len(),
+                next.admin_api_keys.len(),
+            );
+        }
+        if next.enable_docs != current.enable_docs {
+            applied(&mut report, "ENABLE_DOCS", current.enable_docs, next.enable_docs);
+        }
+        if next.rate_limit_per_minute != current.rate_limit_per_minute {
+            applied(
+                &mut report,
+                "RATE_LIMIT_PER_MINUTE",
+                current.rate_limit_per_minute,
+                next.rate_limit_per_minute,
+            );
+        }
+        if next.import_max_body_mb != current.import_max_body_mb {
+            applied(
+                &mut report,
+                "IMPORT_MAX_BODY_MB",
+                current.import_max_body_mb,
+                next.import_max_body_mb,
+            );
+        }
+        if next.import_rate_per_hour != current.import_rate_per_hour {
+            applied(
+                &mut report,
+                "IMPORT_RATE_PER_HOUR",
+                current.import_rate_per_hour,
+                next.import_rate_per_hour,
+            );
+        }
+        if next.import_timeout_ms != current.import_timeout_ms {
+            applied(
+                &mut report,
+                "IMPORT_TIMEOUT_MS",
+                current.import_timeout_ms,
+                next.import_timeout_ms,
+            );
+        }
+        if next.rate_limit_fail_open != current.rate_limit_fail_open {
+            applied(
+                &mut report,
+                "RATE_LIMIT_FAIL_OPEN",
+                current.rate_limit_fail_open,
+                next.rate_limit_fail_open,
+            );
+        }
+        if next.read_only != current.read_only {
+            applied(&mut report, "READ_ONLY", current.read_only, next.read_only);
+        }
+        if next.cors_origins != current.cors_origins {
+            applied(
+                &mut report,
+                "CORS_ORIGINS",
+                current.cors_origins.join(","),
+                next.cors_origins.join(","),
+            );
+        }
+
+        if next.log_client_errors != current.log_client_errors {
+            applied(
+                &mut report,
+                "LOG_CLIENT_ERRORS",
+                current.log_client_errors,
+                next.log_client_errors,
+            );
+        }
+        if next.access_log_exclude != current.access_log_exclude {
+            applied(
+                &mut report,
+                "ACCESS_LOG_EXCLUDE",
+                current.access_log_exclude.join(","),
+                next.access_log_exclude.join(","),
+            );
+        }
+        if next.log_bodies != current.log_bodies {
+            applied(&mut report, "LOG_BODIES", current.log_bodies, next.log_bodies);
+        }
+        if next.log_body_max_bytes != current.log_body_max_bytes {
+            applied(
+                &mut report,
+                "LOG_BODY_MAX_BYTES",
+                current.log_body_max_bytes,
+                next.log_body_max_bytes,
+            );
+        }
+        if next.get_max_age_secs != current.get_max_age_secs {
+            applied(
+                &mut report,
+                "GET_MAX_AGE_SECS",
+                current.get_max_age_secs,
+                next.get_max_age_secs,
+            );
+        }
+        if next.max_page_size != current.max_page_size {
+            applied(&mut report, "MAX_PAGE_SIZE", current.max_page_size, next.max_page_size);
+        }
+        if next.strict_pagination != current.strict_pagination {
+            applied(
+                &mut report,
+                "STRICT_PAGINATION",
+                current.strict_pagination,
+                next.strict_pagination,
+            );
+        }
+        if next.slow_request_threshold_ms != current.slow_request_threshold_ms {
+            applied(
+                &mut report,
+                "SLOW_REQUEST_THRESHOLD_MS",
+                current.slow_request_threshold_ms,
+                next.slow_request_threshold_ms,
+            );
+        }
+        if next.slow_request_overrides != current.slow_request_overrides {
+            applied(
+                &mut report,
+                "SLOW_REQUEST_OVERRIDES",
+                format!("{:?}", current.slow_request_overrides),
+                format!("{:?}", next.slow_request_overrides),
+            );
+        }
+        if next.request_timeout_ms != current.request_timeout_ms {
+            applied(
+                &mut report,
+                "REQUEST_TIMEOUT_MS",
+                current.request_timeout_ms,
+                next.request_timeout_ms,
+            );
+        }
+
+        if next.envelope_responses != current.envelope_responses {
+            applied(
+                &mut report,
+                "ENVELOPE_RESPONSES",
+                current.envelope_responses,
+                next.envelope_responses,
+            );
+        }
+        if next.legacy_deprecation_date != current.legacy_deprecation_date {
+            applied(
+                &mut report,
+                "LEGACY_DEPRECATION_DATE",
+                date(current.legacy_deprecation_date),
+                date(next.legacy_deprecation_date),
+            );
+        }
+        if next.legacy_sunset_date != current.legacy_sunset_date {
+            applied(
+                &mut report,
+                "LEGACY_SUNSET_DATE",
+                date(current.legacy_sunset_date),
+                date(next.legacy_sunset_date),
+            );
+        }
+        if next.event_log_capacity != current.event_log_capacity {
+            applied(
+                &mut report,
+                "EVENT_LOG_CAPACITY",
+                current.event_log_capacity,
+                next.event_log_capacity,
+            );
+        }
+        if next.list_cache_entries != current.list_cache_entries {
+            applied(
+                &mut report,
+                "LIST_CACHE_ENTRIES",
+                current.list_cache_entries,
+                next.list_cache_entries,
+            );
+        }
+        if next.coalesce_lists != current.coalesce_lists {
+            applied(&mut report, "COALESCE_LISTS", current.coalesce_lists, next.coalesce_lists);
+        }
+        if next.feature_flags != current.feature_flags {
+            applied(
+                &mut report,
+                "FEATURE_FLAGS",
+                flag_names(&current.feature_flags),
+                flag_names(&next.feature_flags),
+            );
+        }
+        if next.client_feature_flags != current.client_feature_flags {
+            applied(
+                &mut report,
+                "CLIENT_FEATURE_FLAGS",
+                flag_names(&current.client_feature_flags),
+                flag_names(&next.client_feature_flags),
+            );
+        }
+        if next.pretty_json_default != current.pretty_json_default {
+            applied(
+                &mut report,
+                "PRETTY_JSON_DEFAULT",
+                current.pretty_json_default,
+                next.pretty_json_default,
+            );
+        }
+        if next.compression_enabled != current.compression_enabled {
+            applied(
+                &mut report,
+                "COMPRESSION_ENABLED",
+                current.compression_enabled,
+                next.compression_enabled,
+            );
+        }
+        if next.compression_min_bytes != current.compression_min_bytes {
+            applied(
+                &mut report,
+                "COMPRESSION_MIN_BYTES",
+                current.compression_min_bytes,
+                next.compression_min_bytes,
+            );
+        }
+
+        if next.attachment_max_bytes != current.attachment_max_bytes {
+            applied(
+                &mut report,
+                "ATTACHMENT_MAX_BYTES",
+                current.attachment_max_bytes,
+                next.attachment_max_bytes,
+            );
+        }
+        if next.attachment_content_types != current.attachment_content_types {
+            applied(
+                &mut report,
+                "ATTACHMENT_CONTENT_TYPES",
+                current.attachment_content_types.join(","),
+                next.attachment_content_types.join(","),
+            );
+        }
+        // Checked on each upload against the ledger's usage, which doesn't
+        // depend on them; uploads already stored stay even if over the new
+        // limits.
+        if next.attachment_storage_max_bytes != current.attachment_storage_max_bytes {
+            applied(
+                &mut report,
+                "ATTACHMENTS_MAX_BYTES",
+                current.attachment_storage_max_bytes,
+                next.attachment_storage_max_bytes,
+            );
+        }
+        if next.attachment_max_per_todo != current.attachment_max_per_todo {
+            applied(
+                &mut report,
+                "ATTACHMENT_MAX_PER_TODO",
+                current.attachment_max_per_todo,
+                next.attachment_max_per_todo,
+            );
+        }
+
+        if next.duplicate_warning != current.duplicate_warning {
+            applied(
+                &mut report,
+                "DUPLICATE_WARNING",
+                current.duplicate_warning,
+                next.duplicate_warning,
+            );
+        }
+        if next.duplicate_threshold != current.duplicate_threshold {
+            applied(
+                &mut report,
+                "DUPLICATE_THRESHOLD",
+                current.duplicate_threshold,
+                next.duplicate_threshold,
+            );
+        }
+        if next.reject_exact_duplicates != current.reject_exact_duplicates {
+            applied(
+                &mut report,
+                "REJECT_EXACT_DUPLICATES",
+                current.reject_exact_duplicates,
+                next.reject_exact_duplicates,
+            );
+        }
+        if next.allow_client_ids != current.allow_client_ids {
+            applied(
+                &mut report,
+                "ALLOW_CLIENT_IDS",
+                current.allow_client_ids,
+                next.allow_client_ids,
+            );
+        }
+
+        if next.reminder_interval_secs != current.reminder_interval_secs {
+            applied(
+                &mut report,
+                "REMINDER_INTERVAL_SECS",
+                current.reminder_interval_secs,
+                next.reminder_interval_secs,
+            );
+        }
+
+        if next.webhook_urls != current.webhook_urls {
+            applied(
+                &mut report,
+                "WEBHOOK_URLS",
+                current.webhook_urls.join(","),
+                next.webhook_urls.join(","),
+            );
+        }
+        if next.outbox_max_attempts != current.outbox_max_attempts {
+            applied(
+                &mut report,
+                "OUTBOX_MAX_ATTEMPTS",
+                current.outbox_max_attempts,
+                next.outbox_max_attempts,
+            );
+        }
+        if next.outbox_backoff_ms != current.outbox_backoff_ms {
+            applied(
+                &mut report,
+                "OUTBOX_BACKOFF_MS",
+                current.outbox_backoff_ms,
+                next.outbox_backoff_ms,
+            );
+        }
+        if next.max_todos != current.max_todos {
+            applied(&mut report, "MAX_TODOS", current.max_todos, next.max_todos);
+        }
+        if next.quota_warning_percent != current.quota_warning_percent {
+            applied(
+                &mut report,
+                "QUOTA_WARNING_PERCENT",
+                current.quota_warning_percent,
+                next.quota_warning_percent,
+            );
+        }
+        if next.share_ttl_secs != current.share_ttl_secs {
+            applied(&mut report, "SHARE_TTL_SECS", current.share_ttl_secs, next.share_ttl_secs);
+        }
+        if next.share_rate_per_minute != current.share_rate_per_minute {
+            applied(
+                &mut report,
+                "SHARE_RATE_PER_MINUTE",
+                current.share_rate_per_minute,
+                next.share_rate_per_minute,
+            );
+        }
+        // Supervisors read it when a task fails, not when they start.
+        if next.on_task_failure != current.on_task_failure {
+            applied(
+                &mut report,
+                "ON_TASK_FAILURE",
+                current.on_task_failure,
+                next.on_task_failure,
+            );
+        }
+        // Sessions keep the expiry they were opened with; only logins after
+        // the reload get the new lifetime.
+        if next.session_ttl_secs != current.session_ttl_secs {
+            applied(
+                &mut report,
+                "SESSION_TTL_SECS",
+                current.session_ttl_secs,
+                next.session_ttl_secs,
+            );
+        }
+
+        for setting in &report.requires_restart {
+            tracing::warn!(setting, "config change ignored until restart");
+        }
+
+        self.state.replace_config(next);
+        report
+    }
+}
+
+fn flag_names(flags: &[Flag]) -> String {
+    flags.iter().map(|flag| flag.as_str()).collect::<Vec<_>>().join(",")
+}
+
+fn date(at: Option<DateTime<Utc>>) -> String {
+    at.map_or_else(|| "unset".to_string(), |at| at.to_rfc3339())
+}
+
+fn applied(report: &mut ReloadReport, setting: &'static str, old: impl Display, new: impl Display) {
+    tracing::info!(setting, %old, %new, "config change applied");
+    report.applied.push(setting);
+}
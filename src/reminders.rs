@@ -0,0 +1,74 @@
+//! Reminders for todos whose due date has passed.
+//!
+//! [`run`] wakes every `REMINDER_INTERVAL_SECS` and calls [`tick`], which
+//! publishes a [`TodoEvent::Reminder`] for each open todo that fell due and
+//! stamps it with `reminded_at` so it fires only once. Changing a todo's due
+//! date clears the stamp and arms a new reminder.
+//!
+//! # Exactly once
+//!
+//! Each tick looks at everything due up to now rather than only what fell
+//! due since the previous tick, so a todo created already overdue, or a
+//! clock that jumped, still gets its reminder; `reminded_at` is what keeps
+//! it from firing twice. Setting it goes through
+//! [`TodoRepo::mark_reminded`](crate::state::TodoRepo::mark_reminded), which
+//! only succeeds for the first caller.
+
+use std::{future::Future, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::{errors::AppError, events::TodoEvent, state::AppState};
+
+/// How long to wait before looking at the config again while reminders are
+/// turned off.
+const IDLE_RECHECK: Duration = Duration::from_secs(60);
+
+/// Sends the reminders that are due at `now` and returns how many went out.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> Result<usize, AppError> {
+    let repo = state.service().repo();
+    let mut sent = 0;
+    for todo in repo.due_between(DateTime::<Utc>::MIN_UTC, now).await? {
+        if todo.reminded_at.is_some() {
+            continue;
+        }
+        match repo.mark_reminded(todo.id, now).await {
+            Ok(Some(todo)) => {
+                tracing::info!(todo_id = todo.id, "todo is due");
+                state.events().publish(TodoEvent::Reminder { todo });
+                sent += 1;
+            }
+            // Reminded by someone else, or deleted since the query.
+            Ok(None) | Err(AppError::NotFound) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(sent)
+}
+
+/// Calls [`tick`] with the state's [clock](crate::clock) on the configured
+/// interval until `shutdown` resolves.
+pub async fn run(state: AppState, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    loop {
+        let interval = state.config().reminder_interval_secs;
+        let wait = if interval == 0 {
+            IDLE_RECHECK
+        } else {
+            Duration::from_secs(interval)
+        };
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tokio::time::sleep(wait) => {}
+        }
+
+        // Re-read in case a reload turned reminders off while we slept.
+        if state.config().reminder_interval_secs == 0 {
+            continue;
+        }
+        if let Err(err) = tick(&state, state.clock().now()).await {
+            tracing::error!(error = %err, "reminder check failed");
+        }
+    }
+    tracing::info!("reminder scheduler stopped");
+}
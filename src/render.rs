@@ -0,0 +1,134 @@
+//! Human-readable renderings of todos.
+//!
+//! `curl -H 'Accept: text/plain' /todos` prints a checklist and a browser
+//! (which prefers `text/html`) gets a bare server-side table. Both exist for
+//! debugging; machines should keep using JSON or MessagePack.
+//!
+//! Dates are written for the reader, in the language and relative to the
+//! time of the request's [`i18n`](crate::i18n) context. The HTML keeps the
+//! exact timestamp in each `<time datetime>`.
+//!
+//! # Escaping
+//!
+//! Titles are user input. Everything interpolated into HTML goes through
+//! [`escape_html`], so a title like `<script>` shows up as text instead of
+//! running.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    i18n,
+    models::{CreatedTodo, RenderedTodo, Todo},
+    timestamps,
+};
+
+/// A value that can be shown to people as well as serialized.
+pub trait Render {
+    /// Plain text, one line per todo.
+    fn text(&self) -> String;
+    /// A complete HTML document.
+    fn html(&self) -> String;
+}
+
+impl Render for Todo {
+    fn text(&self) -> String {
+        let mut out = String::new();
+        checklist_line(&mut out, self);
+        out
+    }
+
+    fn html(&self) -> String {
+        document(&format!("Todo {}", self.id), std::slice::from_ref(self))
+    }
+}
+
+impl Render for CreatedTodo {
+    fn text(&self) -> String {
+        self.todo.text()
+    }
+
+    fn html(&self) -> String {
+        self.todo.html()
+    }
+}
+
+impl Render for RenderedTodo {
+    fn text(&self) -> String {
+        self.todo.text()
+    }
+
+    fn html(&self) -> String {
+        self.todo.html()
+    }
+}
+
+impl Render for Vec<Todo> {
+    fn text(&self) -> String {
+        let mut out = String::new();
+        for todo in self {
+            checklist_line(&mut out, todo);
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        document("Todos", self)
+    }
+}
+
+/// `[x] 3 buy milk`, or `[ ] 4 call mom (due tomorrow)`
+fn checklist_line(out: &mut String, todo: &Todo) {
+    let mark = if todo.done { 'x' } else { ' ' };
+    let _ = write!(out, "[{mark}] {} {}", todo.id, todo.title);
+    if let Some(due) = todo.due {
+        let _ = write!(out, " ({})", i18n::current().due(due));
+    }
+    out.push('\n');
+}
+
+fn document(title: &str, todos: &[Todo]) -> String {
+    let context = i18n::current();
+    let mut out = format!("<!DOCTYPE html>\n<html lang=\"{}\">\n", context.locale);
+    out.push_str("<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>\n</head>\n<body>", escape_html(title));
+    out.push_str("<table>\n<thead><tr><th>ID</th><th>Title</th><th>Done</th>");
+    out.push_str("<th>Due</th><th>Updated</th></tr></thead>\n");
+    out.push_str("<tbody>\n");
+    for todo in todos {
+        let due = todo.due.map(|due| time(due, context.due(due))).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{due}</td><td>{}</td></tr>",
+            todo.id,
+            escape_html(&todo.title),
+            if todo.done { "yes" } else { "no" },
+            time(todo.updated_at, context.ago(todo.updated_at)),
+        );
+    }
+    out.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    out
+}
+
+/// `shown` in a `<time>` element carrying the exact timestamp.
+fn time(at: DateTime<Utc>, shown: String) -> String {
+    format!("<time datetime=\"{}\">{}</time>", timestamps::format(&at), escape_html(&shown))
+}
+
+/// Escapes the five characters that are significant in HTML text and
+/// attribute values.
+pub fn escape_html(raw: &str) -> String {
+    let mut escaped = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,76 @@
+//! Reporting server errors to an error tracker.
+//!
+//! A `500` or `503` in the logs is easy to miss. When the state has an
+//! [`ErrorReporter`], [`report_errors`] hands it every response caused by
+//! [`AppError::Internal`](crate::errors::AppError::Internal) or
+//! [`AppError::Unavailable`](crate::errors::AppError::Unavailable), with the
+//! request id, method, and route template attached. Client errors, and
+//! server errors that are expected under load (`Overloaded`,
+//! `DeadlineExceeded`, `ReadOnly`), are not reported.
+//!
+//! # Reporters
+//!
+//! Reporters are called on the response path, so they must not wait on the
+//! network: queue the report and send it from a task of their own. With the
+//! `error-reporting` feature, `SENTRY_DSN` selects
+//! [`sentry::SentryReporter`]. Without a reporter nothing is collected.
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::state::AppState;
+
+#[cfg(feature = "error-reporting")]
+pub mod sentry;
+
+/// Receives server errors worth a human's attention.
+pub trait ErrorReporter: Send + Sync + 'static {
+    /// Must return without blocking; see the [module docs](self).
+    fn report(&self, error: &ReportedError, context: &RequestContext);
+}
+
+/// The error behind a reported response. [`AppError`](crate::errors::AppError)
+/// stores it in the response extensions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportedError {
+    /// [`AppError::code`](crate::errors::AppError::code).
+    pub code: &'static str,
+    /// The error and its sources, as logged.
+    pub message: String,
+}
+
+/// The request that failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestContext {
+    /// The `X-Request-Id` the client got back.
+    pub request_id: Option<String>,
+    pub method: Method,
+    /// Route template such as `/todos/:id`; `None` for unknown paths.
+    pub route: Option<String>,
+}
+
+/// Passes reportable errors to the state's [`ErrorReporter`], if it has one.
+pub async fn report_errors(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(reporter) = state.error_reporter() else {
+        return next.run(req).await;
+    };
+    let context = RequestContext {
+        request_id: req
+            .headers()
+            .get("x-request-id")
+            .and_then(|id| id.to_str().ok())
+            .map(str::to_string),
+        method: req.method().clone(),
+        route: req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string()),
+    };
+
+    let res = next.run(req).await;
+    if let Some(error) = res.extensions().get::<ReportedError>() {
+        reporter.report(error, &context);
+    }
+    res
+}
@@ -0,0 +1,198 @@
+//! [`ErrorReporter`] that sends events to Sentry (`error-reporting` feature).
+//!
+//! Reports are queued, up to [`QUEUE`] of them, and a background task posts
+//! each one to the project's envelope endpoint. When Sentry is slow or down
+//! the queue fills and further reports are dropped with a warning: requests
+//! never wait for the tracker, and a flood of errors can't exhaust memory.
+//!
+//! Only what [`report_errors`](super::report_errors) collects is sent: the
+//! error code and message, the method and route template, and the request
+//! id. Request bodies, headers, and query strings never leave the process.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context as _};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use chrono::Utc;
+use hyper::client::conn::http1;
+use hyper_util::rt::TokioIo;
+use serde_json::json;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{self, pki_types::ServerName},
+    TlsConnector,
+};
+
+use super::{ErrorReporter, ReportedError, RequestContext};
+
+/// Reports waiting to be sent before new ones are dropped.
+pub const QUEUE: usize = 100;
+
+/// Longest a single delivery may take.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// [`ErrorReporter`] backed by Sentry.
+pub struct SentryReporter {
+    queue: mpsc::Sender<String>,
+}
+
+impl SentryReporter {
+    /// Validates `dsn` (`https://<key>@<host>/<project id>`) and starts the
+    /// delivery task, so it must be called within a Tokio runtime.
+    pub fn new(dsn: &str) -> anyhow::Result<Self> {
+        let dsn = Dsn::parse(dsn).context("SENTRY_DSN is not a valid Sentry DSN")?;
+        let transport = Transport::new(dsn)?;
+        let (queue, mut pending) = mpsc::channel::<String>(QUEUE);
+        tokio::spawn(async move {
+            while let Some(envelope) = pending.recv().await {
+                let sent = tokio::time::timeout(TIMEOUT, transport.post(envelope)).await;
+                match sent {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => tracing::warn!(error = %err, "failed to report error"),
+                    Err(_) => tracing::warn!("failed to report error: timed out"),
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+impl ErrorReporter for SentryReporter {
+    fn report(&self, error: &ReportedError, context: &RequestContext) {
+        if self.queue.try_send(envelope(error, context)).is_err() {
+            tracing::warn!(code = error.code, "error report dropped: the queue is full");
+        }
+    }
+}
+
+/// One event in Sentry's envelope format: a header line, an item header, and
+/// the event itself.
+fn envelope(error: &ReportedError, context: &RequestContext) -> String {
+    let now = Utc::now().to_rfc3339();
+    let route = context.route.as_deref().unwrap_or("unmatched");
+    let event = json!({
+        "timestamp": now,
+        "platform": "other",
+        "level": "error",
+        "logger": "rust_api",
+        "release": concat!("rust-api@", env!("CARGO_PKG_VERSION")),
+        "transaction": format!("{} {route}", context.method),
+        "message": { "formatted": error.message },
+        "tags": {
+            "code": error.code,
+            "method": context.method.as_str(),
+            "route": route,
+            "request_id": context.request_id,
+        },
+    });
+    format!("{}\n{}\n{event}\n", json!({ "sent_at": now }), json!({ "type": "event" }))
+}
+
+/// The parts of a DSN needed to deliver events.
+struct Dsn {
+    secure: bool,
+    host: String,
+    port: u16,
+    key: String,
+    /// Path of the project's envelope endpoint.
+    endpoint: String,
+}
+
+impl Dsn {
+    fn parse(raw: &str) -> anyhow::Result<Self> {
+        let (scheme, rest) = raw.trim().split_once("://").ok_or_else(|| anyhow!("no scheme"))?;
+        let secure = match scheme {
+            "https" => true,
+            "http" => false,
+            _ => bail!("the scheme must be https or http, got `{scheme}`"),
+        };
+        let (key, rest) = rest.split_once('@').ok_or_else(|| anyhow!("no public key"))?;
+        // Old DSNs carry a secret after the key; it is no longer needed.
+        let key = key.split(':').next().unwrap_or_default();
+        let (authority, path) = rest.split_once('/').ok_or_else(|| anyhow!("no project id"))?;
+        let (prefix, project) = match path.trim_end_matches('/').rsplit_once('/') {
+            Some((prefix, project)) => (format!("/{prefix}"), project),
+            None => (String::new(), path.trim_end_matches('/')),
+        };
+        if key.is_empty() || project.is_empty() || !project.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("expected <scheme>://<key>@<host>/<project id>");
+        }
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().context("invalid port")?),
+            None => (authority, if secure { 443 } else { 80 }),
+        };
+        Ok(Self {
+            secure,
+            host: host.to_string(),
+            port,
+            key: key.to_string(),
+            endpoint: format!("{prefix}/api/{project}/envelope/"),
+        })
+    }
+}
+
+/// Posts envelopes over a fresh connection each time; errors are rare enough
+/// that keeping one open isn't worth it.
+struct Transport {
+    dsn: Dsn,
+    tls: TlsConnector,
+}
+
+impl Transport {
+    fn new(dsn: Dsn) -> anyhow::Result<Self> {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self {
+            dsn,
+            tls: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    async fn post(&self, envelope: String) -> anyhow::Result<()> {
+        let dsn = &self.dsn;
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_key={}, sentry_client=rust-api/{}",
+            dsn.key,
+            env!("CARGO_PKG_VERSION")
+        );
+        let req = Request::post(dsn.endpoint.as_str())
+            .header(header::HOST, format!("{}:{}", dsn.host, dsn.port))
+            .header(header::CONTENT_TYPE, "application/x-sentry-envelope")
+            .header("x-sentry-auth", auth)
+            .body(Body::from(envelope))?;
+
+        let tcp = TcpStream::connect((dsn.host.as_str(), dsn.port)).await?;
+        let status = if dsn.secure {
+            let name = ServerName::try_from(dsn.host.clone())?;
+            send(self.tls.connect(name, tcp).await?, req).await?
+        } else {
+            send(tcp, req).await?
+        };
+        if !status.is_success() {
+            bail!("sentry answered {status}");
+        }
+        Ok(())
+    }
+}
+
+async fn send<IO>(io: IO, req: Request<Body>) -> anyhow::Result<StatusCode>
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = http1::handshake(TokioIo::new(io)).await?;
+    tokio::spawn(connection);
+    Ok(sender.send_request(req).await?.status())
+}
@@ -8,76 +8,751 @@
 //! # Extractors
 //!
 //! - `State(app)`: Access shared application state (e.g., database connection).
-//! - `Path(id)`: Extract parameters from the URL path (e.g., `/todos/:id`).
-//! - `Json(payload)`: Parse the request body as JSON.
+//! - `Id(id)`: The numeric `:id` segment of the URL path (e.g., `/todos/:id`).
+//! - `AppQuery(params)`: Deserialize the query string (e.g., `?limit=20`),
+//!   with the [repeated parameter rules](crate::query).
+//! - `AppJson(payload)`: Parse the request body as JSON or MessagePack.
+//! - `Format`: The response format the client negotiated via `Accept`.
 //!
-//! The order of extractors matters! `State` and `Path` usually come first,
+//! The order of extractors matters! `State` and `Id` usually come first,
 //! and `Json` (which consumes the body) comes last.
 
+use std::sync::Arc;
+
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Json,
+    body::{Body, Bytes},
+    async_trait,
+    extract::{
+        multipart::MultipartRejection, FromRequestParts, Multipart, Path, State,
+    },
+    http::request::Parts,
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
 };
 
+use chrono::SubsecRound;
+use futures::{Stream, StreamExt};
+use tokio_util::io::ReaderStream;
+
 use crate::{
+    atom,
+    attachments::{self, StorageLimits, StorageScope},
+    caching::{self, CachePolicy},
     errors::AppError,
-    models::{CreateTodo, Todo, UpdateTodo},
+    events::replay::Sequenced,
+    flags::{Flag, RequestFlags},
+    ical,
+    list_cache::CachedPage,
+    markdown,
+    models::{
+        AssignTodo, Attachment, BatchResults, BatchUpdate, CalendarQuery, Changes, ChangesQuery,
+        CreateQuery, CreateTodo, CreatedTodo, DryRun, EventLogQuery, FeedQuery, GetQuery,
+        ImportQuery, ListQuery, OutboxQuery, Pagination, PollQuery, Preferences, RenderAs,
+        RenderedTodo, RunMode, SaveTemplate, SearchHit, SearchQuery, Snapshot, SortField, SortOrder,
+        StorageStats, Template, TimeseriesPoint, TimeseriesQuery, Todo, TodoFilter, UpdateTodo,
+    },
+    negotiation::{AppJson, Format, Negotiated, MSGPACK},
+    outbox::OutboxEntry,
+    poll, preferences, preflight,
+    projection::{Projected, Projection},
+    query::AppQuery,
+    quota::Quota,
+    search,
     state::AppState,
+    streaming::{self, CHUNK_SIZE},
 };
 
+pub mod manifest;
+
+pub use manifest::{manifest, route_manifest};
+
+/// The `:id` path segment. Anything that can't be an id (zero, negative,
+/// fractional, or too large for a `u64`) is a `400` with code `invalid_id` in
+/// the usual JSON error shape rather than axum's plain-text rejection or a
+/// misleading `404`.
+pub struct Id(pub u64);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Id {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(raw) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AppError::Validation(rejection.body_text().into()))?;
+        match raw.parse() {
+            Ok(0) | Err(_) => Err(AppError::InvalidId(raw)),
+            Ok(id) => Ok(Id(id)),
+        }
+    }
+}
+
 /// Tiny health check used by deployment platforms to know the process lives.
 pub async fn health() -> &'static str {
     "ok"
 }
 
-/// `GET /todos` - list everything currently in the store.
+/// `GET /ready` - whether the repository answers, by the same check startup
+/// waits on. `503` while it doesn't, while a critical
+/// [background task](crate::supervisor) is down, and once shutdown begins.
+pub async fn ready(State(app): State<AppState>) -> Result<&'static str, AppError> {
+    if app.shutting_down().is_cancelled() {
+        return Err(AppError::Unavailable);
+    }
+    let down = app.task_health().down();
+    if !down.is_empty() {
+        tracing::warn!(tasks = ?down, "readiness check failed: background tasks are down");
+        return Err(AppError::Unavailable);
+    }
+    match preflight::repository(app.service().repo().as_ref()).await {
+        Ok(()) => Ok("ready"),
+        Err(err) => {
+            tracing::warn!(error = %err, "readiness check failed");
+            Err(AppError::Unavailable)
+        }
+    }
+}
+
+/// `GET /metrics` - Prometheus scrape endpoint.
+pub async fn metrics(State(app): State<AppState>) -> impl IntoResponse {
+    let storage = app.storage_ledger().usage();
+    let metrics = app.metrics();
+    metrics.attachment_storage_bytes.set(i64::try_from(storage.used_bytes).unwrap_or(i64::MAX));
+    metrics.attachment_storage_files.set(i64::try_from(storage.files).unwrap_or(i64::MAX));
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app.metrics().render(),
+    )
+}
+
+/// `GET /todos` - list the todos matching `?done=` and `?q=`. The list
+/// changes often, so clients must revalidate with the ETag before reusing it.
+/// The ETag is the collection's revision, read before the list so it is never
+/// newer than what the client got.
+///
+/// `?limit=` and `?offset=` return a single page, described by a
+/// [`Pagination`] response extension plus `X-Total-Count` and `Link` headers.
+/// Without them, lists that fit in one chunk are sent in one piece (and get an
+/// ETag) and larger JSON lists are streamed chunk by chunk to bound memory.
+///
+/// `?fields=` picks the [fields](crate::projection) each todo is returned with,
+/// and `?sort=` and `?order=` sort the list by something other than id. The
+/// caller's [preferences](crate::preferences) fill in what the query leaves
+/// out.
 pub async fn list_todos(
     State(app): State<AppState>,
-) -> Result<Json<Vec<Todo>>, AppError> {
-    let todos = app.repo().list().await?;
-    Ok(Json(todos))
+    format: Format,
+    uri: Uri,
+    AppQuery(query): AppQuery<ListQuery>,
+) -> Result<Response, AppError> {
+    let preferences = app.preferences().get(preferences::ANONYMOUS).await?.unwrap_or_default();
+    let query = query.or_preferences(&preferences);
+    let filter = query.filter();
+    let fields = Projection::parse(query.fields.as_deref())?;
+    let sort = query.sort.unwrap_or_default();
+    let order = query.order.unwrap_or_default();
+    let collation = query.collation.unwrap_or_default();
+    let sorted = (sort, order) != (SortField::Id, SortOrder::Asc);
+    let service = app.service();
+
+    // Saving preferences changes the list without changing the revision, so
+    // a list they shape is tagged from its body by `caching::etag` instead.
+    let mut tag = HeaderMap::new();
+    if let Some(revision) = service.revision().await?.filter(|_| preferences.is_default()) {
+        tag.insert(header::ETAG, caching::revision_tag(revision));
+    }
+
+    if let Some(page) = query.page(&app.config())? {
+        let (read, page) = if sorted {
+            service.list_sorted_page(&filter, sort, order, collation, page).await?
+        } else {
+            service.list_page(&filter, page).await?
+        };
+        let body = page_body(&read, format, fields, &mut tag)?;
+        let headers = page_headers(&uri, page);
+        let res = (CachePolicy::Revalidate, tag, headers, Extension(page), body);
+        return Ok(res.into_response());
+    }
+
+    if sorted {
+        let whole = Pagination {
+            limit: usize::MAX,
+            ..Pagination::default()
+        };
+        let (read, _) = service.list_sorted_page(&filter, sort, order, collation, whole).await?;
+        let body = page_body(&read, format, fields, &mut tag)?;
+        return Ok((CachePolicy::Revalidate, tag, body).into_response());
+    }
+
+    let mut todos = service.list_after(&filter, None, CHUNK_SIZE).await?;
+
+    if todos.len() == CHUNK_SIZE && format == Format::Json {
+        // The stream outlives this handler, so it needs its own handle.
+        let body = streaming::json_array(Arc::clone(service.repo()), filter, fields, todos);
+        let headers = [(header::CONTENT_TYPE, "application/json")];
+        return Ok((CachePolicy::Revalidate, tag, headers, body).into_response());
+    }
+
+    // MessagePack needs the element count up front, and the text formats are
+    // for debugging, so those are buffered.
+    let mut chunk_len = todos.len();
+    while chunk_len == CHUNK_SIZE {
+        let after = todos.last().map(|todo| todo.id);
+        let more = service.list_after(&filter, after, CHUNK_SIZE).await?;
+        chunk_len = more.len();
+        todos.extend(more);
+    }
+    let body = Negotiated::new(format, Projected::new(todos, fields));
+    Ok((CachePolicy::Revalidate, tag, body).into_response())
 }
 
-/// `POST /todos` - accepts a JSON body and returns `201 Created`.
-pub async fn create_todo(
+/// A listed page as `format`, written from the bytes the
+/// [cache](crate::list_cache) kept for it where there are any. Unless `tag`
+/// already has the revision, those bytes bring their `ETag` along, so
+/// `caching::etag` doesn't hash them again.
+fn page_body(
+    page: &CachedPage,
+    format: Format,
+    fields: Option<Projection>,
+    tag: &mut HeaderMap,
+) -> Result<Response, AppError> {
+    let Some(encoded) = page.encoded(format, fields)? else {
+        let body = Negotiated::new(format, Projected::new(page.todos.clone(), fields));
+        return Ok(body.into_response());
+    };
+    if !tag.contains_key(header::ETAG) {
+        tag.insert(header::ETAG, encoded.etag);
+    }
+    let content_type = match format {
+        Format::MsgPack => MSGPACK,
+        _ => "application/json",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], encoded.bytes).into_response())
+}
+
+/// `GET /todos/search` - todos ranked by how well their title and description
+/// match `?q=`, with the matched words' offsets.
+pub async fn search_todos(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<SearchQuery>,
+) -> Result<(HeaderMap, Json<Vec<SearchHit>>), AppError> {
+    let page = Pagination::requested(query.limit.as_ref(), None, &app.config())?;
+    if search::terms(&query.q).is_empty() {
+        return Err(AppError::Validation("q must contain at least one word".into()));
+    }
+    let hits = app.service().search(&query.q, page.limit).await?;
+    Ok((clamped_header(page), Json(hits)))
+}
+
+/// `POST /todos/:id/attachments` - stores the first file in a
+/// `multipart/form-data` body and returns its metadata with `201 Created`.
+pub async fn upload_attachment(
+    Id(id): Id,
+    State(app): State<AppState>,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Result<(StatusCode, Json<Attachment>), AppError> {
+    let mut multipart =
+        multipart.map_err(|rejection| AppError::Validation(rejection.body_text().into()))?;
+    let service = app.service();
+    // Fail fast instead of storing a file for a todo that doesn't exist.
+    service.get(id).await?;
+
+    let config = app.config();
+    loop {
+        let field = multipart
+            .next_field()
+            .await
+            .map_err(|err| AppError::Validation(err.body_text().into()))?
+            .ok_or_else(|| AppError::Validation("expected a file field".into()))?;
+        if field.file_name().is_none() {
+            continue;
+        }
+
+        let ledger = app.storage_ledger();
+        let stored = match attachments::store(&config, ledger.as_ref(), id, field).await {
+            Err(AppError::StorageQuotaExceeded(overage)) => {
+                let scope = match overage.scope {
+                    StorageScope::Todo => "todo",
+                    StorageScope::Total => "total",
+                };
+                app.metrics().attachment_quota_rejections_total.with_label_values(&[scope]).inc();
+                return Err(AppError::StorageQuotaExceeded(overage));
+            }
+            stored => stored?,
+        };
+        return match service.add_attachment(id, stored).await {
+            Ok(attachment) => Ok((StatusCode::CREATED, Json(attachment))),
+            Err(err) => {
+                // The todo was deleted mid-upload; don't leave its file behind.
+                attachments::remove_todo_files(&config.attachment_dir, id).await;
+                ledger.release_todo(id);
+                Err(err)
+            }
+        };
+    }
+}
+
+/// `GET /todos/:id/attachments` - metadata for every file on a todo.
+pub async fn list_attachments(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<Json<Vec<Attachment>>, AppError> {
+    Ok(Json(app.service().attachments(id).await?))
+}
+
+/// `GET /attachments/:id` - streams the file back under its original name.
+pub async fn download_attachment(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<Response, AppError> {
+    let attachment = app.service().attachment(id).await?;
+    let path = attachments::path(&app.config().attachment_dir, &attachment);
+    let file = tokio::fs::File::open(&path).await.map_err(|err| {
+        tracing::error!(attachment = id, error = %err, "attachment file unreadable");
+        AppError::Internal
+    })?;
+
+    let content_type = HeaderValue::from_str(&attachment.content_type)
+        .map_err(|_| AppError::Internal)?;
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::CONTENT_DISPOSITION, attachments::content_disposition(&attachment.filename)),
+        (header::CONTENT_LENGTH, HeaderValue::from(attachment.size)),
+        (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+    ];
+    let body = streaming::track("attachment", ReaderStream::new(file), Bytes::len);
+    Ok((headers, Body::from_stream(body)).into_response())
+}
+
+/// `DELETE /attachments/:id` - removes an attachment and, once no other
+/// attachment of the todo has the same bytes, its file, giving its bytes
+/// back to the [quotas](attachments#quotas).
+pub async fn delete_attachment(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    app.service().delete_attachment(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /todos/stats/storage` - how much attachment storage is in use, and
+/// the quotas it counts against (`null` when unlimited).
+pub async fn storage_usage(State(app): State<AppState>) -> Json<StorageStats> {
+    let limits = StorageLimits::new(&app.config());
+    let usage = app.storage_ledger().usage();
+    Json(StorageStats {
+        used_bytes: usage.used_bytes,
+        files: usage.files,
+        max_bytes: (limits.total > 0).then_some(limits.total),
+        max_bytes_per_todo: (limits.per_todo > 0).then_some(limits.per_todo),
+    })
+}
+
+/// `GET /todos/calendar.ics` - todos with a due date as an iCalendar feed.
+/// Completed todos are left out unless `?include_done=true`.
+pub async fn calendar(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<CalendarQuery>,
+) -> Result<Response, AppError> {
+    let filter = TodoFilter {
+        done: (!query.include_done).then_some(false),
+        ..TodoFilter::default()
+    };
+    let todos = app.service().list_matching(&filter).await?;
+    let body = ical::calendar(&todos, app.clock().now());
+    let headers = [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")];
+    Ok((headers, body).into_response())
+}
+
+/// `GET /todos/feed.atom` - the most recently changed todos as an Atom feed,
+/// optionally only those changed after `?since=`.
+pub async fn feed(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<FeedQuery>,
+) -> Result<Response, AppError> {
+    let mut todos = app.service().list_matching(&TodoFilter::default()).await?;
+    // Compare at the feed's own precision, so passing back a feed's
+    // `<updated>` as `since` doesn't return that entry again.
+    todos.retain(|todo| {
+        query
+            .since
+            .is_none_or(|since| todo.updated_at.trunc_subsecs(atom::PRECISION) > since)
+    });
+    todos.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+    todos.truncate(atom::MAX_ENTRIES);
+    let headers = [(header::CONTENT_TYPE, "application/atom+xml")];
+    Ok((CachePolicy::Revalidate, headers, atom::feed(&todos)).into_response())
+}
+
+/// `GET /todos/stats/timeseries` - todos created and completed per `?bucket=`
+/// (`day` or `week`) between `?from=` and `?to=`, for charts. Empty buckets
+/// are included with zeros. Carries the [quota](crate::quota) headers.
+pub async fn timeseries(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<TimeseriesQuery>,
+) -> Result<(Option<Quota>, Json<Vec<TimeseriesPoint>>), AppError> {
+    let range = query.range(app.clock().now())?;
+    let points = app.service().timeseries(query.bucket, range).await?;
+    Ok((app.service().quota().await?, Json(points)))
+}
+
+/// `GET /todos/changes` - todos created or updated and ids deleted after
+/// revision `?since=`, for clients that keep a local copy. `409` with code
+/// `resync_required` means the client must refetch everything.
+pub async fn changes(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<ChangesQuery>,
+) -> Result<Json<Changes>, AppError> {
+    Ok(Json(app.service().changes_since(query.since).await?))
+}
+
+/// `GET /todos/poll` - like [`changes`] after `?revision=`, but when nothing
+/// has changed yet, waits up to `?timeout_secs=` for something to; see
+/// [`poll`](crate::poll). A poll that times out gets `200` with nothing
+/// changed and the same revision.
+pub async fn poll(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<PollQuery>,
+) -> Result<Json<Changes>, AppError> {
+    let timeout = poll::timeout(query.timeout_secs)?;
+    Ok(Json(poll::wait_for_change(&app, query.revision, timeout).await?))
+}
+
+/// `GET /todos/events/log` - up to `?limit=` events after `?after_seq=`,
+/// oldest first, for integrations that poll. Once caught up they can switch
+/// to [`event_stream`] with the last `seq` as `Last-Event-ID`. `409` with code
+/// `resync_required` means the events after `after_seq` are no longer kept.
+pub async fn event_log(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<EventLogQuery>,
+) -> Result<(HeaderMap, Json<Vec<Sequenced>>), AppError> {
+    let page = Pagination::requested(query.limit.as_ref(), None, &app.config())?;
+    let events = app.event_log().read(query.after_seq, page.limit)?;
+    Ok((clamped_header(page), Json(events)))
+}
+
+/// `GET /todos/events` - server-sent events, one per change, with the event's
+/// `seq` as the SSE id. Without `Last-Event-ID` the stream starts with the
+/// next change; with it, the events logged since that `seq` come first.
+pub async fn event_stream(
     State(app): State<AppState>,
-    Json(payload): Json<CreateTodo>,
-) -> Result<(StatusCode, Json<Todo>), AppError> {
-    // Validate input before hitting the database.
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    let after = match headers.get("last-event-id") {
+        None => None,
+        Some(value) => {
+            let seq = value.to_str().ok().and_then(|seq| seq.trim().parse().ok());
+            Some(seq.ok_or_else(|| {
+                AppError::Validation("Last-Event-ID must be the seq of an event".into())
+            })?)
+        }
+    };
+    let events = app.event_log().subscribe(after)?.map(|entry| {
+        Event::default().id(entry.seq.to_string()).json_data(&entry)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// `X-Total-Count` plus an RFC 8288 `Link` header with `first`, `prev`,
+/// `next` and `last` URLs. Links keep every other query parameter as sent.
+fn page_headers(uri: &Uri, page: Pagination) -> HeaderMap {
+    let other_params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && key != "limit" && key != "offset"
+        })
+        .collect();
+    let link = |rel: &str, offset: usize| {
+        let mut query = other_params.clone();
+        let paging = format!("limit={}&offset={offset}", page.limit);
+        query.push(&paging);
+        format!("<{}?{}>; rel=\"{rel}\"", uri.path(), query.join("&"))
+    };
+
+    let last = page.total.saturating_sub(1) / page.limit * page.limit;
+    let mut links = vec![link("first", 0)];
+    if page.offset > 0 {
+        links.push(link("prev", page.offset.saturating_sub(page.limit)));
+    }
+    let next = page.offset.saturating_add(page.limit);
+    if next < page.total {
+        links.push(link("next", next));
+    }
+    links.push(link("last", last));
+
+    let mut headers = clamped_header(page);
+    headers.insert(HeaderName::from_static("x-total-count"), page.total.into());
+    if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+        headers.insert(header::LINK, value);
+    }
+    headers
+}
+
+/// `GET /preferences` - the caller's list defaults.
+pub async fn get_preferences(State(app): State<AppState>) -> Result<Json<Preferences>, AppError> {
+    let stored = app.preferences().get(preferences::ANONYMOUS).await?;
+    Ok(Json(stored.unwrap_or_default()))
+}
+
+/// `PUT /preferences` - replaces the caller's list defaults.
+pub async fn put_preferences(
+    State(app): State<AppState>,
+    AppJson(payload): AppJson<Preferences>,
+) -> Result<Json<Preferences>, AppError> {
+    payload.validate(&app.config())?;
+    app.preferences().put(preferences::ANONYMOUS, payload.clone()).await?;
+    Ok(Json(payload))
+}
+
+/// `GET /templates` - every [template](crate::templates), in id order.
+pub async fn list_templates(State(app): State<AppState>) -> Result<Json<Vec<Template>>, AppError> {
+    Ok(Json(app.templates().list().await?))
+}
+
+/// `POST /templates` - save a template; its items are validated now, not
+/// when it is instantiated.
+pub async fn create_template(
+    State(app): State<AppState>,
+    AppJson(payload): AppJson<SaveTemplate>,
+) -> Result<(StatusCode, Json<Template>), AppError> {
     payload.validate()?;
+    Ok((StatusCode::CREATED, Json(app.templates().create(payload).await?)))
+}
+
+pub async fn get_template(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<Json<Template>, AppError> {
+    Ok(Json(app.templates().get(id).await?))
+}
 
-    let todo = app.repo().create(payload).await?;
-    Ok((StatusCode::CREATED, Json(todo)))
+/// `PUT /templates/:id` - replace a template's name and items.
+pub async fn replace_template(
+    Id(id): Id,
+    State(app): State<AppState>,
+    AppJson(payload): AppJson<SaveTemplate>,
+) -> Result<Json<Template>, AppError> {
+    payload.validate()?;
+    Ok(Json(app.templates().replace(id, payload).await?))
 }
 
-/// `GET /todos/:id` - fetch a single todo or bubble up `404`.
+pub async fn delete_template(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    app.templates().delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /templates/:id/instantiate` - create every todo of the template in
+/// one batch and return them with `201`.
+pub async fn instantiate_template(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<(StatusCode, Option<Quota>, Json<Vec<Todo>>), AppError> {
+    let template = app.templates().get(id).await?;
+    let (todos, quota) = app.service().create_many(template.items).await?;
+    Ok((StatusCode::CREATED, quota, Json(todos)))
+}
+
+/// `X-Page-Size-Clamped: true` when the client asked for a bigger page than
+/// it got.
+fn clamped_header(page: Pagination) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if page.clamped {
+        headers.insert(
+            HeaderName::from_static("x-page-size-clamped"),
+            HeaderValue::from_static("true"),
+        );
+    }
+    headers
+}
+
+/// `POST /todos` - accepts a JSON body and returns `201 Created`.
+///
+/// With `DUPLICATE_WARNING` on, the response lists similar open todos in
+/// `possible_duplicates`; `?strict_duplicates=true` or the `strict_duplicates`
+/// feature flag turns that into a `409`.
+pub async fn create_todo(
+    State(app): State<AppState>,
+    format: Format,
+    flags: RequestFlags,
+    AppQuery(query): AppQuery<CreateQuery>,
+    AppJson(payload): AppJson<CreateTodo>,
+) -> Result<(StatusCode, Option<Quota>, Negotiated<CreatedTodo>), AppError> {
+    let strict = query.strict_duplicates || flags.enabled(Flag::StrictDuplicates);
+    let todo = app.service().create(payload, strict).await?;
+    Ok((StatusCode::CREATED, todo.quota, Negotiated::new(format, todo)))
+}
+
+/// `GET /todos/:id` - fetch a single todo or bubble up `404`. The ETag is the
+/// todo's version, read before the todo so it is never newer than what the
+/// client got.
+///
+/// `?render=html` adds `description_html`, the description rendered from
+/// Markdown and sanitized. `?fields=` picks the [fields](crate::projection)
+/// returned.
 pub async fn get_todo(
-    Path(id): Path<u64>,
+    Id(id): Id,
     State(app): State<AppState>,
-) -> Result<Json<Todo>, AppError> {
-    let todo = app.repo().get(id).await?;
-    Ok(Json(todo))
+    format: Format,
+    AppQuery(query): AppQuery<GetQuery>,
+) -> Result<Response, AppError> {
+    let fields = Projection::parse(query.fields.as_deref())?;
+    let mut tag = HeaderMap::new();
+    if let Some(version) = app.service().version(id).await? {
+        tag.insert(header::ETAG, caching::version_tag(version));
+    }
+    let todo = app.service().get(id).await?;
+    let policy = CachePolicy::Private {
+        max_age: app.config().get_max_age_secs,
+    };
+    match query.render {
+        None => {
+            let body = Negotiated::new(format, Projected::new(todo, fields));
+            Ok((policy, tag, body).into_response())
+        }
+        Some(RenderAs::Html) => {
+            let description_html = markdown::to_html(todo.description.as_deref().unwrap_or(""));
+            let todo = RenderedTodo {
+                todo,
+                description_html,
+            };
+            let body = Negotiated::new(format, Projected::new(todo, fields));
+            Ok((policy, tag, body).into_response())
+        }
+    }
 }
 
 /// `PUT /todos/:id` - update existing todos.
+///
+/// With `ALLOW_CLIENT_IDS`, a body with a title for an id that doesn't exist
+/// creates the todo under that id and answers `201 Created` with a `Location`
+/// and the [quota](crate::quota) headers.
 pub async fn update_todo(
-    Path(id): Path<u64>,
+    Id(id): Id,
+    uri: Uri,
     State(app): State<AppState>,
-    Json(payload): Json<UpdateTodo>,
-) -> Result<Json<Todo>, AppError> {
-    // Validate input.
-    payload.validate()?;
+    format: Format,
+    AppJson(payload): AppJson<UpdateTodo>,
+) -> Result<Response, AppError> {
+    if !app.config().allow_client_ids {
+        let todo = app.service().update(id, payload).await?;
+        return Ok(Negotiated::new(format, todo).into_response());
+    }
+    match app.service().upsert(id, payload).await? {
+        (todo, true, quota) => {
+            let location = [(header::LOCATION, uri.path().to_string())];
+            let body = Negotiated::new(format, todo);
+            Ok((StatusCode::CREATED, quota, location, body).into_response())
+        }
+        (todo, false, _) => Ok(Negotiated::new(format, todo).into_response()),
+    }
+}
+
+/// `PATCH /todos/batch` - applies several updates independently and reports
+/// each one's outcome with `207 Multi-Status`.
+///
+/// With `If-Match` set to the list's ETag, nothing is applied (`412`) once
+/// the collection has changed since that list was fetched.
+pub async fn batch_update(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    AppJson(payload): AppJson<Vec<BatchUpdate>>,
+) -> Result<(StatusCode, Json<BatchResults>), AppError> {
+    let service = app.service();
+    let results = match headers.get(header::IF_MATCH) {
+        Some(if_match) => {
+            let accepts = |revision| caching::if_match_accepts(if_match, revision);
+            service.update_many_if(payload, accepts).await?
+        }
+        None => service.update_many(payload).await?,
+    };
+    Ok((StatusCode::MULTI_STATUS, Json(BatchResults { results })))
+}
+
+/// `GET /admin/export` - every todo as stored, ids and timestamps included,
+/// for [`import_all`] into another backend.
+pub async fn export_all(State(app): State<AppState>) -> Result<Json<Snapshot>, AppError> {
+    Ok(Json(app.service().export_all().await?))
+}
+
+/// `POST /admin/import?mode=replace|merge` - load an [`export_all`] document.
+/// A merge that finds taken ids writes nothing and answers `409` with them
+/// in `conflicts`.
+///
+/// With `?dry_run=true` nothing is written either way: the answer is a
+/// [`DryRun`] listing the todos the import would remove or write, or the
+/// same `409` the import would get.
+pub async fn import_all(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<ImportQuery>,
+    AppJson(snapshot): AppJson<Snapshot>,
+) -> Result<Response, AppError> {
+    let written: Vec<u64> = snapshot.todos.iter().map(|todo| todo.id).collect();
+    let run = RunMode::dry_run(query.dry_run);
+    let report = app.service().import_all(snapshot, query.mode, run).await?;
+    if !report.conflicts.is_empty() {
+        return Ok((StatusCode::CONFLICT, Json(report)).into_response());
+    }
+    match run {
+        RunMode::Apply => Ok(Json(report).into_response()),
+        RunMode::Preview => {
+            let affected = report.removed.into_iter().chain(written).collect();
+            Ok(Json(DryRun::new(affected)).into_response())
+        }
+    }
+}
 
-    let todo = app.repo().update(id, payload).await?;
-    Ok(Json(todo))
+/// `GET /admin/outbox?state=pending|failed` - webhook deliveries still
+/// queued, oldest first.
+pub async fn outbox_entries(
+    State(app): State<AppState>,
+    AppQuery(query): AppQuery<OutboxQuery>,
+) -> Json<Vec<OutboxEntry>> {
+    Json(app.outbox().entries(query.state))
+}
+
+/// `POST /admin/outbox/:id/retry` - send an entry again now; a failed one
+/// gets a fresh set of attempts.
+pub async fn retry_outbox_entry(
+    Id(id): Id,
+    State(app): State<AppState>,
+) -> Result<Json<OutboxEntry>, AppError> {
+    Ok(Json(app.outbox().retry(id)?))
+}
+
+/// `POST /todos/:id/assign` - set or clear (`null`) the assignee.
+pub async fn assign_todo(
+    Id(id): Id,
+    State(app): State<AppState>,
+    format: Format,
+    AppJson(payload): AppJson<AssignTodo>,
+) -> Result<Negotiated<Todo>, AppError> {
+    let todo = app.service().update(id, UpdateTodo::from(payload)).await?;
+    Ok(Negotiated::new(format, todo))
 }
 
 /// `DELETE /todos/:id` - respond with `204 No Content`.
+///
+/// With `If-Match` set to the todo's ETag, the todo is left alone (`412`)
+/// once it has changed since it was fetched.
 pub async fn delete_todo(
-    Path(id): Path<u64>,
+    Id(id): Id,
     State(app): State<AppState>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, AppError> {
-    app.repo().delete(id).await?;
+    let version = headers.get(header::IF_MATCH).and_then(caching::if_match_version);
+    match version {
+        Some(version) => app.service().delete_if_version(id, version).await?,
+        None => app.service().delete(id).await?,
+    }
     Ok(StatusCode::NO_CONTENT)
 }
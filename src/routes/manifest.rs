@@ -0,0 +1,447 @@
+//! Every route the server registers, in one list.
+//!
+//! [`manifest`] lists each route by method and path template along with
+//! the handler serving it, its [`RouteGroup`] from [`auth::ROUTES`], and the
+//! concurrency [`Limit`]s it counts against. [`app`](crate::app) builds its
+//! router from that list with [`Manifest::router`], and `GET /admin/routes`
+//! serves it to operators.
+//!
+//! # Conflicts
+//!
+//! Before building anything, [`Manifest::router`] checks the list and
+//! panics with every [`Conflict`] it found, so a bad route fails the process
+//! at startup rather than misrouting requests:
+//!
+//! - the same method and template registered twice;
+//! - a literal path a neighbouring parameter would also accept, e.g.
+//!   `/todos/42` next to `/todos/:id`, hiding todo 42. A literal `:id` can't
+//!   take, like `/todos/search`, is fine;
+//! - two names for the same parameter, e.g. `/todos/:id` and
+//!   `/todos/:todo_id/assign`;
+//! - a template missing from [`auth::ROUTES`], whose requests would all be
+//!   refused with `500`.
+
+use std::fmt;
+
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    http::Method,
+    middleware::from_fn_with_state,
+    routing::{delete, get, patch, post, put, MethodRouter},
+    Json, Router,
+};
+use serde::Serialize;
+
+use crate::{
+    auth::{self, RouteGroup},
+    budgets, deprecation,
+    middleware::{self, ConcurrencyLimit},
+    routes, sessions, sharing,
+    state::AppState,
+};
+
+/// A concurrency limit a route counts against on top of
+/// `MAX_CONCURRENT_REQUESTS`, which every route but the probes does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    /// `MAX_CONCURRENT_EXPENSIVE_REQUESTS`, for routes that walk every todo.
+    Expensive,
+    /// `MAX_CONCURRENT_EXPORTS`. A big export can hold its slot for a long
+    /// time, so exports can't take every expensive slot between them.
+    Exports,
+}
+
+/// One method on one path template.
+#[derive(Clone)]
+pub struct Route {
+    pub method: Method,
+    pub path: String,
+    /// The function serving it, for people reading the manifest.
+    pub handler: &'static str,
+    pub limits: Vec<Limit>,
+    /// Outside `/v1`: marked deprecated and counted; see [`deprecation`].
+    pub deprecated: bool,
+    make: fn(&AppState) -> MethodRouter<AppState>,
+}
+
+impl Route {
+    /// `method` on `path`, served by the method router `make` returns.
+    pub fn new(
+        method: Method,
+        path: impl Into<String>,
+        handler: &'static str,
+        make: fn(&AppState) -> MethodRouter<AppState>,
+    ) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            handler,
+            limits: Vec::new(),
+            deprecated: false,
+            make,
+        }
+    }
+
+    /// Counts the route against `limit` as well.
+    pub fn limit(mut self, limit: Limit) -> Self {
+        self.limits.push(limit);
+        self
+    }
+
+    /// The group whose policy applies, `None` if [`auth::ROUTES`] lacks it.
+    pub fn group(&self) -> Option<RouteGroup> {
+        auth::group(&self.path)
+    }
+}
+
+/// A problem [`Manifest::check`] found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    Duplicate { method: Method, path: String },
+    Shadowed { literal: String, param: String },
+    ParamNames { first: String, second: String },
+    Unlisted { path: String },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate { method, path } => write!(f, "{method} {path} is registered twice"),
+            Self::Shadowed { literal, param } => {
+                write!(f, "{literal} shadows {param}, which accepts the same path")
+            }
+            Self::ParamNames { first, second } => {
+                write!(f, "{first} and {second} name the same parameter differently")
+            }
+            Self::Unlisted { path } => write!(f, "{path} has no entry in auth::ROUTES"),
+        }
+    }
+}
+
+/// The routes to serve, in registration order.
+#[derive(Clone, Default)]
+pub struct Manifest {
+    routes: Vec<Route>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Every conflict between the routes; see the [module docs](self).
+    pub fn check(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (i, route) in self.routes.iter().enumerate() {
+            for earlier in &self.routes[..i] {
+                if earlier.path == route.path {
+                    if earlier.method == route.method {
+                        conflicts.push(Conflict::Duplicate {
+                            method: route.method.clone(),
+                            path: route.path.clone(),
+                        });
+                    }
+                } else if let Some(conflict) = overlap(&earlier.path, &route.path) {
+                    if !conflicts.contains(&conflict) {
+                        conflicts.push(conflict);
+                    }
+                }
+            }
+            if route.group().is_none() && !self.routes[..i].iter().any(|r| r.path == route.path) {
+                conflicts.push(Conflict::Unlisted {
+                    path: route.path.clone(),
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// The router serving every route, with its concurrency limits and, for
+    /// deprecated routes, the [`deprecation::legacy`] middleware.
+    ///
+    /// # Panics
+    ///
+    /// When [`check`](Self::check) finds conflicts, naming all of them.
+    pub fn router(&self, state: &AppState) -> Router<AppState> {
+        let conflicts = self.check();
+        if !conflicts.is_empty() {
+            let list: Vec<String> = conflicts.iter().map(Conflict::to_string).collect();
+            panic!("conflicting routes:\n  {}", list.join("\n  "));
+        }
+
+        let config = state.config();
+        let exports = ConcurrencyLimit::new(config.max_concurrent_exports);
+        let expensive = ConcurrencyLimit::new(config.max_concurrent_expensive_requests);
+        let mut limited = Router::new();
+        let mut probes = Router::new();
+        for route in &self.routes {
+            let mut method_router = (route.make)(state);
+            if route.deprecated {
+                let legacy = from_fn_with_state(state.clone(), deprecation::legacy);
+                method_router = method_router.route_layer(legacy);
+            }
+            let mut router = Router::new().route(&route.path, method_router);
+            // The exports limit sits inside the expensive one.
+            if route.limits.contains(&Limit::Exports) {
+                router = middleware::limit_concurrency(router, &exports);
+            }
+            if route.limits.contains(&Limit::Expensive) {
+                router = middleware::limit_concurrency(router, &expensive);
+            }
+            if route.group() == Some(RouteGroup::Probes) {
+                probes = probes.merge(router);
+            } else {
+                limited = limited.merge(router);
+            }
+        }
+
+        // The probes are added after the limit so they still answer while
+        // every other route is shedding load.
+        let all = ConcurrencyLimit::new(config.max_concurrent_requests);
+        middleware::limit_concurrency(limited, &all).merge(probes)
+    }
+}
+
+/// The conflict between two different templates, if they name a parameter
+/// differently or a request path could match both.
+fn overlap(first: &str, second: &str) -> Option<Conflict> {
+    let a: Vec<&str> = first.split('/').collect();
+    let b: Vec<&str> = second.split('/').collect();
+    for (x, y) in a.iter().zip(&b) {
+        match (param(x), param(y)) {
+            (Some(p), Some(q)) if p != q => {
+                return Some(Conflict::ParamNames {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                });
+            }
+            (Some(_), Some(_)) => {}
+            (None, None) if x == y => {}
+            // Past here the templates are in different branches.
+            _ => break,
+        }
+    }
+
+    let shadowed = |literal: &str, param: &str| Conflict::Shadowed {
+        literal: literal.to_string(),
+        param: param.to_string(),
+    };
+    if covers(&a, &b) {
+        Some(shadowed(second, first))
+    } else if covers(&b, &a) {
+        Some(shadowed(first, second))
+    } else {
+        None
+    }
+}
+
+/// Whether every path `literal` matches, `param` would match too.
+fn covers(param_segments: &[&str], literal: &[&str]) -> bool {
+    for (i, segment) in param_segments.iter().enumerate() {
+        let Some(other) = literal.get(i) else {
+            return false;
+        };
+        match param(segment) {
+            // A catch-all takes the rest of the path.
+            Some(name) if segment.starts_with('*') => return accepts(name, other),
+            Some(name) if other == segment || accepts(name, other) => {}
+            None if other == segment => {}
+            _ => return false,
+        }
+    }
+    param_segments.len() == literal.len()
+}
+
+/// The name of a `:param` or `*catch_all` segment.
+fn param(segment: &str) -> Option<&str> {
+    segment.strip_prefix(':').or_else(|| segment.strip_prefix('*'))
+}
+
+/// Whether the parameter `name` takes the literal segment. `:id` only takes
+/// ids, as [`Id`](routes::Id) parses them; anything else takes any segment.
+fn accepts(name: &str, literal: &str) -> bool {
+    match name {
+        "id" => literal.parse::<u64>().is_ok_and(|id| id > 0),
+        _ => !literal.is_empty(),
+    }
+}
+
+/// The todo, attachment, preferences and template routes under `prefix`.
+fn api(prefix: &str, deprecated: bool) -> Vec<Route> {
+    let route = |method, path: &str, handler, make| Route {
+        deprecated,
+        ..Route::new(method, format!("{prefix}{path}"), handler, make)
+    };
+    vec![
+        route(Method::GET, "/todos", "list_todos", |_| get(routes::list_todos)),
+        route(Method::POST, "/todos", "create_todo", |_| post(routes::create_todo)),
+        route(Method::GET, "/todos/search", "search_todos", |_| get(routes::search_todos))
+            .limit(Limit::Expensive),
+        route(Method::GET, "/todos/calendar.ics", "calendar", |_| get(routes::calendar))
+            .limit(Limit::Expensive)
+            .limit(Limit::Exports),
+        route(Method::GET, "/todos/feed.atom", "feed", |_| get(routes::feed))
+            .limit(Limit::Expensive)
+            .limit(Limit::Exports),
+        route(Method::GET, "/todos/stats/timeseries", "timeseries", |_| get(routes::timeseries))
+            .limit(Limit::Expensive),
+        route(Method::GET, "/todos/stats/storage", "storage_usage", |_| {
+            get(routes::storage_usage)
+        }),
+        route(Method::GET, "/todos/changes", "changes", |_| get(routes::changes)),
+        route(Method::GET, "/todos/poll", "poll", |_| get(routes::poll)),
+        route(Method::GET, "/todos/events", "event_stream", |_| get(routes::event_stream)),
+        route(Method::GET, "/todos/events/log", "event_log", |_| get(routes::event_log)),
+        route(Method::PATCH, "/todos/batch", "batch_update", |_| patch(routes::batch_update)),
+        route(Method::GET, "/todos/:id", "get_todo", |_| get(routes::get_todo)),
+        route(Method::PUT, "/todos/:id", "update_todo", |_| put(routes::update_todo)),
+        route(Method::DELETE, "/todos/:id", "delete_todo", |_| delete(routes::delete_todo)),
+        route(Method::POST, "/todos/:id/assign", "assign_todo", |_| post(routes::assign_todo)),
+        route(Method::POST, "/todos/:id/share", "sharing::share", |_| post(sharing::share)),
+        route(Method::DELETE, "/todos/:id/share", "sharing::unshare", |_| {
+            delete(sharing::unshare)
+        }),
+        route(Method::GET, "/todos/:id/attachments", "list_attachments", |_| {
+            get(routes::list_attachments)
+        }),
+        // Uploads enforce `ATTACHMENT_MAX_BYTES` while streaming.
+        route(Method::POST, "/todos/:id/attachments", "upload_attachment", |_| {
+            post(routes::upload_attachment).layer(DefaultBodyLimit::disable())
+        }),
+        route(Method::GET, "/attachments/:id", "download_attachment", |_| {
+            get(routes::download_attachment)
+        }),
+        route(Method::DELETE, "/attachments/:id", "delete_attachment", |_| {
+            delete(routes::delete_attachment)
+        }),
+        route(Method::GET, "/preferences", "get_preferences", |_| get(routes::get_preferences)),
+        route(Method::PUT, "/preferences", "put_preferences", |_| put(routes::put_preferences)),
+        route(Method::GET, "/templates", "list_templates", |_| get(routes::list_templates)),
+        route(Method::POST, "/templates", "create_template", |_| post(routes::create_template)),
+        route(Method::GET, "/templates/:id", "get_template", |_| get(routes::get_template)),
+        route(Method::PUT, "/templates/:id", "replace_template", |_| {
+            put(routes::replace_template)
+        }),
+        route(Method::DELETE, "/templates/:id", "delete_template", |_| {
+            delete(routes::delete_template)
+        }),
+        route(Method::POST, "/templates/:id/instantiate", "instantiate_template", |_| {
+            post(routes::instantiate_template)
+        }),
+    ]
+}
+
+/// Every route [`app`](crate::app) serves.
+///
+/// The API lives under `/v1`. The unprefixed routes predate it and stay for
+/// existing clients, marked deprecated and counted until they go.
+pub fn manifest() -> Manifest {
+    let mut manifest = Manifest::new()
+        .route(Route::new(Method::GET, "/metrics", "metrics", |_| get(routes::metrics)));
+    for route in api("/v1", false).into_iter().chain(api("", true)) {
+        manifest = manifest.route(route);
+    }
+
+    // Operator-only routes, behind `ADMIN_POLICY`.
+    manifest = manifest
+        .route(
+            Route::new(Method::GET, "/admin/export", "export_all", |_| get(routes::export_all))
+                .limit(Limit::Exports),
+        )
+        .route(Route::new(Method::POST, "/admin/import", "import_all", |state| {
+            budgets::import(post(routes::import_all), state)
+        }))
+        .route(Route::new(Method::GET, "/admin/outbox", "outbox_entries", |_| {
+            get(routes::outbox_entries)
+        }))
+        .route(Route::new(Method::POST, "/admin/outbox/:id/retry", "retry_outbox_entry", |_| {
+            post(routes::retry_outbox_entry)
+        }))
+        .route(Route::new(Method::GET, "/admin/routes", "route_manifest", |_| {
+            get(route_manifest)
+        }));
+
+    // Browser logins; see `sessions`.
+    manifest = manifest
+        .route(Route::new(Method::POST, "/auth/session", "sessions::login", |_| {
+            post(sessions::login)
+        }))
+        .route(Route::new(Method::DELETE, "/auth/session", "sessions::logout", |_| {
+            delete(sessions::logout)
+        }));
+
+    // Anonymous read-only links; see `sharing`.
+    manifest = manifest.route(Route::new(Method::GET, "/shared/:token", "sharing::open", |state| {
+        get(sharing::open).route_layer(from_fn_with_state(state.clone(), sharing::open_budget))
+    }));
+
+    #[cfg(feature = "graphql")]
+    {
+        use crate::graphql;
+
+        manifest = manifest
+            .route(Route::new(Method::GET, "/graphql", "graphql::playground", |_| {
+                get(graphql::playground)
+            }))
+            .route(Route::new(Method::POST, "/graphql", "graphql::execute", |state| {
+                post(graphql::execute).layer(axum::Extension(graphql::schema(state.clone())))
+            }));
+    }
+
+    #[cfg(feature = "ui")]
+    {
+        use crate::ui;
+
+        manifest = manifest
+            .route(Route::new(Method::GET, "/ui", "ui::redirect", |_| get(ui::redirect)))
+            .route(Route::new(Method::GET, "/ui/", "ui::index", |_| get(ui::index)))
+            .route(Route::new(Method::GET, "/ui/*path", "ui::asset", |_| get(ui::asset)));
+    }
+
+    manifest
+        .route(Route::new(Method::GET, "/health", "health", |_| get(routes::health)))
+        .route(Route::new(Method::GET, "/ready", "ready", |_| get(routes::ready)))
+}
+
+/// A [`Route`] as `GET /admin/routes` lists it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteInfo {
+    pub method: String,
+    pub path: String,
+    pub handler: &'static str,
+    pub group: Option<RouteGroup>,
+    /// The group's policy under the current configuration.
+    pub policy: Option<String>,
+    pub limits: Vec<Limit>,
+    pub deprecated: bool,
+}
+
+/// `GET /admin/routes` - every route the server registers, in registration
+/// order, with the policy currently guarding it.
+pub async fn route_manifest(State(app): State<AppState>) -> Json<Vec<RouteInfo>> {
+    let config = app.config();
+    let routes = manifest()
+        .routes()
+        .iter()
+        .map(|route| RouteInfo {
+            method: route.method.to_string(),
+            path: route.path.clone(),
+            handler: route.handler,
+            group: route.group(),
+            policy: route.group().map(|group| group.policy(&config).to_string()),
+            limits: route.limits.clone(),
+            deprecated: route.deprecated,
+        })
+        .collect();
+    Json(routes)
+}
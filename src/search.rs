@@ -0,0 +1,165 @@
+//! Full-text search over titles and descriptions.
+//!
+//! # Tokens
+//!
+//! Text is split on Unicode word boundaries (UAX #29) and each word reduced
+//! to its [`canonical_key`], so `"Milk, eggs & CAFE\u{301}"` yields `milk`,
+//! `eggs`, `café`. Queries go through the same [`tokenize`] as documents.
+//!
+//! # Index
+//!
+//! [`Index`] is an inverted index from token to the todos containing it and
+//! how often. The in-memory repository keeps one up to date on every write;
+//! other backends get one built on the fly by the default
+//! [`TodoRepo::search`](crate::state::TodoRepo::search).
+//!
+//! # Ranking
+//!
+//! Todos matching more distinct query terms always rank first. Ties are
+//! broken by a TF-IDF score where title hits weigh [`TITLE_WEIGHT`] times a
+//! description hit and rare terms count more than common ones, then by id.
+//!
+//! # Highlights
+//!
+//! Each hit carries the `[start, end)` byte offsets of matched words in the
+//! title and description, so clients can wrap them in `<em>` or whatever
+//! their UI needs.
+
+use std::collections::{BTreeMap, HashMap};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::models::{canonical_key, Highlights, SearchHit, Todo};
+
+/// How much more a title occurrence counts than a description one.
+pub const TITLE_WEIGHT: f64 = 2.0;
+
+/// Canonical words of `text`, in order.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.unicode_words().map(canonical_key)
+}
+
+/// Distinct tokens of a query, in the order given.
+pub fn terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for token in tokenize(query) {
+        if !terms.contains(&token) {
+            terms.push(token);
+        }
+    }
+    terms
+}
+
+/// Byte ranges of the words in `text` that match one of `terms`.
+pub fn highlight(text: &str, terms: &[String]) -> Vec<[usize; 2]> {
+    text.unicode_word_indices()
+        .filter(|(_, word)| terms.contains(&canonical_key(word)))
+        .map(|(start, word)| [start, start + word.len()])
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Frequency {
+    title: u32,
+    description: u32,
+}
+
+/// Inverted index of todo titles and descriptions.
+#[derive(Debug, Default)]
+pub struct Index {
+    postings: HashMap<String, BTreeMap<u64, Frequency>>,
+    docs: usize,
+}
+
+impl Index {
+    /// Adds a todo. Call [`Index::remove`] with the old version first when
+    /// re-indexing an update.
+    pub fn insert(&mut self, todo: &Todo) {
+        self.docs += 1;
+        for token in tokenize(&todo.title) {
+            self.entry(token, todo.id).title += 1;
+        }
+        for token in tokenize(todo.description.as_deref().unwrap_or_default()) {
+            self.entry(token, todo.id).description += 1;
+        }
+    }
+
+    /// Forgets a todo, given the version that was inserted.
+    pub fn remove(&mut self, todo: &Todo) {
+        self.docs = self.docs.saturating_sub(1);
+        let description = todo.description.as_deref().unwrap_or_default();
+        for token in tokenize(&todo.title).chain(tokenize(description)) {
+            if let Some(docs) = self.postings.get_mut(&token) {
+                docs.remove(&todo.id);
+                if docs.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    /// Number of todos containing `token`.
+    pub fn document_frequency(&self, token: &str) -> usize {
+        self.postings.get(token).map_or(0, BTreeMap::len)
+    }
+
+    /// The best `limit` matches for `query`, resolved through `lookup`.
+    pub fn search<'a>(
+        &self,
+        query: &str,
+        limit: usize,
+        lookup: impl Fn(u64) -> Option<&'a Todo>,
+    ) -> Vec<SearchHit> {
+        let terms = terms(query);
+        // id -> (distinct terms matched, score)
+        let mut ranked: HashMap<u64, (usize, f64)> = HashMap::new();
+        for term in &terms {
+            let Some(docs) = self.postings.get(term) else {
+                continue;
+            };
+            let idf = (1.0 + self.docs as f64 / docs.len() as f64).ln();
+            for (&id, frequency) in docs {
+                let tf = TITLE_WEIGHT * f64::from(frequency.title)
+                    + f64::from(frequency.description);
+                let (matched, score) = ranked.entry(id).or_default();
+                *matched += 1;
+                *score += tf * idf;
+            }
+        }
+
+        let mut ranked: Vec<(u64, usize, f64)> = ranked
+            .into_iter()
+            .map(|(id, (matched, score))| (id, matched, score))
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then(b.2.total_cmp(&a.2))
+                .then(a.0.cmp(&b.0))
+        });
+
+        ranked
+            .into_iter()
+            .filter_map(|(id, _, score)| Some((lookup(id)?, score)))
+            .take(limit)
+            .map(|(todo, score)| SearchHit {
+                highlights: Highlights {
+                    title: highlight(&todo.title, &terms),
+                    description: highlight(
+                        todo.description.as_deref().unwrap_or_default(),
+                        &terms,
+                    ),
+                },
+                todo: todo.clone(),
+                score,
+            })
+            .collect()
+    }
+
+    fn entry(&mut self, token: String, id: u64) -> &mut Frequency {
+        self.postings
+            .entry(token)
+            .or_default()
+            .entry(id)
+            .or_default()
+    }
+}
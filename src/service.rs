@@ -0,0 +1,534 @@
+//! Business rules shared by every transport.
+//!
+//! REST, GraphQL, and gRPC handlers translate their requests into calls on
+//! [`TodoService`] and its results back into responses; they don't talk to
+//! the repository for writes. That keeps the rules in one place:
+//!
+//! - new todos are validated and checked against the duplicate policy
+//!   (updates are validated by the repository itself) and the
+//!   [quota](crate::quota);
+//! - every write publishes its [`TodoEvent`](crate::events::TodoEvent)s, via
+//!   the [`Publishing`] decorator the service wraps its repository in;
+//! - every write is recorded in the [`AuditLog`].
+//!
+//! Reads have no rules beyond the repository's; the service passes them
+//! through so handlers never need the repository itself, [caching](crate::list_cache)
+//! and [coalescing](crate::coalesce) list pages along the way. [`TodoService::repo`]
+//! remains for streaming lists, background jobs, and tests.
+
+use std::{collections::HashSet, future::Future, ops::Range, sync::Arc};
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    audit::{AuditAction, AuditLog},
+    coalesce::Coalescer,
+    collation::Collation,
+    config::Config,
+    duplicates,
+    errors::AppError,
+    events::{EventBus, Publishing},
+    list_cache::{CachedPage, ListCache, ListKey},
+    metrics::Metrics,
+    models::{
+        Attachment, BatchResult, BatchStatus, BatchUpdate, Bucket, Changes, CreateTodo,
+        CreatedTodo, ImportMode, ImportReport, NewAttachment, Pagination, RunMode, SearchHit,
+        Snapshot, SortField, SortOrder, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+    },
+    quota::Quota,
+    state::TodoRepo,
+};
+
+pub struct TodoService {
+    /// Wrapped in [`Publishing`], so writes through it emit events.
+    repo: Arc<dyn TodoRepo>,
+    events: Arc<dyn EventBus>,
+    audit: AuditLog,
+    config: Arc<ArcSwap<Config>>,
+    list_cache: ListCache,
+    /// List reads in flight, by page and the revision they read at.
+    list_reads: Coalescer<(ListKey, Option<u64>), Arc<CachedPage>>,
+    /// Held from counting todos for the quota until the create is done.
+    creating: tokio::sync::Mutex<()>,
+}
+
+impl TodoService {
+    /// Serves `repo`, publishing to `events` and reading the duplicate policy
+    /// and list cache size from `config` on each call. List cache hits and
+    /// misses are counted in `metrics`.
+    pub fn new(
+        repo: Arc<dyn TodoRepo>,
+        events: Arc<dyn EventBus>,
+        config: Arc<ArcSwap<Config>>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            repo: Arc::new(Publishing::new(repo, Arc::clone(&events))),
+            events,
+            audit: AuditLog::default(),
+            list_cache: ListCache::new(Arc::clone(&config), metrics),
+            list_reads: Coalescer::default(),
+            config,
+            creating: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The publishing repository, for code that needs an owned handle or a
+    /// method the service doesn't pass through. Writes through it publish
+    /// events but skip the audit log.
+    pub fn repo(&self) -> &Arc<dyn TodoRepo> {
+        &self.repo
+    }
+
+    pub fn events(&self) -> &dyn EventBus {
+        self.events.as_ref()
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Validates `input`, applies the duplicate policy (`strict_duplicates`
+    /// refuses near-duplicates instead of listing them), and creates the todo.
+    pub async fn create(
+        &self,
+        input: CreateTodo,
+        strict_duplicates: bool,
+    ) -> Result<CreatedTodo, AppError> {
+        input.validate()?;
+        let config = self.config.load();
+        let possible_duplicates =
+            duplicates::check(self.repo.as_ref(), &config, &input.title, strict_duplicates)
+                .await?;
+        let (todo, quota) = self.within_quota(1, self.repo.create(input)).await?;
+        self.audit.record(AuditAction::Create, todo.id);
+        Ok(CreatedTodo {
+            todo,
+            possible_duplicates,
+            quota,
+        })
+    }
+
+    /// Validates every input, then creates them all with
+    /// [`TodoRepo::create_many`]. The duplicate policy doesn't apply: the
+    /// caller asked for exactly these todos. Returns the quota standing
+    /// afterwards, if there is a quota.
+    pub async fn create_many(
+        &self,
+        inputs: Vec<CreateTodo>,
+    ) -> Result<(Vec<Todo>, Option<Quota>), AppError> {
+        for input in &inputs {
+            input.validate()?;
+        }
+        let count = inputs.len();
+        let (todos, quota) = self.within_quota(count, self.repo.create_many(inputs)).await?;
+        for todo in &todos {
+            self.audit.record(AuditAction::Create, todo.id);
+        }
+        Ok((todos, quota))
+    }
+
+    /// The quota standing now; `None` when `MAX_TODOS` is off.
+    pub async fn quota(&self) -> Result<Option<Quota>, AppError> {
+        let config = self.config.load();
+        if config.max_todos == 0 {
+            return Ok(None);
+        }
+        let used = self.repo.count(&TodoFilter::default()).await?;
+        Ok(Quota::new(&config, used))
+    }
+
+    /// Runs `create`, which adds `count` todos, if they fit in the quota.
+    /// Counting and creating happen under one lock, so two creates can't
+    /// both see the last free slot.
+    async fn within_quota<T>(
+        &self,
+        count: usize,
+        create: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<(T, Option<Quota>), AppError> {
+        if self.config.load().max_todos == 0 {
+            return Ok((create.await?, None));
+        }
+        let _creating = self.creating.lock().await;
+        let Some(quota) = self.quota().await? else {
+            return Ok((create.await?, None));
+        };
+        let after = quota.admit(count)?;
+        Ok((create.await?, Some(after)))
+    }
+
+    pub async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        self.repo.get(id).await
+    }
+
+    /// One page of todos matching `filter`, with `page.total` filled in.
+    pub async fn list(
+        &self,
+        filter: &TodoFilter,
+        page: Pagination,
+    ) -> Result<(Vec<Todo>, Pagination), AppError> {
+        let (read, page) = self.list_page(filter, page).await?;
+        Ok((read.todos.clone(), page))
+    }
+
+    /// Like [`list`](Self::list), returning the page as the
+    /// [cache](crate::list_cache) holds it, so its encoded bodies are reused.
+    pub async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        page: Pagination,
+    ) -> Result<(Arc<CachedPage>, Pagination), AppError> {
+        let read = async {
+            let todos = self.repo.list_page(filter, page.offset, page.limit).await?;
+            let total = self.repo.count(filter).await?;
+            Ok(CachedPage::new(todos, total))
+        };
+        let sort = (SortField::Id, SortOrder::Asc, Collation::default());
+        self.cached(filter, sort, page, read).await
+    }
+
+    /// Up to `limit` todos matching `filter` with ids above `after`.
+    pub async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.repo.list_after(filter, after, limit).await
+    }
+
+    /// Like [`list`](Self::list), ordered by `sort` instead of id, with
+    /// titles compared under `collation`. The matching todos are sorted in
+    /// memory before paging, so each call reads all of them.
+    pub async fn list_sorted(
+        &self,
+        filter: &TodoFilter,
+        sort: SortField,
+        order: SortOrder,
+        collation: Collation,
+        page: Pagination,
+    ) -> Result<(Vec<Todo>, Pagination), AppError> {
+        let (read, page) = self.list_sorted_page(filter, sort, order, collation, page).await?;
+        Ok((read.todos.clone(), page))
+    }
+
+    /// [`list_sorted`](Self::list_sorted) as [`list_page`](Self::list_page)
+    /// returns it.
+    pub async fn list_sorted_page(
+        &self,
+        filter: &TodoFilter,
+        sort: SortField,
+        order: SortOrder,
+        collation: Collation,
+        page: Pagination,
+    ) -> Result<(Arc<CachedPage>, Pagination), AppError> {
+        let read = async {
+            let mut todos = self.list_matching(filter).await?;
+            let collator = collation.collator();
+            todos.sort_by(|a, b| match order {
+                SortOrder::Asc => sort.compare(&collator, a, b),
+                SortOrder::Desc => sort.compare(&collator, b, a),
+            });
+            let total = todos.len();
+            let todos = todos.into_iter().skip(page.offset).take(page.limit).collect();
+            Ok(CachedPage::new(todos, total))
+        };
+        self.cached(filter, (sort, order, collation), page, read).await
+    }
+
+    /// The page `read` returns, or the [cached](crate::list_cache) one while
+    /// the revision it was read at is current. Identical reads in flight at
+    /// the same time are [coalesced](crate::coalesce) into one.
+    async fn cached(
+        &self,
+        filter: &TodoFilter,
+        (sort, order, collation): (SortField, SortOrder, Collation),
+        mut page: Pagination,
+        read: impl Future<Output = Result<CachedPage, AppError>>,
+    ) -> Result<(Arc<CachedPage>, Pagination), AppError> {
+        // The revision is read before the page, so a write landing in between
+        // stores a page newer than its revision, never an older one.
+        let revision = if self.list_cache.enabled() {
+            self.repo.revision().await?
+        } else {
+            None
+        };
+        let key = ListKey::new(filter, sort, order, collation, page.offset, page.limit);
+        if let Some(cached) = revision.and_then(|revision| self.list_cache.get(&key, revision)) {
+            page.total = cached.total;
+            return Ok((cached, page));
+        }
+        let read = async { read.await.map(Arc::new) };
+        let read = if self.config.load().coalesce_lists {
+            self.list_reads.run((key.clone(), revision), read).await?
+        } else {
+            read.await?
+        };
+        if let Some(revision) = revision {
+            self.list_cache.put(key, revision, Arc::clone(&read));
+        }
+        page.total = read.total;
+        Ok((read, page))
+    }
+
+    /// Every todo matching `filter`, in id order.
+    pub async fn list_matching(&self, filter: &TodoFilter) -> Result<Vec<Todo>, AppError> {
+        self.repo.list_page(filter, 0, usize::MAX).await
+    }
+
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        self.repo.search(query, limit).await
+    }
+
+    pub async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        self.repo.changes_since(since).await
+    }
+
+    pub async fn revision(&self) -> Result<Option<u64>, AppError> {
+        self.repo.revision().await
+    }
+
+    pub async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        self.repo.timeseries(bucket, range).await
+    }
+
+    pub async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        self.repo.add_attachment(todo_id, input).await
+    }
+
+    pub async fn attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.repo.list_attachments(todo_id).await
+    }
+
+    pub async fn attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.repo.get_attachment(id).await
+    }
+
+    pub async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.repo.delete_attachment(id).await
+    }
+
+    pub async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        let todo = self.repo.update(id, input).await?;
+        self.audit.record(AuditAction::Update, id);
+        Ok(todo)
+    }
+
+    /// Updates todo `id`, or creates it with that id from a full
+    /// representation, for clients that choose their own ids. Returns whether
+    /// it was created. Client-created todos skip the duplicate policy: a
+    /// client retrying a push is exactly what the id makes safe.
+    ///
+    /// A create counts against the quota like any other, and comes back with
+    /// the standing after it. With a quota, every upsert holds the create
+    /// lock, so the todo can't vanish between checking it exists and
+    /// writing.
+    pub async fn upsert(
+        &self,
+        id: u64,
+        input: UpdateTodo,
+    ) -> Result<(Todo, bool, Option<Quota>), AppError> {
+        let limited = self.config.load().max_todos != 0;
+        let _creating = if limited {
+            Some(self.creating.lock().await)
+        } else {
+            None
+        };
+        let mut quota = None;
+        if limited && input.title.is_some() {
+            if let Err(AppError::NotFound) = self.repo.get(id).await {
+                quota = self.quota().await?.map(|quota| quota.admit(1)).transpose()?;
+            }
+        }
+        let (todo, created) = self.repo.upsert(id, input).await?;
+        let action = if created {
+            AuditAction::Create
+        } else {
+            AuditAction::Update
+        };
+        self.audit.record(action, id);
+        Ok((todo, created, quota.filter(|_| created)))
+    }
+
+    /// Applies each update on its own, in order, so one bad entry doesn't
+    /// stop the rest. Only the batch as a whole is checked up front: it must
+    /// have 1 to [`BatchUpdate::MAX_ENTRIES`] entries with distinct ids.
+    pub async fn update_many(
+        &self,
+        updates: Vec<BatchUpdate>,
+    ) -> Result<Vec<BatchResult>, AppError> {
+        check_batch(&updates)?;
+        let mut results = Vec::with_capacity(updates.len());
+        for BatchUpdate { id, update } in updates {
+            let updated = match update {
+                Ok(update) => self.update(id, update).await,
+                Err(refused) => Err(AppError::Validation(refused)),
+            };
+            results.push(batch_result(id, updated));
+        }
+        Ok(results)
+    }
+
+    /// Like [`update_many`](Self::update_many), but only if `accepts` the
+    /// collection's current revision; otherwise
+    /// [`AppError::PreconditionFailed`] and nothing is applied. The
+    /// repository checks the revision and applies the batch in one write
+    /// ([`TodoRepo::update_many_at`]), so a write landing after the check
+    /// fails the batch instead of being overwritten.
+    pub async fn update_many_if(
+        &self,
+        updates: Vec<BatchUpdate>,
+        accepts: impl FnOnce(u64) -> bool,
+    ) -> Result<Vec<BatchResult>, AppError> {
+        check_batch(&updates)?;
+        let revision = match self.repo.revision().await? {
+            Some(revision) if accepts(revision) => revision,
+            revision => return Err(AppError::PreconditionFailed { revision }),
+        };
+
+        let mut entries = Vec::with_capacity(updates.len());
+        let mut valid = Vec::with_capacity(updates.len());
+        for BatchUpdate { id, update } in updates {
+            match update {
+                Ok(update) => {
+                    valid.push((id, update));
+                    entries.push((id, None));
+                }
+                Err(refused) => entries.push((id, Some(refused))),
+            }
+        }
+        let mut applied = self.repo.update_many_at(revision, valid).await?.into_iter();
+        let mut results = Vec::with_capacity(entries.len());
+        for (id, refused) in entries {
+            let updated = match refused {
+                None => applied.next().expect("one result per update"),
+                Some(refused) => Err(AppError::Validation(refused)),
+            };
+            if updated.is_ok() {
+                self.audit.record(AuditAction::Update, id);
+            }
+            results.push(batch_result(id, updated));
+        }
+        Ok(results)
+    }
+
+    /// Flips `done` in one repository write; see [`TodoRepo::toggle`].
+    pub async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        let todo = self.repo.toggle(id).await?;
+        self.audit.record(AuditAction::Update, id);
+        Ok(todo)
+    }
+
+    pub async fn delete(&self, id: u64) -> Result<(), AppError> {
+        self.repo.delete(id).await?;
+        self.audit.record(AuditAction::Delete, id);
+        Ok(())
+    }
+
+    /// Deletes `id` only while it is at `version`; see
+    /// [`TodoRepo::delete_if_version`].
+    pub async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        self.repo.delete_if_version(id, version).await?;
+        self.audit.record(AuditAction::Delete, id);
+        Ok(())
+    }
+
+    pub async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        self.repo.version(id).await
+    }
+
+    pub async fn export_all(&self) -> Result<Snapshot, AppError> {
+        self.repo.export_all().await
+    }
+
+    /// Loads a snapshot; an imported todo is audited as created whether or
+    /// not it overwrote one. A preview is not audited.
+    ///
+    /// A merge adds todos, so it must fit in the quota, previews included so
+    /// they answer as the import would. Taken ids are reported as conflicts
+    /// before the quota is looked at. A replace is exempt: it restores the
+    /// store to a known state rather than growing it.
+    pub async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        let ids: Vec<u64> = snapshot.todos.iter().map(|todo| todo.id).collect();
+        let report = if mode == ImportMode::Merge && self.config.load().max_todos != 0 {
+            // Held like a create, so nothing takes the slots between the
+            // checks and the import.
+            let _creating = self.creating.lock().await;
+            let preview =
+                self.repo.import_all(snapshot.clone(), mode, RunMode::Preview).await?;
+            if !preview.conflicts.is_empty() {
+                return Ok(preview);
+            }
+            if let Some(quota) = self.quota().await? {
+                quota.admit(ids.len())?;
+            }
+            match run {
+                RunMode::Preview => preview,
+                RunMode::Apply => self.repo.import_all(snapshot, mode, run).await?,
+            }
+        } else {
+            self.repo.import_all(snapshot, mode, run).await?
+        };
+        if run == RunMode::Apply && report.conflicts.is_empty() {
+            for &id in &report.removed {
+                self.audit.record(AuditAction::Delete, id);
+            }
+            for id in ids {
+                self.audit.record(AuditAction::Create, id);
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Checks a batch as a whole: 1 to [`BatchUpdate::MAX_ENTRIES`] entries with
+/// distinct ids.
+fn check_batch(updates: &[BatchUpdate]) -> Result<(), AppError> {
+    if !(1..=BatchUpdate::MAX_ENTRIES).contains(&updates.len()) {
+        return Err(AppError::Validation(
+            format!("a batch must have between 1 and {} entries", BatchUpdate::MAX_ENTRIES).into(),
+        ));
+    }
+    let mut seen = HashSet::new();
+    if let Some(repeated) = updates.iter().find(|entry| !seen.insert(entry.id)) {
+        return Err(AppError::Validation(
+            format!("todo {} appears more than once in the batch", repeated.id).into(),
+        ));
+    }
+    Ok(())
+}
+
+/// How one batch entry went.
+fn batch_result(id: u64, updated: Result<Todo, AppError>) -> BatchResult {
+    let (status, todo, error) = match updated {
+        Ok(todo) => (BatchStatus::Ok, Some(todo), None),
+        Err(err) => {
+            let status = match err {
+                AppError::NotFound => BatchStatus::NotFound,
+                AppError::Validation(_) => BatchStatus::Invalid,
+                _ => BatchStatus::Error,
+            };
+            (status, None, Some(err.to_string()))
+        }
+    };
+    BatchResult {
+        id,
+        status,
+        todo,
+        error,
+    }
+}
@@ -0,0 +1,222 @@
+//! Cookie sessions for browser frontends.
+//!
+//! A browser app shouldn't keep an API key where every script on the page
+//! can read it. It trades the key for a session once instead:
+//! `POST /auth/session` with `{"api_key": "..."}` sets an `HttpOnly`,
+//! `SameSite=Lax`, `Secure` [`SESSION_COOKIE`] naming an entry in the
+//! [`SessionStore`] on the [`AppState`], and [`auth`](crate::auth) accepts
+//! that cookie wherever it accepts the key. The key itself stays on the
+//! server and is checked against `API_KEYS` and `ADMIN_API_KEYS` on every
+//! request, so revoking it ends its sessions too. A session lasts
+//! `SESSION_TTL_SECS`; `DELETE /auth/session` ends it at once.
+//!
+//! Browsers attach cookies to requests other sites trigger, so a request
+//! authenticated by cookie that may change something (anything but `GET`,
+//! `HEAD` and `OPTIONS`) must also send the session's CSRF token in
+//! [`CSRF_HEADER`]. The login returns the token, and sets it in the
+//! script-readable [`CSRF_COOKIE`] so a page can copy it over (the double
+//! submit pattern); another site can do neither. Without it the request
+//! gets `403` with `csrf_failed`.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Redacted, errors::AppError, negotiation::AppJson, state::AppState};
+
+/// Cookie naming the caller's session.
+pub const SESSION_COOKIE: &str = "session";
+
+/// Cookie holding the session's CSRF token, readable by the page.
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+/// Header cookie-authenticated writes echo the CSRF token in.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// A logged-in browser.
+#[derive(Clone, Debug)]
+pub struct Session {
+    /// The API key the session was opened with.
+    pub key: Redacted<String>,
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    /// Whether `headers` echo this session's CSRF token.
+    pub fn csrf_matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|token| same(token, &self.csrf_token))
+    }
+}
+
+/// Open sessions by id, in process memory.
+#[derive(Debug, Default)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl SessionStore {
+    /// Opens a session for `key` until `expires_at` and returns its id.
+    /// Expired sessions are dropped along the way. Fails only if the
+    /// operating system can't supply random bytes.
+    pub fn open(
+        &self,
+        key: String,
+        now: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(String, Session), AppError> {
+        let id = token()?;
+        let session = Session {
+            key: Redacted::new(key),
+            csrf_token: token()?,
+            expires_at,
+        };
+        let mut sessions = self.sessions.lock().expect("session store lock poisoned");
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions.insert(id.clone(), session.clone());
+        Ok((id, session))
+    }
+
+    /// The unexpired session `id` names.
+    pub fn get(&self, id: &str, now: DateTime<Utc>) -> Option<Session> {
+        let sessions = self.sessions.lock().expect("session store lock poisoned");
+        sessions
+            .get(id)
+            .filter(|session| session.expires_at > now)
+            .cloned()
+    }
+
+    /// Ends session `id`; later requests carrying it are anonymous.
+    pub fn close(&self, id: &str) -> bool {
+        self.sessions
+            .lock()
+            .expect("session store lock poisoned")
+            .remove(id)
+            .is_some()
+    }
+
+    /// The session the request's [`SESSION_COOKIE`] names, if still open.
+    pub fn from_headers(&self, headers: &HeaderMap, now: DateTime<Utc>) -> Option<Session> {
+        self.get(&cookie(headers, SESSION_COOKIE)?, now)
+    }
+}
+
+/// 128 bits from the operating system's CSPRNG, as hex.
+fn token() -> Result<String, AppError> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(|err| {
+        tracing::error!(error = %err, "no random bytes for a session token");
+        AppError::Internal
+    })?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Whether `method` can't change anything, so needs no CSRF token.
+pub fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// The value of cookie `name` in the request's `Cookie` headers.
+pub fn cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Compares without stopping at the first differing byte.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Body of `POST /auth/session`.
+#[derive(Deserialize)]
+pub struct Login {
+    pub api_key: String,
+}
+
+/// Answer to a login.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoggedIn {
+    /// Send in [`CSRF_HEADER`] on writes.
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `POST /auth/session` - trades an API key for a session cookie; `401` if
+/// the key isn't one the server knows.
+pub async fn login(
+    State(app): State<AppState>,
+    AppJson(login): AppJson<Login>,
+) -> Result<impl IntoResponse, AppError> {
+    let config = app.config();
+    if !crate::auth::is_known_key(login.api_key.trim(), &config) {
+        return Err(AppError::Unauthorized);
+    }
+    let now = app.clock().now();
+    let ttl = config.session_ttl_secs;
+    // `Config` caps the TTL at a year.
+    let expires_at = now + Duration::seconds(ttl as i64);
+    let (id, session) = app
+        .sessions()
+        .open(login.api_key.trim().to_string(), now, expires_at)?;
+    tracing::info!("session opened");
+
+    let cookies = [
+        set_cookie(SESSION_COOKIE, &id, ttl, true),
+        set_cookie(CSRF_COOKIE, &session.csrf_token, ttl, false),
+    ];
+    let body = LoggedIn {
+        csrf_token: session.csrf_token,
+        expires_at,
+    };
+    Ok((StatusCode::CREATED, AppendHeaders(cookies), Json(body)))
+}
+
+/// `DELETE /auth/session` - ends the caller's session, if any, and clears
+/// its cookies.
+pub async fn logout(State(app): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(id) = cookie(&headers, SESSION_COOKIE) {
+        if app.sessions().close(&id) {
+            tracing::info!("session closed");
+        }
+    }
+    let cookies = [
+        set_cookie(SESSION_COOKIE, "", 0, true),
+        set_cookie(CSRF_COOKIE, "", 0, false),
+    ];
+    (StatusCode::NO_CONTENT, AppendHeaders(cookies))
+}
+
+fn set_cookie(
+    name: &str,
+    value: &str,
+    max_age: u64,
+    http_only: bool,
+) -> (header::HeaderName, HeaderValue) {
+    let http_only = if http_only { "; HttpOnly" } else { "" };
+    let cookie =
+        format!("{name}={value}; Path=/; Max-Age={max_age}; SameSite=Lax; Secure{http_only}");
+    (
+        header::SET_COOKIE,
+        HeaderValue::from_str(&cookie).expect("tokens are valid header values"),
+    )
+}
@@ -0,0 +1,221 @@
+//! Read-only links to single todos, for people without an API key.
+//!
+//! `POST /todos/:id/share` returns a link, `/shared/<token>`, that anyone can
+//! open to see the todo (as JSON, or the HTML view a browser asks for) until
+//! it expires `SHARE_TTL_SECS` later. Nothing is written when a link is made:
+//! the token carries the todo id and expiry, signed with HMAC-SHA256 under
+//! `SHARE_SECRET`, so the server only has to check the signature. A token
+//! whose signature doesn't match is a `401` with `invalid_share_link`; an
+//! expired one is a `410` with `share_link_expired`.
+//!
+//! `DELETE /todos/:id/share` revokes every link made for the todo so far
+//! (`410` with `share_link_revoked`); links made afterwards work again. The
+//! revocations are kept in [`ShareLinks`] on the [`AppState`], in process
+//! memory, so they are forgotten on restart. Changing `SHARE_SECRET` (a
+//! `SIGHUP` reload will do) revokes every link at once, for good.
+//!
+//! Without `SHARE_SECRET` sharing is off and all three routes answer `404`.
+//! Opening a link needs no key, so a guesser could try tokens as fast as the
+//! server answers; `SHARE_RATE_PER_MINUTE` caps how many each client (by IP)
+//! may try, on top of `RATE_LIMIT_PER_MINUTE`.
+
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    errors::AppError,
+    middleware::client_key,
+    negotiation::{Format, Negotiated},
+    routes::Id,
+    state::AppState,
+};
+
+/// What a share token says, once its signature checks out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Claims {
+    pub todo_id: u64,
+    /// The todo's [revocation generation](ShareLinks::generation) when the
+    /// link was made.
+    pub generation: u64,
+    /// Whole seconds; anything finer is dropped when signing.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Claims {
+    /// `<todo id>.<generation>.<expiry as Unix seconds>.<hex HMAC of the rest>`.
+    pub fn sign(&self, secret: &[u8]) -> String {
+        let payload = format!(
+            "{}.{}.{}",
+            self.todo_id,
+            self.generation,
+            self.expires_at.timestamp()
+        );
+        let signature: String = mac(secret, &payload)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        format!("{payload}.{signature}")
+    }
+
+    /// The claims of `token`, if it was signed with `secret`.
+    pub fn verify(token: &str, secret: &[u8]) -> Option<Self> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        mac(secret, payload).verify_slice(&unhex(signature)?).ok()?;
+        let mut parts = payload.split('.');
+        let claims = Self {
+            todo_id: parts.next()?.parse().ok()?,
+            generation: parts.next()?.parse().ok()?,
+            expires_at: DateTime::from_timestamp(parts.next()?.parse().ok()?, 0)?,
+        };
+        parts.next().is_none().then_some(claims)
+    }
+}
+
+fn mac(secret: &[u8], payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Which todos' links have been revoked.
+///
+/// Each todo has a generation, `0` until its links are first revoked.
+/// Links carry the generation they were made in, and revoking bumps it, so
+/// the links made before are refused while new ones are made in the next.
+#[derive(Debug, Default)]
+pub struct ShareLinks {
+    generations: Mutex<HashMap<u64, u64>>,
+}
+
+impl ShareLinks {
+    /// The generation new links to `todo_id` are made in.
+    pub fn generation(&self, todo_id: u64) -> u64 {
+        let generations = self.generations.lock().expect("share links lock poisoned");
+        generations.get(&todo_id).copied().unwrap_or_default()
+    }
+
+    /// Revokes every link to `todo_id` made so far.
+    pub fn revoke(&self, todo_id: u64) {
+        let mut generations = self.generations.lock().expect("share links lock poisoned");
+        *generations.entry(todo_id).or_default() += 1;
+    }
+
+    /// Whether `claims` were made before their todo's links were revoked.
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        claims.generation < self.generation(claims.todo_id)
+    }
+}
+
+/// Answer to `POST /todos/:id/share`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Shared {
+    /// Path of the link, relative to the server's address.
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `SHARE_SECRET`, or `404` while sharing is off.
+fn secret(app: &AppState) -> Result<Vec<u8>, AppError> {
+    let config = app.config();
+    let secret = config.share_secret.as_ref().ok_or(AppError::NotFound)?;
+    Ok(secret.expose().as_bytes().to_vec())
+}
+
+/// `POST /todos/:id/share` - makes a read-only link to the todo, valid for
+/// `SHARE_TTL_SECS`.
+pub async fn share(Id(id): Id, State(app): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let secret = secret(&app)?;
+    app.service().get(id).await?;
+    let ttl = app.config().share_ttl_secs;
+    // `Config` caps the TTL at a year.
+    let expires_at = app.clock().now() + Duration::seconds(ttl as i64);
+    let claims = Claims {
+        todo_id: id,
+        generation: app.share_links().generation(id),
+        expires_at,
+    };
+    let url = format!("/shared/{}", claims.sign(&secret));
+    tracing::info!(todo_id = id, "share link made");
+
+    let location = HeaderValue::from_str(&url).expect("tokens are valid header values");
+    let body = Shared {
+        url,
+        expires_at: claims.expires_at,
+    };
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, location)],
+        Json(body),
+    ))
+}
+
+/// `DELETE /todos/:id/share` - revokes every link to the todo made so far.
+pub async fn unshare(Id(id): Id, State(app): State<AppState>) -> Result<StatusCode, AppError> {
+    secret(&app)?;
+    app.service().get(id).await?;
+    app.share_links().revoke(id);
+    tracing::info!(todo_id = id, "share links revoked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /shared/:token` - the shared todo, read-only and without a key.
+pub async fn open(
+    Path(token): Path<String>,
+    State(app): State<AppState>,
+    format: Format,
+) -> Result<Response, AppError> {
+    let secret = secret(&app)?;
+    let claims = Claims::verify(&token, &secret).ok_or(AppError::InvalidShareLink)?;
+    if claims.expires_at <= app.clock().now() {
+        return Err(AppError::ShareLinkExpired);
+    }
+    if app.share_links().is_revoked(&claims) {
+        return Err(AppError::ShareLinkRevoked);
+    }
+    let todo = app.service().get(claims.todo_id).await?;
+    // The token is in the URL; don't hand it to whatever the page links to.
+    let referrer = [(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    )];
+    Ok((referrer, Negotiated::new(format, todo)).into_response())
+}
+
+/// Enforces `SHARE_RATE_PER_MINUTE` on `GET /shared/:token`.
+pub async fn open_budget(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let per_minute = state.config().share_rate_per_minute;
+    if per_minute > 0 {
+        state
+            .share_limiter()
+            .check(&client_key(&req), per_minute, Instant::now())
+            .map_err(|retry_after_secs| AppError::RateLimited { retry_after_secs })?;
+    }
+    Ok(next.run(req).await)
+}
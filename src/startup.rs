@@ -0,0 +1,96 @@
+//! Serving only once the repository is ready.
+//!
+//! A SQL backend may still be warming up or migrating when the process
+//! starts, and binding straight away would answer `500` until it is done. So
+//! [`run`] first pings the repository with [`wait_for_repo`], retrying with
+//! backoff for up to `STARTUP_WAIT_SECS`, and binds `HOST`:`PORT` only once
+//! it answers. Backends that migrate do so while they are constructed, before
+//! any of this. A repository that never answers makes [`run`] fail without
+//! having listened, so the process exits non-zero.
+//!
+//! The ping is [`preflight::repository`], which `GET /ready` reports too.
+//!
+//! Before binding, [`run`] also recounts the attachment storage ledger from
+//! `ATTACHMENT_DIR` ([`attachments::reconcile`]), so the quotas start from
+//! what is really on disk.
+
+use std::{future::Future, time::Duration};
+
+use anyhow::bail;
+use tokio::{net::TcpListener, time::Instant};
+
+use crate::{attachments, preflight, ready, state::TodoRepo, AppState};
+
+/// Wait after the first failed ping. It doubles after every further failure,
+/// up to [`MAX_RETRY_DELAY`].
+pub const FIRST_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Longest wait between two pings.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Pings `repo` until it answers or `budget` runs out, returning how many
+/// pings it took.
+pub async fn wait_for_repo(repo: &dyn TodoRepo, budget: Duration) -> anyhow::Result<u32> {
+    let deadline = Instant::now() + budget;
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let Err(err) = preflight::repository(repo).await else {
+            return Ok(attempts);
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            bail!(
+                "repository not ready after {attempts} attempts in {}s: {err}",
+                budget.as_secs()
+            );
+        }
+        let wait = delay.min(left);
+        tracing::warn!(
+            attempt = attempts,
+            error = %err,
+            retry_in_ms = wait.as_millis(),
+            "repository not ready"
+        );
+        tokio::time::sleep(wait).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Waits for the repository, then serves `state` on `HOST`:`PORT` until
+/// `shutdown` resolves.
+pub async fn run(
+    state: AppState,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let config = state.config();
+    let budget = Duration::from_secs(config.startup_wait_secs);
+    let attempts = wait_for_repo(state.service().repo().as_ref(), budget).await?;
+    tracing::info!(attempts, "repository ready");
+    let storage =
+        attachments::reconcile(&config.attachment_dir, state.storage_ledger().as_ref()).await?;
+    tracing::info!(bytes = storage.used_bytes, files = storage.files, "attachment storage counted");
+
+    // `TcpListener` + `serve` gives us finer control over graceful shutdown.
+    // Connect info exposes the client address to the rate limiter.
+    let listener = TcpListener::bind(config.server_addr).await?;
+    let addr = listener.local_addr()?;
+    tracing::info!(%addr, "starting server");
+    ready::announce(addr, &config);
+
+    // Parked long polls are let go as soon as shutdown begins, rather than
+    // holding the drain up until they time out.
+    // A failed background task can begin the shutdown too; see `supervisor`.
+    let stopping = state.clone();
+    let shutdown = async move {
+        tokio::select! {
+            _ = shutdown => stopping.begin_shutdown(),
+            _ = stopping.shutting_down().cancelled() => {}
+        }
+    };
+    axum::serve(listener, crate::app_with_connect_info(state))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
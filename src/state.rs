@@ -15,35 +15,571 @@
 //! Inside the `Arc`, we need interior mutability. We use `RwLock` (Read-Write Lock)
 //! instead of `Mutex` because it allows multiple concurrent readers (e.g., many
 //! users listing todos at once) while ensuring exclusive access for writers.
+//!
+//! # Hot-swappable configuration
+//!
+//! The [`Config`] lives behind an `ArcSwap`: readers get a cheap snapshot with
+//! no locking, and a SIGHUP reload (see `reload.rs`) atomically swaps in a new
+//! one. Middleware reads the snapshot per request, so tunable values such as
+//! the rate limit apply without rebuilding the router.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    ops::{
+        Bound::{Excluded, Unbounded},
+        Range,
+    },
+    sync::Arc,
+};
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
+    attachments::{Cleanup, InMemoryLedger, SharedLedger, StorageLedger},
+    budgets,
+    clock::{Clock, SystemClock},
+    config::Config,
+    deprecation, duplicates,
     errors::AppError,
-    models::{CreateTodo, Todo, UpdateTodo},
+    events::{
+        replay::{EventLog, Recording},
+        EventBus, LocalBus,
+    },
+    ids::{self, IdGenerator, Sequential},
+    metrics::{Metered, Metrics},
+    models::{
+        Attachment, Bucket, Changes, CreateTodo, ImportMode, ImportReport, NewAttachment,
+        RunMode, SearchHit, Snapshot, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+        SNAPSHOT_SCHEMA_VERSION,
+    },
+    outbox::{Outbox, Outboxing},
+    preferences::{InMemoryPreferences, PreferencesRepo},
+    rate_limit::{RateLimitStore, RateLimiter},
+    reporting::ErrorReporter,
+    search::Index,
+    service::TodoService,
+    sessions::SessionStore,
+    sharing::ShareLinks,
+    stats,
+    supervisor::TaskHealth,
+    templates::{InMemoryTemplates, TemplateRepo},
 };
 
 /// CRUD contract shared by handlers and tests.
 ///
+/// New implementations must pass the conformance suite in
+/// `tests/repo_conformance/`, which pins down the semantics callers rely on
+/// (validation, `NotFound`, field merging, id allocation).
+///
+/// Calls made while serving a request with a deadline can read it from
+/// [`crate::deadline::current`]; backends with their own timeouts should
+/// honor it.
+///
 /// `Send + Sync + 'static` ensures the trait object can be safely shared
 /// across threads in the async runtime.
 #[async_trait]
 pub trait TodoRepo: Send + Sync + 'static {
     async fn list(&self) -> Result<Vec<Todo>, AppError>;
+
+    /// Up to `limit` todos matching `filter` with ids greater than `after`,
+    /// in id order.
+    ///
+    /// This is keyset pagination: callers walk the whole store one bounded
+    /// chunk at a time without holding a lock between chunks. The default
+    /// implementations below go through `list`; real backends should override
+    /// them.
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        let mut todos = self.list().await?;
+        todos.retain(|todo| after.is_none_or(|after| todo.id > after) && filter.matches(todo));
+        todos.sort_by_key(|todo| todo.id);
+        todos.truncate(limit);
+        Ok(todos)
+    }
+
+    /// One page of todos matching `filter` in id order, skipping the first
+    /// `offset` matches.
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        let mut todos = self.list().await?;
+        todos.retain(|todo| filter.matches(todo));
+        todos.sort_by_key(|todo| todo.id);
+        Ok(todos.into_iter().skip(offset).take(limit).collect())
+    }
+
+    /// Number of todos matching `filter`.
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        let todos = self.list().await?;
+        Ok(todos.iter().filter(|todo| filter.matches(todo)).count())
+    }
+
     async fn create(&self, input: CreateTodo) -> Result<Todo, AppError>;
+
+    /// Creates every todo in `inputs`, in order, and returns them. Backends
+    /// that can should write all of them or none; the default creates them
+    /// one at a time, so a failure part way keeps the ones before it.
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        let mut created = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            created.push(self.create(input).await?);
+        }
+        Ok(created)
+    }
+
     async fn get(&self, id: u64) -> Result<Todo, AppError>;
+    /// Applies the fields set in `input`. Implementations run
+    /// [`UpdateTodo::validate`] first: no caller validates on their behalf.
     async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError>;
+
+    /// Flips `done`, as [`update`](Self::update) would set it. Backends
+    /// should read and write in one step, so two toggles racing flip twice.
+    /// The default reads then updates, so racing toggles may both read the
+    /// same state.
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        let todo = self.get(id).await?;
+        let update = UpdateTodo {
+            done: Some(!todo.done),
+            ..Default::default()
+        };
+        self.update(id, update).await
+    }
+
+    /// Applies each of `updates` like [`update`](Self::update), in order and
+    /// each on its own, but only while the collection is at `revision`;
+    /// otherwise [`AppError::PreconditionFailed`] with the revision it is at,
+    /// and nothing is applied. The check and the updates must be atomic, so
+    /// a write landing in between can't be overwritten unseen.
+    ///
+    /// The default keeps no revisions, so it never applies.
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        let _ = (revision, updates);
+        Err(AppError::PreconditionFailed { revision: None })
+    }
+
+    /// Updates the todo like [`update`](Self::update), or, when there is no
+    /// todo `id` and `input` has a title, creates one with that id. The flag
+    /// says whether it was created. Ids the server assigns later never collide
+    /// with ones created this way.
+    ///
+    /// Used only when clients may choose ids (`ALLOW_CLIENT_IDS`). The default
+    /// can't choose ids, so it only updates.
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        Ok((self.update(id, input).await?, false))
+    }
+
+    /// Removes the todo along with its attachment records.
     async fn delete(&self, id: u64) -> Result<(), AppError>;
+
+    /// The todo's version: the [revision](Self::revision) it was last written
+    /// at. `None` when the todo doesn't exist or the backend keeps none.
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        let _ = id;
+        Ok(None)
+    }
+
+    /// Like [`delete`](Self::delete), but only while the todo is at
+    /// `version`; otherwise [`AppError::VersionMismatch`] with the version it
+    /// is at. The check and the removal must be atomic, so an update landing
+    /// in between can't be deleted unseen.
+    ///
+    /// The default keeps no versions, so it never deletes.
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        let _ = version;
+        self.get(id).await?;
+        Err(AppError::VersionMismatch { version: None })
+    }
+
+    /// Records an upload against a todo, or `NotFound` if the todo is gone.
+    ///
+    /// The attachment methods default to "no attachments" so backends that
+    /// don't store files keep compiling; uploads to them fail.
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        let _ = (todo_id, input);
+        Err(AppError::Internal)
+    }
+
+    /// Attachments of one todo in upload order.
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.get(todo_id).await?;
+        Ok(Vec::new())
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        let _ = id;
+        Err(AppError::NotFound)
+    }
+
+    /// Removes one attachment record and returns it. Its file is the
+    /// caller's to delete, once no other record of the todo shares it.
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        let _ = id;
+        Err(AppError::NotFound)
+    }
+
+    /// Up to `limit` todos matching `query`, most relevant first. The default
+    /// indexes every todo on each call; real backends should override it.
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        let todos: BTreeMap<u64, Todo> =
+            self.list().await?.into_iter().map(|todo| (todo.id, todo)).collect();
+        let mut index = Index::default();
+        for todo in todos.values() {
+            index.insert(todo);
+        }
+        Ok(index.search(query, limit, |id| todos.get(&id)))
+    }
+
+    /// Ids of open todos whose title is at least `threshold` similar to
+    /// `title` (see [`duplicates::similarity`]), most similar first.
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        Ok(similar_open(&self.list().await?, title, threshold))
+    }
+
+    /// Open todos due after `from` and no later than `to`, in id order,
+    /// whether or not they have been reminded about.
+    async fn due_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Todo>, AppError> {
+        let mut todos = self.list().await?;
+        todos.retain(|todo| !todo.done && todo.due.is_some_and(|due| from < due && due <= to));
+        Ok(todos)
+    }
+
+    /// Sets `reminded_at` unless it is already set. Returns the todo when this
+    /// call set it, `None` when an earlier one did, so each reminder fires
+    /// once even if two schedulers race. A reminder isn't an edit, so
+    /// `updated_at` stays as it was.
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        let _ = (id, at);
+        Err(AppError::Internal)
+    }
+
+    /// Todos created and completed per `bucket` within `range`, zero-filled
+    /// (see [`stats::timeseries`]).
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        Ok(stats::timeseries(&self.list().await?, bucket, range))
+    }
+
+    /// Todos written and ids deleted after revision `since`, for delta sync.
+    /// `ResyncRequired` when the deletions since then are no longer all known,
+    /// or `since` is ahead of the store (e.g. from before a restart).
+    ///
+    /// The default keeps no history, so clients of such backends always
+    /// refetch everything.
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        let _ = since;
+        Err(AppError::ResyncRequired)
+    }
+
+    /// The collection's current revision, bumped by every write; the same
+    /// counter [`changes_since`](Self::changes_since) reports. `None` when the
+    /// backend keeps none, which makes every `If-Match` fail.
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        Ok(None)
+    }
+
+    /// Every todo as stored, for [`import_all`](Self::import_all) into
+    /// another backend. The default reads them through `list` and knows no
+    /// `next_id`.
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        Ok(Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            next_id: None,
+            todos: self.list().await?,
+        })
+    }
+
+    /// Loads an [`export_all`](Self::export_all) snapshot, keeping its ids and
+    /// timestamps; see [`ImportMode`] for what each mode writes.
+    /// Implementations run [`Snapshot::validate`] first and write all of it
+    /// or nothing. With [`RunMode::Preview`] they write nothing and return
+    /// the report the import would have.
+    ///
+    /// The default can't choose ids, so it refuses.
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        let _ = (snapshot, mode, run);
+        Err(AppError::Validation("this repository can't import snapshots".into()))
+    }
+
+    /// Checks that the backend is reachable, for the startup self-check. The
+    /// default has nothing to reach and always succeeds; backends with a
+    /// connection should round-trip a trivial query.
+    async fn ping(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    /// Short name of the store behind this repository, such as `memory`,
+    /// for labelling metrics. Decorators report their inner repository's.
+    fn backend(&self) -> &'static str {
+        "custom"
+    }
 }
 
-/// Minimal in-memory store guarded by a RwLock.
+fn similar_open<'a>(
+    todos: impl IntoIterator<Item = &'a Todo>,
+    title: &str,
+    threshold: f64,
+) -> Vec<u64> {
+    let wanted = duplicates::normalize(title);
+    let mut matches: Vec<(u64, f64)> = todos
+        .into_iter()
+        .filter(|todo| !todo.done)
+        .map(|todo| {
+            let existing = duplicates::normalize(&todo.title);
+            (todo.id, duplicates::similarity(&wanted, &existing))
+        })
+        .filter(|(_, similarity)| *similarity >= threshold)
+        .collect();
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    matches.into_iter().map(|(id, _)| id).collect()
+}
+
+/// A fresh in-memory repository without the publishing and cleanup
+/// decorators `AppState` adds.
+pub fn in_memory_repo() -> Arc<dyn TodoRepo> {
+    Arc::new(RwLock::new(InMemory::default()))
+}
+
+/// Like [`in_memory_repo`], numbering new todos with `ids` instead of
+/// sequentially.
+pub fn in_memory_repo_with_ids(ids: Arc<dyn IdGenerator>) -> Arc<dyn TodoRepo> {
+    Arc::new(RwLock::new(InMemory {
+        ids: Ids(ids),
+        ..InMemory::default()
+    }))
+}
+
+/// Like [`in_memory_repo`], stamping todos with the time from `clock`.
+pub fn in_memory_repo_with_clock(clock: Arc<dyn Clock>) -> Arc<dyn TodoRepo> {
+    Arc::new(RwLock::new(InMemory {
+        clock: Time(clock),
+        ..InMemory::default()
+    }))
+}
+
+/// The in-memory repo's [`IdGenerator`], sequential by default.
+struct Ids(Arc<dyn IdGenerator>);
+
+impl Default for Ids {
+    fn default() -> Self {
+        Self(Arc::new(Sequential::default()))
+    }
+}
+
+/// The in-memory repo's [`Clock`], the system's by default.
+struct Time(Arc<dyn Clock>);
+
+impl Default for Time {
+    fn default() -> Self {
+        Self(Arc::new(SystemClock))
+    }
+}
+
+/// Deleted ids the in-memory repo remembers for delta sync. Clients that
+/// fall further behind than this must refetch everything.
+pub const TOMBSTONES: usize = 1000;
+
+/// Minimal in-memory store guarded by a RwLock. A `BTreeMap` keeps items in
+/// id order, which makes listing deterministic and chunked iteration cheap.
 #[derive(Default)]
 struct InMemory {
-    next_id: u64,
-    items: HashMap<u64, Todo>,
+    ids: Ids,
+    clock: Time,
+    items: BTreeMap<u64, Todo>,
+    next_attachment_id: u64,
+    attachments: BTreeMap<u64, Attachment>,
+    /// Kept in step with `items` by every write.
+    index: Index,
+    /// Collection revision, bumped by every write to a todo.
+    revision: u64,
+    /// Revision each todo in `items` was last written at.
+    revisions: BTreeMap<u64, u64>,
+    /// `(revision, id)` of recent deletes, oldest first, up to [`TOMBSTONES`].
+    tombstones: VecDeque<(u64, u64)>,
+    /// Revision of the newest tombstone dropped. Syncs from before it may
+    /// have missed a delete.
+    horizon: u64,
+}
+
+impl InMemory {
+    /// Stamps a created or updated todo with the next revision.
+    fn touch(&mut self, id: u64) {
+        self.revision += 1;
+        self.revisions.insert(id, self.revision);
+    }
+
+    /// Records the delete of `id` at the next revision.
+    fn bury(&mut self, id: u64) {
+        self.revision += 1;
+        self.revisions.remove(&id);
+        self.tombstones.push_back((self.revision, id));
+        if self.tombstones.len() > TOMBSTONES {
+            let (dropped, _) = self.tombstones.pop_front().expect("just pushed");
+            self.horizon = dropped;
+        }
+    }
+
+    /// Removes `id` and its attachment records.
+    fn remove(&mut self, id: u64) -> Result<(), AppError> {
+        let todo = self.items.remove(&id).ok_or(AppError::NotFound)?;
+        self.index.remove(&todo);
+        self.attachments.retain(|_, attachment| attachment.todo_id != id);
+        self.bury(id);
+        Ok(())
+    }
+
+    /// An id no todo has, from the generator, that `pending` (todos about to
+    /// be inserted) doesn't use either.
+    fn fresh_id(&self, pending: &[Todo]) -> Result<u64, AppError> {
+        for _ in 0..ids::ATTEMPTS {
+            let id = self.ids.0.next_id()?;
+            if !self.items.contains_key(&id) && pending.iter().all(|todo| todo.id != id) {
+                return Ok(id);
+            }
+        }
+        tracing::error!(attempts = ids::ATTEMPTS, "every generated id was taken");
+        Err(AppError::Internal)
+    }
+
+    /// A todo made from `input`, not yet stored, with an id that neither the
+    /// store nor `pending` uses.
+    fn new_todo(&self, input: CreateTodo, pending: &[Todo]) -> Result<Todo, AppError> {
+        let id = self.fresh_id(pending)?;
+        let now = self.clock.0.now();
+        Ok(Todo {
+            id,
+            title: input.title,
+            description: input.description,
+            done: false,
+            done_at: None,
+            due: input.due,
+            assignee: input.assignee.map(|assignee| assignee.trim().to_string()),
+            color: input.color,
+            reminded_at: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Stores a new todo under its id.
+    fn insert(&mut self, todo: Todo) {
+        self.index.insert(&todo);
+        self.touch(todo.id);
+        self.items.insert(todo.id, todo);
+    }
+
+    /// The one critical section for edits to an existing todo. Runs `edit`
+    /// on a copy of todo `id` and, only if it succeeds, stores the copy
+    /// stamped with `updated_at` and the next revision. Callers hold the
+    /// write lock throughout, so no other writer can slip in between the
+    /// checks `edit` makes and the write; and a failed edit leaves nothing
+    /// half-applied.
+    fn modify(
+        &mut self,
+        id: u64,
+        edit: impl FnOnce(&mut Todo, DateTime<Utc>) -> Result<(), AppError>,
+    ) -> Result<Todo, AppError> {
+        let now = self.clock.0.now();
+        let mut todo = self.items.get(&id).ok_or(AppError::NotFound)?.clone();
+        edit(&mut todo, now)?;
+        todo.updated_at = now;
+        Ok(self.replace(todo))
+    }
+
+    /// Like [`modify`](Self::modify), for marks the server makes rather than
+    /// edits a user makes: bumps the revision but leaves `updated_at` alone,
+    /// so the todo doesn't look edited in feeds, `updated_since` filters or
+    /// exports.
+    fn mark(&mut self, id: u64, mark: impl FnOnce(&mut Todo)) -> Result<Todo, AppError> {
+        let mut todo = self.items.get(&id).ok_or(AppError::NotFound)?.clone();
+        mark(&mut todo);
+        Ok(self.replace(todo))
+    }
+
+    /// Stores `todo` over the one with its id and re-indexes it.
+    fn replace(&mut self, todo: Todo) -> Todo {
+        let old = self.items.insert(todo.id, todo.clone()).expect("looked up by the caller");
+        self.index.remove(&old);
+        self.index.insert(&todo);
+        self.touch(todo.id);
+        todo
+    }
+}
+
+/// Validates `input` and applies it to `todo`, as of `now`; an edit for
+/// [`InMemory::modify`].
+fn apply(todo: &mut Todo, input: UpdateTodo, now: DateTime<Utc>) -> Result<(), AppError> {
+    input.validate()?;
+    let UpdateTodo {
+        title,
+        description,
+        done,
+        due,
+        assignee,
+        color,
+    } = input;
+
+    if let Some(title) = title {
+        todo.title = title;
+    }
+
+    if description.is_some() {
+        todo.description = description;
+    }
+
+    if let Some(done) = done {
+        if !done {
+            todo.done_at = None;
+        } else if !todo.done {
+            todo.done_at = Some(now);
+        }
+        todo.done = done;
+    }
+
+    if due.is_some() {
+        todo.due = due;
+        // A new due date deserves its own reminder.
+        todo.reminded_at = None;
+    }
+
+    if let Some(assignee) = assignee {
+        todo.assignee = assignee.map(|assignee| assignee.trim().to_string());
+    }
+
+    if let Some(color) = color {
+        todo.color = color;
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -54,95 +590,571 @@ impl TodoRepo for RwLock<InMemory> {
         Ok(guard.items.values().cloned().collect())
     }
 
-    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
-        // Trimming avoids storing strings that only differ by leading/trailing
-        // whitespace.
-        if input.title.trim().is_empty() {
-            return Err(AppError::Validation(
-                "title cannot be empty".to_string(),
-            ));
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        let guard = self.read().await;
+        let start = after.map_or(Unbounded, Excluded);
+        Ok(guard
+            .items
+            .range((start, Unbounded))
+            .map(|(_, todo)| todo)
+            .filter(|todo| filter.matches(todo))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        let guard = self.read().await;
+        Ok(guard
+            .items
+            .values()
+            .filter(|todo| filter.matches(todo))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        let guard = self.read().await;
+        if *filter == TodoFilter::default() {
+            return Ok(guard.items.len());
         }
+        Ok(guard.items.values().filter(|todo| filter.matches(todo)).count())
+    }
+
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        let guard = self.read().await;
+        Ok(stats::timeseries(guard.items.values(), bucket, range))
+    }
 
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
         // Acquire a write lock. This blocks until all readers/writers are done.
         let mut guard = self.write().await;
-        guard.next_id += 1;
-
-        let todo = Todo {
-            id: guard.next_id,
-            title: input.title,
-            done: false,
-        };
-        guard.items.insert(todo.id, todo.clone());
+        let todo = guard.new_todo(input, &[])?;
+        guard.insert(todo.clone());
         Ok(todo)
     }
 
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        for input in &inputs {
+            input.validate()?;
+        }
+        // One lock for the whole batch: other requests see all of it or none.
+        let mut guard = self.write().await;
+        let mut todos = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let todo = guard.new_todo(input, &todos)?;
+            todos.push(todo);
+        }
+        for todo in &todos {
+            guard.insert(todo.clone());
+        }
+        Ok(todos)
+    }
+
     async fn get(&self, id: u64) -> Result<Todo, AppError> {
         let guard = self.read().await;
         guard.items.get(&id).cloned().ok_or(AppError::NotFound)
     }
 
     async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
-        let mut title = input.title;
-        let done = input.done;
-
-        // Peek at the title before we move it.
-        if let Some(title) = title.as_ref() {
-            if title.trim().is_empty() {
-                return Err(AppError::Validation(
-                    "title cannot be empty".to_string(),
-                ));
-            }
+        self.write().await.modify(id, |todo, now| apply(todo, input, now))
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        self.write().await.modify(id, |todo, now| {
+            let update = UpdateTodo {
+                done: Some(!todo.done),
+                ..Default::default()
+            };
+            apply(todo, update, now)
+        })
+    }
+
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        let mut guard = self.write().await;
+        if guard.revision != revision {
+            return Err(AppError::PreconditionFailed {
+                revision: Some(guard.revision),
+            });
         }
+        Ok(updates
+            .into_iter()
+            .map(|(id, input)| guard.modify(id, |todo, now| apply(todo, input, now)))
+            .collect())
+    }
 
-        // PUT/patching nothing is usually a client mistake.
-        if title.is_none() && done.is_none() {
-            return Err(AppError::Validation(
-                "provide at least one field to update".to_string(),
-            ));
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        if id == 0 {
+            return Err(AppError::Validation("id must be at least 1".into()));
         }
 
         let mut guard = self.write().await;
-        let todo = guard
-            .items
-            .get_mut(&id)
-            .ok_or(AppError::NotFound)?;
+        if guard.items.contains_key(&id) {
+            let todo = guard.modify(id, |todo, now| apply(todo, input, now))?;
+            return Ok((todo, false));
+        }
+        input.validate()?;
+        let Some(title) = input.title else {
+            return Err(AppError::NotFound);
+        };
+        // Sequential ids continue above the highest one a client chose.
+        guard.ids.0.observe(id);
+
+        let now = guard.clock.0.now();
+        let todo = Todo {
+            id,
+            title,
+            description: input.description,
+            done: input.done.unwrap_or(false),
+            done_at: input.done.filter(|done| *done).map(|_| now),
+            due: input.due,
+            assignee: input.assignee.flatten().map(|assignee| assignee.trim().to_string()),
+            color: input.color.flatten(),
+            reminded_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        guard.insert(todo.clone());
+        Ok((todo, true))
+    }
 
-        if let Some(title) = title.take() {
-            todo.title = title;
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        self.write().await.remove(id)
+    }
+
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        Ok(self.read().await.revisions.get(&id).copied())
+    }
+
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        let mut guard = self.write().await;
+        let current = *guard.revisions.get(&id).ok_or(AppError::NotFound)?;
+        if current != version {
+            return Err(AppError::VersionMismatch {
+                version: Some(current),
+            });
         }
+        guard.remove(id)
+    }
+
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        let guard = self.read().await;
+        Ok(Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            next_id: guard.ids.0.peek(),
+            todos: guard.items.values().cloned().collect(),
+        })
+    }
 
-        if let Some(done) = done {
-            todo.done = done;
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        snapshot.validate()?;
+        let mut guard = self.write().await;
+        let mut report = ImportReport {
+            mode,
+            imported: 0,
+            removed: Vec::new(),
+            conflicts: Vec::new(),
+        };
+
+        match mode {
+            ImportMode::Merge => {
+                report.conflicts = snapshot
+                    .todos
+                    .iter()
+                    .map(|todo| todo.id)
+                    .filter(|id| guard.items.contains_key(id))
+                    .collect();
+                if !report.conflicts.is_empty() {
+                    return Ok(report);
+                }
+            }
+            ImportMode::Replace => {
+                let kept: HashSet<u64> = snapshot.todos.iter().map(|todo| todo.id).collect();
+                report.removed =
+                    guard.items.keys().copied().filter(|id| !kept.contains(id)).collect();
+            }
         }
 
-        Ok(todo.clone())
+        report.imported = snapshot.todos.len();
+        if run == RunMode::Preview {
+            return Ok(report);
+        }
+        for id in &report.removed {
+            let todo = guard.items.remove(id).expect("listed above");
+            guard.index.remove(&todo);
+            guard.attachments.retain(|_, attachment| attachment.todo_id != *id);
+            guard.bury(*id);
+        }
+        for todo in snapshot.todos {
+            if let Some(old) = guard.items.remove(&todo.id) {
+                guard.index.remove(&old);
+            }
+            guard.ids.0.observe(todo.id);
+            guard.insert(todo);
+        }
+        if let Some(next_id) = snapshot.next_id {
+            guard.ids.0.observe(next_id.saturating_sub(1));
+        }
+        Ok(report)
     }
 
-    async fn delete(&self, id: u64) -> Result<(), AppError> {
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
         let mut guard = self.write().await;
-        guard
-            .items
-            .remove(&id)
-            .map(|_| ())
-            .ok_or(AppError::NotFound)
+        if !guard.items.contains_key(&todo_id) {
+            return Err(AppError::NotFound);
+        }
+        guard.next_attachment_id += 1;
+        let attachment = Attachment {
+            id: guard.next_attachment_id,
+            todo_id,
+            filename: input.filename,
+            content_type: input.content_type,
+            size: input.size,
+            sha256: input.sha256,
+        };
+        guard.attachments.insert(attachment.id, attachment.clone());
+        Ok(attachment)
+    }
+
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        let guard = self.read().await;
+        if !guard.items.contains_key(&todo_id) {
+            return Err(AppError::NotFound);
+        }
+        Ok(guard
+            .attachments
+            .values()
+            .filter(|attachment| attachment.todo_id == todo_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        let guard = self.read().await;
+        guard.attachments.get(&id).cloned().ok_or(AppError::NotFound)
+    }
+
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        let mut guard = self.write().await;
+        guard.attachments.remove(&id).ok_or(AppError::NotFound)
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        let guard = self.read().await;
+        Ok(guard.index.search(query, limit, |id| guard.items.get(&id)))
+    }
+
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        let guard = self.read().await;
+        Ok(similar_open(guard.items.values(), title, threshold))
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        let mut guard = self.write().await;
+        if guard.items.get(&id).ok_or(AppError::NotFound)?.reminded_at.is_some() {
+            return Ok(None);
+        }
+        let todo = guard.mark(id, |todo| todo.reminded_at = Some(at))?;
+        Ok(Some(todo))
+    }
+
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        Ok(Some(self.read().await.revision))
+    }
+
+    fn backend(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        let guard = self.read().await;
+        // Revision 0 is the empty store: a client there has nothing that could
+        // have been deleted.
+        if since > guard.revision || (since != 0 && since < guard.horizon) {
+            return Err(AppError::ResyncRequired);
+        }
+        Ok(Changes {
+            revision: guard.revision,
+            changed: guard
+                .items
+                .values()
+                .filter(|todo| guard.revisions[&todo.id] > since)
+                .cloned()
+                .collect(),
+            deleted: guard
+                .tombstones
+                .iter()
+                .filter(|(revision, _)| *revision > since)
+                .map(|(_, id)| *id)
+                .collect(),
+        })
     }
 }
 
 #[derive(Clone)]
 pub struct AppState {
-    repo: Arc<dyn TodoRepo>,
+    service: Arc<TodoService>,
+    config: Arc<ArcSwap<Config>>,
+    rate_limiter: Arc<dyn RateLimitStore>,
+    metrics: Arc<Metrics>,
+    error_reporter: Option<Arc<dyn ErrorReporter>>,
+    clock: Arc<dyn Clock>,
+    event_log: Arc<EventLog>,
+    outbox: Arc<Outbox>,
+    preferences: Arc<dyn PreferencesRepo>,
+    templates: Arc<dyn TemplateRepo>,
+    deprecations: Arc<deprecation::Tracker>,
+    import_limiter: Arc<RateLimiter>,
+    shutdown: CancellationToken,
+    task_health: Arc<TaskHealth>,
+    sessions: Arc<SessionStore>,
+    share_links: Arc<ShareLinks>,
+    share_limiter: Arc<RateLimiter>,
+    storage: SharedLedger,
 }
 
 impl AppState {
-    /// Provide a ready-to-go state object backed by the in-memory repo.
+    /// Provide a ready-to-go state object backed by the in-memory repo and the
+    /// default configuration.
     pub fn new_in_memory() -> Self {
+        Self::with_repo(in_memory_repo())
+    }
+
+    /// Like [`new_in_memory`](Self::new_in_memory), reading the time from
+    /// `clock` everywhere: todo timestamps, reminders, the time series
+    /// window, and the calendar. One clock serves both the repo and the
+    /// state, so they can't disagree.
+    pub fn in_memory_with_clock(clock: Arc<dyn Clock>) -> Self {
+        let mut state = Self::with_repo(in_memory_repo_with_clock(Arc::clone(&clock)));
+        state.clock = clock;
+        state
+    }
+
+    /// Like [`new_in_memory`](Self::new_in_memory), publishing to `events`.
+    pub fn in_memory_with_events(events: Arc<dyn EventBus>) -> Self {
+        Self::with_repo_and_events(in_memory_repo(), events)
+    }
+
+    /// Builds state around any repository, e.g. a database or a test double.
+    /// Writes through the [`service`](Self::service) (or the state's repo
+    /// handle) publish [`events`](Self::events), deleting a todo also
+    /// deletes its attachment files, and every call is timed in the
+    /// [`metrics`](Self::metrics). Published events are also kept in the
+    /// [`event_log`](Self::event_log).
+    pub fn with_repo(repo: Arc<dyn TodoRepo>) -> Self {
+        Self::with_repo_and_events(repo, Arc::new(LocalBus::default()))
+    }
+
+    /// Like [`with_repo`](Self::with_repo), publishing to `events` instead of
+    /// an in-process bus.
+    pub fn with_repo_and_events(repo: Arc<dyn TodoRepo>, events: Arc<dyn EventBus>) -> Self {
+        let config = Arc::new(ArcSwap::from_pointee(Config::default()));
+        let metrics = Arc::new(Metrics::new());
+        let repo = Arc::new(Metered::new(repo, Arc::clone(&metrics), Arc::clone(&config)));
+        let ledger: Arc<dyn StorageLedger> = Arc::new(InMemoryLedger::default());
+        let storage = Arc::new(ArcSwap::from_pointee(ledger));
+        let repo = Arc::new(Cleanup::new(repo, Arc::clone(&config), Arc::clone(&storage)));
+        let event_log = Arc::new(EventLog::new(Arc::clone(&config)));
+        let outbox = Arc::new(Outbox::new(Arc::clone(&config)));
+        let events = Arc::new(Outboxing::new(events, Arc::clone(&outbox)));
+        let events = Arc::new(Recording::new(events, Arc::clone(&event_log)));
+        let service = TodoService::new(repo, events, Arc::clone(&config), Arc::clone(&metrics));
         Self {
-            repo: Arc::new(RwLock::new(InMemory::default())),
+            service: Arc::new(service),
+            config,
+            rate_limiter: Arc::new(RateLimiter::default()),
+            metrics,
+            error_reporter: None,
+            clock: Arc::new(SystemClock),
+            event_log,
+            outbox,
+            preferences: Arc::new(InMemoryPreferences::default()),
+            templates: Arc::new(InMemoryTemplates::default()),
+            deprecations: Arc::default(),
+            import_limiter: Arc::new(RateLimiter::with_window(budgets::IMPORT_WINDOW)),
+            shutdown: CancellationToken::new(),
+            task_health: Arc::default(),
+            sessions: Arc::default(),
+            share_links: Arc::default(),
+            share_limiter: Arc::new(RateLimiter::default()),
+            storage,
         }
     }
 
-    /// Returns a clone of the repository handle. Cheap thanks to `Arc`.
+    /// Counts requests in `store` instead of in process memory.
+    pub fn with_rate_limit_store(mut self, store: Arc<dyn RateLimitStore>) -> Self {
+        self.rate_limiter = store;
+        self
+    }
+
+    /// Keeps list preferences in `repo` instead of in process memory.
+    pub fn with_preferences_repo(mut self, repo: Arc<dyn PreferencesRepo>) -> Self {
+        self.preferences = repo;
+        self
+    }
+
+    /// Keeps [templates](crate::templates) in `repo` instead of in process
+    /// memory.
+    pub fn with_templates_repo(mut self, repo: Arc<dyn TemplateRepo>) -> Self {
+        self.templates = repo;
+        self
+    }
+
+    /// Counts attachment storage for the [quotas](crate::attachments#quotas)
+    /// in `ledger` instead of in process memory.
+    pub fn with_storage_ledger(self, ledger: Arc<dyn StorageLedger>) -> Self {
+        self.storage.store(Arc::new(ledger));
+        self
+    }
+
+    /// Reports server errors to `reporter`.
+    pub fn with_error_reporter(mut self, reporter: Arc<dyn ErrorReporter>) -> Self {
+        self.error_reporter = Some(reporter);
+        self
+    }
+
+    /// Replaces the configuration this state starts with.
+    pub fn with_config(self, config: Config) -> Self {
+        self.config.store(Arc::new(config));
+        self
+    }
+
+    /// The business rules every API goes through for writes.
+    pub fn service(&self) -> &TodoService {
+        &self.service
+    }
+
+    /// Returns a clone of the repository handle.
+    ///
+    /// Deprecated because it hands every caller the raw repository, where
+    /// writes skip the audit log. Migrate calls like `state.repo().get(id)`
+    /// to the matching [`TodoService`] method (`state.service().get(id)`).
+    /// Code that really needs the repository, such as a conformance test,
+    /// can borrow it with `state.service().repo()` and clone only if it must
+    /// own it.
+    #[deprecated(since = "0.1.0", note = "use the `service()` methods, or `service().repo()`")]
     pub fn repo(&self) -> Arc<dyn TodoRepo> {
-        Arc::clone(&self.repo)
+        Arc::clone(self.service.repo())
+    }
+
+    /// Snapshot of the current configuration. Hold it for the duration of a
+    /// request rather than calling this repeatedly.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Atomically publishes a new configuration to every reader.
+    pub fn replace_config(&self, config: Config) {
+        self.config.store(Arc::new(config));
+    }
+
+    pub fn rate_limiter(&self) -> &dyn RateLimitStore {
+        self.rate_limiter.as_ref()
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn error_reporter(&self) -> Option<&dyn ErrorReporter> {
+        self.error_reporter.as_deref()
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn events(&self) -> &dyn EventBus {
+        self.service.events()
+    }
+
+    pub fn event_log(&self) -> &EventLog {
+        &self.event_log
+    }
+
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+
+    pub fn preferences(&self) -> &dyn PreferencesRepo {
+        self.preferences.as_ref()
+    }
+
+    pub fn templates(&self) -> &dyn TemplateRepo {
+        self.templates.as_ref()
+    }
+
+    pub fn deprecations(&self) -> &deprecation::Tracker {
+        &self.deprecations
+    }
+
+    /// Imports started per client, in [hour-long windows](budgets::IMPORT_WINDOW).
+    pub fn import_limiter(&self) -> &RateLimiter {
+        &self.import_limiter
+    }
+
+    /// Share links opened per client, in minute-long windows.
+    pub fn share_limiter(&self) -> &RateLimiter {
+        &self.share_limiter
+    }
+
+    /// Revocations of [share links](crate::sharing).
+    pub fn share_links(&self) -> &ShareLinks {
+        &self.share_links
+    }
+
+    /// Releases requests parked on the state, such as [long polls](crate::poll),
+    /// so a graceful shutdown doesn't wait for them to time out.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Cancelled once [`begin_shutdown`](Self::begin_shutdown) is called.
+    pub fn shutting_down(&self) -> &CancellationToken {
+        &self.shutdown
+    }
+
+    /// How the [supervised](crate::supervisor) background tasks are doing.
+    pub fn task_health(&self) -> &TaskHealth {
+        &self.task_health
+    }
+
+    /// Where attachment storage is counted.
+    pub fn storage_ledger(&self) -> Arc<dyn StorageLedger> {
+        Arc::clone(&self.storage.load())
+    }
+
+    /// Open browser [sessions](crate::sessions).
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
     }
 }
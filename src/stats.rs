@@ -0,0 +1,53 @@
+//! Completion statistics for burndown-style charts.
+//!
+//! `GET /todos/stats/timeseries` counts the todos created (`created_at`) and
+//! completed (`done_at`) in each day or week of a range. Every bucket that
+//! overlaps the range is listed, empty ones with zeros, so charting libraries
+//! can plot the series without filling gaps themselves. Only events inside
+//! the range are counted, so the first and last buckets may be partial.
+//!
+//! A todo that was completed, reopened, and completed again counts once, at
+//! its latest completion: the repository keeps no history beyond `done_at`.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{Bucket, TimeseriesPoint, Todo};
+
+/// Buckets `todos` in a single pass. Repositories call this from
+/// [`TodoRepo::timeseries`](crate::state::TodoRepo::timeseries).
+pub fn timeseries<'a>(
+    todos: impl IntoIterator<Item = &'a Todo>,
+    bucket: Bucket,
+    range: Range<DateTime<Utc>>,
+) -> Vec<TimeseriesPoint> {
+    let mut points = BTreeMap::new();
+    let mut start = bucket.start(range.start);
+    while start < range.end {
+        points.insert(start, (0, 0));
+        start += bucket.width();
+    }
+
+    for todo in todos {
+        if range.contains(&todo.created_at) {
+            if let Some((created, _)) = points.get_mut(&bucket.start(todo.created_at)) {
+                *created += 1;
+            }
+        }
+        if let Some(done_at) = todo.done_at.filter(|at| range.contains(at)) {
+            if let Some((_, completed)) = points.get_mut(&bucket.start(done_at)) {
+                *completed += 1;
+            }
+        }
+    }
+
+    points
+        .into_iter()
+        .map(|(bucket_start, (created, completed))| TimeseriesPoint {
+            bucket_start,
+            created,
+            completed,
+        })
+        .collect()
+}
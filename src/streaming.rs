@@ -0,0 +1,166 @@
+//! Incremental serialization of large collections.
+//!
+//! # Why stream?
+//!
+//! Serializing a `Vec<Todo>` with `Json` means cloning every todo out of the
+//! store and then building the entire JSON document in memory before the first
+//! byte goes out. With 100k todos that is two full copies and a long wait.
+//!
+//! [`json_array`] instead pulls one bounded chunk at a time through
+//! [`TodoRepo::list_after`], serializes just that chunk, and hands it to the
+//! response body. Memory stays proportional to the chunk size no matter how
+//! big the store is, and the first bytes leave as soon as the first chunk is
+//! ready.
+//!
+//! The output is byte-for-byte what `serde_json::to_vec(&todos)` would produce.
+//! Chunks are read independently, so a concurrent mutation may or may not be
+//! reflected — the same guarantee a paginated client gets.
+//!
+//! # Disconnects
+//!
+//! When a client goes away mid-response, hyper drops the body, and with it
+//! the stream feeding it: no further chunks are read, and an event
+//! subscription behind the stream is released. That is routine, not an
+//! error, so [`track`] logs it at debug with how much had been sent. Every
+//! streamed response (lists, attachment downloads, gRPC `Watch`) goes through
+//! it.
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::body::{Body, Bytes};
+use futures::{stream, Stream, TryStream};
+
+use crate::{
+    errors::AppError,
+    models::{Todo, TodoFilter},
+    projection::{Projected, Projection},
+    state::TodoRepo,
+};
+
+/// Number of todos read and serialized per chunk.
+pub const CHUNK_SIZE: usize = 256;
+
+enum Cursor {
+    /// The first chunk, already fetched by the caller.
+    First(Vec<Todo>),
+    /// Resume after this id.
+    After(u64),
+    Done,
+}
+
+/// Streams every todo matching `filter` as a JSON array, starting with
+/// `first` (the result of `list_after(&filter, None, CHUNK_SIZE)`), with only
+/// the `fields` asked for.
+pub fn json_array(
+    repo: Arc<dyn TodoRepo>,
+    filter: TodoFilter,
+    fields: Option<Projection>,
+    first: Vec<Todo>,
+) -> Body {
+    let filter = Arc::new(filter);
+    let chunks = stream::unfold(Cursor::First(first), move |cursor| {
+        let repo = Arc::clone(&repo);
+        let filter = Arc::clone(&filter);
+        async move {
+            let (todos, opening) = match cursor {
+                Cursor::First(todos) => (todos, true),
+                Cursor::After(after) => {
+                    match repo.list_after(&filter, Some(after), CHUNK_SIZE).await {
+                        Ok(todos) => (todos, false),
+                        Err(err) => return Some((Err(err), Cursor::Done)),
+                    }
+                }
+                Cursor::Done => return None,
+            };
+
+            let next = match todos.last() {
+                Some(last) if todos.len() == CHUNK_SIZE => Cursor::After(last.id),
+                _ => Cursor::Done,
+            };
+            let closing = matches!(next, Cursor::Done);
+
+            Some((encode_chunk(&todos, fields, opening, closing), next))
+        }
+    });
+
+    Body::from_stream(track("list", chunks, Bytes::len))
+}
+
+/// A response stream that notices being dropped before it finished.
+pub struct Tracked<S: TryStream> {
+    stream: Pin<Box<S>>,
+    name: &'static str,
+    size: fn(&S::Ok) -> usize,
+    bytes_sent: usize,
+    finished: bool,
+}
+
+/// Wraps `stream` so an early drop is logged at debug as `name`, with the
+/// bytes sent so far as measured by `size`.
+pub fn track<S: TryStream>(
+    name: &'static str,
+    stream: S,
+    size: fn(&S::Ok) -> usize,
+) -> Tracked<S> {
+    Tracked {
+        stream: Box::pin(stream),
+        name,
+        size,
+        bytes_sent: 0,
+        finished: false,
+    }
+}
+
+impl<S: TryStream> Stream for Tracked<S> {
+    type Item = Result<S::Ok, S::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self.stream.as_mut().try_poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(item))) => self.bytes_sent += (self.size)(item),
+            // An error ends the response too; whoever produced it logs it.
+            Poll::Ready(Some(Err(_)) | None) => self.finished = true,
+            Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+impl<S: TryStream> Drop for Tracked<S> {
+    fn drop(&mut self) {
+        if !self.finished {
+            tracing::debug!(
+                stream = self.name,
+                bytes_sent = self.bytes_sent,
+                "client disconnected mid-stream"
+            );
+        }
+    }
+}
+
+fn encode_chunk(
+    todos: &[Todo],
+    fields: Option<Projection>,
+    opening: bool,
+    closing: bool,
+) -> Result<Bytes, AppError> {
+    let mut buf = Vec::with_capacity(todos.len() * 64 + 2);
+    if opening {
+        buf.push(b'[');
+    }
+    for (index, todo) in todos.iter().enumerate() {
+        if index > 0 || !opening {
+            buf.push(b',');
+        }
+        serde_json::to_writer(&mut buf, &Projected::new(todo, fields))
+            .map_err(|_| AppError::Internal)?;
+    }
+    if closing {
+        buf.push(b']');
+    }
+    Ok(Bytes::from(buf))
+}
@@ -0,0 +1,213 @@
+//! Keeping background tasks alive, or the process honest when they die.
+//!
+//! The outbox dispatcher, the reminder scheduler and the email notifier run
+//! beside the server. Left alone, one that panics or gives up on an error
+//! simply stops, and the server keeps answering while webhooks or reminders
+//! quietly go missing. [`supervise`] runs each of them instead: it records
+//! the task's state in the [`TaskHealth`] registry on the
+//! [`AppState`], logs every failure with the panic payload or error, counts
+//! it in `task_failures_total{task}`, and then follows `ON_TASK_FAILURE`:
+//!
+//! - `restart` (the default): start the task again, waiting
+//!   [`FIRST_RESTART_DELAY`] after the first failure and twice as long after
+//!   each further one, up to [`MAX_RESTART_DELAY`].
+//! - `shutdown`: [begin a graceful shutdown](AppState::begin_shutdown), so
+//!   an orchestrator replaces the process.
+//!
+//! While a task marked critical is down, `GET /ready` answers `503`.
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    str::FromStr,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::state::AppState;
+
+/// Wait before the first restart. It doubles after every further failure,
+/// up to [`MAX_RESTART_DELAY`].
+pub const FIRST_RESTART_DELAY: Duration = Duration::from_millis(100);
+
+/// Longest wait before a restart. A task that ran at least this long before
+/// failing starts over from [`FIRST_RESTART_DELAY`].
+pub const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// What to do when a supervised task fails (`ON_TASK_FAILURE`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnTaskFailure {
+    #[default]
+    Restart,
+    Shutdown,
+}
+
+impl FromStr for OnTaskFailure {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "restart" => Ok(Self::Restart),
+            "shutdown" => Ok(Self::Shutdown),
+            _ => Err(format!("unknown task failure policy `{value}`, expected restart or shutdown")),
+        }
+    }
+}
+
+impl fmt::Display for OnTaskFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Restart => "restart",
+            Self::Shutdown => "shutdown",
+        })
+    }
+}
+
+/// Where a supervised task is at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Running,
+    /// Failed, and not running again yet (or ever, under `shutdown`).
+    Failed,
+    /// Returned normally, as tasks do once shutdown begins.
+    Stopped,
+}
+
+/// One task's entry in [`TaskHealth`].
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskReport {
+    pub status: TaskStatus,
+    /// Whether the server is unready while the task is down.
+    pub critical: bool,
+    pub failures: u64,
+    /// The panic payload or error of the latest failure.
+    pub last_error: Option<String>,
+}
+
+/// The state of every supervised task, by name.
+#[derive(Debug, Default)]
+pub struct TaskHealth {
+    tasks: Mutex<BTreeMap<&'static str, TaskReport>>,
+}
+
+impl TaskHealth {
+    /// Every task's report, by name.
+    pub fn reports(&self) -> BTreeMap<&'static str, TaskReport> {
+        self.tasks.lock().expect("task health lock poisoned").clone()
+    }
+
+    pub fn report(&self, name: &str) -> Option<TaskReport> {
+        self.tasks.lock().expect("task health lock poisoned").get(name).cloned()
+    }
+
+    /// Critical tasks that have failed and aren't running again yet.
+    pub fn down(&self) -> Vec<&'static str> {
+        let tasks = self.tasks.lock().expect("task health lock poisoned");
+        tasks
+            .iter()
+            .filter(|(_, report)| report.critical && report.status == TaskStatus::Failed)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    fn update(&self, name: &'static str, critical: bool, change: impl FnOnce(&mut TaskReport)) {
+        let mut tasks = self.tasks.lock().expect("task health lock poisoned");
+        let report = tasks.entry(name).or_insert(TaskReport {
+            status: TaskStatus::Running,
+            critical,
+            failures: 0,
+            last_error: None,
+        });
+        change(report);
+    }
+}
+
+/// How a task ended: `()` for tasks that can only panic, or a `Result` for
+/// ones that give up on an error.
+pub trait TaskOutcome {
+    fn into_result(self) -> anyhow::Result<()>;
+}
+
+impl TaskOutcome for () {
+    fn into_result(self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl<E: Into<anyhow::Error>> TaskOutcome for Result<(), E> {
+    fn into_result(self) -> anyhow::Result<()> {
+        self.map_err(Into::into)
+    }
+}
+
+/// Runs the task `start` builds as `name` until it returns normally,
+/// starting it again or shutting the server down when it fails; see the
+/// [module docs](self).
+pub async fn supervise<F, Fut>(state: AppState, name: &'static str, critical: bool, mut start: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: TaskOutcome + Send + 'static,
+{
+    let health = state.task_health();
+    let mut delay = FIRST_RESTART_DELAY;
+    loop {
+        health.update(name, critical, |report| report.status = TaskStatus::Running);
+        let started = Instant::now();
+        let error = match tokio::spawn(start()).await {
+            Ok(outcome) => match outcome.into_result() {
+                Ok(()) => {
+                    health.update(name, critical, |report| report.status = TaskStatus::Stopped);
+                    return;
+                }
+                Err(err) => format!("{err:#}"),
+            },
+            Err(err) if err.is_panic() => panic_message(err.into_panic()),
+            Err(err) => err.to_string(),
+        };
+
+        tracing::error!(task = name, critical, error, "background task failed");
+        state.metrics().task_failures_total.with_label_values(&[name]).inc();
+        health.update(name, critical, |report| {
+            report.status = TaskStatus::Failed;
+            report.failures += 1;
+            report.last_error = Some(error);
+        });
+
+        match state.config().on_task_failure {
+            OnTaskFailure::Shutdown => {
+                tracing::error!(task = name, "shutting down after a background task failed");
+                state.begin_shutdown();
+                return;
+            }
+            OnTaskFailure::Restart => {
+                if started.elapsed() >= MAX_RESTART_DELAY {
+                    delay = FIRST_RESTART_DELAY;
+                }
+                tracing::info!(task = name, restart_in_ms = delay.as_millis(), "restarting task");
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = state.shutting_down().cancelled() => return,
+                }
+                delay = (delay * 2).min(MAX_RESTART_DELAY);
+            }
+        }
+    }
+}
+
+/// The message a panic was raised with.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "panicked with a non-string payload".to_string(),
+        },
+    }
+}
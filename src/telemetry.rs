@@ -0,0 +1,215 @@
+//! Logging and tracing setup.
+//!
+//! # Reloadable filters
+//!
+//! `tracing_subscriber::reload::Layer` wraps the `EnvFilter` so it can be
+//! replaced while the process runs. `init` hands back the matching handle,
+//! which the SIGHUP reload path uses to apply a new `RUST_LOG` without a
+//! restart.
+//!
+//! # OpenTelemetry (`otel` feature)
+//!
+//! With the feature enabled and `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are
+//! additionally exported over OTLP/HTTP. Incoming W3C `traceparent` /
+//! `tracestate` headers are extracted in [`make_span`] so our server spans join
+//! the caller's trace, and [`inject_context`] does the reverse for outgoing
+//! HTTP calls. Without the feature or endpoint, only the console layer runs.
+
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderMap, Request},
+};
+use tracing::Span;
+use tracing_subscriber::{
+    fmt::{self, writer::BoxMakeWriter},
+    layer::SubscriberExt,
+    reload,
+    util::SubscriberInitExt,
+    EnvFilter, Registry,
+};
+
+use crate::config::Config;
+
+#[cfg(feature = "otel")]
+pub use otel::{install_propagator, layer as otel_layer};
+
+/// Handle used to swap the active log filter at runtime.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Flushes buffered spans when dropped. Keep it alive until `main` returns.
+#[must_use = "dropping the guard immediately shuts down span export"]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("failed to flush OpenTelemetry spans: {err}");
+            }
+        }
+    }
+}
+
+/// Installs the global subscriber. Call once, early in `main`.
+pub fn init(config: &Config) -> anyhow::Result<(LogFilterHandle, TelemetryGuard)> {
+    // `EnvFilter` uses the `RUST_LOG` syntax to determine what to log. The
+    // string was validated while loading config, so `new` cannot drop anything.
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&config.rust_log));
+    // With `READY_ANNOUNCE`, stdout is reserved for the ready line.
+    let writer = if config.ready_announce {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().compact().with_writer(writer));
+
+    #[cfg(feature = "otel")]
+    {
+        let provider = config
+            .otel_endpoint
+            .as_deref()
+            .map(otel::provider)
+            .transpose()?;
+        let layer = provider.as_ref().map(otel::layer);
+        registry.with(layer).init();
+        Ok((handle, TelemetryGuard { provider }))
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        Ok((handle, TelemetryGuard {}))
+    }
+}
+
+/// Builds the per-request span used by `TraceLayer`.
+///
+/// The span is named after the route template (`GET /todos/:id`) rather than
+/// the raw path so trace backends don't explode with one name per id.
+pub fn make_span<B>(req: &Request<B>) -> Span {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or("unmatched");
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok());
+
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        route,
+        request_id,
+        otel.name = format!("{} {route}", req.method()),
+        otel.kind = "server",
+    );
+
+    #[cfg(feature = "otel")]
+    otel::set_remote_parent(&span, req.headers());
+
+    span
+}
+
+/// Writes the current span's trace context into `headers` so the receiver of
+/// an outgoing request can continue our trace. A no-op without `otel`.
+pub fn inject_context(headers: &mut HeaderMap) {
+    #[cfg(feature = "otel")]
+    otel::inject(&Span::current(), headers);
+
+    #[cfg(not(feature = "otel"))]
+    let _ = headers;
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use axum::http::{HeaderMap, HeaderName, HeaderValue};
+    use opentelemetry::{
+        global,
+        propagation::{Extractor, Injector},
+        trace::TracerProvider as _,
+    };
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{
+        propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource,
+    };
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::{registry::LookupSpan, Layer};
+
+    /// Makes `traceparent`/`tracestate` the propagation format for this process.
+    pub fn install_propagator() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+    }
+
+    /// Sets up the OTLP/HTTP exporter and the W3C propagator.
+    pub(super) fn provider(endpoint: &str) -> anyhow::Result<SdkTracerProvider> {
+        install_propagator();
+
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+            .build()?;
+
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("rust-api").build())
+            .build())
+    }
+
+    /// Bridges `tracing` spans into the given OpenTelemetry provider.
+    pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("rust-api"))
+    }
+
+    pub(super) fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+        let parent = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(headers)));
+        // Fails only when no OpenTelemetry layer is installed, which simply
+        // means there is nothing to join.
+        let _ = span.set_parent(parent);
+    }
+
+    pub(super) fn inject(span: &Span, headers: &mut HeaderMap) {
+        let context = span.context();
+        global::get_text_map_propagator(|p| {
+            p.inject_context(&context, &mut HeaderInjector(headers))
+        });
+    }
+
+    struct HeaderExtractor<'a>(&'a HeaderMap);
+
+    impl Extractor for HeaderExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|value| value.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(HeaderName::as_str).collect()
+        }
+    }
+
+    struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+    impl Injector for HeaderInjector<'_> {
+        fn set(&mut self, key: &str, value: String) {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(key.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                self.0.insert(name, value);
+            }
+        }
+    }
+}
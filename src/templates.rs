@@ -0,0 +1,87 @@
+//! Reusable sets of todos.
+//!
+//! A [`Template`] is a name and up to [`SaveTemplate::MAX_ITEMS`] todo
+//! bodies, validated when it is saved. `POST /templates/:id/instantiate`
+//! creates all of its todos in one [`create_many`] call, so on the in-memory
+//! repository they appear together or not at all, each with a new id.
+//!
+//! Templates live in a [`TemplateRepo`] of their own, next to the todos'
+//! repository in [`AppState`](crate::AppState).
+//!
+//! [`create_many`]: crate::state::TodoRepo::create_many
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::{
+    errors::AppError,
+    models::{SaveTemplate, Template},
+};
+
+/// Storage for [`Template`]s. Implementations may assume the input was
+/// [validated](SaveTemplate::validate).
+#[async_trait]
+pub trait TemplateRepo: Send + Sync + 'static {
+    /// Every template, in id order.
+    async fn list(&self) -> Result<Vec<Template>, AppError>;
+
+    async fn get(&self, id: u64) -> Result<Template, AppError>;
+
+    async fn create(&self, input: SaveTemplate) -> Result<Template, AppError>;
+
+    /// Replaces the name and items of template `id`.
+    async fn replace(&self, id: u64, input: SaveTemplate) -> Result<Template, AppError>;
+
+    async fn delete(&self, id: u64) -> Result<(), AppError>;
+}
+
+/// [`TemplateRepo`] that keeps everything in process memory.
+#[derive(Default)]
+pub struct InMemoryTemplates {
+    inner: RwLock<Templates>,
+}
+
+#[derive(Default)]
+struct Templates {
+    next_id: u64,
+    items: BTreeMap<u64, Template>,
+}
+
+#[async_trait]
+impl TemplateRepo for InMemoryTemplates {
+    async fn list(&self) -> Result<Vec<Template>, AppError> {
+        Ok(self.inner.read().await.items.values().cloned().collect())
+    }
+
+    async fn get(&self, id: u64) -> Result<Template, AppError> {
+        let guard = self.inner.read().await;
+        guard.items.get(&id).cloned().ok_or(AppError::NotFound)
+    }
+
+    async fn create(&self, input: SaveTemplate) -> Result<Template, AppError> {
+        let mut guard = self.inner.write().await;
+        guard.next_id += 1;
+        let template = Template {
+            id: guard.next_id,
+            name: input.name.trim().to_string(),
+            items: input.items,
+        };
+        guard.items.insert(template.id, template.clone());
+        Ok(template)
+    }
+
+    async fn replace(&self, id: u64, input: SaveTemplate) -> Result<Template, AppError> {
+        let mut guard = self.inner.write().await;
+        let template = guard.items.get_mut(&id).ok_or(AppError::NotFound)?;
+        template.name = input.name.trim().to_string();
+        template.items = input.items;
+        Ok(template.clone())
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        let mut guard = self.inner.write().await;
+        guard.items.remove(&id).map(drop).ok_or(AppError::NotFound)
+    }
+}
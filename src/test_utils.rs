@@ -0,0 +1,464 @@
+//! Helpers for testing the service, or an application embedding it, without
+//! a socket (`test-utils` feature).
+//!
+//! - [`TestClient`] drives a [`Router`] in process and hands back the status,
+//!   headers, and parsed body of each response.
+//! - [`seed`] fills a repository with todos.
+//! - [`MockRepo`] behaves like the in-memory repo until told otherwise: each
+//!   method can be scripted to fail or return a fixed value on its next
+//!   calls, and every call is counted.
+//! - [`MockClock`] is a [`Clock`] that only moves when told to.
+//!
+//! ```ignore
+//! let mock = Arc::new(MockRepo::new());
+//! let client = TestClient::new(app(AppState::with_repo(mock.clone())));
+//! mock.fail_next(RepoMethod::List, AppError::Internal);
+//! assert_eq!(client.get("/todos").await.status, StatusCode::INTERNAL_SERVER_ERROR);
+//! assert_eq!(mock.calls(RepoMethod::List), 1);
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{
+    clock::Clock,
+    errors::AppError,
+    models::{
+        Attachment, Bucket, Changes, CreateTodo, ImportMode, ImportReport, NewAttachment,
+        RunMode, SearchHit, Snapshot, TimeseriesPoint, Todo, TodoFilter, UpdateTodo,
+    },
+    state::{in_memory_repo, TodoRepo},
+};
+
+/// In-process HTTP client for a [`Router`].
+#[derive(Clone)]
+pub struct TestClient {
+    router: Router,
+}
+
+/// What a [`TestClient`] request returned.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The body parsed as JSON; a JSON string holding the raw text when it
+    /// isn't JSON, and `null` when it is empty.
+    pub body: Value,
+}
+
+impl TestResponse {
+    /// The body deserialized as `T`.
+    ///
+    /// # Panics
+    ///
+    /// When the body doesn't have `T`'s shape.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_value(self.body.clone())
+            .unwrap_or_else(|err| panic!("unexpected body {}: {err}", self.body))
+    }
+}
+
+impl TestClient {
+    pub fn new(router: Router) -> Self {
+        Self { router }
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.send(Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    pub async fn post_json(&self, uri: &str, body: &impl Serialize) -> TestResponse {
+        self.send(json_request("POST", uri, body)).await
+    }
+
+    pub async fn put_json(&self, uri: &str, body: &impl Serialize) -> TestResponse {
+        self.send(json_request("PUT", uri, body)).await
+    }
+
+    pub async fn patch_json(&self, uri: &str, body: &impl Serialize) -> TestResponse {
+        self.send(json_request("PATCH", uri, body)).await
+    }
+
+    pub async fn delete(&self, uri: &str) -> TestResponse {
+        self.send(Request::delete(uri).body(Body::empty()).unwrap()).await
+    }
+
+    /// Sends any request, for methods, headers, or bodies the shorthands
+    /// don't cover.
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("routers are infallible");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("test response body is readable");
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+}
+
+fn json_request(method: &str, uri: &str, body: &impl Serialize) -> Request<Body> {
+    let body = serde_json::to_vec(body).expect("test body serializes");
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Creates `todos` in order through `repo` and returns them as stored.
+///
+/// # Panics
+///
+/// When the repository rejects one.
+pub async fn seed(repo: &dyn TodoRepo, todos: impl IntoIterator<Item = CreateTodo>) -> Vec<Todo> {
+    let mut created = Vec::new();
+    for input in todos {
+        created.push(repo.create(input).await.expect("seed todo"));
+    }
+    created
+}
+
+/// A [`Clock`] standing still at the time it was given until advanced or
+/// set, for tests of anything time-dependent.
+///
+/// ```ignore
+/// let clock = Arc::new(MockClock::new(start));
+/// let state = AppState::in_memory_with_clock(clock.clone());
+/// clock.advance(chrono::Duration::hours(1));
+/// ```
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    /// Moves the clock to `at`, backwards if need be.
+    pub fn set(&self, at: DateTime<Utc>) {
+        *self.now.lock().unwrap() = at;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The [`MockRepo`] methods that can be scripted and counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RepoMethod {
+    List,
+    ListAfter,
+    ListPage,
+    Count,
+    Create,
+    Get,
+    Update,
+    Toggle,
+    Delete,
+    /// Only scriptable with [`Reply::Error`].
+    Ping,
+}
+
+/// A scripted result for one call.
+#[derive(Debug)]
+pub enum Reply {
+    Error(AppError),
+    /// For `create`, `get`, `update`, and `toggle`.
+    Todo(Todo),
+    /// For `list`, `list_after`, and `list_page`.
+    Todos(Vec<Todo>),
+    /// For `count`.
+    Count(usize),
+    /// For `delete`.
+    Deleted,
+}
+
+/// [`TodoRepo`] test double backed by an in-memory repo.
+///
+/// Replies queued with [`reply_next`](Self::reply_next) are used one per call
+/// in order; once a method's queue is empty it falls through to the real
+/// implementation again. Methods without a [`RepoMethod`] always fall
+/// through.
+#[derive(Default)]
+pub struct MockRepo {
+    inner: Option<Arc<dyn TodoRepo>>,
+    script: Mutex<HashMap<RepoMethod, VecDeque<Reply>>>,
+    calls: Mutex<HashMap<RepoMethod, usize>>,
+}
+
+impl MockRepo {
+    pub fn new() -> Self {
+        Self {
+            inner: Some(in_memory_repo()),
+            ..Default::default()
+        }
+    }
+
+    /// Makes the next call to `method` fail with `error`.
+    pub fn fail_next(&self, method: RepoMethod, error: AppError) {
+        self.reply_next(method, Reply::Error(error));
+    }
+
+    /// Makes the next call to `method` return `reply`.
+    pub fn reply_next(&self, method: RepoMethod, reply: Reply) {
+        let mut script = self.script.lock().expect("mock script lock poisoned");
+        script.entry(method).or_default().push_back(reply);
+    }
+
+    /// How many times `method` has been called.
+    pub fn calls(&self, method: RepoMethod) -> usize {
+        let calls = self.calls.lock().expect("mock call lock poisoned");
+        calls.get(&method).copied().unwrap_or(0)
+    }
+
+    /// Counts a call and returns its scripted reply, if any.
+    fn record(&self, method: RepoMethod) -> Option<Reply> {
+        *self.calls.lock().expect("mock call lock poisoned").entry(method).or_default() += 1;
+        let mut script = self.script.lock().expect("mock script lock poisoned");
+        script.get_mut(&method).and_then(VecDeque::pop_front)
+    }
+
+    fn inner(&self) -> &dyn TodoRepo {
+        self.inner.as_deref().expect("MockRepo::new creates the backing repo")
+    }
+}
+
+/// Unwraps a scripted reply of the expected variant.
+macro_rules! scripted {
+    ($reply:expr, $method:expr, $variant:ident) => {
+        match $reply {
+            Reply::Error(err) => return Err(err),
+            Reply::$variant(value) => return Ok(value),
+            other => panic!("{:?} can't reply {other:?}", $method),
+        }
+    };
+}
+
+#[async_trait]
+impl TodoRepo for MockRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        if let Some(reply) = self.record(RepoMethod::List) {
+            scripted!(reply, RepoMethod::List, Todos);
+        }
+        self.inner().list().await
+    }
+
+    async fn list_after(
+        &self,
+        filter: &TodoFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        if let Some(reply) = self.record(RepoMethod::ListAfter) {
+            scripted!(reply, RepoMethod::ListAfter, Todos);
+        }
+        self.inner().list_after(filter, after, limit).await
+    }
+
+    async fn list_page(
+        &self,
+        filter: &TodoFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Todo>, AppError> {
+        if let Some(reply) = self.record(RepoMethod::ListPage) {
+            scripted!(reply, RepoMethod::ListPage, Todos);
+        }
+        self.inner().list_page(filter, offset, limit).await
+    }
+
+    async fn count(&self, filter: &TodoFilter) -> Result<usize, AppError> {
+        if let Some(reply) = self.record(RepoMethod::Count) {
+            scripted!(reply, RepoMethod::Count, Count);
+        }
+        self.inner().count(filter).await
+    }
+
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
+        if let Some(reply) = self.record(RepoMethod::Create) {
+            scripted!(reply, RepoMethod::Create, Todo);
+        }
+        self.inner().create(input).await
+    }
+
+    async fn create_many(&self, inputs: Vec<CreateTodo>) -> Result<Vec<Todo>, AppError> {
+        self.inner().create_many(inputs).await
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        if let Some(reply) = self.record(RepoMethod::Get) {
+            scripted!(reply, RepoMethod::Get, Todo);
+        }
+        self.inner().get(id).await
+    }
+
+    async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        if let Some(reply) = self.record(RepoMethod::Update) {
+            scripted!(reply, RepoMethod::Update, Todo);
+        }
+        self.inner().update(id, input).await
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        if let Some(reply) = self.record(RepoMethod::Toggle) {
+            scripted!(reply, RepoMethod::Toggle, Todo);
+        }
+        self.inner().toggle(id).await
+    }
+
+    async fn update_many_at(
+        &self,
+        revision: u64,
+        updates: Vec<(u64, UpdateTodo)>,
+    ) -> Result<Vec<Result<Todo, AppError>>, AppError> {
+        self.inner().update_many_at(revision, updates).await
+    }
+
+    async fn upsert(&self, id: u64, input: UpdateTodo) -> Result<(Todo, bool), AppError> {
+        self.inner().upsert(id, input).await
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        if let Some(reply) = self.record(RepoMethod::Delete) {
+            match reply {
+                Reply::Error(err) => return Err(err),
+                Reply::Deleted => return Ok(()),
+                other => panic!("Delete can't reply {other:?}"),
+            }
+        }
+        self.inner().delete(id).await
+    }
+
+    async fn version(&self, id: u64) -> Result<Option<u64>, AppError> {
+        self.inner().version(id).await
+    }
+
+    async fn delete_if_version(&self, id: u64, version: u64) -> Result<(), AppError> {
+        self.inner().delete_if_version(id, version).await
+    }
+
+    async fn add_attachment(
+        &self,
+        todo_id: u64,
+        input: NewAttachment,
+    ) -> Result<Attachment, AppError> {
+        self.inner().add_attachment(todo_id, input).await
+    }
+
+    async fn list_attachments(&self, todo_id: u64) -> Result<Vec<Attachment>, AppError> {
+        self.inner().list_attachments(todo_id).await
+    }
+
+    async fn get_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.inner().get_attachment(id).await
+    }
+
+    async fn delete_attachment(&self, id: u64) -> Result<Attachment, AppError> {
+        self.inner().delete_attachment(id).await
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        self.inner().search(query, limit).await
+    }
+
+    async fn find_similar(&self, title: &str, threshold: f64) -> Result<Vec<u64>, AppError> {
+        self.inner().find_similar(title, threshold).await
+    }
+
+    async fn due_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Todo>, AppError> {
+        self.inner().due_between(from, to).await
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        self.inner().mark_reminded(id, at).await
+    }
+
+    async fn timeseries(
+        &self,
+        bucket: Bucket,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<TimeseriesPoint>, AppError> {
+        self.inner().timeseries(bucket, range).await
+    }
+
+    async fn changes_since(&self, since: u64) -> Result<Changes, AppError> {
+        self.inner().changes_since(since).await
+    }
+
+    async fn export_all(&self) -> Result<Snapshot, AppError> {
+        self.inner().export_all().await
+    }
+
+    async fn import_all(
+        &self,
+        snapshot: Snapshot,
+        mode: ImportMode,
+        run: RunMode,
+    ) -> Result<ImportReport, AppError> {
+        self.inner().import_all(snapshot, mode, run).await
+    }
+
+    async fn revision(&self) -> Result<Option<u64>, AppError> {
+        self.inner().revision().await
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        if let Some(reply) = self.record(RepoMethod::Ping) {
+            match reply {
+                Reply::Error(err) => return Err(err),
+                other => panic!("{:?} can't reply {other:?}", RepoMethod::Ping),
+            }
+        }
+        self.inner().ping().await
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner().backend()
+    }
+}
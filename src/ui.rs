@@ -0,0 +1,86 @@
+//! The bundled web UI, for a single binary that is a complete todo app.
+//!
+//! Built with the `ui` feature, the files under `ui/` are compiled into the
+//! binary and served at `/ui/` as `UI_POLICY` allows. The UI is a
+//! single-page app, so any other path under `/ui/` gets `index.html` and the
+//! client-side router takes it from there. Paths that look like a file (they
+//! have an extension) are the exception: a missing script is a `404`, not a
+//! page of HTML the browser would fail to run.
+//!
+//! The files go through the same layers as the API, so they are compressed,
+//! ETagged, and logged like everything else.
+
+use axum::{
+    extract::Path,
+    http::header,
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{caching::CachePolicy, errors::AppError};
+
+/// A file compiled into the binary.
+struct Asset {
+    path: &'static str,
+    content_type: &'static str,
+    bytes: &'static [u8],
+}
+
+const INDEX: Asset = Asset {
+    path: "index.html",
+    content_type: "text/html; charset=utf-8",
+    bytes: include_bytes!("../ui/index.html"),
+};
+
+const ASSETS: [Asset; 3] = [
+    INDEX,
+    Asset {
+        path: "app.js",
+        content_type: "text/javascript; charset=utf-8",
+        bytes: include_bytes!("../ui/app.js"),
+    },
+    Asset {
+        path: "style.css",
+        content_type: "text/css; charset=utf-8",
+        bytes: include_bytes!("../ui/style.css"),
+    },
+];
+
+/// How long browsers may reuse scripts and styles without asking. Their names
+/// don't change between releases, so this stays short.
+const ASSET_MAX_AGE_SECS: u64 = 60 * 60;
+
+/// `GET /ui` - on to `/ui/`, where the app's relative links resolve.
+pub async fn redirect() -> Redirect {
+    Redirect::permanent("/ui/")
+}
+
+/// `GET /ui/` - the app itself.
+pub async fn index() -> Response {
+    serve(&INDEX)
+}
+
+/// `GET /ui/*path` - a bundled file, or `index.html` for client-side routes.
+pub async fn asset(Path(path): Path<String>) -> Result<Response, AppError> {
+    if let Some(asset) = ASSETS.iter().find(|asset| asset.path == path) {
+        return Ok(serve(asset));
+    }
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    if file_name.contains('.') {
+        return Err(AppError::NotFound);
+    }
+    Ok(serve(&INDEX))
+}
+
+fn serve(asset: &Asset) -> Response {
+    // The page must pick up a new release on the next load; it is what
+    // points at the current scripts.
+    let policy = if asset.path == INDEX.path {
+        CachePolicy::Revalidate
+    } else {
+        CachePolicy::Private {
+            max_age: ASSET_MAX_AGE_SECS,
+        }
+    };
+    let content_type = [(header::CONTENT_TYPE, asset.content_type)];
+    (policy, content_type, asset.bytes).into_response()
+}
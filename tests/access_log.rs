@@ -0,0 +1,97 @@
+// Access-log tests install a capturing subscriber and inspect the single
+// structured event each request produces.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::LogCapture;
+use http_body_util::BodyExt;
+use rust_api::{app, config::Config, AppState};
+use serde_json::json;
+use tower::ServiceExt;
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TARGET: &str = "rust_api::access";
+
+#[tokio::test(flavor = "current_thread")]
+async fn create_request_logs_structured_fields() {
+    let logs = LogCapture::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+
+    let res = app(AppState::new_in_memory())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header("content-type", "application/json")
+                .header("x-request-id", "req-123")
+                .body(Body::from(json!({ "title": "log me" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(res.headers()["x-request-id"], "req-123");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+
+    let events = logs.for_target(TARGET);
+    assert_eq!(events.len(), 1, "{events:?}");
+    let event = &events[0];
+    assert_eq!(event.level, Level::INFO);
+    assert_eq!(event.field("method"), Some("POST"));
+    assert_eq!(event.field("route"), Some("/todos"));
+    assert_eq!(event.field("status"), Some("201"));
+    assert_eq!(event.field("request_id"), Some("req-123"));
+    assert_eq!(event.field("bytes"), Some(body.len().to_string().as_str()));
+    assert!(event.field("latency_ms").is_some());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn request_id_is_generated_when_missing() {
+    let res = app(AppState::new_in_memory())
+        .oneshot(Request::builder().uri("/todos").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(!res.headers()["x-request-id"].is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn client_errors_escalate_only_when_configured() {
+    for (flag, expected) in [("false", Level::INFO), ("true", Level::WARN)] {
+        let logs = LogCapture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+        let config = Config::from_lookup(|key| (key == "LOG_CLIENT_ERRORS").then(|| flag.into()));
+        let state = AppState::new_in_memory().with_config(config.unwrap());
+
+        let res = app(state)
+            .oneshot(Request::builder().uri("/todos/999").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        drop(res);
+
+        let events = logs.for_target(TARGET);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, expected);
+        assert_eq!(events[0].field("route"), Some("/todos/:id"));
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn health_checks_are_excluded_by_default() {
+    let logs = LogCapture::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+
+    let res = app(AppState::new_in_memory())
+        .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    res.into_body().collect().await.unwrap();
+
+    assert!(logs.for_target(TARGET).is_empty());
+}
@@ -0,0 +1,126 @@
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let mut req = Request::builder().method(method).uri(uri);
+    let body = match body {
+        Some(body) => {
+            req = req.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    let res = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+    let status = res.status();
+    let bytes = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn ids(todos: &Value) -> Vec<u64> {
+    todos
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn assigns_and_reassigns() {
+    let app = app(AppState::new_in_memory());
+    let (status, todo) = send(
+        &app,
+        "POST",
+        "/todos",
+        Some(json!({ "title": "take out trash", "assignee": "  alice " })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(todo["assignee"], "alice");
+
+    let (status, todo) =
+        send(&app, "POST", "/todos/1/assign", Some(json!({ "assignee": "bob" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(todo["assignee"], "bob");
+    assert_eq!(todo["title"], "take out trash");
+
+    let (status, todo) =
+        send(&app, "POST", "/todos/1/assign", Some(json!({ "assignee": null }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(todo.get("assignee").is_none());
+
+    let (status, _) = send(&app, "POST", "/todos/9/assign", Some(json!({ "assignee": "bob" })))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn filters_by_assignee_and_unassigned() {
+    let app = app(AppState::new_in_memory());
+    for (title, assignee) in [("dishes", Some("alice")), ("laundry", None), ("vacuum", Some("bob"))]
+    {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/todos",
+            Some(json!({ "title": title, "assignee": assignee })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let (_, todos) = send(&app, "GET", "/todos?assignee=alice", None).await;
+    assert_eq!(ids(&todos), [1]);
+    let (_, todos) = send(&app, "GET", "/todos?assignee=none", None).await;
+    assert_eq!(ids(&todos), [2]);
+    let (_, todos) = send(&app, "GET", "/todos?assignee=carol", None).await;
+    assert_eq!(ids(&todos), Vec::<u64>::new());
+    let (_, todos) = send(&app, "GET", "/todos?assignee=bob&limit=10", None).await;
+    assert_eq!(ids(&todos), [3]);
+}
+
+#[tokio::test]
+async fn update_clears_only_on_explicit_null() {
+    let app = app(AppState::new_in_memory());
+    send(&app, "POST", "/todos", Some(json!({ "title": "mow", "assignee": "alice" }))).await;
+
+    let (_, todo) = send(&app, "PUT", "/todos/1", Some(json!({ "done": true }))).await;
+    assert_eq!(todo["assignee"], "alice");
+
+    let (status, todo) = send(&app, "PUT", "/todos/1", Some(json!({ "assignee": null }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(todo.get("assignee").is_none());
+    let (_, todos) = send(&app, "GET", "/todos?assignee=none", None).await;
+    assert_eq!(ids(&todos), [1]);
+}
+
+#[tokio::test]
+async fn rejects_invalid_assignees() {
+    let app = app(AppState::new_in_memory());
+    send(&app, "POST", "/todos", Some(json!({ "title": "mow" }))).await;
+
+    for assignee in [json!("   "), json!("none"), json!("x".repeat(65))] {
+        let (status, _) =
+            send(&app, "POST", "/todos/1/assign", Some(json!({ "assignee": assignee }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{assignee}");
+    }
+    let (status, _) = send(&app, "POST", "/todos/1/assign", Some(json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, todo) = send(
+        &app,
+        "POST",
+        "/todos/1/assign",
+        Some(json!({ "assignee": "é".repeat(64) })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(todo["assignee"].as_str().unwrap().chars().count(), 64);
+}
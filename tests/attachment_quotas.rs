@@ -0,0 +1,188 @@
+// Attachment quotas: `ATTACHMENT_MAX_PER_TODO` and `ATTACHMENTS_MAX_BYTES`
+// refuse uploads that don't fit, deletes give the bytes back, and the
+// ledger is recounted from the disk at startup.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_api::{
+    app,
+    attachments::{self, StorageScope, StorageUsage},
+    config::Config,
+    models::{CreateTodo, StorageStats},
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+use tempfile::TempDir;
+
+const BOUNDARY: &str = "X-TEST-BOUNDARY";
+
+/// Two todos whose attachments go to a fresh directory.
+async fn setup(vars: &[(&str, &str)]) -> (TempDir, AppState, TestClient) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    vars.insert(
+        "ATTACHMENT_DIR".into(),
+        dir.path().to_str().unwrap().to_string(),
+    );
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    for title in ["first", "second"] {
+        state
+            .service()
+            .repo()
+            .create(CreateTodo::new(title.parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    let client = TestClient::new(app(state.clone()));
+    (dir, state, client)
+}
+
+async fn upload(client: &TestClient, todo_id: u64, data: &[u8]) -> TestResponse {
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+         filename=\"scan.pdf\"\r\nContent-Type: application/pdf\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    let req = Request::post(format!("/todos/{todo_id}/attachments"))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    client.send(req).await
+}
+
+async fn usage(client: &TestClient) -> StorageStats {
+    let res = client.get("/todos/stats/storage").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    res.json()
+}
+
+#[tokio::test]
+async fn a_full_todo_refuses_uploads_until_an_attachment_is_deleted() {
+    let (_dir, _state, client) = setup(&[("ATTACHMENT_MAX_PER_TODO", "10")]).await;
+    let first = upload(&client, 1, b"aaaaaa").await;
+    assert_eq!(first.status, StatusCode::CREATED, "{}", first.body);
+
+    let res = upload(&client, 1, b"bbbbbb").await;
+    assert_eq!(res.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", res.body);
+    assert_eq!(
+        res.body["storage"],
+        json!({ "scope": "todo", "limit": 10, "used": 6, "size": 6 })
+    );
+    // The same bytes again are stored once, so they cost nothing.
+    assert_eq!(
+        upload(&client, 1, b"aaaaaa").await.status,
+        StatusCode::CREATED
+    );
+    // Other todos have their own quota.
+    assert_eq!(
+        upload(&client, 2, b"bbbbbb").await.status,
+        StatusCode::CREATED
+    );
+
+    // Both records share the file; it goes with the last one.
+    for id in [1, 2] {
+        assert_eq!(
+            client.delete(&format!("/attachments/{id}")).await.status,
+            StatusCode::NO_CONTENT
+        );
+    }
+    assert_eq!(
+        client.delete("/attachments/1").await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        upload(&client, 1, b"bbbbbb").await.status,
+        StatusCode::CREATED
+    );
+}
+
+#[tokio::test]
+async fn a_full_store_refuses_uploads_until_a_todo_is_deleted() {
+    let (_dir, _state, client) = setup(&[("ATTACHMENTS_MAX_BYTES", "10")]).await;
+    assert_eq!(
+        upload(&client, 1, b"aaaaaa").await.status,
+        StatusCode::CREATED
+    );
+
+    let res = upload(&client, 2, b"bbbbbb").await;
+    assert_eq!(res.status, StatusCode::INSUFFICIENT_STORAGE, "{}", res.body);
+    assert_eq!(res.body["storage"]["scope"], json!(StorageScope::Total));
+    assert_eq!(res.body["storage"]["used"], 6);
+    let stats = usage(&client).await;
+    assert_eq!(
+        (stats.used_bytes, stats.files, stats.max_bytes),
+        (6, 1, Some(10))
+    );
+    assert_eq!(stats.max_bytes_per_todo, None);
+
+    let scrape = client
+        .get("/metrics")
+        .await
+        .body
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(scrape.contains("attachment_storage_bytes 6"), "{scrape}");
+    assert!(
+        scrape.contains(r#"attachment_quota_rejections_total{scope="total"} 1"#),
+        "{scrape}"
+    );
+
+    assert_eq!(
+        client.delete("/todos/1").await.status,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(usage(&client).await.used_bytes, 0);
+    assert_eq!(
+        upload(&client, 2, b"bbbbbb").await.status,
+        StatusCode::CREATED
+    );
+}
+
+#[tokio::test]
+async fn startup_recounts_what_is_on_disk() {
+    let (dir, state, client) = setup(&[("ATTACHMENTS_MAX_BYTES", "10")]).await;
+    let stored = upload(&client, 1, b"aaaaaa").await;
+    assert_eq!(stored.status, StatusCode::CREATED);
+    assert_eq!(
+        upload(&client, 2, b"bbbbbbbb").await.status,
+        StatusCode::INSUFFICIENT_STORAGE
+    );
+
+    // The file goes missing behind the server's back, and a crash left an
+    // upload half-written.
+    let todo_dir = dir.path().join("1");
+    std::fs::remove_file(todo_dir.join(stored.body["sha256"].as_str().unwrap())).unwrap();
+    std::fs::write(todo_dir.join(".upload-7"), b"partial").unwrap();
+
+    let usage = attachments::reconcile(dir.path(), state.storage_ledger().as_ref()).await;
+    assert_eq!(usage.unwrap(), StorageUsage::default());
+    assert!(!todo_dir.join(".upload-7").exists());
+    assert_eq!(
+        upload(&client, 2, b"bbbbbbbb").await.status,
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        attachments::reconcile(dir.path(), state.storage_ledger().as_ref())
+            .await
+            .unwrap(),
+        StorageUsage {
+            used_bytes: 8,
+            files: 1
+        }
+    );
+}
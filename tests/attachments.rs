@@ -0,0 +1,211 @@
+use std::{collections::HashMap, path::Path};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, config::Config, models::CreateTodo, AppState};
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "X-TEST-BOUNDARY";
+
+/// An app with one todo whose attachments go to a fresh directory.
+async fn setup(vars: &[(&str, &str)]) -> (TempDir, Router) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    vars.insert(
+        "ATTACHMENT_DIR".into(),
+        dir.path().to_str().unwrap().to_string(),
+    );
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    state
+        .service().repo()
+        .create(CreateTodo::new("with files".parse().unwrap()))
+        .await
+        .unwrap();
+    (dir, app(state))
+}
+
+fn multipart(filename: &str, content_type: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nignored\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+             filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+async fn upload(app: &Router, filename: &str, content_type: &str, data: &[u8]) -> Response {
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos/1/attachments")
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={BOUNDARY}"),
+                )
+                .body(Body::from(multipart(filename, content_type, data)))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn send(app: &Router, method: &str, uri: &str) -> Response {
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn json(res: Response) -> Value {
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn files_under(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(files_under(&path));
+        } else {
+            files.push(path.display().to_string());
+        }
+    }
+    files
+}
+
+#[tokio::test]
+async fn upload_list_and_download_round_trip() {
+    let (dir, app) = setup(&[]).await;
+    // Larger than the 2 MiB limit on other request bodies.
+    let data: Vec<u8> = (0..=255u8).cycle().take(3_000_000).collect();
+
+    let res = upload(&app, "report.pdf", "application/pdf", &data).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let attachment = json(res).await;
+    assert_eq!(attachment["todo_id"], 1);
+    assert_eq!(attachment["filename"], "report.pdf");
+    assert_eq!(attachment["content_type"], "application/pdf");
+    assert_eq!(attachment["size"], 3_000_000);
+    let sha256 = attachment["sha256"].as_str().unwrap();
+    assert!(dir.path().join("1").join(sha256).is_file());
+
+    let listed = json(send(&app, "GET", "/todos/1/attachments").await).await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0], attachment);
+
+    let res = send(&app, "GET", &format!("/attachments/{}", attachment["id"])).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(
+        res.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"report.pdf\"; filename*=UTF-8''report.pdf"
+    );
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.as_ref(), data.as_slice());
+}
+
+#[tokio::test]
+async fn oversize_uploads_are_rejected_and_not_kept() {
+    let (dir, app) = setup(&[("ATTACHMENT_MAX_BYTES", "1024")]).await;
+
+    let res = upload(&app, "big.png", "image/png", &[7; 1025]).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(files_under(dir.path()).is_empty());
+
+    let res = upload(&app, "small.png", "image/png", &[7; 1024]).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn content_types_outside_the_allowlist_are_rejected() {
+    let (dir, app) = setup(&[]).await;
+    let res = upload(&app, "run.sh", "application/x-sh", b"#!/bin/sh").await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(files_under(dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn path_traversal_in_filenames_is_neutralized() {
+    let (dir, app) = setup(&[]).await;
+    let res = upload(&app, "../../etc/pass\\wd.png", "image/png", b"png").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let attachment = json(res).await;
+    assert_eq!(attachment["filename"], "wd.png");
+
+    let res = upload(&app, "..", "image/png", b"dots").await;
+    assert_eq!(json(res).await["filename"], "attachment");
+
+    for file in files_under(dir.path()) {
+        assert!(file.starts_with(dir.path().to_str().unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn deleting_the_todo_deletes_its_files() {
+    let (dir, app) = setup(&[]).await;
+    let res = upload(&app, "a.png", "image/png", b"first").await;
+    let attachment = json(res).await;
+    upload(&app, "b.png", "image/png", b"second").await;
+    assert_eq!(files_under(dir.path()).len(), 2);
+
+    let res = send(&app, "DELETE", "/todos/1").await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(files_under(dir.path()).is_empty());
+
+    let res = send(&app, "GET", &format!("/attachments/{}", attachment["id"])).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = send(&app, "GET", "/todos/1/attachments").await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn uploads_to_missing_todos_are_not_found() {
+    let (dir, app) = setup(&[]).await;
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos/99/attachments")
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={BOUNDARY}"),
+                )
+                .body(Body::from(multipart("a.png", "image/png", b"x")))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert!(files_under(dir.path()).is_empty());
+}
@@ -0,0 +1,204 @@
+// Route policies: every route in `auth::ROUTES` is tried with every policy
+// and every kind of caller, so a route that ends up in the wrong group (say,
+// an admin route left public) fails here rather than in production.
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    middleware::from_fn_with_state,
+    routing::get,
+    Router,
+};
+use rust_api::{
+    app,
+    auth::{self, Policy, RouteGroup, ROUTES},
+    config::Config,
+    reload::Reloader,
+    test_utils::TestClient,
+    AppState,
+};
+use tower::ServiceExt;
+
+const KEY: &str = "reader-key-1";
+const ADMIN_KEY: &str = "admin-key-1";
+
+fn config(vars: &[(&str, &str)]) -> anyhow::Result<Config> {
+    Config::from_lookup(|key| {
+        vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    })
+}
+
+/// Every group under `policy`, with one key of each kind.
+fn state(policy: &str) -> AppState {
+    let config = config(&[
+        ("API_POLICY", policy),
+        ("ADMIN_POLICY", policy),
+        ("METRICS_POLICY", policy),
+        ("UI_POLICY", policy),
+        ("API_KEYS", KEY),
+        ("ADMIN_API_KEYS", ADMIN_KEY),
+    ])
+    .unwrap();
+    AppState::new_in_memory().with_config(config)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Caller {
+    Anonymous,
+    WrongKey,
+    Key,
+    Admin,
+}
+
+impl Caller {
+    const ALL: [Caller; 4] = [Self::Anonymous, Self::WrongKey, Self::Key, Self::Admin];
+
+    fn request(self, method: Method, uri: &str) -> Request<Body> {
+        let req = Request::builder().method(method).uri(uri);
+        let req = match self {
+            Self::Anonymous => req,
+            Self::WrongKey => req.header(header::AUTHORIZATION, "Bearer not-a-key"),
+            Self::Key => req.header(header::AUTHORIZATION, format!("Bearer {KEY}")),
+            Self::Admin => req.header(header::AUTHORIZATION, format!("Bearer {ADMIN_KEY}")),
+        };
+        req.body(Body::empty()).unwrap()
+    }
+}
+
+/// What `caller` gets from a route under `policy`. The probe method is
+/// `TRACE`, which no route handles, so getting through shows up as `405`.
+fn expected(policy: Policy, caller: Caller) -> StatusCode {
+    match (policy, caller) {
+        (Policy::Public, _) => StatusCode::METHOD_NOT_ALLOWED,
+        (Policy::Disabled, _) => StatusCode::NOT_FOUND,
+        (_, Caller::Anonymous | Caller::WrongKey) => StatusCode::UNAUTHORIZED,
+        (Policy::ApiKey, _) | (Policy::Role(_), Caller::Admin) => {
+            StatusCode::METHOD_NOT_ALLOWED
+        }
+        (Policy::Role(_), Caller::Key) => StatusCode::FORBIDDEN,
+    }
+}
+
+/// The concrete paths a route template is served at.
+fn uris(template: &str, group: RouteGroup) -> Vec<String> {
+    let path = template.replace(":id", "1").replace("*path", "app.js");
+    if group == RouteGroup::Api && template != "/graphql" {
+        vec![format!("/v1{path}"), path]
+    } else {
+        vec![path]
+    }
+}
+
+fn served(template: &str, group: RouteGroup) -> bool {
+    match group {
+        RouteGroup::Ui => cfg!(feature = "ui"),
+        _ if template == "/graphql" => cfg!(feature = "graphql"),
+        _ => true,
+    }
+}
+
+#[tokio::test]
+async fn every_route_enforces_its_groups_policy() {
+    for name in ["public", "api_key", "admin", "disabled"] {
+        let policy: Policy = name.parse().unwrap();
+        let client = TestClient::new(app(state(name)));
+        for &(template, group) in ROUTES.iter().filter(|(t, g)| served(t, *g)) {
+            for uri in uris(template, group) {
+                for caller in Caller::ALL {
+                    let status = client.send(caller.request(Method::TRACE, &uri)).await.status;
+                    let expected = match group {
+                        RouteGroup::Probes | RouteGroup::Session | RouteGroup::Shared => {
+                            StatusCode::METHOD_NOT_ALLOWED
+                        }
+                        _ => expected(policy, caller),
+                    };
+                    assert_eq!(status, expected, "{name}: {caller:?} {uri}");
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn keys_are_accepted_as_bearer_tokens_or_in_x_api_key() {
+    let config = config(&[
+        ("API_POLICY", "api_key"),
+        ("ADMIN_POLICY", "admin"),
+        ("API_KEYS", KEY),
+        ("ADMIN_API_KEYS", ADMIN_KEY),
+    ]);
+    let client = TestClient::new(app(AppState::new_in_memory().with_config(config.unwrap())));
+
+    let res = client.get("/v1/todos").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers[header::WWW_AUTHENTICATE], "Bearer");
+    assert_eq!(res.body["error"], "a valid API key is required");
+
+    let res = client.send(Caller::Key.request(Method::GET, "/v1/todos")).await;
+    assert_eq!(res.status, StatusCode::OK);
+    let req = Request::get("/v1/todos").header("x-api-key", KEY).body(Body::empty()).unwrap();
+    assert_eq!(client.send(req).await.status, StatusCode::OK);
+
+    let res = client.send(Caller::Key.request(Method::GET, "/admin/export")).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = client.send(Caller::Admin.request(Method::GET, "/admin/export")).await;
+    assert_eq!(res.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn defaults_keep_admin_and_ui_off_and_the_rest_public() {
+    let defaults = config(&[]).unwrap();
+    assert_eq!(defaults.api_policy, Policy::Public);
+    assert_eq!(defaults.metrics_policy, Policy::Public);
+    assert_eq!(defaults.admin_policy, Policy::Disabled);
+    assert_eq!(defaults.ui_policy, Policy::Disabled);
+
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    assert_eq!(client.get("/v1/todos").await.status, StatusCode::OK);
+    assert_eq!(client.get("/admin/export").await.status, StatusCode::NOT_FOUND);
+
+    // The older switches still open the routes to everyone.
+    let legacy = config(&[("ENABLE_ADMIN_ENDPOINTS", "true"), ("ENABLE_UI", "true")]).unwrap();
+    assert_eq!(legacy.admin_policy, Policy::Public);
+    assert_eq!(legacy.ui_policy, Policy::Public);
+    let explicit = config(&[("ENABLE_ADMIN_ENDPOINTS", "true"), ("ADMIN_POLICY", "disabled")]);
+    assert_eq!(explicit.unwrap().admin_policy, Policy::Disabled);
+}
+
+#[test]
+fn policies_are_validated() {
+    let err = |vars: &[(&str, &str)]| format!("{:#}", config(vars).unwrap_err());
+
+    assert!(err(&[("METRICS_POLICY", "secret")]).contains("METRICS_POLICY"));
+    assert!(err(&[("API_POLICY", "api_key")]).contains("needs API_KEYS"));
+    assert!(err(&[("ADMIN_POLICY", "admin"), ("API_KEYS", KEY)]).contains("ADMIN_API_KEYS"));
+    assert!(config(&[("API_POLICY", "api_key"), ("ADMIN_API_KEYS", ADMIN_KEY)]).is_ok());
+    assert!(!format!("{:?}", config(&[("API_KEYS", KEY)]).unwrap()).contains(KEY));
+}
+
+#[tokio::test]
+async fn reloaded_keys_apply_to_the_next_request() {
+    let state = state("api_key");
+    let client = TestClient::new(app(state.clone()));
+    let status = |caller: Caller| client.send(caller.request(Method::GET, "/v1/todos"));
+    assert_eq!(status(Caller::Key).await.status, StatusCode::OK);
+
+    let rotated = config(&[("API_POLICY", "api_key"), ("API_KEYS", "reader-key-2")]).unwrap();
+    let report = Reloader::new(state).apply(rotated);
+    assert!(report.applied.contains(&"API_KEYS"), "{report:?}");
+    assert_eq!(status(Caller::Key).await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn routes_missing_from_the_table_are_refused() {
+    let state = AppState::new_in_memory();
+    let router = Router::new()
+        .route("/unlisted", get(|| async { "should not be reachable" }))
+        .layer(from_fn_with_state(state.clone(), auth::authorize))
+        .with_state(state);
+    let res = router
+        .oneshot(Request::get("/unlisted").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,121 @@
+// `PATCH /todos/batch`: every entry is applied on its own and gets its own
+// outcome, in the order sent.
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    models::{BatchResults, BatchStatus, BatchUpdate, CreateTodo, Todo},
+    test_utils::{seed, TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+
+fn etag(res: &TestResponse) -> String {
+    res.headers["etag"].to_str().unwrap().to_string()
+}
+
+async fn client_with(titles: &[&str]) -> (TestClient, Vec<Todo>) {
+    let state = AppState::new_in_memory();
+    let inputs = titles.iter().map(|title| CreateTodo::new(title.parse().unwrap()));
+    let todos = seed(state.service().repo().as_ref(), inputs).await;
+    (TestClient::new(app(state)), todos)
+}
+
+#[tokio::test]
+async fn reports_each_entry_and_keeps_the_valid_ones() {
+    let (client, todos) = client_with(&["a", "b", "c"]).await;
+    let (a, b, c) = (todos[0].id, todos[1].id, todos[2].id);
+    let body = json!([
+        { "id": a, "done": true },
+        { "id": 999, "title": "ghost" },
+        { "id": b, "title": "   " },
+        { "id": c, "title": "renamed", "assignee": "alice" },
+    ]);
+    let res = client.patch_json("/todos/batch", &body).await;
+    assert_eq!(res.status, StatusCode::MULTI_STATUS, "{}", res.body);
+
+    let results = res.json::<BatchResults>().results;
+    let outcomes: Vec<_> = results.iter().map(|result| (result.id, result.status)).collect();
+    assert_eq!(
+        outcomes,
+        [
+            (a, BatchStatus::Ok),
+            (999, BatchStatus::NotFound),
+            (b, BatchStatus::Invalid),
+            (c, BatchStatus::Ok),
+        ]
+    );
+    assert!(results[0].todo.as_ref().unwrap().done);
+    assert!(results[1].todo.is_none() && results[1].error.is_some());
+    assert!(results[2].error.as_deref().unwrap().contains("title"), "{:?}", results[2]);
+    // Failed entries carry no todo, successful ones no error.
+    assert_eq!(res.body["results"][0].get("error"), None);
+    assert_eq!(res.body["results"][2].get("todo"), None);
+
+    let listed: Vec<Todo> = client.get("/todos").await.json();
+    assert!(listed[0].done);
+    assert_eq!(listed[1].title, "b");
+    let renamed = &listed[2];
+    assert_eq!(renamed.title, "renamed");
+    assert_eq!(renamed.assignee.as_deref(), Some("alice"));
+}
+
+#[tokio::test]
+async fn rejects_the_whole_batch_when_its_shape_is_wrong() {
+    let (client, todos) = client_with(&["a"]).await;
+    let id = todos[0].id;
+
+    let repeated = json!([{ "id": id, "done": true }, { "id": id, "title": "twice" }]);
+    let res = client.patch_json("/todos/batch", &repeated).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert!(res.body["error"].as_str().unwrap().contains("more than once"), "{}", res.body);
+
+    let too_many: Vec<_> = (1..=BatchUpdate::MAX_ENTRIES as u64 + 1)
+        .map(|id| json!({ "id": id, "done": true }))
+        .collect();
+    let res = client.patch_json("/todos/batch", &too_many).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let res = client.patch_json("/todos/batch", &json!([])).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    // Nothing was applied.
+    assert!(!client.get(&format!("/todos/{id}")).await.json::<Todo>().done);
+}
+
+#[tokio::test]
+async fn if_match_applies_only_to_the_collection_it_was_fetched_with() {
+    let (client, todos) = client_with(&["a", "b"]).await;
+    let (a, b) = (todos[0].id, todos[1].id);
+    let tag = etag(&client.get("/todos").await);
+
+    // Another write moves the collection on.
+    client.put_json(&format!("/todos/{b}"), &json!({ "title": "b2" })).await;
+
+    let body = json!([{ "id": a, "done": true }]);
+    let stale = axum::http::Request::patch("/todos/batch")
+        .header("content-type", "application/json")
+        .header("if-match", &tag)
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap();
+    let res = client.send(stale).await;
+    assert_eq!(res.status, StatusCode::PRECONDITION_FAILED, "{}", res.body);
+    assert!(res.body["revision"].is_u64(), "{}", res.body);
+    let current = etag(&client.get("/todos").await);
+    assert_eq!(etag(&res), current);
+    assert_eq!(current, format!("\"rev-{}\"", res.body["revision"]));
+    assert!(!client.get(&format!("/todos/{a}")).await.json::<Todo>().done);
+
+    let fresh = axum::http::Request::patch("/todos/batch")
+        .header("content-type", "application/json")
+        .header("if-match", &current)
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap();
+    let res = client.send(fresh).await;
+    assert_eq!(res.status, StatusCode::MULTI_STATUS, "{}", res.body);
+    assert!(client.get(&format!("/todos/{a}")).await.json::<Todo>().done);
+
+    // Without `If-Match`, batches apply whatever the revision.
+    let res = client.patch_json("/todos/batch", &json!([{ "id": b, "done": true }])).await;
+    assert_eq!(res.status, StatusCode::MULTI_STATUS);
+}
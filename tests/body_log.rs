@@ -0,0 +1,107 @@
+// `LOG_BODIES`: text bodies are logged at debug level, truncated and with
+// secrets redacted, without changing what handlers or clients see.
+
+mod common;
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use common::{CapturedEvent, LogCapture};
+use rust_api::{app, config::Config, test_utils::TestClient, AppState};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+fn config(vars: &[(&str, &str)]) -> Config {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Config::from_lookup(|key| vars.get(key).cloned()).unwrap()
+}
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    TestClient::new(app(AppState::new_in_memory().with_config(config(vars))))
+}
+
+fn bodies(logs: &LogCapture, direction: &str) -> Vec<CapturedEvent> {
+    logs.for_target("rust_api::bodies")
+        .into_iter()
+        .filter(|event| event.field("direction") == Some(direction))
+        .collect()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn logs_bodies_with_secrets_redacted() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = client(&[("LOG_BODIES", "true")]);
+
+    let payload = json!({
+        "title": "buy milk",
+        "password": "hunter2",
+        "nested": [{ "Token": "t0k" }],
+    });
+    let res = client.post_json("/todos", &payload).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert_eq!(res.body["title"], "buy milk");
+
+    let requests = bodies(&logs, "request");
+    assert_eq!(requests.len(), 1, "{requests:?}");
+    let body = requests[0].field("body").unwrap();
+    assert!(body.contains("buy milk"), "{body}");
+    assert!(body.contains(r#""password":"***""#), "{body}");
+    assert!(body.contains(r#""Token":"***""#), "{body}");
+    assert_eq!(requests[0].level, tracing::Level::DEBUG);
+
+    let responses = bodies(&logs, "response");
+    assert_eq!(responses.len(), 1, "{responses:?}");
+    assert!(responses[0].field("body").unwrap().contains("buy milk"));
+
+    let logged = format!("{:?}", logs.events());
+    assert!(!logged.contains("hunter2") && !logged.contains("t0k"), "{logged}");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn long_bodies_are_truncated_and_binary_ones_skipped() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = client(&[("LOG_BODIES", "true"), ("LOG_BODY_MAX_BYTES", "16")]);
+
+    let title = "a fairly long title for a todo";
+    let payload = json!({ "title": title });
+    client.post_json("/todos", &payload).await;
+    let requests = bodies(&logs, "request");
+    assert_eq!(requests[0].field("body"), Some(r#"{"title":"a fair"#));
+    assert_eq!(requests[0].field("truncated"), Some("true"));
+    let bytes = payload.to_string().len().to_string();
+    assert_eq!(requests[0].field("bytes"), Some(bytes.as_str()));
+
+    let request = axum::http::Request::get("/todos/1")
+        .header("accept", "application/msgpack")
+        .body(axum::body::Body::empty())
+        .unwrap();
+    assert_eq!(client.send(request).await.status, StatusCode::OK);
+    assert_eq!(bodies(&logs, "response").len(), 1, "only the JSON create response");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn nothing_is_logged_when_off() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = client(&[]);
+
+    let res = client.post_json("/todos", &json!({ "title": "buy milk" })).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    client.get("/todos/1").await;
+    assert!(logs.for_target("rust_api::bodies").is_empty());
+}
+
+#[test]
+fn turning_it_on_warns_at_startup() {
+    assert!(config(&[]).warnings().iter().all(|w| !w.contains("LOG_BODIES")));
+    let warnings = config(&[("LOG_BODIES", "yes")]).warnings();
+    assert!(warnings.iter().any(|w| w.starts_with("LOG_BODIES is on")), "{warnings:?}");
+}
@@ -0,0 +1,134 @@
+// The import budgets: body size, imports per hour, and processing time, each
+// with its own error code, on just the route they wrap.
+
+use std::{collections::HashMap, time::Duration};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::DefaultBodyLimit,
+    http::{header, Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{budgets, config::Config, errors::ErrorCode, AppState, MAX_BODY_BYTES};
+use serde_json::Value;
+use tower::ServiceExt;
+
+const MB: usize = 1024 * 1024;
+
+/// Echoes the body length; `slow` takes its time.
+async fn import(body: Bytes) -> String {
+    if body.as_ref() == b"slow" {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    body.len().to_string()
+}
+
+/// `/import` under the budgets, next to an ordinary route, both behind the
+/// global body limit the app applies.
+fn router(vars: &[(&str, &str)]) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    Router::new()
+        .route("/import", budgets::import(post(import), &state))
+        .route("/other", post(import))
+        .with_state(state)
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+}
+
+struct Reply {
+    status: StatusCode,
+    code: Option<&'static str>,
+    retry_after: Option<String>,
+    body: String,
+}
+
+async fn send(router: &Router, uri: &str, body: Vec<u8>, declare_length: bool) -> Reply {
+    let mut req = Request::post(uri);
+    if declare_length {
+        req = req.header(header::CONTENT_LENGTH, body.len());
+    }
+    let res = router.clone().oneshot(req.body(Body::from(body)).unwrap()).await.unwrap();
+    let status = res.status();
+    let code = res.extensions().get::<ErrorCode>().map(|code| code.0);
+    let retry_after = res
+        .headers()
+        .get(header::RETRY_AFTER)
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = res.into_body().collect().await.unwrap().to_bytes();
+    Reply {
+        status,
+        code,
+        retry_after,
+        body: String::from_utf8(bytes.to_vec()).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn imports_get_their_own_body_limit() {
+    let router = router(&[("IMPORT_MAX_BODY_MB", "3")]);
+
+    // Above the global limit, within the import one.
+    let res = send(&router, "/import", vec![b'x'; 5 * MB / 2], true).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body, (5 * MB / 2).to_string());
+    let res = send(&router, "/other", vec![b'x'; 5 * MB / 2], true).await;
+    assert_eq!(res.status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Refused from the declared length, and while streaming without one.
+    for declare_length in [true, false] {
+        let res = send(&router, "/import", vec![b'x'; 3 * MB + 1], declare_length).await;
+        assert_eq!(res.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(res.code, Some("import_too_large"));
+        let body: Value = serde_json::from_str(&res.body).unwrap();
+        assert_eq!(body["error"], "import is larger than 3 MB");
+    }
+}
+
+#[tokio::test]
+async fn imports_are_limited_per_hour() {
+    let router = router(&[("IMPORT_RATE_PER_HOUR", "2")]);
+
+    for _ in 0..2 {
+        assert_eq!(send(&router, "/import", b"[]".to_vec(), true).await.status, StatusCode::OK);
+    }
+    let res = send(&router, "/import", b"[]".to_vec(), true).await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.code, Some("import_rate_limited"));
+    let retry_after: u64 = res.retry_after.unwrap().parse().unwrap();
+    assert!((3500..=3600).contains(&retry_after), "{retry_after}");
+
+    // Other routes don't share the budget.
+    assert_eq!(send(&router, "/other", b"[]".to_vec(), true).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn slow_imports_time_out() {
+    let router = router(&[("IMPORT_TIMEOUT_MS", "50")]);
+
+    let res = send(&router, "/import", b"slow".to_vec(), true).await;
+    assert_eq!(res.status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(res.code, Some("import_timeout"));
+    assert_eq!(send(&router, "/import", b"fast".to_vec(), true).await.status, StatusCode::OK);
+}
+
+#[test]
+fn import_settings_are_validated() {
+    let config = |vars: &[(&str, &str)]| {
+        Config::from_lookup(|key| {
+            vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+        })
+    };
+    let defaults = config(&[]).unwrap();
+    assert_eq!(defaults.import_max_body_mb, 50);
+    assert_eq!(defaults.import_rate_per_hour, 10);
+    assert_eq!(defaults.import_timeout_ms, 120_000);
+    assert_eq!(defaults.max_concurrent_exports, 2);
+    assert!(config(&[("IMPORT_MAX_BODY_MB", "0")]).is_err());
+    assert!(config(&[("IMPORT_RATE_PER_HOUR", "lots")]).is_err());
+}
@@ -0,0 +1,179 @@
+// Cache-Control, Vary, and ETag behavior for each class of endpoint.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rust_api::{app, config::Config, AppState};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn send(app: &Router, req: Request<Body>) -> axum::response::Response {
+    app.clone().oneshot(req).await.unwrap()
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::builder().uri(uri).body(Body::empty()).unwrap()
+}
+
+async fn create(app: &Router) -> axum::response::Response {
+    send(
+        app,
+        Request::builder()
+            .method("POST")
+            .uri("/todos")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "title": "cache me" }).to_string()))
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn mutations_are_not_stored() {
+    let app = app(AppState::new_in_memory());
+    let res = create(&app).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-store");
+}
+
+#[tokio::test]
+async fn single_todo_is_privately_cacheable() {
+    let app = app(AppState::new_in_memory());
+    create(&app).await;
+
+    let res = send(&app, get("/todos/1")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "private, max-age=30");
+    assert_eq!(res.headers()[header::VARY], "accept, accept-encoding");
+    assert!(res.headers().contains_key(header::ETAG));
+}
+
+#[tokio::test]
+async fn list_must_revalidate() {
+    let app = app(AppState::new_in_memory());
+    let res = send(&app, get("/todos")).await;
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(res.headers()[header::VARY], "accept, accept-encoding");
+}
+
+#[tokio::test]
+async fn missing_todo_is_not_stored() {
+    let app = app(AppState::new_in_memory());
+    let res = send(&app, get("/todos/404")).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-store");
+    assert!(!res.headers().contains_key(header::ETAG));
+}
+
+#[tokio::test]
+async fn matching_etag_yields_not_modified_until_the_list_changes() {
+    let app = app(AppState::new_in_memory());
+    let first = send(&app, get("/todos")).await;
+    let tag = first.headers()[header::ETAG].clone();
+
+    let revalidate = || {
+        Request::builder()
+            .uri("/todos")
+            .header(header::IF_NONE_MATCH, tag.clone())
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = send(&app, revalidate()).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()[header::ETAG], tag);
+
+    create(&app).await;
+    let res = send(&app, revalidate()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_ne!(res.headers()[header::ETAG], tag);
+}
+
+/// Compresses every body, however small, so a single todo has a gzip variant.
+fn compress_everything() -> Router {
+    let config = Config::from_lookup(|key| (key == "COMPRESSION_MIN_BYTES").then(|| "0".into()))
+        .unwrap();
+    app(AppState::new_in_memory().with_config(config))
+}
+
+fn get_todo(accept_encoding: &str, if_none_match: Option<&str>) -> Request<Body> {
+    let mut req = Request::builder()
+        .uri("/todos/1")
+        .header(header::ACCEPT_ENCODING, accept_encoding);
+    if let Some(tag) = if_none_match {
+        req = req.header(header::IF_NONE_MATCH, tag);
+    }
+    req.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn each_encoding_gets_its_own_etag() {
+    let app = compress_everything();
+    create(&app).await;
+
+    let identity = send(&app, get_todo("identity", None)).await;
+    let gzip = send(&app, get_todo("gzip", None)).await;
+    assert!(!identity.headers().contains_key(header::CONTENT_ENCODING));
+    assert_eq!(gzip.headers()[header::CONTENT_ENCODING], "gzip");
+
+    let plain = identity.headers()[header::ETAG].to_str().unwrap();
+    let zipped = gzip.headers()[header::ETAG].to_str().unwrap();
+    assert_eq!(zipped, format!("{}-gzip\"", plain.trim_end_matches('"')));
+    for res in [&identity, &gzip] {
+        assert_eq!(res.headers()[header::VARY], "accept, accept-encoding");
+    }
+}
+
+#[tokio::test]
+async fn either_variant_revalidates_as_not_modified() {
+    let app = compress_everything();
+    create(&app).await;
+    let plain = send(&app, get_todo("identity", None)).await.headers()[header::ETAG].clone();
+    let zipped = send(&app, get_todo("gzip", None)).await.headers()[header::ETAG].clone();
+
+    for (accept_encoding, tag) in [("identity", &plain), ("gzip", &zipped)] {
+        let res = send(&app, get_todo(accept_encoding, Some(tag.to_str().unwrap()))).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "{accept_encoding}");
+        // The 304 names the variant the client holds.
+        assert_eq!(&res.headers()[header::ETAG], tag);
+        assert!(res.headers()[header::VARY].to_str().unwrap().contains("accept-encoding"));
+    }
+
+    // Once the todo changes, neither variant is fresh.
+    let updated = send(
+        &app,
+        Request::builder()
+            .method("PUT")
+            .uri("/todos/1")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "done": true }).to_string()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(updated.status(), StatusCode::OK);
+    for (accept_encoding, tag) in [("identity", &plain), ("gzip", &zipped)] {
+        let res = send(&app, get_todo(accept_encoding, Some(tag.to_str().unwrap()))).await;
+        assert_eq!(res.status(), StatusCode::OK, "{accept_encoding}");
+    }
+}
+
+#[tokio::test]
+async fn a_compressed_variants_tag_still_deletes_its_version() {
+    let app = compress_everything();
+    create(&app).await;
+    let zipped = send(&app, get_todo("gzip", None)).await.headers()[header::ETAG].clone();
+
+    let res = send(
+        &app,
+        Request::builder()
+            .method("DELETE")
+            .uri("/todos/1")
+            .header(header::IF_MATCH, zipped)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+}
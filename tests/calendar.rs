@@ -0,0 +1,159 @@
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn create(app: &Router, body: Value) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+async fn mark_done(app: &Router, id: u64) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/todos/{id}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "done": true }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+/// Fetches the calendar and returns its unfolded content lines.
+async fn calendar(app: &Router, uri: &str) -> Vec<String> {
+    let res = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()[header::CONTENT_TYPE],
+        "text/calendar; charset=utf-8"
+    );
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(text.ends_with("\r\n"));
+    let physical: Vec<&str> = text.trim_end_matches("\r\n").split("\r\n").collect();
+    for line in &physical {
+        assert!(!line.contains('\n'), "bare LF in {line:?}");
+        assert!(line.len() <= 75, "unfolded line {line:?}");
+    }
+    let mut lines: Vec<String> = Vec::new();
+    for line in physical {
+        match line.strip_prefix(' ') {
+            Some(rest) => lines.last_mut().unwrap().push_str(rest),
+            None => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn vtodos(lines: &[String]) -> Vec<&[String]> {
+    let mut found = Vec::new();
+    let mut start = None;
+    for (i, line) in lines.iter().enumerate() {
+        match line.as_str() {
+            "BEGIN:VTODO" => start = Some(i),
+            "END:VTODO" => found.push(&lines[start.take().unwrap()..=i]),
+            _ => {}
+        }
+    }
+    found
+}
+
+fn property<'a>(component: &'a [String], name: &str) -> &'a str {
+    let prefix = format!("{name}:");
+    component
+        .iter()
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+        .unwrap_or_else(|| panic!("missing {name}"))
+}
+
+#[tokio::test]
+async fn empty_store_is_a_valid_empty_calendar() {
+    let app = app(AppState::new_in_memory());
+    let lines = calendar(&app, "/todos/calendar.ics").await;
+    assert_eq!(lines.first().unwrap(), "BEGIN:VCALENDAR");
+    assert_eq!(lines.last().unwrap(), "END:VCALENDAR");
+    assert!(lines.contains(&"VERSION:2.0".to_string()));
+    assert!(lines.iter().any(|line| line.starts_with("PRODID:")));
+    assert!(vtodos(&lines).is_empty());
+}
+
+#[tokio::test]
+async fn todos_with_due_dates_become_vtodos() {
+    let app = app(AppState::new_in_memory());
+    create(&app, json!({ "title": "no deadline" })).await;
+    create(
+        &app,
+        json!({ "title": "file taxes", "due": "2024-04-15T17:00:00Z" }),
+    )
+    .await;
+
+    let lines = calendar(&app, "/todos/calendar.ics").await;
+    let todos = vtodos(&lines);
+    assert_eq!(todos.len(), 1);
+    let todo = todos[0];
+    assert_eq!(property(todo, "UID"), "todo-2@rust-api");
+    assert_eq!(property(todo, "SUMMARY"), "file taxes");
+    assert_eq!(property(todo, "DUE"), "20240415T170000Z");
+    assert_eq!(property(todo, "STATUS"), "NEEDS-ACTION");
+    assert!(property(todo, "DTSTAMP").ends_with('Z'));
+}
+
+#[tokio::test]
+async fn summaries_are_escaped_and_long_lines_folded() {
+    let app = app(AppState::new_in_memory());
+    // Titles can't hold line breaks, so `\n` never needs escaping here.
+    let title = "milk, eggs; bread\\butter then a very long tail that needs folding ✓✓✓";
+    create(&app, json!({ "title": title, "due": "2024-05-01T09:30:00+02:00" })).await;
+
+    let lines = calendar(&app, "/todos/calendar.ics").await;
+    let todo = vtodos(&lines)[0];
+    assert_eq!(
+        property(todo, "SUMMARY"),
+        r"milk\, eggs\; bread\\butter then a very long tail that needs folding ✓✓✓"
+    );
+    assert_eq!(property(todo, "DUE"), "20240501T073000Z");
+}
+
+#[tokio::test]
+async fn completed_todos_are_opt_in() {
+    let app = app(AppState::new_in_memory());
+    create(&app, json!({ "title": "open", "due": "2024-01-01T00:00:00Z" })).await;
+    create(&app, json!({ "title": "closed", "due": "2024-01-02T00:00:00Z" })).await;
+    mark_done(&app, 2).await;
+
+    let lines = calendar(&app, "/todos/calendar.ics").await;
+    let todos = vtodos(&lines);
+    assert_eq!(todos.len(), 1);
+    assert_eq!(property(todos[0], "SUMMARY"), "open");
+
+    let lines = calendar(&app, "/todos/calendar.ics?include_done=true").await;
+    let todos = vtodos(&lines);
+    assert_eq!(todos.len(), 2);
+    assert_eq!(property(todos[1], "STATUS"), "COMPLETED");
+}
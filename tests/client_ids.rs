@@ -0,0 +1,81 @@
+// `PUT /todos/:id` as an upsert for offline clients that pick their own ids,
+// which only happens with `ALLOW_CLIENT_IDS=true`.
+
+use axum::http::{header, StatusCode};
+use rust_api::{app, config::Config, models::Todo, test_utils::TestClient, AppState};
+use serde_json::json;
+
+fn client(allow_client_ids: bool) -> TestClient {
+    let config = Config {
+        allow_client_ids,
+        ..Config::default()
+    };
+    TestClient::new(app(AppState::new_in_memory().with_config(config)))
+}
+
+#[tokio::test]
+async fn put_creates_a_todo_under_the_client_id() {
+    let client = client(true);
+    let body = json!({ "title": "offline", "done": true, "assignee": "alice" });
+    let res = client.put_json("/todos/42", &body).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(res.headers[header::LOCATION], "/todos/42");
+    let created: Todo = res.json();
+    assert_eq!((created.id, created.done), (42, true));
+    assert_eq!(created.assignee.as_deref(), Some("alice"));
+
+    // A retry of the same push replaces it instead of creating another.
+    let retried = client.put_json("/todos/42", &body).await;
+    assert_eq!(retried.status, StatusCode::OK);
+    assert_eq!(client.get("/todos").await.json::<Vec<Todo>>().len(), 1);
+}
+
+#[tokio::test]
+async fn put_replaces_an_existing_todo() {
+    let client = client(true);
+    let created = client.post_json("/todos", &json!({ "title": "draft" })).await;
+    let uri = format!("/todos/{}", created.body["id"]);
+
+    let res = client.put_json(&uri, &json!({ "title": "final", "done": true })).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(!res.headers.contains_key(header::LOCATION));
+    let todo: Todo = client.get(&uri).await.json();
+    assert_eq!((todo.title.as_str(), todo.done), ("final", true));
+}
+
+#[tokio::test]
+async fn server_ids_continue_above_client_ids() {
+    let client = client(true);
+    let first = client.post_json("/todos", &json!({ "title": "server" })).await;
+    assert_eq!(first.body["id"], 1);
+    client.put_json("/todos/7", &json!({ "title": "client" })).await;
+    // Lower ids stay free for clients, and don't move the counter back.
+    client.put_json("/todos/3", &json!({ "title": "client" })).await;
+
+    let next = client.post_json("/todos", &json!({ "title": "server" })).await;
+    assert_eq!(next.status, StatusCode::CREATED);
+    assert_eq!(next.body["id"], 8);
+    let listed: Vec<Todo> = client.get("/todos").await.json();
+    let ids: Vec<u64> = listed.iter().map(|todo| todo.id).collect();
+    assert_eq!(ids, [1, 3, 7, 8]);
+}
+
+#[tokio::test]
+async fn put_needs_a_title_and_a_valid_id_to_create() {
+    let client = client(true);
+    let res = client.put_json("/todos/5", &json!({ "done": true })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = client.put_json("/todos/0", &json!({ "title": "zero" })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.body);
+    let res = client.put_json("/todos/5", &json!({ "title": " " })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert!(client.get("/todos").await.json::<Vec<Todo>>().is_empty());
+}
+
+#[tokio::test]
+async fn without_the_flag_put_to_a_missing_id_is_404() {
+    let client = client(false);
+    let res = client.put_json("/todos/42", &json!({ "title": "offline" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert!(client.get("/todos").await.json::<Vec<Todo>>().is_empty());
+}
@@ -0,0 +1,117 @@
+// The injected clock: with a `MockClock` every timestamp the service stamps
+// or compares comes from the test, and moving time forward is a method call
+// rather than a sleep.
+
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::{FutureExt, StreamExt};
+use rust_api::{
+    app,
+    config::Config,
+    events::TodoEvent,
+    models::{CreateTodo, Todo},
+    reminders,
+    test_utils::{MockClock, TestClient},
+    timestamps, AppState,
+};
+use serde_json::json;
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap()
+}
+
+fn mocked() -> (Arc<MockClock>, AppState) {
+    let clock = Arc::new(MockClock::new(start()));
+    let state = AppState::in_memory_with_clock(clock.clone());
+    (clock, state)
+}
+
+#[tokio::test]
+async fn todos_are_stamped_with_the_injected_time() {
+    let (clock, state) = mocked();
+    let client = TestClient::new(app(state));
+
+    let res = client.post_json("/v1/todos", &json!({ "title": "ship it" })).await;
+    assert_eq!(res.body["created_at"], "2024-05-01T09:00:00.000Z");
+    assert_eq!(res.body["updated_at"], "2024-05-01T09:00:00.000Z");
+
+    clock.advance(Duration::minutes(90) + Duration::milliseconds(250));
+    let res = client.put_json("/v1/todos/1", &json!({ "done": true })).await;
+    assert_eq!(res.body["created_at"], "2024-05-01T09:00:00.000Z");
+    assert_eq!(res.body["updated_at"], "2024-05-01T10:30:00.250Z");
+    assert_eq!(res.body["done_at"], "2024-05-01T10:30:00.250Z");
+}
+
+#[tokio::test]
+async fn the_default_time_series_window_ends_at_the_injected_now() {
+    let (clock, state) = mocked();
+    let client = TestClient::new(app(state));
+    client.post_json("/v1/todos", &json!({ "title": "ship it" })).await;
+
+    // The window ends before `now`, so step past the creation time.
+    clock.advance(Duration::minutes(1));
+    let res = client.get("/v1/todos/stats/timeseries").await;
+    let points = res.body.as_array().unwrap();
+    assert_eq!(points.last().unwrap()["bucket_start"], "2024-05-01T00:00:00.000Z");
+    assert_eq!(points.last().unwrap()["created"], 1);
+
+    clock.set(Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap());
+    let res = client.get("/v1/todos/stats/timeseries").await;
+    let points = res.body.as_array().unwrap();
+    assert_eq!(points.last().unwrap()["bucket_start"], "2024-07-01T00:00:00.000Z");
+    assert!(points.iter().all(|point| point["created"] == 0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn reminders_fire_when_the_clock_passes_the_due_date() {
+    let (clock, state) = mocked();
+    let interval = |key: &str| (key == "REMINDER_INTERVAL_SECS").then(|| "60".to_string());
+    let config = Config::from_lookup(interval);
+    let state = state.with_config(config.unwrap());
+    let input = CreateTodo {
+        due: Some(start() + Duration::hours(1)),
+        ..CreateTodo::new("file taxes".parse().unwrap())
+    };
+    let id = state.service().repo().create(input).await.unwrap().id;
+    let mut events = state.events().subscribe();
+
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let scheduler = tokio::spawn(reminders::run(state.clone(), stopped.map(drop)));
+
+    // A few ticks go by, but the mock clock hasn't reached the due date.
+    tokio::time::sleep(StdDuration::from_secs(300)).await;
+    assert!(events.next().now_or_never().is_none());
+
+    clock.advance(Duration::hours(1));
+    tokio::time::sleep(StdDuration::from_secs(60)).await;
+    match events.next().await {
+        Some(TodoEvent::Reminder { todo }) => {
+            assert_eq!(todo.id, id);
+            assert_eq!(todo.reminded_at, Some(start() + Duration::hours(1)));
+        }
+        other => panic!("expected a reminder, got {other:?}"),
+    }
+
+    stop.send(()).unwrap();
+    scheduler.await.unwrap();
+}
+
+#[test]
+fn timestamps_are_written_in_utc_with_milliseconds() {
+    let todo: Todo = serde_json::from_value(json!({
+        "id": 1,
+        "title": "ship it",
+        "done": false,
+        "created_at": "2024-05-01T11:00:00+02:00",
+        "updated_at": "2024-05-01T09:00:00.123456Z",
+        "due": "2024-05-02T09:00:00Z",
+    }))
+    .unwrap();
+
+    let written = serde_json::to_value(&todo).unwrap();
+    assert_eq!(written["created_at"], "2024-05-01T09:00:00.000Z");
+    assert_eq!(written["updated_at"], "2024-05-01T09:00:00.123Z");
+    assert_eq!(written["due"], "2024-05-02T09:00:00.000Z");
+    assert_eq!(timestamps::format(&start()), "2024-05-01T09:00:00.000Z");
+}
@@ -0,0 +1,134 @@
+// `COALESCE_LISTS`: identical list pages requested at the same time share
+// one repository read, its result, and its errors.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::future::join_all;
+use rust_api::{
+    app,
+    config::Config,
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::TodoRepo,
+    test_utils::TestClient,
+    AppState,
+};
+
+/// Lists take a while, so concurrent requests overlap, and are counted.
+/// With `fail` set they fail instead; with `panic` set the next one panics.
+#[derive(Default)]
+struct SlowRepo {
+    lists: AtomicUsize,
+    fail: AtomicBool,
+    panic: AtomicBool,
+}
+
+#[async_trait]
+impl TodoRepo for SlowRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.lists.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if self.panic.swap(false, Ordering::SeqCst) {
+            panic!("list blew up");
+        }
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(AppError::Unavailable);
+        }
+        Ok(Vec::new())
+    }
+    async fn create(&self, _input: CreateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn get(&self, _id: u64) -> Result<Todo, AppError> {
+        Err(AppError::NotFound)
+    }
+    async fn update(&self, _id: u64, _input: UpdateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn delete(&self, _id: u64) -> Result<(), AppError> {
+        Err(AppError::Internal)
+    }
+}
+
+fn client(vars: &[(&str, &str)]) -> (TestClient, Arc<SlowRepo>) {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let repo = Arc::new(SlowRepo::default());
+    let state = AppState::with_repo(repo.clone()).with_config(config);
+    (TestClient::new(app(state)), repo)
+}
+
+/// The statuses of 50 identical page requests sent at once.
+async fn herd(client: &TestClient) -> Vec<StatusCode> {
+    let requests = (0..50).map(|_| client.get("/v1/todos?limit=10&sort=title"));
+    join_all(requests).await.into_iter().map(|res| res.status).collect()
+}
+
+#[tokio::test]
+async fn identical_concurrent_lists_read_the_repo_once() {
+    let (client, repo) = client(&[]);
+
+    let statuses = herd(&client).await;
+    assert!(statuses.iter().all(|status| *status == StatusCode::OK), "{statuses:?}");
+    assert_eq!(repo.lists.load(Ordering::SeqCst), 1);
+
+    // Nothing is kept once the read is done.
+    client.get("/v1/todos?limit=10&sort=title").await;
+    assert_eq!(repo.lists.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn a_failed_read_fails_every_waiter() {
+    let (client, repo) = client(&[]);
+    repo.fail.store(true, Ordering::SeqCst);
+
+    let statuses = herd(&client).await;
+    assert!(
+        statuses.iter().all(|status| *status == StatusCode::SERVICE_UNAVAILABLE),
+        "{statuses:?}"
+    );
+    assert_eq!(repo.lists.load(Ordering::SeqCst), 1);
+
+    // The failure isn't remembered either.
+    repo.fail.store(false, Ordering::SeqCst);
+    let res = client.get("/v1/todos?limit=10&sort=title").await;
+    assert_eq!(res.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_panicking_read_hands_over_to_a_waiter() {
+    let (client, repo) = client(&[]);
+    repo.panic.store(true, Ordering::SeqCst);
+
+    // Each on its own task, as the server runs them, so the panic only
+    // takes down one.
+    let requests = (0..50).map(|_| {
+        let client = client.clone();
+        tokio::spawn(async move { client.get("/v1/todos?limit=10&sort=title").await.status })
+    });
+    let results = join_all(requests).await;
+    let panicked = results.iter().filter(|result| result.is_err()).count();
+    assert_eq!(panicked, 1);
+    assert!(results.iter().flatten().all(|status| *status == StatusCode::OK));
+    assert_eq!(repo.lists.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn coalescing_can_be_turned_off() {
+    let (client, repo) = client(&[("COALESCE_LISTS", "false")]);
+
+    herd(&client).await;
+    assert_eq!(repo.lists.load(Ordering::SeqCst), 50);
+}
@@ -0,0 +1,80 @@
+// `?collation=`: `sort=title` orders accented and mixed-case titles the way
+// people read them, under every collation, across page boundaries too.
+
+use axum::http::StatusCode;
+use rust_api::{app, test_utils::TestClient, AppState};
+use serde_json::{json, Value};
+
+const TITLES: [&str; 7] = ["Zebra", "Öl", "apple", "banana", "Äpfel", "Ost", "Apfel"];
+
+/// Where every collation puts [`TITLES`]: accents and case don't split
+/// letters apart, and the unaccented title of a pair comes first.
+const SORTED: [&str; 7] = ["Apfel", "Äpfel", "apple", "banana", "Öl", "Ost", "Zebra"];
+
+async fn client() -> TestClient {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    for title in TITLES {
+        let res = client
+            .post_json("/v1/todos", &json!({ "title": title }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+    }
+    client
+}
+
+fn titles(body: &Value) -> Vec<&str> {
+    body.as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn titles_sort_by_letter_not_code_point() {
+    let client = client().await;
+
+    let res = client.get("/v1/todos?sort=title").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(titles(&res.body), SORTED);
+
+    for collation in ["case_insensitive", "de", "en"] {
+        let res = client
+            .get(&format!("/v1/todos?sort=title&collation={collation}"))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(titles(&res.body), SORTED, "{collation}");
+    }
+
+    let res = client
+        .get("/v1/todos?sort=title&order=desc&collation=de")
+        .await;
+    let mut reversed = SORTED;
+    reversed.reverse();
+    assert_eq!(titles(&res.body), reversed);
+}
+
+#[tokio::test]
+async fn pages_follow_the_collation() {
+    let client = client().await;
+
+    for collation in ["case_insensitive", "de", "en"] {
+        let mut listed = Vec::new();
+        for offset in [0, 3, 6] {
+            let uri = format!("/v1/todos?sort=title&collation={collation}&limit=3&offset={offset}");
+            let res = client.get(&uri).await;
+            assert_eq!(res.status, StatusCode::OK);
+            assert_eq!(res.headers["x-total-count"], "7");
+            listed.extend(titles(&res.body).into_iter().map(str::to_string));
+        }
+        assert_eq!(listed, SORTED, "{collation}");
+    }
+}
+
+#[tokio::test]
+async fn unknown_collations_are_rejected() {
+    let client = client().await;
+
+    let res = client.get("/v1/todos?sort=title&collation=klingon").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
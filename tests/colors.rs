@@ -0,0 +1,58 @@
+// `color`: a label from a fixed palette that UIs group todos by.
+
+use axum::http::StatusCode;
+use rust_api::{app, models::Color, test_utils::TestClient, AppState};
+use serde_json::{json, Value};
+
+fn ids(todos: &Value) -> Vec<u64> {
+    todos
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn creates_filters_and_clears_colors() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let res = client
+        .post_json("/todos", &json!({ "title": "pay rent", "color": "red" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert_eq!(res.body["color"], "red");
+    client.post_json("/todos", &json!({ "title": "water plants", "color": "green" })).await;
+    client.post_json("/todos", &json!({ "title": "call mom" })).await;
+
+    assert_eq!(ids(&client.get("/todos?color=red").await.body), [1]);
+    assert_eq!(ids(&client.get("/todos?color=green").await.body), [2]);
+    assert!(ids(&client.get("/todos?color=blue").await.body).is_empty());
+
+    // Absent leaves the color alone; null clears it.
+    let res = client.put_json("/todos/1", &json!({ "done": true })).await;
+    assert_eq!(res.body["color"], "red");
+    let res = client.put_json("/todos/1", &json!({ "color": null })).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.get("color").is_none());
+    assert!(ids(&client.get("/todos?color=red").await.body).is_empty());
+}
+
+#[tokio::test]
+async fn unknown_colors_list_the_palette() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let palette = "red, orange, yellow, green, blue, purple, pink, gray";
+    assert_eq!(Color::ALL.map(Color::as_str).join(", "), palette);
+
+    let res = client
+        .post_json("/todos", &json!({ "title": "paint", "color": "teal" }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let error = res.body["error"].as_str().unwrap();
+    let expected = format!("unknown color `teal`, expected one of: {palette}");
+    assert!(error.contains(&expected), "{error}");
+
+    let res = client.get("/todos?color=Red").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let error = res.body["error"].as_str().unwrap();
+    assert!(error.contains(palette), "{error}");
+}
@@ -0,0 +1,80 @@
+// Helpers shared by the integration tests. Each test crate pulls this in with
+// `mod common;`, so not every helper is used everywhere.
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// One recorded `tracing` event with its fields rendered as strings.
+#[derive(Clone, Debug)]
+pub struct CapturedEvent {
+    pub level: Level,
+    pub target: String,
+    pub fields: BTreeMap<String, String>,
+    /// Name of the span the event was emitted in, if any.
+    pub span: Option<String>,
+}
+
+impl CapturedEvent {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+/// A `tracing` layer that stores every event so tests can assert on them.
+///
+/// Install with `tracing::subscriber::set_default` inside a
+/// `#[tokio::test(flavor = "current_thread")]` so all events land on the
+/// thread that owns the guard.
+#[derive(Clone, Default)]
+pub struct LogCapture(Arc<Mutex<Vec<CapturedEvent>>>);
+
+impl LogCapture {
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Events emitted under `target`.
+    pub fn for_target(&self, target: &str) -> Vec<CapturedEvent> {
+        self.events()
+            .into_iter()
+            .filter(|event| event.target == target)
+            .collect()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LogCapture {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldRecorder::default();
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(CapturedEvent {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            fields: fields.0,
+            span: ctx.event_span(event).map(|span| span.name().to_string()),
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldRecorder(BTreeMap<String, String>);
+
+impl Visit for FieldRecorder {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
@@ -0,0 +1,82 @@
+// Response compression follows the client's Accept-Encoding, skips small
+// bodies, and can be switched off entirely.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rust_api::{app, config::Config, models::CreateTodo, AppState};
+use tower::ServiceExt;
+
+async fn seeded(vars: &[(&str, &str)]) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    for i in 0..100 {
+        state
+            .service().repo()
+            .create(CreateTodo::new(format!("todo number {i}").parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    app(state)
+}
+
+/// Returns the `Content-Encoding` of the response, if any.
+async fn encoding(app: &Router, uri: &str, accept_encoding: &str) -> Option<String> {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn large_lists_use_the_requested_encoding() {
+    let app = seeded(&[]).await;
+    for algorithm in ["gzip", "br", "zstd"] {
+        assert_eq!(
+            encoding(&app, "/todos", algorithm).await.as_deref(),
+            Some(algorithm)
+        );
+    }
+    assert_eq!(encoding(&app, "/todos", "identity").await, None);
+}
+
+#[tokio::test]
+async fn small_responses_stay_identity() {
+    let app = seeded(&[]).await;
+    assert_eq!(encoding(&app, "/health", "gzip, br, zstd").await, None);
+}
+
+#[tokio::test]
+async fn algorithms_can_be_restricted() {
+    let app = seeded(&[("COMPRESSION_ALGORITHMS", "gzip")]).await;
+    assert_eq!(encoding(&app, "/todos", "br").await, None);
+    assert_eq!(
+        encoding(&app, "/todos", "br, gzip;q=0.5").await.as_deref(),
+        Some("gzip")
+    );
+}
+
+#[tokio::test]
+async fn compression_can_be_disabled() {
+    let app = seeded(&[("COMPRESSION_ENABLED", "false")]).await;
+    assert_eq!(encoding(&app, "/todos", "gzip, br, zstd").await, None);
+}
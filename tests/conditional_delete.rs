@@ -0,0 +1,83 @@
+// `DELETE /todos/:id` with `If-Match`: a todo is deleted only while it is at
+// the version the client fetched, and unconditionally without the header.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_api::{
+    app,
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+
+fn etag(res: &TestResponse) -> String {
+    res.headers[header::ETAG].to_str().unwrap().to_string()
+}
+
+async fn delete_if_match(client: &TestClient, id: u64, tag: &str) -> TestResponse {
+    let request = Request::delete(format!("/v1/todos/{id}"))
+        .header(header::IF_MATCH, tag)
+        .body(Body::empty())
+        .unwrap();
+    client.send(request).await
+}
+
+async fn client_with_todo() -> (TestClient, u64) {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let res = client.post_json("/v1/todos", &json!({ "title": "water the plants" })).await;
+    (client, res.body["id"].as_u64().unwrap())
+}
+
+#[tokio::test]
+async fn a_stale_version_leaves_the_todo_intact() {
+    let (client, id) = client_with_todo().await;
+    let stale = etag(&client.get(&format!("/v1/todos/{id}")).await);
+
+    // Someone else edits it in the meantime.
+    client.put_json(&format!("/v1/todos/{id}"), &json!({ "done": true })).await;
+
+    let res = delete_if_match(&client, id, &stale).await;
+    assert_eq!(res.status, StatusCode::PRECONDITION_FAILED, "{}", res.body);
+    let current = etag(&client.get(&format!("/v1/todos/{id}")).await);
+    assert_ne!(current, stale);
+    assert_eq!(etag(&res), current);
+    assert_eq!(current, format!("\"{}\"", res.body["version"]));
+    assert_eq!(client.get(&format!("/v1/todos/{id}")).await.status, StatusCode::OK);
+
+    // Weak, malformed, and collection tags name no version.
+    let revision = etag(&client.get("/v1/todos").await);
+    for tag in [format!("W/{current}"), "soon".to_string(), revision] {
+        let res = delete_if_match(&client, id, &tag).await;
+        assert_eq!(res.status, StatusCode::PRECONDITION_FAILED, "{tag}");
+    }
+}
+
+#[tokio::test]
+async fn the_current_version_deletes() {
+    let (client, id) = client_with_todo().await;
+    client.put_json(&format!("/v1/todos/{id}"), &json!({ "done": true })).await;
+    let current = etag(&client.get(&format!("/v1/todos/{id}")).await);
+
+    let res = delete_if_match(&client, id, &current).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.body);
+    assert_eq!(client.get(&format!("/v1/todos/{id}")).await.status, StatusCode::NOT_FOUND);
+
+    // Gone is gone, whatever version was asked for.
+    let res = delete_if_match(&client, id, &current).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn without_if_match_deletes_unconditionally() {
+    let (client, id) = client_with_todo().await;
+    client.put_json(&format!("/v1/todos/{id}"), &json!({ "done": true })).await;
+    let res = client.delete(&format!("/v1/todos/{id}")).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+
+    // `*` matches any version.
+    let (client, id) = client_with_todo().await;
+    let res = delete_if_match(&client, id, "*").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+}
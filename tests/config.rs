@@ -0,0 +1,101 @@
+// Configuration tests feed values through `Config::from_lookup` instead of
+// mutating the real process environment, which would race with other tests.
+
+use std::collections::HashMap;
+
+use rust_api::{auth::Policy, config::Config};
+
+fn load(vars: &[(&str, &str)]) -> anyhow::Result<Config> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Config::from_lookup(|key| vars.get(key).cloned())
+}
+
+fn error_text(vars: &[(&str, &str)]) -> String {
+    format!("{:#}", load(vars).expect_err("config should be rejected"))
+}
+
+#[test]
+fn defaults_load_cleanly() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.server_addr.port(), 8080);
+    assert_eq!(config.admin_policy, Policy::Disabled);
+    assert!(config.warnings().is_empty());
+}
+
+#[test]
+fn rejects_misspelled_log_level() {
+    let err = error_text(&[("RUST_LOG", "tower_http=info,rust_api=infoo")]);
+    assert!(err.contains("RUST_LOG"), "{err}");
+    assert!(err.contains("rust_api=infoo"), "{err}");
+}
+
+#[test]
+fn rejects_out_of_range_ports() {
+    for port in ["0", "65536", "-1", "http"] {
+        let err = error_text(&[("PORT", port)]);
+        assert!(err.contains("PORT"), "{err}");
+        assert!(err.contains("1 and 65535"), "{err}");
+    }
+}
+
+#[test]
+fn rejects_non_ip_host() {
+    let err = error_text(&[("HOST", "localhost")]);
+    assert!(err.contains("HOST"), "{err}");
+}
+
+#[test]
+fn rejects_non_boolean_flag() {
+    let err = error_text(&[("ENABLE_ADMIN_ENDPOINTS", "maybe")]);
+    assert!(err.contains("ENABLE_ADMIN_ENDPOINTS"), "{err}");
+}
+
+#[test]
+fn warns_when_admin_endpoints_listen_everywhere() {
+    let config = load(&[("ENABLE_ADMIN_ENDPOINTS", "true")]).unwrap();
+    let warnings = config.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("ENABLE_ADMIN_ENDPOINTS"));
+
+    let local = load(&[("ENABLE_ADMIN_ENDPOINTS", "true"), ("HOST", "127.0.0.1")]).unwrap();
+    assert!(local.warnings().is_empty());
+}
+
+#[test]
+fn debug_output_redacts_secrets() {
+    let config = load(&[("JWT_SECRET", "hunter2-but-longer"), ("PORT", "9191")]).unwrap();
+    let debug = format!("{config:?}");
+
+    assert!(!debug.contains("hunter2-but-longer"), "{debug}");
+    assert!(debug.contains("***"), "{debug}");
+    assert!(debug.contains("9191"), "{debug}");
+    assert_eq!(
+        config.jwt_secret.as_ref().map(|s| s.expose().as_str()),
+        Some("hunter2-but-longer")
+    );
+}
+
+#[test]
+fn parses_compression_settings() {
+    use rust_api::config::CompressionAlgorithm;
+    use tower_http::CompressionLevel;
+
+    let config = load(&[
+        ("COMPRESSION_ALGORITHMS", "zstd, GZIP"),
+        ("COMPRESSION_LEVEL", "fastest"),
+    ])
+    .unwrap();
+    assert_eq!(
+        config.compression_algorithms,
+        [CompressionAlgorithm::Zstd, CompressionAlgorithm::Gzip]
+    );
+    assert_eq!(config.compression_level, CompressionLevel::Fastest);
+
+    let err = error_text(&[("COMPRESSION_ALGORITHMS", "gzip,lz4")]);
+    assert!(err.contains("`lz4`"), "{err}");
+    let err = error_text(&[("COMPRESSION_LEVEL", "max")]);
+    assert!(err.contains("COMPRESSION_LEVEL"), "{err}");
+}
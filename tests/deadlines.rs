@@ -0,0 +1,149 @@
+// `X-Request-Deadline`: requests are abandoned with `504` once the caller's
+// budget (or `REQUEST_TIMEOUT_MS`) runs out, and the repository can see it.
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use axum::http::{Request, StatusCode};
+use chrono::Utc;
+use common::LogCapture;
+use rust_api::{
+    app,
+    config::Config,
+    deadline::{self, Deadline},
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::TodoRepo,
+    test_utils::TestClient,
+    AppState,
+};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Reads take `delay` and find nothing, remembering the deadline they ran
+/// under.
+#[derive(Default)]
+struct SlowRepo {
+    delay: Duration,
+    seen: Mutex<Vec<Option<Deadline>>>,
+}
+
+#[async_trait]
+impl TodoRepo for SlowRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.seen.lock().unwrap().push(deadline::current());
+        tokio::time::sleep(self.delay).await;
+        Ok(Vec::new())
+    }
+    async fn create(&self, _input: CreateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn get(&self, _id: u64) -> Result<Todo, AppError> {
+        self.seen.lock().unwrap().push(deadline::current());
+        tokio::time::sleep(self.delay).await;
+        Err(AppError::NotFound)
+    }
+    async fn update(&self, _id: u64, _input: UpdateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn delete(&self, _id: u64) -> Result<(), AppError> {
+        Err(AppError::Internal)
+    }
+}
+
+fn client(delay_ms: u64, vars: &[(&str, &str)]) -> (TestClient, Arc<SlowRepo>) {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let repo = Arc::new(SlowRepo {
+        delay: Duration::from_millis(delay_ms),
+        ..Default::default()
+    });
+    let state = AppState::with_repo(repo.clone()).with_config(config);
+    (TestClient::new(app(state)), repo)
+}
+
+fn with_deadline(uri: &str, deadline: &str) -> Request<axum::body::Body> {
+    Request::get(uri)
+        .header(deadline::HEADER, deadline)
+        .body(axum::body::Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn a_generous_deadline_succeeds_and_reaches_the_repo() {
+    let (client, repo) = client(10, &[]);
+
+    let res = client.send(with_deadline("/todos", "5000")).await;
+    assert_eq!(res.status, StatusCode::OK);
+    let at = (Utc::now() + chrono::Duration::seconds(5)).to_rfc3339();
+    let res = client.send(with_deadline("/todos/1", &at)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let seen = repo.seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    for deadline in seen.iter() {
+        let remaining = deadline.expect("repo ran without a deadline").remaining();
+        assert!(remaining > Duration::from_secs(4) && remaining <= Duration::from_secs(5));
+    }
+}
+
+#[tokio::test]
+async fn an_expired_deadline_returns_504() {
+    let (client, _) = client(200, &[]);
+
+    let res = client.send(with_deadline("/todos/1", "1")).await;
+    assert_eq!(res.status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(res.body["error"], "deadline exceeded");
+
+    let res = client.send(with_deadline("/todos/1?envelope=true", "1")).await;
+    assert_eq!(res.body["error"]["code"], "deadline_exceeded");
+
+    // Already in the past when it arrives.
+    let res = client.send(with_deadline("/todos", "2000-01-01T00:00:00Z")).await;
+    assert_eq!(res.status, StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn the_global_timeout_caps_the_callers_deadline() {
+    let (client, repo) = client(200, &[("REQUEST_TIMEOUT_MS", "20")]);
+
+    assert_eq!(client.get("/todos").await.status, StatusCode::GATEWAY_TIMEOUT);
+    let res = client.send(with_deadline("/todos", "60000")).await;
+    assert_eq!(res.status, StatusCode::GATEWAY_TIMEOUT);
+    assert!(repo.seen.lock().unwrap().iter().all(Option::is_some));
+}
+
+#[tokio::test]
+async fn no_deadline_without_header_or_timeout() {
+    let (client, repo) = client(0, &[]);
+    assert_eq!(client.get("/todos").await.status, StatusCode::OK);
+    assert_eq!(*repo.seen.lock().unwrap(), [None]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_malformed_header_is_ignored_with_a_warning() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let (client, repo) = client(0, &[]);
+
+    let res = client.send(with_deadline("/todos", "soon")).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(*repo.seen.lock().unwrap(), [None]);
+
+    let warnings: Vec<_> = logs
+        .events()
+        .into_iter()
+        .filter(|event| event.field("message") == Some("ignoring malformed X-Request-Deadline"))
+        .collect();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].field("reason").unwrap().contains("soon"));
+}
@@ -0,0 +1,95 @@
+// Compressed request bodies: gzip and deflate are inflated before the
+// extractors run, and the body limit applies to the inflated size.
+
+use std::io::Write;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, models::Todo, AppState, MAX_BODY_BYTES};
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// HTTP `deflate` is the zlib format (RFC 9110), not raw DEFLATE.
+fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+async fn post(app: &Router, encoding: &str, body: Vec<u8>) -> (StatusCode, Vec<u8>) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_ENCODING, encoding)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    (status, res.into_body().collect().await.unwrap().to_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn gzip_and_deflate_bodies_are_decompressed() {
+    let app = app(AppState::new_in_memory());
+
+    let (status, body) = post(&app, "gzip", gzip(br#"{"title":"zipped"}"#)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let created: Todo = serde_json::from_slice(&body).unwrap();
+    assert_eq!(created.title, "zipped");
+
+    let (status, body) = post(&app, "deflate", deflate(br#"{"title":"deflated"}"#)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let created: Todo = serde_json::from_slice(&body).unwrap();
+    assert_eq!(created.title, "deflated");
+}
+
+#[tokio::test]
+async fn limit_applies_to_the_decompressed_size() {
+    let app = app(AppState::new_in_memory());
+    // A few kilobytes on the wire, well over the limit once inflated.
+    let title = "a".repeat(MAX_BODY_BYTES);
+    let payload = gzip(format!(r#"{{"title":"{title}"}}"#).as_bytes());
+    assert!(payload.len() < MAX_BODY_BYTES / 100);
+
+    let (status, body) = post(&app, "gzip", payload).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["error"], "payload too large");
+}
+
+#[tokio::test]
+async fn unsupported_encodings_get_a_json_415() {
+    let app = app(AppState::new_in_memory());
+
+    let (status, body) = post(&app, "br", br#"{"title":"nope"}"#.to_vec()).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("`br`"));
+}
+
+#[tokio::test]
+async fn corrupt_gzip_is_a_client_error() {
+    let app = app(AppState::new_in_memory());
+    let (status, _) = post(&app, "gzip", b"definitely not gzip".to_vec()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
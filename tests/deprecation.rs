@@ -0,0 +1,125 @@
+// Routes outside `/v1` keep working but carry `Deprecation`/`Sunset`, are
+// counted per route and client, and warn once per client per window.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_api::{
+    app,
+    config::Config,
+    deprecation::{Tracker, MAX_CLIENTS, WARN_EVERY},
+    state::in_memory_repo,
+    test_utils::TestClient,
+    AppState,
+};
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    let config = Config::from_lookup(|key| {
+        vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    })
+    .unwrap();
+    TestClient::new(app(AppState::with_repo(in_memory_repo()).with_config(config)))
+}
+
+fn dated() -> TestClient {
+    client(&[
+        ("LEGACY_DEPRECATION_DATE", "2026-01-01"),
+        ("LEGACY_SUNSET_DATE", "2026-12-31T12:00:00Z"),
+    ])
+}
+
+async fn get_as(client: &TestClient, uri: &str, user_agent: &str) -> StatusCode {
+    let req = Request::get(uri)
+        .header(header::USER_AGENT, user_agent)
+        .body(Body::empty())
+        .unwrap();
+    client.send(req).await.status
+}
+
+async fn scrape(client: &TestClient) -> String {
+    client.get("/metrics").await.body.as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn legacy_routes_carry_deprecation_and_sunset() {
+    let client = dated();
+
+    let res = client.post_json("/todos", &serde_json::json!({"title": "old"})).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert_eq!(res.headers["deprecation"], "@1767225600");
+    assert_eq!(res.headers["sunset"], "Thu, 31 Dec 2026 12:00:00 GMT");
+
+    let res = client.get("/todos/1").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.headers.contains_key("deprecation"));
+}
+
+#[tokio::test]
+async fn versioned_routes_are_not_deprecated() {
+    let client = dated();
+
+    let res = client.post_json("/v1/todos", &serde_json::json!({"title": "new"})).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert!(!res.headers.contains_key("deprecation"));
+    assert!(!res.headers.contains_key("sunset"));
+    assert!(!client.get("/health").await.headers.contains_key("deprecation"));
+    assert!(!scrape(&client).await.contains("deprecated_requests_total{"));
+    // Both prefixes share the one repository.
+    assert_eq!(client.get("/todos/1").await.body["title"], "new");
+}
+
+#[tokio::test]
+async fn headers_wait_for_the_dates() {
+    let res = client(&[]).get("/todos").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(!res.headers.contains_key("deprecation"));
+    assert!(!res.headers.contains_key("sunset"));
+}
+
+#[tokio::test]
+async fn requests_are_counted_per_route_and_client() {
+    let client = client(&[]);
+    get_as(&client, "/todos", "todo-ios/3.2 (iPhone)").await;
+    get_as(&client, "/todos", "todo-ios/3.3").await;
+    get_as(&client, "/todos/7", "curl/8.5.0").await;
+    get_as(&client, "/v1/todos", "curl/8.5.0").await;
+
+    let scrape = scrape(&client).await;
+    for line in [
+        r#"deprecated_requests_total{client="todo-ios",route="/todos"} 2"#,
+        r#"deprecated_requests_total{client="curl",route="/todos/:id"} 1"#,
+    ] {
+        assert!(scrape.contains(line), "{line} missing from\n{scrape}");
+    }
+    assert_eq!(scrape.matches("deprecated_requests_total{").count(), 2);
+}
+
+#[test]
+fn each_client_is_warned_once_per_window() {
+    let tracker = Tracker::default();
+    let start = Instant::now();
+
+    assert!(tracker.should_warn("curl", start));
+    assert!(!tracker.should_warn("curl", start + Duration::from_secs(1)));
+    assert!(tracker.should_warn("todo-ios", start + Duration::from_secs(1)));
+    assert!(!tracker.should_warn("curl", start + WARN_EVERY - Duration::from_secs(1)));
+    assert!(tracker.should_warn("curl", start + WARN_EVERY));
+}
+
+#[test]
+fn client_labels_are_bounded() {
+    let tracker = Tracker::default();
+    assert_eq!(tracker.client(None), "unknown");
+    assert_eq!(tracker.client(Some("Mozilla/5.0 (X11)")), "Mozilla");
+    assert_eq!(tracker.client(Some("bad\"label")), "other");
+
+    let now = Instant::now();
+    for n in 0..MAX_CLIENTS {
+        tracker.should_warn(&format!("client-{n}"), now);
+    }
+    assert_eq!(tracker.client(Some("latecomer/1.0")), "other");
+    assert_eq!(tracker.client(Some("client-3/1.0")), "client-3");
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    config::Config,
+    duplicates,
+    models::{CreateTodo, UpdateTodo},
+    AppState,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn setup(vars: &[(&str, &str)], titles: &[&str]) -> (AppState, Router) {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    for title in titles {
+        state
+            .service().repo()
+            .create(CreateTodo::new(title.parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    (state.clone(), app(state))
+}
+
+async fn create(app: &Router, uri: &str, title: &str) -> (StatusCode, Value) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "title": title }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn normalizes_case_punctuation_and_spacing() {
+    assert_eq!(duplicates::normalize("  Buy   MILK! "), "buy milk");
+    assert_eq!(duplicates::normalize("ＢＵＹ CAFE\u{301}"), "buy café");
+    assert_eq!(duplicates::similarity("buy milk", "buy milk"), 1.0);
+    assert_eq!(duplicates::similarity("buy milk", "buy silk"), 1.0 - 1.0 / 8.0);
+    assert!(duplicates::similarity("buy milk", "call mom") < 0.5);
+}
+
+#[tokio::test]
+async fn warns_about_similar_open_todos() {
+    let (_, app) = setup(
+        &[("DUPLICATE_WARNING", "true")],
+        &["Buy milk!", "Call mom", "buy milks"],
+    )
+    .await;
+
+    let (status, body) = create(&app, "/todos", "buy milk").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["id"], 4);
+    assert_eq!(body["possible_duplicates"], json!([1, 3]));
+
+    let (status, body) = create(&app, "/todos", "walk the dog").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body.get("possible_duplicates").is_none());
+}
+
+#[tokio::test]
+async fn ignores_done_todos_and_disabled_warning() {
+    let (state, app) = setup(&[("DUPLICATE_WARNING", "true")], &["buy milk"]).await;
+    let done = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    state.service().repo().update(1, done).await.unwrap();
+    let (_, body) = create(&app, "/todos", "Buy milk").await;
+    assert!(body.get("possible_duplicates").is_none());
+
+    let (_, app) = setup(&[], &["buy milk"]).await;
+    let (status, body) = create(&app, "/todos?strict_duplicates=true", "buy milk").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body.get("possible_duplicates").is_none());
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_with_conflict() {
+    let (state, app) = setup(&[("DUPLICATE_WARNING", "true")], &["Buy milk!"]).await;
+
+    let (status, body) = create(&app, "/todos?strict_duplicates=true", "buy milk").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["possible_duplicates"], json!([1]));
+    assert_eq!(state.service().repo().list().await.unwrap().len(), 1);
+
+    let (status, _) = create(&app, "/todos?strict_duplicates=true", "call mom").await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn titles_differing_in_form_alone_are_exact_duplicates() {
+    let (_, app) = setup(&[("REJECT_EXACT_DUPLICATES", "true")], &["Café au lait"]).await;
+
+    for title in ["CAFE\u{301}  AU LAIT", "ｃａｆé au lait", "\u{a0}café au lait"] {
+        let (status, body) = create(&app, "/todos", title).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{title:?}");
+        assert_eq!(body["possible_duplicates"], json!([1]));
+    }
+}
+
+#[tokio::test]
+async fn exact_duplicate_rejection_takes_precedence() {
+    let (_, app) = setup(
+        &[
+            ("DUPLICATE_WARNING", "true"),
+            ("REJECT_EXACT_DUPLICATES", "true"),
+        ],
+        &["Buy milk!", "buy milks"],
+    )
+    .await;
+
+    // Without `strict_duplicates` a near match would only warn, but the exact
+    // one is refused, and only it is listed.
+    let (status, body) = create(&app, "/todos", "buy milk").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["possible_duplicates"], json!([1]));
+
+    let (status, body) = create(&app, "/todos", "buy milkk").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["possible_duplicates"], json!([1, 2]));
+}
+
+#[test]
+fn threshold_must_be_a_fraction() {
+    for raw in ["0", "1.5", "high"] {
+        let err = Config::from_lookup(|key| {
+            (key == "DUPLICATE_THRESHOLD").then(|| raw.to_string())
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("DUPLICATE_THRESHOLD"), "{err}");
+    }
+}
@@ -0,0 +1,115 @@
+// Serving the app from a server of one's own: `app_with_connect_info` hands
+// the client's address to the access log and rate limiter, and requests
+// without connect info (in-process `oneshot`) are served all the same.
+
+mod common;
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::connect_info::Connected,
+    http::{Request, StatusCode},
+    serve::IncomingStream,
+};
+use common::LogCapture;
+use rust_api::{app, app_with_connect_info, app_with_connect_info_as, config::Config, AppState};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TARGET: &str = "rust_api::access";
+
+/// A listener on a free local port, and its address.
+async fn listener() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+/// The status line of `GET path` sent over a real connection.
+async fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response.lines().next().unwrap_or_default().to_string()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn requests_without_connect_info_share_one_bucket() {
+    let logs = LogCapture::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let config =
+        Config::from_lookup(|key| (key == "RATE_LIMIT_PER_MINUTE").then(|| "1".to_string()));
+    let app = app(AppState::new_in_memory().with_config(config.unwrap()));
+
+    let todos = || {
+        Request::builder()
+            .uri("/todos")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let res = app.clone().oneshot(todos()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    drop(res);
+    let res = app.oneshot(todos()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    drop(res);
+
+    let events = logs.for_target(TARGET);
+    assert_eq!(events.len(), 2, "{events:?}");
+    assert!(
+        events
+            .iter()
+            .all(|event| event.field("client_ip").is_none()),
+        "{events:?}"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_bound_listener_logs_the_client_ip() {
+    let logs = LogCapture::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let (listener, addr) = listener().await;
+    let service = app_with_connect_info(AppState::new_in_memory());
+    tokio::spawn(async move { axum::serve(listener, service).await.unwrap() });
+
+    assert_eq!(get(addr, "/todos").await, "HTTP/1.1 200 OK");
+
+    let events = logs.for_target(TARGET);
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].field("client_ip"), Some("127.0.0.1"));
+}
+
+/// Connect info without an IP, as a Unix socket server would record.
+#[derive(Clone)]
+struct Peer;
+
+impl Connected<IncomingStream<'_>> for Peer {
+    fn connect_info(_: IncomingStream<'_>) -> Self {
+        Peer
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn other_connect_info_is_served_without_an_ip() {
+    let logs = LogCapture::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let (listener, addr) = listener().await;
+    let service = app_with_connect_info_as::<Peer>(AppState::new_in_memory());
+    tokio::spawn(async move { axum::serve(listener, service).await.unwrap() });
+
+    assert_eq!(get(addr, "/todos").await, "HTTP/1.1 200 OK");
+
+    let events = logs.for_target(TARGET);
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].field("client_ip"), None);
+}
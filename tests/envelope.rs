@@ -0,0 +1,132 @@
+// Envelope mode: the same handlers, wrapped as `{ data, meta }` or
+// `{ error: { code, message }, meta }` when the config or query asks for it.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, config::Config, models::CreateTodo, streaming::CHUNK_SIZE, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn seeded(vars: &[(&str, &str)], count: usize) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    for i in 0..count {
+        state
+            .service().repo()
+            .create(CreateTodo::new(format!("todo {i}").parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    app(state)
+}
+
+async fn get(app: &Router, uri: &str, accept: &str) -> (StatusCode, Vec<u8>) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    (status, res.into_body().collect().await.unwrap().to_bytes().to_vec())
+}
+
+async fn get_json(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let (status, body) = get(app, uri, "application/json").await;
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn responses_stay_bare_by_default() {
+    let app = seeded(&[], 1).await;
+    let (status, body) = get_json(&app, "/todos/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["title"], "todo 0");
+    assert!(body.get("data").is_none());
+}
+
+#[tokio::test]
+async fn query_flag_wraps_a_single_todo() {
+    let app = seeded(&[], 1).await;
+    let (status, body) = get_json(&app, "/todos/1?envelope=true").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["title"], "todo 0");
+    assert!(body["meta"]["request_id"].is_string());
+    assert!(body["meta"].get("pagination").is_none());
+}
+
+#[tokio::test]
+async fn paginated_lists_describe_the_page() {
+    let app = seeded(&[("ENVELOPE_RESPONSES", "true")], 5).await;
+    let (status, body) = get_json(&app, "/todos?limit=2&offset=1").await;
+    assert_eq!(status, StatusCode::OK);
+    let titles: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["todo 1", "todo 2"]);
+    assert_eq!(
+        body["meta"]["pagination"],
+        json!({ "total": 5, "limit": 2, "offset": 1 })
+    );
+}
+
+#[tokio::test]
+async fn errors_carry_a_code_and_message() {
+    let app = seeded(&[("ENVELOPE_RESPONSES", "true")], 0).await;
+
+    let (status, body) = get_json(&app, "/todos/42").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body["error"],
+        json!({ "code": "not_found", "message": "not found" })
+    );
+    assert!(body["meta"]["request_id"].is_string());
+
+    // Errors axum produces itself get a code derived from the status.
+    let (status, body) = get_json(&app, "/nowhere").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn query_flag_can_opt_out_of_the_configured_envelope() {
+    let app = seeded(&[("ENVELOPE_RESPONSES", "true")], 1).await;
+    let (_, body) = get_json(&app, "/todos/1?envelope=false").await;
+    assert_eq!(body["title"], "todo 0");
+}
+
+#[tokio::test]
+async fn streamed_lists_are_wrapped_without_buffering() {
+    let app = seeded(&[("ENVELOPE_RESPONSES", "true")], CHUNK_SIZE + 10).await;
+    let (_, body) = get_json(&app, "/todos").await;
+    assert_eq!(body["data"].as_array().unwrap().len(), CHUNK_SIZE + 10);
+    assert!(body["meta"]["request_id"].is_string());
+}
+
+#[tokio::test]
+async fn msgpack_bodies_are_wrapped_too() {
+    let app = seeded(&[], 2).await;
+    let (status, body) = get(&app, "/todos?envelope=true", "application/msgpack").await;
+    assert_eq!(status, StatusCode::OK);
+    let body: Value = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    assert!(body["meta"]["request_id"].is_string());
+}
@@ -0,0 +1,81 @@
+// Error responses leave a structured trace: validation failures name the field
+// and rule plus a fingerprint of the input (never the input itself), and
+// server errors log their cause.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use common::{CapturedEvent, LogCapture};
+use rust_api::{
+    app,
+    errors::{fingerprint, AppError},
+    test_utils::{MockRepo, RepoMethod, TestClient},
+    AppState,
+};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+fn with_message(logs: &LogCapture, message: &str) -> Vec<CapturedEvent> {
+    logs.events()
+        .into_iter()
+        .filter(|event| event.field("message") == Some(message))
+        .collect()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn validation_failures_log_the_rule_but_not_the_payload() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = TestClient::new(app(AppState::new_in_memory()));
+
+    let blank = "  \t ";
+    let res = client.post_json("/todos", &json!({ "title": blank })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let secret = format!("hunter2-{}", "x".repeat(100));
+    let res = client.post_json("/todos", &json!({ "title": secret })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let events = with_message(&logs, "validation failed");
+    assert_eq!(events.len(), 2, "{events:?}");
+    let expected = [(&events[0], "empty", blank), (&events[1], "too_long", &secret)];
+    for (event, rule, title) in expected {
+        assert_eq!(event.level, tracing::Level::WARN);
+        assert_eq!(event.field("code"), Some("validation_failed"));
+        assert_eq!(event.field("field"), Some("title"));
+        assert_eq!(event.field("rule"), Some(rule));
+        assert_eq!(event.field("payload_hash"), Some(fingerprint(title).as_str()));
+        assert_eq!(event.span.as_deref(), Some("request"));
+    }
+
+    let logged = format!("{:?}", logs.events());
+    assert!(!logged.contains("hunter2"), "{logged}");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn server_errors_log_their_cause() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let mock = Arc::new(MockRepo::new());
+    let client = TestClient::new(app(AppState::with_repo(mock.clone())));
+
+    mock.fail_next(RepoMethod::Get, AppError::Internal);
+    let res = client.get("/todos/1").await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let events = with_message(&logs, "request failed");
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].level, tracing::Level::ERROR);
+    assert_eq!(events[0].field("code"), Some("internal"));
+    assert_eq!(events[0].field("error"), Some("internal error"));
+    assert_eq!(events[0].span.as_deref(), Some("request"));
+
+    // Client errors other than validation stay quiet here; the access log
+    // already records them.
+    client.get("/todos/12345").await;
+    assert!(with_message(&logs, "validation failed").is_empty());
+    assert_eq!(with_message(&logs, "request failed").len(), 1);
+}
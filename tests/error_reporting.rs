@@ -0,0 +1,63 @@
+// Server errors go to the state's `ErrorReporter` with the request they came
+// from; client errors don't.
+
+use std::sync::{Arc, Mutex};
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    errors::AppError,
+    reporting::{ErrorReporter, ReportedError, RequestContext},
+    test_utils::{MockRepo, RepoMethod, TestClient},
+    AppState,
+};
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(ReportedError, RequestContext)>>);
+
+impl ErrorReporter for Recorder {
+    fn report(&self, error: &ReportedError, context: &RequestContext) {
+        self.0.lock().unwrap().push((error.clone(), context.clone()));
+    }
+}
+
+fn setup() -> (Arc<MockRepo>, Arc<Recorder>, TestClient) {
+    let repo = Arc::new(MockRepo::new());
+    let recorder = Arc::new(Recorder::default());
+    let state = AppState::with_repo(repo.clone()).with_error_reporter(recorder.clone());
+    (repo, recorder, TestClient::new(app(state)))
+}
+
+#[tokio::test]
+async fn reports_a_500_once_with_its_request() {
+    let (repo, recorder, client) = setup();
+    repo.fail_next(RepoMethod::Get, AppError::Internal);
+
+    let res = client.get("/todos/7").await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let reports = recorder.0.lock().unwrap();
+    assert_eq!(reports.len(), 1, "{reports:?}");
+    let (error, context) = &reports[0];
+    assert_eq!(error.code, "internal");
+    assert_eq!(error.message, "internal error");
+    assert_eq!(context.method, "GET");
+    assert_eq!(context.route.as_deref(), Some("/todos/:id"));
+    let request_id = res.headers["x-request-id"].to_str().unwrap();
+    assert_eq!(context.request_id.as_deref(), Some(request_id));
+}
+
+#[tokio::test]
+async fn reports_unavailable_but_not_client_errors() {
+    let (repo, recorder, client) = setup();
+    assert_eq!(client.get("/todos/7").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/todos/abc").await.status, StatusCode::BAD_REQUEST);
+    assert!(recorder.0.lock().unwrap().is_empty());
+
+    repo.fail_next(RepoMethod::ListPage, AppError::Unavailable);
+    let res = client.get("/todos?limit=5").await;
+    assert_eq!(res.status, StatusCode::SERVICE_UNAVAILABLE);
+    let reports = recorder.0.lock().unwrap();
+    let codes: Vec<_> = reports.iter().map(|(error, _)| error.code).collect();
+    assert_eq!(codes, ["unavailable"]);
+}
@@ -0,0 +1,126 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{FutureExt, StreamExt};
+use rust_api::{
+    events::{EventBus, EventStream, LocalBus, TodoEvent},
+    models::{CreateTodo, UpdateTodo},
+    AppState,
+};
+
+async fn next(events: &mut EventStream) -> TodoEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("event arrives in time")
+        .expect("bus still open")
+}
+
+fn deleted(id: u64) -> TodoEvent {
+    TodoEvent::Deleted { id }
+}
+
+fn id(event: &TodoEvent) -> u64 {
+    match event {
+        TodoEvent::Deleted { id } => *id,
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+/// Behavior every [`EventBus`] must have. `settle` is how long to wait for
+/// events that should *not* arrive.
+async fn conformance(bus: Arc<dyn EventBus>, settle: Duration) {
+    // Nothing published before subscribing is delivered.
+    bus.publish(deleted(0));
+    let mut first = bus.subscribe();
+    let mut second = bus.subscribe();
+
+    // Every subscriber sees every event, in publish order.
+    for n in 1..=3 {
+        bus.publish(deleted(n));
+    }
+    for events in [&mut first, &mut second] {
+        let ids = [next(events).await, next(events).await, next(events).await];
+        assert_eq!(ids.iter().map(id).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    // Dropping a subscriber doesn't affect publishing or the others.
+    drop(second);
+    bus.publish(deleted(4));
+    assert_eq!(id(&next(&mut first).await), 4);
+
+    tokio::time::sleep(settle).await;
+    assert!(first.next().now_or_never().is_none(), "no extra events");
+}
+
+#[tokio::test]
+async fn local_bus_conforms() {
+    conformance(Arc::new(LocalBus::default()), Duration::ZERO).await;
+}
+
+#[tokio::test]
+async fn local_stream_ends_when_bus_drops() {
+    let bus = LocalBus::default();
+    let mut events = bus.subscribe();
+    drop(bus);
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn state_publishes_writes_on_the_given_bus() {
+    let bus: Arc<dyn EventBus> = Arc::new(LocalBus::default());
+    let mut events = bus.subscribe();
+    let state = AppState::in_memory_with_events(bus);
+
+    let input = CreateTodo::new("ship it".parse().unwrap());
+    state.service().repo().create(input).await.unwrap();
+    let input = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    state.service().repo().update(1, input).await.unwrap();
+    state.service().repo().delete(1).await.unwrap();
+
+    assert!(matches!(next(&mut events).await, TodoEvent::Created { todo } if todo.id == 1));
+    assert!(matches!(next(&mut events).await, TodoEvent::Updated { todo } if todo.done));
+    assert_eq!(id(&next(&mut events).await), 1);
+}
+
+/// Runs against a real server when `REDIS_URL` is set, e.g.
+/// `REDIS_URL=redis://localhost:6379 cargo test --features redis`.
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_bus_conforms() {
+    use rust_api::events::redis::RedisBus;
+
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL is unset; skipping");
+        return;
+    };
+    let bus = RedisBus::connect(&url).await.expect("redis reachable");
+    conformance(Arc::new(bus), Duration::from_millis(200)).await;
+}
+
+/// Two buses on one server stand in for two replicas.
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_bus_reaches_other_replicas() {
+    use rust_api::events::redis::RedisBus;
+
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL is unset; skipping");
+        return;
+    };
+    let here = RedisBus::connect(&url).await.expect("redis reachable");
+    let there = RedisBus::connect(&url).await.expect("redis reachable");
+    let mut events = there.subscribe();
+
+    here.publish(deleted(7));
+    assert_eq!(id(&next(&mut events).await), 7);
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_bus_rejects_invalid_urls() {
+    use rust_api::events::redis::RedisBus;
+
+    assert!(RedisBus::connect("not a url").await.is_err());
+}
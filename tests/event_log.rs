@@ -0,0 +1,166 @@
+// The numbered event log: polling `GET /todos/events/log`, resuming the SSE
+// stream at `GET /todos/events` with `Last-Event-ID`, and the horizon past
+// which clients must resync.
+
+use std::{collections::HashMap, time::Duration};
+
+use axum::{
+    body::{Body, BodyDataStream},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use futures::StreamExt;
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    config::Config,
+    models::{CreateTodo, UpdateTodo},
+    AppState,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn state(vars: &[(&str, &str)]) -> AppState {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    AppState::new_in_memory().with_config(config)
+}
+
+/// Creates `count` todos, then marks the first one done and deletes it: one
+/// event per create plus two more.
+async fn mutate(state: &AppState, count: usize) {
+    let repo = state.service().repo();
+    for n in 0..count {
+        let input = CreateTodo::new(format!("todo {n}").parse().unwrap());
+        repo.create(input).await.unwrap();
+    }
+    let done = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    repo.update(1, done).await.unwrap();
+    repo.delete(1).await.unwrap();
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn seqs(events: &Value) -> Vec<u64> {
+    events.as_array().unwrap().iter().map(|event| event["seq"].as_u64().unwrap()).collect()
+}
+
+/// Reads SSE frames until `count` events arrived, returning their ids and
+/// data.
+async fn read_events(body: &mut BodyDataStream, count: usize) -> Vec<(u64, Value)> {
+    let mut text = String::new();
+    let mut events = Vec::new();
+    while events.len() < count {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("an event within 5s")
+            .expect("the stream stays open")
+            .unwrap();
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = text.find("\n\n") {
+            let frame: String = text.drain(..end + 2).collect();
+            let field = |name: &str| {
+                frame
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(str::to_string)
+            };
+            if let (Some(id), Some(data)) = (field("id: "), field("data: ")) {
+                events.push((id.parse().unwrap(), serde_json::from_str(&data).unwrap()));
+            }
+        }
+    }
+    events
+}
+
+#[tokio::test]
+async fn polling_from_zero_returns_every_event_in_order() {
+    let state = state(&[]);
+    mutate(&state, 3).await;
+    let app = app(state);
+
+    let (status, events) = get(&app, "/todos/events/log?after_seq=0").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(seqs(&events), [1, 2, 3, 4, 5]);
+    let types: Vec<&str> =
+        events.as_array().unwrap().iter().map(|event| event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["created", "created", "created", "updated", "deleted"]);
+    assert_eq!(events[0]["todo"]["title"], "todo 0");
+    assert_eq!(events[4]["id"], 1);
+
+    // Pollers page through with `limit` and carry on from the last `seq`.
+    let (_, events) = get(&app, "/todos/events/log?after_seq=1&limit=2").await;
+    assert_eq!(seqs(&events), [2, 3]);
+    let (_, events) = get(&app, "/todos/events/log?after_seq=5").await;
+    assert_eq!(seqs(&events), Vec::<u64>::new());
+}
+
+#[tokio::test]
+async fn sse_resumes_after_last_event_id_then_goes_live() {
+    let state = state(&[]);
+    mutate(&state, 2).await;
+    let app = app(state.clone());
+
+    let req = Request::builder()
+        .uri("/todos/events")
+        .header("last-event-id", "2")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "text/event-stream");
+    let mut body = res.into_body().into_data_stream();
+
+    let replayed = read_events(&mut body, 2).await;
+    assert_eq!(replayed[0].0, 3);
+    assert_eq!(replayed[0].1["type"], "updated");
+    assert_eq!(replayed[1].0, 4);
+    assert_eq!(replayed[1].1["type"], "deleted");
+
+    let input = CreateTodo::new("live".parse().unwrap());
+    state.service().repo().create(input).await.unwrap();
+    let live = read_events(&mut body, 1).await;
+    assert_eq!(live[0].0, 5);
+    assert_eq!(live[0].1["todo"]["title"], "live");
+}
+
+#[tokio::test]
+async fn events_past_the_horizon_require_a_resync() {
+    let state = state(&[("EVENT_LOG_CAPACITY", "3")]);
+    mutate(&state, 3).await;
+    let app = app(state);
+
+    // Events 1 and 2 are gone, so a client that saw 0 or 1 has a gap.
+    for uri in ["/todos/events/log?after_seq=0", "/todos/events/log?after_seq=1"] {
+        let (status, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{uri}");
+        assert!(body["error"].as_str().unwrap().contains("resync"), "{body}");
+    }
+    let (status, events) = get(&app, "/todos/events/log?after_seq=2").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(seqs(&events), [3, 4, 5]);
+
+    // A seq from before a restart is as unusable as an expired one.
+    let (status, _) = get(&app, "/todos/events/log?after_seq=99").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let req = Request::builder()
+        .uri("/todos/events")
+        .header("last-event-id", "1")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
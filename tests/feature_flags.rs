@@ -0,0 +1,106 @@
+// `X-Feature-Flags`: clients turn allowlisted behaviors on per request, on
+// top of the flags the config turns on for everyone.
+
+mod common;
+
+use std::collections::HashMap;
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use common::LogCapture;
+use rust_api::{
+    app,
+    config::Config,
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+fn config(vars: &[(&str, &str)]) -> Config {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Config::from_lookup(|key| vars.get(key).cloned()).unwrap()
+}
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    TestClient::new(app(AppState::new_in_memory().with_config(config(vars))))
+}
+
+async fn get_with_flags(client: &TestClient, uri: &str, flags: &str) -> TestResponse {
+    let req = Request::get(uri).header("x-feature-flags", flags).body(Body::empty()).unwrap();
+    client.send(req).await
+}
+
+async fn create_with_flags(client: &TestClient, title: &str, flags: &str) -> TestResponse {
+    let req = Request::post("/todos")
+        .header("content-type", "application/json")
+        .header("x-feature-flags", flags)
+        .body(Body::from(json!({ "title": title }).to_string()))
+        .unwrap();
+    client.send(req).await
+}
+
+#[tokio::test]
+async fn clients_turn_on_allowed_flags() {
+    let client = client(&[
+        ("CLIENT_FEATURE_FLAGS", "envelope,strict_duplicates"),
+        ("DUPLICATE_WARNING", "true"),
+    ]);
+    let res = create_with_flags(&client, "buy milk", "").await;
+    assert_eq!(res.status, StatusCode::CREATED);
+
+    // Near-duplicates are only a warning without the flag, and a `409` with it.
+    let res = create_with_flags(&client, "Buy milk!", "").await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert_eq!(res.body["possible_duplicates"], json!([1]));
+    let res = create_with_flags(&client, "buy milk.", "Strict_Duplicates").await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+
+    let res = get_with_flags(&client, "/todos", "").await;
+    assert!(res.body.is_array(), "{}", res.body);
+    let res = get_with_flags(&client, "/todos", " envelope , ").await;
+    assert_eq!(res.body["data"].as_array().map(Vec::len), Some(2), "{}", res.body);
+    // The query parameter still has the last word.
+    let res = get_with_flags(&client, "/todos?envelope=false", "envelope").await;
+    assert!(res.body.is_array(), "{}", res.body);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn flags_off_the_allowlist_are_ignored() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = client(&[("CLIENT_FEATURE_FLAGS", "strict_duplicates")]);
+
+    let res = get_with_flags(&client, "/todos", "envelope,no_such_flag").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.is_array(), "{}", res.body);
+
+    let messages: Vec<_> = logs
+        .for_target("rust_api::flags")
+        .into_iter()
+        .filter(|event| event.level == tracing::Level::DEBUG)
+        .filter_map(|event| event.field("message").map(str::to_string))
+        .collect();
+    assert_eq!(
+        messages,
+        ["ignoring feature flag clients may not set", "ignoring unknown feature flag"]
+    );
+}
+
+#[tokio::test]
+async fn config_defaults_apply_to_every_request() {
+    let client = client(&[("FEATURE_FLAGS", "envelope")]);
+    let res = client.get("/todos").await;
+    assert!(res.body["data"].is_array(), "{}", res.body);
+    assert!(res.body["meta"].is_object());
+}
+
+#[test]
+fn unknown_flags_in_the_config_are_rejected() {
+    let vars = HashMap::from([("FEATURE_FLAGS".to_string(), "envelop".to_string())]);
+    let err = Config::from_lookup(|key| vars.get(key).cloned()).unwrap_err();
+    assert!(err.to_string().contains("unknown feature flag `envelop`"), "{err}");
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use quick_xml::{events::Event, Reader};
+use rust_api::{app, models::CreateTodo, AppState};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn seeded(count: usize) -> (AppState, Router) {
+    let state = AppState::new_in_memory();
+    for i in 1..=count {
+        state
+            .service().repo()
+            .create(CreateTodo::new(format!("todo {i}").parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    (state.clone(), app(state))
+}
+
+/// The parts of a feed the tests look at.
+#[derive(Debug, Default)]
+struct Feed {
+    updated: String,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Entry {
+    id: String,
+    title: String,
+    updated: String,
+}
+
+/// Fetches the feed and parses it, failing on malformed XML.
+async fn fetch(app: &Router, uri: &str) -> Feed {
+    let res = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/atom+xml");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    parse(std::str::from_utf8(&body).unwrap())
+}
+
+fn parse(xml: &str) -> Feed {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = true;
+    let mut feed = Feed::default();
+    let mut path: Vec<String> = Vec::new();
+    loop {
+        match reader.read_event().expect("well-formed XML") {
+            Event::Start(start) => {
+                let name = start.name().as_ref().to_string();
+                if name == "entry" {
+                    feed.entries.push(Entry::default());
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Text(text) => {
+                let text = text.xml10_content().into_owned();
+                match path.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    ["feed", "updated"] => feed.updated = text,
+                    ["feed", "entry", field] => {
+                        let entry = feed.entries.last_mut().unwrap();
+                        match field {
+                            "id" => entry.id = text,
+                            // Titles with entities arrive in several pieces.
+                            "title" => entry.title.push_str(&text),
+                            "updated" => entry.updated = text,
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::GeneralRef(reference) => {
+                let resolved = match reference.xml10_content().as_ref() {
+                    "amp" => "&".to_string(),
+                    "lt" => "<".to_string(),
+                    "gt" => ">".to_string(),
+                    "quot" => "\"".to_string(),
+                    other => reference
+                        .resolve_char_ref()
+                        .unwrap()
+                        .map(String::from)
+                        .unwrap_or_else(|| panic!("unknown entity {other}")),
+                };
+                if path.last().map(String::as_str) == Some("title") {
+                    if let Some(entry) = feed.entries.last_mut() {
+                        entry.title.push_str(&resolved);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    assert!(path.is_empty(), "unclosed elements: {path:?}");
+    feed
+}
+
+#[tokio::test]
+async fn empty_feed_is_well_formed() {
+    let (_, app) = seeded(0).await;
+    let feed = fetch(&app, "/todos/feed.atom").await;
+    assert!(feed.entries.is_empty());
+    assert_eq!(feed.updated, "1970-01-01T00:00:00.000000Z");
+}
+
+#[tokio::test]
+async fn lists_newest_fifty_with_tag_ids() {
+    let (_, app) = seeded(60).await;
+    let feed = fetch(&app, "/todos/feed.atom").await;
+    assert_eq!(feed.entries.len(), 50);
+    assert_eq!(feed.entries[0].id, "tag:rust-api,2024:todos:60");
+    assert_eq!(feed.entries[0].title, "todo 60");
+    assert_eq!(feed.entries[49].title, "todo 11");
+    assert_eq!(feed.updated, feed.entries[0].updated);
+}
+
+#[tokio::test]
+async fn titles_are_escaped() {
+    let state = AppState::new_in_memory();
+    let title = "<b>fish & chips</b> \"now\"";
+    state
+        .service().repo()
+        .create(CreateTodo::new(title.parse().unwrap()))
+        .await
+        .unwrap();
+    let feed = fetch(&app(state), "/todos/feed.atom").await;
+    assert_eq!(feed.entries[0].title, title);
+}
+
+#[tokio::test]
+async fn update_bumps_the_feed_and_moves_the_entry_first() {
+    let (_, app) = seeded(3).await;
+    let before = fetch(&app, "/todos/feed.atom").await;
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/todos/1")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "done": true }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let after = fetch(&app, "/todos/feed.atom").await;
+    assert!(after.updated > before.updated);
+    assert_eq!(after.entries[0].id, "tag:rust-api,2024:todos:1");
+    assert_eq!(after.updated, after.entries[0].updated);
+    assert_eq!(after.entries.len(), 3);
+}
+
+#[tokio::test]
+async fn since_filters_older_changes() {
+    let (state, app) = seeded(2).await;
+    let cutoff = fetch(&app, "/todos/feed.atom").await.updated;
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    state
+        .service().repo()
+        .create(CreateTodo::new("fresh".parse().unwrap()))
+        .await
+        .unwrap();
+
+    let feed = fetch(&app, &format!("/todos/feed.atom?since={cutoff}")).await;
+    assert_eq!(feed.entries.len(), 1);
+    assert_eq!(feed.entries[0].title, "fresh");
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/todos/feed.atom?since=yesterday")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
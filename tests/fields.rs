@@ -0,0 +1,101 @@
+// `?fields=` returns todos with only the named fields, plus `id`.
+
+mod test_support;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, streaming::CHUNK_SIZE, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn seeded(count: usize) -> Router {
+    let state = AppState::new_in_memory();
+    test_support::seed(state.service().repo().as_ref(), count).await;
+    app(state)
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .uri(uri)
+        .header(header::ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn projected_lists_omit_the_other_fields() {
+    let app = seeded(3).await;
+
+    let (status, body) = get(&app, "/todos?fields=id,done").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!([
+            { "id": 1, "done": false },
+            { "id": 2, "done": false },
+            { "id": 3, "done": false },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn id_is_always_included() {
+    let app = seeded(1).await;
+
+    let (_, body) = get(&app, "/todos?fields=title").await;
+    assert_eq!(body, json!([{ "id": 1, "title": "todo 0" }]));
+}
+
+#[tokio::test]
+async fn unknown_fields_are_rejected_with_the_valid_ones() {
+    let app = seeded(1).await;
+
+    for uri in ["/todos?fields=id,colour", "/todos/1?fields=colour"] {
+        let (status, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("`colour`"), "{message}");
+        assert!(message.contains("id, title, description, done"), "{message}");
+    }
+}
+
+#[tokio::test]
+async fn a_single_todo_can_be_projected() {
+    let app = seeded(2).await;
+
+    let (status, body) = get(&app, "/todos/2?fields=title,created_at").await;
+    assert_eq!(status, StatusCode::OK);
+    let keys: Vec<&str> = body.as_object().unwrap().keys().map(String::as_str).collect();
+    assert_eq!(keys, ["created_at", "id", "title"]);
+    assert_eq!(body["title"], "todo 1");
+}
+
+#[tokio::test]
+async fn pages_and_envelopes_are_projected_too() {
+    let app = seeded(5).await;
+
+    let (_, body) = get(&app, "/todos?fields=done&limit=2&offset=1&envelope=true").await;
+    assert_eq!(
+        body["data"],
+        json!([{ "id": 2, "done": false }, { "id": 3, "done": false }])
+    );
+    assert_eq!(body["meta"]["pagination"]["total"], 5);
+}
+
+#[tokio::test]
+async fn streamed_lists_are_projected_in_every_chunk() {
+    let app = seeded(CHUNK_SIZE + 3).await;
+
+    let (_, body) = get(&app, "/todos?fields=id").await;
+    let todos = body.as_array().unwrap();
+    assert_eq!(todos.len(), CHUNK_SIZE + 3);
+    assert!(todos.iter().all(|todo| todo.as_object().unwrap().len() == 1));
+}
@@ -0,0 +1,108 @@
+// GraphQL is feature-gated; run with `cargo test --features graphql`.
+#![cfg(feature = "graphql")]
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, config::Config, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app_with(vars: &[(&str, &str)]) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    app(AppState::new_in_memory().with_config(config))
+}
+
+async fn graphql(app: &Router, query: &str) -> Value {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "query": query }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn create_then_query() {
+    let app = app_with(&[]);
+
+    let created = graphql(
+        &app,
+        r#"mutation { createTodo(title: "buy milk") { id title done } }"#,
+    )
+    .await;
+    assert_eq!(
+        created["data"]["createTodo"],
+        json!({ "id": 1, "title": "buy milk", "done": false })
+    );
+
+    let toggled = graphql(&app, "mutation { toggleTodo(id: 1) { done } }").await;
+    assert_eq!(toggled["data"]["toggleTodo"]["done"], true);
+
+    let listed = graphql(
+        &app,
+        r#"{
+            todos(filter: { done: true, q: "MILK" }, limit: 10) { id title }
+            todo(id: 1) { title }
+        }"#,
+    )
+    .await;
+    assert_eq!(listed["data"]["todos"], json!([{ "id": 1, "title": "buy milk" }]));
+    assert_eq!(listed["data"]["todo"]["title"], "buy milk");
+}
+
+#[tokio::test]
+async fn errors_carry_our_error_codes() {
+    let app = app_with(&[]);
+
+    let invalid = graphql(&app, r#"mutation { createTodo(title: "   ") { id } }"#).await;
+    assert_eq!(
+        invalid["errors"][0]["message"],
+        "validation error: title cannot be empty"
+    );
+    assert_eq!(invalid["errors"][0]["extensions"]["code"], "validation_failed");
+
+    let missing = graphql(&app, "{ todo(id: 99) { id } }").await;
+    assert_eq!(missing["errors"][0]["extensions"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn read_only_mode_blocks_mutations_but_not_queries() {
+    let app = app_with(&[("READ_ONLY", "true")]);
+
+    let query = graphql(&app, "{ todos { id } }").await;
+    assert_eq!(query["data"]["todos"], json!([]));
+
+    let mutation = graphql(&app, r#"mutation { createTodo(title: "x") { id } }"#).await;
+    assert_eq!(mutation["errors"][0]["extensions"]["code"], "read_only");
+}
+
+#[tokio::test]
+async fn playground_requires_docs() {
+    for (enabled, expected) in [("false", StatusCode::NOT_FOUND), ("true", StatusCode::OK)] {
+        let app = app_with(&[("ENABLE_DOCS", enabled)]);
+        let res = app
+            .oneshot(Request::builder().uri("/graphql").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+    }
+}
@@ -0,0 +1,182 @@
+// gRPC is feature-gated; run with `cargo test --features grpc`.
+#![cfg(feature = "grpc")]
+
+use std::{collections::HashMap, time::Duration};
+
+use rust_api::{
+    config::Config,
+    grpc::{
+        proto::{
+            todo_event::Event, todos_client::TodosClient, CreateRequest, DeleteRequest,
+            GetRequest, ListRequest, UpdateRequest, WatchRequest,
+        },
+        TodoGrpcService,
+    },
+    AppState,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Channel, Code};
+
+/// Serves the gRPC API on an ephemeral port and returns a connected client.
+async fn client_with(vars: &[(&str, &str)]) -> TodosClient<Channel> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    serve(AppState::new_in_memory().with_config(config)).await
+}
+
+async fn serve(state: AppState) -> TodosClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(TodoGrpcService::server(state))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    TodosClient::connect(format!("http://{addr}")).await.unwrap()
+}
+
+#[tokio::test]
+async fn crud_flow() {
+    let mut client = client_with(&[]).await;
+
+    let created = client
+        .create(CreateRequest {
+            title: "write proto".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(created.title, "write proto");
+    assert!(!created.done);
+
+    let fetched = client
+        .get(GetRequest { id: created.id })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fetched, created);
+
+    let updated = client
+        .update(UpdateRequest {
+            id: created.id,
+            done: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(updated.done);
+
+    let listed = client
+        .list(ListRequest {
+            done: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(listed.total, 1);
+    assert_eq!(listed.todos, vec![updated]);
+
+    client
+        .delete(DeleteRequest { id: created.id })
+        .await
+        .unwrap();
+    let err = client.get(GetRequest { id: created.id }).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    assert_eq!(err.metadata().get("error-code").unwrap(), "not_found");
+}
+
+#[tokio::test]
+async fn validation_failures_are_invalid_argument() {
+    let mut client = client_with(&[]).await;
+
+    let err = client
+        .create(CreateRequest {
+            title: "  ".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let err = client
+        .list(ListRequest {
+            limit: Some(0),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn read_only_rejects_writes() {
+    let mut client = client_with(&[("READ_ONLY", "true")]).await;
+
+    let err = client
+        .create(CreateRequest {
+            title: "nope".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    assert!(client.list(ListRequest::default()).await.is_ok());
+}
+
+#[tokio::test]
+async fn watch_streams_changes() {
+    let mut client = client_with(&[]).await;
+    let mut events = client.watch(WatchRequest {}).await.unwrap().into_inner();
+
+    let created = client
+        .create(CreateRequest {
+            title: "watched".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), events.message())
+        .await
+        .expect("event arrives")
+        .unwrap()
+        .expect("stream stays open");
+    assert_eq!(event.event, Some(Event::Created(created)));
+}
+
+#[tokio::test]
+async fn watch_releases_the_subscription_when_the_client_hangs_up() {
+    let state = AppState::new_in_memory();
+    let before = state.events().subscriber_count();
+    let mut client = serve(state.clone()).await;
+    let mut events = client.watch(WatchRequest {}).await.unwrap().into_inner();
+
+    let request = CreateRequest {
+        title: "watched".into(),
+        ..Default::default()
+    };
+    client.create(request).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), events.message())
+        .await
+        .expect("event arrives")
+        .unwrap();
+    assert_eq!(state.events().subscriber_count(), before + 1);
+
+    drop(events);
+    let released = async {
+        while state.events().subscriber_count() > before {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), released)
+        .await
+        .expect("subscription released after the client left");
+}
@@ -0,0 +1,126 @@
+// `HEAD` mirrors `GET`: same status and headers, no body.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, models::CreateTodo, streaming::CHUNK_SIZE, AppState};
+use tower::ServiceExt;
+
+/// Headers that must agree between `GET` and `HEAD`.
+const MIRRORED: [header::HeaderName; 5] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CACHE_CONTROL,
+    header::ETAG,
+    header::VARY,
+];
+
+async fn seeded(count: usize) -> Router {
+    let state = AppState::new_in_memory();
+    for i in 0..count {
+        state
+            .service().repo()
+            .create(CreateTodo::new(format!("todo {i}").parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    app(state)
+}
+
+async fn send(app: &Router, method: &str, uri: &str) -> (StatusCode, HeaderMap, usize) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let headers = res.headers().clone();
+    let len = res.into_body().collect().await.unwrap().to_bytes().len();
+    (status, headers, len)
+}
+
+/// Asserts `HEAD uri` matches `GET uri` and returns the shared headers.
+async fn assert_mirrors_get(app: &Router, uri: &str) -> (StatusCode, HeaderMap) {
+    let (get_status, get_headers, get_len) = send(app, "GET", uri).await;
+    let (head_status, head_headers, head_len) = send(app, "HEAD", uri).await;
+
+    assert_eq!(head_status, get_status, "{uri}");
+    assert_eq!(head_len, 0, "{uri}");
+    assert!(get_len > 0, "{uri}");
+    for name in MIRRORED {
+        assert_eq!(
+            head_headers.get_all(&name).iter().collect::<Vec<_>>(),
+            get_headers.get_all(&name).iter().collect::<Vec<_>>(),
+            "{name} differs for {uri}"
+        );
+    }
+    (head_status, head_headers)
+}
+
+#[tokio::test]
+async fn head_on_a_todo_matches_get() {
+    let app = seeded(1).await;
+    let (status, headers) = assert_mirrors_get(&app, "/todos/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.contains_key(header::ETAG));
+    assert!(headers.contains_key(header::CONTENT_LENGTH));
+}
+
+#[tokio::test]
+async fn head_on_a_missing_todo_is_a_bodiless_404() {
+    let app = seeded(0).await;
+    let (status, headers) = assert_mirrors_get(&app, "/todos/7").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert_eq!(headers[header::CACHE_CONTROL], "no-store");
+}
+
+#[tokio::test]
+async fn head_on_the_list_matches_get() {
+    let app = seeded(3).await;
+    let (status, headers) = assert_mirrors_get(&app, "/todos").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.contains_key(header::CONTENT_LENGTH));
+
+    let (_, headers) = assert_mirrors_get(&app, "/todos?limit=2").await;
+    assert_eq!(headers["x-total-count"], "3");
+}
+
+#[tokio::test]
+async fn head_on_a_streamed_list_has_no_length() {
+    let app = seeded(CHUNK_SIZE + 1).await;
+    let (status, headers) = assert_mirrors_get(&app, "/todos").await;
+    assert_eq!(status, StatusCode::OK);
+    // The size of a streamed list isn't known until it has been sent.
+    assert!(!headers.contains_key(header::CONTENT_LENGTH));
+    assert_eq!(headers[header::CACHE_CONTROL], "no-cache");
+}
+
+#[tokio::test]
+async fn head_honors_if_none_match() {
+    let app = seeded(1).await;
+    let (_, headers, _) = send(&app, "GET", "/todos/1").await;
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("HEAD")
+                .uri("/todos/1")
+                .header(header::IF_NONE_MATCH, headers[header::ETAG].clone())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert!(!res.headers().contains_key(header::CONTENT_LENGTH));
+}
@@ -0,0 +1,125 @@
+// Id generation strategies: uniqueness under concurrency, snowflake layout
+// and ordering, and selection through `ID_STRATEGY`.
+
+use std::{collections::HashSet, sync::Arc, thread};
+
+use rust_api::{
+    config::Config,
+    ids::{self, IdGenerator, IdStrategy, Random, Sequential, Snowflake, SEQUENCE_BITS},
+    models::CreateTodo,
+    state::in_memory_repo_with_ids,
+};
+
+const THREADS: usize = 8;
+const PER_THREAD: usize = 5000;
+
+/// Draws ids from `ids` on several threads at once.
+fn draw_concurrently(ids: Arc<dyn IdGenerator>) -> Vec<u64> {
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let ids = Arc::clone(&ids);
+            thread::spawn(move || {
+                (0..PER_THREAD).map(|_| ids.next_id().unwrap()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+}
+
+fn config(vars: &[(&str, &str)]) -> anyhow::Result<Config> {
+    Config::from_lookup(|key| {
+        vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    })
+}
+
+fn input(n: usize) -> CreateTodo {
+    CreateTodo::new(format!("todo {n}").parse().unwrap())
+}
+
+#[test]
+fn every_generator_is_unique_under_concurrency() {
+    let generators: [Arc<dyn IdGenerator>; 3] = [
+        Arc::new(Sequential::default()),
+        Arc::new(Snowflake::new(3)),
+        Arc::new(Random::default()),
+    ];
+    for ids in generators {
+        let drawn = draw_concurrently(ids);
+        assert_eq!(drawn.len(), THREADS * PER_THREAD);
+        assert!(drawn.iter().all(|id| (1..1 << 63).contains(id)), "ids must fit 63 bits");
+        let unique: HashSet<_> = drawn.iter().collect();
+        assert_eq!(unique.len(), drawn.len());
+    }
+}
+
+#[test]
+fn sequential_ids_continue_above_observed_ones() {
+    let ids = Sequential::default();
+    assert_eq!(ids.next_id().unwrap(), 1);
+    ids.observe(41);
+    assert_eq!(ids.next_id().unwrap(), 42);
+    ids.observe(7);
+    assert_eq!(ids.next_id().unwrap(), 43);
+}
+
+#[test]
+fn snowflake_ids_are_time_ordered_and_carry_the_node() {
+    let ids = Snowflake::new(5);
+    let first = ids.next_id().unwrap();
+    thread::sleep(std::time::Duration::from_millis(2));
+    let drawn: Vec<u64> = (0..10_000).map(|_| ids.next_id().unwrap()).collect();
+
+    assert!(drawn.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(drawn[0] > first);
+    // A later millisecond, not just a later sequence number.
+    let millis = |id: u64| id >> (ids::NODE_BITS + SEQUENCE_BITS);
+    assert!(millis(drawn[0]) > millis(first));
+    for id in [first, drawn[0], drawn[9_999]] {
+        assert_eq!((id >> SEQUENCE_BITS) & ids::MAX_NODE_ID, 5, "{id:#x}");
+    }
+}
+
+#[tokio::test]
+async fn config_selects_the_generator() {
+    assert_eq!(config(&[]).unwrap().id_strategy, IdStrategy::Sequential);
+    assert!(config(&[("ID_STRATEGY", "uuid")]).is_err());
+    assert!(config(&[("NODE_ID", "1024")]).is_err());
+
+    let sequential = in_memory_repo_with_ids(ids::from_config(&config(&[]).unwrap()));
+    assert_eq!(sequential.create(input(0)).await.unwrap().id, 1);
+
+    let snowflake = config(&[("ID_STRATEGY", "Snowflake"), ("NODE_ID", "7")]).unwrap();
+    let repo = in_memory_repo_with_ids(ids::from_config(&snowflake));
+    let a = repo.create(input(0)).await.unwrap().id;
+    let b = repo.create(input(1)).await.unwrap().id;
+    assert!(a < b);
+    assert_eq!((a >> SEQUENCE_BITS) & ids::MAX_NODE_ID, 7);
+
+    let random = config(&[("ID_STRATEGY", "random")]).unwrap();
+    let repo = in_memory_repo_with_ids(ids::from_config(&random));
+    let ids: Vec<u64> = [repo.create(input(0)).await, repo.create(input(1)).await]
+        .into_iter()
+        .map(|todo| todo.unwrap().id)
+        .collect();
+    // Random ids land far above anything a counter would reach.
+    assert!(ids.iter().all(|id| *id > 1 << 32), "{ids:?}");
+    assert_eq!(repo.list().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn taken_ids_are_skipped() {
+    /// Hands out 1, 1, 1, 2, 3, ...
+    struct Stuck(Sequential);
+
+    impl IdGenerator for Stuck {
+        fn next_id(&self) -> Result<u64, rust_api::errors::AppError> {
+            let id = self.0.next_id()?;
+            Ok(if id <= 3 { 1 } else { id - 2 })
+        }
+    }
+
+    let repo = in_memory_repo_with_ids(Arc::new(Stuck(Sequential::default())));
+    assert_eq!(repo.create(input(0)).await.unwrap().id, 1);
+    // 1 is taken twice over before 2 comes up.
+    assert_eq!(repo.create(input(1)).await.unwrap().id, 2);
+}
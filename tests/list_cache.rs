@@ -0,0 +1,168 @@
+// `LIST_CACHE_ENTRIES`: list pages served again while the revision holds,
+// invalidated by writes, keyed per filter, and bounded by an LRU.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_api::{
+    app,
+    config::Config,
+    models::{CreateTodo, UpdateTodo},
+    state::TodoRepo,
+    test_utils::{seed, MockRepo, RepoMethod, TestClient},
+    AppState,
+};
+use serde_json::json;
+
+/// A client over a mock seeded with `count` todos, every other one done.
+async fn client(count: usize, vars: &[(&str, &str)]) -> (TestClient, Arc<MockRepo>) {
+    let repo = Arc::new(MockRepo::new());
+    let inputs = (0..count).map(|n| CreateTodo::new(format!("todo {n}").parse().unwrap()));
+    seed(repo.as_ref(), inputs).await;
+    for id in (2..=count as u64).step_by(2) {
+        let done = UpdateTodo {
+            done: Some(true),
+            ..Default::default()
+        };
+        repo.update(id, done).await.unwrap();
+    }
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::with_repo(repo.clone()).with_config(config);
+    (TestClient::new(app(state)), repo)
+}
+
+/// Hits and misses so far.
+async fn counts(client: &TestClient) -> (u64, u64) {
+    let scrape = client.get("/metrics").await.body.as_str().unwrap().to_string();
+    let sample = |name: &str| {
+        scrape
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.rsplit(' ').next()?.parse().ok())
+            .unwrap_or_else(|| panic!("{name} missing from\n{scrape}"))
+    };
+    (sample("list_cache_hits_total"), sample("list_cache_misses_total"))
+}
+
+#[tokio::test]
+async fn repeated_lists_are_served_from_the_cache() {
+    let (client, repo) = client(5, &[("LIST_CACHE_ENTRIES", "8")]).await;
+
+    let first = client.get("/v1/todos?limit=2&offset=1").await;
+    assert_eq!(first.status, StatusCode::OK);
+    for _ in 0..3 {
+        let again = client.get("/v1/todos?limit=2&offset=1").await;
+        assert_eq!(again.body, first.body);
+        assert_eq!(again.headers[header::ETAG], first.headers[header::ETAG]);
+        assert_eq!(again.headers["x-total-count"], "5");
+    }
+
+    assert_eq!(counts(&client).await, (3, 1));
+    assert_eq!(repo.calls(RepoMethod::ListPage), 1);
+    assert_eq!(repo.calls(RepoMethod::Count), 1);
+}
+
+#[tokio::test]
+async fn each_format_and_projection_is_encoded_from_one_read() {
+    let (client, repo) = client(3, &[("LIST_CACHE_ENTRIES", "8")]).await;
+    let msgpack = || {
+        Request::get("/v1/todos?limit=2")
+            .header(header::ACCEPT, "application/msgpack")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let full = client.get("/v1/todos?limit=2").await;
+    let projected = client.get("/v1/todos?limit=2&fields=id").await;
+    assert_eq!(projected.body, json!([{ "id": 1 }, { "id": 2 }]));
+    let first = client.send(msgpack()).await;
+    assert_eq!(first.headers[header::CONTENT_TYPE], "application/msgpack");
+    let again = client.send(msgpack()).await;
+    assert_eq!(again.body, first.body);
+    assert_eq!(again.headers[header::ETAG], first.headers[header::ETAG]);
+    assert_eq!(client.get("/v1/todos?limit=2").await.body, full.body);
+
+    assert_eq!(counts(&client).await, (4, 1));
+    assert_eq!(repo.calls(RepoMethod::ListPage), 1);
+}
+
+#[tokio::test]
+async fn writes_invalidate_the_cache() {
+    let (client, repo) = client(3, &[("LIST_CACHE_ENTRIES", "8")]).await;
+    client.get("/v1/todos?limit=10").await;
+    client.get("/v1/todos?limit=10").await;
+
+    let res = client.post_json("/v1/todos", &json!({ "title": "fresh" })).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    let res = client.get("/v1/todos?limit=10").await;
+    assert_eq!(res.body.as_array().unwrap().len(), 4);
+    assert_eq!(res.body[3]["title"], "fresh");
+
+    client.put_json("/v1/todos/1", &json!({ "done": true })).await;
+    let res = client.get("/v1/todos?limit=10").await;
+    assert_eq!(res.body[0]["done"], true);
+
+    assert_eq!(counts(&client).await, (1, 3));
+    assert_eq!(repo.calls(RepoMethod::ListPage), 3);
+}
+
+#[tokio::test]
+async fn each_filter_sort_and_page_gets_its_own_entry() {
+    let (client, _repo) = client(6, &[("LIST_CACHE_ENTRIES", "8")]).await;
+    let uris = [
+        "/v1/todos?limit=10&done=true",
+        "/v1/todos?limit=10&done=false",
+        "/v1/todos?limit=10&sort=title&order=desc",
+        "/v1/todos?limit=2&offset=2",
+    ];
+    let mut bodies = Vec::new();
+    for uri in uris {
+        bodies.push(client.get(uri).await.body);
+    }
+    for (uri, body) in uris.iter().zip(&bodies) {
+        assert_eq!(&client.get(uri).await.body, body, "{uri}");
+    }
+    assert_eq!(bodies[0].as_array().unwrap().len(), 3);
+    assert_eq!(bodies[1].as_array().unwrap().len(), 3);
+    assert_ne!(bodies[0], bodies[1]);
+    assert_eq!(counts(&client).await, (4, 4));
+
+    // The same ids in another order are the same list.
+    let ids = client.get("/v1/todos?limit=10&ids=3,1").await.body;
+    assert_eq!(client.get("/v1/todos?limit=10&ids=1,3,3").await.body, ids);
+    assert_eq!(counts(&client).await, (5, 5));
+}
+
+#[tokio::test]
+async fn the_least_recently_used_page_is_evicted() {
+    let (client, repo) = client(4, &[("LIST_CACHE_ENTRIES", "2")]).await;
+    let [a, b, c] =
+        ["done=true", "done=false", "q=todo"].map(|q| format!("/v1/todos?limit=5&{q}"));
+
+    client.get(&a).await;
+    client.get(&b).await;
+    client.get(&a).await; // hit; `b` is now the oldest
+    client.get(&c).await; // evicts `b`
+    client.get(&a).await; // hit
+    client.get(&b).await; // miss
+
+    assert_eq!(counts(&client).await, (2, 4));
+    assert_eq!(repo.calls(RepoMethod::ListPage), 4);
+}
+
+#[tokio::test]
+async fn lists_are_read_every_time_without_the_setting() {
+    let (client, repo) = client(3, &[]).await;
+    for _ in 0..3 {
+        client.get("/v1/todos?limit=10").await;
+    }
+    assert_eq!(counts(&client).await, (0, 0));
+    assert_eq!(repo.calls(RepoMethod::ListPage), 3);
+}
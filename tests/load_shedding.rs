@@ -0,0 +1,127 @@
+// Concurrency limits: requests beyond `MAX_CONCURRENT_REQUESTS` (or the
+// tighter `MAX_CONCURRENT_EXPENSIVE_REQUESTS` for search and exports, and
+// `MAX_CONCURRENT_EXPORTS` for the exports alone) are shed with `503` instead
+// of queueing, while `/health` keeps answering.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum::http::{header, StatusCode};
+use futures::future::join_all;
+use rust_api::{
+    app,
+    config::Config,
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::TodoRepo,
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+
+/// Every read takes a while and finds nothing, so requests pile up.
+struct SlowRepo;
+
+const DELAY: Duration = Duration::from_millis(200);
+
+#[async_trait]
+impl TodoRepo for SlowRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        tokio::time::sleep(DELAY).await;
+        Ok(Vec::new())
+    }
+    async fn create(&self, _input: CreateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn get(&self, _id: u64) -> Result<Todo, AppError> {
+        tokio::time::sleep(DELAY).await;
+        Err(AppError::NotFound)
+    }
+    async fn update(&self, _id: u64, _input: UpdateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn delete(&self, _id: u64) -> Result<(), AppError> {
+        Err(AppError::Internal)
+    }
+}
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    TestClient::new(app(AppState::with_repo(Arc::new(SlowRepo)).with_config(config)))
+}
+
+fn shed(responses: &[TestResponse]) -> Vec<&TestResponse> {
+    responses
+        .iter()
+        .filter(|res| res.status == StatusCode::SERVICE_UNAVAILABLE)
+        .collect()
+}
+
+#[tokio::test]
+async fn sheds_requests_beyond_the_limit_but_not_health() {
+    let client = client(&[("MAX_CONCURRENT_REQUESTS", "3")]);
+
+    let todos = join_all((0..6).map(|_| client.get("/todos/1")));
+    let (todos, health) = tokio::join!(todos, client.get("/health"));
+    assert_eq!(health.status, StatusCode::OK);
+
+    let rejected = shed(&todos);
+    assert_eq!(rejected.len(), 3);
+    for res in &rejected {
+        assert_eq!(res.headers[header::RETRY_AFTER], "1");
+        assert!(res.body["error"].as_str().unwrap().contains("overloaded"), "{}", res.body);
+    }
+    let served = todos.iter().filter(|res| res.status == StatusCode::NOT_FOUND);
+    assert_eq!(served.count(), 3);
+
+    // Capacity comes back once the slow requests finish.
+    assert_eq!(client.get("/todos/1").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn search_and_exports_share_a_tighter_limit() {
+    let client = client(&[
+        ("MAX_CONCURRENT_REQUESTS", "10"),
+        ("MAX_CONCURRENT_EXPENSIVE_REQUESTS", "2"),
+    ]);
+
+    let expensive = join_all(
+        ["/todos/search?q=a", "/todos/feed.atom", "/todos/calendar.ics", "/todos/search?q=b"]
+            .map(|uri| client.get(uri)),
+    );
+    let cheap = join_all((0..4).map(|_| client.get("/todos")));
+    let (expensive, cheap) = tokio::join!(expensive, cheap);
+
+    assert_eq!(shed(&expensive).len(), 2);
+    assert!(cheap.iter().all(|res| res.status == StatusCode::OK));
+}
+
+#[tokio::test]
+async fn exports_have_a_limit_of_their_own() {
+    let client = client(&[
+        ("MAX_CONCURRENT_EXPENSIVE_REQUESTS", "5"),
+        ("MAX_CONCURRENT_EXPORTS", "1"),
+    ]);
+
+    // The limit is shared between `/v1` and the unprefixed routes.
+    let exports = join_all(
+        ["/v1/todos/feed.atom", "/todos/calendar.ics", "/v1/todos/calendar.ics"]
+            .map(|uri| client.get(uri)),
+    );
+    let searches =
+        join_all(["/v1/todos/search?q=a", "/todos/search?q=b"].map(|uri| client.get(uri)));
+    let (exports, searches) = tokio::join!(exports, searches);
+
+    assert_eq!(shed(&exports).len(), 2);
+    assert!(searches.iter().all(|res| res.status == StatusCode::OK));
+}
+
+#[tokio::test]
+async fn no_limit_by_default() {
+    let client = client(&[]);
+    let responses = join_all((0..20).map(|_| client.get("/todos"))).await;
+    assert!(shed(&responses).is_empty());
+}
@@ -0,0 +1,79 @@
+// Smoke tests for the `loadgen` binary: run it briefly against an in-process
+// server on an ephemeral port and check the report it prints.
+
+use std::{net::SocketAddr, process::Output};
+
+use rust_api::{app, config::Config, AppState};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+/// Serves `state` on an ephemeral port and returns its base URL.
+async fn serve(state: AppState) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = app(state).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+async fn loadgen(args: &[&str]) -> Output {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    tokio::task::spawn_blocking(move || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_loadgen"))
+            .args(args)
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_latencies_and_cleans_up() {
+    let state = AppState::new_in_memory();
+    let url = serve(state.clone()).await;
+    let output = loadgen(&[
+        "--url", &url, "--duration", "1s", "--concurrency", "4", "--seed", "20", "--cleanup",
+        "--json",
+    ])
+    .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["total"]["errors"], 0, "{report}");
+    assert!(report["total"]["count"].as_u64().unwrap() > 0, "{report}");
+    for op in ["list", "create", "delete"] {
+        let row = &report["ops"][op];
+        assert!(row["throughput"].is_number(), "{op}: {row}");
+        let (p50, p99) = (row["p50_ms"].as_f64().unwrap(), row["p99_ms"].as_f64().unwrap());
+        assert!(p50 <= p99, "{op}: {row}");
+    }
+    assert!(report["ops"]["list"]["count"].as_u64().unwrap() > 0, "{report}");
+
+    // Seeded and created todos were all deleted again.
+    assert!(state.service().repo().list().await.unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fails_when_the_error_rate_is_too_high() {
+    let config = Config::from_lookup(|key| (key == "READ_ONLY").then(|| "true".to_string()));
+    let url = serve(AppState::new_in_memory().with_config(config.unwrap())).await;
+    let output = loadgen(&[
+        "--url", &url, "--duration", "200ms", "--concurrency", "2", "--seed", "0", "--mix",
+        "list=1,create=1",
+    ])
+    .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exceeds the limit"), "{stderr}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("error rate"));
+}
+
+#[tokio::test]
+async fn rejects_bad_arguments() {
+    for args in [&["--mix", "list=0"][..], &["--duration", "soon"], &["--frobnicate"]] {
+        let output = loadgen(args).await;
+        assert!(!output.status.success(), "{args:?}");
+    }
+}
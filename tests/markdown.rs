@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, models::MAX_DESCRIPTION_BYTES, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn create(app: &Router, description: &str) -> Value {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "title": "notes", "description": description }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+async fn get(app: &Router, uri: &str) -> Value {
+    let res = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn renders_basic_formatting() {
+    let app = app(AppState::new_in_memory());
+    let source = "# Groceries\n\n- **milk**\n- _eggs_\n\n[shop](https://example.com)";
+    let created = create(&app, source).await;
+    assert!(created.get("description_html").is_none());
+
+    let todo = get(&app, "/todos/1?render=html").await;
+    assert_eq!(todo["description"], source);
+    let html = todo["description_html"].as_str().unwrap();
+    assert!(html.contains("<h1>Groceries</h1>"));
+    assert!(html.contains("<li><strong>milk</strong></li>"));
+    assert!(html.contains("<em>eggs</em>"));
+    assert!(html.contains("href=\"https://example.com\""));
+}
+
+#[tokio::test]
+async fn scripts_and_javascript_links_are_stripped() {
+    let app = app(AppState::new_in_memory());
+    let source = "hi <script>alert(1)</script> <img src=x onerror=alert(2)>\n\n\
+                  [click](javascript:alert(3))";
+    create(&app, source).await;
+
+    let todo = get(&app, "/todos/1?render=html").await;
+    let html = todo["description_html"].as_str().unwrap();
+    assert!(!html.contains("<script"), "{html}");
+    assert!(!html.contains("alert(1)"), "{html}");
+    assert!(!html.contains("onerror"), "{html}");
+    assert!(!html.contains("javascript:"), "{html}");
+    assert!(html.contains("click"));
+    // The stored source is untouched.
+    assert_eq!(todo["description"], source);
+}
+
+#[tokio::test]
+async fn omitting_render_skips_the_field() {
+    let app = app(AppState::new_in_memory());
+    create(&app, "*plain*").await;
+    let todo = get(&app, "/todos/1").await;
+    assert_eq!(todo["description"], "*plain*");
+    assert!(todo.get("description_html").is_none());
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/todos/1?render=pdf")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn pathological_input_renders_quickly() {
+    let app = app(AppState::new_in_memory());
+
+    let mut nested = String::new();
+    let mut depth = 0;
+    while nested.len() + depth * 2 + 4 < MAX_DESCRIPTION_BYTES / 2 {
+        nested.push_str(&"  ".repeat(depth));
+        nested.push_str("- x\n");
+        depth += 1;
+    }
+    let quotes = ">".repeat(MAX_DESCRIPTION_BYTES / 2 - 1);
+    let source = format!("{nested}\n{quotes}");
+    assert!(source.len() <= MAX_DESCRIPTION_BYTES);
+    create(&app, &source).await;
+
+    let started = Instant::now();
+    let todo = get(&app, "/todos/1?render=html").await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(todo["description_html"].as_str().unwrap().contains('x'));
+}
+
+#[tokio::test]
+async fn oversized_descriptions_are_rejected() {
+    let app = app(AppState::new_in_memory());
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({
+                        "title": "too long",
+                        "description": "a".repeat(MAX_DESCRIPTION_BYTES + 1),
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,207 @@
+// `GET /admin/export` and `POST /admin/import`: a faithful round trip between
+// stores, merge conflicts, and the admin gate.
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    config::Config,
+    models::{CreateTodo, DryRun, ImportMode, RunMode, DRY_RUN_MAX_IDS},
+    state::in_memory_repo,
+    test_utils::{seed, TestClient},
+    AppState,
+};
+use serde_json::{json, Value};
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    let config = Config::from_lookup(|key| {
+        vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    })
+    .unwrap();
+    TestClient::new(app(AppState::with_repo(in_memory_repo()).with_config(config)))
+}
+
+fn admin() -> TestClient {
+    client(&[("ENABLE_ADMIN_ENDPOINTS", "true")])
+}
+
+/// A store with `titles` created in order, so todo `n` is `titles[n - 1]`.
+async fn with_todos(titles: &[&str]) -> TestClient {
+    let client = admin();
+    for title in titles {
+        let res = client.post_json("/v1/todos", &json!({ "title": title })).await;
+        assert_eq!(res.status, StatusCode::CREATED);
+    }
+    client
+}
+
+#[tokio::test]
+async fn an_export_imports_into_a_fresh_store_unchanged() {
+    let source = with_todos(&["one", "two", "three", "four"]).await;
+    source.put_json("/v1/todos/2", &json!({ "done": true, "description": "half" })).await;
+    source.delete("/v1/todos/4").await;
+
+    let export = source.get("/admin/export").await;
+    assert_eq!(export.status, StatusCode::OK);
+    assert_eq!(export.body["schema_version"], 1);
+    assert_eq!(export.body["next_id"], 5);
+    assert_eq!(export.body["todos"].as_array().unwrap().len(), 3);
+
+    let target = admin();
+    let res = target.post_json("/admin/import?mode=replace", &export.body).await;
+    assert_eq!(res.status, StatusCode::OK);
+    let report = json!({ "mode": "replace", "imported": 3, "removed": [], "conflicts": [] });
+    assert_eq!(res.body, report);
+
+    assert_eq!(target.get("/admin/export").await.body, export.body);
+    assert_eq!(target.get("/v1/todos/2").await.body, source.get("/v1/todos/2").await.body);
+    // New todos continue after the source's, not after the highest imported id.
+    let res = target.post_json("/v1/todos", &json!({ "title": "five" })).await;
+    assert_eq!(res.body["id"], 5);
+}
+
+#[tokio::test]
+async fn replace_removes_todos_missing_from_the_snapshot() {
+    let export = with_todos(&["kept"]).await.get("/admin/export").await.body;
+    let target = with_todos(&["overwritten", "stale"]).await;
+
+    let res = target.post_json("/admin/import?mode=replace", &export).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["removed"], json!([2]));
+    let todos = target.get("/v1/todos").await.body;
+    assert_eq!(todos.as_array().unwrap().len(), 1);
+    assert_eq!(todos[0]["title"], "kept");
+}
+
+#[tokio::test]
+async fn merge_reports_conflicts_and_writes_nothing() {
+    let target = with_todos(&["a", "b", "c"]).await;
+    let before = target.get("/admin/export").await.body;
+    let snapshot = json!({
+        "schema_version": 1,
+        "todos": [todo(2, "clash"), todo(9, "new")],
+    });
+
+    let res = target.post_json("/admin/import?mode=merge", &snapshot).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    let report = json!({ "mode": "merge", "imported": 0, "removed": [], "conflicts": [2] });
+    assert_eq!(res.body, report);
+    assert_eq!(target.get("/admin/export").await.body, before);
+
+    // Without the clash the merge goes through and keeps the others.
+    let snapshot = json!({ "schema_version": 1, "todos": [todo(9, "new")] });
+    let res = target.post_json("/admin/import?mode=merge", &snapshot).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["imported"], 1);
+    assert_eq!(target.get("/v1/todos/9").await.body["title"], "new");
+    assert_eq!(target.get("/v1/todos/2").await.body["title"], "b");
+}
+
+#[tokio::test]
+async fn a_dry_run_reports_what_the_import_then_does() {
+    let export = with_todos(&["kept", "new"]).await.get("/admin/export").await.body;
+    let target = with_todos(&["overwritten", "stale", "staler"]).await;
+    let before = target.get("/admin/export").await.body;
+    let events = target.get("/v1/todos/events/log").await.body;
+
+    let res = target.post_json("/admin/import?mode=replace&dry_run=true", &export).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body, json!({ "dry_run": true, "would_affect": 3, "ids": [1, 2, 3] }));
+    assert_eq!(target.get("/admin/export").await.body, before);
+    assert_eq!(target.get("/v1/todos/events/log").await.body, events);
+
+    let res = target.post_json("/admin/import?mode=replace", &export).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["removed"], json!([3]));
+    assert_eq!(res.body["imported"], 2);
+    let after = target.get("/admin/export").await.body;
+    assert_ne!(after, before);
+    let ids = |export: &Value| -> Vec<u64> {
+        let todos = export["todos"].as_array().unwrap();
+        todos.iter().map(|todo| todo["id"].as_u64().unwrap()).collect()
+    };
+    assert_eq!(ids(&after), [1, 2]);
+}
+
+#[tokio::test]
+async fn a_dry_run_merge_reports_conflicts_like_the_merge() {
+    let target = with_todos(&["a", "b"]).await;
+    let before = target.get("/admin/export").await.body;
+    let snapshot = json!({
+        "schema_version": 1,
+        "todos": [todo(2, "clash"), todo(9, "new")],
+    });
+
+    let res = target.post_json("/admin/import?mode=merge&dry_run=true", &snapshot).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.body["conflicts"], json!([2]));
+
+    let snapshot = json!({ "schema_version": 1, "todos": [todo(9, "new"), todo(7, "newer")] });
+    let res = target.post_json("/admin/import?mode=merge&dry_run=true", &snapshot).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.json::<DryRun>(), DryRun::new(vec![7, 9]));
+    assert_eq!(target.get("/admin/export").await.body, before);
+    assert_eq!(target.get("/v1/todos/9").await.status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn dry_runs_count_every_id_but_list_a_capped_few() {
+    let ids: Vec<u64> = (1..=1500).rev().chain([7, 7]).collect();
+    let dry_run = DryRun::new(ids);
+    assert!(dry_run.dry_run);
+    assert_eq!(dry_run.would_affect, 1500);
+    assert_eq!(dry_run.ids.len(), DRY_RUN_MAX_IDS);
+    assert_eq!(dry_run.ids[..3], [1, 2, 3]);
+}
+
+#[tokio::test]
+async fn invalid_snapshots_are_rejected() {
+    let client = admin();
+    for (version, todos, message) in [
+        (2, vec![], "unsupported schema_version 2"),
+        (1, vec![todo(0, "x")], "at least 1"),
+        (1, vec![todo(3, "x"), todo(3, "y")], "more than once"),
+    ] {
+        let snapshot = json!({ "schema_version": version, "todos": todos });
+        let res = client.post_json("/admin/import?mode=merge", &snapshot).await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+        let error = res.body["error"].as_str().unwrap();
+        assert!(error.contains(message), "{error}");
+    }
+    let res = client.post_json("/admin/import?mode=overwrite", &json!({})).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn the_routes_need_admin_endpoints() {
+    let client = client(&[]);
+    assert_eq!(client.get("/admin/export").await.status, StatusCode::NOT_FOUND);
+    let snapshot = json!({ "schema_version": 1, "todos": [] });
+    let res = client.post_json("/admin/import?mode=merge", &snapshot).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_repository_round_trips_its_own_export() {
+    let source = in_memory_repo();
+    seed(source.as_ref(), ["a", "b"].map(|title| CreateTodo::new(title.parse().unwrap())))
+    .await;
+
+    let snapshot = source.export_all().await.unwrap();
+    let target = in_memory_repo();
+    target.import_all(snapshot.clone(), ImportMode::Merge, RunMode::Apply).await.unwrap();
+    let copy = target.export_all().await.unwrap();
+    assert_eq!(copy.next_id, Some(3));
+    assert_eq!(serde_json::to_value(copy).unwrap(), serde_json::to_value(snapshot).unwrap());
+}
+
+/// A todo as an export writes it.
+fn todo(id: u64, title: &str) -> Value {
+    json!({
+        "id": id,
+        "title": title,
+        "description": null,
+        "done": false,
+        "created_at": "2026-01-01T00:00:00Z",
+        "updated_at": "2026-01-01T00:00:00Z",
+    })
+}
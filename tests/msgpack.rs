@@ -0,0 +1,139 @@
+// MessagePack content negotiation: the same handlers speak JSON by default and
+// MessagePack when the client asks for it.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, models::Todo, AppState};
+use serde::Deserialize;
+use serde_json::json;
+use tower::ServiceExt;
+
+const MSGPACK: &str = "application/msgpack";
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, Option<String>, Vec<u8>) {
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = res.into_body().collect().await.unwrap().to_bytes().to_vec();
+    (status, content_type, body)
+}
+
+#[tokio::test]
+async fn create_and_get_round_trip_in_msgpack() {
+    let app = app(AppState::new_in_memory());
+    let payload = rmp_serde::to_vec_named(&json!({ "title": "binary please" })).unwrap();
+
+    let (status, content_type, body) = send(
+        &app,
+        Request::builder()
+            .method("POST")
+            .uri("/todos")
+            .header(header::CONTENT_TYPE, MSGPACK)
+            .header(header::ACCEPT, MSGPACK)
+            .body(Body::from(payload))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(content_type.as_deref(), Some(MSGPACK));
+    let created: Todo = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(created.title, "binary please");
+
+    let (status, content_type, body) = send(
+        &app,
+        Request::builder()
+            .uri(format!("/todos/{}", created.id))
+            .header(header::ACCEPT, MSGPACK)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some(MSGPACK));
+    let fetched: Todo = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(fetched.id, created.id);
+
+    let (_, content_type, body) = send(
+        &app,
+        Request::builder()
+            .uri("/todos")
+            .header(header::ACCEPT, MSGPACK)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(content_type.as_deref(), Some(MSGPACK));
+    let listed: Vec<Todo> = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(listed.len(), 1);
+}
+
+#[tokio::test]
+async fn json_clients_are_unaffected() {
+    let app = app(AppState::new_in_memory());
+
+    let (status, content_type, body) = send(
+        &app,
+        Request::builder()
+            .method("POST")
+            .uri("/todos")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "*/*")
+            .body(Body::from(json!({ "title": "text please" }).to_string()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    let created: Todo = serde_json::from_slice(&body).unwrap();
+    assert_eq!(created.title, "text please");
+}
+
+#[tokio::test]
+async fn errors_follow_the_negotiated_format() {
+    let app = app(AppState::new_in_memory());
+
+    let (status, content_type, body) = send(
+        &app,
+        Request::builder()
+            .uri("/todos/77")
+            .header(header::ACCEPT, MSGPACK)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(content_type.as_deref(), Some(MSGPACK));
+    let error: ErrorBody = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(error.error, "not found");
+}
+
+#[tokio::test]
+async fn unknown_body_types_are_rejected_with_our_error_body() {
+    let app = app(AppState::new_in_memory());
+
+    let (status, _, body) = send(
+        &app,
+        Request::builder()
+            .method("POST")
+            .uri("/todos")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from("learn rust"))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+    assert!(error.error.contains("application/msgpack"));
+}
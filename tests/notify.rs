@@ -0,0 +1,168 @@
+// Email notifications are feature-gated; run with `cargo test --features email`.
+#![cfg(feature = "email")]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use futures::StreamExt;
+use rust_api::{
+    config::Config,
+    events::TodoEvent,
+    models::{CreateTodo, Todo, UpdateTodo},
+    notify::{Mailer, Notifier, MAX_ATTEMPTS},
+    reminders, AppState,
+};
+
+/// Records every message and fails the first `failures` sends.
+#[derive(Default)]
+struct Recorder {
+    sent: Mutex<Vec<(String, String, String)>>,
+    failures: Mutex<u32>,
+}
+
+impl Recorder {
+    fn failing(failures: u32) -> Arc<Self> {
+        Arc::new(Self {
+            failures: Mutex::new(failures),
+            ..Default::default()
+        })
+    }
+
+    fn sent(&self) -> Vec<(String, String, String)> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for Recorder {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()> {
+        let mut failures = self.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            anyhow::bail!("connection refused");
+        }
+        let message = (to.to_string(), subject.to_string(), body.to_string());
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
+
+fn notifier(mailer: &Arc<Recorder>) -> Notifier {
+    Notifier::new(mailer.clone())
+        .with_default_to(Some("household@example.com".to_string()))
+        .with_retry_delay(Duration::from_millis(1))
+}
+
+fn reminder(id: u64, title: &str, assignee: Option<&str>) -> TodoEvent {
+    TodoEvent::Reminder {
+        todo: Todo {
+            assignee: assignee.map(str::to_string),
+            ..Todo::new(id, title.parse().unwrap(), Utc::now())
+        },
+    }
+}
+
+#[tokio::test]
+async fn sends_one_email_per_reminder() {
+    let state = AppState::new_in_memory();
+    let mailer = Arc::new(Recorder::default());
+    let task = tokio::spawn(
+        notifier(&mailer).run(state.events().subscribe(), std::future::pending()),
+    );
+
+    let due = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    let input = CreateTodo {
+        description: Some("Bring two photos.".to_string()),
+        due: Some(due),
+        ..CreateTodo::new("Renew passport".parse().unwrap())
+    };
+    state.service().repo().create(input).await.unwrap();
+    reminders::tick(&state, due).await.unwrap();
+    reminders::tick(&state, due + chrono::Duration::hours(1)).await.unwrap();
+
+    // Dropping the last handle closes the bus; the notifier drains and exits.
+    drop(state);
+    task.await.unwrap();
+
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 1);
+    let (to, subject, body) = &sent[0];
+    assert_eq!(to, "household@example.com");
+    assert!(subject.contains("Renew passport"), "{subject}");
+    assert!(body.contains("2024-05-01 12:00 UTC"), "{body}");
+    assert!(body.contains("Bring two photos."), "{body}");
+}
+
+#[tokio::test]
+async fn assignment_emails_go_to_email_assignees() {
+    let state = AppState::new_in_memory();
+    let mailer = Arc::new(Recorder::default());
+    let notifier = Notifier::new(mailer.clone());
+    let mut events = state.events().subscribe();
+
+    let input = CreateTodo {
+        assignee: Some("sam@example.com".to_string()),
+        ..CreateTodo::new("Fix the sink".parse().unwrap())
+    };
+    state.service().repo().create(input).await.unwrap();
+    let input = UpdateTodo {
+        assignee: Some(Some("alex".to_string())),
+        ..Default::default()
+    };
+    state.service().repo().update(1, input).await.unwrap();
+    drop(state);
+
+    let mut handled = 0;
+    while let Some(event) = events.next().await {
+        handled += usize::from(notifier.handle(&event).await);
+    }
+    // `alex` is not an address and there is no default recipient.
+    assert_eq!(handled, 1);
+    let sent = mailer.sent();
+    assert_eq!(sent[0].0, "sam@example.com");
+    assert_eq!(sent[0].1, "Assigned: Fix the sink");
+    assert!(sent[0].2.contains("assigned to sam@example.com"));
+}
+
+#[tokio::test]
+async fn limits_emails_per_recipient() {
+    let mailer = Arc::new(Recorder::default());
+    let notifier = notifier(&mailer).with_max_per_hour(2);
+
+    for id in 1..=3 {
+        notifier.handle(&reminder(id, "water plants", None)).await;
+    }
+    assert!(notifier.handle(&reminder(4, "call", Some("kim@example.com"))).await);
+
+    let recipients: Vec<String> = mailer.sent().into_iter().map(|(to, _, _)| to).collect();
+    assert_eq!(
+        recipients,
+        ["household@example.com", "household@example.com", "kim@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn retries_failed_sends() {
+    let mailer = Recorder::failing(MAX_ATTEMPTS - 1);
+    assert!(notifier(&mailer).handle(&reminder(1, "pay rent", None)).await);
+    assert_eq!(mailer.sent().len(), 1);
+
+    let mailer = Recorder::failing(MAX_ATTEMPTS);
+    assert!(!notifier(&mailer).handle(&reminder(1, "pay rent", None)).await);
+    assert!(mailer.sent().is_empty());
+}
+
+#[test]
+fn inert_without_smtp_config() {
+    assert!(Notifier::from_config(&Config::default()).unwrap().is_none());
+
+    let err = Config::from_lookup(|key| {
+        (key == "SMTP_URL").then(|| "smtp://localhost".to_string())
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("SMTP_FROM"), "{err}");
+}
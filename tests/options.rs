@@ -0,0 +1,70 @@
+// `OPTIONS` without CORS headers lists a route's methods in `Allow`; CORS
+// preflights keep getting the `Access-Control-*` answer.
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use rust_api::{app, test_utils::TestClient, AppState};
+
+fn client() -> TestClient {
+    TestClient::new(app(AppState::new_in_memory()))
+}
+
+fn options(uri: &str) -> Request<Body> {
+    Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn methods(allow: &header::HeaderValue) -> Vec<&str> {
+    let mut methods: Vec<_> = allow.to_str().unwrap().split(',').map(str::trim).collect();
+    methods.sort_unstable();
+    methods
+}
+
+#[tokio::test]
+async fn plain_options_lists_the_registered_methods() {
+    let client = client();
+    for (uri, expected) in [
+        ("/todos", vec!["GET", "HEAD", "OPTIONS", "POST"]),
+        ("/todos/1", vec!["DELETE", "GET", "HEAD", "OPTIONS", "PUT"]),
+        ("/todos/batch", vec!["OPTIONS", "PATCH"]),
+    ] {
+        let res = client.send(options(uri)).await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT, "{uri}");
+        assert_eq!(methods(&res.headers[header::ALLOW]), expected, "{uri}");
+        assert!(!res.headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS), "{uri}");
+    }
+
+    // Unknown paths have no methods to list.
+    assert_eq!(client.send(options("/nope")).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn cors_preflights_are_unchanged() {
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/todos/1")
+        .header(header::ORIGIN, "https://app.example")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .body(Body::empty())
+        .unwrap();
+    let res = client().send(request).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example");
+    assert!(res.headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+}
+
+#[tokio::test]
+async fn other_methods_still_get_cors_headers() {
+    let request = Request::get("/todos")
+        .header(header::ORIGIN, "https://app.example")
+        .body(Body::empty())
+        .unwrap();
+    let res = client().send(request).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example");
+}
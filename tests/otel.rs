@@ -0,0 +1,47 @@
+// OpenTelemetry export is feature-gated; run with `cargo test --features otel`.
+#![cfg(feature = "otel")]
+
+use axum::{body::Body, http::Request};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+use rust_api::{app, telemetry, AppState};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+/// The server span is named after the route template and joins the caller's
+/// trace through the incoming `traceparent` header.
+#[tokio::test(flavor = "current_thread")]
+async fn request_span_joins_caller_trace() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    telemetry::install_propagator();
+
+    let subscriber = tracing_subscriber::registry().with(telemetry::otel_layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    app(AppState::new_in_memory())
+        .oneshot(
+            Request::builder()
+                .uri("/todos/42")
+                .header("traceparent", format!("00-{TRACE_ID}-{PARENT_ID}-01"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    provider.force_flush().unwrap();
+    let spans = exporter.get_finished_spans().unwrap();
+    let names: Vec<_> = spans.iter().map(|span| span.name.clone()).collect();
+    let span = spans
+        .iter()
+        .find(|span| span.name == "GET /todos/:id")
+        .unwrap_or_else(|| panic!("no route span in {names:?}"));
+
+    assert_eq!(span.span_context.trace_id().to_string(), TRACE_ID);
+    assert_eq!(span.parent_span_id.to_string(), PARENT_ID);
+}
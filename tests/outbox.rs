@@ -0,0 +1,205 @@
+// The webhook outbox: writes queue one entry per webhook, a receiver that is
+// down leaves them queued, and they drain in order once it is back. Entries
+// that keep failing end up in the dead letter list until an admin retries
+// them.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use rust_api::{
+    app,
+    config::Config,
+    events::TodoEvent,
+    outbox::{DeliveryState, OutboxEntry, WebhookSender},
+    test_utils::TestClient,
+    AppState,
+};
+use serde_json::json;
+
+const HOOK_A: &str = "http://hooks-a.test/todos";
+const HOOK_B: &str = "http://hooks-b.test/todos";
+
+/// Records what it was sent, or fails while `down` is set.
+#[derive(Default)]
+struct Receiver {
+    down: AtomicBool,
+    received: Mutex<Vec<(String, TodoEvent)>>,
+}
+
+#[async_trait]
+impl WebhookSender for Receiver {
+    async fn send(&self, entry: &OutboxEntry) -> anyhow::Result<()> {
+        if self.down.load(Ordering::SeqCst) {
+            bail!("connection refused");
+        }
+        let delivery = (entry.destination.clone(), entry.event.clone());
+        self.received.lock().unwrap().push(delivery);
+        Ok(())
+    }
+}
+
+impl Receiver {
+    fn titles(&self, destination: &str) -> Vec<String> {
+        let received = self.received.lock().unwrap();
+        received
+            .iter()
+            .filter(|(to, _)| to == destination)
+            .map(|(_, event)| match event {
+                TodoEvent::Created { todo } | TodoEvent::Updated { todo } => todo.title.to_string(),
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+}
+
+fn state(vars: &[(&str, &str)]) -> AppState {
+    let hooks = format!("{HOOK_A},{HOOK_B}");
+    let mut all = vec![
+        ("WEBHOOK_URLS", hooks.as_str()),
+        ("OUTBOX_MAX_ATTEMPTS", "3"),
+        ("OUTBOX_BACKOFF_MS", "1000"),
+        ("ADMIN_POLICY", "public"),
+    ];
+    all.extend_from_slice(vars);
+    let config = Config::from_lookup(|key| {
+        all.iter().rev().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    });
+    AppState::new_in_memory().with_config(config.unwrap())
+}
+
+#[tokio::test]
+async fn entries_wait_for_a_failing_receiver_and_drain_in_order() {
+    let state = state(&[]);
+    let client = TestClient::new(app(state.clone()));
+    let receiver = Receiver::default();
+    receiver.down.store(true, Ordering::SeqCst);
+
+    for title in ["first", "second", "third"] {
+        client.post_json("/v1/todos", &json!({ "title": title })).await;
+    }
+    let now = Utc::now();
+    assert_eq!(state.outbox().dispatch(&receiver, now).await, 0);
+    let pending = state.outbox().entries(Some(DeliveryState::Pending));
+    assert_eq!(pending.len(), 6);
+    assert_eq!(pending[0].attempts, 1);
+    assert_eq!(pending[0].last_error.as_deref(), Some("connection refused"));
+    // Only the head of each destination was tried.
+    assert_eq!(pending[1..].iter().filter(|entry| entry.attempts > 0).count(), 1);
+
+    // Back up, but the backoff hasn't passed yet.
+    receiver.down.store(false, Ordering::SeqCst);
+    assert_eq!(state.outbox().dispatch(&receiver, now).await, 0);
+
+    let later = now + Duration::seconds(1);
+    assert_eq!(state.outbox().dispatch(&receiver, later).await, 6);
+    assert_eq!(receiver.titles(HOOK_A), ["first", "second", "third"]);
+    assert_eq!(receiver.titles(HOOK_B), ["first", "second", "third"]);
+    assert!(state.outbox().entries(None).is_empty());
+}
+
+#[tokio::test]
+async fn entries_are_dead_lettered_and_can_be_retried() {
+    let state = state(&[("WEBHOOK_URLS", HOOK_A)]);
+    let client = TestClient::new(app(state.clone()));
+    let receiver = Receiver::default();
+    receiver.down.store(true, Ordering::SeqCst);
+    client.post_json("/v1/todos", &json!({ "title": "ship it" })).await;
+
+    // 3 attempts, 1s then 2s apart.
+    let start = Utc::now();
+    for after in [0, 1, 3] {
+        state.outbox().dispatch(&receiver, start + Duration::seconds(after)).await;
+    }
+    let res = client.get("/admin/outbox?state=failed").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body[0]["attempts"], 3);
+    assert_eq!(res.body[0]["state"], "failed");
+    assert_eq!(client.get("/admin/outbox?state=pending").await.body, json!([]));
+    // Nothing more is tried, however long we wait.
+    state.outbox().dispatch(&receiver, start + Duration::days(1)).await;
+    assert_eq!(state.outbox().entries(None)[0].attempts, 3);
+
+    let id = res.body[0]["id"].as_u64().unwrap();
+    let res = client.post_json(&format!("/admin/outbox/{id}/retry"), &json!({})).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["state"], "pending");
+    assert_eq!(res.body["attempts"], 0);
+
+    receiver.down.store(false, Ordering::SeqCst);
+    assert_eq!(state.outbox().dispatch(&receiver, start + Duration::days(1)).await, 1);
+    assert_eq!(receiver.titles(HOOK_A), ["ship it"]);
+    let res = client.post_json(&format!("/admin/outbox/{id}/retry"), &json!({})).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_journal_keeps_undelivered_entries_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.jsonl");
+
+    let state = state(&[]);
+    assert_eq!(state.outbox().load(&path).unwrap(), 0);
+    let client = TestClient::new(app(state.clone()));
+    client.post_json("/v1/todos", &json!({ "title": "first" })).await;
+    client.post_json("/v1/todos", &json!({ "title": "second" })).await;
+    let receiver = Receiver::default();
+    receiver.down.store(true, Ordering::SeqCst);
+    state.outbox().dispatch(&receiver, Utc::now()).await;
+    drop((client, state));
+
+    let restarted = self::state(&[]);
+    assert_eq!(restarted.outbox().load(&path).unwrap(), 4);
+    let entries = restarted.outbox().entries(None);
+    assert_eq!(entries.iter().map(|entry| entry.attempts).sum::<u32>(), 2);
+
+    receiver.down.store(false, Ordering::SeqCst);
+    let later = Utc::now() + Duration::seconds(1);
+    assert_eq!(restarted.outbox().dispatch(&receiver, later).await, 4);
+    assert_eq!(receiver.titles(HOOK_B), ["first", "second"]);
+
+    // Delivered entries don't come back.
+    assert_eq!(self::state(&[]).outbox().load(&path).unwrap(), 0);
+}
+
+#[tokio::test]
+async fn ids_are_not_reissued_after_the_queue_drains_and_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.jsonl");
+    let receiver = Receiver::default();
+
+    let state = state(&[]);
+    state.outbox().load(&path).unwrap();
+    let client = TestClient::new(app(state.clone()));
+    client.post_json("/v1/todos", &json!({ "title": "first" })).await;
+    let issued: Vec<u64> = state.outbox().entries(None).iter().map(|entry| entry.id).collect();
+    assert_eq!(state.outbox().dispatch(&receiver, Utc::now()).await, 2);
+    assert!(state.outbox().entries(None).is_empty());
+    drop((client, state));
+
+    let restarted = self::state(&[]);
+    assert_eq!(restarted.outbox().load(&path).unwrap(), 0);
+    let client = TestClient::new(app(restarted.clone()));
+    client.post_json("/v1/todos", &json!({ "title": "second" })).await;
+    let highest = issued.into_iter().max().unwrap();
+    let entries = restarted.outbox().entries(None);
+    assert_eq!(entries.len(), 2);
+    let reused: Vec<u64> =
+        entries.iter().map(|entry| entry.id).filter(|&id| id <= highest).collect();
+    assert!(reused.is_empty(), "reissued {reused:?}, at or below {highest}");
+}
+
+#[test]
+fn webhook_urls_must_be_plain_http() {
+    let config =
+        |url: &str| Config::from_lookup(|key| (key == "WEBHOOK_URLS").then(|| url.to_string()));
+    assert!(config("http://hooks.test/todos, http://other.test:8080/").is_ok());
+    let err = format!("{:#}", config("https://hooks.test/todos").unwrap_err());
+    assert!(err.contains("WEBHOOK_URLS"), "{err}");
+    assert!(config("not a url").is_err());
+}
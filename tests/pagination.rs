@@ -0,0 +1,230 @@
+// `GET /todos?limit=&offset=` returns one page in id order, with the total and
+// navigation links in headers. Every paginated endpoint validates and caps
+// `limit` the same way.
+
+mod test_support;
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, config::Config, models::CreateTodo, models::Todo, AppState};
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn seeded(count: usize) -> Router {
+    seeded_with(&[], count).await
+}
+
+async fn seeded_with(vars: &[(&str, &str)], count: usize) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let state = AppState::new_in_memory().with_config(config);
+    test_support::seed(state.service().repo().as_ref(), count).await;
+    app(state)
+}
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let headers = res.headers().clone();
+    (status, headers, res.into_body().collect().await.unwrap().to_bytes().to_vec())
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, HeaderMap, Vec<u8>) {
+    send(app, Request::builder().uri(uri).body(Body::empty()).unwrap()).await
+}
+
+/// The `rel -> url` pairs of a `Link` header.
+fn links(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers[header::LINK]
+        .to_str()
+        .unwrap()
+        .split(", ")
+        .map(|link| {
+            let (url, rel) = link.split_once("; ").unwrap();
+            let url = url.trim_start_matches('<').trim_end_matches('>');
+            let rel = rel.trim_start_matches("rel=\"").trim_end_matches('"');
+            (rel.to_string(), url.to_string())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn returns_the_requested_slice() {
+    let app = seeded(10).await;
+
+    let (status, _, body) = get(&app, "/todos?limit=3&offset=4").await;
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<u64> = serde_json::from_slice::<Vec<Todo>>(&body)
+        .unwrap()
+        .iter()
+        .map(|todo| todo.id)
+        .collect();
+    assert_eq!(ids, [5, 6, 7]);
+
+    // Past the end is an empty page, not an error.
+    let (status, _, body) = get(&app, "/todos?offset=50").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"[]");
+}
+
+#[tokio::test]
+async fn rejects_bad_limits() {
+    let app = seeded(1).await;
+    for uri in ["/todos?limit=0", "/todos?limit=-5", "/todos?limit=ten"] {
+        let (status, _, _) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn middle_page_links_every_relation_and_keeps_filters() {
+    let app = seeded(25).await;
+    // Titles are "todo 0".."todo 24"; `q=1` keeps 1, 10-19 and 21.
+    let (status, headers, body) = get(&app, "/todos?q=1&limit=4&offset=4").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_slice::<Vec<Todo>>(&body).unwrap().len(), 4);
+    assert_eq!(headers["x-total-count"], "12");
+    assert_eq!(
+        links(&headers),
+        [
+            ("first", "/todos?q=1&limit=4&offset=0"),
+            ("prev", "/todos?q=1&limit=4&offset=0"),
+            ("next", "/todos?q=1&limit=4&offset=8"),
+            ("last", "/todos?q=1&limit=4&offset=8"),
+        ]
+        .map(|(rel, url)| (rel.to_string(), url.to_string()))
+    );
+}
+
+#[tokio::test]
+async fn edge_pages_omit_prev_and_next() {
+    let app = seeded(3).await;
+    let (_, headers, _) = get(&app, "/todos?limit=5").await;
+    let rels: Vec<String> = links(&headers).into_iter().map(|(rel, _)| rel).collect();
+    assert_eq!(rels, ["first", "last"]);
+    assert_eq!(headers["x-total-count"], "3");
+}
+
+#[tokio::test]
+async fn done_filter_applies_to_pages_and_counts() {
+    let state = AppState::new_in_memory();
+    for i in 0..6 {
+        let todo = state
+            .service().repo()
+            .create(CreateTodo::new(format!("todo {i}").parse().unwrap()))
+            .await
+            .unwrap();
+        if i % 3 == 0 {
+            state
+                .service().repo()
+                .update(
+                    todo.id,
+                    rust_api::models::UpdateTodo {
+                        done: Some(true),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+    }
+    let app = app(state);
+
+    let (_, headers, body) = get(&app, "/todos?done=true&limit=10").await;
+    let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+    assert!(todos.iter().all(|todo| todo.done));
+    assert_eq!(headers["x-total-count"], "2");
+}
+
+#[tokio::test]
+async fn browsers_may_read_the_pagination_headers() {
+    let app = seeded(3).await;
+    let (_, headers, _) = send(
+        &app,
+        Request::builder()
+            .uri("/todos?limit=1")
+            .header(header::ORIGIN, "https://admin.example")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    // `*` exposes every header to non-credentialed requests, which is all our
+    // CORS policy allows.
+    assert_eq!(headers[header::ACCESS_CONTROL_EXPOSE_HEADERS], "*");
+}
+
+#[tokio::test]
+async fn huge_offsets_return_an_empty_page() {
+    let app = seeded(3).await;
+    let uri = format!("/todos?limit=1000&offset={}", usize::MAX);
+    let (status, headers, body) = get(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"[]");
+    assert!(!links(&headers).iter().any(|(rel, _)| rel == "next"));
+}
+
+#[tokio::test]
+async fn oversized_limits_are_clamped_and_flagged() {
+    let app = seeded_with(&[("MAX_PAGE_SIZE", "3")], 5).await;
+
+    let (status, headers, body) = get(&app, "/todos?limit=10").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_slice::<Vec<Todo>>(&body).unwrap().len(), 3);
+    assert_eq!(headers["x-page-size-clamped"], "true");
+    // The links describe the page actually returned.
+    assert!(links(&headers).contains(&("next".into(), "/todos?limit=3&offset=3".into())));
+
+    let (status, headers, body) = get(&app, "/todos/search?q=todo&limit=10").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_slice::<Vec<Value>>(&body).unwrap().len(), 3);
+    assert_eq!(headers["x-page-size-clamped"], "true");
+
+    let (_, headers, _) = get(&app, "/todos?limit=3").await;
+    assert!(!headers.contains_key("x-page-size-clamped"));
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_oversized_limits() {
+    let app = seeded_with(&[("MAX_PAGE_SIZE", "3"), ("STRICT_PAGINATION", "true")], 5).await;
+
+    for uri in ["/todos?limit=4", "/todos/search?q=todo&limit=4"] {
+        let (status, _, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let expected = "validation error: limit must be a whole number between 1 and 3";
+        assert_eq!(body["error"], expected, "{uri}");
+    }
+    let (status, _, _) = get(&app, "/todos?limit=3").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn zero_negative_and_non_numeric_values_name_the_valid_range() {
+    let app = seeded(1).await;
+
+    let cases = [
+        ("/todos?limit=0", "limit must be a whole number between 1 and 200"),
+        ("/todos?limit=-1", "limit must be a whole number between 1 and 200"),
+        ("/todos?limit=2.5", "limit must be a whole number between 1 and 200"),
+        ("/todos?offset=-10", "offset must be a whole number of at least 0"),
+        ("/todos?offset=first", "offset must be a whole number of at least 0"),
+        ("/todos/search?q=todo&limit=0", "limit must be a whole number between 1 and 200"),
+        ("/todos/search?q=todo&limit=-3", "limit must be a whole number between 1 and 200"),
+        ("/todos/search?q=todo&limit=many", "limit must be a whole number between 1 and 200"),
+    ];
+    for (uri, message) in cases {
+        let (status, _, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], format!("validation error: {message}"), "{uri}");
+    }
+}
@@ -0,0 +1,101 @@
+// Long polling: `GET /todos/poll?revision=` answers with what changed after
+// that revision, waiting for a change when there is none yet.
+
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    models::Changes,
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+
+fn changes(res: &TestResponse) -> Changes {
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    res.json()
+}
+
+async fn current_revision(client: &TestClient) -> u64 {
+    changes(&client.get("/v1/todos/changes").await).revision
+}
+
+#[tokio::test]
+async fn answers_at_once_when_already_behind() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    client.post_json("/v1/todos", &json!({ "title": "buy milk" })).await;
+
+    let started = Instant::now();
+    let res = client.get("/v1/todos/poll?revision=0&timeout_secs=10").await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let polled = changes(&res);
+    assert!(polled.revision > 0);
+    assert_eq!(polled.changed[0].title.as_str(), "buy milk");
+}
+
+#[tokio::test]
+async fn a_parked_poll_resolves_with_a_concurrent_create() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let revision = current_revision(&client).await;
+
+    let uri = format!("/v1/todos/poll?revision={revision}&timeout_secs=30");
+    let poll = client.get(&uri);
+    let create = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.post_json("/v1/todos", &json!({ "title": "water the plants" })).await
+    };
+    let started = Instant::now();
+    let (polled, created) = tokio::join!(poll, create);
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    let polled = changes(&polled);
+    assert!(polled.revision > revision);
+    assert_eq!(polled.changed.len(), 1);
+    assert_eq!(json!(polled.changed[0].id), created.body["id"]);
+}
+
+#[tokio::test]
+async fn times_out_with_nothing_changed() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    client.post_json("/v1/todos", &json!({ "title": "buy milk" })).await;
+    let revision = current_revision(&client).await;
+
+    let started = Instant::now();
+    let res = client.get(&format!("/v1/todos/poll?revision={revision}&timeout_secs=1")).await;
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_secs(1), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+
+    let polled = changes(&res);
+    assert_eq!(polled.revision, revision);
+    assert!(polled.changed.is_empty() && polled.deleted.is_empty());
+}
+
+#[tokio::test]
+async fn shutdown_releases_parked_polls() {
+    let state = AppState::new_in_memory();
+    let client = TestClient::new(app(state.clone()));
+    let revision = current_revision(&client).await;
+
+    let uri = format!("/v1/todos/poll?revision={revision}&timeout_secs=60");
+    let poll = client.get(&uri);
+    let shutdown = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        state.begin_shutdown();
+    };
+    let started = Instant::now();
+    let (res, ()) = tokio::join!(poll, shutdown);
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let polled = changes(&res);
+    assert_eq!(polled.revision, revision);
+    assert!(polled.changed.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_timeouts_past_the_limit() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let res = client.get("/v1/todos/poll?timeout_secs=3600").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert!(res.body["error"].as_str().unwrap().contains("timeout_secs"), "{}", res.body);
+}
@@ -0,0 +1,110 @@
+// `GET`/`PUT /preferences` and how saved preferences shape `GET /todos`.
+
+mod test_support;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, models::UpdateTodo, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+/// `count` todos (at least two), of which the second is done.
+async fn seeded(count: usize) -> Router {
+    let state = AppState::new_in_memory();
+    let repo = state.service().repo();
+    test_support::seed(repo.as_ref(), count).await;
+    let done = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    repo.update(2, done).await.unwrap();
+    app(state)
+}
+
+async fn send(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::ACCEPT, "application/json")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn ids(todos: &Value) -> Vec<u64> {
+    todos.as_array().unwrap().iter().map(|todo| todo["id"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn defaults_are_returned_before_anything_is_saved() {
+    let app = seeded(2).await;
+
+    let (status, body) = send(&app, Method::GET, "/preferences", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "default_sort": "id",
+            "default_order": "asc",
+            "page_size": null,
+            "hide_completed": false,
+        })
+    );
+}
+
+#[tokio::test]
+async fn saved_preferences_shape_lists_that_leave_them_out() {
+    let app = seeded(5).await;
+
+    let saved = json!({ "default_order": "desc", "page_size": 2, "hide_completed": true });
+    let (status, body) = send(&app, Method::PUT, "/preferences", Some(saved)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["page_size"], 2);
+    let (_, body) = send(&app, Method::GET, "/preferences", None).await;
+    assert_eq!(body["default_order"], "desc");
+
+    // Todo 2 is done, so it's hidden.
+    let (status, todos) = send(&app, Method::GET, "/todos", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&todos), [5, 4]);
+}
+
+#[tokio::test]
+async fn query_parameters_override_preferences() {
+    let app = seeded(5).await;
+
+    let saved = json!({ "default_sort": "title", "default_order": "desc", "hide_completed": true });
+    send(&app, Method::PUT, "/preferences", Some(saved)).await;
+
+    let (_, todos) = send(&app, Method::GET, "/todos?done=true", None).await;
+    assert_eq!(ids(&todos), [2]);
+    let (_, todos) = send(&app, Method::GET, "/todos?sort=id&order=asc&limit=3", None).await;
+    assert_eq!(ids(&todos), [1, 3, 4]);
+    let (_, todos) = send(&app, Method::GET, "/todos?order=asc", None).await;
+    assert_eq!(ids(&todos), [1, 3, 4, 5]);
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_rejected() {
+    let app = seeded(2).await;
+
+    let (status, _) =
+        send(&app, Method::PUT, "/preferences", Some(json!({ "default_sort": "colour" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) =
+        send(&app, Method::PUT, "/preferences", Some(json!({ "page_size": 0 }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("page_size"), "{body}");
+
+    let (_, body) = send(&app, Method::GET, "/preferences", None).await;
+    assert_eq!(body["page_size"], Value::Null);
+}
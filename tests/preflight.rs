@@ -0,0 +1,93 @@
+// The startup self-check: `preflight::run` on its own, and the binary's
+// `--check` mode end to end.
+
+use std::path::PathBuf;
+
+use rust_api::{
+    config::Config,
+    errors::AppError,
+    preflight,
+    state::in_memory_repo,
+    test_utils::{MockRepo, RepoMethod},
+};
+
+/// A path of our own under the temp directory.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rust-api-preflight-{name}-{}", std::process::id()))
+}
+
+/// A directory path that can't be created because a file is in the way.
+fn blocked_dir(name: &str) -> PathBuf {
+    let file = temp_path(name);
+    std::fs::write(&file, b"not a directory").unwrap();
+    file.join("attachments")
+}
+
+#[tokio::test]
+async fn passes_with_a_writable_dir_and_a_healthy_repo() {
+    let dir = temp_path("ok");
+    let config = Config {
+        attachment_dir: dir.clone(),
+        ..Config::default()
+    };
+    let report = preflight::run(&config, in_memory_repo().as_ref()).await;
+    assert!(report.passed(), "{report}");
+    let names: Vec<_> = report.checks.iter().map(|check| check.name).collect();
+    assert_eq!(names, ["repository", "attachment_dir"]);
+    // The probe file is cleaned up again.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    std::fs::remove_dir(dir).unwrap();
+}
+
+#[tokio::test]
+async fn reports_every_failure() {
+    let config = Config {
+        attachment_dir: blocked_dir("fail"),
+        ..Config::default()
+    };
+    let repo = MockRepo::new();
+    repo.fail_next(RepoMethod::Ping, AppError::Unavailable);
+
+    let report = preflight::run(&config, &repo).await;
+    assert!(!report.passed());
+    let failed: Vec<_> = report.failures().map(|check| check.name).collect();
+    assert_eq!(failed, ["repository", "attachment_dir"]);
+
+    let printed = report.to_string();
+    assert!(printed.contains("FAIL  repository: service temporarily unavailable"), "{printed}");
+    let blocked = format!("FAIL  attachment_dir: {}", config.attachment_dir.display());
+    assert!(printed.contains(&blocked), "{printed}");
+    std::fs::remove_file(temp_path("fail")).unwrap();
+}
+
+fn check(envs: &[(&str, &str)], args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_rust-api"))
+        .args(args)
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn check_mode_exits_without_serving() {
+    let dir = temp_path("bin");
+    let dir = dir.to_str().unwrap();
+    // Port 1 would need privileges to bind; the check must not try.
+    let output = check(&[("ATTACHMENT_DIR", dir), ("PORT", "1")], &["--check"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("ok    config") && stdout.contains("ok    repository"), "{stdout}");
+
+    let blocked = blocked_dir("bin-blocked");
+    let envs = [("ATTACHMENT_DIR", blocked.to_str().unwrap()), ("APP_CHECK", "1")];
+    let output = check(&envs, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("FAIL  attachment_dir"));
+
+    let output = check(&[("APP_CHECK", "true"), ("PORT", "not a port")], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("FAIL  config"));
+
+    std::fs::remove_dir(temp_path("bin")).unwrap();
+    std::fs::remove_file(temp_path("bin-blocked")).unwrap();
+}
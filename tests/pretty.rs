@@ -0,0 +1,108 @@
+// `?pretty=true` and `PRETTY_JSON_DEFAULT`: indented JSON that matches
+// `serde_json::to_string_pretty`, without touching other formats.
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, Request},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    config::Config,
+    models::{CreateTodo, Todo},
+    streaming::CHUNK_SIZE,
+    test_utils::seed,
+    AppState,
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn seeded(count: usize, config: Config) -> (Router, Vec<Todo>) {
+    let state = AppState::new_in_memory().with_config(config);
+    let inputs = (0..count).map(|i| CreateTodo {
+        description: Some("line one\nline two".to_string()),
+        ..CreateTodo::new(format!("todo \"{i}\": [x], {{y}}").parse().unwrap())
+    });
+    let todos = seed(state.service().repo().as_ref(), inputs).await;
+    (app(state), todos)
+}
+
+async fn fetch(app: &Router, uri: &str, accept: &str) -> (HeaderMap, Bytes) {
+    let request = Request::get(uri)
+        .header(header::ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(request).await.unwrap();
+    let headers = res.headers().clone();
+    (headers, res.into_body().collect().await.unwrap().to_bytes())
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .map(|value| value.to_str().unwrap().parse().unwrap())
+}
+
+#[tokio::test]
+async fn pretty_matches_to_string_pretty() {
+    let (app, todos) = seeded(2, Config::default()).await;
+    let uri = format!("/todos/{}", todos[0].id);
+
+    let (headers, compact) = fetch(&app, &uri, "application/json").await;
+    assert_eq!(compact, serde_json::to_vec(&todos[0]).unwrap());
+    assert!(!compact.contains(&b'\n'));
+
+    let (headers_pretty, pretty) = fetch(&app, &format!("{uri}?pretty=true"), "*/*").await;
+    assert_eq!(pretty, serde_json::to_string_pretty(&todos[0]).unwrap());
+    assert!(pretty.starts_with(b"{\n  \"id\": "));
+    assert_eq!(headers_pretty[header::CONTENT_TYPE], headers[header::CONTENT_TYPE]);
+    assert_eq!(content_length(&headers_pretty), Some(pretty.len()));
+
+    let (_, list) = fetch(&app, "/todos?pretty=true", "application/json").await;
+    assert_eq!(list, serde_json::to_string_pretty(&todos).unwrap());
+    let (_, empty) = fetch(&app, "/todos?pretty=true&done=true", "application/json").await;
+    assert_eq!(empty, "[]");
+}
+
+#[tokio::test]
+async fn streamed_lists_and_errors_are_indented_too() {
+    let (app, todos) = seeded(CHUNK_SIZE + 1, Config::default()).await;
+
+    let (_, list) = fetch(&app, "/todos?pretty=true", "application/json").await;
+    assert_eq!(list, serde_json::to_string_pretty(&todos).unwrap());
+
+    let (headers, error) = fetch(&app, "/todos/999999?pretty=true", "application/json").await;
+    let expected = serde_json::to_string_pretty(&json!({ "error": "not found" })).unwrap();
+    assert_eq!(error, expected);
+    assert_eq!(content_length(&headers), Some(error.len()));
+}
+
+#[tokio::test]
+async fn other_formats_are_left_alone() {
+    let (app, todos) = seeded(1, Config::default()).await;
+    let uri = format!("/todos/{}", todos[0].id);
+
+    let (_, plain) = fetch(&app, &uri, "application/msgpack").await;
+    let (_, pretty) = fetch(&app, &format!("{uri}?pretty=true"), "application/msgpack").await;
+    assert_eq!(plain, pretty);
+
+    let (_, plain) = fetch(&app, &uri, "text/plain").await;
+    let (_, pretty) = fetch(&app, &format!("{uri}?pretty=true"), "text/plain").await;
+    assert_eq!(plain, pretty);
+}
+
+#[tokio::test]
+async fn the_config_default_can_be_overridden_per_request() {
+    let config = Config {
+        pretty_json_default: true,
+        ..Config::default()
+    };
+    let (app, todos) = seeded(1, config).await;
+    let uri = format!("/todos/{}", todos[0].id);
+
+    let (_, pretty) = fetch(&app, &uri, "application/json").await;
+    assert_eq!(pretty, serde_json::to_string_pretty(&todos[0]).unwrap());
+    let (_, compact) = fetch(&app, &format!("{uri}?pretty=false"), "application/json").await;
+    assert_eq!(compact, serde_json::to_vec(&todos[0]).unwrap());
+}
@@ -0,0 +1,188 @@
+// Property-based tests for validation and repository invariants. Failing
+// cases are shrunk and printed; proptest also records them under
+// `proptest-regressions/` so they are retried first on the next run.
+
+mod test_support;
+
+use std::collections::BTreeMap;
+
+use proptest::prelude::*;
+use rust_api::{
+    errors::AppError,
+    models::{canonical_key, CreateTodo, Title, UpdateTodo, MAX_TITLE_CHARS},
+    state::in_memory_repo,
+};
+use test_support::{block_on, long_title, Op};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// `text` with its ASCII in fullwidth forms, as East Asian keyboards type it.
+fn fullwidth(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap(),
+            _ => c,
+        })
+        .collect()
+}
+
+fn acceptable(title: &str) -> bool {
+    let title = title.trim();
+    let collapsed = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    !title.is_empty()
+        && collapsed.graphemes(true).count() <= MAX_TITLE_CHARS
+        && !title.chars().any(char::is_control)
+}
+
+proptest! {
+    #[test]
+    fn titles_are_accepted_iff_length_in_range(
+        title in prop_oneof![any::<String>(), long_title()]
+    ) {
+        prop_assert_eq!(title.parse::<Title>().is_ok(), acceptable(&title));
+    }
+
+    #[test]
+    fn normalized_titles_round_trip(
+        title in prop_oneof![any::<String>(), long_title(), "[ a-z\u{a0}]{1,20}"]
+    ) {
+        let Ok(title) = title.parse::<Title>() else {
+            return Ok(());
+        };
+        prop_assert_eq!(&title.to_string().parse::<Title>().unwrap(), &title);
+        let json = serde_json::to_string(&title).unwrap();
+        prop_assert_eq!(&serde_json::from_str::<Title>(&json).unwrap(), &title);
+    }
+
+    #[test]
+    fn canonical_keys_are_idempotent(text in any::<String>()) {
+        let key = canonical_key(&text);
+        prop_assert_eq!(canonical_key(&key), key);
+    }
+
+    #[test]
+    fn case_width_composition_and_spacing_share_a_key(
+        text in "[a-zA-Z0-9ßéüñÅ ,.!]{0,20}",
+        padding in "[ \t\u{a0}]{1,3}",
+    ) {
+        let key = canonical_key(&text);
+        prop_assert_eq!(&canonical_key(&text.to_uppercase()), &key);
+        prop_assert_eq!(&canonical_key(&text.to_lowercase()), &key);
+        prop_assert_eq!(&canonical_key(&fullwidth(&text)), &key);
+        prop_assert_eq!(&canonical_key(&text.nfd().collect::<String>()), &key);
+        let spaced = format!("{padding}{}{padding}", text.replace(' ', &padding));
+        prop_assert_eq!(&canonical_key(&spaced), &key);
+    }
+
+    #[test]
+    fn repo_invariants_hold(ops in prop::collection::vec(any::<Op>(), 1..40)) {
+        block_on(check_ops(ops))?;
+    }
+}
+
+/// Applies `ops` to the in-memory repo and to a trivial model of it,
+/// checking after every step that they agree.
+async fn check_ops(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let repo = in_memory_repo();
+    // id -> (title, done) for the todos that should exist.
+    let mut model: BTreeMap<u64, (Title, bool)> = BTreeMap::new();
+    let mut created: Vec<u64> = Vec::new();
+    let (mut creates, mut deletes) = (0, 0);
+    let slot = |created: &[u64], slot: usize| created[slot % created.len()];
+
+    for op in ops {
+        match op {
+            Op::Create { title } => {
+                let input = CreateTodo::new(title.clone());
+                let todo = repo.create(input).await.map_err(fail)?;
+                prop_assert!(!model.contains_key(&todo.id), "id {} reused", todo.id);
+                let fetched = repo.get(todo.id).await.map_err(fail)?;
+                prop_assert_eq!(&fetched.title, &title);
+                model.insert(todo.id, (title, false));
+                created.push(todo.id);
+                creates += 1;
+            }
+            Op::Rename { .. } | Op::Toggle { .. } | Op::Delete { .. } if created.is_empty() => {}
+            Op::Rename { slot: n, title } => {
+                let id = slot(&created, n);
+                let update = UpdateTodo {
+                    title: Some(title.clone()),
+                    ..Default::default()
+                };
+                match (repo.update(id, update).await, model.get_mut(&id)) {
+                    (Ok(todo), Some(entry)) => {
+                        prop_assert_eq!(&todo.title, &title);
+                        entry.0 = title;
+                    }
+                    (Err(AppError::NotFound), None) => {}
+                    (result, entry) => {
+                        prop_assert!(false, "rename {id}: {result:?} vs {entry:?}")
+                    }
+                }
+            }
+            Op::Toggle { slot: n } => {
+                let id = slot(&created, n);
+                let done = model.get(&id).is_some_and(|(_, done)| !done);
+                let update = UpdateTodo {
+                    done: Some(done),
+                    ..Default::default()
+                };
+                match (repo.update(id, update).await, model.get_mut(&id)) {
+                    (Ok(todo), Some(entry)) => {
+                        prop_assert_eq!(todo.done, done);
+                        entry.1 = done;
+                    }
+                    (Err(AppError::NotFound), None) => {}
+                    (result, entry) => {
+                        prop_assert!(false, "toggle {id}: {result:?} vs {entry:?}")
+                    }
+                }
+            }
+            Op::Delete { slot: n } => {
+                let id = slot(&created, n);
+                match (repo.delete(id).await, model.remove(&id)) {
+                    (Ok(()), Some(_)) => deletes += 1,
+                    (Err(AppError::NotFound), None) => {}
+                    (result, entry) => {
+                        prop_assert!(false, "delete {id}: {result:?} vs {entry:?}")
+                    }
+                }
+            }
+        }
+
+        let listed = repo.list().await.map_err(fail)?;
+        prop_assert_eq!(listed.len(), creates - deletes);
+        prop_assert!(
+            listed.windows(2).all(|pair| pair[0].id < pair[1].id),
+            "ids not unique and ascending"
+        );
+        let actual: BTreeMap<u64, (Title, bool)> = listed
+            .into_iter()
+            .map(|todo| (todo.id, (todo.title, todo.done)))
+            .collect();
+        prop_assert_eq!(&actual, &model);
+    }
+    Ok(())
+}
+
+fn fail(err: AppError) -> TestCaseError {
+    TestCaseError::fail(err.to_string())
+}
+
+#[test]
+fn title_limit_counts_graphemes_not_bytes() {
+    let accepted = |title: String| title.parse::<Title>().is_ok();
+    assert!(accepted("é".repeat(MAX_TITLE_CHARS)));
+    assert!(accepted("e\u{301}".repeat(MAX_TITLE_CHARS)));
+    assert!(accepted(format!("  {}  ", "a".repeat(MAX_TITLE_CHARS))));
+    assert!(!accepted("日".repeat(MAX_TITLE_CHARS + 1)));
+}
+
+#[test]
+fn title_limit_counts_whitespace_runs_as_one_space() {
+    let title: Title = format!("a{}b", " ".repeat(150)).parse().unwrap();
+    assert_eq!(title, "a b");
+    assert!(format!("{} b", "a".repeat(MAX_TITLE_CHARS - 1))
+        .parse::<Title>()
+        .is_err());
+}
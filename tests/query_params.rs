@@ -0,0 +1,94 @@
+// Repeated query parameters: scalars may appear once, `ids` and `fields`
+// accumulate up to a cap.
+
+mod test_support;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use rust_api::{app, query::MAX_REPEATED_VALUES, AppState};
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn seeded(count: usize) -> Router {
+    let state = AppState::new_in_memory();
+    test_support::seed(state.service().repo().as_ref(), count).await;
+    app(state)
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .uri(uri)
+        .header(header::ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn ids(todos: &Value) -> Vec<u64> {
+    todos.as_array().unwrap().iter().map(|todo| todo["id"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn repeated_scalars_are_rejected_by_name() {
+    let app = seeded(2).await;
+
+    for (uri, name) in [
+        ("/todos?done=true&done=false", "done"),
+        ("/todos?limit=1&offset=0&limit=1", "limit"),
+        ("/todos/search?q=todo&q=other", "q"),
+        ("/todos/events/log?after_seq=0&after_seq=0", "after_seq"),
+    ] {
+        let (status, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains(&format!("`{name}` may only be given once")), "{message}");
+    }
+}
+
+#[tokio::test]
+async fn repeated_and_comma_separated_ids_combine() {
+    let app = seeded(6).await;
+
+    let (status, todos) = get(&app, "/todos?ids=1,3&ids=5").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&todos), [1, 3, 5]);
+
+    let (_, todos) = get(&app, "/todos?ids=2&ids=4,%206&limit=2").await;
+    assert_eq!(ids(&todos), [2, 4]);
+
+    let (status, body) = get(&app, "/todos?ids=1,two").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("`two`"), "{body}");
+}
+
+#[tokio::test]
+async fn repeated_fields_accumulate() {
+    let app = seeded(1).await;
+
+    let (status, body) = get(&app, "/todos?fields=title&fields=done").await;
+    assert_eq!(status, StatusCode::OK);
+    let keys: Vec<&str> = body[0].as_object().unwrap().keys().map(String::as_str).collect();
+    assert_eq!(keys, ["done", "id", "title"]);
+}
+
+#[tokio::test]
+async fn repeatable_parameters_are_capped() {
+    let app = seeded(1).await;
+
+    let at_cap: Vec<String> = (1..=MAX_REPEATED_VALUES).map(|id| id.to_string()).collect();
+    let (status, _) = get(&app, &format!("/todos?ids={}", at_cap.join(","))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/todos?ids={}&ids=0", at_cap.join(","));
+    let (status, body) = get(&app, &uri).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let message = body["error"].as_str().unwrap();
+    assert!(message.contains(&format!("at most {MAX_REPEATED_VALUES}")), "{message}");
+}
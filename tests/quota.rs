@@ -0,0 +1,169 @@
+// The `MAX_TODOS` quota: creates report how much is left in headers, warn
+// once little is, and are refused with the overage once nothing is.
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    config::Config,
+    quota::{LIMIT_HEADER, REMAINING_HEADER, WARNING_HEADER},
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+
+fn client(max_todos: &str, warning_percent: &str) -> TestClient {
+    client_with(&[("MAX_TODOS", max_todos), ("QUOTA_WARNING_PERCENT", warning_percent)])
+}
+
+fn client_with(vars: &[(&str, &str)]) -> TestClient {
+    let config = Config::from_lookup(|key| {
+        vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    });
+    TestClient::new(app(AppState::new_in_memory().with_config(config.unwrap())))
+}
+
+/// `(limit, remaining, warning)` from the quota headers.
+fn standing(res: &TestResponse) -> (&str, &str, bool) {
+    let header = |name| res.headers.get(name).map(|value| value.to_str().unwrap());
+    let warning = header(WARNING_HEADER).is_some_and(|value| value == "true");
+    (header(LIMIT_HEADER).unwrap(), header(REMAINING_HEADER).unwrap(), warning)
+}
+
+#[tokio::test]
+async fn headers_go_from_comfortable_to_exhausted() {
+    let client = client("4", "50");
+
+    let mut seen = Vec::new();
+    for n in 1..=4 {
+        let res = client.post_json("/v1/todos", &json!({ "title": format!("todo {n}") })).await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let (limit, remaining, warning) = standing(&res);
+        seen.push((limit.to_string(), remaining.to_string(), warning));
+    }
+    let expected = [("4", "3", false), ("4", "2", false), ("4", "1", true), ("4", "0", true)];
+    let expected = expected.map(|(limit, left, warning)| (limit.into(), left.into(), warning));
+    assert_eq!(seen, expected);
+
+    let res = client.post_json("/v1/todos", &json!({ "title": "one too many" })).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.body["error"], "quota exceeded: 1 over the limit of 4 todos");
+    assert_eq!(res.body["quota"], json!({ "limit": 4, "used": 4, "over": 1 }));
+    assert_eq!(client.get("/v1/todos").await.body.as_array().unwrap().len(), 4);
+
+    let res = client.get("/v1/todos/stats/timeseries").await;
+    assert_eq!(standing(&res), ("4", "0", true));
+
+    // Deleting frees a slot.
+    client.delete("/v1/todos/1").await;
+    let res = client.post_json("/v1/todos", &json!({ "title": "fits again" })).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn templates_report_how_far_over_they_would_go() {
+    let client = client("3", "10");
+    client.post_json("/v1/todos", &json!({ "title": "existing" })).await;
+    let items = vec![json!({ "title": "step" }); 4];
+    client.post_json("/v1/templates", &json!({ "name": "big", "items": items })).await;
+
+    let res = client.post_json("/v1/templates/1/instantiate", &json!({})).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.body["quota"]["over"], 2);
+    assert_eq!(client.get("/v1/todos").await.body.as_array().unwrap().len(), 1);
+
+    client.put_json("/v1/templates/1", &json!({ "name": "small", "items": [items[0]] })).await;
+    let res = client.post_json("/v1/templates/1/instantiate", &json!({})).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    assert_eq!(standing(&res), ("3", "1", false));
+}
+
+#[tokio::test]
+async fn concurrent_creates_cannot_overshoot_the_limit() {
+    let client = client("5", "10");
+    let creates = (0..20).map(|n| {
+        let client = &client;
+        async move { client.post_json("/v1/todos", &json!({ "title": format!("{n}") })).await }
+    });
+    let results = futures::future::join_all(creates).await;
+    let created = results.iter().filter(|res| res.status == StatusCode::CREATED).count();
+    assert_eq!(created, 5);
+    assert_eq!(client.get("/v1/todos").await.body.as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn puts_that_create_count_against_the_limit() {
+    let client = client_with(&[("MAX_TODOS", "2"), ("ALLOW_CLIENT_IDS", "true")]);
+    client.post_json("/v1/todos", &json!({ "title": "existing" })).await;
+
+    let res = client.put_json("/v1/todos/42", &json!({ "title": "offline" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(standing(&res), ("2", "0", true));
+
+    let res = client.put_json("/v1/todos/43", &json!({ "title": "one too many" })).await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+    assert_eq!(res.body["quota"], json!({ "limit": 2, "used": 2, "over": 1 }));
+    assert_eq!(client.get("/v1/todos/43").await.status, StatusCode::NOT_FOUND);
+
+    // Updating a todo that exists takes no slot.
+    let res = client.put_json("/v1/todos/42", &json!({ "done": true })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+}
+
+/// A quota of 2 holding one todo, and an export of it with its todo copied
+/// under each of `ids`.
+async fn merge_setup(ids: &[u64]) -> (TestClient, serde_json::Value) {
+    let client = client_with(&[("MAX_TODOS", "2"), ("ADMIN_POLICY", "public")]);
+    client.post_json("/v1/todos", &json!({ "title": "existing" })).await;
+    let mut snapshot = client.get("/admin/export").await.body;
+    let imported: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            let mut todo = snapshot["todos"][0].clone();
+            todo["id"] = json!(id);
+            todo["title"] = json!(format!("imported {id}"));
+            todo
+        })
+        .collect();
+    snapshot["todos"] = json!(imported);
+    (client, snapshot)
+}
+
+#[tokio::test]
+async fn merge_imports_must_fit() {
+    let (client, snapshot) = merge_setup(&[10, 11]).await;
+
+    let res = client.post_json("/admin/import?mode=merge", &snapshot).await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+    assert_eq!(res.body["quota"]["over"], 1);
+    assert_eq!(client.get("/v1/todos").await.body.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn merge_previews_are_refused_like_the_import() {
+    let (client, snapshot) = merge_setup(&[10, 11]).await;
+
+    let res = client.post_json("/admin/import?mode=merge&dry_run=true", &snapshot).await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+    assert_eq!(res.body["quota"]["over"], 1);
+}
+
+#[tokio::test]
+async fn merges_onto_taken_ids_report_conflicts_before_the_quota() {
+    let (client, snapshot) = merge_setup(&[1, 12]).await;
+
+    for uri in ["/admin/import?mode=merge", "/admin/import?mode=merge&dry_run=true"] {
+        let res = client.post_json(uri, &snapshot).await;
+        assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+        assert_eq!(res.body["conflicts"], json!([1]), "{uri}");
+        assert!(res.body.get("quota").is_none(), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn without_a_limit_there_are_no_headers() {
+    let client = client("0", "10");
+    let res = client.post_json("/v1/todos", &json!({ "title": "unlimited" })).await;
+    assert!(res.headers.get(LIMIT_HEADER).is_none());
+    let percent = |key: &str| (key == "QUOTA_WARNING_PERCENT").then(|| "101".to_string());
+    assert!(Config::from_lookup(percent).is_err());
+}
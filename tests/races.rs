@@ -0,0 +1,220 @@
+// Stress test for concurrent writes: many tasks toggle, rename, delete, and
+// list a shared set of todos at once, then the outcome is checked against
+// what each call reported.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use futures::StreamExt;
+use rust_api::{
+    errors::AppError,
+    events::TodoEvent,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::{in_memory_repo, TodoRepo},
+    AppState,
+};
+
+const TODOS: u64 = 64;
+const TASKS: usize = 64;
+const OPS_PER_TASK: usize = 200;
+
+/// What the tasks saw, in the order each call completed.
+#[derive(Default)]
+struct Log {
+    /// Successful updates: when they started, the requested id and change,
+    /// and the returned todo.
+    updates: Vec<(Instant, u64, UpdateTodo, Todo)>,
+    /// Ids whose update found the todo gone.
+    missed_updates: Vec<u64>,
+    /// When each successful delete returned.
+    deletes: HashMap<u64, Instant>,
+    /// When each list started, and the ids it returned.
+    lists: Vec<(Instant, Vec<u64>)>,
+}
+
+/// Small LCG so each task's sequence is reproducible without a rand crate.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) % bound
+    }
+}
+
+async fn task(repo: Arc<dyn TodoRepo>, ids: Arc<Vec<u64>>, seed: u64, log: Arc<Mutex<Log>>) {
+    let mut rng = Lcg(seed);
+    for op in 0..OPS_PER_TASK {
+        let id = ids[rng.next(ids.len() as u64) as usize];
+        match rng.next(1000) {
+            0..=399 => {
+                let update = UpdateTodo {
+                    done: Some(rng.next(2) == 0),
+                    ..Default::default()
+                };
+                apply(&repo, id, update, &log).await;
+            }
+            400..=799 => {
+                let update = UpdateTodo {
+                    title: Some(format!("task {seed} op {op}").parse().unwrap()),
+                    ..Default::default()
+                };
+                apply(&repo, id, update, &log).await;
+            }
+            // Rare, so most todos live long enough to race with updates.
+            800..=802 => match repo.delete(id).await {
+                Ok(()) => {
+                    let previous = log.lock().unwrap().deletes.insert(id, Instant::now());
+                    assert!(previous.is_none(), "todo {id} deleted twice");
+                }
+                Err(AppError::NotFound) => {}
+                Err(err) => panic!("delete {id}: {err:?}"),
+            },
+            _ => {
+                let started = Instant::now();
+                let listed = repo.list().await.unwrap();
+                let listed = listed.into_iter().map(|todo| todo.id).collect();
+                log.lock().unwrap().lists.push((started, listed));
+            }
+        }
+    }
+}
+
+async fn apply(repo: &Arc<dyn TodoRepo>, id: u64, update: UpdateTodo, log: &Mutex<Log>) {
+    let started = Instant::now();
+    match repo.update(id, update.clone()).await {
+        Ok(todo) => log.lock().unwrap().updates.push((started, id, update, todo)),
+        Err(AppError::NotFound) => log.lock().unwrap().missed_updates.push(id),
+        Err(err) => panic!("update {id}: {err:?}"),
+    }
+}
+
+/// Runs the workload against `repo` and checks every invariant.
+async fn stress(repo: Arc<dyn TodoRepo>) {
+    let mut ids = Vec::new();
+    for n in 0..TODOS {
+        let input = CreateTodo::new(format!("todo {n}").parse().unwrap());
+        ids.push(repo.create(input).await.unwrap().id);
+    }
+    let ids = Arc::new(ids);
+    let log = Arc::new(Mutex::new(Log::default()));
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|seed| {
+            let (repo, ids, log) = (Arc::clone(&repo), Arc::clone(&ids), Arc::clone(&log));
+            tokio::spawn(task(repo, ids, seed as u64 + 1, log))
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("no task panicked");
+    }
+    let log = Arc::try_unwrap(log).ok().unwrap().into_inner().unwrap();
+
+    // Every successful update returned the todo it was asked to change, with
+    // the change applied.
+    for (_, id, update, todo) in &log.updates {
+        assert_eq!(todo.id, *id);
+        if let Some(title) = &update.title {
+            assert_eq!(&todo.title, title);
+        }
+        if let Some(done) = update.done {
+            assert_eq!(todo.done, done);
+        }
+    }
+
+    // Updates only missed deleted todos, and none started after the delete
+    // returned succeeded.
+    for id in &log.missed_updates {
+        assert!(log.deletes.contains_key(id), "update of live todo {id} was NotFound");
+    }
+    for (started, id, _, _) in &log.updates {
+        if let Some(deleted) = log.deletes.get(id) {
+            assert!(started < deleted, "todo {id} updated after it was deleted");
+        }
+    }
+
+    // No list that started after a delete returned the deleted id.
+    for (started, listed) in &log.lists {
+        for id in listed {
+            if let Some(deleted) = log.deletes.get(id) {
+                assert!(started < deleted, "list returned {id} after it was deleted");
+            }
+        }
+    }
+
+    // Updates to one todo were applied one at a time: each got its own
+    // timestamp, and the survivor is exactly what the last update returned.
+    let mut by_id: HashMap<u64, Vec<&Todo>> = HashMap::new();
+    for (_, id, _, todo) in &log.updates {
+        by_id.entry(*id).or_default().push(todo);
+    }
+    for id in ids.iter() {
+        let stored = repo.get(*id).await;
+        if log.deletes.contains_key(id) {
+            assert!(matches!(stored, Err(AppError::NotFound)), "{id}: {stored:?}");
+            continue;
+        }
+        let stored = stored.unwrap();
+        let Some(updates) = by_id.get(id) else {
+            assert_eq!(stored.title, format!("todo {}", id - ids[0]));
+            continue;
+        };
+        let stamps: HashSet<_> = updates.iter().map(|todo| todo.updated_at).collect();
+        assert_eq!(stamps.len(), updates.len(), "{id}: two updates share a timestamp");
+        let last = updates.iter().max_by_key(|todo| todo.updated_at).unwrap();
+        assert_eq!(stored.updated_at, last.updated_at, "{id}");
+        assert_eq!((&stored.title, stored.done), (&last.title, last.done), "{id}");
+    }
+
+    let listed: HashSet<u64> = repo.list().await.unwrap().into_iter().map(|t| t.id).collect();
+    let surviving: HashSet<u64> =
+        ids.iter().copied().filter(|id| !log.deletes.contains_key(id)).collect();
+    assert_eq!(listed, surviving);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn in_memory_repo_survives_concurrent_writes() {
+    stress(in_memory_repo()).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn decorated_repo_never_announces_updates_after_deletes() {
+    let state = AppState::new_in_memory();
+    let mut events = state.events().subscribe();
+    // Drain concurrently: the workload publishes far more than the bus
+    // buffers. `u64::MAX` is never a real id, so it marks the end.
+    let collector = tokio::spawn(async move {
+        let mut seen = Vec::new();
+        while let Some(event) = events.next().await {
+            if matches!(event, TodoEvent::Deleted { id: u64::MAX }) {
+                break;
+            }
+            seen.push(event);
+        }
+        seen
+    });
+    stress(Arc::clone(state.service().repo())).await;
+    state.events().publish(TodoEvent::Deleted { id: u64::MAX });
+
+    // Events follow the order the writes were applied in: per todo, updates
+    // carry increasing timestamps and nothing follows the delete.
+    let mut deleted = HashSet::new();
+    let mut last_update = HashMap::new();
+    for event in collector.await.unwrap() {
+        match event {
+            TodoEvent::Deleted { id } => assert!(deleted.insert(id), "{id} deleted twice"),
+            TodoEvent::Updated { todo } => {
+                assert!(!deleted.contains(&todo.id), "{} updated after its delete", todo.id);
+                let previous = last_update.insert(todo.id, todo.updated_at);
+                assert!(previous < Some(todo.updated_at), "{} updates out of order", todo.id);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use rust_api::{
+    app,
+    config::Config,
+    rate_limit::{RateLimitStore, RateLimiter, WINDOW},
+    AppState,
+};
+use tower::ServiceExt;
+
+/// Behavior every [`RateLimitStore`] must have. `prefix` keeps runs against a
+/// shared server apart.
+async fn conformance(store: Arc<dyn RateLimitStore>, prefix: &str) {
+    let alice = format!("{prefix}alice");
+    let bob = format!("{prefix}bob");
+
+    for _ in 0..3 {
+        assert_eq!(store.hit(&alice, 3).await.unwrap(), Ok(()));
+    }
+    let retry_after = store.hit(&alice, 3).await.unwrap().unwrap_err();
+    assert!((1..=WINDOW.as_secs()).contains(&retry_after), "{retry_after}");
+    assert!(store.hit(&alice, 3).await.unwrap().is_err(), "stays limited");
+
+    // Clients are counted separately, and a raised limit applies at once.
+    assert_eq!(store.hit(&bob, 3).await.unwrap(), Ok(()));
+    assert_eq!(store.hit(&alice, 10).await.unwrap(), Ok(()));
+}
+
+#[tokio::test]
+async fn in_memory_store_conforms() {
+    conformance(Arc::new(RateLimiter::default()), "").await;
+}
+
+/// Always unreachable.
+struct Down;
+
+#[async_trait]
+impl RateLimitStore for Down {
+    async fn hit(&self, _key: &str, _limit: u32) -> anyhow::Result<Result<(), u64>> {
+        anyhow::bail!("connection refused")
+    }
+}
+
+fn app_with_down_store(vars: &[(&str, &str)]) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    app(AppState::new_in_memory()
+        .with_rate_limit_store(Arc::new(Down))
+        .with_config(config))
+}
+
+async fn list_status(app: &Router) -> StatusCode {
+    app.clone()
+        .oneshot(Request::builder().uri("/todos").body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn unavailable_store_fails_open_by_default() {
+    let app = app_with_down_store(&[("RATE_LIMIT_PER_MINUTE", "1")]);
+    for _ in 0..3 {
+        assert_eq!(list_status(&app).await, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn unavailable_store_can_fail_closed() {
+    let app = app_with_down_store(&[
+        ("RATE_LIMIT_PER_MINUTE", "1"),
+        ("RATE_LIMIT_FAIL_OPEN", "false"),
+    ]);
+    assert_eq!(list_status(&app).await, StatusCode::SERVICE_UNAVAILABLE);
+
+    // With the limiter off the store is never consulted.
+    let app = app_with_down_store(&[("RATE_LIMIT_FAIL_OPEN", "false")]);
+    assert_eq!(list_status(&app).await, StatusCode::OK);
+}
+
+/// Runs against a real server when `REDIS_URL` is set, e.g.
+/// `REDIS_URL=redis://localhost:6379 cargo test --features redis`.
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_store_conforms() {
+    use rust_api::rate_limit::redis::RedisRateLimitStore;
+
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL is unset; skipping");
+        return;
+    };
+    let store = RedisRateLimitStore::new(&url).unwrap();
+    let prefix = format!("test-{}-", std::process::id());
+    conformance(Arc::new(store), &prefix).await;
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_store_reports_unreachable_servers() {
+    use rust_api::rate_limit::redis::RedisRateLimitStore;
+
+    // Nothing listens on port 1.
+    let store = RedisRateLimitStore::new("redis://127.0.0.1:1").unwrap();
+    assert!(store.hit("alice", 3).await.is_err());
+    assert!(RedisRateLimitStore::new("not a url").is_err());
+}
@@ -0,0 +1,99 @@
+// The `READY_ANNOUNCE` line: start the real binary, wait for it instead of
+// sleeping, use the server, and stop it with SIGTERM.
+
+#![cfg(unix)]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use assert_cmd::cargo::CommandCargoExt;
+use serde_json::Value;
+
+/// Shutdown waits 200ms for in-flight requests; allow for a slow machine.
+const GRACE: Duration = Duration::from_secs(5);
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn spawn(port: u16) -> Child {
+    Command::cargo_bin("rust-api")
+        .unwrap()
+        .envs([
+            ("HOST", "127.0.0.1"),
+            ("PORT", &port.to_string()),
+            ("READY_ANNOUNCE", "true"),
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+/// Reads stdout until the `listening` event and returns it with every line
+/// read before it.
+fn wait_for_ready(child: &mut Child) -> (Value, Vec<String>) {
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut before = Vec::new();
+    loop {
+        let line = rx.recv_timeout(Duration::from_secs(30)).expect("a ready line within 30s");
+        match serde_json::from_str::<Value>(&line) {
+            Ok(event) if event["event"] == "listening" => return (event, before),
+            _ => before.push(line),
+        }
+    }
+}
+
+fn get_health(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn announces_readiness_and_exits_cleanly_on_sigterm() {
+    let port = free_port();
+    let mut child = spawn(port);
+
+    let (event, before) = wait_for_ready(&mut child);
+    assert!(before.is_empty(), "logs belong on stderr: {before:?}");
+    assert_eq!(event["addr"], format!("127.0.0.1:{port}"));
+    assert_eq!(event["pid"], child.id());
+
+    let addr: SocketAddr = event["addr"].as_str().unwrap().parse().unwrap();
+    let response = get_health(addr);
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    let pid = i32::try_from(child.id()).unwrap();
+    assert_eq!(unsafe { libc::kill(pid, libc::SIGTERM) }, 0);
+    let deadline = Instant::now() + GRACE;
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("still running {GRACE:?} after SIGTERM");
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    assert!(status.success(), "{status}");
+}
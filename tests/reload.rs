@@ -0,0 +1,142 @@
+// Runtime reload tests call `Reloader::apply` directly, which is exactly what
+// the SIGHUP handler does after re-reading the environment.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rust_api::{app, config::Config, reload::Reloader, AppState};
+use serde_json::json;
+use tower::ServiceExt;
+
+fn config(vars: &[(&str, &str)]) -> Config {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Config::from_lookup(|key| vars.get(key).cloned()).unwrap()
+}
+
+async fn list_status(app: &Router) -> StatusCode {
+    app.clone()
+        .oneshot(Request::builder().uri("/todos").body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn reloaded_rate_limit_applies_to_next_request() {
+    let state = AppState::new_in_memory();
+    let app = app(state.clone());
+    let reloader = Reloader::new(state);
+
+    for _ in 0..3 {
+        assert_eq!(list_status(&app).await, StatusCode::OK);
+    }
+
+    let report = reloader.apply(config(&[("RATE_LIMIT_PER_MINUTE", "1")]));
+    assert_eq!(report.applied, vec!["RATE_LIMIT_PER_MINUTE"]);
+
+    assert_eq!(list_status(&app).await, StatusCode::OK);
+    let limited = app
+        .clone()
+        .oneshot(Request::builder().uri("/todos").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(limited.headers().contains_key(header::RETRY_AFTER));
+
+    // Health probes are never limited.
+    let health = app
+        .clone()
+        .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(health.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reloaded_read_only_flag_blocks_writes() {
+    let state = AppState::new_in_memory();
+    let app = app(state.clone());
+    Reloader::new(state).apply(config(&[("READ_ONLY", "true")]));
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/todos")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "title": "blocked" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(list_status(&app).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reloaded_cors_origins_apply_to_next_request() {
+    let state = AppState::new_in_memory();
+    let app = app(state.clone());
+    Reloader::new(state).apply(config(&[("CORS_ORIGINS", "https://app.example")]));
+
+    let preflight = |origin: &'static str| {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/todos")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let allowed = app.clone().oneshot(preflight("https://app.example")).await.unwrap();
+    assert_eq!(
+        allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://app.example"
+    );
+
+    let denied = app.clone().oneshot(preflight("https://evil.example")).await.unwrap();
+    assert!(!denied
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn startup_only_settings_are_reported_not_applied() {
+    let state = AppState::new_in_memory();
+    let reloader = Reloader::new(state.clone());
+
+    let report = reloader.apply(config(&[("PORT", "9999"), ("READ_ONLY", "true")]));
+
+    assert_eq!(report.applied, vec!["READ_ONLY"]);
+    assert_eq!(report.requires_restart, vec!["HOST/PORT"]);
+    assert_eq!(state.config().server_addr.port(), 8080);
+    assert!(state.config().read_only);
+}
+
+#[tokio::test]
+async fn share_secret_needs_a_restart_but_share_limits_apply() {
+    let secret = "an old secret that is at least 32 bytes";
+    let state = AppState::new_in_memory().with_config(config(&[("SHARE_SECRET", secret)]));
+    let reloader = Reloader::new(state.clone());
+
+    let report = reloader.apply(config(&[
+        ("SHARE_SECRET", "a brand new secret, also 32 bytes or more"),
+        ("SHARE_TTL_SECS", "60"),
+        ("SHARE_RATE_PER_MINUTE", "5"),
+    ]));
+
+    assert_eq!(report.applied, vec!["SHARE_TTL_SECS", "SHARE_RATE_PER_MINUTE"]);
+    assert_eq!(report.requires_restart, vec!["SHARE_SECRET"]);
+    let config = state.config();
+    assert_eq!(config.share_secret.as_ref().unwrap().expose(), secret);
+    assert_eq!((config.share_ttl_secs, config.share_rate_per_minute), (60, 5));
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::{FutureExt, StreamExt};
+use rust_api::{
+    events::{EventStream, TodoEvent},
+    models::{CreateTodo, UpdateTodo},
+    reminders, AppState,
+};
+
+fn at(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap()
+}
+
+async fn create(state: &AppState, title: &str, due: Option<DateTime<Utc>>) -> u64 {
+    let input = CreateTodo {
+        due,
+        ..CreateTodo::new(title.parse().unwrap())
+    };
+    state.service().repo().create(input).await.unwrap().id
+}
+
+/// Ids of the reminders published since the last call, ignoring other events.
+fn reminded(events: &mut EventStream) -> Vec<u64> {
+    let mut ids = Vec::new();
+    while let Some(Some(event)) = events.next().now_or_never() {
+        if let TodoEvent::Reminder { todo } = event {
+            ids.push(todo.id);
+        }
+    }
+    ids
+}
+
+#[tokio::test]
+async fn fires_once_when_due_passes() {
+    let state = AppState::new_in_memory();
+    let mut events = state.events().subscribe();
+    let id = create(&state, "file taxes", Some(at(12))).await;
+    create(&state, "someday", None).await;
+
+    assert_eq!(reminders::tick(&state, at(11)).await.unwrap(), 0);
+    assert_eq!(reminders::tick(&state, at(12)).await.unwrap(), 1);
+    assert_eq!(reminded(&mut events), [id]);
+
+    assert_eq!(reminders::tick(&state, at(13)).await.unwrap(), 0);
+    assert_eq!(reminders::tick(&state, at(20)).await.unwrap(), 0);
+    assert!(reminded(&mut events).is_empty());
+
+    let todo = state.service().repo().get(id).await.unwrap();
+    assert_eq!(todo.reminded_at, Some(at(12)));
+}
+
+#[tokio::test]
+async fn already_overdue_todos_fire_once() {
+    let state = AppState::new_in_memory();
+    let mut events = state.events().subscribe();
+    reminders::tick(&state, at(12)).await.unwrap();
+
+    // Created after the last check with a due date before it.
+    let id = create(&state, "overdue", Some(at(1))).await;
+    assert_eq!(reminders::tick(&state, at(13)).await.unwrap(), 1);
+    assert_eq!(reminded(&mut events), [id]);
+
+    // The clock stepping backwards doesn't resend it.
+    assert_eq!(reminders::tick(&state, at(12)).await.unwrap(), 0);
+    assert_eq!(reminders::tick(&state, at(14)).await.unwrap(), 0);
+    assert!(reminded(&mut events).is_empty());
+}
+
+#[tokio::test]
+async fn skips_done_todos_and_rearms_on_new_due_date() {
+    let state = AppState::new_in_memory();
+    let mut events = state.events().subscribe();
+    let done = create(&state, "done already", Some(at(9))).await;
+    let moved = create(&state, "dentist", Some(at(10))).await;
+    let update = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    state.service().repo().update(done, update).await.unwrap();
+
+    reminders::tick(&state, at(11)).await.unwrap();
+    assert_eq!(reminded(&mut events), [moved]);
+
+    let update = UpdateTodo {
+        due: Some(at(11) + Duration::days(1)),
+        ..Default::default()
+    };
+    let todo = state.service().repo().update(moved, update).await.unwrap();
+    assert_eq!(todo.reminded_at, None);
+
+    reminders::tick(&state, at(12)).await.unwrap();
+    assert!(reminded(&mut events).is_empty());
+    reminders::tick(&state, at(12) + Duration::days(1)).await.unwrap();
+    assert_eq!(reminded(&mut events), [moved]);
+}
+
+#[tokio::test]
+async fn run_stops_on_shutdown() {
+    let state = AppState::new_in_memory();
+    tokio::time::timeout(
+        std::time::Duration::from_secs(1),
+        reminders::run(state, async {}),
+    )
+    .await
+    .expect("scheduler exits when shutdown resolves");
+}
@@ -0,0 +1,186 @@
+// Human-readable renderings: `text/plain` checklists and escaped `text/html`
+// tables with dates in the reader's language, with JSON still the default.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use chrono::{Duration, TimeZone, Utc};
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    models::{CreateTodo, Todo, UpdateTodo},
+    test_utils::MockClock,
+    AppState,
+};
+use tower::ServiceExt;
+
+async fn seeded(titles: &[&str]) -> Router {
+    let state = AppState::new_in_memory();
+    for title in titles {
+        state
+            .service().repo()
+            .create(CreateTodo::new(title.parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    app(state)
+}
+
+async fn get(app: &Router, uri: &str, accept: &str) -> (StatusCode, String, String) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let content_type = res.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn plain_text_is_a_checklist() {
+    let state = AppState::new_in_memory();
+    for title in ["buy milk", "call mom"] {
+        state
+            .service().repo()
+            .create(CreateTodo::new(title.parse().unwrap()))
+            .await
+            .unwrap();
+    }
+    let done = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    state.service().repo().update(1, done).await.unwrap();
+    let app = app(state);
+
+    let (status, content_type, body) = get(&app, "/todos", "text/plain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/plain; charset=utf-8");
+    assert_eq!(body, "[x] 1 buy milk\n[ ] 2 call mom\n");
+
+    let (_, _, body) = get(&app, "/todos/2", "text/plain").await;
+    assert_eq!(body, "[ ] 2 call mom\n");
+}
+
+#[tokio::test]
+async fn html_escapes_titles() {
+    let app = seeded(&["<script>alert('hi')</script> & more"]).await;
+
+    for uri in ["/todos", "/todos/1"] {
+        let (status, content_type, body) = get(&app, uri, "text/html").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(body.starts_with("<!DOCTYPE html>"), "{body}");
+        assert!(!body.contains("<script>"), "{body}");
+        assert!(
+            body.contains("&lt;script&gt;alert(&#x27;hi&#x27;)&lt;/script&gt; &amp; more"),
+            "{body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn browsers_get_html_and_everyone_else_json() {
+    let app = seeded(&["buy milk"]).await;
+
+    let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+    let (_, content_type, _) = get(&app, "/todos", browser).await;
+    assert_eq!(content_type, "text/html; charset=utf-8");
+
+    for accept in ["*/*", "application/json", "application/json, text/plain;q=0.5"] {
+        let (_, content_type, body) = get(&app, "/todos", accept).await;
+        assert_eq!(content_type, "application/json", "{accept}");
+        let todos: Vec<Todo> = serde_json::from_str(&body).unwrap();
+        assert_eq!(todos[0].title, "buy milk");
+    }
+}
+
+/// Two todos due tomorrow and in two months, last updated two hours before
+/// the mocked now.
+async fn dated() -> Router {
+    let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap()));
+    let state = AppState::in_memory_with_clock(clock.clone());
+    for (title, due) in [
+        ("ship it", Utc.with_ymd_and_hms(2024, 5, 2, 17, 0, 0).unwrap()),
+        ("file taxes", Utc.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap()),
+    ] {
+        let input = CreateTodo {
+            due: Some(due),
+            ..CreateTodo::new(title.parse().unwrap())
+        };
+        state.service().repo().create(input).await.unwrap();
+    }
+    clock.advance(Duration::hours(2));
+    app(state)
+}
+
+async fn get_in(app: &Router, uri: &str, accept: &str, language: &str) -> (HeaderMap, String) {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, accept)
+                .header(header::ACCEPT_LANGUAGE, language)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let headers = res.headers().clone();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn dates_are_written_for_the_reader() {
+    let app = dated().await;
+
+    for language in ["en-US,en;q=0.9", "fr-FR", ""] {
+        let (headers, body) = get_in(&app, "/todos", "text/plain", language).await;
+        assert_eq!(
+            body, "[ ] 1 ship it (due tomorrow)\n[ ] 2 file taxes (due Jun 30, 2024)\n",
+            "{language:?}"
+        );
+        assert_eq!(headers[header::CONTENT_LANGUAGE], "en");
+    }
+    let (headers, body) = get_in(&app, "/todos", "text/plain", "fr;q=1, de-DE;q=0.8").await;
+    assert_eq!(body, "[ ] 1 ship it (morgen fällig)\n[ ] 2 file taxes (fällig am 30. Juni 2024)\n");
+    assert_eq!(headers[header::CONTENT_LANGUAGE], "de");
+    let vary = headers[header::VARY].to_str().unwrap();
+    assert!(vary.split(", ").any(|name| name == "accept-language"), "{vary}");
+
+    let (_, body) = get_in(&app, "/todos/1", "text/html", "en").await;
+    assert!(body.contains("<html lang=\"en\">"), "{body}");
+    assert!(body.contains("<time datetime=\"2024-05-02T17:00:00.000Z\">due tomorrow</time>"), "{body}");
+    assert!(body.contains("<time datetime=\"2024-05-01T09:00:00.000Z\">2 hours ago</time>"), "{body}");
+    let (_, body) = get_in(&app, "/todos/1", "text/html", "de").await;
+    assert!(body.contains("<html lang=\"de\">"), "{body}");
+    assert!(body.contains(">morgen fällig</time>"), "{body}");
+    assert!(body.contains(">vor 2 Stunden</time>"), "{body}");
+}
+
+#[tokio::test]
+async fn json_keeps_its_timestamps_in_every_language() {
+    let app = dated().await;
+
+    let (headers, english) = get_in(&app, "/todos/1", "application/json", "en").await;
+    let (_, german) = get_in(&app, "/todos/1", "application/json", "de").await;
+    assert_eq!(english, german);
+    assert!(headers.get(header::CONTENT_LANGUAGE).is_none());
+    let todo: serde_json::Value = serde_json::from_str(&english).unwrap();
+    assert_eq!(todo["due"], "2024-05-02T17:00:00.000Z");
+    assert_eq!(todo["updated_at"], "2024-05-01T09:00:00.000Z");
+}
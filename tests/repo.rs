@@ -0,0 +1,164 @@
+// Runs the shared repository contract against every backend in the crate,
+// and against deliberately broken repositories to show the contract bites.
+
+mod repo_conformance;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_api::{
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::{in_memory_repo, TodoRepo},
+    AppState,
+};
+
+#[tokio::test]
+async fn in_memory_repo_conforms() {
+    repo_conformance::assert_conforms(in_memory_repo).await;
+}
+
+#[tokio::test]
+async fn decorated_state_repo_conforms() {
+    let decorated = || Arc::clone(AppState::new_in_memory().service().repo());
+    repo_conformance::assert_conforms(decorated).await;
+}
+
+/// One way a repository can get the contract wrong.
+#[derive(Clone, Copy)]
+enum Flaw {
+    AcceptsEmptyUpdates,
+    DropsDescriptionOnUpdate,
+    ForgetsToDelete,
+    /// Reads the todo, then writes all of it back with the update merged
+    /// in, clobbering whatever changed in between.
+    WritesBackStaleFields,
+    /// Toggles by reading `done` and then updating to its opposite.
+    TogglesFromStaleRead,
+}
+
+/// The in-memory repo with one [`Flaw`].
+struct Flawed {
+    inner: Arc<dyn TodoRepo>,
+    flaw: Flaw,
+}
+
+#[async_trait]
+impl TodoRepo for Flawed {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        self.inner.list().await
+    }
+
+    async fn create(&self, input: CreateTodo) -> Result<Todo, AppError> {
+        self.inner.create(input).await
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo, AppError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, id: u64, input: UpdateTodo) -> Result<Todo, AppError> {
+        let empty = input.is_empty();
+        match self.flaw {
+            Flaw::AcceptsEmptyUpdates if empty => self.inner.get(id).await,
+            Flaw::DropsDescriptionOnUpdate => {
+                let todo = self.inner.update(id, input).await?;
+                Ok(Todo {
+                    description: None,
+                    ..todo
+                })
+            }
+            Flaw::WritesBackStaleFields if !empty => {
+                let current = self.inner.get(id).await?;
+                tokio::task::yield_now().await;
+                let merged = UpdateTodo {
+                    title: Some(input.title.unwrap_or(current.title)),
+                    description: input.description.or(current.description),
+                    done: Some(input.done.unwrap_or(current.done)),
+                    due: input.due,
+                    assignee: Some(input.assignee.unwrap_or(current.assignee)),
+                    color: Some(input.color.unwrap_or(current.color)),
+                };
+                self.inner.update(id, merged).await
+            }
+            _ => self.inner.update(id, input).await,
+        }
+    }
+
+    async fn toggle(&self, id: u64) -> Result<Todo, AppError> {
+        match self.flaw {
+            Flaw::TogglesFromStaleRead => {
+                let current = self.inner.get(id).await?;
+                tokio::task::yield_now().await;
+                let update = UpdateTodo {
+                    done: Some(!current.done),
+                    ..Default::default()
+                };
+                self.inner.update(id, update).await
+            }
+            _ => self.inner.toggle(id).await,
+        }
+    }
+
+    async fn delete(&self, id: u64) -> Result<(), AppError> {
+        match self.flaw {
+            Flaw::ForgetsToDelete => self.inner.get(id).await.map(drop),
+            _ => self.inner.delete(id).await,
+        }
+    }
+
+    async fn mark_reminded(&self, id: u64, at: DateTime<Utc>) -> Result<Option<Todo>, AppError> {
+        self.inner.mark_reminded(id, at).await
+    }
+}
+
+async fn failed_checks(flaw: Flaw) -> Vec<String> {
+    let failures = repo_conformance::failures(|| {
+        Arc::new(Flawed {
+            inner: in_memory_repo(),
+            flaw,
+        }) as Arc<dyn TodoRepo>
+    })
+    .await;
+    failures
+        .into_iter()
+        .map(|failure| failure.split(':').next().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn catches_broken_repos() {
+    assert_eq!(
+        failed_checks(Flaw::AcceptsEmptyUpdates).await,
+        ["empty_updates_are_rejected"]
+    );
+    assert_eq!(
+        failed_checks(Flaw::DropsDescriptionOnUpdate).await,
+        ["updates_merge_fields"]
+    );
+    assert_eq!(
+        failed_checks(Flaw::ForgetsToDelete).await,
+        ["deleted_todos_are_gone"]
+    );
+    assert_eq!(
+        failed_checks(Flaw::WritesBackStaleFields).await,
+        ["concurrent_updates_are_not_lost"]
+    );
+    assert_eq!(
+        failed_checks(Flaw::TogglesFromStaleRead).await,
+        ["concurrent_toggles_are_not_lost"]
+    );
+}
+
+#[tokio::test]
+#[should_panic(expected = "repository breaks its contract")]
+async fn assert_conforms_panics_on_broken_repos() {
+    repo_conformance::assert_conforms(|| {
+        Arc::new(Flawed {
+            inner: in_memory_repo(),
+            flaw: Flaw::ForgetsToDelete,
+        }) as Arc<dyn TodoRepo>
+    })
+    .await;
+}
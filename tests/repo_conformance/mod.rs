@@ -0,0 +1,545 @@
+// Behavioral contract every `TodoRepo` must honor. A backend's test crate
+// pulls this in with `mod repo_conformance;` and calls `assert_conforms` with
+// a factory for empty repositories; see `tests/repo.rs`.
+#![allow(dead_code)]
+
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::{TimeZone, Utc};
+use rust_api::{
+    errors::AppError,
+    models::{
+        CreateTodo, Todo, TodoFilter, UpdateTodo, MAX_ASSIGNEE_CHARS, MAX_DESCRIPTION_BYTES,
+    },
+    state::TodoRepo,
+};
+
+/// Outcome of one check; the message says what went wrong.
+type Check = Result<(), String>;
+
+macro_rules! ensure {
+    ($cond:expr, $($msg:tt)+) => {
+        if !$cond {
+            return Err(format!($($msg)+));
+        }
+    };
+}
+
+/// Unwraps a repository result inside a check.
+macro_rules! ok {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => return Err(format!("`{}` failed: {err:?}", stringify!($result))),
+        }
+    };
+}
+
+/// Panics listing every check the repositories built by `factory` fail.
+/// Each check gets a fresh, empty repository.
+pub async fn assert_conforms(factory: impl Fn() -> Arc<dyn TodoRepo>) {
+    let failures = failures(factory).await;
+    assert!(
+        failures.is_empty(),
+        "repository breaks its contract:\n  {}",
+        failures.join("\n  ")
+    );
+}
+
+/// Names and reasons of the checks the repositories built by `factory` fail.
+pub async fn failures(factory: impl Fn() -> Arc<dyn TodoRepo>) -> Vec<String> {
+    let mut failures = Vec::new();
+    macro_rules! run {
+        ($($check:ident),+ $(,)?) => {
+            $(
+                if let Err(why) = $check(factory()).await {
+                    failures.push(format!("{}: {why}", stringify!($check)));
+                }
+            )+
+        };
+    }
+    run!(
+        create_round_trips,
+        ids_increase_and_are_never_reused,
+        concurrent_creates_get_unique_ids,
+        titles_are_stored_normalized,
+        assignees_are_trimmed,
+        missing_ids_are_not_found,
+        updates_merge_fields,
+        concurrent_updates_are_not_lost,
+        concurrent_toggles_are_not_lost,
+        batches_apply_only_at_their_revision,
+        empty_updates_are_rejected,
+        blank_assignee_updates_are_rejected,
+        invalid_updates_are_rejected,
+        assignee_can_be_cleared,
+        new_due_date_rearms_reminder,
+        reminders_leave_updated_at_alone,
+        deleted_todos_are_gone,
+        listing_is_in_id_order,
+        filters_and_pages_agree,
+    );
+    failures
+}
+
+fn titled(title: &str) -> CreateTodo {
+    CreateTodo::new(title.parse().unwrap())
+}
+
+fn same(a: &Todo, b: &Todo) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn is_validation<T>(result: &Result<T, AppError>) -> bool {
+    matches!(result, Err(AppError::Validation(_)))
+}
+
+fn is_not_found<T>(result: &Result<T, AppError>) -> bool {
+    matches!(result, Err(AppError::NotFound))
+}
+
+fn ids(todos: &[Todo]) -> Vec<u64> {
+    todos.iter().map(|todo| todo.id).collect()
+}
+
+async fn create_round_trips(repo: Arc<dyn TodoRepo>) -> Check {
+    let due = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    let input = CreateTodo {
+        description: Some("the *README* first".to_string()),
+        due: Some(due),
+        ..CreateTodo::new("write docs".parse().unwrap())
+    };
+    let created = ok!(repo.create(input).await);
+    ensure!(created.title == "write docs", "title stored as {:?}", created.title);
+    ensure!(
+        created.description.as_deref() == Some("the *README* first"),
+        "description stored as {:?}",
+        created.description
+    );
+    ensure!(created.due == Some(due), "due stored as {:?}", created.due);
+    ensure!(!created.done, "new todos start open");
+    ensure!(created.assignee.is_none(), "new todos start unassigned");
+    ensure!(created.reminded_at.is_none(), "new todos start unreminded");
+    ensure!(created.created_at == created.updated_at, "timestamps differ on create");
+
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(same(&created, &fetched), "get returned {fetched:?}, create {created:?}");
+    Ok(())
+}
+
+async fn ids_increase_and_are_never_reused(repo: Arc<dyn TodoRepo>) -> Check {
+    let first = ok!(repo.create(titled("one")).await).id;
+    let second = ok!(repo.create(titled("two")).await).id;
+    ensure!(first > 0, "ids start above zero, got {first}");
+    ensure!(second > first, "ids went {first} then {second}");
+
+    ok!(repo.delete(second).await);
+    let third = ok!(repo.create(titled("three")).await).id;
+    ensure!(third > second, "id {third} reused after deleting {second}");
+    Ok(())
+}
+
+async fn concurrent_creates_get_unique_ids(repo: Arc<dyn TodoRepo>) -> Check {
+    const TASKS: usize = 64;
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|n| {
+            let repo = Arc::clone(&repo);
+            tokio::spawn(async move { repo.create(titled(&format!("task {n}"))).await })
+        })
+        .collect();
+    let mut seen = HashSet::new();
+    for task in tasks {
+        let todo = ok!(ok!(task.await));
+        ensure!(seen.insert(todo.id), "id {} handed out twice", todo.id);
+    }
+    let listed = ok!(repo.list().await);
+    ensure!(listed.len() == TASKS, "listed {} of {TASKS} todos", listed.len());
+    Ok(())
+}
+
+/// Titles are normalized before they reach the repository, which must store
+/// them as given.
+async fn titles_are_stored_normalized(repo: Arc<dyn TodoRepo>) -> Check {
+    let created = ok!(repo.create(titled("  buy \u{a0} milk ")).await);
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(fetched.title == "buy milk", "title stored as {:?}", fetched.title);
+    Ok(())
+}
+
+async fn assignees_are_trimmed(repo: Arc<dyn TodoRepo>) -> Check {
+    let input = CreateTodo {
+        assignee: Some("  alice ".to_string()),
+        ..titled("dishes")
+    };
+    let todo = ok!(repo.create(input).await);
+    ensure!(todo.assignee.as_deref() == Some("alice"), "created with {:?}", todo.assignee);
+
+    let update = UpdateTodo {
+        assignee: Some(Some("\tbob ".to_string())),
+        ..Default::default()
+    };
+    let todo = ok!(repo.update(todo.id, update).await);
+    ensure!(todo.assignee.as_deref() == Some("bob"), "updated to {:?}", todo.assignee);
+    Ok(())
+}
+
+async fn missing_ids_are_not_found(repo: Arc<dyn TodoRepo>) -> Check {
+    let result = repo.get(1).await;
+    ensure!(is_not_found(&result), "get gave {result:?}");
+    let update = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    let result = repo.update(1, update).await;
+    ensure!(is_not_found(&result), "update gave {result:?}");
+    let result = repo.delete(1).await;
+    ensure!(is_not_found(&result), "delete gave {result:?}");
+    let result = repo.get(u64::MAX).await;
+    ensure!(is_not_found(&result), "get(u64::MAX) gave {result:?}");
+    Ok(())
+}
+
+async fn updates_merge_fields(repo: Arc<dyn TodoRepo>) -> Check {
+    let input = CreateTodo {
+        description: Some("two of them".to_string()),
+        assignee: Some("alice".to_string()),
+        ..titled("buy lamps")
+    };
+    let created = ok!(repo.create(input).await);
+
+    let update = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    let todo = ok!(repo.update(created.id, update).await);
+    ensure!(todo.done, "done not applied");
+    ensure!(todo.title == "buy lamps", "title changed to {:?}", todo.title);
+    ensure!(
+        todo.description.as_deref() == Some("two of them"),
+        "description changed to {:?}",
+        todo.description
+    );
+    ensure!(todo.assignee.as_deref() == Some("alice"), "assignee changed");
+    ensure!(todo.created_at == created.created_at, "created_at changed");
+    ensure!(todo.updated_at >= created.updated_at, "updated_at went backwards");
+
+    let update = UpdateTodo {
+        title: Some("buy three lamps".parse().unwrap()),
+        ..Default::default()
+    };
+    let todo = ok!(repo.update(created.id, update).await);
+    ensure!(todo.title == "buy three lamps", "title not applied");
+    ensure!(todo.done, "done reverted by a title update");
+
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(same(&todo, &fetched), "get returned {fetched:?}, update {todo:?}");
+    Ok(())
+}
+
+/// Writers editing different fields of one todo at once all keep their
+/// edits: an update must not write back a stale copy of the fields it
+/// wasn't asked to change.
+async fn concurrent_updates_are_not_lost(repo: Arc<dyn TodoRepo>) -> Check {
+    const ROUNDS: usize = 25;
+    let id = ok!(repo.create(titled("share me")).await).id;
+    let revision = ok!(repo.revision().await);
+
+    let edits: [fn(usize) -> UpdateTodo; 3] = [
+        |n| UpdateTodo {
+            description: Some(format!("draft {n}")),
+            ..Default::default()
+        },
+        |n| UpdateTodo {
+            assignee: Some(Some(format!("owner {n}"))),
+            ..Default::default()
+        },
+        |n| UpdateTodo {
+            done: Some(n.is_multiple_of(2)),
+            ..Default::default()
+        },
+    ];
+    let writers: Vec<_> = edits
+        .into_iter()
+        .map(|edit| {
+            let repo = Arc::clone(&repo);
+            tokio::spawn(async move {
+                for n in 0..ROUNDS {
+                    repo.update(id, edit(n)).await?;
+                    tokio::task::yield_now().await;
+                }
+                Ok::<_, AppError>(())
+            })
+        })
+        .collect();
+    for writer in writers {
+        ok!(ok!(writer.await));
+    }
+
+    let last = ROUNDS - 1;
+    let todo = ok!(repo.get(id).await);
+    let description = format!("draft {last}");
+    ensure!(
+        todo.description.as_deref() == Some(description.as_str()),
+        "description ended as {:?}",
+        todo.description
+    );
+    let assignee = format!("owner {last}");
+    ensure!(
+        todo.assignee.as_deref() == Some(assignee.as_str()),
+        "assignee ended as {:?}",
+        todo.assignee
+    );
+    ensure!(todo.done == last.is_multiple_of(2), "done ended as {}", todo.done);
+    if let (Some(before), Some(after)) = (revision, ok!(repo.revision().await)) {
+        let updates = (edits.len() * ROUNDS) as u64;
+        let moved = after - before;
+        ensure!(moved == updates, "{updates} updates moved the revision by {moved}");
+    }
+    Ok(())
+}
+
+/// Toggles racing on one todo each flip it: none may flip from a state
+/// another has already flipped away from.
+async fn concurrent_toggles_are_not_lost(repo: Arc<dyn TodoRepo>) -> Check {
+    const TOGGLES: usize = 24;
+    let id = ok!(repo.create(titled("flip me")).await).id;
+    let revision = ok!(repo.revision().await);
+
+    let togglers: Vec<_> = (0..TOGGLES)
+        .map(|_| {
+            let repo = Arc::clone(&repo);
+            tokio::spawn(async move { repo.toggle(id).await })
+        })
+        .collect();
+    for toggler in togglers {
+        ok!(ok!(toggler.await));
+    }
+
+    let todo = ok!(repo.get(id).await);
+    ensure!(
+        todo.done != TOGGLES.is_multiple_of(2),
+        "{TOGGLES} toggles left done {}",
+        todo.done
+    );
+    if let (Some(before), Some(after)) = (revision, ok!(repo.revision().await)) {
+        let moved = after - before;
+        ensure!(moved == TOGGLES as u64, "{TOGGLES} toggles moved the revision by {moved}");
+    }
+    Ok(())
+}
+
+/// A batch sent with a revision is applied in full while the collection is
+/// at it, and not at all once another write has moved it on. Backends that
+/// keep no revisions are exempt.
+async fn batches_apply_only_at_their_revision(repo: Arc<dyn TodoRepo>) -> Check {
+    let id = ok!(repo.create(titled("a")).await).id;
+    let Some(seen) = ok!(repo.revision().await) else {
+        return Ok(());
+    };
+    let done = || UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    let rename = UpdateTodo {
+        title: Some("a2".parse().unwrap()),
+        ..Default::default()
+    };
+    ok!(repo.update(id, rename).await);
+
+    let stale = repo.update_many_at(seen, vec![(id, done())]).await;
+    let refused = matches!(
+        stale,
+        Err(AppError::PreconditionFailed { revision: Some(current) }) if current > seen
+    );
+    ensure!(refused, "stale batch gave {stale:?}");
+    ensure!(!ok!(repo.get(id).await).done, "stale batch was applied");
+
+    let Some(current) = ok!(repo.revision().await) else {
+        return Err("revision went away".to_string());
+    };
+    let results = ok!(repo.update_many_at(current, vec![(id, done()), (id + 1, done())]).await);
+    ensure!(results.len() == 2, "{} results for 2 updates", results.len());
+    ensure!(
+        results[0].as_ref().is_ok_and(|todo| todo.done && todo.title == "a2"),
+        "fresh batch gave {:?}",
+        results[0]
+    );
+    ensure!(is_not_found(&results[1]), "missing id gave {:?}", results[1]);
+    Ok(())
+}
+
+async fn empty_updates_are_rejected(repo: Arc<dyn TodoRepo>) -> Check {
+    let created = ok!(repo.create(titled("stay put")).await);
+    let result = repo.update(created.id, UpdateTodo::default()).await;
+    ensure!(is_validation(&result), "empty update gave {result:?}");
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(same(&created, &fetched), "empty update changed the todo to {fetched:?}");
+    Ok(())
+}
+
+async fn blank_assignee_updates_are_rejected(repo: Arc<dyn TodoRepo>) -> Check {
+    let created = ok!(repo.create(titled("keep me")).await);
+    let update = UpdateTodo {
+        assignee: Some(Some("  ".to_string())),
+        done: Some(true),
+        ..Default::default()
+    };
+    let result = repo.update(created.id, update).await;
+    ensure!(is_validation(&result), "blank assignee update gave {result:?}");
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(same(&created, &fetched), "rejected update was partly applied: {fetched:?}");
+    Ok(())
+}
+
+/// Every rule of `UpdateTodo::validate` holds for callers that skip it.
+/// Title rules need no check here: an invalid `Title` can't be made.
+async fn invalid_updates_are_rejected(repo: Arc<dyn TodoRepo>) -> Check {
+    let created = ok!(repo.create(titled("keep me")).await);
+    let invalid = [
+        UpdateTodo {
+            description: Some("x".repeat(MAX_DESCRIPTION_BYTES + 1)),
+            ..Default::default()
+        },
+        UpdateTodo {
+            assignee: Some(Some(" ".to_string())),
+            ..Default::default()
+        },
+        UpdateTodo {
+            assignee: Some(Some("x".repeat(MAX_ASSIGNEE_CHARS + 1))),
+            done: Some(true),
+            ..Default::default()
+        },
+    ];
+    for update in invalid {
+        ensure!(update.validate().is_err(), "{update:?} is valid after all");
+        let result = repo.update(created.id, update.clone()).await;
+        ensure!(is_validation(&result), "{update:?} gave {result:?}");
+    }
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(same(&created, &fetched), "rejected update was partly applied: {fetched:?}");
+    Ok(())
+}
+
+async fn assignee_can_be_cleared(repo: Arc<dyn TodoRepo>) -> Check {
+    let input = CreateTodo {
+        assignee: Some("alice".to_string()),
+        ..titled("mow")
+    };
+    let created = ok!(repo.create(input).await);
+    let update = UpdateTodo {
+        assignee: Some(None),
+        ..Default::default()
+    };
+    let todo = ok!(repo.update(created.id, update).await);
+    ensure!(todo.assignee.is_none(), "still assigned to {:?}", todo.assignee);
+    Ok(())
+}
+
+async fn new_due_date_rearms_reminder(repo: Arc<dyn TodoRepo>) -> Check {
+    let due = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    let input = CreateTodo {
+        due: Some(due),
+        ..titled("dentist")
+    };
+    let created = ok!(repo.create(input).await);
+
+    let reminded = ok!(repo.mark_reminded(created.id, due).await);
+    ensure!(
+        reminded.is_some_and(|todo| todo.reminded_at == Some(due)),
+        "first mark_reminded didn't set it"
+    );
+    let again = ok!(repo.mark_reminded(created.id, due).await);
+    ensure!(again.is_none(), "second mark_reminded set it again");
+
+    let update = UpdateTodo {
+        due: Some(due + chrono::Duration::days(1)),
+        ..Default::default()
+    };
+    let todo = ok!(repo.update(created.id, update).await);
+    ensure!(todo.reminded_at.is_none(), "reminded_at kept after a new due date");
+    Ok(())
+}
+
+async fn reminders_leave_updated_at_alone(repo: Arc<dyn TodoRepo>) -> Check {
+    let due = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    let input = CreateTodo {
+        due: Some(due),
+        ..titled("dentist")
+    };
+    let created = ok!(repo.create(input).await);
+    // Far enough apart that a fresh stamp would differ.
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+    let reminded = ok!(repo.mark_reminded(created.id, due).await);
+    ensure!(
+        reminded.is_some_and(|todo| todo.updated_at == created.updated_at),
+        "mark_reminded moved updated_at"
+    );
+    let fetched = ok!(repo.get(created.id).await);
+    ensure!(fetched.updated_at == created.updated_at, "stored updated_at moved");
+    Ok(())
+}
+
+async fn deleted_todos_are_gone(repo: Arc<dyn TodoRepo>) -> Check {
+    let gone = ok!(repo.create(titled("gone")).await).id;
+    let kept = ok!(repo.create(titled("kept")).await).id;
+    ok!(repo.delete(gone).await);
+
+    let result = repo.get(gone).await;
+    ensure!(is_not_found(&result), "get after delete gave {result:?}");
+    let result = repo.delete(gone).await;
+    ensure!(is_not_found(&result), "second delete gave {result:?}");
+    let listed = ids(&ok!(repo.list().await));
+    ensure!(listed == [kept], "list after delete gave {listed:?}");
+    Ok(())
+}
+
+async fn listing_is_in_id_order(repo: Arc<dyn TodoRepo>) -> Check {
+    let mut created = Vec::new();
+    for title in ["c", "a", "b"] {
+        created.push(ok!(repo.create(titled(title)).await).id);
+    }
+    let listed = ids(&ok!(repo.list().await));
+    ensure!(listed == created, "list gave {listed:?}, created {created:?}");
+    Ok(())
+}
+
+async fn filters_and_pages_agree(repo: Arc<dyn TodoRepo>) -> Check {
+    let mut created = Vec::new();
+    for n in 1..=6 {
+        let id = ok!(repo.create(titled(&format!("Item {n}"))).await).id;
+        created.push(id);
+        if n % 2 == 0 {
+            let update = UpdateTodo {
+                done: Some(true),
+                ..Default::default()
+            };
+            ok!(repo.update(id, update).await);
+        }
+    }
+    let done = TodoFilter {
+        done: Some(true),
+        ..Default::default()
+    };
+    let everything = TodoFilter::default();
+
+    let count = ok!(repo.count(&done).await);
+    ensure!(count == 3, "counted {count} done todos");
+    let count = ok!(repo.count(&everything).await);
+    ensure!(count == 6, "counted {count} todos");
+
+    let page = ids(&ok!(repo.list_page(&done, 1, 5).await));
+    ensure!(page == [created[3], created[5]], "offset page gave {page:?}");
+    let chunk = ids(&ok!(repo.list_after(&done, Some(created[1]), 1).await));
+    ensure!(chunk == [created[3]], "keyset chunk gave {chunk:?}");
+    let chunk = ids(&ok!(repo.list_after(&everything, None, 2).await));
+    ensure!(chunk == created[..2], "first keyset chunk gave {chunk:?}");
+
+    let titled_5 = TodoFilter {
+        q: Some("item 5".to_string()),
+        ..Default::default()
+    };
+    let found = ids(&ok!(repo.list_page(&titled_5, 0, 10).await));
+    ensure!(found == [created[4]], "title filter gave {found:?}");
+    Ok(())
+}
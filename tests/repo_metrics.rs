@@ -0,0 +1,81 @@
+// `repo_op_duration_seconds` and `repo_errors_total`: repository calls timed
+// and counted per operation and backend.
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use rust_api::{app, config::Config, test_utils::TestClient, AppState};
+use serde_json::json;
+
+fn client(vars: &[(&str, &str)]) -> TestClient {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    TestClient::new(app(AppState::new_in_memory().with_config(config)))
+}
+
+async fn scrape(client: &TestClient) -> String {
+    client.get("/metrics").await.body.as_str().unwrap().to_string()
+}
+
+/// The value of the sample line starting with `series`.
+fn sample(scrape: &str, series: &str) -> Option<f64> {
+    let line = scrape.lines().find(|line| line.starts_with(series))?;
+    line.rsplit(' ').next()?.parse().ok()
+}
+
+#[tokio::test]
+async fn times_each_operation_by_backend() {
+    let client = client(&[]);
+    client.post_json("/todos", &json!({ "title": "buy milk" })).await;
+    client.get("/todos/1").await;
+    client.get("/todos/1").await;
+    client.put_json("/todos/1", &json!({ "done": true })).await;
+    client.delete("/todos/1").await;
+
+    let scrape = scrape(&client).await;
+    for (op, calls) in [("create", 1.0), ("get", 2.0), ("update", 1.0), ("delete", 1.0)] {
+        let series = format!(r#"repo_op_duration_seconds_count{{backend="memory",op="{op}"}}"#);
+        assert_eq!(sample(&scrape, &series), Some(calls), "{series}\n{scrape}");
+    }
+    // Sub-millisecond buckets by default, for the in-memory store.
+    assert!(scrape.contains(r#"backend="memory",op="get",le="0.0001""#), "{scrape}");
+    assert!(!scrape.contains("repo_errors_total{"), "{scrape}");
+}
+
+#[tokio::test]
+async fn counts_failures_by_error_code() {
+    let client = client(&[]);
+    assert_eq!(client.get("/todos/42").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(client.delete("/todos/42").await.status, StatusCode::NOT_FOUND);
+
+    let scrape = scrape(&client).await;
+    let series = r#"repo_errors_total{backend="memory",kind="not_found",op="get"}"#;
+    assert_eq!(sample(&scrape, series), Some(1.0), "{scrape}");
+    let series = r#"repo_errors_total{backend="memory",kind="not_found",op="delete"}"#;
+    assert_eq!(sample(&scrape, series), Some(1.0), "{scrape}");
+}
+
+#[tokio::test]
+async fn buckets_come_from_the_config() {
+    let client = client(&[("REPO_LATENCY_BUCKETS", "0.005, 0.05,2")]);
+    client.get("/todos").await;
+    let scrape = scrape(&client).await;
+    let series = r#"repo_op_duration_seconds_bucket{backend="memory",op="revision""#;
+    let bounds: Vec<_> = scrape
+        .lines()
+        .filter(|line| line.starts_with(series))
+        .filter_map(|line| line.split("le=\"").nth(1)?.split('"').next())
+        .collect();
+    assert_eq!(bounds, ["0.005", "0.05", "2", "+Inf"], "{scrape}");
+}
+
+#[test]
+fn rejects_unordered_buckets() {
+    for raw in ["0.1,0.01", "0.1,0.1", "-1", "fast", ""] {
+        let vars = HashMap::from([("REPO_LATENCY_BUCKETS".to_string(), raw.to_string())]);
+        assert!(Config::from_lookup(|key| vars.get(key).cloned()).is_err(), "{raw}");
+    }
+}
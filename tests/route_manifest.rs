@@ -0,0 +1,127 @@
+// The route manifest: `app()` is built from `routes::manifest()`, which
+// refuses conflicting routes at startup, and `GET /admin/routes` lists it.
+// The listing is pinned as a snapshot so route changes show up in review.
+
+use axum::{
+    http::{Method, StatusCode},
+    routing::get,
+};
+use rust_api::{
+    app,
+    config::Config,
+    routes::{
+        self,
+        manifest::{Conflict, Manifest, Route},
+    },
+    test_utils::TestClient,
+    AppState,
+};
+use serde_json::Value;
+
+fn ok(_: &AppState) -> axum::routing::MethodRouter<AppState> {
+    get(|| async { "ok" })
+}
+
+#[test]
+fn the_served_manifest_has_no_conflicts() {
+    assert_eq!(routes::manifest().check(), []);
+}
+
+#[test]
+fn a_literal_a_parameter_would_take_is_caught() {
+    let manifest = Manifest::new()
+        .route(Route::new(Method::GET, "/todos/:id", "get_todo", ok))
+        .route(Route::new(Method::GET, "/todos/42", "answer", ok))
+        // Not an id, so `:id` never sees it.
+        .route(Route::new(Method::GET, "/todos/search", "search", ok));
+    let conflicts = manifest.check();
+    assert!(conflicts.contains(&Conflict::Shadowed {
+        literal: "/todos/42".into(),
+        param: "/todos/:id".into(),
+    }));
+    assert!(!conflicts.iter().any(|c| c.to_string().contains("/todos/search")), "{conflicts:?}");
+
+    // Registration order doesn't matter.
+    let reversed = Manifest::new()
+        .route(Route::new(Method::GET, "/ui/app.js", "script", ok))
+        .route(Route::new(Method::GET, "/ui/*path", "asset", ok));
+    assert!(reversed.check().contains(&Conflict::Shadowed {
+        literal: "/ui/app.js".into(),
+        param: "/ui/*path".into(),
+    }));
+}
+
+#[test]
+fn duplicates_and_renamed_parameters_are_caught() {
+    let manifest = Manifest::new()
+        .route(Route::new(Method::GET, "/todos/:id", "get_todo", ok))
+        .route(Route::new(Method::PUT, "/todos/:id", "update_todo", ok))
+        .route(Route::new(Method::GET, "/todos/:id", "get_todo_again", ok))
+        .route(Route::new(Method::POST, "/todos/:todo_id/assign", "assign", ok));
+    let conflicts = manifest.check();
+    assert_eq!(
+        conflicts,
+        [
+            Conflict::Duplicate {
+                method: Method::GET,
+                path: "/todos/:id".into(),
+            },
+            Conflict::ParamNames {
+                first: "/todos/:id".into(),
+                second: "/todos/:todo_id/assign".into(),
+            },
+            Conflict::Unlisted {
+                path: "/todos/:todo_id/assign".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "/todos/42 shadows /todos/:id")]
+fn building_a_conflicting_manifest_fails_fast() {
+    let _ = Manifest::new()
+        .route(Route::new(Method::GET, "/todos/:id", "get_todo", ok))
+        .route(Route::new(Method::DELETE, "/todos/42", "answer", ok))
+        .router(&AppState::new_in_memory());
+}
+
+#[tokio::test]
+async fn operators_can_list_the_routes() {
+    let config =
+        Config::from_lookup(|key| (key == "ADMIN_POLICY").then(|| "public".to_string())).unwrap();
+    let client = TestClient::new(app(AppState::new_in_memory().with_config(config)));
+    let res = client.get("/admin/routes").await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    let routes = res.body.as_array().unwrap();
+    assert_eq!(routes.len(), routes::manifest().routes().len());
+    let listing: Vec<String> = routes
+        .iter()
+        // Feature-gated routes would make the snapshot depend on the build.
+        .filter(|route| {
+            let path = route["path"].as_str().unwrap();
+            !path.starts_with("/graphql") && !path.starts_with("/ui")
+        })
+        .map(|route| {
+            let limits: Vec<&str> =
+                route["limits"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+            let mut line = format!(
+                "{} {} -> {} ({}: {})",
+                route["method"].as_str().unwrap(),
+                route["path"].as_str().unwrap(),
+                route["handler"].as_str().unwrap(),
+                route["group"].as_str().unwrap(),
+                route["policy"].as_str().unwrap(),
+            );
+            if !limits.is_empty() {
+                line += &format!(" limits={}", limits.join(","));
+            }
+            if route["deprecated"] == Value::Bool(true) {
+                line += " deprecated";
+            }
+            line
+        })
+        .collect();
+    insta::assert_snapshot!("route_manifest", listing.join("\n"));
+}
@@ -0,0 +1,177 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    models::{CreateTodo, Todo, UpdateTodo},
+    search::{self, Index},
+    AppState,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn seeded(todos: &[(&str, Option<&str>)]) -> (AppState, Router) {
+    let state = AppState::new_in_memory();
+    for (title, description) in todos {
+        state
+            .service().repo()
+            .create(CreateTodo {
+                description: description.map(str::to_string),
+                ..CreateTodo::new(title.parse().unwrap())
+            })
+            .await
+            .unwrap();
+    }
+    (state.clone(), app(state))
+}
+
+async fn search(app: &Router, query: &str) -> Vec<Value> {
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/todos/search?{query}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice::<Value>(&body)
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+fn ids(hits: &[Value]) -> Vec<u64> {
+    hits.iter()
+        .map(|hit| hit["todo"]["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn matching_more_terms_ranks_higher() {
+    let (_, app) = seeded(&[
+        ("milk milk milk", Some("milk again")),
+        ("weekly shopping", Some("buy milk at the market")),
+        ("shopping list", None),
+        ("call mom", None),
+    ])
+    .await;
+
+    let hits = search(&app, "q=milk+shopping").await;
+    assert_eq!(ids(&hits)[0], 2);
+    assert_eq!(hits.len(), 3);
+    assert!(hits[0]["score"].as_f64().unwrap() > 0.0);
+}
+
+#[tokio::test]
+async fn title_matches_outweigh_description_matches() {
+    let (_, app) = seeded(&[
+        ("errands", Some("pick up the parcel")),
+        ("parcel", Some("post office")),
+    ])
+    .await;
+    assert_eq!(ids(&search(&app, "q=parcel").await), vec![2, 1]);
+}
+
+#[tokio::test]
+async fn highlights_are_byte_offsets_of_matched_words() {
+    let (_, app) = seeded(&[("Café, then MILK!", Some("oat-milk or cow milk"))]).await;
+
+    let hits = search(&app, "q=milk%20caf%C3%A9").await;
+    let highlights = &hits[0]["highlights"];
+    // "Café" is five bytes long in UTF-8.
+    assert_eq!(highlights["title"], serde_json::json!([[0, 5], [12, 16]]));
+    assert_eq!(highlights["description"], serde_json::json!([[4, 8], [16, 20]]));
+
+    let title = "Café, then MILK!";
+    assert_eq!(&title[12..16], "MILK");
+}
+
+#[tokio::test]
+async fn index_follows_updates_and_deletes() {
+    let (state, app) = seeded(&[("buy milk", None), ("buy bread", None)]).await;
+    assert_eq!(ids(&search(&app, "q=milk").await), vec![1]);
+
+    let rename = UpdateTodo {
+        title: Some("buy cheese".parse().unwrap()),
+        description: Some("and crackers".into()),
+        ..Default::default()
+    };
+    state.service().repo().update(1, rename).await.unwrap();
+    assert!(search(&app, "q=milk").await.is_empty());
+    assert_eq!(ids(&search(&app, "q=crackers").await), vec![1]);
+
+    state.service().repo().delete(2).await.unwrap();
+    assert!(search(&app, "q=bread").await.is_empty());
+    assert_eq!(ids(&search(&app, "q=buy").await), vec![1]);
+}
+
+#[tokio::test]
+async fn empty_queries_and_bad_limits_are_rejected() {
+    let (_, app) = seeded(&[]).await;
+    for query in ["q=", "q=%20%2C%20", "q=milk&limit=0", "q=milk&limit=-1"] {
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/todos/search?{query}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn queries_match_titles_by_canonical_key() {
+    let (_, app) = seeded(&[("CAFE\u{301} au lait", None), ("Ｔｅａ", None), ("coffee", None)]).await;
+
+    assert_eq!(ids(&search(&app, "q=caf%C3%A9").await), [1]);
+    assert_eq!(ids(&search(&app, "q=TEA").await), [2]);
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/todos?q=caf%C3%A9%20%20AU")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let todos: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(todos.as_array().unwrap().len(), 1);
+    assert_eq!(todos[0]["title"], "CAFE\u{301} au lait");
+}
+
+#[test]
+fn tokenizer_lowercases_on_word_boundaries() {
+    let tokens: Vec<String> = search::tokenize("Milk, eggs & CAFÉ — don't").collect();
+    assert_eq!(tokens, ["milk", "eggs", "café", "don't"]);
+}
+
+#[test]
+fn removing_a_document_drops_its_postings() {
+    let todo = Todo {
+        description: Some("beta gamma".into()),
+        ..Todo::new(7, "alpha beta".parse().unwrap(), Utc::now())
+    };
+    let mut index = Index::default();
+    index.insert(&todo);
+    assert_eq!(index.document_frequency("beta"), 1);
+    index.remove(&todo);
+    for token in ["alpha", "beta", "gamma"] {
+        assert_eq!(index.document_frequency(token), 0);
+    }
+}
@@ -0,0 +1,183 @@
+// The service layer on its own: no router, a scriptable repository, and a
+// local event bus, checking which events and audit entries each call leaves.
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use axum::http::StatusCode;
+use futures::{FutureExt, StreamExt};
+use rust_api::{
+    app,
+    audit::AuditAction,
+    config::Config,
+    errors::AppError,
+    events::{EventBus, EventStream, LocalBus, TodoEvent},
+    models::{CreateTodo, Pagination, TodoFilter, UpdateTodo},
+    service::TodoService,
+    test_utils::{MockRepo, RepoMethod, TestClient},
+    AppState,
+};
+use serde_json::json;
+
+struct Harness {
+    service: TodoService,
+    mock: Arc<MockRepo>,
+    events: EventStream,
+}
+
+fn harness(config: Config) -> Harness {
+    let mock = Arc::new(MockRepo::new());
+    let bus = Arc::new(LocalBus::default());
+    let events = bus.subscribe();
+    let config = Arc::new(ArcSwap::from_pointee(config));
+    let service = TodoService::new(mock.clone(), bus, config, Arc::default());
+    Harness {
+        service,
+        mock,
+        events,
+    }
+}
+
+fn titled(title: &str) -> CreateTodo {
+    CreateTodo::new(title.parse().unwrap())
+}
+
+/// Events published since the last call, as `(kind, id)`.
+fn published(events: &mut EventStream) -> Vec<(&'static str, u64)> {
+    let mut seen = Vec::new();
+    while let Some(Some(event)) = events.next().now_or_never() {
+        seen.push(match event {
+            TodoEvent::Created { todo } => ("created", todo.id),
+            TodoEvent::Updated { todo } => ("updated", todo.id),
+            TodoEvent::Deleted { id } => ("deleted", id),
+            TodoEvent::Assigned { todo } => ("assigned", todo.id),
+            TodoEvent::Reminder { todo } => ("reminder", todo.id),
+        });
+    }
+    seen
+}
+
+fn audited(service: &TodoService) -> Vec<(AuditAction, u64)> {
+    let entries = service.audit().entries();
+    entries.into_iter().map(|entry| (entry.action, entry.todo_id)).collect()
+}
+
+#[tokio::test]
+async fn writes_publish_events_and_are_audited() {
+    let Harness {
+        service,
+        mut events,
+        ..
+    } = harness(Config::default());
+
+    let input = CreateTodo {
+        assignee: Some("alice".to_string()),
+        ..titled("write docs")
+    };
+    let id = service.create(input, false).await.unwrap().todo.id;
+    assert_eq!(published(&mut events), [("created", id), ("assigned", id)]);
+
+    let done = UpdateTodo {
+        done: Some(true),
+        ..Default::default()
+    };
+    assert!(service.update(id, done).await.unwrap().done);
+    assert!(!service.toggle(id).await.unwrap().done);
+    assert_eq!(published(&mut events), [("updated", id), ("updated", id)]);
+
+    service.delete(id).await.unwrap();
+    assert_eq!(published(&mut events), [("deleted", id)]);
+
+    let expected = [
+        (AuditAction::Create, id),
+        (AuditAction::Update, id),
+        (AuditAction::Update, id),
+        (AuditAction::Delete, id),
+    ];
+    assert_eq!(audited(&service), expected);
+}
+
+#[tokio::test]
+async fn rejected_writes_leave_no_trace() {
+    let Harness {
+        service,
+        mock,
+        mut events,
+    } = harness(Config::default());
+    let id = service.create(titled("keep me"), false).await.unwrap().todo.id;
+    published(&mut events);
+
+    let blank = CreateTodo {
+        assignee: Some("  ".to_string()),
+        ..titled("unassignable")
+    };
+    let blank = service.create(blank, false).await;
+    assert!(matches!(blank, Err(AppError::Validation(_))), "{blank:?}");
+    assert_eq!(mock.calls(RepoMethod::Create), 1, "invalid input reached the repo");
+
+    let empty = service.update(id, UpdateTodo::default()).await;
+    assert!(matches!(empty, Err(AppError::Validation(_))), "{empty:?}");
+
+    mock.fail_next(RepoMethod::Toggle, AppError::Internal);
+    assert!(matches!(service.toggle(id).await, Err(AppError::Internal)));
+    mock.fail_next(RepoMethod::Delete, AppError::Internal);
+    assert!(matches!(service.delete(id).await, Err(AppError::Internal)));
+    assert!(matches!(service.delete(id + 1).await, Err(AppError::NotFound)));
+
+    assert!(published(&mut events).is_empty());
+    assert_eq!(audited(&service), [(AuditAction::Create, id)]);
+}
+
+#[tokio::test]
+async fn create_applies_the_duplicate_policy() {
+    let config = Config {
+        duplicate_warning: true,
+        ..Config::default()
+    };
+    let Harness {
+        service,
+        mut events,
+        ..
+    } = harness(config);
+    let first = service.create(titled("buy milk"), false).await.unwrap().todo.id;
+
+    let warned = service.create(titled("Buy milk!"), false).await.unwrap();
+    assert_eq!(warned.possible_duplicates, [first]);
+
+    let both = [first, warned.todo.id];
+    let refused = service.create(titled("buy milk"), true).await;
+    assert!(matches!(&refused, Err(AppError::Duplicate(ids)) if ids == &both), "{refused:?}");
+    assert_eq!(published(&mut events).len(), 2);
+    assert_eq!(audited(&service).len(), 2);
+}
+
+#[tokio::test]
+async fn list_fills_in_the_total() {
+    let Harness { service, .. } = harness(Config::default());
+    for title in ["a", "b", "c"] {
+        service.create(titled(title), false).await.unwrap();
+    }
+    let page = Pagination {
+        limit: 2,
+        offset: 1,
+        ..Pagination::default()
+    };
+    let (todos, page) = service.list(&TodoFilter::default(), page).await.unwrap();
+    let titles: Vec<_> = todos.iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, ["b", "c"]);
+    assert_eq!(page.total, 3);
+}
+
+#[tokio::test]
+async fn http_writes_go_through_the_service() {
+    let state = AppState::new_in_memory();
+    let client = TestClient::new(app(state.clone()));
+    let created = client.post_json("/todos", &json!({ "title": "ship it" })).await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    let uri = format!("/todos/{}", created.body["id"]);
+    client.put_json(&uri, &json!({ "done": true })).await;
+    client.delete(&uri).await;
+
+    let actions: Vec<_> = audited(state.service()).into_iter().map(|(action, _)| action).collect();
+    assert_eq!(actions, [AuditAction::Create, AuditAction::Update, AuditAction::Delete]);
+}
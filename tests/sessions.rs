@@ -0,0 +1,170 @@
+// Browser sessions: `POST /auth/session` trades an API key for a cookie the
+// API accepts like the key, writes on the cookie alone need the CSRF token,
+// and `DELETE /auth/session` ends the session at once.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_api::{
+    app,
+    config::Config,
+    sessions::{LoggedIn, CSRF_HEADER},
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+use serde_json::json;
+
+const KEY: &str = "browser-key-1";
+
+fn client() -> TestClient {
+    let config = Config::from_lookup(|key| match key {
+        "API_POLICY" => Some("api_key".into()),
+        "API_KEYS" => Some(KEY.into()),
+        _ => None,
+    })
+    .unwrap();
+    TestClient::new(app(AppState::new_in_memory().with_config(config)))
+}
+
+/// Logs in and returns the `Cookie` header to send and the CSRF token.
+async fn login(client: &TestClient) -> (String, String) {
+    let res = client
+        .post_json("/auth/session", &json!({ "api_key": KEY }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    let cookies: Vec<&str> = res
+        .headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .collect();
+    let session = cookies.iter().find(|c| c.starts_with("session=")).unwrap();
+    for attribute in ["HttpOnly", "SameSite=Lax", "Secure"] {
+        assert!(session.contains(attribute), "{session}");
+    }
+    let csrf = cookies
+        .iter()
+        .find(|c| c.starts_with("csrf_token="))
+        .unwrap();
+    assert!(
+        !csrf.contains("HttpOnly"),
+        "the page must be able to read {csrf}"
+    );
+
+    let logged_in: LoggedIn = res.json();
+    let cookie = cookies
+        .iter()
+        .map(|c| c.split(';').next().unwrap())
+        .collect::<Vec<_>>()
+        .join("; ");
+    (cookie, logged_in.csrf_token)
+}
+
+async fn create(client: &TestClient, cookie: &str, csrf: Option<&str>) -> TestResponse {
+    let mut req = Request::post("/v1/todos")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::COOKIE, cookie);
+    if let Some(csrf) = csrf {
+        req = req.header(CSRF_HEADER, csrf);
+    }
+    let body = json!({ "title": "from the browser" }).to_string();
+    client.send(req.body(Body::from(body)).unwrap()).await
+}
+
+async fn list(client: &TestClient, cookie: &str) -> TestResponse {
+    let req = Request::get("/v1/todos").header(header::COOKIE, cookie);
+    client.send(req.body(Body::empty()).unwrap()).await
+}
+
+#[tokio::test]
+async fn a_session_cookie_authenticates_writes_with_the_csrf_token() {
+    let client = client();
+    assert_eq!(
+        client.get("/v1/todos").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    let (cookie, csrf) = login(&client).await;
+
+    assert_eq!(list(&client, &cookie).await.status, StatusCode::OK);
+    let res = create(&client, &cookie, Some(&csrf)).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+}
+
+#[tokio::test]
+async fn cookie_writes_without_the_csrf_token_are_refused() {
+    let client = client();
+    let (cookie, csrf) = login(&client).await;
+
+    for token in [None, Some("not-the-token")] {
+        let res = create(&client, &cookie, token).await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN, "{token:?}");
+        assert!(
+            res.body["error"].as_str().unwrap().contains("CSRF"),
+            "{}",
+            res.body
+        );
+    }
+    // Another session's token doesn't do either.
+    let (other_cookie, _) = login(&client).await;
+    assert_eq!(
+        create(&client, &other_cookie, Some(&csrf)).await.status,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        list(&client, &cookie).await.body.as_array().unwrap().len(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session_at_once() {
+    let client = client();
+    let (cookie, csrf) = login(&client).await;
+
+    let req = Request::delete("/auth/session").header(header::COOKIE, &cookie);
+    let res = client.send(req.body(Body::empty()).unwrap()).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert!(res.headers[header::SET_COOKIE]
+        .to_str()
+        .unwrap()
+        .contains("Max-Age=0"));
+
+    assert_eq!(
+        list(&client, &cookie).await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        create(&client, &cookie, Some(&csrf)).await.status,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn only_known_keys_can_log_in() {
+    let client = client();
+    let res = client
+        .post_json("/auth/session", &json!({ "api_key": "guess" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert!(res.headers.get(header::SET_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn session_ids_and_csrf_tokens_are_distinct_128_bit_tokens() {
+    let client = client();
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..2 {
+        let (cookie, csrf) = login(&client).await;
+        let id = cookie
+            .split("; ")
+            .find_map(|c| c.strip_prefix("session="))
+            .unwrap()
+            .to_string();
+        for token in [id, csrf] {
+            assert_eq!(token.len(), 32, "{token}");
+            assert!(token.bytes().all(|b| b.is_ascii_hexdigit()), "{token}");
+            assert!(seen.insert(token), "tokens repeat");
+        }
+    }
+}
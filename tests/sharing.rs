@@ -0,0 +1,222 @@
+// Share links: `POST /todos/:id/share` makes a signed, expiring link that
+// opens the todo without a key until it expires or is revoked.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use chrono::{Duration, TimeZone, Utc};
+use rust_api::{
+    app,
+    config::Config,
+    models::CreateTodo,
+    sharing::{Claims, Shared},
+    test_utils::{MockClock, TestClient, TestResponse},
+    AppState,
+};
+
+const SECRET: &str = "0123456789abcdef0123456789abcdef";
+const KEY: &str = "owner-key";
+
+/// The API behind `api_key`, sharing on, and todo 1 to share.
+async fn setup(vars: &[(&str, &str)]) -> (TestClient, Arc<MockClock>) {
+    let mut vars: HashMap<String, String> = [
+        ("API_POLICY", "api_key"),
+        ("API_KEYS", KEY),
+        ("SHARE_SECRET", SECRET),
+    ]
+    .iter()
+    .chain(vars)
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    vars.retain(|_, value| !value.is_empty());
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let clock = Arc::new(MockClock::new(
+        Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap(),
+    ));
+    let state = AppState::in_memory_with_clock(clock.clone()).with_config(config);
+    state
+        .service()
+        .repo()
+        .create(CreateTodo::new("water the plants".parse().unwrap()))
+        .await
+        .unwrap();
+    (TestClient::new(app(state)), clock)
+}
+
+/// Sends `method uri` with the owner's key.
+async fn owner(client: &TestClient, method: &str, uri: &str) -> TestResponse {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {KEY}"))
+        .body(Body::empty())
+        .unwrap();
+    client.send(request).await
+}
+
+async fn share(client: &TestClient) -> Shared {
+    let res = owner(client, "POST", "/v1/todos/1/share").await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let shared: Shared = res.json();
+    assert_eq!(res.headers[header::LOCATION], shared.url.as_str());
+    shared
+}
+
+#[tokio::test]
+async fn shared_todos_open_without_a_key() {
+    let (client, _) = setup(&[]).await;
+    assert_eq!(
+        client.get("/v1/todos/1").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let shared = share(&client).await;
+    assert!(shared.url.starts_with("/shared/"), "{}", shared.url);
+    assert_eq!(
+        shared.expires_at,
+        Utc.with_ymd_and_hms(2024, 5, 8, 9, 0, 0).unwrap()
+    );
+
+    let res = client.get(&shared.url).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["id"], 1);
+    assert_eq!(res.body["title"], "water the plants");
+    assert_eq!(res.headers[header::REFERRER_POLICY], "no-referrer");
+    assert_eq!(res.headers[header::CACHE_CONTROL], "no-store");
+
+    let request = Request::get(&shared.url)
+        .header(header::ACCEPT, "text/html")
+        .body(Body::empty())
+        .unwrap();
+    let res = client.send(request).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.as_str().unwrap().contains("water the plants"));
+
+    // Read-only: the link is no key for anything else.
+    let request = Request::put("/v1/todos/1")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"done": true}"#))
+        .unwrap();
+    assert_eq!(client.send(request).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        owner(&client, "POST", "/v1/todos/9/share").await.status,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn links_expire() {
+    let (client, clock) = setup(&[("SHARE_TTL_SECS", "3600")]).await;
+    let shared = share(&client).await;
+
+    clock.advance(Duration::minutes(59));
+    assert_eq!(client.get(&shared.url).await.status, StatusCode::OK);
+
+    clock.advance(Duration::minutes(1));
+    let res = client.get(&shared.url).await;
+    assert_eq!(res.status, StatusCode::GONE);
+    assert_eq!(res.body["error"], "this share link has expired");
+}
+
+#[tokio::test]
+async fn revoking_ends_every_earlier_link() {
+    let (client, _) = setup(&[]).await;
+    let first = share(&client).await;
+    let second = share(&client).await;
+
+    let res = owner(&client, "DELETE", "/v1/todos/1/share").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    for shared in [&first, &second] {
+        let res = client.get(&shared.url).await;
+        assert_eq!(res.status, StatusCode::GONE);
+        assert_eq!(res.body["error"], "this share link has been revoked");
+    }
+
+    // Sharing again makes a link that works.
+    let third = share(&client).await;
+    assert_eq!(client.get(&third.url).await.status, StatusCode::OK);
+    assert_eq!(client.get(&first.url).await.status, StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn tampered_tokens_are_refused() {
+    let (client, _) = setup(&[]).await;
+    let shared = share(&client).await;
+    let token = shared.url.strip_prefix("/shared/").unwrap();
+
+    let claims = Claims::verify(token, SECRET.as_bytes()).unwrap();
+    assert_eq!(claims.todo_id, 1);
+    let (payload, signature) = token.rsplit_once('.').unwrap();
+    let other_todo = format!("2{}.{signature}", &payload[1..]);
+    let far_future = Claims {
+        expires_at: claims.expires_at + Duration::days(365),
+        ..claims
+    };
+    let forged = far_future.sign(b"not the server's secret, but long enough");
+    for token in [
+        other_todo.as_str(),
+        &forged,
+        &token[..token.len() - 2],
+        "garbage",
+    ] {
+        let res = client.get(&format!("/shared/{token}")).await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED, "{token}");
+        assert_eq!(res.body["error"], "invalid share link");
+        assert!(res.headers.get(header::WWW_AUTHENTICATE).is_none());
+    }
+}
+
+#[tokio::test]
+async fn opening_links_is_rate_limited() {
+    let (client, _) = setup(&[("SHARE_RATE_PER_MINUTE", "3")]).await;
+    let shared = share(&client).await;
+
+    for _ in 0..2 {
+        assert_eq!(
+            client.get("/shared/guess").await.status,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+    assert_eq!(client.get(&shared.url).await.status, StatusCode::OK);
+    let res = client.get(&shared.url).await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(res.headers.contains_key(header::RETRY_AFTER));
+
+    // The owner's own routes aren't counted.
+    assert_eq!(
+        owner(&client, "GET", "/v1/todos/1").await.status,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn sharing_is_off_without_a_secret() {
+    let (client, _) = setup(&[("SHARE_SECRET", "")]).await;
+
+    assert_eq!(
+        owner(&client, "POST", "/v1/todos/1/share").await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        owner(&client, "DELETE", "/v1/todos/1/share").await.status,
+        StatusCode::NOT_FOUND
+    );
+    let token = Claims {
+        todo_id: 1,
+        generation: 0,
+        expires_at: Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap(),
+    }
+    .sign(SECRET.as_bytes());
+    let res = client.get(&format!("/shared/{token}")).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn short_secrets_are_rejected() {
+    let err = Config::from_lookup(|key| (key == "SHARE_SECRET").then(|| "hunter2".to_string()))
+        .unwrap_err();
+    assert!(err.to_string().contains("SHARE_SECRET"), "{err}");
+}
@@ -0,0 +1,127 @@
+// Slow-request detection: a repository that sleeps lets us push a request past
+// its latency budget deterministically.
+
+mod common;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum::{body::Body, http::Request, Router};
+use common::LogCapture;
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    config::Config,
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::TodoRepo,
+    AppState,
+};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Every call takes `delay` and finds nothing.
+struct SlowRepo {
+    delay: Duration,
+}
+
+#[async_trait]
+impl TodoRepo for SlowRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        tokio::time::sleep(self.delay).await;
+        Ok(Vec::new())
+    }
+    async fn create(&self, _input: CreateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn get(&self, _id: u64) -> Result<Todo, AppError> {
+        tokio::time::sleep(self.delay).await;
+        Err(AppError::NotFound)
+    }
+    async fn update(&self, _id: u64, _input: UpdateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn delete(&self, _id: u64) -> Result<(), AppError> {
+        Err(AppError::Internal)
+    }
+}
+
+fn slow_app(delay_ms: u64, vars: &[(&str, &str)]) -> Router {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    let repo = Arc::new(SlowRepo {
+        delay: Duration::from_millis(delay_ms),
+    });
+    app(AppState::with_repo(repo).with_config(config))
+}
+
+async fn get(app: &Router, uri: &str) -> String {
+    let res = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+fn slow_warnings(logs: &LogCapture) -> Vec<common::CapturedEvent> {
+    logs.events()
+        .into_iter()
+        .filter(|event| event.field("message") == Some("slow request"))
+        .collect()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn logs_and_counts_requests_over_threshold() {
+    let logs = LogCapture::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let app = slow_app(30, &[("SLOW_REQUEST_THRESHOLD_MS", "10")]);
+
+    get(&app, "/todos").await;
+
+    let warnings = slow_warnings(&logs);
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].field("route"), Some("/todos"));
+    assert!(warnings[0].field("request_id").is_some());
+
+    let scrape = get(&app, "/metrics").await;
+    assert!(
+        scrape.contains(r#"slow_requests_total{route="/todos"} 1"#),
+        "{scrape}"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn fast_requests_are_not_flagged() {
+    let logs = LogCapture::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let app = slow_app(0, &[("SLOW_REQUEST_THRESHOLD_MS", "500")]);
+
+    get(&app, "/todos").await;
+
+    assert!(slow_warnings(&logs).is_empty());
+    assert!(!get(&app, "/metrics").await.contains("slow_requests_total{"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn route_group_override_raises_the_budget() {
+    let logs = LogCapture::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(logs.clone()));
+    let app = slow_app(
+        30,
+        &[
+            ("SLOW_REQUEST_THRESHOLD_MS", "10"),
+            ("SLOW_REQUEST_OVERRIDES", "/todos/:id=5000"),
+        ],
+    );
+
+    get(&app, "/todos/1").await;
+    assert!(slow_warnings(&logs).is_empty());
+
+    get(&app, "/todos").await;
+    assert_eq!(slow_warnings(&logs).len(), 1);
+}
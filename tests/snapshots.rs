@@ -0,0 +1,154 @@
+// Wire-format contract: every JSON shape clients see is pinned as an `insta`
+// snapshot under `tests/snapshots/`. A renamed or dropped field shows up as
+// a snapshot diff; review it with `cargo insta review` and accept it only if
+// the change is intended.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use http_body_util::BodyExt;
+use insta::assert_json_snapshot;
+use rust_api::{app, errors::AppError, models::CreateTodo, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn seeded() -> Router {
+    let state = AppState::new_in_memory();
+    for (title, description) in [("write docs", Some("the *README*")), ("ship it", None)] {
+        let input = CreateTodo {
+            description: description.map(str::to_string),
+            ..CreateTodo::new(title.parse().unwrap())
+        };
+        state.service().repo().create(input).await.unwrap();
+    }
+    app(state)
+}
+
+/// Status, content type, and body (JSON when it parses, text otherwise).
+async fn shape(response: Response) -> Value {
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    json!({ "status": status, "content_type": content_type, "body": body })
+}
+
+async fn send(app: &Router, method: &str, uri: &str, body: Option<Value>) -> Value {
+    let mut request = Request::builder().method(method).uri(uri);
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    shape(app.clone().oneshot(request.body(body).unwrap()).await.unwrap()).await
+}
+
+#[tokio::test]
+async fn created_todo() {
+    let app = seeded().await;
+    let body = json!({
+        "title": "buy milk",
+        "description": "oat",
+        "due": "2024-05-01T17:00:00Z",
+        "assignee": "alice",
+    });
+    let created = send(&app, "POST", "/todos", Some(body)).await;
+    assert_json_snapshot!(created, {
+        ".body.id" => "[id]",
+        ".body.created_at" => "[timestamp]",
+        ".body.updated_at" => "[timestamp]",
+    });
+}
+
+#[tokio::test]
+async fn list() {
+    let app = seeded().await;
+    assert_json_snapshot!(send(&app, "GET", "/todos", None).await, {
+        ".body[].created_at" => "[timestamp]",
+        ".body[].updated_at" => "[timestamp]",
+    });
+}
+
+#[tokio::test]
+async fn list_envelope() {
+    let app = seeded().await;
+    let list = send(&app, "GET", "/todos?envelope=true", None).await;
+    assert_json_snapshot!(list, {
+        ".body.data[].created_at" => "[timestamp]",
+        ".body.data[].updated_at" => "[timestamp]",
+        ".body.meta.request_id" => "[request id]",
+    });
+}
+
+#[tokio::test]
+async fn paginated_list_envelope() {
+    let app = seeded().await;
+    let page = send(&app, "GET", "/todos?envelope=true&limit=1&offset=1", None).await;
+    assert_json_snapshot!(page, {
+        ".body.data[].created_at" => "[timestamp]",
+        ".body.data[].updated_at" => "[timestamp]",
+        ".body.meta.request_id" => "[request id]",
+    });
+}
+
+#[tokio::test]
+async fn error_bodies() {
+    let errors = [
+        AppError::NotFound,
+        AppError::Validation("title cannot be empty".into()),
+        AppError::InvalidId("-1".to_string()),
+        AppError::UnsupportedMediaType("text/plain".to_string()),
+        AppError::PayloadTooLarge,
+        AppError::Internal,
+        AppError::ReadOnly,
+        AppError::RateLimited {
+            retry_after_secs: 42,
+        },
+        AppError::Unavailable,
+        AppError::Duplicate(vec![3, 7]),
+        AppError::PreconditionFailed { revision: Some(12) },
+    ];
+    for error in errors {
+        let name = format!("error_{}", error.code());
+        let response = error.into_response();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .map(|value| value.to_str().unwrap().to_string());
+        let mut shape = shape(response).await;
+        shape["retry_after"] = json!(retry_after);
+        assert_json_snapshot!(name, shape);
+    }
+}
+
+#[tokio::test]
+async fn enveloped_error() {
+    let app = seeded().await;
+    let missing = send(&app, "GET", "/todos/99?envelope=true", None).await;
+    assert_json_snapshot!(missing, { ".body.meta.request_id" => "[request id]" });
+}
+
+#[tokio::test]
+async fn unknown_route() {
+    let app = seeded().await;
+    let response = send(&app, "GET", "/nope", None).await;
+    assert_eq!(response["status"], StatusCode::NOT_FOUND.as_u16());
+    assert_json_snapshot!(response);
+}
+
+#[tokio::test]
+async fn method_not_allowed() {
+    let app = seeded().await;
+    let response = send(&app, "PATCH", "/todos", None).await;
+    assert_eq!(response["status"], StatusCode::METHOD_NOT_ALLOWED.as_u16());
+    assert_json_snapshot!(response);
+}
@@ -0,0 +1,103 @@
+// Startup waits for the repository before binding, and `GET /ready` reports
+// the same check.
+
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    config::Config,
+    errors::AppError,
+    startup,
+    test_utils::{MockRepo, RepoMethod, TestClient},
+    AppState,
+};
+use tokio::{net::TcpStream, sync::oneshot};
+
+/// A mock whose first `failures` pings fail.
+fn flaky(failures: usize) -> Arc<MockRepo> {
+    let repo = Arc::new(MockRepo::new());
+    for _ in 0..failures {
+        repo.fail_next(RepoMethod::Ping, AppError::Unavailable);
+    }
+    repo
+}
+
+/// State serving `repo` on a free local port, with `vars` on top.
+fn state(repo: Arc<MockRepo>, vars: &[(&str, &str)]) -> (AppState, SocketAddr) {
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    vars.insert("HOST".into(), "127.0.0.1".into());
+    vars.insert("PORT".into(), addr.port().to_string());
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    (AppState::with_repo(repo).with_config(config), addr)
+}
+
+#[tokio::test]
+async fn retries_until_the_repository_answers() {
+    let repo = flaky(2);
+
+    let attempts = startup::wait_for_repo(repo.as_ref(), Duration::from_secs(5)).await.unwrap();
+    assert_eq!(attempts, 3);
+    assert_eq!(repo.calls(RepoMethod::Ping), 3);
+}
+
+#[tokio::test]
+async fn binds_only_after_the_repository_is_ready() {
+    let repo = flaky(2);
+    let (state, addr) = state(repo.clone(), &[]);
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(startup::run(state, async {
+        stopped.await.ok();
+    }));
+
+    let connected = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert!(connected.is_ok(), "never bound {addr}");
+    // Nothing listened until the third ping succeeded.
+    assert_eq!(repo.calls(RepoMethod::Ping), 3);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn gives_up_without_binding_when_the_budget_runs_out() {
+    let repo = flaky(usize::from(u8::MAX));
+    let (state, addr) = state(repo.clone(), &[("STARTUP_WAIT_SECS", "1")]);
+
+    let err = startup::run(state, std::future::pending()).await.unwrap_err();
+    let message = format!("{err:#}");
+    assert!(message.starts_with("repository not ready after"), "{message}");
+    assert!(message.contains("service temporarily unavailable"), "{message}");
+    let attempts = repo.calls(RepoMethod::Ping);
+    assert!((3..=10).contains(&attempts), "{attempts} pings in 1s");
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn ready_reports_the_same_check() {
+    let repo = flaky(1);
+    let client = TestClient::new(app(AppState::with_repo(repo)));
+
+    let res = client.get("/ready").await;
+    assert_eq!(res.status, StatusCode::SERVICE_UNAVAILABLE);
+    let res = client.get("/ready").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body, "ready");
+}
@@ -0,0 +1,155 @@
+// `GET /todos/stats/timeseries`: created/completed counts per day or week,
+// with empty buckets filled in.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use rust_api::{
+    app,
+    errors::AppError,
+    models::{CreateTodo, Todo, UpdateTodo},
+    state::TodoRepo,
+    test_utils::TestClient,
+    AppState,
+};
+use serde_json::{json, Value};
+
+const URI: &str = "/todos/stats/timeseries";
+
+/// Serves a fixed set of todos, so timestamps are under the test's control.
+struct FixedRepo(Vec<Todo>);
+
+#[async_trait]
+impl TodoRepo for FixedRepo {
+    async fn list(&self) -> Result<Vec<Todo>, AppError> {
+        Ok(self.0.clone())
+    }
+    async fn create(&self, _input: CreateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn get(&self, _id: u64) -> Result<Todo, AppError> {
+        Err(AppError::NotFound)
+    }
+    async fn update(&self, _id: u64, _input: UpdateTodo) -> Result<Todo, AppError> {
+        Err(AppError::Internal)
+    }
+    async fn delete(&self, _id: u64) -> Result<(), AppError> {
+        Err(AppError::Internal)
+    }
+}
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+fn todo(id: u64, created: &str, done: Option<&str>) -> Todo {
+    Todo {
+        done: done.is_some(),
+        done_at: done.map(at),
+        ..Todo::new(id, format!("todo {id}").parse().unwrap(), at(created))
+    }
+}
+
+fn fixed_client() -> TestClient {
+    let todos = vec![
+        todo(1, "2024-05-01T09:00:00Z", Some("2024-05-03T10:00:00Z")),
+        todo(2, "2024-05-01T23:59:59Z", None),
+        todo(3, "2024-05-03T00:00:00Z", Some("2024-05-06T12:00:00Z")),
+        // Outside the ranges below.
+        todo(4, "2024-04-30T23:59:59Z", Some("2024-05-20T08:00:00Z")),
+    ];
+    TestClient::new(app(AppState::with_repo(Arc::new(FixedRepo(todos)))))
+}
+
+fn counts(points: &Value) -> Vec<(&str, u64, u64)> {
+    points
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| {
+            let start = point["bucket_start"].as_str().unwrap();
+            let created = point["created"].as_u64().unwrap();
+            (start, created, point["completed"].as_u64().unwrap())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn daily_buckets_are_zero_filled() {
+    let res = fixed_client()
+        .get(&format!("{URI}?bucket=day&from=2024-05-01T00:00:00Z&to=2024-05-08T00:00:00Z"))
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(
+        counts(&res.body),
+        [
+            ("2024-05-01T00:00:00.000Z", 2, 0),
+            ("2024-05-02T00:00:00.000Z", 0, 0),
+            ("2024-05-03T00:00:00.000Z", 1, 1),
+            ("2024-05-04T00:00:00.000Z", 0, 0),
+            ("2024-05-05T00:00:00.000Z", 0, 0),
+            ("2024-05-06T00:00:00.000Z", 0, 1),
+            ("2024-05-07T00:00:00.000Z", 0, 0),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn weekly_buckets_start_on_monday() {
+    let res = fixed_client()
+        .get(&format!("{URI}?bucket=week&from=2024-05-01T00:00:00Z&to=2024-05-20T00:00:00Z"))
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    // 2024-04-29 is a Monday. Todo 4 was created before `from` and finished
+    // at `to`, so it counts in neither.
+    assert_eq!(
+        counts(&res.body),
+        [
+            ("2024-04-29T00:00:00.000Z", 3, 1),
+            ("2024-05-06T00:00:00.000Z", 0, 1),
+            ("2024-05-13T00:00:00.000Z", 0, 0),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn completing_and_reopening_moves_done_at() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    client.post_json("/todos", &json!({ "title": "ship it" })).await;
+    let res = client.put_json("/todos/1", &json!({ "done": true })).await;
+    assert!(res.body["done_at"].is_string());
+
+    // The last 30 days by default, today included.
+    let res = client.get(URI).await;
+    let points = counts(&res.body);
+    assert_eq!(points.len(), 31);
+    assert_eq!(points.last().map(|(_, created, done)| (*created, *done)), Some((1, 1)));
+
+    let res = client.put_json("/todos/1", &json!({ "done": false })).await;
+    assert!(res.body.get("done_at").is_none());
+    let res = client.get(URI).await;
+    let points = counts(&res.body);
+    assert_eq!(points.last().map(|(_, created, done)| (*created, *done)), Some((1, 0)));
+}
+
+#[tokio::test]
+async fn rejects_long_and_inverted_ranges() {
+    let client = fixed_client();
+    for query in [
+        "from=2023-01-01T00:00:00Z&to=2024-01-03T00:00:00Z",
+        "from=2024-05-08T00:00:00Z&to=2024-05-01T00:00:00Z",
+        "bucket=month",
+    ] {
+        let res = client.get(&format!("{URI}?{query}")).await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    // Exactly 366 days is allowed.
+    let res = client
+        .get(&format!("{URI}?from=2023-01-01T00:00:00Z&to=2024-01-02T00:00:00Z"))
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body.as_array().unwrap().len(), 366);
+}
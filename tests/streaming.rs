@@ -0,0 +1,104 @@
+// Large lists are streamed chunk by chunk; the bytes on the wire must be
+// exactly what serializing the whole list at once would produce.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use common::LogCapture;
+use http_body_util::BodyExt;
+use rust_api::{
+    app,
+    models::CreateTodo,
+    models::Todo,
+    streaming::CHUNK_SIZE,
+    test_utils::{seed, MockRepo, RepoMethod},
+    AppState,
+};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+async fn seeded(count: usize) -> AppState {
+    let state = AppState::new_in_memory();
+    let repo = state.service().repo();
+    for i in 0..count {
+        repo.create(CreateTodo::new(format!("todo \"{i}\" ✓").parse().unwrap()))
+        .await
+        .unwrap();
+    }
+    state
+}
+
+async fn list_body(state: &AppState, accept: &str) -> (axum::http::HeaderMap, Vec<u8>) {
+    let res = app(state.clone())
+        .oneshot(
+            Request::builder()
+                .uri("/todos")
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers().clone();
+    (headers, res.into_body().collect().await.unwrap().to_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn streamed_list_is_byte_identical_to_buffered_json() {
+    for count in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, CHUNK_SIZE * 3 + 7] {
+        let state = seeded(count).await;
+        let expected = serde_json::to_vec(&state.service().repo().list().await.unwrap()).unwrap();
+
+        let (headers, body) = list_body(&state, "application/json").await;
+        assert_eq!(body, expected, "mismatch for {count} todos");
+
+        let parsed: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed.len(), count);
+        assert!(parsed.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        // The ETag is the collection revision, so streaming doesn't lose it.
+        assert!(headers.contains_key(header::ETAG), "no ETag for {count} todos");
+    }
+}
+
+#[tokio::test]
+async fn msgpack_lists_still_contain_every_todo() {
+    let state = seeded(CHUNK_SIZE * 2 + 3).await;
+    let (_, body) = list_body(&state, "application/msgpack").await;
+    let todos: Vec<Todo> = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(todos.len(), CHUNK_SIZE * 2 + 3);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn a_client_that_hangs_up_stops_the_stream_quietly() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let mock = Arc::new(MockRepo::new());
+    let inputs = (0..CHUNK_SIZE * 3).map(|i| CreateTodo::new(format!("todo {i}").parse().unwrap()));
+    seed(mock.as_ref(), inputs).await;
+
+    let request = Request::builder().uri("/v1/todos").body(Body::empty()).unwrap();
+    let res = app(AppState::with_repo(mock.clone())).oneshot(request).await.unwrap();
+    let mut body = res.into_body();
+    let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+    drop(body);
+
+    // Only the handler's first read happened; the rest were never fetched.
+    assert_eq!(mock.calls(RepoMethod::ListAfter), 1);
+    let events = logs.events();
+    let hangup = events
+        .iter()
+        .find(|event| event.field("message") == Some("client disconnected mid-stream"))
+        .expect("disconnect is logged");
+    assert_eq!(hangup.level, tracing::Level::DEBUG);
+    assert_eq!(hangup.field("stream"), Some("list"));
+    assert_eq!(hangup.field("bytes_sent"), Some(first.len().to_string().as_str()));
+    assert!(events.iter().all(|event| event.level > tracing::Level::WARN), "{events:?}");
+}
@@ -0,0 +1,127 @@
+// Supervised background tasks: a failure is logged, counted and reported by
+// `GET /ready` while a critical task is down, then handled per
+// `ON_TASK_FAILURE`.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    config::Config,
+    supervisor::{supervise, TaskStatus},
+    test_utils::TestClient,
+    AppState,
+};
+
+fn state_with(policy: &str) -> AppState {
+    let policy = policy.to_string();
+    let config =
+        Config::from_lookup(|key| (key == "ON_TASK_FAILURE").then(|| policy.clone())).unwrap();
+    AppState::new_in_memory().with_config(config)
+}
+
+/// Spawns a critical task that panics on its first run and then waits for
+/// shutdown like the real ones do.
+fn spawn_flaky(state: &AppState) -> Arc<AtomicU32> {
+    let runs = Arc::new(AtomicU32::new(0));
+    let stopping = state.shutting_down().clone();
+    tokio::spawn(supervise(state.clone(), "flaky", true, {
+        let runs = Arc::clone(&runs);
+        move || {
+            let first = runs.fetch_add(1, Ordering::SeqCst) == 0;
+            let stopping = stopping.clone();
+            async move {
+                if first {
+                    panic!("boom");
+                }
+                stopping.cancelled().await;
+            }
+        }
+    }));
+    runs
+}
+
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    for _ in 0..100 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting");
+}
+
+#[tokio::test]
+async fn a_panicking_task_is_restarted_and_readiness_recovers() {
+    let state = state_with("restart");
+    let client = TestClient::new(app(state.clone()));
+    let runs = spawn_flaky(&state);
+
+    wait_until(|| {
+        state
+            .task_health()
+            .report("flaky")
+            .is_some_and(|r| r.failures == 1)
+    })
+    .await;
+    // Down until the restart delay has passed.
+    if runs.load(Ordering::SeqCst) == 1 {
+        assert_eq!(
+            client.get("/ready").await.status,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    wait_until(|| runs.load(Ordering::SeqCst) == 2).await;
+    let report = state.task_health().report("flaky").unwrap();
+    assert_eq!(report.status, TaskStatus::Running);
+    assert_eq!(report.last_error.as_deref(), Some("boom"));
+    assert_eq!(client.get("/ready").await.status, StatusCode::OK);
+
+    let scrape = client
+        .get("/metrics")
+        .await
+        .body
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(
+        scrape.contains(r#"task_failures_total{task="flaky"} 1"#),
+        "{scrape}"
+    );
+
+    state.begin_shutdown();
+    wait_until(|| state.task_health().report("flaky").unwrap().status == TaskStatus::Stopped).await;
+    assert!(!state.task_health().reports().is_empty());
+}
+
+#[tokio::test]
+async fn under_the_shutdown_policy_a_failure_drains_the_server() {
+    let state = state_with("shutdown");
+    let client = TestClient::new(app(state.clone()));
+    let runs = spawn_flaky(&state);
+
+    wait_until(|| state.shutting_down().is_cancelled()).await;
+    assert_eq!(
+        client.get("/ready").await.status,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    let report = state.task_health().report("flaky").unwrap();
+    assert_eq!(report.status, TaskStatus::Failed);
+    // Not started again.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn the_policy_is_validated() {
+    let err = Config::from_lookup(|key| (key == "ON_TASK_FAILURE").then(|| "ignore".to_string()))
+        .unwrap_err();
+    assert!(err.to_string().contains("ON_TASK_FAILURE"), "{err}");
+}
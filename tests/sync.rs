@@ -0,0 +1,101 @@
+// Delta sync: `GET /todos/changes?since=` returns what changed after the
+// revision a client last saw, or asks it to refetch everything.
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    models::{Changes, CreateTodo},
+    state::TOMBSTONES,
+    test_utils::{seed, TestClient},
+    AppState,
+};
+use serde_json::json;
+
+fn titled(title: &str) -> CreateTodo {
+    CreateTodo::new(title.parse().unwrap())
+}
+
+async fn changes(client: &TestClient, since: u64) -> Changes {
+    let res = client.get(&format!("/todos/changes?since={since}")).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    res.json()
+}
+
+fn titles(changes: &Changes) -> Vec<&str> {
+    changes.changed.iter().map(|todo| todo.title.as_str()).collect()
+}
+
+#[tokio::test]
+async fn returns_what_changed_since_the_last_sync() {
+    let state = AppState::new_in_memory();
+    let client = TestClient::new(app(state.clone()));
+    let repo = state.service().repo();
+    let todos = seed(repo.as_ref(), [titled("a"), titled("b"), titled("c")]).await;
+    let uri = |n: usize| format!("/todos/{}", todos[n].id);
+
+    let first = changes(&client, 0).await;
+    assert_eq!(titles(&first), ["a", "b", "c"]);
+    assert!(first.deleted.is_empty());
+
+    client.put_json(&uri(1), &json!({ "done": true })).await;
+    client.delete(&uri(2)).await;
+    let created = client.post_json("/todos", &json!({ "title": "d" })).await;
+    assert_eq!(created.status, StatusCode::CREATED);
+
+    let second = changes(&client, first.revision).await;
+    assert!(second.revision > first.revision);
+    assert_eq!(titles(&second), ["b", "d"]);
+    assert!(second.changed[0].done);
+    assert_eq!(second.deleted, [todos[2].id]);
+
+    // Nothing new since the latest revision.
+    let third = changes(&client, second.revision).await;
+    assert_eq!(third.revision, second.revision);
+    assert!(third.changed.is_empty() && third.deleted.is_empty());
+
+    // Created and deleted between syncs: only the delete is reported.
+    let id = client.post_json("/todos", &json!({ "title": "e" })).await.body["id"].clone();
+    client.delete(&format!("/todos/{id}")).await;
+    let fourth = changes(&client, third.revision).await;
+    assert!(fourth.changed.is_empty());
+    assert_eq!(fourth.deleted, [id.as_u64().unwrap()]);
+}
+
+#[tokio::test]
+async fn asks_for_a_resync_once_deletes_are_forgotten() {
+    let state = AppState::new_in_memory();
+    let client = TestClient::new(app(state.clone()));
+    let repo = state.service().repo();
+    let kept = seed(repo.as_ref(), [titled("kept")]).await.remove(0);
+    let synced = changes(&client, 0).await.revision;
+
+    let doomed = seed(repo.as_ref(), (0..=TOMBSTONES).map(|n| titled(&format!("{n}")))).await;
+    for todo in &doomed {
+        repo.delete(todo.id).await.unwrap();
+    }
+
+    let res = client.get(&format!("/todos/changes?since={synced}")).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert!(res.body["error"].as_str().unwrap().contains("resync"), "{}", res.body);
+    let res = client.get(&format!("/todos/changes?since={synced}&envelope=true")).await;
+    assert_eq!(res.body["error"]["code"], "resync_required");
+
+    // A full refetch starts over from revision 0.
+    let full = changes(&client, 0).await;
+    assert_eq!(full.changed.len(), 1);
+    assert_eq!(full.changed[0].id, kept.id);
+    assert_eq!(full.deleted.len(), TOMBSTONES);
+    assert!(changes(&client, full.revision).await.changed.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_revisions_the_server_never_issued() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    client.post_json("/todos", &json!({ "title": "a" })).await;
+    let current = changes(&client, 0).await.revision;
+
+    let res = client.get(&format!("/todos/changes?since={}", current + 1)).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    let res = client.get("/todos/changes?since=yesterday").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,86 @@
+// Templates: saved sets of todos, validated on save and created together by
+// `POST /templates/:id/instantiate`.
+
+use axum::http::StatusCode;
+use rust_api::{app, models::SaveTemplate, test_utils::TestClient, AppState};
+use serde_json::{json, Value};
+
+fn weekly_review() -> Value {
+    json!({
+        "name": "weekly review",
+        "items": [
+            { "title": "clear the inbox" },
+            { "title": "review the calendar", "assignee": "alice" },
+            { "title": "plan next week", "color": "green" },
+        ],
+    })
+}
+
+fn ids(todos: &Value) -> Vec<u64> {
+    todos.as_array().unwrap().iter().map(|todo| todo["id"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn instantiating_twice_creates_distinct_todos() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let res = client.post_json("/v1/templates", &weekly_review()).await;
+    assert_eq!(res.status, StatusCode::CREATED);
+    let id = res.body["id"].as_u64().unwrap();
+
+    let first = client.post_json(&format!("/v1/templates/{id}/instantiate"), &json!({})).await;
+    assert_eq!(first.status, StatusCode::CREATED);
+    assert_eq!(first.body[1]["title"], "review the calendar");
+    assert_eq!(first.body[1]["assignee"], "alice");
+    assert_eq!(first.body[2]["color"], "green");
+
+    let second = client.post_json(&format!("/v1/templates/{id}/instantiate"), &json!({})).await;
+    assert_eq!(second.status, StatusCode::CREATED);
+    let (first, second) = (ids(&first.body), ids(&second.body));
+    assert_eq!(first.len(), 3);
+    assert!(first.iter().all(|id| !second.contains(id)), "{first:?} {second:?}");
+
+    let listed = client.get("/v1/todos").await;
+    assert_eq!(listed.body.as_array().unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn invalid_items_are_rejected_when_saving() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    let mut template = weekly_review();
+    template["items"][1]["title"] = json!("   ");
+
+    let res = client.post_json("/v1/templates", &template).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    // Titles are checked while the body is parsed, before items are numbered.
+    assert_eq!(res.body["error"], "validation error: title cannot be empty");
+    assert_eq!(client.get("/v1/templates").await.body, json!([]));
+
+    let items = vec![json!({ "title": "step" }); SaveTemplate::MAX_ITEMS + 1];
+    let res = client.post_json("/v1/templates", &json!({ "name": "long", "items": items })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = client.post_json("/v1/templates", &json!({ "name": "empty", "items": [] })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let unnamed = json!({ "name": " ", "items": [{ "title": "a" }] });
+    let res = client.post_json("/v1/templates", &unnamed).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn templates_can_be_read_replaced_and_deleted() {
+    let client = TestClient::new(app(AppState::new_in_memory()));
+    client.post_json("/v1/templates", &weekly_review()).await;
+
+    let replacement = json!({ "name": "daily", "items": [{ "title": "stand-up" }] });
+    let res = client.put_json("/v1/templates/1", &replacement).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(client.get("/v1/templates/1").await.body["name"], "daily");
+    let res = client.post_json("/v1/templates/1/instantiate", &json!({})).await;
+    assert_eq!(res.body.as_array().unwrap().len(), 1);
+
+    assert_eq!(client.delete("/v1/templates/1").await.status, StatusCode::NO_CONTENT);
+    assert_eq!(client.get("/v1/templates/1").await.status, StatusCode::NOT_FOUND);
+    let res = client.post_json("/v1/templates/1/instantiate", &json!({})).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = client.put_json("/v1/templates/1", &replacement).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,74 @@
+// Data helpers shared by the tests and benchmarks. A test crate pulls this in
+// with `mod test_support;`, a bench with
+// `#[path = "../tests/test_support/mod.rs"] mod test_support;`.
+#![allow(dead_code)]
+
+use std::future::Future;
+
+use proptest::prelude::*;
+use rust_api::{
+    models::{CreateTodo, Title},
+    state::TodoRepo,
+    test_utils,
+};
+
+/// One step of a random repository workload. Ops refer to todos by `slot`,
+/// an index into the ids created so far (wrapping), so shrunk cases stay
+/// meaningful; a slot whose todo was deleted exercises the `NotFound` path.
+#[derive(Clone, Debug)]
+pub enum Op {
+    Create { title: Title },
+    Rename { slot: usize, title: Title },
+    Toggle { slot: usize },
+    Delete { slot: usize },
+}
+
+/// Short lowercase titles keep shrunk failures readable.
+fn title() -> impl Strategy<Value = Title> {
+    "[a-z]{1,8}".prop_map(|title| title.parse().unwrap())
+}
+
+impl Arbitrary for Op {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![
+            3 => title().prop_map(|title| Op::Create { title }),
+            1 => (any::<usize>(), title()).prop_map(|(slot, title)| Op::Rename { slot, title }),
+            1 => any::<usize>().prop_map(|slot| Op::Toggle { slot }),
+            1 => any::<usize>().prop_map(|slot| Op::Delete { slot }),
+        ]
+        .boxed()
+    }
+}
+
+/// Titles near the length limit, mixing ASCII, multi-byte letters, and
+/// combining marks so bytes, chars, and graphemes all disagree.
+pub fn long_title() -> impl Strategy<Value = String> {
+    let cluster = prop_oneof![
+        Just("a".to_string()),
+        Just("é".to_string()),
+        Just("e\u{301}".to_string()),
+        Just("日".to_string()),
+        Just("👍🏽".to_string()),
+    ];
+    (prop::collection::vec(cluster, 90..110), " {0,3}", " {0,3}")
+        .prop_map(|(clusters, before, after)| format!("{before}{}{after}", clusters.concat()))
+}
+
+/// Runs `future` to completion on a fresh single-threaded runtime; proptest
+/// bodies are synchronous.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime builds")
+        .block_on(future)
+}
+
+/// Creates `count` open todos titled `todo 0`, `todo 1`, ... and returns
+/// their ids.
+pub async fn seed(repo: &dyn TodoRepo, count: usize) -> Vec<u64> {
+    let todos = (0..count).map(|n| CreateTodo::new(format!("todo {n}").parse().unwrap()));
+    test_utils::seed(repo, todos).await.into_iter().map(|todo| todo.id).collect()
+}
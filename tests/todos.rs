@@ -1,133 +1,150 @@
 // Integration-style test that spins up the full router and issues requests as
 // if we were an HTTP client. This gives new Rustaceans a practical example of
 // how to exercise Axum handlers without opening a socket.
+//
+// `TestClient` and `MockRepo` come from the crate's `test-utils` feature, which
+// the dev-dependency on the crate itself turns on.
+
+use std::sync::Arc;
 
 use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
-use http_body_util::BodyExt;
-use rust_api::{app, models::Todo, AppState};
+use rust_api::{
+    app,
+    errors::AppError,
+    models::{CreateTodo, Todo},
+    test_utils::{seed, MockRepo, Reply, RepoMethod, TestClient},
+    AppState,
+};
 use serde_json::json;
-use tower::ServiceExt;
+
+fn client() -> TestClient {
+    TestClient::new(app(AppState::new_in_memory()))
+}
+
+/// A client over a [`MockRepo`] holding one todo.
+async fn mocked() -> (TestClient, Arc<MockRepo>, Todo) {
+    let mock = Arc::new(MockRepo::new());
+    let input = CreateTodo::new("learn rust".parse().unwrap());
+    let todo = seed(mock.as_ref(), [input]).await.remove(0);
+    (TestClient::new(app(AppState::with_repo(mock.clone()))), mock, todo)
+}
 
 /// Walks through create -> read -> update -> list -> delete to demonstrate
 /// the different status codes and payloads the service emits.
 #[tokio::test]
 async fn todo_crud_flow() {
-    let state = AppState::new_in_memory();
-    let app = app(state);
-
-    let create_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("POST")
-                .uri("/todos")
-                .header("content-type", "application/json")
-                .body(Body::from(json!({ "title": "learn rust" }).to_string()))
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(create_res.status(), StatusCode::CREATED);
-    let body = create_res
-        .into_body()
-        .collect()
-        .await
-        .unwrap()
-        .to_bytes();
-    let created: Todo = serde_json::from_slice(&body).unwrap();
-
-    let get_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("GET")
-                .uri(format!("/todos/{}", created.id))
-                .body(Body::empty())
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(get_res.status(), StatusCode::OK);
-    let fetched = serde_json::from_slice::<Todo>(
-        &get_res
-            .into_body()
-            .collect()
-            .await
-            .unwrap()
-            .to_bytes(),
-    )
-    .unwrap();
-    assert_eq!(fetched.title, created.title);
-
-    let update_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("PUT")
-                .uri(format!("/todos/{}", created.id))
-                .header("content-type", "application/json")
-                .body(Body::from(json!({ "done": true }).to_string()))
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(update_res.status(), StatusCode::OK);
-    let updated: Todo = serde_json::from_slice(
-        &update_res
-            .into_body()
-            .collect()
-            .await
-            .unwrap()
-            .to_bytes(),
-    )
-    .unwrap();
-    assert!(updated.done);
-
-    let list_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("GET")
-                .uri("/todos")
-                .body(Body::empty())
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(list_res.status(), StatusCode::OK);
-
-    let delete_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("DELETE")
-                .uri(format!("/todos/{}", created.id))
-                .body(Body::empty())
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(delete_res.status(), StatusCode::NO_CONTENT);
-
-    let missing_res = app
-        .clone()
-        .oneshot(
-            Request::builder()
-                .method("GET")
-                .uri(format!("/todos/{}", created.id))
-                .body(Body::empty())
-                .unwrap(),
-        )
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(missing_res.status(), StatusCode::NOT_FOUND);
+    let client = client();
+
+    let created = client.post_json("/todos", &json!({ "title": "learn rust" })).await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    let created: Todo = created.json();
+
+    let uri = format!("/todos/{}", created.id);
+    let fetched = client.get(&uri).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.json::<Todo>().title, created.title);
+
+    let updated = client.put_json(&uri, &json!({ "done": true })).await;
+    assert_eq!(updated.status, StatusCode::OK);
+    assert!(updated.json::<Todo>().done);
+
+    let listed = client.get("/todos").await;
+    assert_eq!(listed.status, StatusCode::OK);
+    assert_eq!(listed.json::<Vec<Todo>>().len(), 1);
+
+    assert_eq!(client.delete(&uri).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(client.get(&uri).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn malformed_ids_are_json_bad_requests() {
+    let client = client();
+    let routes = [
+        ("GET", "/todos/{}"),
+        ("PUT", "/todos/{}"),
+        ("DELETE", "/todos/{}"),
+        ("POST", "/todos/{}/assign"),
+        ("GET", "/todos/{}/attachments"),
+        ("POST", "/todos/{}/attachments"),
+        ("GET", "/attachments/{}"),
+    ];
+    let ids = ["abc", "-1", "0", "1e3", "18446744073709551616", "99999999999999999999999"];
+    for (method, route) in routes {
+        for id in ids {
+            let uri = route.replace("{}", id);
+            let request = Request::builder().method(method).uri(&uri).body(Body::empty());
+            let res = client.send(request.unwrap()).await;
+            assert_eq!(res.status, StatusCode::BAD_REQUEST, "{method} {uri}");
+            let expected = format!(
+                "invalid id `{id}`: ids are whole numbers from 1 to 18446744073709551615"
+            );
+            assert_eq!(res.body["error"], expected, "{method} {uri}");
+        }
+    }
+
+    let res = client.get("/todos/0?envelope=true").await;
+    assert_eq!(res.body["error"]["code"], "invalid_id");
+
+    // The largest id is well-formed, just missing.
+    let res = client.get("/todos/18446744073709551615").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn repo_failures_are_opaque_internal_errors() {
+    let (client, mock, todo) = mocked().await;
+    let uri = format!("/todos/{}", todo.id);
+
+    mock.fail_next(RepoMethod::Create, AppError::Internal);
+    let res = client.post_json("/todos", &json!({ "title": "ship it" })).await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(res.body, json!({ "error": "internal error" }));
+
+    mock.fail_next(RepoMethod::Get, AppError::Internal);
+    assert_eq!(client.get(&uri).await.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    mock.fail_next(RepoMethod::Update, AppError::Internal);
+    let res = client.put_json(&uri, &json!({ "done": true })).await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    mock.fail_next(RepoMethod::Delete, AppError::Internal);
+    assert_eq!(client.delete(&uri).await.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    // Nothing was changed by the failed calls, and the script is used up.
+    let fetched = client.get(&uri).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert!(!fetched.json::<Todo>().done);
+    assert_eq!(mock.calls(RepoMethod::Create), 2);
+    assert_eq!(mock.calls(RepoMethod::Delete), 1);
+}
+
+#[tokio::test]
+async fn list_failures_are_internal_errors() {
+    let (client, mock, _) = mocked().await;
+    mock.fail_next(RepoMethod::ListPage, AppError::Internal);
+    mock.fail_next(RepoMethod::ListAfter, AppError::Internal);
+
+    for uri in ["/todos?limit=10&offset=0", "/todos"] {
+        let res = client.get(uri).await;
+        assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR, "{uri}");
+        assert_eq!(res.body, json!({ "error": "internal error" }), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn handlers_return_what_the_repo_returns() {
+    let (client, mock, todo) = mocked().await;
+    let stale = Todo {
+        title: "from the mock".parse().unwrap(),
+        ..todo.clone()
+    };
+    mock.reply_next(RepoMethod::Get, Reply::Todo(stale.clone()));
+
+    let uri = format!("/todos/{}", todo.id);
+    assert_eq!(client.get(&uri).await.json::<Todo>().title, stale.title);
+    assert_eq!(client.get(&uri).await.json::<Todo>().title, todo.title);
+    assert_eq!(mock.calls(RepoMethod::Get), 2);
 }
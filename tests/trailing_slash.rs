@@ -0,0 +1,60 @@
+// Trailing slashes: `/todos/` redirects to `/todos` with a `308`, so clients
+// repeat the same method and body against the canonical path.
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use rust_api::{app, test_utils::TestClient, AppState};
+use serde_json::json;
+
+fn client() -> TestClient {
+    TestClient::new(app(AppState::new_in_memory()))
+}
+
+async fn redirect(client: &TestClient, method: &str, uri: &str) -> (StatusCode, Option<String>) {
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "title": "buy milk" }).to_string()))
+        .unwrap();
+    let res = client.send(req).await;
+    let location = res.headers.get("location").map(|value| value.to_str().unwrap().to_string());
+    (res.status, location)
+}
+
+#[tokio::test]
+async fn redirects_to_the_canonical_path() {
+    let client = client();
+    client.post_json("/todos", &json!({ "title": "buy milk" })).await;
+
+    for (method, uri, canonical) in [
+        ("GET", "/todos/", "/todos"),
+        ("POST", "/todos/", "/todos"),
+        ("GET", "/todos/1/", "/todos/1"),
+        ("PUT", "/todos/1/", "/todos/1"),
+        ("DELETE", "/todos/1//", "/todos/1"),
+        ("GET", "/health/", "/health"),
+        ("GET", "/todos/?done=false&limit=5", "/todos?done=false&limit=5"),
+        // Unknown paths redirect too, and then 404 where they land.
+        ("GET", "/nope/", "/nope"),
+    ] {
+        let (status, location) = redirect(&client, method, uri).await;
+        assert_eq!(status, StatusCode::PERMANENT_REDIRECT, "{method} {uri}");
+        assert_eq!(location.as_deref(), Some(canonical), "{method} {uri}");
+    }
+
+    // Nothing was created or deleted along the way.
+    assert_eq!(client.get("/todos").await.body.as_array().map(Vec::len), Some(1));
+}
+
+#[tokio::test]
+async fn canonical_paths_are_untouched() {
+    let client = client();
+    assert_eq!(client.get("/todos").await.status, StatusCode::OK);
+    assert_eq!(client.get("/health").await.status, StatusCode::OK);
+    assert_eq!(client.get("/").await.status, StatusCode::NOT_FOUND);
+
+    // `//host` would be a redirect to another site.
+    let (status, location) = redirect(&client, "GET", "//example.com/").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(location, None);
+}
@@ -0,0 +1,65 @@
+// The bundled web UI is feature-gated; run with `cargo test --features ui`.
+#![cfg(feature = "ui")]
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use rust_api::{
+    app,
+    config::Config,
+    test_utils::{TestClient, TestResponse},
+    AppState,
+};
+
+fn client(enable_ui: &str) -> TestClient {
+    let vars = HashMap::from([("ENABLE_UI".to_string(), enable_ui.to_string())]);
+    let config = Config::from_lookup(|key| vars.get(key).cloned()).unwrap();
+    TestClient::new(app(AppState::new_in_memory().with_config(config)))
+}
+
+fn header<'a>(res: &'a TestResponse, name: &str) -> &'a str {
+    res.headers[name].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn serves_the_app_and_falls_back_to_it() {
+    let client = client("true");
+
+    let index = client.get("/ui/").await;
+    assert_eq!(index.status, StatusCode::OK);
+    assert!(header(&index, "content-type").starts_with("text/html"));
+    assert_eq!(header(&index, "cache-control"), "no-cache");
+    let html = index.body.as_str().unwrap();
+    assert!(html.contains("<title>Todos</title>"), "{html}");
+
+    let route = client.get("/ui/some/client/route").await;
+    assert_eq!(route.status, StatusCode::OK);
+    assert_eq!(route.body, index.body);
+
+    let script = client.get("/ui/app.js").await;
+    assert_eq!(script.status, StatusCode::OK);
+    assert!(header(&script, "content-type").starts_with("text/javascript"));
+    assert_eq!(header(&script, "cache-control"), "private, max-age=3600");
+    assert!(script.headers.contains_key("etag"));
+
+    // A missing file is not papered over with HTML.
+    assert_eq!(client.get("/ui/missing.js").await.status, StatusCode::NOT_FOUND);
+    let redirect = client.get("/ui").await;
+    assert_eq!(redirect.status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(header(&redirect, "location"), "/ui/");
+}
+
+#[tokio::test]
+async fn leaves_the_api_alone() {
+    let client = client("true");
+    assert_eq!(client.get("/todos").await.status, StatusCode::OK);
+    assert_eq!(client.get("/todos/ui").await.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn nothing_is_served_when_off() {
+    let client = client("false");
+    for path in ["/ui", "/ui/", "/ui/app.js", "/ui/some/client/route"] {
+        assert_eq!(client.get(path).await.status, StatusCode::NOT_FOUND, "{path}");
+    }
+}
@@ -0,0 +1,78 @@
+// A minimal client for the REST API, served from the same origin.
+
+const list = document.getElementById("todos");
+const form = document.getElementById("new-todo");
+const error = document.getElementById("error");
+
+// Behind a session cookie, writes must echo its CSRF token.
+function csrfToken() {
+  const cookie = document.cookie.split("; ").find((c) => c.startsWith("csrf_token="));
+  return cookie ? cookie.slice("csrf_token=".length) : undefined;
+}
+
+async function api(method, path, body) {
+  const headers = body ? { "content-type": "application/json" } : {};
+  const csrf = csrfToken();
+  if (csrf) headers["x-csrf-token"] = csrf;
+  const res = await fetch(path, {
+    method,
+    headers,
+    body: body ? JSON.stringify(body) : undefined,
+  });
+  if (!res.ok) {
+    const problem = await res.json().catch(() => ({}));
+    throw new Error(problem.error || `${method} ${path} failed with ${res.status}`);
+  }
+  return res.status === 204 ? null : res.json();
+}
+
+function show(err) {
+  error.textContent = err ? err.message : "";
+  error.hidden = !err;
+}
+
+function render(todos) {
+  list.replaceChildren(
+    ...todos.map((todo) => {
+      const item = document.createElement("li");
+      const done = document.createElement("input");
+      done.type = "checkbox";
+      done.checked = todo.done;
+      done.addEventListener("change", () =>
+        api("PUT", `/v1/todos/${todo.id}`, { done: done.checked }).then(refresh, show),
+      );
+      const title = document.createElement("span");
+      title.textContent = todo.title;
+      const remove = document.createElement("button");
+      remove.textContent = "Delete";
+      remove.addEventListener("click", () =>
+        api("DELETE", `/v1/todos/${todo.id}`).then(refresh, show),
+      );
+      item.append(done, title, remove);
+      item.classList.toggle("done", todo.done);
+      return item;
+    }),
+  );
+}
+
+async function refresh() {
+  try {
+    render(await api("GET", "/v1/todos"));
+    show(null);
+  } catch (err) {
+    show(err);
+  }
+}
+
+form.addEventListener("submit", async (event) => {
+  event.preventDefault();
+  try {
+    await api("POST", "/v1/todos", { title: form.title.value });
+    form.reset();
+    await refresh();
+  } catch (err) {
+    show(err);
+  }
+});
+
+refresh();